@@ -0,0 +1,447 @@
+use anyhow::{bail, Result};
+use chrono::{Datelike, Local, NaiveDate};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::batch;
+use crate::media_server;
+use crate::notify;
+use crate::sources::{gfriends, minnano, mock, xslist};
+use crate::store;
+use crate::types::{ActorItem, ActressProfile};
+use crate::util;
+
+/// Every profile seen in the actress list, by name; backs `actors birthdays`
+const CACHE_NAME: &str = "actresses";
+
+/// `--cup`: a single letter (`E`), at least (`E+`) or a range (`D-F`)
+#[derive(Debug, Clone, Copy)]
+pub struct CupRange {
+    min: u8,
+    max: u8,
+}
+
+impl std::str::FromStr for CupRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_uppercase();
+        let letter = |c: &str| match c.as_bytes() {
+            [b] if (b'A'..=b'O').contains(b) => Ok(*b),
+            _ => Err(format!("无效的罩杯: {}（可用 A-O，如 E、E+、D-F）", s)),
+        };
+        if let Some(min) = s.strip_suffix('+') {
+            Ok(CupRange { min: letter(min)?, max: b'O' })
+        } else if let Some((a, b)) = s.split_once('-') {
+            let (min, max) = (letter(a)?, letter(b)?);
+            Ok(CupRange { min: min.min(max), max: min.max(max) })
+        } else {
+            let c = letter(&s)?;
+            Ok(CupRange { min: c, max: c })
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub cup: Option<CupRange>,
+    pub min_height: Option<u16>,
+    pub max_height: Option<u16>,
+    /// Debut in or after this year
+    pub debut_after: Option<u16>,
+    /// Debut in or before this year
+    pub debut_before: Option<u16>,
+}
+
+impl Filter {
+    /// Profiles missing a field the filter asks about don't match
+    pub fn matches(&self, p: &ActressProfile) -> bool {
+        let cup = p.cup.as_deref().and_then(|c| c.bytes().next());
+        let in_range = |v: Option<u16>, min: Option<u16>, max: Option<u16>| {
+            (min.is_none() && max.is_none()) || v.is_some_and(|v| min.is_none_or(|m| v >= m) && max.is_none_or(|m| v <= m))
+        };
+        self.cup.is_none_or(|r| cup.is_some_and(|c| (r.min..=r.max).contains(&c)))
+            && in_range(p.height_cm, self.min_height, self.max_height)
+            && in_range(p.debut_year, self.debut_after, self.debut_before)
+    }
+}
+
+/// Fetch `pages` pages of the actress list (`--jobs` at a time), in list order, and add
+/// the profiles to the local cache
+async fn scan(pages: u32) -> Result<Vec<ActressProfile>> {
+    if mock::is_enabled() {
+        return Ok(mock::actresses());
+    }
+    let pages = pages.max(1);
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for page in 1..=pages {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (page, minnano::actresses(page).await)
+        });
+    }
+    let mut done = tasks.join_all().await;
+    done.sort_by_key(|(page, _)| *page);
+    let mut failed = 0;
+    let mut out = Vec::new();
+    for (page, result) in done {
+        match result {
+            Ok(profiles) => out.extend(profiles),
+            Err(e) => {
+                failed += 1;
+                eprintln!("[WARN] 女优列表第 {} 页获取失败: {:#}", page, e);
+            }
+        }
+    }
+    if failed == pages {
+        bail!("无法获取女优资料（minnano-av）");
+    }
+    let mut cache: BTreeMap<String, ActressProfile> = store::load(CACHE_NAME)?;
+    cache.extend(out.iter().map(|p| (p.name.clone(), p.clone())));
+    if let Err(e) = store::save(CACHE_NAME, &cache) {
+        eprintln!("[WARN] 保存女优资料缓存失败: {:#}", e);
+    }
+    Ok(out)
+}
+
+/// Scan up to `pages` pages of the actress list and keep the first `limit` profiles
+/// matching the filter, in list order
+pub async fn find(filter: &Filter, pages: u32, limit: usize) -> Result<Vec<ActressProfile>> {
+    let scanned = scan(pages).await?;
+    let total = scanned.len();
+    let out: Vec<ActressProfile> = scanned.into_iter().filter(|p| filter.matches(p)).take(limit).collect();
+    util::debug(format!("actors find: {} profiles scanned, {} matched", total, out.len()));
+    Ok(out)
+}
+
+fn print_table(profiles: &[ActressProfile]) {
+    println!("{} {}", "Total".bold(), profiles.len());
+    let dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    for (i, p) in profiles.iter().enumerate() {
+        let sizes = match (p.bust, p.waist, p.hip) {
+            (Some(b), Some(w), Some(h)) => Some(format!("B{} W{} H{}", b, w, h)),
+            _ => None,
+        };
+        println!(
+            "{:>3}  {}  罩杯 {}  身高 {}  三围 {}  出道 {}",
+            i + 1,
+            p.name.bold(),
+            dash(p.cup.clone()),
+            dash(p.height_cm.map(|h| format!("{}cm", h))),
+            dash(sizes),
+            dash(p.debut_year.map(|y| y.to_string())),
+        );
+    }
+}
+
+/// `av actors find`
+pub async fn run(filter: Filter, pages: u32, limit: usize, json: bool) -> Result<()> {
+    let profiles = find(&filter, pages, limit).await?;
+    if json {
+        util::print_output(&profiles, true);
+    } else if profiles.is_empty() {
+        println!("{}", "没有符合条件的女优".yellow());
+    } else {
+        print_table(&profiles);
+    }
+    Ok(())
+}
+
+/// A ranking entry whose newest title is at most this old counts as active
+const ACTIVE_DAYS: i64 = 365;
+
+/// `av actors --min-works/--debut-after/--active-only`, on the ranking's entries rather
+/// than the profile list `actors find` searches. What the ranking page doesn't show is
+/// read from her JavDB page (works, newest title) or her profile (debut year).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RankingFilter {
+    pub min_works: Option<u32>,
+    /// Debut in or after this year
+    pub debut_after: Option<u16>,
+    /// A title out in the last `ACTIVE_DAYS` days
+    pub active_only: bool,
+}
+
+impl RankingFilter {
+    pub fn is_set(&self) -> bool {
+        self.min_works.is_some() || self.debut_after.is_some() || self.active_only
+    }
+
+    /// Entries missing a field the filter asks about don't match
+    fn matches(&self, a: &ActorItem, today: NaiveDate) -> bool {
+        let active = || {
+            a.latest_release
+                .as_deref()
+                .and_then(crate::dates::parse)
+                .is_some_and(|d| (today - d).num_days() <= ACTIVE_DAYS)
+        };
+        self.min_works.is_none_or(|m| a.works_count.is_some_and(|w| w >= m))
+            && self.debut_after.is_none_or(|y| a.debut_year.is_some_and(|d| d >= y))
+            && (!self.active_only || active())
+    }
+}
+
+/// Her debut year from the cached actress list, else xslist
+async fn debut_year(name: &str, cached: &BTreeMap<String, ActressProfile>) -> Option<u16> {
+    if let Some(y) = cached.get(name).and_then(|p| p.debut_year) {
+        return Some(y);
+    }
+    if mock::is_enabled() {
+        return None;
+    }
+    match xslist::profile(name).await {
+        Ok(p) => p.and_then(|p| p.debut_year),
+        Err(e) => {
+            util::debug(format!("xslist {}: {:#}", name, e));
+            None
+        }
+    }
+}
+
+/// Fill in what `filter` needs for each entry, `--jobs` at a time, and keep the ones it
+/// matches, in order
+pub async fn filter_ranking(filter: &RankingFilter, actors: Vec<ActorItem>) -> Result<Vec<ActorItem>> {
+    if !filter.is_set() || actors.is_empty() {
+        return Ok(actors);
+    }
+    let cached: Arc<BTreeMap<String, ActressProfile>> = Arc::new(if mock::is_enabled() {
+        mock::actresses().into_iter().map(|p| (p.name.clone(), p)).collect()
+    } else if filter.debut_after.is_some() {
+        store::load(CACHE_NAME)?
+    } else {
+        BTreeMap::new()
+    });
+    let filter = *filter;
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, mut actor) in actors.into_iter().enumerate() {
+        let (permits, cached) = (permits.clone(), cached.clone());
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let needs_page = (filter.min_works.is_some() && actor.works_count.is_none()) || filter.active_only;
+            if needs_page {
+                match crate::scraper::actor_stats(&actor).await {
+                    Ok((works, latest)) => {
+                        actor.works_count = actor.works_count.or(works);
+                        actor.latest_release = latest;
+                    }
+                    Err(e) => util::debug(format!("actor page {}: {:#}", actor.name, e)),
+                }
+            }
+            if filter.debut_after.is_some() {
+                actor.debut_year = debut_year(&actor.name, &cached).await;
+            }
+            (idx, actor)
+        });
+    }
+    let mut done = tasks.join_all().await;
+    crate::cancel::check()?;
+    done.sort_by_key(|(idx, _)| *idx);
+    let today = Local::now().date_naive();
+    Ok(done.into_iter().map(|(_, a)| a).filter(|a| filter.matches(a, today)).collect())
+}
+
+/// `actors --all`: every ranking page through `filter`, printed as it arrives. `job` holds
+/// the crawl's cursor, moved on after each page so `av resume` can pick up from there.
+pub async fn crawl(filter: &RankingFilter, uncensored_only: bool, json: bool, mut job: batch::Job) -> Result<()> {
+    let start = job.cursor.clone().unwrap_or_default();
+    if !start.seen.is_empty() {
+        util::note(format!("继续批量任务 {}：已列出 {} 位演员（{}）", job.id, start.seen.len(), job.command()));
+    }
+    let mut listed = start.kept;
+    let result = crate::scraper::actors_all(uncensored_only, start, async |label: &str, page, actors, mut cursor: batch::Cursor| {
+        let actors = filter_ranking(filter, actors).await?;
+        if json {
+            actors.iter().for_each(util::print_json_line);
+        } else {
+            crate::table::print_actor_batch(label, page, &actors, listed)?;
+        }
+        listed += actors.len();
+        cursor.kept = listed;
+        job.advance(cursor)
+    })
+    .await;
+    let total = job.hint(result)?;
+    job.finish()?;
+    if !json {
+        let kept = if filter.is_set() { format!("（符合条件 {}）", listed) } else { String::new() };
+        println!("{} {}{}", "Total".bold(), total, kept);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct Birthday {
+    name: String,
+    birth_date: String,
+    /// Age reached on this year's birthday
+    age: i32,
+    /// 0 on the day; negative once it has passed this year
+    days_until: i64,
+    url: Option<String>,
+}
+
+fn birthdays_in(profiles: &[ActressProfile], today: NaiveDate, month: Option<u32>, today_only: bool) -> Vec<Birthday> {
+    let mut out: Vec<(u32, u32, Birthday)> = profiles
+        .iter()
+        .filter_map(|p| {
+            let born = NaiveDate::parse_from_str(p.birth_date.as_deref()?, "%Y-%m-%d").ok()?;
+            // Feb 29 birthdays fall on Mar 1 in common years
+            let this_year = born
+                .with_year(today.year())
+                .or_else(|| NaiveDate::from_ymd_opt(today.year(), 3, 1))?;
+            let keep = if today_only { this_year == today } else { born.month() == month.unwrap_or(today.month()) };
+            keep.then(|| {
+                let b = Birthday {
+                    name: p.name.clone(),
+                    birth_date: born.format("%Y-%m-%d").to_string(),
+                    age: today.year() - born.year(),
+                    days_until: (this_year - today).num_days(),
+                    url: p.url.clone(),
+                };
+                (born.month(), born.day(), b)
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| (a.0, a.1, &a.2.name).cmp(&(b.0, b.1, &b.2.name)));
+    out.into_iter().map(|(.., b)| b).collect()
+}
+
+/// `av actors birthdays`: birthdays (and ages) of the actresses in the local profile cache,
+/// for `month` (default: this month) or just today. An empty cache, or `refresh`, scans
+/// `pages` pages of the actress list first. `notify` pushes today's birthdays.
+pub async fn birthdays(month: Option<u32>, today_only: bool, refresh: bool, pages: u32, push: bool, json: bool) -> Result<()> {
+    let mut profiles: Vec<ActressProfile> = if mock::is_enabled() {
+        mock::actresses()
+    } else {
+        store::load::<BTreeMap<String, ActressProfile>>(CACHE_NAME)?.into_values().collect()
+    };
+    if refresh || profiles.is_empty() {
+        util::debug(format!("actors birthdays: scanning {} page(s) of the actress list", pages));
+        scan(pages).await?;
+        if !mock::is_enabled() {
+            profiles = store::load::<BTreeMap<String, ActressProfile>>(CACHE_NAME)?.into_values().collect();
+        }
+    }
+    let today = Local::now().date_naive();
+    let list = birthdays_in(&profiles, today, month, today_only);
+    if push {
+        for b in list.iter().filter(|b| b.days_until == 0) {
+            let event = notify::Event::Birthday { name: b.name.clone(), birth_date: b.birth_date.clone(), age: b.age, url: b.url.clone() };
+            notify::emit(&event).await;
+        }
+    }
+    if json {
+        util::print_output(&list, true);
+        return Ok(());
+    }
+    if list.is_empty() {
+        println!("{}", "没有找到符合的生日（资料来自本地缓存，可加 --refresh 更新）".yellow());
+        return Ok(());
+    }
+    for b in &list {
+        let when = match b.days_until {
+            0 => "今天".green().bold().to_string(),
+            d if d > 0 => format!("{} 天后", d),
+            _ => "已过".dimmed().to_string(),
+        };
+        println!("{}  {}  {} 岁  {}", &b.birth_date[5..], b.name.bold(), b.age, when);
+    }
+    Ok(())
+}
+
+/// `actors profile <name>`: xslist's profile, with the names to search for her by
+pub async fn profile(name: &str, json: bool) -> Result<()> {
+    let Some(p) = xslist::profile(name).await? else {
+        bail!("xslist 上没有找到 {}", name.trim());
+    };
+    if json {
+        util::print_output(&p, true);
+        return Ok(());
+    }
+    println!("{} {}", p.name.green().bold(), p.url.dimmed());
+    for (lang, n) in &p.names {
+        println!("  {}： {}", lang, n);
+    }
+    if !p.aliases.is_empty() {
+        println!("别名： {}", p.aliases.join("、"));
+    }
+    if let Some(b) = &p.birth_date {
+        println!("生日： {}", b);
+    }
+    if let Some(h) = p.height_cm {
+        println!("身高： {} cm", h);
+    }
+    if let (Some(b), Some(w), Some(h)) = (p.bust, p.waist, p.hip) {
+        let cup = p.cup.as_deref().map(|c| format!("（{} 罩杯）", c)).unwrap_or_default();
+        println!("三围： B{} W{} H{}{}", b, w, h, cup);
+    } else if let Some(c) = &p.cup {
+        println!("罩杯： {}", c);
+    }
+    if let Some(y) = p.debut_year {
+        println!("出道： {}", y);
+    }
+    if let Some(bio) = &p.bio {
+        println!("简介：\n{}", bio);
+    }
+    Ok(())
+}
+
+/// `av actors avatar <name>`: her portrait in the gfriends repository
+pub async fn avatar(name: &str, refresh: bool, json: bool) -> Result<()> {
+    let index = gfriends::index(refresh).await?;
+    let Some(url) = index.avatar_url(name) else {
+        bail!("gfriends 中没有 {} 的头像（共 {} 位）", name.trim(), index.avatars.len());
+    };
+    if json {
+        util::print_output(&serde_json::json!({ "name": name.trim(), "avatar_url": url }), true);
+    } else {
+        println!("{}", url);
+    }
+    Ok(())
+}
+
+/// `av actors push-images`: upload gfriends portraits to the Jellyfin/Emby people
+pub async fn push_images(force: bool, json: bool) -> Result<()> {
+    let Some(server) = &crate::config::get().media_server else {
+        bail!("未配置 [media_server]，无法推送演员头像");
+    };
+    let people = media_server::push_people_images(server, force).await?;
+    if json {
+        util::print_output(&people, true);
+        return Ok(());
+    }
+    let mut pushed = 0;
+    let mut failed = 0;
+    for p in &people {
+        match (&p.avatar_url, &p.error) {
+            (Some(_), Some(e)) => {
+                eprintln!("[WARN] {}: {}", p.name, e);
+                failed += 1;
+            }
+            (Some(url), None) => {
+                util::debug(format!("push-images: {} ← {}", p.name, url));
+                pushed += 1;
+            }
+            _ => {}
+        }
+    }
+    let skipped = people.iter().filter(|p| p.skipped).count();
+    let missing = people.len() - pushed - failed - skipped;
+    util::note(format!(
+        "{} {} 位演员头像；{} 位已有头像（--force 覆盖），{} 位 gfriends 中没有",
+        "已推送".green().bold(),
+        pushed,
+        skipped,
+        missing
+    ));
+    if failed > 0 {
+        bail!("{} 位演员头像推送失败", failed);
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! User-defined command aliases (`[alias]` in the config): the first word naming the
+//! subcommand is replaced by the alias's words before the arguments are parsed, so with
+//! `play = "view --player mpv"`, `av play SSIS-001` runs `av view --player mpv SSIS-001`.
+//! Per-command default flags (`[defaults]`) are inserted after the expansion, right after
+//! the command's name: with `top = "--uncen --limit 50"`, `av top --limit 10` runs
+//! `av top --uncen --limit 50 --limit 10`, and the later `--limit` wins.
+
+use anyhow::{bail, Result};
+use clap::CommandFactory;
+use std::ffi::OsString;
+
+use crate::config;
+use crate::shell;
+use crate::Cli;
+
+/// Index of the subcommand in `args`, skipping global flags and their values
+fn subcommand_index(args: &[OsString]) -> Option<usize> {
+    let cmd = Cli::command();
+    let takes_value = |flag: &str| {
+        cmd.get_arguments().any(|a| {
+            a.get_action().takes_values()
+                && (a.get_long().is_some_and(|l| flag.strip_prefix("--") == Some(l))
+                    || a.get_short().is_some_and(|s| flag.strip_prefix('-') == Some(s.encode_utf8(&mut [0; 4]))))
+        })
+    };
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        let arg = arg.to_str()?;
+        if arg == "--" {
+            return None;
+        }
+        if !arg.starts_with('-') {
+            return Some(i);
+        }
+        if !arg.contains('=') && takes_value(arg) {
+            i += 1;
+        }
+        i += 1;
+    }
+    None
+}
+
+/// `args` (program name first) with a leading alias expanded, then the command's
+/// `[defaults]` inserted. Built-in subcommands win over aliases of the same name; aliases
+/// are not expanded recursively.
+pub fn expand(args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let args = expand_alias(args)?;
+    with_defaults(args)
+}
+
+fn expand_alias(args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let aliases = &config::get().alias;
+    let Some(i) = subcommand_index(&args) else { return Ok(args) };
+    let name = args[i].to_str().unwrap_or_default();
+    let Some(value) = aliases.get(name) else { return Ok(args) };
+    if Cli::command().find_subcommand(name).is_some() {
+        eprintln!("[WARN] 别名 {} 与内置命令同名，已忽略", name);
+        return Ok(args);
+    }
+    let Some(words) = shell::split(value) else { bail!("别名 {} 的引号未闭合: {}", name, value) };
+    if words.is_empty() {
+        bail!("别名 {} 为空", name);
+    }
+    let mut out = args[..i].to_vec();
+    out.extend(words.into_iter().map(OsString::from));
+    out.extend_from_slice(&args[i + 1..]);
+    Ok(out)
+}
+
+/// `--no-defaults` before any `--`
+fn defaults_disabled(args: &[OsString]) -> bool {
+    args.iter().skip(1).map(|a| a.to_string_lossy()).take_while(|a| a != "--").any(|a| a == "--no-defaults")
+}
+
+/// `args` with the `[defaults]` of the command they run inserted after its name; the
+/// longest configured path wins, so `"actors find"` beats `"actors"`. Keys use the
+/// commands' own names, which built-in aliases (`get`, `ls`) resolve to.
+fn with_defaults(args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let defaults = &config::get().defaults;
+    if defaults.is_empty() || defaults_disabled(&args) {
+        return Ok(args);
+    }
+    let Some(i) = subcommand_index(&args) else { return Ok(args) };
+    let root = Cli::command();
+    let Some(cmd) = args[i].to_str().and_then(|name| root.find_subcommand(name)) else { return Ok(args) };
+    let mut path = cmd.get_name().to_string();
+    let mut found = defaults.get(&path).map(|v| (v, i));
+    // A nested subcommand follows directly, as in `av actors find ...`
+    if let Some(sub) = args.get(i + 1).and_then(|a| a.to_str()).and_then(|name| cmd.find_subcommand(name)) {
+        path = format!("{} {}", path, sub.get_name());
+        if let Some(v) = defaults.get(&path) {
+            found = Some((v, i + 1));
+        }
+    }
+    let Some((value, at)) = found else { return Ok(args) };
+    let Some(words) = shell::split(value) else { bail!("[defaults] {} 的引号未闭合: {}", path, value) };
+    let mut out = args[..=at].to_vec();
+    out.extend(words.into_iter().map(OsString::from));
+    out.extend_from_slice(&args[at + 1..]);
+    Ok(out)
+}
@@ -0,0 +1,45 @@
+//! Amateur (素人) releases: the MGS-style labels (`SIRO`, `LUXU`, `MAAN`, ...) and
+//! anything carrying the 素人 genre. `--amateur` keeps only them, `--no-amateur` drops
+//! them; details say so in `amateur`.
+
+use std::sync::Mutex;
+
+use crate::code::{self, Code};
+use crate::genre;
+use crate::types::{AvDetail, AvItem};
+
+/// Amateur labels without DMM's label id (`259LUXU` → `LUXU`)
+const LABELS: &[&str] = &[
+    "SIRO", "LUXU", "MAAN", "GANA", "ARA", "ORE", "OREC", "MIUM", "NTK", "DCV", "EVA", "SCP", "KNB", "JAC",
+    "MFC", "SUKE", "SGK", "JNT",
+];
+
+/// `--amateur` → `Some(true)`, `--no-amateur` → `Some(false)`
+static FILTER: Mutex<Option<bool>> = Mutex::new(None);
+
+pub fn set_filter(filter: Option<bool>) {
+    *FILTER.lock().unwrap() = filter;
+}
+
+fn amateur_label(c: &str) -> bool {
+    match code::parse(c) {
+        Some(Code::Standard { prefix, .. }) => LABELS.contains(&prefix.trim_start_matches(|c: char| c.is_ascii_digit())),
+        _ => false,
+    }
+}
+
+/// By code and title alone, for listings
+pub fn looks_amateur(item: &AvItem) -> bool {
+    amateur_label(&item.code) || item.title.contains("素人")
+}
+
+pub fn is_amateur(d: &AvDetail) -> bool {
+    amateur_label(&d.code) || genre::has_genre(d, "amateur")
+}
+
+/// `--amateur` / `--no-amateur` on a listing
+pub fn filter_items(items: &mut Vec<AvItem>) {
+    if let Some(want) = *FILTER.lock().unwrap() {
+        items.retain(|i| looks_amateur(i) == want);
+    }
+}
@@ -0,0 +1,268 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use image::{imageops::FilterType, DynamicImage, GenericImage, Rgb, RgbImage};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::config::{self, ArtworkConfig};
+use crate::scan;
+use crate::scraper;
+use crate::types::AvDetail;
+use crate::util;
+
+/// Width of each tile in a contact sheet; heights keep the source aspect ratio
+const SHEET_TILE_WIDTH: u32 = 400;
+const SHEET_GAP: u32 = 4;
+
+#[derive(Debug, Serialize)]
+struct PreviewsOutput {
+    code: String,
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+    /// Near-duplicate previews that were not saved
+    duplicates: usize,
+    sheet: Option<PathBuf>,
+}
+
+/// File extension for an image URL, defaulting to jpg
+fn extension(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match path.rsplit('.').next().map(|e| e.to_ascii_lowercase()) {
+        Some(e) if e == "png" => "png",
+        Some(e) if e == "webp" => "webp",
+        _ => "jpg",
+    }
+}
+
+/// Max Hamming distance between two dHashes for the images to count as the same picture
+const DUPLICATE_DISTANCE: u32 = 6;
+/// Max difference in mean brightness; flat frames (black/title cards) all hash to zero,
+/// so the hash alone can't tell a black frame from a white one
+const DUPLICATE_LUMA: u8 = 16;
+
+/// Perceptual fingerprint: a 64-bit difference hash of the image shrunk to 9x8 grayscale
+/// (survives rescaling and recompression) plus its mean brightness and pixel count
+#[derive(Clone, Copy)]
+struct Fingerprint {
+    hash: u64,
+    luma: u8,
+    pixels: u64,
+}
+
+impl Fingerprint {
+    fn of(img: &DynamicImage) -> Self {
+        let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+        let mut hash = 0u64;
+        for y in 0..8 {
+            for x in 0..8 {
+                hash = (hash << 1) | (small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]) as u64;
+            }
+        }
+        let luma = (small.pixels().map(|p| p[0] as u32).sum::<u32>() / 72) as u8;
+        Fingerprint { hash, luma, pixels: img.width() as u64 * img.height() as u64 }
+    }
+
+    fn same_picture(&self, other: &Fingerprint) -> bool {
+        (self.hash ^ other.hash).count_ones() <= DUPLICATE_DISTANCE && self.luma.abs_diff(other.luma) <= DUPLICATE_LUMA
+    }
+}
+
+struct Fetched {
+    url: String,
+    bytes: Vec<u8>,
+}
+
+/// Drop near-duplicate images, keeping the highest-resolution variant in the position of
+/// the first one seen. Images that don't decode are kept as they are.
+fn dedup(images: Vec<Fetched>) -> (Vec<Fetched>, usize) {
+    let mut kept: Vec<(Option<Fingerprint>, Fetched)> = Vec::new();
+    let mut dropped = 0;
+    for f in images {
+        let fp = image::load_from_memory(&f.bytes).ok().map(|img| Fingerprint::of(&img));
+        let Some(fp) = fp else {
+            kept.push((None, f));
+            continue;
+        };
+        match kept.iter().position(|(k, _)| k.is_some_and(|k| k.same_picture(&fp))) {
+            Some(i) => {
+                dropped += 1;
+                util::debug(format!("artwork: {} duplicates {}", f.url, kept[i].1.url));
+                if kept[i].0.is_some_and(|k| fp.pixels > k.pixels) {
+                    kept[i] = (Some(fp), f);
+                }
+            }
+            None => kept.push((Some(fp), f)),
+        }
+    }
+    (kept.into_iter().map(|(_, f)| f).collect(), dropped)
+}
+
+/// Download `urls` `--jobs` at a time, drop perceptual duplicates and save the rest into
+/// `dir`, named by `name(n, extension)` counting from 1. Failed downloads are warned
+/// about and left out. Returns the saved files and how many duplicates were dropped.
+pub async fn download_all(urls: &[String], dir: &Path, name: fn(usize, &str) -> String) -> Result<(Vec<PathBuf>, usize)> {
+    std::fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, url) in urls.iter().cloned().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = scraper::get_bytes(&scraper::client(), &url).await;
+            (idx, url, result)
+        });
+    }
+    let mut done = tasks.join_all().await;
+    done.sort_by_key(|(idx, ..)| *idx);
+    let mut fetched = Vec::new();
+    for (_, url, result) in done {
+        match result {
+            Ok(bytes) => fetched.push(Fetched { url, bytes }),
+            Err(e) => eprintln!("[WARN] 预览图下载失败 {}: {:#}", url, e),
+        }
+    }
+    let (images, dropped) = dedup(fetched);
+    let mut files = Vec::new();
+    for (idx, f) in images.into_iter().enumerate() {
+        let path = dir.join(name(idx + 1, extension(&f.url)));
+        std::fs::write(&path, f.bytes).with_context(|| format!("写入失败: {}", path.display()))?;
+        files.push(path);
+    }
+    Ok((files, dropped))
+}
+
+/// Tile `files` into one image, `columns` per row, each scaled to the same width
+pub fn contact_sheet(files: &[PathBuf], columns: u32, out: &Path) -> Result<()> {
+    let mut tiles: Vec<DynamicImage> = Vec::new();
+    for f in files {
+        match image::open(f) {
+            Ok(img) => tiles.push(img.resize(SHEET_TILE_WIDTH, u32::MAX, FilterType::Triangle)),
+            Err(e) => eprintln!("[WARN] 无法读取图片 {}: {}", f.display(), e),
+        }
+    }
+    if tiles.is_empty() {
+        bail!("没有可用于拼图的预览图");
+    }
+    let columns = columns.clamp(1, tiles.len() as u32);
+    let rows: Vec<&[DynamicImage]> = tiles.chunks(columns as usize).collect();
+    let row_heights: Vec<u32> = rows.iter().map(|r| r.iter().map(|t| t.height()).max().unwrap_or(0)).collect();
+    let width = columns * SHEET_TILE_WIDTH + (columns + 1) * SHEET_GAP;
+    let height = row_heights.iter().sum::<u32>() + (rows.len() as u32 + 1) * SHEET_GAP;
+    let mut sheet = RgbImage::from_pixel(width, height, Rgb([20, 20, 20]));
+    let mut y = SHEET_GAP;
+    for (row, h) in rows.iter().zip(&row_heights) {
+        let mut x = SHEET_GAP;
+        for tile in row.iter() {
+            sheet.copy_from(&tile.to_rgb8(), x, y).context("拼图失败")?;
+            x += SHEET_TILE_WIDTH + SHEET_GAP;
+        }
+        y += h + SHEET_GAP;
+    }
+    sheet.save(out).with_context(|| format!("写入失败: {}", out.display()))
+}
+
+/// Portrait poster cut from a wide two-panel cover. Covers that are already portrait
+/// (or narrower than the ratio asks) come back unchanged.
+pub fn poster_from_cover(cover: &DynamicImage, cfg: &ArtworkConfig) -> DynamicImage {
+    let (w, h) = (cover.width(), cover.height());
+    let ratio = if cfg.poster_ratio > 0.0 { cfg.poster_ratio } else { ArtworkConfig::default().poster_ratio };
+    let crop_w = ((h as f32) * ratio).round() as u32;
+    if crop_w == 0 || crop_w >= w {
+        return cover.clone();
+    }
+    let x = ((w - crop_w) as f32 * cfg.poster_offset.clamp(0.0, 1.0)).round() as u32;
+    cover.crop_imm(x, 0, crop_w, h)
+}
+
+/// The name videos of `code` in `dir` go by, which Kodi/Jellyfin match `-thumb.jpg` to:
+/// the one video's own name, else the code (as `rename` names them, multi-part ones too)
+fn video_base(code: &str, dir: &Path) -> String {
+    let single = scan::scan(dir).ok().and_then(|r| r.groups.into_iter().find(|g| g.code == code)).filter(|g| g.files.len() == 1);
+    single
+        .and_then(|g| g.files[0].path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| code.to_string())
+}
+
+/// Save artwork into `dir` under the names Kodi/Jellyfin pick up next to a video:
+/// `fanart.jpg` (the full cover), the cropped `poster.jpg`, `<video>-thumb.jpg` (the cover
+/// again, as the landscape thumbnail) and, with `[artwork] extrafanart`, the previews as
+/// `extrafanart/fanart1.jpg`, `fanart2.jpg`, ...
+pub async fn save_cover_art(d: &AvDetail, dir: &Path) -> Result<Vec<PathBuf>> {
+    let url = d.cover_url.as_deref().with_context(|| format!("{} 没有封面", d.code))?;
+    let bytes = scraper::get_bytes(&scraper::client(), url).await?;
+    let cover = image::load_from_memory(&bytes).with_context(|| format!("无法解析封面图片: {}", url))?;
+    std::fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+    let cfg = &config::get().artwork;
+    let fanart = dir.join("fanart.jpg");
+    let poster = dir.join("poster.jpg");
+    let thumb = dir.join(format!("{}-thumb.jpg", video_base(&d.code, dir)));
+    let full = cover.to_rgb8();
+    for path in [&fanart, &thumb] {
+        full.save(path).with_context(|| format!("写入失败: {}", path.display()))?;
+    }
+    poster_from_cover(&cover, cfg).to_rgb8().save(&poster).with_context(|| format!("写入失败: {}", poster.display()))?;
+    let mut files = vec![fanart, poster, thumb];
+    if cfg.extrafanart && !d.preview_images.is_empty() {
+        let (extra, _) = download_all(&d.preview_images, &dir.join("extrafanart"), |n, ext| format!("fanart{}.{}", n, ext)).await?;
+        files.extend(extra);
+    }
+    Ok(files)
+}
+
+/// `av artwork`: save the cover as fanart, thumbnail and a correctly framed poster, and the
+/// previews as extrafanart
+pub async fn artwork(code: &str, out: Option<PathBuf>, json: bool) -> Result<()> {
+    let detail = scraper::fetch_detail(code).await?;
+    let dir = out.unwrap_or_else(|| PathBuf::from(&detail.code));
+    if util::dry_run_skip(format!("将保存 {} 的 fanart.jpg、poster.jpg、缩略图与 extrafanart 到 {}", detail.code, dir.display())) {
+        return Ok(());
+    }
+    let files = save_cover_art(&detail, &dir).await?;
+    if json {
+        util::print_output(&files, true);
+        return Ok(());
+    }
+    for f in &files {
+        println!("{} {}", "已保存".green().bold(), f.display());
+    }
+    Ok(())
+}
+
+/// `av previews`: download every preview image, optionally composing a contact sheet
+pub async fn previews(code: &str, out: Option<PathBuf>, sheet: bool, columns: u32, json: bool) -> Result<()> {
+    let detail = scraper::fetch_detail(code).await?;
+    if detail.preview_images.is_empty() {
+        bail!("{} 没有预览图", detail.code);
+    }
+    let dir = out.unwrap_or_else(|| PathBuf::from(format!("{}-previews", detail.code)));
+    let sheet_path = dir.join("sheet.jpg");
+    if util::dry_run_skip(format!(
+        "将下载 {} 张预览图到 {}{}",
+        detail.preview_images.len(),
+        dir.display(),
+        if sheet { format!("，并生成 {}", sheet_path.display()) } else { String::new() }
+    )) {
+        return Ok(());
+    }
+    let (files, duplicates) = download_all(&detail.preview_images, &dir, |n, ext| format!("{:02}.{}", n, ext)).await?;
+    let sheet = if sheet {
+        contact_sheet(&files, columns, &sheet_path)?;
+        Some(sheet_path)
+    } else {
+        None
+    };
+    if json {
+        util::print_output(&PreviewsOutput { code: detail.code, dir, files, duplicates, sheet }, true);
+        return Ok(());
+    }
+    println!("{} {} 张预览图 → {}", "已下载".green().bold(), files.len(), dir.display());
+    if duplicates > 0 {
+        println!("  跳过 {} 张重复的预览图", duplicates);
+    }
+    if let Some(s) = sheet {
+        println!("{} {}", "拼图".green().bold(), s.display());
+    }
+    Ok(())
+}
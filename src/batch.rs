@@ -0,0 +1,284 @@
+//! Resumable batch runs: downloads of several picked rows (`--pick 1-20 --then download`),
+//! `subscribe check --queue`, `refresh --all` and `prefetch` record what they are going to do in
+//! `batch_jobs.json` and tick entries off as they finish. `actors --all` has no entries to
+//! list up front; its job keeps a [`Cursor`] instead, moved on after every page. A run that
+//! is interrupted (Ctrl-C, a crash, a download that fails) leaves its job behind, and
+//! `av resume <id>` carries on with the entries that aren't done, or from the next page.
+
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::sync::Mutex;
+
+use crate::scraper;
+use crate::store;
+use crate::util;
+
+const BATCH_JOBS: &str = "batch_jobs";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// Fetch each code's detail and download it (its `magnet`, else the best one)
+    Download,
+    /// Refresh each code's cached magnets
+    Refresh,
+    /// Look each code up into the detail cache (`av prefetch`)
+    Prefetch,
+    /// Read every actor ranking page (`actors --all`), from the job's cursor
+    Actors,
+}
+
+/// How far an `actors --all` crawl got: the ranking and page to read next, and the actors
+/// listed so far, so a resumed crawl doesn't list them again
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cursor {
+    pub ranking: usize,
+    pub page: usize,
+    pub seen: Vec<String>,
+    /// Actors the filters kept so far, for the closing total
+    #[serde(default)]
+    pub kept: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub magnet: Option<String>,
+    #[serde(default)]
+    pub done: bool,
+}
+
+impl Entry {
+    pub fn new(code: impl Into<String>, magnet: Option<String>) -> Entry {
+        Entry { code: code.into(), magnet, done: false }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: Kind,
+    pub started: String,
+    /// The command line that started it, aliases expanded
+    pub args: Vec<String>,
+    /// A failing entry is warned about and the rest still run (`subscribe check`)
+    /// instead of stopping the run
+    #[serde(default)]
+    pub keep_going: bool,
+    pub entries: Vec<Entry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<Cursor>,
+    /// Not written to disk: a single entry, or `--dry-run`
+    #[serde(skip)]
+    transient: bool,
+}
+
+/// The command line being run, recorded with each job it starts
+static ARGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn set_args(args: &[OsString]) {
+    *ARGS.lock().unwrap() = args.iter().map(|a| a.to_string_lossy().into_owned()).collect();
+}
+
+fn load_all() -> Result<Vec<Job>> {
+    store::load(BATCH_JOBS)
+}
+
+impl Job {
+    /// Record a new job; one entry isn't worth resuming and is kept in memory only
+    pub fn start(kind: Kind, entries: Vec<Entry>, keep_going: bool) -> Result<Job> {
+        let mut job = Job::new(kind, entries, keep_going);
+        if job.entries.len() < 2 {
+            job.transient = true;
+        }
+        Job::record(job)
+    }
+
+    /// Record a new `actors --all` crawl, starting at the first page
+    pub fn crawl() -> Result<Job> {
+        let cursor = Cursor { page: 1, ..Cursor::default() };
+        Job::record(Job { cursor: Some(cursor), ..Job::new(Kind::Actors, Vec::new(), false) })
+    }
+
+    fn new(kind: Kind, entries: Vec<Entry>, keep_going: bool) -> Job {
+        Job {
+            id: String::new(),
+            kind,
+            started: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            args: ARGS.lock().unwrap().clone(),
+            keep_going,
+            entries,
+            cursor: None,
+            transient: util::is_dry_run(),
+        }
+    }
+
+    /// Give `job` an ID and write it down, unless it is transient
+    fn record(mut job: Job) -> Result<Job> {
+        if job.transient {
+            return Ok(job);
+        }
+        let mut jobs = load_all()?;
+        let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        job.id = stamp.clone();
+        let mut n = 1;
+        while jobs.iter().any(|j| j.id == job.id) {
+            n += 1;
+            job.id = format!("{}-{}", stamp, n);
+        }
+        jobs.push(job.clone());
+        store::save(BATCH_JOBS, &jobs)?;
+        util::debug(format!("batch job {}: {} entries", job.id, job.entries.len()));
+        Ok(job)
+    }
+
+    pub fn load(id: &str) -> Result<Job> {
+        load_all()?
+            .into_iter()
+            .find(|j| j.id == id)
+            .with_context(|| format!("没有未完成的批量任务 {}，用 av resume 查看全部", id))
+    }
+
+    /// For listings: `av top --pick 1-5 --then download`
+    pub fn command(&self) -> String {
+        let mut words = vec!["av".to_string()];
+        words.extend(self.args.iter().skip(1).cloned());
+        words.join(" ")
+    }
+
+    pub fn pending(&self) -> Vec<Entry> {
+        self.entries.iter().filter(|e| !e.done).cloned().collect()
+    }
+
+    /// Tick `finished` off; the job is dropped once every entry is done
+    pub fn done(&mut self, finished: &[Entry]) -> Result<()> {
+        for e in self.entries.iter_mut() {
+            if finished.iter().any(|f| f.code == e.code && f.magnet == e.magnet) {
+                e.done = true;
+            }
+        }
+        self.save(self.entries.iter().all(|e| e.done))
+    }
+
+    /// Move a crawl's cursor on past a page that has been listed
+    pub fn advance(&mut self, cursor: Cursor) -> Result<()> {
+        self.cursor = Some(cursor);
+        self.save(false)
+    }
+
+    /// Drop a crawl that read its last page
+    pub fn finish(&mut self) -> Result<()> {
+        self.save(true)
+    }
+
+    fn save(&self, finished: bool) -> Result<()> {
+        if self.transient {
+            return Ok(());
+        }
+        let mut jobs = load_all()?;
+        if finished {
+            jobs.retain(|j| j.id != self.id);
+        } else if let Some(j) = jobs.iter_mut().find(|j| j.id == self.id) {
+            *j = self.clone();
+        }
+        store::save(BATCH_JOBS, &jobs)
+    }
+
+    /// `3/10` entries done, or the page a crawl is at
+    fn progress(&self) -> String {
+        match &self.cursor {
+            Some(c) => format!("已列出 {}，下一页 {}", c.seen.len(), c.page),
+            None => format!("{}/{}", self.entries.iter().filter(|e| e.done).count(), self.entries.len()),
+        }
+    }
+
+    /// Tell how to carry on after `result`, when it stopped the run early
+    pub fn hint<T>(&self, result: Result<T>) -> Result<T> {
+        if result.is_err() && !self.transient {
+            if self.cursor.is_some() {
+                eprintln!("可用 av resume {} 从下一页继续", self.id);
+                return result;
+            }
+            let left = self.entries.iter().filter(|e| !e.done).count();
+            if left > 0 {
+                eprintln!("还有 {} 项未完成，可用 av resume {} 继续", left, self.id);
+            }
+        }
+        result
+    }
+}
+
+/// Download the job's pending entries in order
+pub async fn download(job: &mut Job) -> Result<()> {
+    let result = download_pending(job).await;
+    job.hint(result)
+}
+
+async fn download_pending(job: &mut Job) -> Result<()> {
+    let mut failed = 0;
+    for entry in job.pending() {
+        crate::cancel::check()?;
+        let attempt = async {
+            let d = scraper::fetch_detail(&entry.code).await?;
+            util::download_detail(&d, entry.magnet.clone(), None).await
+        };
+        match attempt.await {
+            Ok(()) => job.done(std::slice::from_ref(&entry))?,
+            Err(e) if job.keep_going && !crate::cancel::is_cancelled() => {
+                eprintln!("[WARN] {} 下载失败: {:#}", entry.code, e);
+                failed += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if failed > 0 {
+        bail!("{} 项下载失败", failed);
+    }
+    Ok(())
+}
+
+/// `av resume`: the unfinished jobs
+pub fn list(json: bool) -> Result<()> {
+    let jobs = load_all()?;
+    if json {
+        util::print_output(&jobs, true);
+        return Ok(());
+    }
+    if jobs.is_empty() {
+        println!("{}", "没有未完成的批量任务".yellow());
+        return Ok(());
+    }
+    for j in &jobs {
+        println!("{}  {}  {}  {}", j.id.bold(), j.started, j.progress(), j.command().dimmed());
+    }
+    Ok(())
+}
+
+/// `av resume ID`: carry on with the entries that aren't done
+pub async fn resume(mut job: Job, json: bool) -> Result<()> {
+    let left = job.pending().len();
+    util::note(format!("继续批量任务 {}：剩余 {}/{} 项（{}）", job.id, left, job.entries.len(), job.command()));
+    match job.kind {
+        Kind::Actors => unreachable!("resumed through actress::crawl"),
+        Kind::Download => download(&mut job).await,
+        Kind::Refresh => crate::cache::refresh_job(&mut job, json).await,
+        Kind::Prefetch => crate::cache::prefetch_job(&mut job, json).await,
+    }
+}
+
+/// `av resume ID --drop`
+pub fn drop_job(id: &str) -> Result<()> {
+    let mut jobs = load_all()?;
+    let before = jobs.len();
+    jobs.retain(|j| j.id != id);
+    if jobs.len() == before {
+        bail!("没有未完成的批量任务 {}", id);
+    }
+    store::save(BATCH_JOBS, &jobs)?;
+    util::note(format!("已删除批量任务 {}", id));
+    Ok(())
+}
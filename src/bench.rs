@@ -0,0 +1,196 @@
+//! `av bench`: time every enabled detail source and JavDB mirror on a fixed set of sample
+//! codes — latency percentiles, how often a request succeeds and finds the code, and how
+//! much of the detail its parser fills in — to help order `[javdb] mirrors` and choose
+//! which sources are worth keeping enabled.
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::mirrors;
+use crate::scraper;
+use crate::source_diff;
+use crate::table;
+use crate::util;
+
+/// Long-running, well-indexed releases every source should have
+const SAMPLE_CODES: [&str; 5] = ["SSIS-001", "ABP-123", "IPX-177", "MIDE-001", "STARS-080"];
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    name: String,
+    /// `source` or `mirror`
+    kind: &'static str,
+    runs: usize,
+    /// Requests that didn't fail; a source answering "no such code" counts
+    ok: usize,
+    /// Requests that returned the code's detail
+    hits: usize,
+    p50_ms: Option<u64>,
+    p95_ms: Option<u64>,
+    /// Average share of the compared fields filled in per hit, 0.0 ..= 1.0
+    completeness: Option<f32>,
+    /// The last failure, to tell a block from a timeout
+    last_error: Option<String>,
+}
+
+impl Stats {
+    fn new(name: String, kind: &'static str) -> Self {
+        Stats { name, kind, runs: 0, ok: 0, hits: 0, p50_ms: None, p95_ms: None, completeness: None, last_error: None }
+    }
+
+    fn rate(n: usize, of: usize) -> f64 {
+        if of == 0 { 0.0 } else { n as f64 / of as f64 }
+    }
+
+    fn finish(&mut self, mut latencies: Vec<Duration>, completeness: Vec<f32>) {
+        latencies.sort();
+        self.p50_ms = percentile(&latencies, 0.50);
+        self.p95_ms = percentile(&latencies, 0.95);
+        if !completeness.is_empty() {
+            self.completeness = Some(completeness.iter().sum::<f32>() / completeness.len() as f32);
+        }
+    }
+
+    /// Best first: what finds or answers most often, then the fastest
+    fn rank_key(&self) -> (std::cmp::Reverse<u64>, u64) {
+        let found = if self.kind == "mirror" { self.ok } else { self.hits };
+        (std::cmp::Reverse((Stats::rate(found, self.runs) * 1000.0) as u64), self.p50_ms.unwrap_or(u64::MAX))
+    }
+}
+
+/// Nearest-rank percentile of sorted latencies, in milliseconds
+fn percentile(sorted: &[Duration], p: f64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    Some(sorted[rank - 1].as_millis() as u64)
+}
+
+async fn bench_source(name: String, codes: Vec<String>, rounds: usize) -> Result<Stats> {
+    let mut stats = Stats::new(name, "source");
+    let (mut latencies, mut filled) = (Vec::new(), Vec::new());
+    for _ in 0..rounds {
+        for code in &codes {
+            cancel::check()?;
+            let started = Instant::now();
+            let result = scraper::fetch_detail_named(&stats.name, code).await;
+            stats.runs += 1;
+            match result {
+                Ok(found) => {
+                    stats.ok += 1;
+                    latencies.push(started.elapsed());
+                    if let Some(d) = found {
+                        stats.hits += 1;
+                        filled.push(source_diff::completeness(&d));
+                    }
+                }
+                Err(e) => {
+                    cancel::check()?;
+                    util::debug(format!("bench {} {}: {:#}", stats.name, code, e));
+                    stats.last_error = Some(format!("{:#}", e));
+                }
+            }
+        }
+    }
+    stats.finish(latencies, filled);
+    Ok(stats)
+}
+
+async fn bench_mirror(base: String, samples: usize) -> Result<Stats> {
+    let c = scraper::client();
+    let mut stats = Stats::new(base, "mirror");
+    let mut latencies = Vec::new();
+    for _ in 0..samples {
+        cancel::check()?;
+        stats.runs += 1;
+        match mirrors::probe(&c, &stats.name).await {
+            Some(d) => {
+                stats.ok += 1;
+                latencies.push(d);
+            }
+            None => stats.last_error = Some("无响应或返回错误".to_string()),
+        }
+    }
+    stats.finish(latencies, Vec::new());
+    Ok(stats)
+}
+
+/// `av bench`: `rounds` lookups of each of `codes` (the built-in samples when empty) per
+/// source, restricted to `only` when given; sources run side by side, each one's requests
+/// one after another so they don't queue behind each other
+pub async fn run(codes: &[String], rounds: usize, only: &[String], json: bool) -> Result<()> {
+    let codes: Vec<String> = if codes.is_empty() {
+        SAMPLE_CODES.iter().map(|c| c.to_string()).collect()
+    } else {
+        codes.iter().map(|c| crate::code::normalize(c)).collect()
+    };
+    let rounds = rounds.max(1);
+    let wanted = |name: &str| only.is_empty() || only.iter().any(|o| o.eq_ignore_ascii_case(name));
+    let mut sources: Vec<String> = Vec::new();
+    for code in &codes {
+        for name in scraper::detail_sources(code) {
+            if wanted(&name) && !sources.contains(&name) {
+                sources.push(name);
+            }
+        }
+    }
+    // Mirrors only matter for JavDB, and mock runs touch no network
+    let mirrors = if crate::sources::mock::is_enabled() || !wanted("javdb") { Vec::new() } else { mirrors::list() };
+    if sources.is_empty() && mirrors.is_empty() {
+        anyhow::bail!("没有匹配的来源（可用: {}）", scraper::detail_sources(&codes[0]).join(", "));
+    }
+    util::note(format!(
+        "测试 {} 个来源、{} 个镜像：{} 个番号 × {} 轮",
+        sources.len(),
+        mirrors.len(),
+        codes.len(),
+        rounds
+    ));
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for name in sources {
+        tasks.spawn(bench_source(name, codes.clone(), rounds));
+    }
+    for base in mirrors {
+        tasks.spawn(bench_mirror(base, codes.len() * rounds));
+    }
+    let mut results = Vec::new();
+    for done in tasks.join_all().await {
+        results.push(done?);
+    }
+    results.sort_by_key(|s| (s.kind != "source", s.rank_key()));
+
+    if json {
+        util::print_output(&results, true);
+        return Ok(());
+    }
+    let ms = |v: Option<u64>| v.map(|v| format!("{}ms", v)).unwrap_or_else(|| "-".to_string());
+    let pct = |n: usize, of: usize| format!("{:.0}%", Stats::rate(n, of) * 100.0);
+    let rows = results
+        .iter()
+        .map(|s| {
+            vec![
+                s.name.clone(),
+                if s.kind == "mirror" { "镜像" } else { "来源" }.to_string(),
+                ms(s.p50_ms),
+                ms(s.p95_ms),
+                pct(s.ok, s.runs),
+                if s.kind == "mirror" { "-".to_string() } else { pct(s.hits, s.runs) },
+                s.completeness.map(|c| format!("{:.0}%", c * 100.0)).unwrap_or_else(|| "-".to_string()),
+                s.last_error.as_deref().map(|e| util::truncate_chars(e, 40)).unwrap_or_default(),
+            ]
+        })
+        .collect();
+    table::print_rows(&["名称", "类型", "p50", "p95", "成功率", "命中率", "完整度", "最近错误"], rows);
+
+    let mirrors: Vec<&Stats> = results.iter().filter(|s| s.kind == "mirror" && s.ok > 0).collect();
+    if mirrors.len() > 1 {
+        let order = mirrors.iter().map(|s| format!("\"{}\"", s.name)).collect::<Vec<_>>().join(", ");
+        println!("\n{} [javdb] mirrors = [{}]", "建议的镜像顺序:".bold(), order);
+    }
+    Ok(())
+}
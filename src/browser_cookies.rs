@@ -0,0 +1,200 @@
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+use crate::cookies::ImportedCookie;
+use crate::util;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Browser {
+    Chrome,
+    Chromium,
+    Firefox,
+}
+
+/// Seconds between 1601-01-01 (Chrome's epoch) and 1970-01-01
+const CHROME_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/// Read cookies for `domain` (and its subdomains) from the browser's local store
+pub fn read(browser: Browser, domain: &str) -> Result<Vec<ImportedCookie>> {
+    let domain = domain.trim().trim_start_matches('.').to_lowercase();
+    match browser {
+        Browser::Firefox => read_firefox(&domain),
+        Browser::Chrome | Browser::Chromium => read_chrome(browser, &domain),
+    }
+}
+
+/// Browsers keep their databases locked while running; work on a private copy
+fn snapshot(db: &Path) -> Result<tempfile::TempPath> {
+    let tmp = tempfile::NamedTempFile::new().context("创建临时文件失败")?.into_temp_path();
+    std::fs::copy(db, &tmp).with_context(|| format!("无法读取 {}", db.display()))?;
+    Ok(tmp)
+}
+
+fn newest(paths: Vec<PathBuf>) -> Option<PathBuf> {
+    paths
+        .into_iter()
+        .filter(|p| p.exists())
+        .max_by_key(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok())
+}
+
+fn firefox_profile_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        roots.push(home.join(".mozilla/firefox"));
+        roots.push(home.join("snap/firefox/common/.mozilla/firefox"));
+        roots.push(home.join("Library/Application Support/Firefox/Profiles"));
+    }
+    if let Some(appdata) = dirs::config_dir() {
+        roots.push(appdata.join("Mozilla/Firefox/Profiles"));
+    }
+    roots
+}
+
+fn read_firefox(domain: &str) -> Result<Vec<ImportedCookie>> {
+    let candidates: Vec<PathBuf> = firefox_profile_roots()
+        .into_iter()
+        .filter_map(|root| std::fs::read_dir(root).ok())
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().join("cookies.sqlite"))
+        .collect();
+    let db = newest(candidates).context("未找到 Firefox 的 cookies.sqlite")?;
+    util::debug(format!("firefox cookies: {}", db.display()));
+    let copy = snapshot(&db)?;
+    let conn = rusqlite::Connection::open(&copy).context("打开 Firefox Cookie 数据库失败")?;
+    let mut stmt = conn.prepare(
+        "SELECT name, value, host, path, expiry, isSecure, isHttpOnly FROM moz_cookies WHERE host = ?1 OR host LIKE ?2",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![domain, format!("%.{}", domain)], |r| {
+        let expiry: i64 = r.get(4)?;
+        Ok(ImportedCookie {
+            name: r.get(0)?,
+            value: r.get(1)?,
+            domain: r.get(2)?,
+            path: r.get(3)?,
+            // Recent Firefox versions store milliseconds
+            expires: Some(if expiry > 100_000_000_000 { expiry / 1000 } else { expiry }),
+            secure: r.get::<_, i64>(5)? != 0,
+            http_only: r.get::<_, i64>(6)? != 0,
+        })
+    })?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+fn chrome_db(browser: Browser) -> Option<PathBuf> {
+    let vendor = match browser {
+        Browser::Chromium => "chromium",
+        _ => "google-chrome",
+    };
+    let mac_vendor = match browser {
+        Browser::Chromium => "Chromium",
+        _ => "Google/Chrome",
+    };
+    let mut candidates = Vec::new();
+    if let Some(cfg) = dirs::config_dir() {
+        for profile in ["Default", "Profile 1"] {
+            candidates.push(cfg.join(vendor).join(profile).join("Cookies"));
+            candidates.push(cfg.join(mac_vendor).join(profile).join("Cookies"));
+        }
+    }
+    newest(candidates)
+}
+
+/// Chrome on Linux/macOS encrypts values with AES-128-CBC using a PBKDF2 key derived
+/// from a password kept in the OS keyring ("peanuts" when no keyring is in use).
+fn chrome_key(browser: Browser) -> Result<[u8; 16]> {
+    let (password, iterations) = if cfg!(target_os = "macos") {
+        let service = match browser {
+            Browser::Chromium => "Chromium Safe Storage",
+            _ => "Chrome Safe Storage",
+        };
+        let out = std::process::Command::new("security")
+            .args(["find-generic-password", "-w", "-s", service])
+            .output()
+            .context("无法调用 security 读取钥匙串")?;
+        if !out.status.success() {
+            bail!("无法从钥匙串读取 {}（需要允许访问）", service);
+        }
+        (String::from_utf8_lossy(&out.stdout).trim().to_string(), 1003)
+    } else {
+        let app = match browser {
+            Browser::Chromium => "chromium",
+            _ => "chrome",
+        };
+        let from_keyring = std::process::Command::new("secret-tool")
+            .args(["lookup", "application", app])
+            .output()
+            .ok()
+            .filter(|o| o.status.success() && !o.stdout.is_empty())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+        (from_keyring.unwrap_or_else(|| "peanuts".to_string()), 1)
+    };
+    let mut key = [0u8; 16];
+    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password.as_bytes(), b"saltysalt", iterations, &mut key);
+    Ok(key)
+}
+
+fn decrypt_chrome_value(key: &[u8; 16], encrypted: &[u8], strip_domain_hash: bool) -> Option<String> {
+    let payload = encrypted.strip_prefix(b"v10").or_else(|| encrypted.strip_prefix(b"v11"))?;
+    let mut buf = payload.to_vec();
+    let plain = cbc::Decryptor::<aes::Aes128>::new(key.into(), &[b' '; 16].into())
+        .decrypt_padded_mut::<Pkcs7>(&mut buf)
+        .ok()?;
+    // Since DB version 24 the plaintext is prefixed with SHA-256(host_key)
+    let plain = if strip_domain_hash && plain.len() >= 32 { &plain[32..] } else { plain };
+    String::from_utf8(plain.to_vec()).ok()
+}
+
+fn read_chrome(browser: Browser, domain: &str) -> Result<Vec<ImportedCookie>> {
+    if cfg!(windows) {
+        bail!("暂不支持读取 Windows 上的 Chrome Cookie（使用 DPAPI 加密），请改用 Firefox 或 av login javdb --cookie");
+    }
+    let db = chrome_db(browser).context("未找到 Chrome 的 Cookies 数据库")?;
+    util::debug(format!("chrome cookies: {}", db.display()));
+    let copy = snapshot(&db)?;
+    let conn = rusqlite::Connection::open(&copy).context("打开 Chrome Cookie 数据库失败")?;
+    let version: i64 = conn
+        .query_row("SELECT value FROM meta WHERE key = 'version'", [], |r| r.get::<_, String>(0))
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let key = chrome_key(browser)?;
+    let mut stmt = conn.prepare(
+        "SELECT name, value, encrypted_value, host_key, path, expires_utc, is_secure, is_httponly FROM cookies WHERE host_key = ?1 OR host_key LIKE ?2",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![domain, format!("%.{}", domain)], |r| {
+        Ok((
+            r.get::<_, String>(0)?,
+            r.get::<_, String>(1)?,
+            r.get::<_, Vec<u8>>(2)?,
+            r.get::<_, String>(3)?,
+            r.get::<_, String>(4)?,
+            r.get::<_, i64>(5)?,
+            r.get::<_, i64>(6)? != 0,
+            r.get::<_, i64>(7)? != 0,
+        ))
+    })?;
+    let mut out = Vec::new();
+    let mut undecryptable = 0;
+    for (name, value, encrypted, host, path, expires_utc, secure, http_only) in rows.filter_map(|r| r.ok()) {
+        let value = if !value.is_empty() {
+            value
+        } else {
+            match decrypt_chrome_value(&key, &encrypted, version >= 24) {
+                Some(v) => v,
+                None => {
+                    undecryptable += 1;
+                    continue;
+                }
+            }
+        };
+        let expires = (expires_utc > 0).then(|| expires_utc / 1_000_000 - CHROME_EPOCH_OFFSET);
+        out.push(ImportedCookie { name, value, domain: host, path, expires, secure, http_only });
+    }
+    if undecryptable > 0 {
+        eprintln!("[WARN] {} 个 Cookie 无法解密（钥匙串/密钥环不可用？）", undecryptable);
+    }
+    Ok(out)
+}
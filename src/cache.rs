@@ -0,0 +1,353 @@
+//! Local copy of every merged detail fetched, keyed by code. Lookups are served from it
+//! only when `[cache] ttl_hours` is set; `av refresh` brings the magnets of cached titles
+//! up to date without fetching their metadata again.
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+
+use crate::batch;
+use crate::code;
+use crate::config;
+use crate::hooks;
+use crate::magnet;
+use crate::scraper;
+use crate::seeders;
+use crate::store;
+use crate::types::{AvDetail, MagnetInfo};
+use crate::util;
+
+const DETAILS: &str = "details";
+/// Least recently fetched titles beyond this are dropped
+const MAX_ENTRIES: usize = 2000;
+/// `refresh` saves after this many codes, so an interrupted `refresh --all` loses at most
+/// one chunk
+const REFRESH_CHUNK: usize = 50;
+
+/// Serializes the load-modify-save of concurrent lookups
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cached {
+    /// Unix seconds of the full fetch
+    pub fetched_at: u64,
+    /// Unix seconds of the last `av refresh`, if any
+    #[serde(default)]
+    pub magnets_at: Option<u64>,
+    pub detail: AvDetail,
+}
+
+fn load() -> Result<BTreeMap<String, Cached>> {
+    store::load(DETAILS)
+}
+
+/// The cached detail of `code` while it is younger than `[cache] ttl_hours`
+pub fn get(code: &str) -> Option<AvDetail> {
+    let ttl = config::get().cache.ttl_hours;
+    if ttl == 0 {
+        return None;
+    }
+    let code = code::normalize(code);
+    let _guard = LOCK.lock().unwrap();
+    let c = load().ok()?.remove(&code)?;
+    let age = util::now_secs().saturating_sub(c.fetched_at);
+    if age > ttl * 3600 {
+        return None;
+    }
+    util::debug(format!("cache: {} ({} min old)", code, age / 60));
+    Some(c.detail)
+}
+
+/// Remember a freshly merged detail. Failures only warn: the cache must never break a lookup.
+pub fn put(d: &AvDetail) {
+    if util::is_dry_run() {
+        return;
+    }
+    let _guard = LOCK.lock().unwrap();
+    let mut all = match load() {
+        Ok(all) => all,
+        Err(e) => {
+            eprintln!("[WARN] 读取详情缓存失败: {:#}", e);
+            return;
+        }
+    };
+    all.insert(code::normalize(&d.code), Cached { fetched_at: util::now_secs(), magnets_at: None, detail: d.clone() });
+    while all.len() > MAX_ENTRIES {
+        let Some(oldest) = all.iter().min_by_key(|(_, c)| c.fetched_at).map(|(k, _)| k.clone()) else { break };
+        all.remove(&oldest);
+    }
+    if let Err(e) = store::save(DETAILS, &all) {
+        eprintln!("[WARN] 保存详情缓存失败: {:#}", e);
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Refreshed {
+    code: String,
+    magnets: usize,
+    /// Magnets not in the cache before
+    added: usize,
+    /// Known magnets whose seeders, leechers, downloads or size changed
+    updated: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Fold fresh magnets into a cached detail: known ones get the new counters and size,
+/// new ones go through `transform_magnet` like any fetched magnet and are appended.
+/// Returns (added, updated).
+async fn merge(d: &mut AvDetail, magnets: Vec<String>, infos: Vec<MagnetInfo>) -> (usize, usize) {
+    let mut updated = 0;
+    let mut fresh = Vec::new();
+    for f in infos {
+        match d.magnet_infos.iter_mut().find(|m| magnet::same(m, &f)) {
+            Some(m) => {
+                if (m.seeders, m.leechers, m.downloads, &m.size) != (f.seeders, f.leechers, f.downloads, &f.size) {
+                    updated += 1;
+                }
+                m.seeders = f.seeders;
+                m.leechers = f.leechers;
+                m.downloads = f.downloads;
+                m.size = f.size.or(m.size.take());
+                m.date = f.date.or(m.date.take());
+            }
+            None => fresh.push(f),
+        }
+    }
+    let known: Vec<Option<String>> = d.magnet_infos.iter().map(|m| m.infohash.clone()).collect();
+    let fresh_urls: Vec<String> = magnets
+        .into_iter()
+        .filter(|u| !d.magnets.contains(u))
+        .filter(|u| magnet::info(u).is_none_or(|i| !known.contains(&i.infohash)))
+        .collect();
+    let added = fresh.len().max(fresh_urls.len());
+    if added > 0 {
+        let mut new = d.clone();
+        new.magnets = fresh_urls;
+        new.magnet_infos = fresh;
+        hooks::transform_magnets(&mut new).await;
+        d.magnets.extend(new.magnets);
+        d.magnet_infos.extend(new.magnet_infos);
+    }
+    magnet::score_all(d, &config::get().magnets.score);
+    (added, updated)
+}
+
+/// `av refresh CODE... | --all`: re-scrape the magnets of cached titles
+pub async fn refresh(codes: &[String], all: bool, json: bool) -> Result<()> {
+    let cached = {
+        let _guard = LOCK.lock().unwrap();
+        load()?
+    };
+    let targets: Vec<String> = if all {
+        cached.keys().cloned().collect()
+    } else {
+        let mut out = Vec::new();
+        for c in codes.iter().map(|c| code::normalize(c)) {
+            if cached.contains_key(&c) {
+                out.push(c);
+            } else {
+                eprintln!("[WARN] {} 不在详情缓存中，请先用 av detail 获取", c);
+            }
+        }
+        out
+    };
+    if targets.is_empty() {
+        if json {
+            util::print_output(&Vec::<Refreshed>::new(), true);
+        } else {
+            println!("{}", "没有需要刷新的缓存番号".yellow());
+        }
+        return Ok(());
+    }
+    let entries = targets.into_iter().map(|c| batch::Entry::new(c, None)).collect();
+    let mut job = batch::Job::start(batch::Kind::Refresh, entries, false)?;
+    refresh_job(&mut job, json).await
+}
+
+/// Refresh the codes `job` hasn't done yet, a chunk at a time
+pub async fn refresh_job(job: &mut batch::Job, json: bool) -> Result<()> {
+    let mut report = Vec::new();
+    let result = refresh_pending(job, &mut report).await;
+    job.hint(result)?;
+    if json {
+        util::print_output(&report, true);
+        return Ok(());
+    }
+    for r in &report {
+        match &r.error {
+            Some(e) => println!("{} {}", r.code.bold(), format!("刷新失败: {}", e).red()),
+            None => println!(
+                "{} {} 个磁力，新增 {}，更新 {}",
+                r.code.bold(),
+                r.magnets,
+                r.added.to_string().green(),
+                r.updated.to_string().cyan()
+            ),
+        }
+    }
+    Ok(())
+}
+
+async fn refresh_pending(job: &mut batch::Job, report: &mut Vec<Refreshed>) -> Result<()> {
+    for chunk in job.pending().chunks(REFRESH_CHUNK) {
+        crate::cancel::check()?;
+        report.extend(refresh_chunk(chunk.iter().map(|e| e.code.clone()).collect()).await?);
+        job.done(chunk)?;
+    }
+    Ok(())
+}
+
+/// Seconds `av prefetch` waits between two lookups by default
+pub const PREFETCH_DELAY_SECS: u64 = 5;
+/// Lookups in a row that may fail before `av prefetch` stops, taking it for a block
+const PREFETCH_MAX_FAILURES: usize = 3;
+
+#[derive(Debug, Default, Serialize)]
+struct Prefetched {
+    /// Looked up and cached by this run
+    fetched: Vec<String>,
+    /// Skipped: their cache entry is still fresh
+    cached: Vec<String>,
+    failed: Vec<Refreshed>,
+}
+
+/// `av prefetch`: look up the newest `top` titles, `actors`' titles and `codes` ahead of
+/// time, one at a time with `delay` seconds between lookups, so that queries within
+/// `[cache] ttl_hours` are served from the cache. Titles cached recently enough are left
+/// alone. Recorded as a batch job, so an interrupted run carries on with `av resume`.
+pub async fn prefetch(codes: &[String], top: Option<usize>, actors: &[String], delay: u64, json: bool) -> Result<()> {
+    if config::get().cache.ttl_hours == 0 {
+        bail!(crate::errors::Tagged::new("config", "详情缓存未启用：先在配置中设置 [cache] ttl_hours，预取的详情才会被使用"));
+    }
+    let mut wanted: Vec<String> = codes.iter().map(|c| code::normalize(c)).collect();
+    if let Some(n) = top {
+        wanted.extend(scraper::top(n).await?.into_iter().map(|i| code::normalize(&i.code)));
+    }
+    for (actor, found) in scraper::list_actors_titles(actors).await {
+        match found {
+            Ok(items) => wanted.extend(items.into_iter().map(|i| code::normalize(&i.code))),
+            Err(e) => eprintln!("[WARN] {} 的作品列表获取失败: {:#}", actor, e),
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    wanted.retain(|c| seen.insert(c.clone()));
+
+    let (cached, pending): (Vec<String>, Vec<String>) = wanted.into_iter().partition(|c| get(c).is_some());
+    let mut report = Prefetched { cached, ..Prefetched::default() };
+    if pending.is_empty() {
+        return print_prefetched(&report, json);
+    }
+    if util::dry_run_skip(format!("将预取 {} 个番号的详情：{}", pending.len(), pending.join(", "))) {
+        return Ok(());
+    }
+    let entries = pending.into_iter().map(|c| batch::Entry::new(c, None)).collect();
+    let mut job = batch::Job::start(batch::Kind::Prefetch, entries, true)?;
+    let result = prefetch_pending(&mut job, delay, &mut report).await;
+    print_prefetched(&report, json)?;
+    job.hint(result)
+}
+
+/// `av resume` of a prefetch job, at the default pace
+pub async fn prefetch_job(job: &mut batch::Job, json: bool) -> Result<()> {
+    let mut report = Prefetched::default();
+    let result = prefetch_pending(job, PREFETCH_DELAY_SECS, &mut report).await;
+    print_prefetched(&report, json)?;
+    job.hint(result)
+}
+
+async fn prefetch_pending(job: &mut batch::Job, delay: u64, report: &mut Prefetched) -> Result<()> {
+    let pending = job.pending();
+    let total = pending.len();
+    let mut failures = 0;
+    for (i, entry) in pending.into_iter().enumerate() {
+        crate::cancel::check()?;
+        if i > 0 && delay > 0 {
+            let token = crate::cancel::token();
+            tokio::select! {
+                _ = tokio::time::sleep(std::time::Duration::from_secs(delay)) => {}
+                _ = token.cancelled() => return Err(crate::cancel::error()),
+            }
+        }
+        util::note(format!("[{}/{}] {}", i + 1, total, entry.code));
+        match scraper::fetch_detail(&entry.code).await {
+            Ok(_) => {
+                failures = 0;
+                report.fetched.push(entry.code.clone());
+                job.done(std::slice::from_ref(&entry))?;
+            }
+            Err(e) if !crate::cancel::is_cancelled() => {
+                eprintln!("[WARN] {} 获取失败: {:#}", entry.code, e);
+                report.failed.push(Refreshed { code: entry.code.clone(), magnets: 0, added: 0, updated: 0, error: Some(format!("{:#}", e)) });
+                failures += 1;
+                if failures >= PREFETCH_MAX_FAILURES {
+                    bail!("连续 {} 个番号获取失败，可能已被限流，稍后再继续", failures);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn print_prefetched(report: &Prefetched, json: bool) -> Result<()> {
+    if json {
+        util::print_output(report, true);
+        return Ok(());
+    }
+    println!(
+        "已预取 {}，缓存中已有 {}，失败 {}",
+        report.fetched.len().to_string().green(),
+        report.cached.len(),
+        report.failed.len().to_string().red()
+    );
+    Ok(())
+}
+
+/// Fetch `targets`' magnets together and save them into the cache
+async fn refresh_chunk(targets: Vec<String>) -> Result<Vec<Refreshed>> {
+    let cached = {
+        let _guard = LOCK.lock().unwrap();
+        load()?
+    };
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, code) in targets.into_iter().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let res = scraper::fetch_magnets(&code).await;
+            (idx, code, res)
+        });
+    }
+    let mut fetched = tasks.join_all().await;
+    fetched.sort_by_key(|(idx, _, _)| *idx);
+
+    let mut report = Vec::new();
+    let mut refreshed: Vec<(String, Cached)> = Vec::new();
+    for (_, code, res) in fetched {
+        let Some(mut entry) = cached.get(&code).cloned() else { continue };
+        match res {
+            Ok((magnets, infos)) => {
+                seeders::record(&infos);
+                let (added, updated) = merge(&mut entry.detail, magnets, infos).await;
+                entry.magnets_at = Some(util::now_secs());
+                report.push(Refreshed { code: code.clone(), magnets: entry.detail.magnet_infos.len(), added, updated, error: None });
+                refreshed.push((code, entry));
+            }
+            Err(e) => report.push(Refreshed { code, magnets: 0, added: 0, updated: 0, error: Some(format!("{:#}", e)) }),
+        }
+    }
+    if !util::dry_run_skip(format!("将更新 {} 个番号的缓存磁力", refreshed.len())) {
+        // Re-read under the lock so details cached meanwhile are kept
+        let _guard = LOCK.lock().unwrap();
+        let mut latest = load()?;
+        latest.extend(refreshed);
+        store::save(DETAILS, &latest)?;
+    }
+    Ok(report)
+}
+
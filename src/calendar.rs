@@ -0,0 +1,134 @@
+//! Release calendar of the subscribed actors, studios and series: `av calendar` lists
+//! their titles with a release date from `--past` days ago on (JavDB lists announced
+//! titles ahead of their date), and `--ics FILE` writes them as all-day iCalendar events
+//! for Google Calendar, Thunderbird and the like.
+
+use anyhow::{Context, Result};
+use chrono::{Duration, Local, NaiveDate, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::dates;
+use crate::scraper;
+use crate::subscribe;
+use crate::util;
+
+#[derive(Debug, Clone, Serialize)]
+struct Release {
+    code: String,
+    title: String,
+    release_date: String,
+    /// The subscriptions that list it: `演员 NAME`, `片商 NAME`
+    sources: Vec<String>,
+}
+
+/// The subscriptions' titles released on or after `from`, by date
+async fn collect(from: NaiveDate, uncen: bool) -> Result<Vec<Release>> {
+    let mut out: Vec<Release> = Vec::new();
+    for (source, items) in subscribe::listings().await? {
+        for i in items {
+            let Some(date) = i.release_date.as_deref().and_then(dates::parse) else { continue };
+            if date < from || (uncen && !util::looks_uncensored(&i.title)) {
+                continue;
+            }
+            // A title listed under its actor and its studio is one event with both sources
+            match out.iter_mut().find(|r| r.code == i.code) {
+                Some(r) if !r.sources.contains(&source) => r.sources.push(source.clone()),
+                Some(_) => {}
+                None => out.push(Release {
+                    title: i.title.trim().strip_prefix(i.code.as_str()).unwrap_or(&i.title).trim().to_string(),
+                    code: i.code,
+                    release_date: date.format("%Y-%m-%d").to_string(),
+                    sources: vec![source.clone()],
+                }),
+            }
+        }
+    }
+    out.sort_by(|a, b| a.release_date.cmp(&b.release_date).then_with(|| a.code.cmp(&b.code)));
+    Ok(out)
+}
+
+/// `av calendar [--ics FILE]`
+pub async fn run(ics: Option<&Path>, past: u32, uncen: bool, json: bool) -> Result<()> {
+    let today = Local::now().date_naive();
+    let releases = collect(today - Duration::days(past.into()), uncen).await?;
+    if let Some(path) = ics {
+        let body = render_ics(&releases);
+        if !util::dry_run_skip(format!("将写入 {}（{} 个日程）", path.display(), releases.len())) {
+            std::fs::write(path, body).with_context(|| format!("写入日历文件失败: {}", path.display()))?;
+            eprintln!("{} {}（{} 个日程）", "已生成日历:".green().bold(), path.display(), releases.len());
+        }
+        return Ok(());
+    }
+    if json {
+        util::print_output(&releases, true);
+        return Ok(());
+    }
+    if releases.is_empty() {
+        println!("{}", "订阅中没有即将发售或近期发售的作品".yellow());
+        return Ok(());
+    }
+    let today = today.format("%Y-%m-%d").to_string();
+    for r in &releases {
+        let date = dates::render(&r.release_date);
+        let date = if r.release_date >= today { date.green().bold() } else { date.dimmed() };
+        println!("{}  {}  {}  {}", date, r.code.bold(), r.title, format!("[{}]", r.sources.join("，")).dimmed());
+    }
+    Ok(())
+}
+
+/// Text escaped for an iCalendar property value (RFC 5545 §3.3.11)
+fn ics_text(s: &str) -> String {
+    s.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+/// `line` folded at 75 octets without splitting a UTF-8 character, CRLF-terminated
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+fn render_ics(releases: &[Release]) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//av//calendar {}//ZH", env!("CARGO_PKG_VERSION")),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        "X-WR-CALNAME:av 发售日历".to_string(),
+    ];
+    for r in releases {
+        let Some(date) = dates::parse(&r.release_date) else { continue };
+        let mut description = format!("订阅: {}", r.sources.join(", "));
+        if !r.title.is_empty() {
+            description = format!("{}\n{}", r.title, description);
+        }
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            // Stable per code, so importing a newer export updates the events
+            format!("UID:{}@av", r.code.to_lowercase()),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", date.format("%Y%m%d")),
+            format!("DTEND;VALUE=DATE:{}", (date + Duration::days(1)).format("%Y%m%d")),
+            format!("SUMMARY:{}", ics_text(format!("{} {}", r.code, r.title).trim())),
+            format!("DESCRIPTION:{}", ics_text(&description)),
+            format!("URL:{}", scraper::javdb_search_url(&r.code)),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|l| fold(l)).collect()
+}
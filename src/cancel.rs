@@ -0,0 +1,47 @@
+use anyhow::{anyhow, Result};
+use std::sync::{LazyLock, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Process-wide token, cancelled by the first Ctrl-C. Fetches stop at the next request,
+/// batch operations keep what they already have and save their state before exiting.
+/// `av shell` swaps in a fresh one after every command.
+static TOKEN: LazyLock<Mutex<CancellationToken>> = LazyLock::new(|| Mutex::new(CancellationToken::new()));
+
+pub fn token() -> CancellationToken {
+    TOKEN.lock().unwrap().clone()
+}
+
+pub fn is_cancelled() -> bool {
+    token().is_cancelled()
+}
+
+/// Start over after a cancelled command, so Ctrl-C only stops the command it interrupted
+pub fn reset() {
+    *TOKEN.lock().unwrap() = CancellationToken::new();
+}
+
+pub fn error() -> anyhow::Error {
+    anyhow!("操作已取消")
+}
+
+/// Bail out early once cancelled
+pub fn check() -> Result<()> {
+    if is_cancelled() {
+        return Err(error());
+    }
+    Ok(())
+}
+
+/// Listen for Ctrl-C: the first one cancels gracefully, a second one before `reset`
+/// exits immediately
+pub fn install() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if is_cancelled() {
+                std::process::exit(130);
+            }
+            eprintln!("\n[WARN] 正在取消，保存已获取的结果…（再次按 Ctrl-C 立即退出）");
+            token().cancel();
+        }
+    });
+}
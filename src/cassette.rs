@@ -0,0 +1,176 @@
+//! Record/replay of scraper traffic ("cassettes"), so parser changes can be checked
+//! against real JavDB/Sukebei/JavLibrary pages without hitting the sites.
+//!
+//! `AV_CASSETTE=record` saves every response under `AV_CASSETTE_DIR` (default
+//! `tests/cassettes`), `AV_CASSETTE=replay` serves them back and fails on anything that
+//! was never recorded. Interactions are keyed by source and path, so a recording made
+//! through one JavDB mirror replays on any other.
+
+use anyhow::{bail, Context, Result};
+use reqwest::ResponseBuilderExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{LazyLock, RwLock};
+
+use crate::metrics;
+use crate::util;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Off,
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Clone)]
+struct Settings {
+    mode: Mode,
+    dir: PathBuf,
+}
+
+static SETTINGS: LazyLock<RwLock<Settings>> = LazyLock::new(|| {
+    let mode = match std::env::var("AV_CASSETTE").unwrap_or_default().to_lowercase().as_str() {
+        "record" => Mode::Record,
+        "replay" => Mode::Replay,
+        _ => Mode::Off,
+    };
+    let dir = std::env::var("AV_CASSETTE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("tests/cassettes"));
+    RwLock::new(Settings { mode, dir })
+});
+
+fn settings() -> Settings {
+    SETTINGS.read().unwrap_or_else(|p| p.into_inner()).clone()
+}
+
+/// Switch to replaying from `dir` (tests set this instead of the environment)
+#[cfg(test)]
+pub fn replay_from(dir: impl Into<PathBuf>) {
+    *SETTINGS.write().unwrap_or_else(|p| p.into_inner()) = Settings { mode: Mode::Replay, dir: dir.into() };
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Interaction {
+    url: String,
+    status: u16,
+    content_type: Option<String>,
+    body: String,
+}
+
+/// `<dir>/<source>/<sha256(path?query)[..16]>.json`
+fn cassette_path(dir: &std::path::Path, url: &str) -> Result<PathBuf> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("URL 无效: {}", url))?;
+    let mut key = parsed.path().to_string();
+    if let Some(q) = parsed.query() {
+        key.push('?');
+        key.push_str(q);
+    }
+    let digest = hex::encode(Sha256::digest(key.as_bytes()));
+    Ok(dir.join(metrics::source_of(url)).join(format!("{}.json", &digest[..16])))
+}
+
+fn to_response(url: &str, i: Interaction) -> Result<reqwest::Response> {
+    let mut builder = http::Response::builder().status(i.status);
+    if let Ok(u) = reqwest::Url::parse(url) {
+        builder = builder.url(u);
+    }
+    if let Some(ct) = i.content_type {
+        builder = builder.header(reqwest::header::CONTENT_TYPE, ct);
+    }
+    Ok(reqwest::Response::from(builder.body(i.body).context("构造响应失败")?))
+}
+
+/// In replay mode, the recorded response for `url`; `None` when not replaying
+pub fn replay(url: &str) -> Result<Option<reqwest::Response>> {
+    let s = settings();
+    if s.mode != Mode::Replay {
+        return Ok(None);
+    }
+    let path = cassette_path(&s.dir, url)?;
+    if !path.exists() {
+        bail!("cassette 中没有该请求: {}（{}）", url, path.display());
+    }
+    util::debug(format!("cassette: replay {} from {}", url, path.display()));
+    let raw = std::fs::read_to_string(&path).with_context(|| format!("读取失败: {}", path.display()))?;
+    let interaction: Interaction = serde_json::from_str(&raw).with_context(|| format!("cassette 格式错误: {}", path.display()))?;
+    to_response(url, interaction).map(Some)
+}
+
+/// In record mode, save `resp` for `url` and hand back an equivalent response
+pub async fn record(url: &str, resp: reqwest::Response) -> Result<reqwest::Response> {
+    let s = settings();
+    if s.mode != Mode::Record {
+        return Ok(resp);
+    }
+    let status = resp.status().as_u16();
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let final_url = resp.url().to_string();
+    let body = resp.text().await.context("读取响应失败")?;
+    let interaction = Interaction { url: url.to_string(), status, content_type, body };
+    let path = cassette_path(&s.dir, url)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("创建目录失败: {}", parent.display()))?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&interaction)?)
+        .with_context(|| format!("写入失败: {}", path.display()))?;
+    util::debug(format!("cassette: recorded {} to {}", url, path.display()));
+    to_response(&final_url, interaction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraper;
+
+    fn use_fixtures() {
+        replay_from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cassettes"));
+    }
+
+    #[tokio::test]
+    async fn javdb_detail_merges_javlibrary() {
+        use_fixtures();
+        let d = scraper::fetch_detail("abc-123").await.unwrap();
+        assert_eq!(d.code, "ABC-123");
+        assert!(d.title.contains("ABC-123"));
+        assert_eq!(d.release_date.as_deref(), Some("2024-05-17"));
+        assert_eq!(d.duration_minutes, Some(120));
+        assert_eq!(d.actor_names, vec!["Sample Actress".to_string()]);
+        assert_eq!(d.studio.as_deref(), Some("Sample Studio"));
+        assert!(d.genres.contains(&"Drama".to_string()));
+        assert_eq!(d.magnets.len(), 1);
+        assert!(d.magnets[0].starts_with("magnet:?xt=urn:btih:"));
+        // Only on JavLibrary
+        assert_eq!(d.label.as_deref(), Some("Sample Label"));
+    }
+
+    #[tokio::test]
+    async fn sukebei_fallback_when_javdb_has_no_result() {
+        use_fixtures();
+        let d = scraper::fetch_detail("xyz-001").await.unwrap();
+        assert_eq!(d.code, "XYZ-001");
+        assert!(d.title.contains("XYZ-001"));
+        assert_eq!(d.magnets, vec!["magnet:?xt=urn:btih:89abcdef0123456789abcdef0123456789abcdef&dn=XYZ-001".to_string()]);
+        assert_eq!(d.magnet_infos.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn javdb_latest_listing() {
+        use_fixtures();
+        let items = scraper::top(2).await.unwrap();
+        let codes: Vec<&str> = items.iter().map(|i| i.code.as_str()).collect();
+        assert_eq!(codes, vec!["ABC-123", "DEF-456"]);
+    }
+
+    #[tokio::test]
+    async fn unrecorded_request_fails() {
+        use_fixtures();
+        let err = scraper::top(50).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("cassette"));
+    }
+}
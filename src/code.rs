@@ -0,0 +1,389 @@
+use regex::Regex;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::LazyLock;
+
+/// A release identifier in canonical form. Everything that keys on a code (search,
+/// detail lookups, the library, caches) goes through `normalize` so `abp00123`,
+/// `ABP-123` and `[FHD] ABP-123-C.mp4` all end up as `ABP-123`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Code {
+    /// Studio prefix and number: `ABP-123`, `259LUXU-1234`, `T28-633`
+    Standard { prefix: String, number: String },
+    /// `FC2-PPV-1234567`
+    Fc2(String),
+    /// `HEYZO-1234`
+    Heyzo(String),
+    /// Tokyo-Hot `N1234` / `K1234`, also written `n-1234` or `Tokyo-Hot n1234`
+    TokyoHot(String),
+    /// Date-based uncensored ids (`010124_001` 1Pondo, `010124-001` Caribbeancom); the
+    /// separator is part of the id on those sites, so it is kept as given
+    Dated(String),
+    /// A western studio scene as releases are named, `Brazzers.23.05.12.Jane.Doe`: the
+    /// site, the release date (`YY.MM.DD`) and up to two words of the first performer's
+    /// name, each word capitalized. Only recognized in western mode (see `set_western`).
+    Scene { site: String, date: String, performer: String },
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Code::Standard { prefix, number } => write!(f, "{}-{}", prefix, number),
+            Code::Fc2(id) => write!(f, "FC2-PPV-{}", id),
+            Code::Heyzo(id) => write!(f, "HEYZO-{}", id),
+            Code::TokyoHot(id) | Code::Dated(id) => f.write_str(id),
+            Code::Scene { site, date, performer } if performer.is_empty() => write!(f, "{}.{}", site, date),
+            Code::Scene { site, date, performer } => write!(f, "{}.{}.{}", site, date, performer),
+        }
+    }
+}
+
+impl Code {
+    /// The canonical scene code for `site`, a release date and the performer named after
+    /// it, however they are cased or separated
+    pub fn scene(site: &str, date: chrono::NaiveDate, performer: &str) -> Code {
+        let words: Vec<String> = performer
+            .split(|c: char| !c.is_alphanumeric())
+            .take_while(|w| !is_release_tag(w))
+            .filter(|w| !w.is_empty())
+            .take(2)
+            .map(capitalized)
+            .collect();
+        let site: String = site.split(|c: char| !c.is_alphanumeric()).collect();
+        Code::Scene { site: capitalized(&site), date: date.format("%y.%m.%d").to_string(), performer: words.join(".") }
+    }
+}
+
+static WESTERN: AtomicBool = AtomicBool::new(false);
+
+/// Western mode: scene names (`Site.YY.MM.DD.Performer`) count as codes too, for
+/// `--western` / `[metadata] western`
+pub fn set_western(on: bool) {
+    WESTERN.store(on, Ordering::Relaxed);
+}
+
+pub fn western() -> bool {
+    WESTERN.load(Ordering::Relaxed)
+}
+
+fn capitalized(word: &str) -> String {
+    let lower = word.to_lowercase();
+    let mut chars = lower.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// The words scene release names carry after the performers and title: `XXX`, `1080p`,
+/// `x265`, `WEBRIP` and the like
+fn is_release_tag(word: &str) -> bool {
+    const TAGS: &[&str] = &["XXX", "WEB", "WEBRIP", "WEBDL", "HEVC", "AVC", "SD", "HD", "FHD", "UHD", "MP4", "MKV", "PROPER", "REPACK"];
+    word.chars().any(|c| c.is_ascii_digit()) || TAGS.contains(&word.to_uppercase().as_str())
+}
+
+/// Amateur (MGS) labels whose number prefix is part of the code itself. Elsewhere a
+/// leading number is DMM's label id (`118abp00123`) and is dropped.
+const MGS_PREFIXES: &[&str] = &[
+    "200GANA", "230ORE", "230OREC", "259LUXU", "261ARA", "277DCV", "300MAAN", "300MIUM", "300NTK",
+    "326EVA", "326SCP", "336KNB", "348NTR", "390JAC", "435MFC", "428SUKE", "483SGK", "390JNT",
+];
+
+static FC2: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"FC2[-_ ]?(?:PPV[-_ ]?)?(\d{5,8})").unwrap());
+static HEYZO: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"HEYZO[-_ ]?(?:HD[-_ ]?)?(\d{4})").unwrap());
+static DATED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d{6})([-_])(\d{2,3})$").unwrap());
+static TOKYO_HOT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:TOKYO[-_ ]?HOT[-_ ]*)?([NK])[-_ ]?(\d{4})$").unwrap());
+/// `SITE.YY.MM.DD[.PERFORMER...]`, usually dotted but spaces, dashes and underscores too;
+/// a site of up to three words (`Brazzers Exxtra`) is joined into one
+static SCENE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([A-Z][A-Z0-9]{2,}(?:[._ ][A-Z][A-Z0-9]*){0,2}?)[._ -](\d{2})[._ -](\d{2})[._ -](\d{2})(?:[._ -]+(.*))?$").unwrap());
+/// `ABP-123`, `259LUXU-1234`, `T28-633`, with an optional subtitle/quality suffix
+static DASHED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:H_)?(\d{0,4})([A-Z]{1,8}\d{0,2})[-_ ](\d{2,6})(?:[-_ ]?(?:C|CH|UC|U|R|HD|FHD|4K))?$").unwrap()
+});
+/// DMM content ids and bare codes: `ABP00123`, `118ABP00123`, `H_1234ABC00123`, `ssis123`
+static UNDASHED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:H_)?(\d{0,4})([A-Z]{2,8})(\d{2,6})(?:C|CH|UC|U)?$").unwrap());
+
+/// Pieces used when looking for a code inside free text (titles, file names)
+static FIND_DASHED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|[^A-Z0-9_])(\d{0,4})([A-Z]{1,8}\d{0,2})-(\d{2,6})(?:[^0-9]|$)").unwrap()
+});
+static FIND_DATED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^0-9])(\d{6}[-_]\d{2,3})(?:[^0-9]|$)").unwrap());
+static FIND_TOKYO_HOT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^A-Z0-9])([NK])(\d{4})(?:[^A-Z0-9]|$)").unwrap());
+/// `Tokyo-Hot n-1234`: with the studio named, a dashed id is Tokyo-Hot's and not a
+/// one-letter standard code
+static FIND_TOKYO_HOT_NAMED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"TOKYO[-_ ]?HOT[^A-Z0-9]*([NK])[-_ ]?(\d{4})(?:[^0-9]|$)").unwrap());
+static FIND_BARE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^A-Z0-9])([A-Z]{2,8})_?(\d{3,5})(?:[^A-Z0-9]|$)").unwrap());
+
+/// Strip DMM's zero padding down to three digits (`00123` -> `123`, `01234` -> `1234`)
+fn pad_number(digits: &str) -> String {
+    let mut n = digits;
+    while n.len() > 3 && n.starts_with('0') {
+        n = &n[1..];
+    }
+    n.to_string()
+}
+
+/// `label_id` is the number in front of the letters, e.g. `259` in `259LUXU` or `118` in
+/// DMM's `118abp00123`
+fn standard(label_id: &str, letters: &str, number: &str) -> Code {
+    let full = format!("{}{}", label_id, letters);
+    let prefix = if MGS_PREFIXES.contains(&full.as_str()) { full } else { letters.to_string() };
+    Code::Standard { prefix, number: pad_number(number) }
+}
+
+fn whole(re: &Regex, s: &str) -> Option<String> {
+    re.captures(s)
+        .filter(|c| c.get(0).is_some_and(|m| m.start() == 0 && m.end() == s.len()))
+        .map(|c| c[1].to_string())
+}
+
+/// A scene name or a file name starting with one, whatever the mode
+fn scene(s: &str) -> Option<Code> {
+    let c = SCENE.captures(s)?;
+    let date = chrono::NaiveDate::parse_from_str(&format!("20{}-{}-{}", &c[2], &c[3], &c[4]), "%Y-%m-%d").ok()?;
+    Some(Code::scene(&c[1], date, c.get(5).map_or("", |m| m.as_str())))
+}
+
+/// Parse a string that is (only) a code, tolerating case, separators and DMM padding
+pub fn parse(input: &str) -> Option<Code> {
+    let s = input.trim().to_uppercase();
+    if s.is_empty() {
+        return None;
+    }
+    if western() {
+        if let Some(c) = scene(&s) {
+            return Some(c);
+        }
+    }
+    if let Some(id) = whole(&FC2, &s) {
+        return Some(Code::Fc2(id));
+    }
+    if let Some(id) = whole(&HEYZO, &s) {
+        return Some(Code::Heyzo(id));
+    }
+    if let Some(c) = DATED.captures(&s) {
+        return Some(Code::Dated(format!("{}{}{}", &c[1], &c[2], &c[3])));
+    }
+    if let Some(c) = TOKYO_HOT.captures(&s) {
+        return Some(Code::TokyoHot(format!("{}{}", &c[1], &c[2])));
+    }
+    DASHED
+        .captures(&s)
+        .or_else(|| UNDASHED.captures(&s))
+        .map(|c| standard(&c[1], &c[2], &c[3]))
+}
+
+/// First code mentioned in free text, e.g. `[FHD] ABP-123-C 中文字幕`
+pub fn find(text: &str) -> Option<Code> {
+    let s = text.to_uppercase();
+    // A scene name leads the file name, and its quality tags would pass for bare codes
+    if western() {
+        if let Some(c) = scene(s.trim()) {
+            return Some(c);
+        }
+    }
+    if let Some(c) = FC2.captures(&s) {
+        return Some(Code::Fc2(c[1].to_string()));
+    }
+    if let Some(c) = HEYZO.captures(&s) {
+        return Some(Code::Heyzo(c[1].to_string()));
+    }
+    if let Some(c) = FIND_TOKYO_HOT_NAMED.captures(&s) {
+        return Some(Code::TokyoHot(format!("{}{}", &c[1], &c[2])));
+    }
+    if let Some(c) = FIND_DASHED.captures(&s) {
+        return Some(standard(&c[1], &c[2], &c[3]));
+    }
+    if let Some(c) = FIND_DATED.captures(&s) {
+        return Some(Code::Dated(c[1].to_string()));
+    }
+    if let Some(c) = FIND_TOKYO_HOT.captures(&s) {
+        return Some(Code::TokyoHot(format!("{}{}", &c[1], &c[2])));
+    }
+    FIND_BARE.captures(&s).map(|c| standard("", &c[1], &c[2]))
+}
+
+/// Whether a torrent name is about `code`: the first code it mentions is that one. For
+/// indexes that match names loosely (`ABP-12` also finds `ABP-123`).
+pub fn is_named(name: &str, code: &str) -> bool {
+    find(name).is_some_and(|c| same_release(&c.to_string(), code))
+}
+
+/// Whether two codes name the same release. Scenes are told apart by site and date alone,
+/// since file names list the performers (or the title) differently from the databases.
+pub fn same_release(a: &str, b: &str) -> bool {
+    match (scene(&a.trim().to_uppercase()), scene(&b.trim().to_uppercase())) {
+        (Some(Code::Scene { site: s1, date: d1, .. }), Some(Code::Scene { site: s2, date: d2, .. })) => s1 == s2 && d1 == d2,
+        _ => normalize(a) == normalize(b),
+    }
+}
+
+/// Whether `input` is a scene name in western mode, for the western-capable sources
+pub fn is_scene(input: &str) -> bool {
+    matches!(parse(input), Some(Code::Scene { .. }))
+}
+
+/// Canonical form of `input`, or the trimmed, upper-cased input when it is not a code
+pub fn normalize(input: &str) -> String {
+    parse(input).map(|c| c.to_string()).unwrap_or_else(|| input.trim().to_uppercase())
+}
+
+static PART_MARKER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|[^A-Z0-9])(?:CD|DISC|DISK|PART|PT)[-_ ]?(\d{1,2})(?:[^0-9]|$)").unwrap()
+});
+/// `ABP-123-A` / `ABP-123B`; `-C` is left alone since it usually means Chinese subtitles
+static PART_LETTER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d[-_]?([AB])$").unwrap());
+/// `FC2-PPV-1234567-2`
+static PART_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d{2}[-_]([1-9])$").unwrap());
+
+/// 1-based part index of a multi-part release (`CD2`, `part2`, `-B`, `-2`), from a magnet
+/// name or a file stem without its extension
+pub fn part(name: &str) -> Option<u32> {
+    let s = name.trim().to_uppercase();
+    if let Some(c) = PART_MARKER.captures(&s) {
+        return c[1].parse().ok().filter(|n| *n > 0);
+    }
+    if let Some(c) = PART_LETTER.captures(&s) {
+        return Some(if &c[1] == "A" { 1 } else { 2 });
+    }
+    PART_NUMBER.captures(&s).and_then(|c| c[1].parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_variants() {
+        let cases: &[(&str, Option<&str>)] = &[
+            ("ABP-123", Some("ABP-123")),
+            ("abp-123", Some("ABP-123")),
+            ("abp_123", Some("ABP-123")),
+            ("ABP 123", Some("ABP-123")),
+            ("ABP00123", Some("ABP-123")),
+            ("abp00123", Some("ABP-123")),
+            ("118abp00123", Some("ABP-123")),
+            ("h_1234abc00123", Some("ABC-123")),
+            ("ssis123", Some("SSIS-123")),
+            ("ABP-01234", Some("ABP-1234")),
+            ("ABP-123-C", Some("ABP-123")),
+            ("ABP-123C", Some("ABP-123")),
+            ("abp123ch", Some("ABP-123")),
+            ("ABP-123-UC", Some("ABP-123")),
+            ("  ABP-123  ", Some("ABP-123")),
+            ("259LUXU-1234", Some("259LUXU-1234")),
+            ("259luxu_1234", Some("259LUXU-1234")),
+            ("259LUXU01234", Some("259LUXU-1234")),
+            ("T28-633", Some("T28-633")),
+            ("t28_633", Some("T28-633")),
+            ("FC2-PPV-1234567", Some("FC2-PPV-1234567")),
+            ("fc2ppv1234567", Some("FC2-PPV-1234567")),
+            ("FC2_PPV_1234567", Some("FC2-PPV-1234567")),
+            ("FC2-1234567", Some("FC2-PPV-1234567")),
+            ("HEYZO-1234", Some("HEYZO-1234")),
+            ("heyzo_hd_1234", Some("HEYZO-1234")),
+            ("heyzo1234", Some("HEYZO-1234")),
+            ("n1234", Some("N1234")),
+            ("N-1234", Some("N1234")),
+            ("Tokyo-Hot k1234", Some("K1234")),
+            ("010124_001", Some("010124_001")),
+            ("010124-001", Some("010124-001")),
+            ("", None),
+            ("   ", None),
+            ("hello", None),
+            ("ABP", None),
+            ("123", None),
+            ("ABP-1", None),
+            ("ABP-1234567", None),
+            ("A-B-C", None),
+            ("FC2-PPV-123", None),
+            ("n12345", None),
+            ("[FHD] ABP-123", None),
+        ];
+        for (input, want) in cases {
+            assert_eq!(parse(input).map(|c| c.to_string()).as_deref(), *want, "parse({:?})", input);
+        }
+    }
+
+    #[test]
+    fn parse_kinds() {
+        assert_eq!(parse("abp00123"), Some(Code::Standard { prefix: "ABP".into(), number: "123".into() }));
+        assert_eq!(parse("118LUXU-1234"), Some(Code::Standard { prefix: "LUXU".into(), number: "1234".into() }));
+        assert_eq!(parse("fc2-ppv-1234567"), Some(Code::Fc2("1234567".into())));
+        assert_eq!(parse("HEYZO-0123"), Some(Code::Heyzo("0123".into())));
+        assert_eq!(parse("k-1234"), Some(Code::TokyoHot("K1234".into())));
+        assert_eq!(parse("010124_001"), Some(Code::Dated("010124_001".into())));
+    }
+
+    #[test]
+    fn find_in_text() {
+        let cases: &[(&str, Option<&str>)] = &[
+            ("[FHD] ABP-123-C 中文字幕", Some("ABP-123")),
+            ("abp-123.mp4", Some("ABP-123")),
+            ("【ABP-123】タイトル", Some("ABP-123")),
+            ("ssis_456 uncensored", Some("SSIS-456")),
+            ("SSIS456.mkv", Some("SSIS-456")),
+            ("259LUXU-1234 素人", Some("259LUXU-1234")),
+            ("T28-633.mp4", Some("T28-633")),
+            ("FC2 PPV 1234567 title", Some("FC2-PPV-1234567")),
+            ("[fc2-ppv-1234567]-2.mp4", Some("FC2-PPV-1234567")),
+            ("heyzo_hd_1234_full.mp4", Some("HEYZO-1234")),
+            ("Tokyo-Hot n-1234 title", Some("N1234")),
+            ("tokyo hot n1234.avi", Some("N1234")),
+            ("n1234.mp4", Some("N1234")),
+            ("Caribbeancom 010124-001 title", Some("010124-001")),
+            ("1pon_010124_001.mp4", Some("010124_001")),
+            ("title ABP-123 then SSIS-456", Some("ABP-123")),
+            ("", None),
+            ("just some words", None),
+            ("1080p x264", None),
+            ("2024-05-01 holiday", None),
+            ("IMG_12.jpg", None),
+        ];
+        for (input, want) in cases {
+            assert_eq!(find(input).map(|c| c.to_string()).as_deref(), *want, "find({:?})", input);
+        }
+    }
+
+    #[test]
+    fn normalize_and_compare() {
+        let cases: &[(&str, &str)] = &[
+            ("abp00123", "ABP-123"),
+            ("ABP_123", "ABP-123"),
+            ("259luxu-01234", "259LUXU-1234"),
+            ("fc2ppv-1234567", "FC2-PPV-1234567"),
+            ("heyzo 1234", "HEYZO-1234"),
+            ("n1234", "N1234"),
+            (" not a code ", "NOT A CODE"),
+            ("", ""),
+        ];
+        for (input, want) in cases {
+            assert_eq!(normalize(input), *want, "normalize({:?})", input);
+        }
+        assert!(same_release("abp00123", "ABP-123"));
+        assert!(!same_release("ABP-123", "ABP-124"));
+        assert!(is_named("[FHD] abp-123-C.mp4", "ABP-123"));
+        assert!(!is_named("ABP-1234 title", "ABP-123"));
+    }
+
+    #[test]
+    fn parts() {
+        let cases: &[(&str, Option<u32>)] = &[
+            ("ABP-123-CD2", Some(2)),
+            ("ABP-123 part1", Some(1)),
+            ("ABP-123-A", Some(1)),
+            ("ABP-123B", Some(2)),
+            ("FC2-PPV-1234567-2", Some(2)),
+            ("ABP-123-C", None),
+            ("ABP-123", None),
+            ("ABP-123-CD0", None),
+        ];
+        for (input, want) in cases {
+            assert_eq!(part(input), *want, "part({:?})", input);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::code;
+use crate::dates;
+use crate::magnet;
+use crate::scraper;
+use crate::types::{AvDetail, MagnetInfo};
+use crate::util;
+
+/// One side of the comparison: a release and the magnet that represents it
+#[derive(Debug, Serialize)]
+struct Side {
+    code: String,
+    title: String,
+    release_date: Option<String>,
+    duration_minutes: Option<u32>,
+    studio: Option<String>,
+    actors: Vec<String>,
+    /// 1-based, numbered as in `av install`
+    magnet_index: Option<usize>,
+    magnet: Option<Specs>,
+}
+
+#[derive(Debug, Serialize)]
+struct Specs {
+    url: String,
+    name: Option<String>,
+    size: Option<String>,
+    size_bytes: Option<u64>,
+    bitrate_mbps: Option<f32>,
+    resolution: Option<&'static str>,
+    codec: Option<String>,
+    seeders: Option<u32>,
+    subtitles: bool,
+    leak: bool,
+    quality_score: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct Comparison {
+    left: Side,
+    right: Side,
+}
+
+/// Magnets in `av install` order (best ranked first)
+fn sorted_magnets(d: &AvDetail) -> Vec<MagnetInfo> {
+    let mut v = d.magnet_infos.clone();
+    v.sort_by(MagnetInfo::ranking);
+    v
+}
+
+fn specs(m: &MagnetInfo, d: &AvDetail) -> Specs {
+    Specs {
+        url: m.url.clone(),
+        name: m.name.clone().or_else(|| m.display_name.clone()),
+        size: m.size.clone(),
+        size_bytes: magnet::size_bytes(m),
+        bitrate_mbps: magnet::bitrate_mbps(m, d.duration_minutes),
+        resolution: magnet::resolution(m),
+        codec: m.codec.clone(),
+        seeders: m.seeders,
+        subtitles: magnet::has_subtitles(m),
+        leak: magnet::is_leak(m),
+        quality_score: m.quality_score,
+    }
+}
+
+/// `index` picks a magnet (1-based); otherwise the highest `quality_score` stands in
+fn side(d: &AvDetail, index: Option<usize>) -> Result<Side> {
+    let magnets = sorted_magnets(d);
+    let picked = match index {
+        Some(i) => {
+            let m = magnets
+                .get(i.wrapping_sub(1))
+                .with_context(|| format!("{} 没有第 {} 个磁力链接（共 {} 个）", d.code, i, magnets.len()))?;
+            Some((i, m))
+        }
+        None => magnets
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.quality_score.unwrap_or(0.0).total_cmp(&b.quality_score.unwrap_or(0.0)))
+            .map(|(i, m)| (i + 1, m)),
+    };
+    Ok(Side {
+        code: d.code.clone(),
+        title: d.title.clone(),
+        release_date: d.release_date.clone(),
+        duration_minutes: d.duration_minutes,
+        studio: d.studio.clone(),
+        actors: d.actor_names.clone(),
+        magnet_index: picked.map(|(i, _)| i),
+        magnet: picked.map(|(_, m)| specs(m, d)),
+    })
+}
+
+/// Which side wins a numeric row, if either
+fn better<T: PartialOrd>(a: Option<T>, b: Option<T>) -> (bool, bool) {
+    match (a, b) {
+        (Some(a), Some(b)) if a > b => (true, false),
+        (Some(a), Some(b)) if b > a => (false, true),
+        (Some(_), None) => (true, false),
+        (None, Some(_)) => (false, true),
+        _ => (false, false),
+    }
+}
+
+fn print_table(left: &Side, right: &Side) {
+    let dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    let yes_no = |b: bool| if b { "是".to_string() } else { "否".to_string() };
+    let (lm, rm) = (left.magnet.as_ref(), right.magnet.as_ref());
+    let res_rank = |r: Option<&'static str>| match r {
+        Some("4K") => Some(4),
+        Some("1080p") => Some(3),
+        Some("720p") => Some(2),
+        Some("480p") => Some(1),
+        _ => None,
+    };
+    let rows: Vec<(&str, String, String, (bool, bool))> = vec![
+        ("番号", left.code.clone(), right.code.clone(), (false, false)),
+        ("标题", util::truncate_chars(&left.title, 40), util::truncate_chars(&right.title, 40), (false, false)),
+        ("发行", dash(left.release_date.as_deref().map(dates::render)), dash(right.release_date.as_deref().map(dates::render)), (false, false)),
+        (
+            "时长",
+            dash(left.duration_minutes.map(|m| format!("{} 分钟", m))),
+            dash(right.duration_minutes.map(|m| format!("{} 分钟", m))),
+            better(left.duration_minutes, right.duration_minutes),
+        ),
+        ("片商", dash(left.studio.clone()), dash(right.studio.clone()), (false, false)),
+        ("演员", dash(Some(left.actors.join(", ")).filter(|s| !s.is_empty())), dash(Some(right.actors.join(", ")).filter(|s| !s.is_empty())), (false, false)),
+        (
+            "磁力",
+            dash(left.magnet_index.map(|i| format!("#{}", i))),
+            dash(right.magnet_index.map(|i| format!("#{}", i))),
+            (false, false),
+        ),
+        (
+            "名称",
+            dash(lm.and_then(|m| m.name.as_deref()).map(|n| util::truncate_chars(n, 40))),
+            dash(rm.and_then(|m| m.name.as_deref()).map(|n| util::truncate_chars(n, 40))),
+            (false, false),
+        ),
+        (
+            "大小",
+            dash(lm.and_then(|m| m.size.clone())),
+            dash(rm.and_then(|m| m.size.clone())),
+            better(lm.and_then(|m| m.size_bytes), rm.and_then(|m| m.size_bytes)),
+        ),
+        (
+            "码率",
+            dash(lm.and_then(|m| m.bitrate_mbps).map(|b| format!("~{:.2} Mbps", b))),
+            dash(rm.and_then(|m| m.bitrate_mbps).map(|b| format!("~{:.2} Mbps", b))),
+            better(lm.and_then(|m| m.bitrate_mbps), rm.and_then(|m| m.bitrate_mbps)),
+        ),
+        (
+            "分辨率",
+            dash(lm.and_then(|m| m.resolution).map(str::to_string)),
+            dash(rm.and_then(|m| m.resolution).map(str::to_string)),
+            better(res_rank(lm.and_then(|m| m.resolution)), res_rank(rm.and_then(|m| m.resolution))),
+        ),
+        ("编码", dash(lm.and_then(|m| m.codec.clone())), dash(rm.and_then(|m| m.codec.clone())), (false, false)),
+        (
+            "做种",
+            dash(lm.and_then(|m| m.seeders).map(|s| s.to_string())),
+            dash(rm.and_then(|m| m.seeders).map(|s| s.to_string())),
+            better(lm.and_then(|m| m.seeders), rm.and_then(|m| m.seeders)),
+        ),
+        (
+            "字幕",
+            dash(lm.map(|m| yes_no(m.subtitles))),
+            dash(rm.map(|m| yes_no(m.subtitles))),
+            better(lm.map(|m| m.subtitles), rm.map(|m| m.subtitles)),
+        ),
+        ("流出", dash(lm.map(|m| yes_no(m.leak))), dash(rm.map(|m| yes_no(m.leak))), (false, false)),
+        (
+            "评分",
+            dash(lm.and_then(|m| m.quality_score).map(|q| format!("{:.1}", q))),
+            dash(rm.and_then(|m| m.quality_score).map(|q| format!("{:.1}", q))),
+            better(lm.and_then(|m| m.quality_score), rm.and_then(|m| m.quality_score)),
+        ),
+    ];
+    let width = rows.iter().map(|(_, l, ..)| util::display_width(l)).max().unwrap_or(0).max(4);
+    let label_width = rows.iter().map(|(label, ..)| util::display_width(label)).max().unwrap_or(0);
+    for (label, l, r, (lw, rw)) in rows {
+        let pad = " ".repeat(width.saturating_sub(util::display_width(&l)));
+        let l = if lw { l.green().bold().to_string() } else { l };
+        let r = if rw { r.green().bold().to_string() } else { r };
+        println!("{}  {}{}  {}", util::pad(label, label_width).bold(), l, pad, r);
+    }
+}
+
+/// `av compare A B` compares two releases (each by its best magnet); `av compare CODE I J`
+/// compares two magnets of one release
+pub async fn run(first: &str, second: &str, third: Option<&str>, json: bool) -> Result<()> {
+    let index = |s: &str| s.trim().parse::<usize>().ok();
+    let (left, right) = match (index(second), third.map(index)) {
+        (Some(i), Some(Some(j))) => {
+            let d = scraper::fetch_detail(first).await?;
+            (side(&d, Some(i))?, side(&d, Some(j))?)
+        }
+        (None, None) => {
+            if code::normalize(first) == code::normalize(second) {
+                bail!("两个番号相同；比较同一番号的两个磁力请用 av compare {} <序号1> <序号2>", first);
+            }
+            let (a, b) = tokio::join!(scraper::fetch_detail(first), scraper::fetch_detail(second));
+            (side(&a?, None)?, side(&b?, None)?)
+        }
+        _ => bail!("用法: av compare CODE1 CODE2 或 av compare CODE 序号1 序号2（序号见 av install）"),
+    };
+    if json {
+        util::print_output(&Comparison { left, right }, true);
+        return Ok(());
+    }
+    print_table(&left, &right);
+    Ok(())
+}
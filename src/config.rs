@@ -0,0 +1,1011 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::errors::Tagged;
+
+/// Settings loaded from `config.toml`. Environment variables and CLI flags still win
+/// over anything set here; a missing file simply means defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub notify: NotifyConfig,
+    pub daemon: DaemonConfig,
+    pub network: NetworkConfig,
+    pub javdb: JavdbConfig,
+    pub magnets: MagnetsConfig,
+    pub translate: Option<TranslateConfig>,
+    pub metadata: MetadataConfig,
+    pub cache: CacheConfig,
+    pub pipeline: PipelineConfig,
+    pub media_server: Option<MediaServerConfig>,
+    pub download: DownloadConfig,
+    pub qbittorrent: Option<QbittorrentConfig>,
+    pub deluge: Option<DelugeConfig>,
+    pub rtorrent: Option<RtorrentConfig>,
+    pub remote: Option<RemoteConfig>,
+    pub aria2: Option<Aria2Config>,
+    pub artwork: ArtworkConfig,
+    pub sources: SourcesConfig,
+    /// Per-source CSS selector overrides, keyed like the bundled `selectors.toml`
+    pub selectors: BTreeMap<String, BTreeMap<String, String>>,
+    pub hooks: HooksConfig,
+    pub view: ViewConfig,
+    pub update: UpdateConfig,
+    pub subtitles: SubtitlesConfig,
+    /// `name = "subcommand --flags"`: `av name ...` runs the expansion
+    pub alias: BTreeMap<String, String>,
+    /// `command = "--flags"`, inserted after the command's name on every run (`"actors find"`
+    /// for a nested one); flags given on the command line override them
+    pub defaults: BTreeMap<String, String>,
+    /// The profile used when neither `--profile` nor `AV_PROFILE` names one
+    pub default_profile: Option<String>,
+    /// Named sets of overrides, `[profiles.<name>]` laid out like the file itself; the
+    /// selected one is merged over the rest of the file when it is loaded
+    pub profiles: BTreeMap<String, toml::Table>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewConfig {
+    /// Streaming site URL templates offered by `av view` after JavDB's play links;
+    /// `{code}` and `{code_lower}` are replaced with the code
+    pub streaming: Vec<String>,
+    /// Play in this local player instead of the browser; `--player` overrides it
+    pub player: Option<Player>,
+    /// Extra arguments passed to the player
+    pub player_args: Vec<String>,
+    /// Item table columns (`list`/`search`/`top`); `--columns` overrides them
+    pub columns: Vec<String>,
+    /// Actor table columns (`actors`)
+    pub actor_columns: Vec<String>,
+    /// Cut titles longer than this many terminal columns; `--title-width` overrides it
+    pub title_width: Option<usize>,
+    /// How release dates are shown outside JSON; `--date-format` overrides it
+    pub date_format: Option<crate::dates::DateFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Player {
+    Mpv,
+    Vlc,
+}
+
+/// User scripts run after an action, with the JSON payload on stdin (see `hooks`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub on_detail: Vec<HookConfig>,
+    pub on_install: Vec<HookConfig>,
+    pub on_new_release: Vec<HookConfig>,
+    pub on_download_complete: Vec<HookConfig>,
+    /// Rewrites magnets before they are printed or dispatched
+    pub transform_magnet: Option<HookConfig>,
+    /// Guards that may veto downloads, rips, organizing (and its undo), JavDB list changes
+    /// and self-update
+    pub validate: Vec<HookConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_hook_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourcesConfig {
+    /// External executables or WebAssembly modules that act as extra sources (see `sources::plugin`)
+    pub plugins: Vec<PluginConfig>,
+    /// Jackett/Prowlarr indexers asked for magnets (see `sources::torznab`)
+    pub torznab: Vec<TorznabConfig>,
+    /// ThePornDB's API, for western scenes (see `sources::theporndb`)
+    pub theporndb: ThePornDbConfig,
+    /// Where the DMM API is searched (see `sources::dmm`)
+    pub dmm: DmmConfig,
+    /// The gfriends actress-portrait repository (see `sources::gfriends`)
+    pub gfriends: GfriendsConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginMode {
+    /// Only asked when the built-in sources find nothing
+    #[default]
+    Fallback,
+    /// Always asked; fills fields the built-in sources left empty
+    Merge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginConfig {
+    pub name: String,
+    /// The executable to run; leave it out for a WebAssembly plugin
+    #[serde(default)]
+    pub command: String,
+    /// A WASI (`wasm32-wasip1`) module run in-process instead of `command`, with the same
+    /// JSON on its stdin and stdout
+    #[serde(default)]
+    pub module: Option<std::path::PathBuf>,
+    /// The command's arguments, or the module's `argv` after its name
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub mode: PluginMode,
+    #[serde(default = "default_plugin_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_plugin_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TorznabConfig {
+    pub name: String,
+    /// The indexer's Torznab endpoint, e.g. Jackett's
+    /// `http://localhost:9117/api/v2.0/indexers/all/results/torznab/api`
+    pub url: String,
+    pub api_key: String,
+    /// Newznab categories to search (`6000` is XXX); empty searches all
+    #[serde(default)]
+    pub categories: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThePornDbConfig {
+    /// API token from the account's settings page; empty leaves the source off
+    pub token: String,
+    pub url: String,
+}
+
+impl Default for ThePornDbConfig {
+    fn default() -> Self {
+        ThePornDbConfig { token: String::new(), url: "https://api.theporndb.net".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DmmConfig {
+    /// `FANZA` (the adult catalogue) or `DMM.com`
+    pub site: String,
+    /// The service floors without their own `service/` go under
+    pub service: String,
+    /// Floors asked in order until one has the code: `videoa`, or `rental/rental_dvd` for
+    /// another service's floor
+    pub floors: Vec<String>,
+}
+
+impl Default for DmmConfig {
+    fn default() -> Self {
+        DmmConfig {
+            site: "FANZA".to_string(),
+            service: "digital".to_string(),
+            floors: vec!["videoa".to_string(), "videoc".to_string(), "rental/rental_dvd".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GfriendsConfig {
+    /// The repository's raw-file root, holding `Filetree.json` and `Content/`; a mirror's
+    /// root works the same
+    pub url: String,
+    /// Days the downloaded index is used before it is fetched again
+    pub max_age_days: u64,
+}
+
+impl Default for GfriendsConfig {
+    fn default() -> Self {
+        GfriendsConfig { url: "https://raw.githubusercontent.com/gfriends/gfriends/master".to_string(), max_age_days: 7 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtworkConfig {
+    /// Poster width / height; JAV covers' front panel is about 0.71
+    pub poster_ratio: f32,
+    /// Horizontal position of the crop, 0.0 = left edge, 1.0 = right edge (the front panel)
+    pub poster_offset: f32,
+    /// Also save the preview images as `extrafanart/fanartN.jpg`
+    pub extrafanart: bool,
+}
+
+impl Default for ArtworkConfig {
+    fn default() -> Self {
+        ArtworkConfig { poster_ratio: 0.71, poster_offset: 1.0, extrafanart: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataLang {
+    Ja,
+    Zh,
+    En,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataConfig {
+    /// Preferred language for titles and genres; unset keeps first-hit-wins merging.
+    /// `--lang` / `AV_LANG` override it
+    pub lang: Option<MetadataLang>,
+    /// Weight of each source (`javdb`, `dmm`, `javlibrary`, plugin names) in the averaged
+    /// rating; unlisted sources count 1.0
+    pub rating_weights: BTreeMap<String, f32>,
+    /// Western mode: scene names (`Site.YY.MM.DD.Performer`) count as codes, looked up on
+    /// ThePornDB; `--western` turns it on for one command
+    pub western: bool,
+}
+
+/// One post-download step of `av get --full`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PipelineStep {
+    /// Move the videos to `<library_dir>/<CODE>/` under their canonical names
+    Rename,
+    /// Write `<CODE>.nfo` next to them
+    Nfo,
+    /// Save fanart.jpg, poster.jpg, the thumbnail and extrafanart next to them
+    Artwork,
+    /// Have the media server rescan the title's folder (`[media_server]`), or request
+    /// `refresh_url`
+    Refresh,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    /// Run in this order once the download has finished
+    pub steps: Vec<PipelineStep>,
+    /// aria2c saves each code into a subfolder of this; the current directory when unset
+    pub download_dir: Option<PathBuf>,
+    /// `rename` moves the videos to `<library_dir>/<CODE>/`; unset renames them in place
+    pub library_dir: Option<PathBuf>,
+    /// Requested by `refresh` when no `[media_server]` is configured, e.g. a full-library
+    /// refresh URL
+    pub refresh_url: Option<String>,
+    /// HTTP method used for `refresh_url`
+    pub refresh_method: String,
+    /// `rename` links the videos under their new names instead of moving them, so the
+    /// torrent client keeps seeding the originals; `--link` overrides it
+    pub link: Option<LinkMode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    /// 硬链接（需与下载目录在同一文件系统）
+    Hard,
+    /// 符号链接（指向下载目录中的原文件）
+    Sym,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            steps: vec![PipelineStep::Rename, PipelineStep::Nfo, PipelineStep::Artwork, PipelineStep::Refresh],
+            download_dir: None,
+            library_dir: None,
+            refresh_url: None,
+            refresh_method: "POST".to_string(),
+            link: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaServerKind {
+    Jellyfin,
+    Emby,
+    Plex,
+}
+
+/// The media server told about newly placed files, one folder at a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaServerConfig {
+    pub kind: MediaServerKind,
+    /// Base URL, e.g. `http://localhost:8096` or `http://localhost:32400`
+    pub url: String,
+    /// API key (Jellyfin/Emby) or X-Plex-Token
+    pub token: String,
+    /// Where the server sees `[pipeline] library_dir`, when it runs in a container or on
+    /// another machine
+    #[serde(default)]
+    pub library_path: Option<String>,
+    /// Plex library section id; looked up from the folder when unset
+    #[serde(default)]
+    pub section: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadConfig {
+    /// Where downloads go; unset, the first configured of `[qbittorrent]`, `[deluge]`,
+    /// `[rtorrent]` and `[remote]`, else aria2c
+    pub client: Option<DownloadClientKind>,
+    /// Download pieces in order, so playback can start early
+    pub sequential: bool,
+    /// Stop seeding at this ratio
+    pub ratio_limit: Option<f64>,
+    /// Upload cap per download, a speed per second like `500KB` or `2MB`
+    pub upload_limit: Option<String>,
+    /// Stop seeding after this many minutes (aria2c's default without it or a ratio: none)
+    pub seed_minutes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadClientKind {
+    Aria2,
+    Qbittorrent,
+    Deluge,
+    Rtorrent,
+    Remote,
+}
+
+/// qBittorrent's WebUI. `category`, `tags` and `save_path` are templates filled from the
+/// detail (see `downloader`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QbittorrentConfig {
+    /// WebUI address, e.g. `http://localhost:8080`
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// e.g. `/downloads/{actor}/{code}`; qBittorrent's default folder when unset
+    #[serde(default)]
+    pub save_path: Option<String>,
+}
+
+/// Deluge's WebUI, which passes the calls on to the daemon. `category` becomes a label
+/// (Label plugin); `save_path` is a template as for qBittorrent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelugeConfig {
+    /// WebUI address, e.g. `http://localhost:8112`
+    pub url: String,
+    /// The WebUI password; Deluge's default `deluge` when unset
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Daemon (`host:port`) the WebUI connects to when it isn't connected yet; the first
+    /// one in its connection manager when unset
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub save_path: Option<String>,
+}
+
+/// rTorrent's XML-RPC endpoint. `category` becomes the ruTorrent label; `save_path` is a
+/// template as for qBittorrent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RtorrentConfig {
+    /// e.g. `https://box.example/RPC2`, or ruTorrent's
+    /// `https://box.example/rutorrent/plugins/httprpc/action.php`
+    pub url: String,
+    /// HTTP basic auth, as seedboxes put in front of it
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub save_path: Option<String>,
+    /// Keep polling until the download completes, so it is reported done (and notified)
+    /// like an aria2c download
+    #[serde(default)]
+    pub wait: bool,
+    #[serde(default = "default_rtorrent_poll")]
+    pub poll_secs: u64,
+}
+
+/// An aria2c running as a daemon with its JSON-RPC interface on (`--enable-rpc`), for
+/// `av progress` to watch when no torrent client is configured
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aria2Config {
+    /// e.g. `http://localhost:6800/jsonrpc`
+    pub rpc_url: String,
+    /// aria2c's `--rpc-secret`
+    #[serde(default)]
+    pub rpc_secret: Option<String>,
+}
+
+fn default_rtorrent_poll() -> u64 {
+    60
+}
+
+/// A machine reached over SSH that downloads instead of this one, for networks that can't
+/// torrent. Key-based auth only: `ssh` runs in batch mode and never prompts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// SSH destination, e.g. `me@box.example` or a `~/.ssh/config` host alias
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Private key file (`ssh -i`)
+    #[serde(default)]
+    pub identity: Option<String>,
+    #[serde(default)]
+    pub mode: RemoteMode,
+    /// `aria2` mode: where aria2c saves on the remote machine, a template like
+    /// `[qbittorrent] save_path`; relative to the remote home directory
+    #[serde(default = "default_remote_dir")]
+    pub download_dir: String,
+    /// `watch` mode: the remote client's watch folder the `.magnet` files are written to
+    #[serde(default)]
+    pub watch_dir: Option<String>,
+    /// `aria2` mode: copy each finished download back into this local directory
+    #[serde(default)]
+    pub pull_to: Option<PathBuf>,
+    /// `aria2` mode: how often to check whether the remote aria2c is done
+    #[serde(default = "default_remote_poll")]
+    pub poll_secs: u64,
+}
+
+fn default_remote_poll() -> u64 {
+    30
+}
+
+fn default_remote_dir() -> String {
+    "av-downloads/{code}".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteMode {
+    /// Run aria2c on the remote machine and wait for it
+    #[default]
+    Aria2,
+    /// Drop a `.magnet` file into `watch_dir` for a client there to pick up
+    Watch,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Serve merged details from the local cache while younger than this; 0 always
+    /// fetches (the cache is still kept up to date for `av refresh`)
+    pub ttl_hours: u64,
+}
+
+/// `av sub --generate`: whisper.cpp run locally
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubtitlesConfig {
+    /// The whisper.cpp executable; `whisper-cli`, then its older names, on PATH when unset
+    pub whisper: Option<PathBuf>,
+    /// Where `ggml-<model>.bin` files are looked up; `<data dir>/whisper` when unset
+    pub models_dir: Option<PathBuf>,
+    /// `--model`'s default
+    pub model: String,
+}
+
+impl Default for SubtitlesConfig {
+    fn default() -> Self {
+        SubtitlesConfig { whisper: None, models_dir: None, model: "small".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateConfig {
+    /// GitHub `owner/repo` whose releases `av update` installs
+    pub repo: String,
+    pub channel: UpdateChannel,
+    /// Stay on this version: `av update` installs it instead of the channel's latest
+    pub pin: Option<String>,
+    /// minisign public key; when set, releases must carry a valid `.minisig` for the asset
+    pub minisign_pubkey: Option<String>,
+    /// Check for a new release at most once a day and mention it after other commands
+    pub notify: bool,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self { repo: "auv-sh/av".into(), channel: UpdateChannel::Stable, pin: None, minisign_pubkey: None, notify: false }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// The latest full release
+    Stable,
+    /// The newest release including pre-releases
+    Nightly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslateBackend {
+    Deepl,
+    Google,
+    Libretranslate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslateConfig {
+    pub backend: TranslateBackend,
+    /// Required for DeepL and Google, optional for LibreTranslate
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// API base URL; required for LibreTranslate, overrides the default host for the others
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MagnetsConfig {
+    /// Weights behind each magnet's `quality_score`
+    pub score: ScoreWeights,
+    /// aria2c gives up on a magnet that downloads nothing for this many seconds and the
+    /// next-best magnet of the code is tried; 0 keeps the single-magnet behavior
+    pub fallback_timeout_secs: u64,
+    /// Magnets tried per code at most, the first included
+    pub fallback_attempts: usize,
+    /// Ask every magnet source for every code and keep the union, instead of falling back
+    /// to Sukebei only when the metadata source had none; `--all-magnets` turns it on once
+    pub all_sources: bool,
+    /// BTSOW's current domain (`AV_BTSOW_BASE` wins); the built-in one otherwise
+    pub btsow_base: Option<String>,
+    /// TorrentKitty's mirror (`AV_TORRENTKITTY_BASE` wins); the built-in one otherwise
+    pub torrentkitty_base: Option<String>,
+}
+
+impl Default for MagnetsConfig {
+    fn default() -> Self {
+        MagnetsConfig { score: ScoreWeights::default(), fallback_timeout_secs: 0, fallback_attempts: 3, all_sources: false, btsow_base: None, torrentkitty_base: None }
+    }
+}
+
+/// Points a magnet earns for each factor at its best; negative values penalize instead
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoreWeights {
+    /// Full marks at 100 seeders, on a log scale
+    pub seeders: f32,
+    /// Full marks at 8 Mbps, from size and duration
+    pub bitrate: f32,
+    /// 4K full marks, 1080p 0.75, 720p 0.45, 480p 0.2
+    pub resolution: f32,
+    /// Chinese subtitles (`-C`, `中文字幕`, ...)
+    pub subtitles: f32,
+    /// Leaked / decensored releases
+    pub leak: f32,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        ScoreWeights { seeders: 40.0, bitrate: 25.0, resolution: 20.0, subtitles: 10.0, leak: 5.0 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JavdbConfig {
+    /// Base URLs tried in order when one is unreachable; `AV_JAVDB_BASE` is always tried first
+    pub mirrors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// http(s)://, socks5:// or socks5h:// proxy; `AV_HTTP_PROXY` overrides it
+    pub proxy: Option<String>,
+    /// Per-source overrides keyed by source (javdb, sukebei, javlibrary, dmm); "direct" bypasses the proxy
+    pub proxies: BTreeMap<String, String>,
+    /// Replaces the built-in pool of browser User-Agent strings
+    pub user_agents: Vec<String>,
+    /// "session" (default), "request" or "off"
+    pub user_agent_rotation: crate::ua::Rotation,
+    /// Parallel fetches for batch operations; `--jobs` / `AV_JOBS` override it
+    pub jobs: Option<usize>,
+    /// Seconds a source request may take in all; unset waits as long as the server keeps
+    /// the connection open. `--timeout` / `AV_TIMEOUT` override it
+    pub timeout_secs: Option<u64>,
+    /// Attempts added to a source request that fails in passing (connection error,
+    /// timeout, HTTP 429 / 5xx), waiting 1s, 2s, 4s ... in between; `--retries` /
+    /// `AV_RETRIES` override it
+    pub retries: Option<u32>,
+    /// DNS-over-HTTPS endpoint (RFC 8484), e.g. "https://1.1.1.1/dns-query"; unset uses
+    /// the system resolver
+    pub doh: Option<String>,
+    /// Fixed addresses, keyed by host name or by source (javdb, sukebei, javlibrary, dmm,
+    /// minnano) for all of its hosts; skips DNS for them entirely
+    pub hosts: BTreeMap<String, HostAddrs>,
+    /// Fetch pages through curl-impersonate: a browser ("chrome", "edge", "firefox",
+    /// "safari") for its newest `curl_<browser>*` wrapper, or a wrapper's name or path
+    pub impersonate: Option<String>,
+    /// Connection tuning shared by every HTTP client
+    pub client: ClientTuning,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientTuning {
+    pub http2: Http2Mode,
+    /// Ask for gzip/brotli/deflate bodies
+    pub compression: bool,
+    /// Close pooled connections idle for longer than this; 0 keeps them until the server does
+    pub pool_idle_timeout_secs: u64,
+    /// Idle connections kept per host; 0 turns pooling off
+    pub pool_max_idle_per_host: usize,
+    /// TCP keepalive probe interval (and HTTP/2 ping interval); 0 turns it off
+    pub tcp_keepalive_secs: u64,
+    /// 0 waits as long as the request's own timeout allows
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for ClientTuning {
+    fn default() -> Self {
+        Self {
+            http2: Http2Mode::Auto,
+            compression: true,
+            pool_idle_timeout_secs: 90,
+            pool_max_idle_per_host: 8,
+            tcp_keepalive_secs: 30,
+            connect_timeout_secs: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Http2Mode {
+    /// Negotiated through TLS ALPN, HTTP/1.1 otherwise
+    Auto,
+    /// HTTP/2 without negotiating, also over plain http://; only for servers known to speak it
+    PriorKnowledge,
+    /// HTTP/1.1 only
+    Off,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HostAddrs {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HostAddrs {
+    pub fn list(&self) -> Vec<&str> {
+        match self {
+            HostAddrs::One(a) => vec![a.as_str()],
+            HostAddrs::Many(a) => a.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Append timestamped job logs here in addition to stdout
+    pub log_file: Option<PathBuf>,
+    /// Serve Prometheus `/metrics` on this address, e.g. "127.0.0.1:9108"
+    pub metrics_bind: Option<String>,
+    pub jobs: Vec<JobConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Notify about codes not seen on earlier runs
+    NewReleases,
+    /// Regenerate an RSS/Atom file
+    Rss,
+    /// Save the newest-releases listing for `av top --diff` and `av trends`
+    TopSnapshot,
+    /// Run `av <args>` as a child process
+    Command,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobConfig {
+    pub name: String,
+    pub kind: JobKind,
+    /// Fixed interval such as "30m", "6h" or "1d"
+    #[serde(default)]
+    pub every: Option<String>,
+    /// 5-field cron expression, e.g. "0 */6 * * *"
+    #[serde(default)]
+    pub cron: Option<String>,
+    #[serde(default)]
+    pub run_on_start: bool,
+    #[serde(default)]
+    pub actors: Vec<String>,
+    /// Also watch the newest-releases listing when actors are given
+    #[serde(default)]
+    pub include_top: bool,
+    #[serde(default = "default_job_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub uncen: bool,
+    /// Output path for `rss` jobs
+    #[serde(default)]
+    pub out: Option<PathBuf>,
+    #[serde(default)]
+    pub format: crate::feed::FeedFormat,
+    /// Arguments for `command` jobs, e.g. ["rss", "--out", "feed.xml"]
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+fn default_job_limit() -> usize {
+    20
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    pub webhooks: Vec<WebhookConfig>,
+    pub telegram: Option<TelegramConfig>,
+    pub discord: Vec<DiscordConfig>,
+    pub ntfy: Option<NtfyConfig>,
+    pub gotify: Option<GotifyConfig>,
+    pub email: Option<EmailConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// When set, the body is signed with HMAC-SHA256 and sent as `X-Av-Signature: sha256=<hex>`
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Event names to deliver (e.g. "new_release", "download_complete"); empty means all
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelegramConfig {
+    pub bot_token: String,
+    /// Target chat; also the only chat whose commands `av notify telegram-bot` accepts
+    pub chat_id: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Allow `/get CODE` and download buttons to trigger downloads on this machine
+    #[serde(default)]
+    pub allow_commands: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscordConfig {
+    pub webhook_url: String,
+    /// Event names to post for this webhook; empty means all
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NtfyConfig {
+    #[serde(default = "default_ntfy_server")]
+    pub server: String,
+    pub topic: String,
+    /// Access token for protected topics / self-hosted servers
+    #[serde(default)]
+    pub token: Option<String>,
+    /// 1 (min) ..= 5 (max)
+    #[serde(default)]
+    pub priority: Option<u8>,
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".to_string()
+}
+
+/// New releases collected into an HTML email sent once a day or week
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    /// 465 for `tls`, 587 for `starttls`, 25 for `none` when unset
+    #[serde(default)]
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// `av@example.com` or `av <av@example.com>`
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub every: DigestPeriod,
+    /// Event names to deliver ("new_release", "test"); empty means all
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS
+    #[default]
+    Starttls,
+    /// TLS from the start (SMTPS)
+    Tls,
+    /// No encryption, for a relay on localhost
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    #[default]
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GotifyConfig {
+    /// Server base URL, e.g. https://gotify.example.com
+    pub url: String,
+    /// Application token
+    pub token: String,
+    #[serde(default)]
+    pub priority: Option<u8>,
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// `--profile` as found in the arguments, `None` for "not given"
+static PROFILE_ARG: OnceLock<Option<String>> = OnceLock::new();
+
+/// The profile merged into the loaded config
+static ACTIVE_PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// The file as written, before the profile was merged in; `av env` tells them apart
+static FILE_TABLE: OnceLock<toml::Table> = OnceLock::new();
+
+/// `$AV_CONFIG`, else `<config dir>/av/config.toml` (e.g. `~/.config/av/config.toml`)
+pub fn config_path() -> Option<PathBuf> {
+    if let Ok(p) = std::env::var("AV_CONFIG") {
+        if !p.trim().is_empty() {
+            return Some(PathBuf::from(p));
+        }
+    }
+    dirs::config_dir().map(|d| d.join("av").join("config.toml"))
+}
+
+/// Pick `--profile NAME` / `--profile=NAME` out of the raw arguments. The config has to
+/// be loaded before they are parsed (aliases expand from it), so clap's copy comes too late.
+pub fn select_profile(args: &[std::ffi::OsString]) {
+    let mut found = None;
+    let mut iter = args.iter().skip(1).map(|a| a.to_string_lossy());
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--profile" {
+            found = iter.next().map(|v| v.into_owned());
+        } else if let Some(v) = arg.strip_prefix("--profile=") {
+            found = Some(v.to_string());
+        }
+    }
+    let _ = PROFILE_ARG.set(found);
+}
+
+/// `--profile`, else `AV_PROFILE`, else `default_profile`
+fn profile_name(default: Option<&str>) -> Option<String> {
+    PROFILE_ARG
+        .get()
+        .cloned()
+        .flatten()
+        .or_else(|| std::env::var("AV_PROFILE").ok())
+        .or_else(|| default.map(str::to_string))
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+}
+
+/// The profile the running config came from, if any
+pub fn active_profile() -> Option<&'static str> {
+    ACTIVE_PROFILE.get().and_then(|p| p.as_deref())
+}
+
+/// `--profile` when it was given on the command line
+pub fn profile_arg() -> Option<&'static str> {
+    PROFILE_ARG.get().and_then(|p| p.as_deref())
+}
+
+/// The config file's own settings, empty without one
+pub fn file_table() -> &'static toml::Table {
+    FILE_TABLE.get_or_init(toml::Table::new)
+}
+
+/// Whether the config file loaded (a broken one leaves the defaults in place)
+pub fn loaded() -> bool {
+    ACTIVE_PROFILE.get().is_some()
+}
+
+/// Tables merge key by key, so a profile can change `[network] proxy` and keep the rest of
+/// `[network]`; anything else, arrays included, is replaced
+fn merge(base: &mut toml::Table, overrides: &toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(b)), toml::Value::Table(o)) => merge(b, o),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// The file's settings with the selected profile merged over them
+fn parse(text: &str, path: &std::path::Path) -> Result<(Config, Option<String>, toml::Table)> {
+    let file: toml::Table = toml::from_str(text).with_context(|| Tagged::new("config", format!("配置文件格式错误: {}", path.display())))?;
+    let mut table = file.clone();
+    let default = table.get("default_profile").and_then(|v| v.as_str()).map(str::to_string);
+    let profile = profile_name(default.as_deref());
+    if let Some(name) = &profile {
+        let overrides = table.get("profiles").and_then(|p| p.get(name.as_str())).and_then(|p| p.as_table()).cloned();
+        let Some(overrides) = overrides else {
+            let known: Vec<&String> = table.get("profiles").and_then(|p| p.as_table()).map(|t| t.keys().collect()).unwrap_or_default();
+            let known = if known.is_empty() { "（未定义任何 profile）".to_string() } else { known.iter().map(|k| k.as_str()).collect::<Vec<_>>().join("、") };
+            anyhow::bail!(Tagged::new("config", format!("配置文件中没有 [profiles.{}]，可用: {}", name, known)));
+        };
+        merge(&mut table, &overrides);
+    }
+    let cfg = Config::deserialize(table).with_context(|| Tagged::new("config", format!("配置文件格式错误: {}", path.display())))?;
+    Ok((cfg, profile, file))
+}
+
+/// The config file with the selected profile applied, that profile's name, and the file's
+/// own table
+pub fn load() -> Result<(Config, Option<String>, toml::Table)> {
+    let Some(path) = config_path() else { return Ok((Config::default(), None, toml::Table::new())) };
+    if !path.exists() {
+        if let Some(name) = profile_name(None) {
+            anyhow::bail!(Tagged::new("config", format!("配置文件 {} 不存在，无法使用 profile {}", path.display(), name)));
+        }
+        return Ok((Config::default(), None, toml::Table::new()));
+    }
+    let text = std::fs::read_to_string(&path).with_context(|| Tagged::new("config", format!("读取配置文件失败: {}", path.display())))?;
+    parse(&text, &path)
+}
+
+/// Load the config file once at startup; later calls to `get()` return it.
+pub fn init() -> Result<()> {
+    let (cfg, profile, file) = load()?;
+    let _ = CONFIG.set(cfg);
+    let _ = ACTIVE_PROFILE.set(profile);
+    let _ = FILE_TABLE.set(file);
+    Ok(())
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
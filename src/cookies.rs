@@ -0,0 +1,266 @@
+use anyhow::{Context, Result};
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use reqwest_cookie_store::{CookieStore, CookieStoreMutex};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use crate::keychain;
+use crate::metrics;
+use crate::store;
+use crate::util;
+
+/// Cookie provider shared by all scraping clients. Each source (javdb, sukebei, ...)
+/// gets its own jar, loaded from and written back to `<data dir>/cookies/<source>.json`,
+/// so Cloudflare clearance and session cookies survive between runs. The jars in
+/// `KEYCHAIN_JARS` hold a login and go to the OS keychain instead, when there is one.
+pub struct SourceJars {
+    jars: Mutex<HashMap<String, Arc<CookieStoreMutex>>>,
+}
+
+/// Sources whose jar carries a login session (`av login javdb`)
+const KEYCHAIN_JARS: &[&str] = &["javdb"];
+
+/// The JSON last saved per source, so a response that changed no cookie isn't saved again
+static SAVED: LazyLock<Mutex<HashMap<String, Vec<u8>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static JARS: LazyLock<Arc<SourceJars>> = LazyLock::new(|| Arc::new(SourceJars { jars: Mutex::new(HashMap::new()) }));
+
+pub fn provider() -> Arc<SourceJars> {
+    JARS.clone()
+}
+
+pub fn dir() -> PathBuf {
+    store::data_dir().join("cookies")
+}
+
+fn jar_path(source: &str) -> PathBuf {
+    dir().join(format!("{}.json", source))
+}
+
+fn load_jar(source: &str) -> CookieStore {
+    if let Some(json) = KEYCHAIN_JARS.contains(&source).then(|| keychain::jar(source)).flatten() {
+        match cookie_store::serde::json::load(json.as_bytes()) {
+            Ok(store) => return store,
+            Err(e) => eprintln!("[WARN] 钥匙串中的 {} Cookie 已损坏，已忽略 ({})", source, e),
+        }
+    }
+    let p = jar_path(source);
+    let Ok(file) = std::fs::File::open(&p) else { return CookieStore::default() };
+    match cookie_store::serde::json::load(std::io::BufReader::new(file)) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("[WARN] Cookie 文件已损坏，已忽略: {} ({})", p.display(), e);
+            CookieStore::default()
+        }
+    }
+}
+
+/// Save one jar: a login jar to the keychain (dropping its file), the rest, or a login jar
+/// when there is no keychain, to disk
+pub fn save_jar(source: &str, jar: &CookieStore) -> Result<()> {
+    let mut buf = Vec::new();
+    // Session cookies are kept too: the point is to look like the same browser next run
+    cookie_store::serde::json::save_incl_expired_and_nonpersistent(jar, &mut buf)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .context("序列化 Cookie 失败")?;
+    if SAVED.lock().unwrap().get(source) == Some(&buf) {
+        return Ok(());
+    }
+    let in_keychain = KEYCHAIN_JARS.contains(&source)
+        && match keychain::save_jar(source, &String::from_utf8_lossy(&buf)) {
+            Ok(()) => true,
+            Err(e) => {
+                util::debug(format!("cookies: {} not in the keychain: {:#}", source, e));
+                false
+            }
+        };
+    if in_keychain {
+        let p = jar_path(source);
+        if p.exists() {
+            std::fs::remove_file(&p).with_context(|| format!("删除失败: {}", p.display()))?;
+        }
+    } else {
+        write_jar(source, &buf)?;
+    }
+    SAVED.lock().unwrap().insert(source.to_string(), buf);
+    Ok(())
+}
+
+/// Write one jar's JSON to disk (temp file + rename), readable only by the owner
+fn write_jar(source: &str, buf: &[u8]) -> Result<()> {
+    let d = dir();
+    std::fs::create_dir_all(&d).with_context(|| format!("创建目录失败: {}", d.display()))?;
+    let p = jar_path(source);
+    let tmp = d.join(format!(".{}.json.tmp", source));
+    std::fs::write(&tmp, buf).with_context(|| format!("写入 Cookie 失败: {}", tmp.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600));
+    }
+    std::fs::rename(&tmp, &p).with_context(|| format!("写入 Cookie 失败: {}", p.display()))?;
+    Ok(())
+}
+
+impl SourceJars {
+    pub fn jar(&self, source: &str) -> Arc<CookieStoreMutex> {
+        let mut jars = self.jars.lock().unwrap_or_else(|p| p.into_inner());
+        jars.entry(source.to_string())
+            .or_insert_with(|| Arc::new(CookieStoreMutex::new(load_jar(source))))
+            .clone()
+    }
+}
+
+impl reqwest::cookie::CookieStore for SourceJars {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let source = metrics::source_of(url.as_str());
+        let jar = self.jar(&source);
+        jar.set_cookies(cookie_headers, url);
+        let guard = jar.lock().unwrap_or_else(|p| p.into_inner());
+        if let Err(e) = save_jar(&source, &guard) {
+            util::debug(format!("cookies: save {} failed: {:#}", source, e));
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let source = metrics::source_of(url.as_str());
+        self.jar(&source).cookies(url)
+    }
+}
+
+/// A cookie taken from outside the HTTP flow (browser store, pasted header)
+#[derive(Debug, Clone)]
+pub struct ImportedCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    /// Unix seconds; `None` for session cookies
+    pub expires: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+/// Add cookies to the jar of whichever source `domain` belongs to and persist it.
+/// Returns the number of cookies stored.
+pub fn import(domain: &str, cookies: &[ImportedCookie]) -> Result<usize> {
+    let host = domain.trim().trim_start_matches('.');
+    let source = metrics::source_of(&format!("https://{}/", host));
+    let jar = JARS.jar(&source);
+    let mut guard = jar.lock().unwrap_or_else(|p| p.into_inner());
+    let now = util::now_secs() as i64;
+    let mut stored = 0;
+    for c in cookies {
+        if c.expires.is_some_and(|e| e <= now) {
+            continue;
+        }
+        let cookie_host = c.domain.trim_start_matches('.');
+        let Ok(url) = Url::parse(&format!("https://{}/", cookie_host)) else { continue };
+        let mut raw = reqwest_cookie_store::RawCookie::new(c.name.clone(), c.value.clone());
+        raw.set_domain(cookie_host.to_string());
+        raw.set_path(if c.path.is_empty() { "/".to_string() } else { c.path.clone() });
+        raw.set_secure(c.secure);
+        raw.set_http_only(c.http_only);
+        if let Some(e) = c.expires {
+            raw.set_max_age(cookie::time::Duration::seconds(e - now));
+        }
+        if guard.insert_raw(&raw, &url).is_ok() {
+            stored += 1;
+        }
+    }
+    save_jar(&source, &guard)?;
+    Ok(stored)
+}
+
+/// Parse a `Cookie:` header value ("a=1; b=2") into session cookies for `domain`
+pub fn parse_header(domain: &str, header: &str) -> Vec<ImportedCookie> {
+    header
+        .trim()
+        .trim_start_matches("Cookie:")
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            Some(ImportedCookie {
+                name: name.trim().to_string(),
+                value: value.trim().to_string(),
+                domain: domain.to_string(),
+                path: "/".to_string(),
+                expires: None,
+                secure: false,
+                http_only: false,
+            })
+        })
+        .filter(|c| !c.name.is_empty())
+        .collect()
+}
+
+/// Drop every cookie in one source's jar, in memory, on disk and in the keychain
+pub fn clear_source(source: &str) -> Result<()> {
+    let jar = JARS.jar(source);
+    jar.lock().unwrap_or_else(|p| p.into_inner()).clear();
+    SAVED.lock().unwrap().remove(source);
+    let p = jar_path(source);
+    if p.exists() {
+        std::fs::remove_file(&p).with_context(|| format!("删除失败: {}", p.display()))?;
+    }
+    if KEYCHAIN_JARS.contains(&source) {
+        keychain::delete_jar(source)?;
+    }
+    Ok(())
+}
+
+/// Whether `source` has a jar saved, as a file or in the keychain
+fn is_saved(source: &str) -> bool {
+    jar_path(source).exists() || (KEYCHAIN_JARS.contains(&source) && keychain::jar(source).is_some())
+}
+
+/// Where `source`'s jar is kept, for messages
+pub fn location(source: &str) -> String {
+    if jar_path(source).exists() {
+        jar_path(source).display().to_string()
+    } else {
+        "系统钥匙串".to_string()
+    }
+}
+
+/// Sources with a saved jar and how many cookies each holds
+pub fn list() -> Vec<(String, usize)> {
+    let files = std::fs::read_dir(dir()).into_iter().flatten();
+    let mut out: Vec<(String, usize)> = files
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let source = name.strip_suffix(".json")?.to_string();
+            if source.starts_with('.') {
+                return None;
+            }
+            let count = load_jar(&source).iter_any().count();
+            Some((source, count))
+        })
+        .collect();
+    for source in KEYCHAIN_JARS {
+        if !out.iter().any(|(s, _)| s == source) && keychain::jar(source).is_some() {
+            out.push((source.to_string(), load_jar(source).iter_any().count()));
+        }
+    }
+    out.sort();
+    out
+}
+
+/// Delete the saved jar for one source, or all of them
+pub fn clear(source: Option<&str>) -> Result<usize> {
+    let targets: Vec<String> = match source {
+        Some(s) => vec![s.to_string()],
+        None => list().into_iter().map(|(s, _)| s).collect(),
+    };
+    let mut removed = 0;
+    for s in targets {
+        if is_saved(&s) {
+            removed += 1;
+        }
+        clear_source(&s)?;
+    }
+    Ok(removed)
+}
@@ -0,0 +1,461 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::cancel;
+use crate::config::{self, JobConfig, JobKind};
+use crate::feed::{self, FeedOptions};
+use crate::metrics;
+use crate::notify;
+use crate::scraper;
+use crate::store;
+use crate::trending;
+use crate::util;
+
+/// Cap on remembered codes per job so the state file does not grow forever
+const SEEN_LIMIT: usize = 5000;
+
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(CronSpec),
+}
+
+impl Schedule {
+    pub fn from_job(job: &JobConfig) -> Result<Schedule> {
+        match (&job.every, &job.cron) {
+            (Some(_), Some(_)) => bail!("任务 {} 不能同时设置 every 与 cron", job.name),
+            (Some(every), None) => Ok(Schedule::Every(util::parse_duration(every).with_context(|| format!("任务 {} 的 every 无效: {}", job.name, every))?)),
+            (None, Some(cron)) => Ok(Schedule::Cron(CronSpec::parse(cron).with_context(|| format!("任务 {} 的 cron 无效: {}", job.name, cron))?)),
+            (None, None) => bail!("任务 {} 需要设置 every 或 cron", job.name),
+        }
+    }
+
+    pub fn next_after(&self, t: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Schedule::Every(d) => Some(t + ChronoDuration::from_std(*d).ok()?),
+            Schedule::Cron(c) => c.next_after(t),
+        }
+    }
+}
+
+/// Classic 5-field cron: minute hour day-of-month month day-of-week.
+/// Fields accept `*`, `*/n`, `a-b`, `a-b/n` and comma lists; day-of-week 0 and 7 are Sunday.
+/// As in Vixie cron, a day field starting with `*` (or covering every day) is unrestricted:
+/// the other day field alone decides, ANDed with any step it has, while two restricted day
+/// fields match on either.
+#[derive(Debug, Clone)]
+pub struct CronSpec {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    days_any: bool,
+    weekdays_any: bool,
+}
+
+impl CronSpec {
+    pub fn parse(expr: &str) -> Result<CronSpec> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            bail!("cron 表达式需要 5 个字段（分 时 日 月 周）");
+        }
+        let days = parse_cron_field(fields[2], 1, 31)?;
+        let mut weekdays = parse_cron_field(fields[4], 0, 7)?;
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        Ok(CronSpec {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            days_any: fields[2].starts_with('*') || days[1..].iter().all(|d| *d),
+            weekdays_any: fields[4].starts_with('*') || weekdays[..7].iter().all(|d| *d),
+            days,
+            weekdays,
+        })
+    }
+
+    fn day_matches(&self, d: NaiveDate) -> bool {
+        if !self.months[d.month() as usize] {
+            return false;
+        }
+        let dom = self.days[d.day() as usize];
+        let dow = self.weekdays[d.weekday().num_days_from_sunday() as usize];
+        // Vixie cron semantics: when both day fields are restricted, either may match
+        if self.days_any || self.weekdays_any {
+            dom && dow
+        } else {
+            dom || dow
+        }
+    }
+
+    pub fn next_after(&self, t: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = (t + ChronoDuration::minutes(1)).with_second(0)?.with_nanosecond(0)?.naive_local();
+        let mut day = start.date();
+        // Day by day: any 8 years hold a Feb 29 (1896 to 1904 skips 1900), so every
+        // satisfiable expression, `0 0 29 2 *` included, is found within them
+        for _ in 0..(8 * 366) {
+            if self.day_matches(day) {
+                let (from_hour, from_minute) = if day == start.date() { (start.hour(), start.minute()) } else { (0, 0) };
+                for h in (from_hour..24).filter(|h| self.hours[*h as usize]) {
+                    let first = if h == from_hour { from_minute } else { 0 };
+                    for m in (first..60).filter(|m| self.minutes[*m as usize]) {
+                        // A time skipped by a DST change doesn't exist that day
+                        if let Some(at) = Local.from_local_datetime(&day.and_hms_opt(h, m, 0)?).earliest() {
+                            return Some(at);
+                        }
+                    }
+                }
+            }
+            day = day.succ_opt()?;
+        }
+        None
+    }
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>> {
+    let mut set = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().context("步长无效")?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("步长不能为 0");
+        }
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (a.parse::<u32>().context("范围无效")?, b.parse::<u32>().context("范围无效")?)
+        } else {
+            let v = range.parse::<u32>().with_context(|| format!("无法解析: {}", range))?;
+            // `5/15` means "from 5, every 15"
+            (v, if part.contains('/') { max } else { v })
+        };
+        if lo < min || hi > max || lo > hi {
+            bail!("取值超出范围 {}-{}: {}", min, max, part);
+        }
+        let mut v = lo;
+        while v <= hi {
+            set[v as usize] = true;
+            v += step;
+        }
+    }
+    Ok(set)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JobState {
+    last_run: Option<String>,
+    seen: Vec<String>,
+    /// `new_releases` recorded its baseline; until then a run notifies nothing. Missing in
+    /// state files from before it existed, where a job with seen codes had its baseline.
+    #[serde(default)]
+    initialized: bool,
+}
+
+/// Ordered so the state file only changes where a job did
+type DaemonState = BTreeMap<String, JobState>;
+
+const STATE_NAME: &str = "daemon_state";
+
+struct Logger {
+    file: Option<std::fs::File>,
+}
+
+impl Logger {
+    fn log(&mut self, msg: &str) {
+        let line = format!("[{}] {}", Local::now().format("%Y-%m-%d %H:%M:%S"), msg);
+        util::note(&line);
+        if let Some(f) = self.file.as_mut() {
+            let _ = writeln!(f, "{}", line);
+        }
+    }
+}
+
+/// Take the single-instance lock; held for as long as the returned file stays open.
+fn acquire_lock() -> Result<std::fs::File> {
+    let dir = store::data_dir();
+    std::fs::create_dir_all(&dir).with_context(|| format!("创建数据目录失败: {}", dir.display()))?;
+    let path = dir.join("daemon.lock");
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("无法打开锁文件: {}", path.display()))?;
+    if file.try_lock().is_err() {
+        bail!("已有 av daemon 在运行（锁文件: {}）", path.display());
+    }
+    file.set_len(0)?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(file)
+}
+
+pub async fn run(once: bool) -> Result<()> {
+    let cfg = &config::get().daemon;
+    if cfg.jobs.is_empty() {
+        bail!("未配置任何任务（见配置文件 [[daemon.jobs]]）");
+    }
+    let schedules = cfg
+        .jobs
+        .iter()
+        .map(|j| Schedule::from_job(j).map(|s| (j, s)))
+        .collect::<Result<Vec<_>>>()?;
+    let _lock = acquire_lock()?;
+    let file = match &cfg.log_file {
+        Some(p) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(p)
+                .with_context(|| format!("无法打开日志文件: {}", p.display()))?,
+        ),
+        None => None,
+    };
+    let mut logger = Logger { file };
+    if let Some(bind) = cfg.metrics_bind.clone() {
+        let listener_bind = bind.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(&listener_bind).await {
+                eprintln!("[WARN] {:#}", e);
+            }
+        });
+        logger.log(&format!("metrics 已启动: http://{}/metrics", bind));
+    }
+    let mut state: DaemonState = store::load(STATE_NAME)?;
+
+    if once {
+        for (job, _) in &schedules {
+            if cancel::is_cancelled() {
+                break;
+            }
+            run_and_log(job, &mut state, &mut logger).await;
+        }
+        return Ok(());
+    }
+
+    let now = Local::now();
+    let mut next: Vec<Option<DateTime<Local>>> = schedules
+        .iter()
+        .map(|(job, s)| if job.run_on_start { Some(now) } else { s.next_after(now) })
+        .collect();
+    logger.log(&format!("daemon 已启动，共 {} 个任务", schedules.len()));
+    for ((job, _), at) in schedules.iter().zip(&next) {
+        match at {
+            Some(at) => logger.log(&format!("任务 {} 下次运行: {}", job.name, at.format("%Y-%m-%d %H:%M"))),
+            None => logger.log(&format!("任务 {} 没有下次运行时间（cron 表达式永远不会触发？），不会运行", job.name)),
+        }
+    }
+
+    loop {
+        let Some((idx, at)) = next
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| t.map(|t| (i, t)))
+            .min_by_key(|(_, t)| *t)
+        else {
+            bail!("没有可调度的任务（cron 表达式永远不会触发？）");
+        };
+        let wait = (at - Local::now()).to_std().unwrap_or(Duration::ZERO);
+        let token = cancel::token();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = token.cancelled() => {}
+        }
+        if cancel::is_cancelled() {
+            logger.log("daemon 已停止");
+            return Ok(());
+        }
+        let (job, schedule) = &schedules[idx];
+        run_and_log(job, &mut state, &mut logger).await;
+        next[idx] = schedule.next_after(Local::now());
+        if next[idx].is_none() {
+            logger.log(&format!("任务 {} 没有下次运行时间，已停止调度", job.name));
+        }
+    }
+}
+
+async fn run_and_log(job: &JobConfig, state: &mut DaemonState, logger: &mut Logger) {
+    logger.log(&format!("运行任务 {}", job.name));
+    let result = run_job(job, state).await;
+    metrics::record_job(&job.name, result.is_ok());
+    match result {
+        Ok(summary) => logger.log(&format!("任务 {} 完成: {}", job.name, summary)),
+        Err(e) => logger.log(&format!("任务 {} 失败: {:#}", job.name, e)),
+    }
+    state.entry(job.name.clone()).or_default().last_run = Some(Local::now().to_rfc3339());
+    if util::is_dry_run() {
+        return;
+    }
+    if let Err(e) = store::save(STATE_NAME, state) {
+        logger.log(&format!("保存 daemon 状态失败: {:#}", e));
+    }
+}
+
+async fn run_job(job: &JobConfig, state: &mut DaemonState) -> Result<String> {
+    match job.kind {
+        JobKind::NewReleases => check_new_releases(job, state).await,
+        JobKind::Rss => {
+            let out = job.out.as_ref().with_context(|| format!("rss 任务 {} 需要设置 out", job.name))?;
+            let opts = FeedOptions { limit: job.limit, actors: job.actors.clone(), uncen: job.uncen };
+            let body = feed::build(&opts, job.format).await?;
+            // A cancelled build holds only part of the items; keep the previous file
+            cancel::check()?;
+            if util::dry_run_skip(format!("将写入 {}（{} 字节）", out.display(), body.len())) {
+                return Ok(format!("未写入 {}（dry-run）", out.display()));
+            }
+            std::fs::write(out, body).with_context(|| format!("写入订阅文件失败: {}", out.display()))?;
+            Ok(format!("已写入 {}", out.display()))
+        }
+        JobKind::TopSnapshot => {
+            let items = scraper::top(job.limit).await?;
+            let n = trending::snapshot(&items).await?;
+            Ok(format!("已保存榜单快照（{} 部）", n))
+        }
+        JobKind::Command => {
+            if job.args.is_empty() {
+                bail!("command 任务 {} 需要设置 args", job.name);
+            }
+            if util::dry_run_skip(format!("将运行 av {}", job.args.join(" "))) {
+                return Ok(format!("未运行 av {}（dry-run）", job.args.join(" ")));
+            }
+            let exe = std::env::current_exe().context("无法确定当前可执行文件路径")?;
+            let status = tokio::process::Command::new(exe)
+                .args(&job.args)
+                .status()
+                .await
+                .context("启动子命令失败")?;
+            if !status.success() {
+                bail!("av {} 退出码: {:?}", job.args.join(" "), status.code());
+            }
+            Ok(format!("av {}", job.args.join(" ")))
+        }
+    }
+}
+
+/// Diff the newest listing (and configured actors) against codes seen on earlier runs and
+/// notify about the new ones. The very first run only records what is there.
+async fn check_new_releases(job: &JobConfig, state: &mut DaemonState) -> Result<String> {
+    let mut codes = Vec::new();
+    let mut failed_actors = Vec::new();
+    for (actor, listed) in scraper::list_actors_titles(&job.actors).await {
+        match listed {
+            Ok(items) => codes.extend(items.into_iter().filter(|i| !job.uncen || util::looks_uncensored(&i.title)).map(|i| i.code)),
+            Err(e) => {
+                util::debug(format!("daemon: actor {} failed: {}", actor, e));
+                failed_actors.push(actor);
+            }
+        }
+    }
+    if job.actors.is_empty() || job.include_top {
+        let top = scraper::top(job.limit).await?;
+        codes.extend(top.into_iter().filter(|i| !job.uncen || util::looks_uncensored(&i.title)).map(|i| i.code));
+    }
+    let mut uniq = HashSet::new();
+    codes.retain(|c| uniq.insert(c.clone()));
+
+    let entry = state.entry(job.name.clone()).or_default();
+    let first_run = !entry.initialized && entry.seen.is_empty();
+    if first_run {
+        // A baseline missing an actor's works would announce all of them on the next run
+        if !failed_actors.is_empty() {
+            bail!("首次运行未能获取演员 {} 的作品，稍后重试", failed_actors.join("、"));
+        }
+        cancel::check()?;
+    }
+    let seen: HashSet<&String> = entry.seen.iter().collect();
+    let fresh: Vec<String> = codes.iter().filter(|c| !seen.contains(c)).cloned().collect();
+
+    let mut notified = Vec::new();
+    if !first_run && !fresh.is_empty() {
+        for detail in scraper::fetch_details(fresh.clone()).await {
+            let code = detail.code.clone();
+            notify::emit(&notify::Event::NewRelease { source: job.name.clone(), detail: Box::new(detail) }).await;
+            notified.push(code);
+        }
+    }
+    // Only announced codes count as seen: those cut off by a cancel or whose detail failed
+    // come up again next run
+    if first_run {
+        entry.seen.extend(fresh.iter().cloned());
+        entry.initialized = true;
+    } else {
+        entry.seen.extend(notified.iter().cloned());
+    }
+    if entry.seen.len() > SEEN_LIMIT {
+        let drop = entry.seen.len() - SEEN_LIMIT;
+        entry.seen.drain(0..drop);
+    }
+    Ok(if first_run {
+        format!("首次运行，记录 {} 个番号", fresh.len())
+    } else {
+        format!("发现 {} 个新番号，已通知 {} 个", fresh.len(), notified.len())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    fn next(expr: &str, from: DateTime<Local>) -> DateTime<Local> {
+        CronSpec::parse(expr).unwrap().next_after(from).unwrap()
+    }
+
+    #[test]
+    fn cron_fields() {
+        // 2024-06-01 is a Saturday
+        let sat = at(2024, 6, 1, 10, 7);
+        for (expr, want) in [
+            ("* * * * *", at(2024, 6, 1, 10, 8)),
+            ("*/15 * * * *", at(2024, 6, 1, 10, 15)),
+            ("5/20 * * * *", at(2024, 6, 1, 10, 25)),
+            ("0 4 * * *", at(2024, 6, 2, 4, 0)),
+            ("30 9-17/4 * * *", at(2024, 6, 1, 13, 30)),
+            ("0 0 1,15 * *", at(2024, 6, 15, 0, 0)),
+            ("0 0 * 8 *", at(2024, 8, 1, 0, 0)),
+            ("30 4 * * 1", at(2024, 6, 3, 4, 30)),
+            ("0 12 * * 7", at(2024, 6, 2, 12, 0)),
+            ("0 12 * * 0", at(2024, 6, 2, 12, 0)),
+        ] {
+            assert_eq!(next(expr, sat), want, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn cron_day_fields() {
+        let sat = at(2024, 6, 1, 10, 7);
+        // Both restricted: the 10th or any Monday, whichever comes first
+        assert_eq!(next("0 0 10 * 1", sat), at(2024, 6, 3, 0, 0));
+        assert_eq!(next("0 0 2 * 1", sat), at(2024, 6, 2, 0, 0));
+        // A stepped `*` still counts as unrestricted: odd days that are Mondays
+        assert_eq!(next("0 0 */2 * 1", sat), at(2024, 6, 3, 0, 0));
+        assert_eq!(next("0 0 */2 * 2", sat), at(2024, 6, 11, 0, 0));
+        // A range over every day is as good as `*`
+        assert_eq!(next("0 0 1-31 * 1", sat), at(2024, 6, 3, 0, 0));
+        assert_eq!(next("0 0 5 * 0-6", sat), at(2024, 6, 5, 0, 0));
+        // Leap days are years apart: 2097-2103 have none (2100 isn't a leap year)
+        assert_eq!(next("0 0 29 2 *", sat), at(2028, 2, 29, 0, 0));
+        assert_eq!(next("0 0 29 2 *", at(2096, 3, 1, 0, 0)), at(2104, 2, 29, 0, 0));
+        // Never satisfiable
+        assert!(CronSpec::parse("0 0 31 2 *").unwrap().next_after(sat).is_none());
+    }
+
+    #[test]
+    fn cron_rejects() {
+        for expr in ["* * * *", "* * * * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *", "* * * * 8", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            assert!(CronSpec::parse(expr).is_err(), "{}", expr);
+        }
+    }
+}
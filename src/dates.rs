@@ -0,0 +1,118 @@
+//! Release dates for people: `--date-format` / `[view] date_format` picks ISO
+//! (`2024-06-01`, the default), the metadata language's own form (`2024年6月1日`,
+//! `Jun 1, 2024`) or relative to today (`3 天前`). JSON, NFO and feeds always keep the
+//! ISO date the sources give.
+
+use chrono::{Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use crate::config::{self, MetadataLang};
+use crate::types::AvItem;
+use crate::util;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DateFormat {
+    /// 2024-06-01
+    #[default]
+    Iso,
+    /// 按元数据语言（--lang）：2024年6月1日 / Jun 1, 2024
+    Local,
+    /// 相对今天：3 天前、2 个月后
+    Relative,
+}
+
+/// `--date-format` from the command line
+static OVERRIDE: Mutex<Option<DateFormat>> = Mutex::new(None);
+
+pub fn set_format(format: Option<DateFormat>) {
+    *OVERRIDE.lock().unwrap() = format;
+}
+
+fn format() -> DateFormat {
+    OVERRIDE.lock().unwrap().or(config::get().view.date_format).unwrap_or_default()
+}
+
+/// `2024-06-01`, `2024/6/1` or `2024.06.01`, with anything after the date (a time) ignored
+pub fn parse(s: &str) -> Option<NaiveDate> {
+    let s = s.trim();
+    let date = s.split([' ', 'T']).next()?;
+    let mut parts = date.split(['-', '/', '.']).map(|p| p.parse::<u32>().ok());
+    let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(y as i32, m, d)
+}
+
+/// `--from` / `--to` as a clap value parser
+pub fn parse_arg(s: &str) -> Result<NaiveDate, String> {
+    parse(s).ok_or_else(|| format!("无效的日期: {}（如 2024-01-01）", s))
+}
+
+/// `--from` .. `--to`, both ends included
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn is_set(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|f| date >= f) && self.to.is_none_or(|t| date <= t)
+    }
+
+    /// The items released within the range; undated ones can't be placed and are dropped
+    /// with a warning
+    pub fn filter(&self, items: Vec<AvItem>) -> Vec<AvItem> {
+        if !self.is_set() {
+            return items;
+        }
+        let total = items.len();
+        let (dated, undated): (Vec<AvItem>, Vec<AvItem>) =
+            items.into_iter().partition(|i| i.release_date.as_deref().and_then(parse).is_some());
+        if !undated.is_empty() {
+            eprintln!("[WARN] {}/{} 条没有发行日期，已略过", undated.len(), total);
+        }
+        dated.into_iter().filter(|i| i.release_date.as_deref().and_then(parse).is_some_and(|d| self.contains(d))).collect()
+    }
+}
+
+/// "3 天前" / "2 个月后"; whole days, then months and years once they are more than that
+fn relative(date: NaiveDate, today: NaiveDate) -> String {
+    let days = (date - today).num_days();
+    let n = days.unsigned_abs();
+    let span = match n {
+        0 => return "今天".to_string(),
+        1 if days < 0 => return "昨天".to_string(),
+        1 => return "明天".to_string(),
+        2..=30 => format!("{} 天", n),
+        31..=364 => format!("{} 个月", n / 30),
+        _ => format!("{} 年", n / 365),
+    };
+    format!("{}{}", span, if days < 0 { "前" } else { "后" })
+}
+
+fn localized(date: NaiveDate) -> String {
+    match util::lang() {
+        Some(MetadataLang::En) => date.format("%b %-d, %Y").to_string(),
+        // Chinese and Japanese write dates the same way; the UI is Chinese otherwise
+        _ => format!("{}年{}月{}日", date.year(), date.month(), date.day()),
+    }
+}
+
+/// `date` as the user asked for it; text that isn't a date is shown as it is
+pub fn render(date: &str) -> String {
+    let fmt = format();
+    let Some(parsed) = parse(date).filter(|_| fmt != DateFormat::Iso) else { return date.to_string() };
+    match fmt {
+        DateFormat::Iso => date.to_string(),
+        DateFormat::Local => localized(parsed),
+        DateFormat::Relative => relative(parsed, Local::now().date_naive()),
+    }
+}
@@ -0,0 +1,424 @@
+use anyhow::{bail, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::config::{self, Config};
+use crate::doh;
+use crate::http;
+use crate::metrics::FetchOutcome;
+use crate::mirrors;
+use crate::platform;
+use crate::store;
+use crate::ua;
+use crate::util;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const PROXY_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Subcommand, Debug)]
+pub enum Check {
+    /// 通过当前代理设置访问每个数据源，报告延迟并给出修复建议
+    Proxy,
+}
+
+#[derive(Debug, Serialize)]
+struct ProxyReport {
+    source: String,
+    url: String,
+    /// Proxy in use, `None` for a direct connection
+    proxy: Option<String>,
+    ok: bool,
+    status: Option<u16>,
+    latency_ms: Option<u64>,
+    error: Option<String>,
+    hints: Vec<String>,
+}
+
+fn sources() -> Vec<(&'static str, String)> {
+    let mut sources = vec![
+        ("javdb", format!("{}/", mirrors::current())),
+        ("sukebei", "https://sukebei.nyaa.si/".to_string()),
+        ("javlibrary", "https://www.javlibrary.com/".to_string()),
+        ("sokmil", "https://www.sokmil.com/".to_string()),
+        ("dmm", "https://api.dmm.com/".to_string()),
+        ("btsow", format!("{}/", crate::sources::btsow::base())),
+        ("torrentkitty", format!("{}/", crate::sources::torrentkitty::base())),
+    ];
+    if crate::sources::theporndb::enabled() {
+        sources.push(("theporndb", format!("{}/", config::get().sources.theporndb.url.trim().trim_end_matches('/'))));
+    }
+    sources
+}
+
+/// Whether anything is listening on the proxy's host:port at all
+async fn proxy_listening(proxy: &reqwest::Url) -> bool {
+    let Some(host) = proxy.host_str() else { return false };
+    // socks URLs have no registered default port; 1080 is what reqwest assumes
+    let port = proxy.port_or_known_default().unwrap_or(1080);
+    matches!(
+        tokio::time::timeout(PROXY_CONNECT_TIMEOUT, tokio::net::TcpStream::connect((host, port))).await,
+        Ok(Ok(_))
+    )
+}
+
+async fn check_source(source: &str, url: &str) -> ProxyReport {
+    let mut report = ProxyReport {
+        source: source.to_string(),
+        url: url.to_string(),
+        proxy: http::configured_route(source),
+        ok: false,
+        status: None,
+        latency_ms: None,
+        error: None,
+        hints: Vec::new(),
+    };
+
+    let mut proxy_url = None;
+    if let Some(p) = report.proxy.clone() {
+        match http::parse_proxy(&p) {
+            Ok(u) => proxy_url = Some(u),
+            Err(e) => {
+                if http::is_socks(&p) && !cfg!(feature = "socks") {
+                    report.hints.push("当前构建不含 SOCKS 支持：cargo install --features socks，或改用 http:// 代理".to_string());
+                } else {
+                    report.hints.push("检查代理地址格式，如 socks5h://127.0.0.1:1080 或 http://127.0.0.1:7890".to_string());
+                }
+                report.error = Some(format!("{:#}", e));
+                return report;
+            }
+        }
+        if p.trim().to_lowercase().starts_with("socks5://") {
+            report.hints.push(format!("{} 在本地解析域名，可能泄露 DNS 或遭到污染；建议改用 socks5h://", p));
+        }
+    }
+
+    let client = http::with_proxy(reqwest::Client::builder())
+        .default_headers(ua::navigation_headers(ua::session_profile()))
+        .timeout(TIMEOUT)
+        .build();
+    let client = match client {
+        Ok(c) => c,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    util::debug(format!("doctor: {} via {}", url, report.proxy.as_deref().unwrap_or("direct")));
+    let started = Instant::now();
+    let result = client.get(url).send().await;
+    let elapsed = started.elapsed();
+    let outcome = FetchOutcome::of(&result);
+    match result {
+        Ok(resp) => {
+            let status = resp.status().as_u16();
+            report.status = Some(status);
+            report.latency_ms = Some(elapsed.as_millis() as u64);
+            // Any answer below 500 proves connectivity (dmm's API root is a 4xx, for example)
+            report.ok = !matches!(outcome, FetchOutcome::Blocked) && status < 500;
+            if matches!(outcome, FetchOutcome::Blocked) {
+                report.hints.push(format!(
+                    "HTTP {}：可能被 Cloudflare 或地区限制拦截；尝试更换代理出口，或用 av cookies import 导入浏览器 Cookie",
+                    status
+                ));
+            }
+        }
+        Err(e) => {
+            report.error = Some(if e.is_timeout() { format!("{}s 内无响应", TIMEOUT.as_secs()) } else { format!("{:#}", anyhow::Error::from(e)) });
+            match &proxy_url {
+                Some(p) if !proxy_listening(p).await => {
+                    report.hints.push(format!("无法连接到代理 {}，确认代理程序正在运行且端口正确", p));
+                }
+                Some(_) => report.hints.push("代理可连接但请求失败：检查代理的出站规则或更换节点".to_string()),
+                None => report.hints.push(format!(
+                    "直连失败，{} 可能在当前网络被屏蔽；在 [network] proxy 或 [network.proxies] {} 中配置代理",
+                    source, source
+                )),
+            }
+            if source == "javdb" && mirrors::list().len() < 2 {
+                report.hints.push("也可以在 [javdb] mirrors 中配置备用镜像".to_string());
+            }
+        }
+    }
+    report
+}
+
+async fn proxy(json: bool) -> Result<()> {
+    let mut reports = Vec::new();
+    for (source, url) in sources() {
+        let report = check_source(source, &url).await;
+        if !json {
+            let route = report.proxy.clone().unwrap_or_else(|| "直连".to_string());
+            let state = match (report.ok, report.status, report.latency_ms) {
+                (true, Some(s), Some(ms)) => format!("{} HTTP {} {}ms", "OK".green(), s, ms),
+                (false, Some(s), _) => format!("{} HTTP {}", "失败".red(), s),
+                _ => format!("{} {}", "失败".red(), report.error.as_deref().unwrap_or("")),
+            };
+            println!("{:<11} {:<32} {}", report.source.bold(), route, state);
+            for hint in &report.hints {
+                println!("  {} {}", "→".yellow(), hint);
+            }
+        }
+        reports.push(report);
+    }
+    if json {
+        util::print_output(&reports, true);
+    }
+    let failed = reports.iter().filter(|r| !r.ok).count();
+    if failed > 0 {
+        bail!("{} 个数据源无法访问", failed);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    /// Works, but something is missing or likely to bite later
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+struct Item {
+    group: &'static str,
+    name: String,
+    status: Status,
+    detail: String,
+    hint: Option<String>,
+}
+
+impl Item {
+    fn new(group: &'static str, name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self { group, name: name.into(), status, detail: detail.into(), hint: None }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// `(program, what needs it, install hint)`
+const TOOLS: &[(&str, &str, &str)] = &[
+    ("aria2c", "av get 下载、--full 流水线、磁力回退", "brew install aria2 / apt install aria2 / scoop install aria2"),
+    ("ffmpeg", "av trailer --download、av rip、av embed、av thumbs", "brew install ffmpeg / apt install ffmpeg / scoop install ffmpeg"),
+    ("ffprobe", "av verify、av thumbs", "随 ffmpeg 安装"),
+    ("mpv", "av view --player mpv", "brew install mpv / apt install mpv / scoop install mpv"),
+    ("vlc", "av view --player vlc", "https://www.videolan.org/vlc/"),
+];
+
+fn tools(cfg: &Config) -> Vec<Item> {
+    let mut items: Vec<Item> = TOOLS
+        .iter()
+        .map(|&(program, used_by, install)| match platform::find_program(program) {
+            Some(path) => Item::new("tools", program, Status::Ok, path.display().to_string()),
+            None => {
+                // A player that's configured as the default has to be there
+                let required = cfg.view.player.is_some_and(|p| format!("{:?}", p).eq_ignore_ascii_case(program));
+                let status = if required { Status::Fail } else { Status::Warn };
+                Item::new("tools", program, status, format!("未找到（用于 {}）", used_by)).hint(format!("安装: {}", install))
+            }
+        })
+        .collect();
+    if cfg.update.minisign_pubkey.is_some() {
+        items.push(match platform::find_program("minisign") {
+            Some(path) => Item::new("tools", "minisign", Status::Ok, path.display().to_string()),
+            None => Item::new("tools", "minisign", Status::Fail, "已配置 [update] minisign_pubkey 但未找到 minisign")
+                .hint("安装: brew install minisign / scoop install minisign，否则 av update 会拒绝安装"),
+        });
+    }
+    if let Some(setting) = cfg.network.impersonate.as_deref().filter(|s| !s.trim().is_empty()) {
+        #[cfg(feature = "impersonate")]
+        items.push(match crate::impersonate::resolve(setting) {
+            Ok(path) => Item::new("tools", "curl-impersonate", Status::Ok, path.display().to_string()),
+            Err(e) => Item::new("tools", "curl-impersonate", Status::Fail, format!("{:#}", e))
+                .hint("安装 curl-impersonate: https://github.com/lexiforest/curl-impersonate/releases"),
+        });
+        #[cfg(not(feature = "impersonate"))]
+        items.push(
+            Item::new("tools", "curl-impersonate", Status::Fail, format!("已配置 [network] impersonate = \"{}\"，但编译时未启用 impersonate 功能", setting))
+                .hint("cargo install --features impersonate"),
+        );
+    }
+    items
+}
+
+/// Dotted paths of keys in `file` that `Config` doesn't know, which serde silently ignores.
+/// Free-form tables (aliases, selectors, unset optional sections) aren't descended into.
+fn unknown_keys(file: &toml::Table, known: &Value, prefix: &str, out: &mut Vec<String>) {
+    let Some(known) = known.as_object() else { return };
+    for (key, value) in file {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match known.get(key) {
+            None => out.push(path),
+            Some(k) if k.as_object().is_some_and(|o| !o.is_empty()) => {
+                if let Some(t) = value.as_table() {
+                    unknown_keys(t, k, &path, out);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+fn config_file() -> Vec<Item> {
+    let Some(path) = config::config_path() else {
+        return vec![Item::new("config", "config.toml", Status::Warn, "无法确定配置目录，使用默认设置").hint("设置 AV_CONFIG 指向配置文件")];
+    };
+    let name = path.display().to_string();
+    if !path.exists() {
+        return vec![Item::new("config", name, Status::Ok, "不存在，使用默认设置")];
+    }
+    let text = match std::fs::read_to_string(&path) {
+        Ok(t) => t,
+        Err(e) => return vec![Item::new("config", name, Status::Fail, format!("无法读取: {}", e)).hint("检查文件权限")],
+    };
+    // "（第 3 行）" where the parser stopped
+    let at = |e: &toml::de::Error| e.span().map(|s| format!("（第 {} 行）", text[..s.start].matches('\n').count() + 1)).unwrap_or_default();
+    let table: toml::Table = match toml::from_str(&text) {
+        Ok(t) => t,
+        Err(e) => {
+            return vec![Item::new("config", name, Status::Fail, format!("TOML 语法错误{}: {}", at(&e), e.message().trim()))
+                .hint("修正语法后重试；启动其他命令时也会因此报错")];
+        }
+    };
+    if let Err(e) = toml::from_str::<Config>(&text) {
+        return vec![Item::new("config", name, Status::Fail, format!("配置项无效{}: {}", at(&e), e.message().trim()))
+            .hint("对照 README 的 Configuration 一节检查取值和类型")];
+    }
+    let mut unknown = Vec::new();
+    unknown_keys(&table, &serde_json::to_value(Config::default()).unwrap_or_default(), "", &mut unknown);
+    if unknown.is_empty() {
+        vec![Item::new("config", name, Status::Ok, "语法正确")]
+    } else {
+        vec![Item::new("config", name, Status::Warn, format!("未知配置项（会被忽略）: {}", unknown.join(", "))).hint("检查拼写，或删除不再使用的配置")]
+    }
+}
+
+async fn dns() -> Vec<Item> {
+    let mut items = Vec::new();
+    let overrides = match http::host_overrides() {
+        Ok(o) => o,
+        Err(e) => {
+            items.push(Item::new("dns", "[network.hosts]", Status::Fail, format!("{:#}", e)).hint("每项写成 \"域名或数据源\" = \"IP\" 或 [\"IP\", ...]"));
+            Vec::new()
+        }
+    };
+    for (source, url) in sources() {
+        let Some(host) = reqwest::Url::parse(&url).ok().and_then(|u| u.host_str().map(str::to_string)) else { continue };
+        if let Some((_, addrs)) = overrides.iter().find(|(h, _)| *h == host) {
+            let ips: Vec<String> = addrs.iter().map(|a| a.ip().to_string()).collect();
+            items.push(Item::new("dns", &host, Status::Ok, format!("{}（[network.hosts]）", ips.join(", "))));
+            continue;
+        }
+        // socks5h and HTTP proxies resolve the name themselves
+        let proxied = http::configured_route(source).is_some_and(|p| !p.trim().to_lowercase().starts_with("socks5://"));
+        let doh = doh::resolver();
+        let lookup = async {
+            match &doh {
+                Some(r) => r.lookup(&host).await.map_err(|e| format!("{:#}", e)),
+                None => tokio::net::lookup_host((host.as_str(), 443)).await.map(|a| a.map(|a| a.ip()).collect()).map_err(|e| e.to_string()),
+            }
+        };
+        let via = if doh.is_some() { "（DoH）" } else { "" };
+        let item = match tokio::time::timeout(DNS_TIMEOUT, lookup).await {
+            Ok(Ok(ips)) => match ips.first() {
+                Some(ip) if ip.is_loopback() || ip.is_unspecified() => {
+                    Item::new("dns", &host, Status::Warn, format!("解析到 {}{}，可能遭到 DNS 污染", ip, via))
+                        .hint(if doh.is_some() {
+                            "更换 [network] doh 服务器，或使用 socks5h:// 代理让代理端解析"
+                        } else {
+                            "在 [network] doh 中配置 DNS-over-HTTPS，或使用 socks5h:// 代理让代理端解析"
+                        })
+                }
+                Some(ip) => Item::new("dns", &host, Status::Ok, format!("{}{}", ip, via)),
+                None => Item::new("dns", &host, Status::Fail, "没有解析结果"),
+            },
+            Ok(Err(e)) => Item::new("dns", &host, if proxied { Status::Warn } else { Status::Fail }, format!("无法解析{}: {}", via, e)),
+            Err(_) => Item::new("dns", &host, if proxied { Status::Warn } else { Status::Fail }, format!("{}s 内无响应", DNS_TIMEOUT.as_secs())),
+        };
+        let item = if item.status == Status::Ok || item.hint.is_some() {
+            item
+        } else if proxied {
+            item.hint("已通过代理访问，本地解析失败不影响使用")
+        } else {
+            item.hint(format!("检查网络与 DNS 设置，配置 [network] doh，或在 [network.proxies] {} 中配置 socks5h:// 代理", source))
+        };
+        items.push(item);
+    }
+    items
+}
+
+/// Create `dir` if needed and write and remove a probe file in it
+fn writable(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(".av-doctor");
+    std::fs::write(&probe, b"ok")?;
+    std::fs::remove_file(probe)
+}
+
+fn dirs(cfg: &Config) -> Vec<Item> {
+    let mut dirs: Vec<(&str, PathBuf)> = vec![("data_dir", store::data_dir())];
+    if let Some(d) = &cfg.pipeline.download_dir {
+        dirs.push(("[pipeline] download_dir", d.clone()));
+    }
+    if let Some(d) = &cfg.pipeline.library_dir {
+        dirs.push(("[pipeline] library_dir", d.clone()));
+    }
+    dirs.into_iter()
+        .map(|(what, dir)| match writable(&dir) {
+            Ok(()) => Item::new("dirs", what, Status::Ok, dir.display().to_string()),
+            Err(e) => Item::new("dirs", what, Status::Fail, format!("{} 不可写: {}", dir.display(), e))
+                .hint(if what.starts_with('[') { "检查目录权限，或修改 config.toml 中的路径" } else { "检查目录权限，或设置 AV_DATA_DIR 指向可写目录" }),
+        })
+        .collect()
+}
+
+/// `av doctor`: tools, config, DNS and writable directories
+async fn env(json: bool) -> Result<()> {
+    let cfg = config::get();
+    let mut items = tools(cfg);
+    items.extend(config_file());
+    items.extend(dns().await);
+    items.extend(dirs(cfg));
+
+    if json {
+        util::print_output(&items, true);
+    } else {
+        let mut group = "";
+        for item in &items {
+            if item.group != group {
+                group = item.group;
+                println!("{}", group.bold());
+            }
+            let mark = match item.status {
+                Status::Ok => "✓".green(),
+                Status::Warn => "!".yellow(),
+                Status::Fail => "✗".red(),
+            };
+            println!("  {} {:<24} {}", mark, item.name, item.detail);
+            if let Some(hint) = &item.hint {
+                println!("    {} {}", "→".yellow(), hint);
+            }
+        }
+        println!("\n{}", "网络访问可用 av doctor proxy 逐个检查".dimmed());
+    }
+    let failed = items.iter().filter(|i| i.status == Status::Fail).count();
+    if failed > 0 {
+        bail!("{} 项检查未通过", failed);
+    }
+    Ok(())
+}
+
+pub async fn run(check: Option<Check>, json: bool) -> Result<()> {
+    match check {
+        None => env(json).await,
+        Some(Check::Proxy) => proxy(json).await,
+    }
+}
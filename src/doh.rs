@@ -0,0 +1,224 @@
+//! DNS-over-HTTPS (RFC 8484) for every HTTP client, so poisoned local DNS for the source
+//! domains doesn't break lookups. Enabled by `[network] doh`; answers are cached for
+//! their TTL. Names only the local resolver can know (`localhost`, single labels, `.local`,
+//! `.lan`, `.home.arpa`) go to the system resolver, so a NAS or media server on the LAN is
+//! still found. Otherwise, only when the DoH server itself can't be reached (connection, HTTP status,
+//! timeout) is the system resolver used; what the server answers — no such domain, no
+//! records, a refusal — stands, or a blocked name would just go to the poisoned resolver.
+
+use anyhow::{bail, Context, Result};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::http;
+use crate::util;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Cache bounds for the answers' TTL
+const MIN_TTL: u64 = 60;
+const MAX_TTL: u64 = 3600;
+
+/// Cheap to clone; clones share the cache
+#[derive(Clone)]
+pub struct DohResolver(Arc<Inner>);
+
+struct Inner {
+    url: String,
+    /// Reaches the DoH server itself: proxied like everything else, but resolving the
+    /// server's own name (if it isn't an IP) through the system
+    client: reqwest::Client,
+    cache: Mutex<HashMap<String, (Vec<IpAddr>, Instant)>>,
+}
+
+/// The shared resolver when `[network] doh` is set
+static RESOLVER: LazyLock<Option<DohResolver>> = LazyLock::new(|| {
+    let url = config::get().network.doh.clone().filter(|u| !u.trim().is_empty())?;
+    let client = http::with_routes(reqwest::Client::builder()).timeout(TIMEOUT).build().ok()?;
+    Some(DohResolver(Arc::new(Inner { url: url.trim().to_string(), client, cache: Mutex::new(HashMap::new()) })))
+});
+
+pub fn resolver() -> Option<DohResolver> {
+    RESOLVER.clone()
+}
+
+/// Why a lookup gave no addresses
+#[derive(Debug)]
+pub enum Failure {
+    /// The DoH server couldn't be asked; the system resolver may try instead
+    Unreachable(anyhow::Error),
+    /// It answered (NXDOMAIN, no records, an error code) or sent something unreadable
+    Answered(anyhow::Error),
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Unreachable(e) | Failure::Answered(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+/// A wire-format query for `name`
+fn query(name: &str, qtype: u16) -> Result<Vec<u8>> {
+    // id 0 (RFC 8484 §4.1, cache friendly), recursion desired, one question
+    let mut q = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("域名无效: {}", name);
+        }
+        q.push(label.len() as u8);
+        q.extend_from_slice(label.as_bytes());
+    }
+    q.push(0);
+    q.extend_from_slice(&qtype.to_be_bytes());
+    q.extend_from_slice(&1u16.to_be_bytes());
+    Ok(q)
+}
+
+/// Offset just past the (possibly compressed) name at `pos`
+fn skip_name(buf: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *buf.get(pos)? as usize;
+        match len {
+            0 => return Some(pos + 1),
+            l if l & 0xC0 == 0xC0 => return Some(pos + 2),
+            l => pos += 1 + l,
+        }
+    }
+}
+
+fn u16_at(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*buf.get(pos)?, *buf.get(pos + 1)?]))
+}
+
+/// A/AAAA addresses in a response and the smallest TTL among them
+fn parse(buf: &[u8]) -> Result<(Vec<IpAddr>, u64)> {
+    let flags = u16_at(buf, 2).context("DoH 响应过短")?;
+    match flags & 0x000F {
+        0 => {}
+        3 => bail!("域名不存在"),
+        rcode => bail!("DoH 服务器返回错误码 {}", rcode),
+    }
+    let questions = u16_at(buf, 4).context("DoH 响应过短")?;
+    let answers = u16_at(buf, 6).context("DoH 响应过短")?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(buf, pos).context("DoH 响应格式错误")? + 4;
+    }
+    let mut addrs = Vec::new();
+    let mut ttl = MAX_TTL;
+    for _ in 0..answers {
+        pos = skip_name(buf, pos).context("DoH 响应格式错误")?;
+        let rtype = u16_at(buf, pos).context("DoH 响应格式错误")?;
+        let record_ttl = buf.get(pos + 4..pos + 8).context("DoH 响应格式错误")?;
+        let rdlen = u16_at(buf, pos + 8).context("DoH 响应格式错误")? as usize;
+        let data = buf.get(pos + 10..pos + 10 + rdlen).context("DoH 响应格式错误")?;
+        let ip = match (rtype, data.len()) {
+            (TYPE_A, 4) => Some(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+            (TYPE_AAAA, 16) => <[u8; 16]>::try_from(data).ok().map(|b| IpAddr::V6(Ipv6Addr::from(b))),
+            // CNAMEs on the way; the recursive answer carries the final records too
+            _ => None,
+        };
+        if let Some(ip) = ip {
+            addrs.push(ip);
+            ttl = ttl.min(u32::from_be_bytes([record_ttl[0], record_ttl[1], record_ttl[2], record_ttl[3]]) as u64);
+        }
+        pos += 10 + rdlen;
+    }
+    Ok((addrs, ttl))
+}
+
+impl DohResolver {
+    async fn ask(&self, name: &str, qtype: u16) -> std::result::Result<(Vec<IpAddr>, u64), Failure> {
+        let body = query(name, qtype).map_err(Failure::Answered)?;
+        let resp = self
+            .0
+            .client
+            .post(&self.0.url)
+            .header("Content-Type", "application/dns-message")
+            .header("Accept", "application/dns-message")
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("请求 DoH 服务器失败: {}", self.0.url))
+            .map_err(Failure::Unreachable)?;
+        if !resp.status().is_success() {
+            return Err(Failure::Unreachable(anyhow::anyhow!("DoH 服务器返回 HTTP {}", resp.status().as_u16())));
+        }
+        let bytes = resp.bytes().await.context("读取 DoH 响应失败").map_err(Failure::Unreachable)?;
+        parse(&bytes).map_err(Failure::Answered)
+    }
+
+    /// IPv4 first, then IPv6, cached for the shortest TTL. `Unreachable` only when neither
+    /// query got an answer.
+    pub async fn lookup(&self, name: &str) -> std::result::Result<Vec<IpAddr>, Failure> {
+        let key = name.to_lowercase();
+        if let Some((addrs, expires)) = self.0.cache.lock().unwrap().get(&key) {
+            if *expires > Instant::now() {
+                return Ok(addrs.clone());
+            }
+        }
+        let (v4, v6) = tokio::join!(self.ask(name, TYPE_A), self.ask(name, TYPE_AAAA));
+        let (mut addrs, mut ttl) = (Vec::new(), MAX_TTL);
+        let (mut answered, mut answer_err, mut unreachable) = (false, None, None);
+        for answer in [v4, v6] {
+            match answer {
+                Ok((a, t)) => {
+                    answered = true;
+                    if !a.is_empty() {
+                        ttl = ttl.min(t);
+                    }
+                    addrs.extend(a);
+                }
+                Err(Failure::Answered(e)) => answer_err = answer_err.or(Some(e)),
+                Err(Failure::Unreachable(e)) => unreachable = unreachable.or(Some(e)),
+            }
+        }
+        if addrs.is_empty() {
+            return Err(match (answer_err, unreachable) {
+                (Some(e), _) => Failure::Answered(e),
+                (None, Some(e)) if !answered => Failure::Unreachable(e),
+                _ => Failure::Answered(anyhow::anyhow!("{} 没有 A/AAAA 记录", name)),
+            });
+        }
+        util::debug(format!("doh: {} -> {:?} (ttl {}s)", name, addrs, ttl));
+        let expires = Instant::now() + Duration::from_secs(ttl.clamp(MIN_TTL, MAX_TTL));
+        self.0.cache.lock().unwrap().insert(key, (addrs.clone(), expires));
+        Ok(addrs)
+    }
+}
+
+async fn system_lookup(host: String) -> std::io::Result<Vec<SocketAddr>> {
+    tokio::task::spawn_blocking(move || (host.as_str(), 0).to_socket_addrs().map(|a| a.collect())).await?
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let doh = self.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            // A public resolver would only say NXDOMAIN for these
+            if http::is_local_host(&host) {
+                return Ok(Box::new(system_lookup(host).await?.into_iter()) as Addrs);
+            }
+            let addrs: Vec<SocketAddr> = match doh.lookup(&host).await {
+                Ok(ips) => ips.into_iter().map(|ip| SocketAddr::new(ip, 0)).collect(),
+                Err(Failure::Unreachable(e)) => {
+                    util::debug(format!("doh: {} failed ({:#}), using system DNS", host, e));
+                    system_lookup(host).await?
+                }
+                Err(Failure::Answered(e)) => {
+                    util::debug(format!("doh: {}: {:#}", host, e));
+                    return Err(e.context(format!("DoH 解析 {} 失败", host)).into());
+                }
+            };
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
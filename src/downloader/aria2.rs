@@ -0,0 +1,133 @@
+//! aria2's JSON-RPC interface, for `av progress` to watch an aria2c daemon started with
+//! `--enable-rpc`. av's own aria2c runs are foreground processes without RPC; their
+//! progress is on the terminal that ran them.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::{Transfer, TransferState};
+use crate::config::Aria2Config;
+use crate::http;
+
+/// Stopped (finished, failed, removed) downloads listed besides the active and waiting ones
+const STOPPED_SHOWN: u64 = 50;
+
+const KEYS: [&str; 12] = [
+    "gid",
+    "status",
+    "totalLength",
+    "completedLength",
+    "downloadSpeed",
+    "uploadSpeed",
+    "numSeeders",
+    "connections",
+    "infoHash",
+    "seeder",
+    "bittorrent",
+    "files",
+];
+
+pub struct Rpc {
+    cfg: &'static Aria2Config,
+    c: reqwest::Client,
+}
+
+impl Rpc {
+    pub fn new(cfg: &'static Aria2Config) -> Result<Rpc> {
+        let c = http::with_proxy(reqwest::Client::builder()).timeout(Duration::from_secs(20)).build().context("client build")?;
+        Ok(Rpc { cfg, c })
+    }
+
+    async fn call(&self, method: &str, params: Vec<Value>) -> Result<Value> {
+        let mut all = Vec::new();
+        if let Some(secret) = self.cfg.rpc_secret.as_deref().filter(|s| !s.is_empty()) {
+            all.push(Value::String(format!("token:{}", secret)));
+        }
+        all.extend(params);
+        let body = json!({ "jsonrpc": "2.0", "id": "av", "method": method, "params": all });
+        let resp: Value = self
+            .c
+            .post(self.cfg.rpc_url.trim())
+            .json(&body)
+            .send()
+            .await
+            .context("请求 aria2 RPC 失败")?
+            .json()
+            .await
+            .context("aria2 RPC 返回的内容无法解析")?;
+        if let Some(err) = resp.get("error") {
+            bail!("aria2 RPC {} 失败: {}", method, err.get("message").and_then(Value::as_str).unwrap_or("未知错误"));
+        }
+        Ok(resp.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    pub async fn transfers(&self) -> Result<Vec<Transfer>> {
+        let mut out = Vec::new();
+        for (method, mut params) in [
+            ("aria2.tellActive", vec![]),
+            ("aria2.tellWaiting", vec![json!(0), json!(1000)]),
+            ("aria2.tellStopped", vec![json!(0), json!(STOPPED_SHOWN)]),
+        ] {
+            params.push(json!(KEYS));
+            let listed = self.call(method, params).await?;
+            out.extend(listed.as_array().into_iter().flatten().map(transfer));
+        }
+        Ok(out)
+    }
+
+    pub async fn pause(&self, gid: &str) -> Result<()> {
+        self.call("aria2.pause", vec![json!(gid)]).await.map(drop)
+    }
+
+    pub async fn resume(&self, gid: &str) -> Result<()> {
+        self.call("aria2.unpause", vec![json!(gid)]).await.map(drop)
+    }
+
+    /// Stop the download (or forget a stopped one), leaving its files
+    pub async fn remove(&self, gid: &str, state: TransferState) -> Result<()> {
+        let method = match state {
+            TransferState::Complete | TransferState::Error => "aria2.removeDownloadResult",
+            _ => "aria2.remove",
+        };
+        self.call(method, vec![json!(gid)]).await.map(drop)
+    }
+}
+
+/// An entry of `tellActive`/`tellWaiting`/`tellStopped`, which give numbers as strings
+fn transfer(d: &Value) -> Transfer {
+    let text = |key: &str| d.get(key).and_then(Value::as_str).unwrap_or_default();
+    let num = |key: &str| text(key).parse::<u64>().ok();
+    let (size, downloaded, down_speed) = (num("totalLength").unwrap_or(0), num("completedLength").unwrap_or(0), num("downloadSpeed").unwrap_or(0));
+    let state = match text("status") {
+        "active" if text("seeder") == "true" => TransferState::Seeding,
+        "active" if down_speed == 0 => TransferState::Stalled,
+        "active" => TransferState::Downloading,
+        "waiting" => TransferState::Queued,
+        "paused" => TransferState::Paused,
+        "complete" => TransferState::Complete,
+        _ => TransferState::Error,
+    };
+    let name = d
+        .pointer("/bittorrent/info/name")
+        .and_then(Value::as_str)
+        .or_else(|| d.pointer("/files/0/path").and_then(Value::as_str).and_then(|p| p.rsplit('/').next()))
+        .filter(|n| !n.is_empty())
+        .unwrap_or(text("gid"));
+    let seeders = num("numSeeders").map(|n| n as u32);
+    Transfer {
+        id: text("gid").to_string(),
+        infohash: Some(text("infoHash").to_lowercase()).filter(|h| !h.is_empty()),
+        code: String::new(),
+        name: name.to_string(),
+        size,
+        downloaded,
+        down_speed,
+        up_speed: num("uploadSpeed").unwrap_or(0),
+        eta_secs: (down_speed > 0 && size > downloaded).then(|| (size - downloaded) / down_speed),
+        seeders,
+        // `connections` counts the seeders too
+        peers: num("connections").map(|n| (n as u32).saturating_sub(seeders.unwrap_or(0))),
+        state,
+    }
+}
@@ -0,0 +1,130 @@
+//! Deluge through its WebUI's JSON-RPC (`/json`), which also reaches the daemon: log in,
+//! connect the WebUI to a daemon if it isn't yet, add the magnet with its download
+//! location, then label it. The category becomes a label when the Label plugin is on;
+//! Deluge has no tags, so those are left out.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::{DownloadClient, Placement, Templates};
+use crate::config::DelugeConfig;
+use crate::http;
+use crate::keychain::{self, Secret};
+use crate::util;
+
+static REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+fn client() -> Result<reqwest::Client> {
+    http::with_proxy(reqwest::Client::builder())
+        .cookie_store(true)
+        .timeout(Duration::from_secs(20))
+        .build()
+        .context("client build")
+}
+
+/// One JSON-RPC call; Deluge's own errors (`{"error": {"message": ...}}`) become `Err`
+async fn call(c: &reqwest::Client, cfg: &DelugeConfig, method: &str, params: Value) -> Result<Value> {
+    let url = format!("{}/json", cfg.url.trim().trim_end_matches('/'));
+    let id = REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    let resp = c
+        .post(&url)
+        .json(&json!({ "method": method, "params": params, "id": id }))
+        .send()
+        .await
+        .context("请求 Deluge 失败")?;
+    let status = resp.status().as_u16();
+    let body = resp.text().await.unwrap_or_default();
+    if status != 200 {
+        bail!("Deluge {} 失败（{}）", method, super::failure(status, &body));
+    }
+    let reply: Value = serde_json::from_str(&body).with_context(|| format!("Deluge {} 的响应不是 JSON", method))?;
+    if let Some(err) = reply.get("error").filter(|e| !e.is_null()) {
+        bail!("Deluge {}: {}", method, err.get("message").and_then(Value::as_str).unwrap_or("未知错误"));
+    }
+    Ok(reply.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// Connect the WebUI to `[deluge] host` (`host:port`), else the first daemon it knows
+async fn connect(c: &reqwest::Client, cfg: &DelugeConfig) -> Result<()> {
+    if call(c, cfg, "web.connected", json!([])).await?.as_bool() == Some(true) {
+        return Ok(());
+    }
+    let hosts = call(c, cfg, "web.get_hosts", json!([])).await?;
+    let hosts = hosts.as_array().cloned().unwrap_or_default();
+    // Each host is `[id, address, port, ...]`
+    let address = |h: &Value| format!("{}:{}", h[1].as_str().unwrap_or(""), h[2].as_u64().unwrap_or(0));
+    let host = match cfg.host.as_deref().map(str::trim).filter(|h| !h.is_empty()) {
+        Some(wanted) => hosts.iter().find(|h| address(h) == wanted).with_context(|| format!("Deluge WebUI 中没有守护进程 {}", wanted))?,
+        None => hosts.first().context("Deluge WebUI 没有可连接的守护进程，请先在 WebUI 的连接管理器中添加")?,
+    };
+    let id = host[0].as_str().context("Deluge 守护进程 id 无效")?;
+    util::debug(format!("Deluge: connecting the WebUI to {}", address(host)));
+    call(c, cfg, "web.connect", json!([id])).await?;
+    Ok(())
+}
+
+/// Deluge labels are lower-case letters, digits, `_`, `-` and `.`
+fn label_of(category: &str) -> String {
+    category
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') { c } else { '_' })
+        .collect()
+}
+
+impl DownloadClient for DelugeConfig {
+    fn name(&self) -> &'static str {
+        "Deluge"
+    }
+
+    fn templates(&self) -> Templates<'_> {
+        Templates { category: self.category.as_deref(), tags: &[], save_path: self.save_path.as_deref() }
+    }
+
+    async fn add(&self, magnet: &str, placement: &Placement) -> Result<()> {
+        let c = client()?;
+        let password = self.password.clone().or_else(|| keychain::get(Secret::DelugePassword)).unwrap_or_else(|| "deluge".to_string());
+        if call(&c, self, "auth.login", json!([password])).await?.as_bool() != Some(true) {
+            bail!("Deluge 登录失败，请检查 [deluge] password");
+        }
+        connect(&c, self).await?;
+        let mut options = json!({});
+        if let Some(path) = &placement.save_path {
+            options["download_location"] = json!(path);
+        }
+        let o = &placement.options;
+        if o.sequential {
+            options["sequential_download"] = json!(true);
+        }
+        if let Some(r) = o.ratio_limit {
+            options["stop_at_ratio"] = json!(true);
+            options["stop_ratio"] = json!(r);
+        }
+        if let Some(b) = o.upload_limit {
+            // KiB/s
+            options["max_upload_speed"] = json!(b as f64 / 1024.0);
+        }
+        if o.seed_minutes.is_some() {
+            super::ignore("Deluge", &["做种时间"]);
+        }
+        let id = call(&c, self, "core.add_torrent_magnet", json!([magnet, options])).await?;
+        let Some(id) = id.as_str() else { bail!("Deluge 未能添加磁力（可能已在下载列表中）") };
+        let Some(category) = &placement.category else { return Ok(()) };
+        // Labeling is extra: a missing Label plugin shouldn't fail a download that was added
+        let label = label_of(category);
+        let labeled = async {
+            let labels = call(&c, self, "label.get_labels", json!([])).await?;
+            if !labels.as_array().is_some_and(|l| l.iter().any(|x| x.as_str() == Some(&label))) {
+                call(&c, self, "label.add", json!([label])).await?;
+            }
+            call(&c, self, "label.set_torrent", json!([id, label])).await
+        };
+        if let Err(e) = labeled.await {
+            eprintln!("[WARN] Deluge 无法设置标签 {}（需启用 Label 插件）: {:#}", label, e);
+        }
+        Ok(())
+    }
+}
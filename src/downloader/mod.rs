@@ -0,0 +1,393 @@
+//! Torrent clients downloads can be handed to instead of aria2c: qBittorrent and Deluge
+//! through their WebUI APIs, rTorrent over XML-RPC, or another machine over SSH.
+//! `[download] client` picks one; unset, the first configured of `[qbittorrent]`,
+//! `[deluge]`, `[rtorrent]` and `[remote]` is used, and aria2c when none is.
+//!
+//! Each download is placed by the release's metadata: `category`, `tags` and `save_path`
+//! are templates (`save_path = "/downloads/{actor}/{code}"`, `tags = ["{actors}"]`).
+//! Placeholders: `{code}`, `{title}`, `{actor}` (the first one), `{actors}`, `{studio}`,
+//! `{label}`, `{series}`, `{director}`, `{year}`. One with no value comes out empty: a tag
+//! left empty is dropped, as is an empty folder in the save path.
+//!
+//! `Options` (sequential download, ratio limit, upload cap, seeding time) come from
+//! `[download]` and the flags of the same names and go to whichever backend downloads,
+//! aria2c included; a client that can't apply one says so and goes on without it.
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::sync::{LazyLock, Mutex};
+
+use crate::config::{self, DownloadClientKind};
+use crate::types::AvDetail;
+
+pub mod aria2;
+pub mod deluge;
+pub mod qbittorrent;
+pub mod remote;
+pub mod rtorrent;
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{(\w+)\}").unwrap());
+
+/// Where and how one download is added
+#[derive(Debug, Clone, Default)]
+pub struct Placement {
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub save_path: Option<String>,
+    pub options: Options,
+}
+
+/// How a download transfers and seeds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub sequential: bool,
+    /// Stop seeding at this upload/download ratio
+    pub ratio_limit: Option<f64>,
+    /// Bytes per second
+    pub upload_limit: Option<u64>,
+    /// Stop seeding after this many minutes
+    pub seed_minutes: Option<u64>,
+}
+
+impl Options {
+    /// The names of the options that are set, for warning about ones a client ignores
+    pub fn set_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.sequential {
+            names.push("顺序下载");
+        }
+        if self.ratio_limit.is_some() {
+            names.push("分享率上限");
+        }
+        if self.upload_limit.is_some() {
+            names.push("上传限速");
+        }
+        if self.seed_minutes.is_some() {
+            names.push("做种时间");
+        }
+        names
+    }
+}
+
+/// `[download]` with the flags over it
+static OPTIONS: Mutex<Options> = Mutex::new(Options { sequential: false, ratio_limit: None, upload_limit: None, seed_minutes: None });
+
+/// The download option flags; the ones not given fall back to `[download]`
+pub fn set_options(flags: Options) -> Result<()> {
+    let cfg = &config::get().download;
+    let upload_limit = match (flags.upload_limit, cfg.upload_limit.as_deref()) {
+        (Some(b), _) => Some(b),
+        (None, Some(s)) => Some(crate::magnet::parse_size(s).map_err(anyhow::Error::msg).context("[download] upload_limit 无效")?),
+        (None, None) => None,
+    };
+    *OPTIONS.lock().unwrap() = Options {
+        sequential: flags.sequential || cfg.sequential,
+        ratio_limit: flags.ratio_limit.or(cfg.ratio_limit),
+        upload_limit,
+        seed_minutes: flags.seed_minutes.or(cfg.seed_minutes),
+    };
+    Ok(())
+}
+
+pub fn options() -> Options {
+    *OPTIONS.lock().unwrap()
+}
+
+/// aria2c's arguments for `o`. Without a ratio or seeding time aria2c stops once the
+/// download is done, as it always has here. It has no sequential mode; the first and
+/// last pieces come first instead, which is what a player needs to start.
+pub fn aria2_args(o: &Options) -> Vec<String> {
+    let mut args = Vec::new();
+    match (o.seed_minutes, o.ratio_limit) {
+        (Some(m), _) => args.push(format!("--seed-time={}", m)),
+        (None, None) => args.push("--seed-time=0".to_string()),
+        (None, Some(_)) => {}
+    }
+    if let Some(r) = o.ratio_limit {
+        args.push(format!("--seed-ratio={}", r));
+    }
+    if let Some(b) = o.upload_limit {
+        args.push(format!("--max-upload-limit={}", b));
+    }
+    if o.sequential {
+        args.push("--bt-prioritize-piece=head,tail".to_string());
+    }
+    args
+}
+
+/// Warn that `client` can't apply the options `names`
+pub(crate) fn ignore(client: &str, names: &[&str]) {
+    if !names.is_empty() {
+        eprintln!("[WARN] {} 不支持按下载设置{}，已忽略", client, names.join("、"));
+    }
+}
+
+/// The templates a client's config section carries
+pub struct Templates<'a> {
+    pub category: Option<&'a str>,
+    pub tags: &'a [String],
+    pub save_path: Option<&'a str>,
+}
+
+pub trait DownloadClient {
+    /// For messages: "qBittorrent", "Deluge"
+    fn name(&self) -> &'static str;
+    fn templates(&self) -> Templates<'_>;
+    /// Add `magnet`, placed as `placement` says as far as the client can
+    async fn add(&self, magnet: &str, placement: &Placement) -> Result<()>;
+    /// Whether `wait` should follow `add`, so the download counts as done only once it is
+    fn waits(&self) -> bool {
+        false
+    }
+    /// Return once the torrent with `infohash`, added as `placement`, has finished
+    /// downloading
+    async fn wait(&self, _infohash: &str, _placement: &Placement) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The client picked by `[download] client`, or the first one configured
+pub enum Selected {
+    Qbittorrent(&'static config::QbittorrentConfig),
+    Deluge(&'static config::DelugeConfig),
+    Rtorrent(&'static config::RtorrentConfig),
+    Remote(&'static config::RemoteConfig),
+}
+
+impl Selected {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Selected::Qbittorrent(c) => c.name(),
+            Selected::Deluge(c) => c.name(),
+            Selected::Rtorrent(c) => c.name(),
+            Selected::Remote(c) => c.name(),
+        }
+    }
+}
+
+/// `None`: downloads go to aria2c (or the system BT client)
+pub fn selected() -> Result<Option<Selected>> {
+    let cfg = config::get();
+    let qbit = cfg.qbittorrent.as_ref().filter(|q| !q.url.trim().is_empty());
+    let deluge = cfg.deluge.as_ref().filter(|d| !d.url.trim().is_empty());
+    let rtorrent = cfg.rtorrent.as_ref().filter(|r| !r.url.trim().is_empty());
+    let remote = cfg.remote.as_ref().filter(|r| !r.host.trim().is_empty());
+    Ok(match cfg.download.client {
+        Some(DownloadClientKind::Aria2) => None,
+        Some(DownloadClientKind::Qbittorrent) => match qbit {
+            Some(q) => Some(Selected::Qbittorrent(q)),
+            None => bail!("[download] client = \"qbittorrent\"，但没有配置 [qbittorrent] url"),
+        },
+        Some(DownloadClientKind::Deluge) => match deluge {
+            Some(d) => Some(Selected::Deluge(d)),
+            None => bail!("[download] client = \"deluge\"，但没有配置 [deluge] url"),
+        },
+        Some(DownloadClientKind::Rtorrent) => match rtorrent {
+            Some(r) => Some(Selected::Rtorrent(r)),
+            None => bail!("[download] client = \"rtorrent\"，但没有配置 [rtorrent] url"),
+        },
+        Some(DownloadClientKind::Remote) => match remote {
+            Some(r) => Some(Selected::Remote(r)),
+            None => bail!("[download] client = \"remote\"，但没有配置 [remote] host"),
+        },
+        None => qbit
+            .map(Selected::Qbittorrent)
+            .or(deluge.map(Selected::Deluge))
+            .or(rtorrent.map(Selected::Rtorrent))
+            .or(remote.map(Selected::Remote)),
+    })
+}
+
+/// Characters that would split a value into folders (or tags) are replaced
+fn clean(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | ',') { '_' } else { c })
+        .collect()
+}
+
+/// The values of placeholder `name`; `None` for names that aren't placeholders
+fn values(name: &str, code: &str, d: Option<&AvDetail>) -> Option<Vec<String>> {
+    let one = |v: Option<&String>| v.map(|s| clean(s)).into_iter().collect::<Vec<_>>();
+    Some(match name {
+        "code" => vec![clean(code)],
+        "title" => one(d.map(|d| &d.title)),
+        "actor" => one(d.and_then(|d| d.actor_names.first())),
+        "actors" => d.map(|d| d.actor_names.iter().map(|a| clean(a)).collect()).unwrap_or_default(),
+        "studio" => one(d.and_then(|d| d.studio.as_ref())),
+        "label" => one(d.and_then(|d| d.label.as_ref())),
+        "series" => one(d.and_then(|d| d.series.as_ref())),
+        "director" => one(d.and_then(|d| d.director.as_ref())),
+        "year" => d.and_then(|d| d.release_date.as_deref()).and_then(|r| r.get(..4)).map(str::to_string).into_iter().collect(),
+        _ => return None,
+    }
+    .into_iter()
+    .filter(|v| !v.is_empty())
+    .collect())
+}
+
+/// `template` with its placeholders replaced; several values (`{actors}`) are joined
+/// with ", ", and unknown placeholders are kept as written
+pub fn fill(template: &str, code: &str, d: Option<&AvDetail>) -> String {
+    PLACEHOLDER
+        .replace_all(template, |c: &regex::Captures| match values(&c[1], code, d) {
+            Some(v) => v.join(", "),
+            None => c[0].to_string(),
+        })
+        .into_owned()
+}
+
+/// Most file systems refuse longer names (ENAMETOOLONG)
+const NAME_MAX: usize = 255;
+
+/// One folder of a filled save path: `.` and `..` (which a scraped value can be on its
+/// own) are dropped so the path can't climb out of the template, trailing dots and
+/// spaces are trimmed, and long titles are cut to `NAME_MAX` bytes
+fn folder(segment: &str) -> Option<String> {
+    let mut name = segment.trim();
+    if name.len() > NAME_MAX {
+        let mut end = NAME_MAX;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name = &name[..end];
+    }
+    let name = name.trim_end_matches(['.', ' ']);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// `templates` filled for `code`, from its detail when there is one
+pub fn placement(templates: &Templates, code: &str, d: Option<&AvDetail>) -> Placement {
+    let category = templates.category.map(|t| fill(t, code, d).trim().to_string()).filter(|c| !c.is_empty());
+    let mut tags: Vec<String> = Vec::new();
+    for t in templates.tags {
+        // `{actors}` alone makes one tag per actor rather than one tag listing them all
+        let filled = if t.trim() == "{actors}" { values("actors", code, d).unwrap_or_default() } else { vec![fill(t, code, d)] };
+        for tag in filled.into_iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    let save_path = templates.save_path.map(|t| {
+        let filled = fill(t, code, d);
+        let folders: Vec<String> = filled.split('/').filter_map(folder).collect();
+        format!("{}{}", if filled.starts_with('/') { "/" } else { "" }, folders.join("/"))
+    });
+    Placement { category, tags, save_path: save_path.filter(|p| !p.is_empty()), options: options() }
+}
+
+/// What `--dry-run` and the success note say about `placement`
+pub fn describe(placement: &Placement) -> String {
+    let mut parts = Vec::new();
+    if let Some(c) = &placement.category {
+        parts.push(format!("分类 {}", c));
+    }
+    if !placement.tags.is_empty() {
+        parts.push(format!("标签 {}", placement.tags.join(",")));
+    }
+    if let Some(p) = &placement.save_path {
+        parts.push(format!("保存到 {}", p));
+    }
+    let o = &placement.options;
+    if o.sequential {
+        parts.push("顺序下载".to_string());
+    }
+    if let Some(r) = o.ratio_limit {
+        parts.push(format!("分享率上限 {}", r));
+    }
+    if let Some(b) = o.upload_limit {
+        parts.push(format!("上传限速 {}/s", crate::magnet::format_size(b)));
+    }
+    if let Some(m) = o.seed_minutes {
+        parts.push(format!("做种 {} 分钟", m));
+    }
+    if parts.is_empty() { String::new() } else { format!("（{}）", parts.join("，")) }
+}
+
+/// Where a transfer is at, from the client's own state names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferState {
+    Downloading,
+    /// Downloading, but nothing is coming in
+    Stalled,
+    Seeding,
+    Paused,
+    Queued,
+    Checking,
+    /// Finished and no longer seeding
+    Complete,
+    Error,
+}
+
+impl TransferState {
+    pub fn label(self) -> &'static str {
+        match self {
+            TransferState::Downloading => "下载中",
+            TransferState::Stalled => "等待连接",
+            TransferState::Seeding => "做种中",
+            TransferState::Paused => "已暂停",
+            TransferState::Queued => "排队中",
+            TransferState::Checking => "校验中",
+            TransferState::Complete => "已完成",
+            TransferState::Error => "出错",
+        }
+    }
+}
+
+/// One download as a client reports it, for `av progress`
+#[derive(Debug, Clone, Serialize)]
+pub struct Transfer {
+    /// What the client's calls take: the infohash for qBittorrent, the GID for aria2
+    pub id: String,
+    /// Lower-case hex; aria2 has none for plain HTTP downloads
+    pub infohash: Option<String>,
+    /// The code it was dispatched for, from the journal
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub code: String,
+    pub name: String,
+    pub size: u64,
+    pub downloaded: u64,
+    /// Bytes per second
+    pub down_speed: u64,
+    pub up_speed: u64,
+    pub eta_secs: Option<u64>,
+    /// Connected seeders and other peers
+    pub seeders: Option<u32>,
+    pub peers: Option<u32>,
+    pub state: TransferState,
+}
+
+impl Transfer {
+    pub fn progress(&self) -> f64 {
+        if self.size == 0 { 0.0 } else { (self.downloaded as f64 / self.size as f64).min(1.0) }
+    }
+}
+
+/// HTTP status and body for the error messages, the body cut short
+pub(crate) fn failure(status: u16, body: &str) -> String {
+    format!("HTTP {}: {}", status, crate::util::truncate_chars(body.trim(), 200))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_path_stays_inside_template() {
+        let mut d = crate::magnet::detail_of("ABP-123", Vec::new());
+        d.studio = Some("..".to_string());
+        d.series = Some(" . ".to_string());
+        d.title = "長いタイトル".repeat(30) + "...";
+        let save = |t: &str| placement(&Templates { category: None, tags: &[], save_path: Some(t) }, "ABP-123", Some(&d)).save_path;
+        assert_eq!(save("/data/{studio}/{series}/{code}").as_deref(), Some("/data/ABP-123"));
+        assert_eq!(save("/data/{studio}").as_deref(), Some("/data"));
+        let long = save("/data/{title}").unwrap();
+        let name = long.strip_prefix("/data/").unwrap();
+        assert!(name.len() <= NAME_MAX && !name.ends_with('.'));
+        assert!(name.starts_with("長いタイトル"));
+    }
+}
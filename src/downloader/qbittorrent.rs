@@ -0,0 +1,182 @@
+//! qBittorrent's WebUI API: log in, create the category when needed, add the magnet with
+//! its category, tags and save path. `Session` also lists, pauses and removes torrents
+//! for `av progress`.
+
+use anyhow::{bail, Context, Result};
+use std::time::Duration;
+
+use super::{failure, DownloadClient, Placement, Templates, Transfer, TransferState};
+use crate::config::QbittorrentConfig;
+use crate::http;
+use crate::keychain::{self, Secret};
+
+fn client() -> Result<reqwest::Client> {
+    http::with_proxy(reqwest::Client::builder())
+        .cookie_store(true)
+        .timeout(Duration::from_secs(20))
+        .build()
+        .context("client build")
+}
+
+async fn post(c: &reqwest::Client, cfg: &QbittorrentConfig, path: &str, form: &[(&str, String)]) -> Result<(u16, String)> {
+    let url = format!("{}/api/v2/{}", cfg.url.trim().trim_end_matches('/'), path);
+    // The WebUI rejects requests whose Referer doesn't match its own address (CSRF check)
+    let resp = c.post(&url).header("Referer", cfg.url.trim()).form(form).send().await.context("请求 qBittorrent 失败")?;
+    let status = resp.status().as_u16();
+    Ok((status, resp.text().await.unwrap_or_default()))
+}
+
+/// A client with the session cookie, when the WebUI asks for a login
+async fn login(cfg: &QbittorrentConfig) -> Result<reqwest::Client> {
+    let c = client()?;
+    if let Some(user) = cfg.username.as_deref().filter(|u| !u.is_empty()) {
+        let form = [("username", user.to_string()), ("password", cfg.password.clone().or_else(|| keychain::get(Secret::QbittorrentPassword)).unwrap_or_default())];
+        let (status, body) = post(&c, cfg, "auth/login", &form).await?;
+        if status != 200 || body.trim() != "Ok." {
+            bail!("qBittorrent 登录失败（{}）", failure(status, &body));
+        }
+    }
+    Ok(c)
+}
+
+/// A logged-in WebUI session, logging in again when it expires
+pub struct Session {
+    cfg: &'static QbittorrentConfig,
+    c: reqwest::Client,
+}
+
+impl Session {
+    pub async fn open(cfg: &'static QbittorrentConfig) -> Result<Session> {
+        Ok(Session { cfg, c: login(cfg).await? })
+    }
+
+    async fn call(&mut self, path: &str, form: &[(&str, String)]) -> Result<(u16, String)> {
+        let (status, body) = post(&self.c, self.cfg, path, form).await?;
+        if status != 403 {
+            return Ok((status, body));
+        }
+        self.c = login(self.cfg).await?;
+        post(&self.c, self.cfg, path, form).await
+    }
+
+    pub async fn transfers(&mut self) -> Result<Vec<Transfer>> {
+        let (status, body) = self.call("torrents/info", &[]).await?;
+        if status != 200 {
+            bail!("qBittorrent 无法列出种子（{}）", failure(status, &body));
+        }
+        let torrents: Vec<serde_json::Value> = serde_json::from_str(&body).context("qBittorrent 返回的种子列表无法解析")?;
+        Ok(torrents.iter().map(transfer).collect())
+    }
+
+    /// qBittorrent 5 renamed pause/resume to stop/start; older versions only know the
+    /// first names
+    async fn either(&mut self, new: &str, old: &str, hash: &str) -> Result<()> {
+        let form = [("hashes", hash.to_string())];
+        let (mut status, mut body) = self.call(new, &form).await?;
+        if status == 404 {
+            (status, body) = self.call(old, &form).await?;
+        }
+        if status != 200 {
+            bail!("qBittorrent 操作失败（{}）", failure(status, &body));
+        }
+        Ok(())
+    }
+
+    pub async fn pause(&mut self, hash: &str) -> Result<()> {
+        self.either("torrents/stop", "torrents/pause", hash).await
+    }
+
+    pub async fn resume(&mut self, hash: &str) -> Result<()> {
+        self.either("torrents/start", "torrents/resume", hash).await
+    }
+
+    /// Remove the torrent, keeping whatever it downloaded
+    pub async fn remove(&mut self, hash: &str) -> Result<()> {
+        let (status, body) = self.call("torrents/delete", &[("hashes", hash.to_string()), ("deleteFiles", "false".to_string())]).await?;
+        if status != 200 {
+            bail!("qBittorrent 无法删除种子（{}）", failure(status, &body));
+        }
+        Ok(())
+    }
+}
+
+/// An entry of `torrents/info`
+fn transfer(t: &serde_json::Value) -> Transfer {
+    let num = |key: &str| t.get(key).and_then(serde_json::Value::as_u64);
+    let state = match t.get("state").and_then(serde_json::Value::as_str).unwrap_or_default() {
+        "downloading" | "forcedDL" | "metaDL" | "forcedMetaDL" => TransferState::Downloading,
+        "stalledDL" => TransferState::Stalled,
+        "uploading" | "forcedUP" | "stalledUP" => TransferState::Seeding,
+        "pausedDL" | "stoppedDL" => TransferState::Paused,
+        "pausedUP" | "stoppedUP" => TransferState::Complete,
+        "queuedDL" | "queuedUP" | "allocating" | "moving" => TransferState::Queued,
+        "checkingDL" | "checkingUP" | "checkingResumeData" => TransferState::Checking,
+        _ => TransferState::Error,
+    };
+    let hash = t.get("hash").and_then(serde_json::Value::as_str).unwrap_or_default().to_lowercase();
+    Transfer {
+        id: hash.clone(),
+        infohash: Some(hash),
+        code: String::new(),
+        name: t.get("name").and_then(serde_json::Value::as_str).unwrap_or_default().to_string(),
+        size: num("size").unwrap_or(0),
+        downloaded: num("completed").unwrap_or(0),
+        down_speed: num("dlspeed").unwrap_or(0),
+        up_speed: num("upspeed").unwrap_or(0),
+        // 8640000 is its "infinity"
+        eta_secs: num("eta").filter(|&e| e < 8_640_000),
+        seeders: num("num_seeds").map(|n| n as u32),
+        peers: num("num_leechs").map(|n| n as u32),
+        state,
+    }
+}
+
+impl DownloadClient for QbittorrentConfig {
+    fn name(&self) -> &'static str {
+        "qBittorrent"
+    }
+
+    fn templates(&self) -> Templates<'_> {
+        Templates { category: self.category.as_deref(), tags: &self.tags, save_path: self.save_path.as_deref() }
+    }
+
+    async fn add(&self, magnet: &str, placement: &Placement) -> Result<()> {
+        let c = login(self).await?;
+        if let Some(category) = &placement.category {
+            // 409 is "already exists"; qBittorrent would refuse the torrent with an unknown category
+            let (status, body) = post(&c, self, "torrents/createCategory", &[("category", category.clone())]).await?;
+            if status != 200 && status != 409 {
+                bail!("qBittorrent 无法创建分类 {}（{}）", category, failure(status, &body));
+            }
+        }
+        let mut form = vec![("urls", magnet.to_string())];
+        if let Some(category) = &placement.category {
+            form.push(("category", category.clone()));
+        }
+        if !placement.tags.is_empty() {
+            form.push(("tags", placement.tags.join(",")));
+        }
+        if let Some(path) = &placement.save_path {
+            form.push(("savepath", path.clone()));
+        }
+        let o = &placement.options;
+        if o.sequential {
+            form.push(("sequentialDownload", "true".to_string()));
+        }
+        if let Some(r) = o.ratio_limit {
+            form.push(("ratioLimit", r.to_string()));
+        }
+        if let Some(m) = o.seed_minutes {
+            form.push(("seedingTimeLimit", m.to_string()));
+        }
+        if let Some(b) = o.upload_limit {
+            form.push(("upLimit", b.to_string()));
+        }
+        let (status, body) = post(&c, self, "torrents/add", &form).await?;
+        match status {
+            200 if body.trim() != "Fails." => Ok(()),
+            403 => bail!("qBittorrent 拒绝访问，请检查 [qbittorrent] 的用户名和密码"),
+            _ => bail!("qBittorrent 未能添加磁力（{}）", failure(status, &body)),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! A remote download node, for networks that can't torrent: magnets go over SSH to a
+//! machine that can. In `aria2` mode aria2c is started there in the background (so it
+//! survives a dropped connection), polled until it exits, and the download is optionally
+//! copied back with rsync (scp without it). In `watch` mode a `.magnet` file is written
+//! to a client's watch folder and that client takes it from there.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use which::which;
+
+use super::{DownloadClient, Placement, Templates};
+use crate::config::{RemoteConfig, RemoteMode};
+use crate::util;
+
+/// `value` as one word for the remote shell
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Remote paths are relative to the home directory, where ssh, rsync and scp all start;
+/// a leading `~/` says the same thing and is dropped, since quoting would stop the shell
+/// from expanding it
+fn remote_path(path: &str) -> &str {
+    path.strip_prefix("~/").unwrap_or(path)
+}
+
+/// `ssh` options, also handed to rsync and scp
+fn ssh_options(cfg: &RemoteConfig, port_flag: &str) -> Vec<String> {
+    let mut args = vec!["-o".to_string(), "BatchMode=yes".to_string()];
+    if let Some(port) = cfg.port {
+        args.extend([port_flag.to_string(), port.to_string()]);
+    }
+    if let Some(identity) = cfg.identity.as_deref().filter(|i| !i.is_empty()) {
+        args.extend(["-i".to_string(), identity.to_string()]);
+    }
+    args
+}
+
+/// Run `script` on the remote machine; its exit code (255: ssh itself failed) and stdout
+async fn ssh(cfg: &RemoteConfig, script: &str) -> Result<(Option<i32>, String)> {
+    util::debug(format!("ssh {}: {}", cfg.host, script));
+    let out = Command::new("ssh")
+        .args(ssh_options(cfg, "-p"))
+        .arg(cfg.host.trim())
+        .arg(script)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("启动 ssh 失败")?;
+    Ok((out.status.code(), String::from_utf8_lossy(&out.stdout).into_owned()))
+}
+
+/// The download folder on the remote machine, and the names of the files its aria2c
+/// run leaves next to the download
+fn job(placement: &Placement, infohash: &str) -> Result<(String, String, String)> {
+    let dir = placement.save_path.as_deref().context("[remote] download_dir 为空")?;
+    Ok((remote_path(dir).to_string(), format!(".av-{}.log", infohash), format!(".av-{}.exit", infohash)))
+}
+
+impl RemoteConfig {
+    async fn start_aria2(&self, magnet: &str, placement: &Placement) -> Result<()> {
+        let hash = crate::magnet::parse(magnet)?.infohash;
+        let (dir, log, exit) = job(placement, &hash)?;
+        let args: Vec<String> = super::aria2_args(&placement.options).iter().map(|a| sh_quote(a)).collect();
+        let run = format!("aria2c {} --dir=. {} > {} 2>&1; echo $? > {}", args.join(" "), sh_quote(magnet), log, exit);
+        let script = format!(
+            "command -v aria2c > /dev/null || exit 127; mkdir -p {dir} && cd {dir} && rm -f {exit} && nohup sh -c {run} > /dev/null 2>&1 < /dev/null &",
+            dir = sh_quote(&dir),
+            exit = exit,
+            run = sh_quote(&run)
+        );
+        match ssh(self, &script).await?.0 {
+            Some(0) => Ok(()),
+            Some(127) => bail!("远程主机 {} 上没有 aria2c", self.host),
+            Some(255) => bail!("无法通过 SSH 连接 {}（需要免密登录，如 ssh-copy-id）", self.host),
+            code => bail!("在 {} 上启动 aria2c 失败，退出码: {:?}", self.host, code),
+        }
+    }
+
+    async fn write_watch_file(&self, magnet: &str) -> Result<()> {
+        let Some(watch) = self.watch_dir.as_deref().filter(|w| !w.trim().is_empty()) else {
+            bail!("[remote] mode = \"watch\" 需要设置 watch_dir");
+        };
+        let hash = crate::magnet::parse(magnet)?.infohash;
+        let watch = sh_quote(remote_path(watch.trim()));
+        let script = format!("mkdir -p {w} && printf '%s\\n' {m} > {w}/{h}.magnet", w = watch, m = sh_quote(magnet), h = hash);
+        match ssh(self, &script).await?.0 {
+            Some(0) => Ok(()),
+            Some(255) => bail!("无法通过 SSH 连接 {}（需要免密登录，如 ssh-copy-id）", self.host),
+            code => bail!("无法写入 {} 上的监视目录 {}，退出码: {:?}", self.host, watch, code),
+        }
+    }
+
+    /// Copy the download folder `dir` back into `pull_to`
+    async fn pull(&self, dir: &str) -> Result<()> {
+        let Some(local) = self.pull_to.as_deref() else { return Ok(()) };
+        std::fs::create_dir_all(local).with_context(|| format!("无法创建目录 {}", local.display()))?;
+        let source = format!("{}:{}", self.host.trim(), dir.trim_end_matches('/'));
+        let mut cmd = if which("rsync").is_ok() {
+            let mut ssh = vec!["ssh".to_string()];
+            ssh.extend(ssh_options(self, "-p"));
+            let mut cmd = Command::new("rsync");
+            // -s: the remote path is taken literally, spaces and all
+            cmd.args(["-a", "--partial", "-s", "--exclude=.av-*", "-e"]).arg(ssh.join(" "));
+            cmd
+        } else {
+            let mut cmd = Command::new("scp");
+            cmd.arg("-r").args(ssh_options(self, "-P"));
+            cmd
+        };
+        let status = cmd.arg(&source).arg(local).stdin(Stdio::null()).status().await.context("启动 rsync/scp 失败")?;
+        if !status.success() {
+            bail!("从 {} 取回下载失败，退出码: {:?}", source, status.code());
+        }
+        let name = dir.trim_end_matches('/').rsplit('/').next().unwrap_or(dir);
+        util::note(format!("{} {}", "已取回到".green().bold(), local.join(name).display()));
+        Ok(())
+    }
+}
+
+impl DownloadClient for RemoteConfig {
+    fn name(&self) -> &'static str {
+        "SSH"
+    }
+
+    fn templates(&self) -> Templates<'_> {
+        let save_path = match self.mode {
+            RemoteMode::Aria2 => Some(self.download_dir.as_str()),
+            RemoteMode::Watch => None,
+        };
+        Templates { category: None, tags: &[], save_path }
+    }
+
+    async fn add(&self, magnet: &str, placement: &Placement) -> Result<()> {
+        match self.mode {
+            RemoteMode::Aria2 => self.start_aria2(magnet, placement).await,
+            RemoteMode::Watch => {
+                super::ignore("监视目录", &placement.options.set_names());
+                self.write_watch_file(magnet).await
+            }
+        }
+    }
+
+    fn waits(&self) -> bool {
+        self.mode == RemoteMode::Aria2
+    }
+
+    async fn wait(&self, infohash: &str, placement: &Placement) -> Result<()> {
+        let (dir, log, exit) = job(placement, infohash)?;
+        // Prints the exit code and the end of the log once aria2c is done, and cleans up
+        let script = format!(
+            "cd {dir} 2>/dev/null && [ -f {exit} ] && {{ cat {exit}; tail -n 5 {log}; rm -f {exit} {log}; }}",
+            dir = sh_quote(&dir),
+            exit = exit,
+            log = log
+        );
+        let every = Duration::from_secs(self.poll_secs.max(5));
+        loop {
+            crate::cancel::check()?;
+            match ssh(self, &script).await? {
+                (Some(0), out) => {
+                    let mut lines = out.lines();
+                    match lines.next().map(str::trim) {
+                        Some("0") => break,
+                        code => {
+                            let tail: Vec<&str> = lines.collect();
+                            bail!("{} 上的 aria2c 下载失败，退出码: {}\n{}", self.host, code.unwrap_or("?"), tail.join("\n"));
+                        }
+                    }
+                }
+                // A dropped connection doesn't stop the remote aria2c; ask again next time
+                (Some(255), _) => eprintln!("[WARN] 无法连接 {}，稍后重试", self.host),
+                _ => {}
+            }
+            let token = crate::cancel::token();
+            tokio::select! {
+                _ = tokio::time::sleep(every) => {}
+                _ = token.cancelled() => return Err(crate::cancel::error()),
+            }
+        }
+        self.pull(&dir).await
+    }
+}
@@ -0,0 +1,110 @@
+//! rTorrent over XML-RPC, for seedboxes: the daemon's own HTTP endpoint (`/RPC2` behind
+//! the web server) or ruTorrent's httprpc plugin (`.../plugins/httprpc/action.php`), which
+//! relays the same calls. The magnet is started with its directory and ruTorrent label
+//! (`d.custom1`) set; with `wait`, `d.complete` is polled until the download is done.
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use super::{DownloadClient, Placement, Templates};
+use crate::config::RtorrentConfig;
+use crate::http;
+use crate::keychain::{self, Secret};
+use crate::util;
+
+static FAULT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<name>faultString</name>\s*<value>\s*(?:<string>)?(.*?)(?:</string>)?\s*</value>").unwrap());
+static VALUE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<params>\s*<param>\s*<value>\s*(?:<(\w+)>(.*?)</\w+>|([^<]*))\s*</value>").unwrap());
+
+/// Polls of a hash rTorrent doesn't know yet before giving up: it registers a magnet a
+/// moment after `load.start` returns
+const UNKNOWN_POLLS: u32 = 5;
+
+fn client() -> Result<reqwest::Client> {
+    http::with_proxy(reqwest::Client::builder()).timeout(Duration::from_secs(30)).build().context("client build")
+}
+
+fn request(method: &str, params: &[String]) -> String {
+    let params: String =
+        params.iter().map(|p| format!("<param><value><string>{}</string></value></param>", util::xml_escape(p))).collect();
+    format!(r#"<?xml version="1.0"?><methodCall><methodName>{}</methodName><params>{}</params></methodCall>"#, method, params)
+}
+
+/// One call; a `<fault>` becomes `Err`, otherwise the first returned value as text
+async fn call(c: &reqwest::Client, cfg: &RtorrentConfig, method: &str, params: &[String]) -> Result<String> {
+    let mut req = c.post(cfg.url.trim()).header("Content-Type", "text/xml").body(request(method, params));
+    if let Some(user) = cfg.username.as_deref().filter(|u| !u.is_empty()) {
+        req = req.basic_auth(user, cfg.password.clone().or_else(|| keychain::get(Secret::RtorrentPassword)));
+    }
+    let resp = req.send().await.context("请求 rTorrent 失败")?;
+    let status = resp.status().as_u16();
+    let body = resp.text().await.unwrap_or_default();
+    match status {
+        200 => {}
+        401 | 403 => bail!("rTorrent 拒绝访问（HTTP {}），请检查 [rtorrent] 的用户名和密码", status),
+        _ => bail!("rTorrent {} 失败（{}）", method, super::failure(status, &body)),
+    }
+    if let Some(fault) = FAULT.captures(&body) {
+        bail!("rTorrent {}: {}", method, util::xml_unescape(&fault[1]));
+    }
+    let value = VALUE.captures(&body).with_context(|| format!("rTorrent {} 的响应无法解析", method))?;
+    Ok(util::xml_unescape(value.get(2).or(value.get(3)).map_or("", |m| m.as_str())))
+}
+
+/// A value inside an rTorrent command (`d.directory.set="..."`)
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl DownloadClient for RtorrentConfig {
+    fn name(&self) -> &'static str {
+        "rTorrent"
+    }
+
+    fn templates(&self) -> Templates<'_> {
+        Templates { category: self.category.as_deref(), tags: &[], save_path: self.save_path.as_deref() }
+    }
+
+    async fn add(&self, magnet: &str, placement: &Placement) -> Result<()> {
+        // Throttles and ratio groups are set up in .rtorrent.rc, not per download
+        super::ignore("rTorrent", &placement.options.set_names());
+        let c = client()?;
+        // The first parameter is the target, empty for commands that don't act on a download
+        let mut params = vec![String::new(), magnet.to_string()];
+        if let Some(path) = &placement.save_path {
+            params.push(format!("d.directory.set={}", quoted(path)));
+        }
+        if let Some(label) = &placement.category {
+            params.push(format!("d.custom1.set={}", quoted(label)));
+        }
+        call(&c, self, "load.start", &params).await?;
+        Ok(())
+    }
+
+    fn waits(&self) -> bool {
+        self.wait
+    }
+
+    async fn wait(&self, infohash: &str, _placement: &Placement) -> Result<()> {
+        let c = client()?;
+        let every = Duration::from_secs(self.poll_secs.max(5));
+        let mut unknown = 0;
+        loop {
+            crate::cancel::check()?;
+            match call(&c, self, "d.complete", &[infohash.to_string()]).await {
+                Ok(done) if done.trim() == "1" => return Ok(()),
+                Ok(_) => unknown = 0,
+                Err(e) if format!("{:#}", e).to_lowercase().contains("info-hash") && unknown < UNKNOWN_POLLS => unknown += 1,
+                Err(e) => return Err(e),
+            }
+            let token = crate::cancel::token();
+            tokio::select! {
+                _ = tokio::time::sleep(every) => {}
+                _ = token.cancelled() => return Err(crate::cancel::error()),
+            }
+        }
+    }
+}
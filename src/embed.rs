@@ -0,0 +1,134 @@
+//! `av embed`: write the code's metadata (title, actors, date, genres, ...) into the
+//! videos' own MP4/MKV tags with ffmpeg, for players that don't read NFO files. Streams
+//! are copied as they are, into a temporary file that then replaces the original.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use which::which;
+
+use crate::scraper;
+use crate::types::AvDetail;
+use crate::util;
+use crate::verify;
+
+/// Containers whose tags ffmpeg writes and players read
+const EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "mkv"];
+
+#[derive(Debug, Serialize)]
+struct Embedded {
+    path: PathBuf,
+    code: String,
+    /// ffmpeg key → value, in the order written
+    tags: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The tags for `d`; ffmpeg maps these generic keys to each container's own atoms/tags
+fn tags(d: &AvDetail) -> Vec<(&'static str, String)> {
+    let title = d.title.trim();
+    let title = title.strip_prefix(d.code.as_str()).unwrap_or(title).trim();
+    let mut tags = vec![("title", format!("{} {}", d.code, title).trim().to_string())];
+    let mut add = |key, value: Option<String>| {
+        if let Some(v) = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+            tags.push((key, v));
+        }
+    };
+    add("artist", Some(d.actor_names.join(", ")));
+    add("date", d.release_date.clone());
+    add("genre", Some(d.genres.join(", ")));
+    add("album", d.series.clone());
+    add("album_artist", d.studio.clone());
+    add("publisher", d.label.clone());
+    add("composer", d.director.clone());
+    add("description", d.plot.clone());
+    add("comment", Some(d.code.clone()));
+    tags
+}
+
+/// Rewrite `path` with `tags`, keeping every stream and the tags already there
+async fn write_tags(path: &Path, tags: &[(&str, String)]) -> Result<()> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    // Same folder, so the final rename never crosses filesystems
+    let tmp = path.with_file_name(format!(".{}.av-embed.{}", name, ext));
+    let mut cmd = tokio::process::Command::new("ffmpeg");
+    cmd.args(["-v", "error", "-y", "-i"]).arg(path).args(["-map", "0", "-c", "copy", "-map_metadata", "0"]);
+    for (key, value) in tags {
+        cmd.arg("-metadata").arg(format!("{}={}", key, value));
+    }
+    if ext != "mkv" {
+        // Keeps the moov atom at the front, as most downloads have it for streaming
+        cmd.args(["-movflags", "+faststart"]);
+    }
+    let out = cmd.arg(&tmp).stdin(Stdio::null()).output().await.context("启动 ffmpeg 失败")?;
+    if !out.status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        bail!("ffmpeg 写入元数据失败: {}", String::from_utf8_lossy(&out.stderr).trim());
+    }
+    std::fs::rename(&tmp, path).with_context(|| format!("替换原文件失败: {}", path.display()))
+}
+
+/// `av embed PATH [--code CODE]`: a video, or every video under a folder, each with the
+/// code its name carries (`--code` for a single file whose name doesn't)
+pub async fn run(target: &Path, code: Option<&str>, json: bool) -> Result<()> {
+    if which("ffmpeg").is_err() {
+        bail!("未检测到 ffmpeg，请先安装: brew install ffmpeg");
+    }
+    let groups = match code {
+        Some(c) if target.is_file() => vec![(c.to_string(), vec![target.to_path_buf()])],
+        Some(_) => bail!("--code 只能用于单个文件"),
+        None => verify::targets(target)?,
+    };
+    if groups.is_empty() {
+        bail!("{} 下没有找到视频文件", target.display());
+    }
+    let mut done = Vec::new();
+    let mut failed = 0;
+    for (code, paths) in groups {
+        crate::cancel::check()?;
+        let d = match scraper::fetch_detail(&code).await {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("[WARN] 获取 {} 的元数据失败: {:#}", code, e);
+                failed += paths.len();
+                continue;
+            }
+        };
+        let tags = tags(&d);
+        for path in paths {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+            if !EXTENSIONS.contains(&ext.as_str()) {
+                eprintln!("[WARN] 不支持写入 .{} 的元数据（支持 {}），跳过: {}", ext, EXTENSIONS.join("/"), path.display());
+                continue;
+            }
+            if util::dry_run_skip(format!("将写入 {} 的元数据到 {}", d.code, path.display())) {
+                for (k, v) in &tags {
+                    eprintln!("  {} = {}", k, util::truncate_chars(v, 80));
+                }
+                continue;
+            }
+            match write_tags(&path, &tags).await {
+                Ok(()) => {
+                    if !json {
+                        println!("{} {}  {}", "已写入".green().bold(), d.code.bold(), path.display());
+                    }
+                    let tags = tags.iter().map(|(k, v)| (k.to_string(), v.clone().into())).collect();
+                    done.push(Embedded { path, code: d.code.clone(), tags });
+                }
+                Err(e) => {
+                    eprintln!("[WARN] {}: {:#}", path.display(), e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if json {
+        util::print_output(&done, true);
+    }
+    if failed > 0 {
+        bail!("{} 个文件未能写入元数据", failed);
+    }
+    Ok(())
+}
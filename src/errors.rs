@@ -0,0 +1,91 @@
+//! Failures as JSON: with `--json` (or `--query` / `--fields`) a failed command prints
+//! `{"error": {kind, message, source, http_status}}` on stdout instead of the plain
+//! message on stderr, so scripts can tell a block from a missing title from a broken
+//! pipe without scraping Chinese text. The exit code is still non-zero.
+//!
+//! `kind` comes from a `Tagged` context attached where the error is raised, or else from
+//! the typed errors in the chain (`FetchError`, `reqwest::Error`, `io::Error`).
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::cancel;
+use crate::metrics;
+use crate::sanity::FetchError;
+
+/// A context carrying its `kind`: `err.context(Tagged::new("query", "--query 执行失败"))`
+#[derive(Debug)]
+pub struct Tagged {
+    kind: &'static str,
+    message: String,
+}
+
+impl Tagged {
+    pub fn new(kind: &'static str, message: impl Into<String>) -> Self {
+        Tagged { kind, message: message.into() }
+    }
+}
+
+impl fmt::Display for Tagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+    /// cancelled, blocked, not_found, bad_response, http, timeout, network, io, query,
+    /// config, or error when nothing more specific is known
+    kind: &'static str,
+    message: String,
+    /// The data source involved (javdb, sukebei, ...), when the error names one
+    source: Option<String>,
+    http_status: Option<u16>,
+}
+
+/// A `T` anywhere in `err`: attached as context (which `chain` doesn't show as its own
+/// type) or as a cause
+fn find<T: std::error::Error + Send + Sync + 'static>(err: &anyhow::Error) -> Option<&T> {
+    err.downcast_ref::<T>().or_else(|| err.chain().find_map(|c| c.downcast_ref::<T>()))
+}
+
+fn describe(err: &anyhow::Error) -> ErrorBody {
+    let mut body = ErrorBody { kind: "error", message: format!("{:#}", err), source: None, http_status: None };
+    if cancel::is_cancelled() {
+        body.kind = "cancelled";
+        return body;
+    }
+    if let Some(t) = err.downcast_ref::<Tagged>() {
+        body.kind = t.kind;
+    } else if let Some(f) = find::<FetchError>(err) {
+        let (kind, source) = match f {
+            FetchError::Blocked { url, .. } => ("blocked", metrics::source_of(url)),
+            FetchError::Unexpected { url, .. } => ("bad_response", metrics::source_of(url)),
+            FetchError::NotFound { source, .. } => ("not_found", source.clone()),
+        };
+        body.kind = kind;
+        body.source = Some(source);
+        body.http_status = f.status();
+    } else if let Some(e) = find::<reqwest::Error>(err) {
+        body.kind = if e.is_timeout() {
+            "timeout"
+        } else if e.is_status() {
+            "http"
+        } else if e.is_decode() || e.is_body() {
+            "bad_response"
+        } else {
+            "network"
+        };
+        body.source = e.url().map(|u| metrics::source_of(u.as_str()));
+        body.http_status = e.status().map(|s| s.as_u16());
+    } else if find::<std::io::Error>(err).is_some() {
+        body.kind = "io";
+    }
+    body
+}
+
+/// Print `err` as the JSON error object on stdout
+pub fn print_json(err: &anyhow::Error) {
+    let out = serde_json::json!({ "error": describe(err) });
+    println!("{}", serde_json::to_string_pretty(&out).unwrap_or_default());
+}
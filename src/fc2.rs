@@ -0,0 +1,39 @@
+//! `av fc2`: FC2 titles come from individual sellers rather than studios, so besides
+//! looking up `FC2-PPV-<id>` codes like any other, a seller's whole catalogue can be
+//! listed.
+
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::dates;
+use crate::sources::fc2 as market;
+use crate::table;
+use crate::util;
+
+/// `av fc2 seller <id>`: the seller's releases, newest first, with dates and prices
+pub async fn seller(input: &str, pages: u32, json: bool) -> Result<()> {
+    let id = market::seller_id(input)?;
+    let seller = market::seller(&id, pages).await?;
+    if json {
+        util::print_output(&seller, true);
+        return Ok(());
+    }
+    let name = seller.name.as_deref().map(|n| format!("{}（{}）", n, seller.id)).unwrap_or_else(|| seller.id.clone());
+    println!("{} {}  共 {} 部  {}", "FC2 卖家".bold(), name.green().bold(), seller.articles.len(), seller.url.dimmed());
+    let rows = seller
+        .articles
+        .iter()
+        .enumerate()
+        .map(|(idx, a)| {
+            vec![
+                (idx + 1).to_string(),
+                a.code.clone(),
+                a.release_date.as_deref().map(dates::render).unwrap_or_else(|| "-".to_string()),
+                a.price.map(|p| format!("{}pt", p)).unwrap_or_else(|| "-".to_string()),
+                a.title.clone(),
+            ]
+        })
+        .collect();
+    table::print_rows(&["#", "番号", "发行日期", "价格", "标题"], rows);
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::scraper;
+use crate::types::AvDetail;
+use crate::util;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    #[default]
+    Rss,
+    Atom,
+}
+
+impl FeedFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+        }
+    }
+}
+
+pub struct FeedOptions {
+    /// Max entries taken from the newest-releases listing (and from each actor)
+    pub limit: usize,
+    /// Actors whose listings are merged into the feed
+    pub actors: Vec<String>,
+    pub uncen: bool,
+}
+
+/// Collect newest releases plus the configured actors' titles and render them as a feed
+/// whose entry links are magnets, so torrent clients' RSS downloaders can consume it.
+pub async fn build(opts: &FeedOptions, format: FeedFormat) -> Result<String> {
+    let mut codes: Vec<String> = Vec::new();
+    for (actor, listed) in scraper::list_actors_titles(&opts.actors).await {
+        match listed {
+            Ok(items) => codes.extend(filter_items(items, opts.uncen).into_iter().take(opts.limit)),
+            Err(e) => util::debug(format!("feed: actor {} failed: {}", actor, e)),
+        }
+    }
+    let top = scraper::top(opts.limit).await?;
+    codes.extend(filter_items(top, opts.uncen));
+    let mut seen = std::collections::HashSet::new();
+    codes.retain(|c| seen.insert(c.clone()));
+
+    let details = scraper::fetch_details(codes).await;
+    Ok(match format {
+        FeedFormat::Rss => render_rss(&details),
+        FeedFormat::Atom => render_atom(&details),
+    })
+}
+
+fn filter_items(items: Vec<crate::types::AvItem>, uncen: bool) -> Vec<String> {
+    items
+        .into_iter()
+        .filter(|i| !uncen || util::looks_uncensored(&i.title))
+        .map(|i| i.code)
+        .collect()
+}
+
+fn entry_title(d: &AvDetail) -> String {
+    format!("{} {}", d.code, d.title.trim())
+}
+
+fn entry_summary(d: &AvDetail) -> String {
+    let mut parts = Vec::new();
+    if !d.actor_names.is_empty() {
+        parts.push(format!("演员: {}", d.actor_names.join(", ")));
+    }
+    if let Some(date) = &d.release_date {
+        parts.push(format!("发行: {}", date));
+    }
+    if let Some(studio) = &d.studio {
+        parts.push(format!("片商: {}", studio));
+    }
+    if !d.genres.is_empty() {
+        parts.push(format!("类别: {}", d.genres.join(", ")));
+    }
+    parts.join(" | ")
+}
+
+pub fn render_rss(details: &[AvDetail]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<rss version=\"2.0\">\n<channel>\n");
+    out.push_str("<title>av</title>\n<link>https://github.com/auv-sh/av</link>\n<description>av newest releases</description>\n");
+    for d in details {
+        let Some(raw) = util::best_magnet(d) else { continue };
+        let magnet = util::xml_escape(&raw);
+        let guid = util::magnet_infohash(&raw).unwrap_or_else(|| d.code.clone());
+        out.push_str("<item>\n");
+        out.push_str(&format!("<title>{}</title>\n", util::xml_escape(&entry_title(d))));
+        out.push_str(&format!("<link>{}</link>\n", magnet));
+        out.push_str(&format!("<guid isPermaLink=\"false\">{}</guid>\n", util::xml_escape(&guid)));
+        out.push_str(&format!("<description>{}</description>\n", util::xml_escape(&entry_summary(d))));
+        if let Some(date) = d.release_date.as_deref().and_then(util::rfc2822_from_date) {
+            out.push_str(&format!("<pubDate>{}</pubDate>\n", date));
+        }
+        out.push_str(&format!("<enclosure url=\"{}\" length=\"0\" type=\"application/x-bittorrent\" />\n", magnet));
+        out.push_str("</item>\n");
+    }
+    out.push_str("</channel>\n</rss>\n");
+    out
+}
+
+pub fn render_atom(details: &[AvDetail]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    out.push_str("<title>av</title>\n<id>urn:av:feed</id>\n");
+    let updated = details
+        .iter()
+        .filter_map(|d| d.release_date.as_deref().and_then(atom_date))
+        .max()
+        .unwrap_or_else(|| "1970-01-01T00:00:00Z".to_string());
+    out.push_str(&format!("<updated>{}</updated>\n", updated));
+    for d in details {
+        let Some(magnet) = util::best_magnet(d) else { continue };
+        let magnet = util::xml_escape(&magnet);
+        out.push_str("<entry>\n");
+        out.push_str(&format!("<title>{}</title>\n", util::xml_escape(&entry_title(d))));
+        out.push_str(&format!("<id>urn:av:{}</id>\n", util::xml_escape(&d.code)));
+        out.push_str(&format!("<link rel=\"enclosure\" type=\"application/x-bittorrent\" href=\"{}\" />\n", magnet));
+        let date = d.release_date.as_deref().and_then(atom_date).unwrap_or_else(|| updated.clone());
+        out.push_str(&format!("<updated>{}</updated>\n", date));
+        out.push_str(&format!("<summary>{}</summary>\n", util::xml_escape(&entry_summary(d))));
+        out.push_str("</entry>\n");
+    }
+    out.push_str("</feed>\n");
+    out
+}
+
+/// RFC 3339 timestamp from a scraped `YYYY-MM-DD` date
+fn atom_date(s: &str) -> Option<String> {
+    let date = s.trim().get(0..10)?;
+    let ok = date.len() == 10 && date.chars().enumerate().all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() });
+    ok.then(|| format!("{}T00:00:00Z", date))
+}
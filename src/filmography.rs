@@ -0,0 +1,169 @@
+//! `av list <actor> --stats`: genre and studio distribution, releases per year and average
+//! rating over an actor's works, from their details fetched as one batch. With `--xlsx`,
+//! the same tables as sheets of a workbook, besides the works themselves.
+
+use anyhow::{bail, Result};
+use chrono::{Datelike, NaiveDate};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::dates;
+use crate::scraper;
+use crate::types::{AvDetail, AvItem};
+use crate::util;
+use crate::xlsx::{self, Cell, Sheet, Workbook};
+
+/// Rows shown per table in the terminal; `--json` has them all
+const TOP_ROWS: usize = 10;
+const BAR_WIDTH: usize = 30;
+
+#[derive(Debug, Serialize)]
+struct Count {
+    name: String,
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    actor: String,
+    /// Titles listed for the actor
+    works: usize,
+    /// Of those, titles whose detail could be fetched; every figure below is over these
+    with_details: usize,
+    first_release: Option<String>,
+    latest_release: Option<String>,
+    per_year: BTreeMap<String, usize>,
+    genres: Vec<Count>,
+    studios: Vec<Count>,
+    average_rating: Option<f32>,
+    rated: usize,
+}
+
+/// Most frequent first, then by name
+fn ranked(values: impl Iterator<Item = String>) -> Vec<Count> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for v in values {
+        *counts.entry(v).or_default() += 1;
+    }
+    let mut out: Vec<Count> = counts.into_iter().map(|(name, count)| Count { name, count }).collect();
+    out.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    out
+}
+
+fn stats(actor: &str, works: usize, details: &[AvDetail]) -> Stats {
+    let mut dates: Vec<NaiveDate> = details.iter().filter_map(|d| d.release_date.as_deref().and_then(dates::parse)).collect();
+    dates.sort_unstable();
+    let mut per_year = BTreeMap::new();
+    for d in &dates {
+        *per_year.entry(d.year().to_string()).or_default() += 1;
+    }
+    let ratings: Vec<f32> = details.iter().filter_map(|d| d.rating).collect();
+    Stats {
+        actor: actor.to_string(),
+        works,
+        with_details: details.len(),
+        first_release: dates.first().map(|d| d.to_string()),
+        latest_release: dates.last().map(|d| d.to_string()),
+        per_year,
+        genres: ranked(details.iter().flat_map(|d| d.genres.iter().cloned())),
+        studios: ranked(details.iter().filter_map(|d| d.studio.clone())),
+        average_rating: (!ratings.is_empty()).then(|| ratings.iter().sum::<f32>() / ratings.len() as f32),
+        rated: ratings.len(),
+    }
+}
+
+fn bar(count: usize, max: usize) -> String {
+    "█".repeat((count * BAR_WIDTH).div_ceil(max.max(1)))
+}
+
+fn print_counts(heading: &str, counts: &[Count], total: usize) {
+    if counts.is_empty() {
+        return;
+    }
+    println!("\n{}", heading.green().bold());
+    let shown = &counts[..counts.len().min(TOP_ROWS)];
+    let width = shown.iter().map(|c| util::display_width(&c.name)).max().unwrap_or(0);
+    for c in shown {
+        let pct = c.count as f64 * 100.0 / total.max(1) as f64;
+        println!("  {}  {:>3}  {}", util::pad(&c.name, width), c.count, format!("{:.0}%", pct).dimmed());
+    }
+    if counts.len() > TOP_ROWS {
+        println!("  {}", format!("…另有 {} 项", counts.len() - TOP_ROWS).dimmed());
+    }
+}
+
+fn counts_sheet(name: &str, counts: &[Count], total: usize) -> Sheet {
+    let mut sheet = Sheet::new(name, &[name, "作品数", "占比（%）"]);
+    for c in counts {
+        sheet.push(vec![Cell::text(&c.name), Cell::Int(c.count as i64), Cell::Decimal(c.count as f64 * 100.0 / total.max(1) as f64)]);
+    }
+    sheet
+}
+
+/// The overview, per-year, genre and studio sheets, then every work
+fn save_xlsx(s: &Stats, items: &[AvItem], details: &[AvDetail], path: &Path) -> Result<()> {
+    let mut overview = Sheet::new("概览", &["项目", "值"]);
+    overview.push(vec![Cell::text("演员"), Cell::text(&s.actor)]);
+    overview.push(vec![Cell::text("作品数"), Cell::Int(s.works as i64)]);
+    overview.push(vec![Cell::text("有详情"), Cell::Int(s.with_details as i64)]);
+    overview.push(vec![Cell::text("最早发行"), Cell::text(s.first_release.clone().unwrap_or_default())]);
+    overview.push(vec![Cell::text("最近发行"), Cell::text(s.latest_release.clone().unwrap_or_default())]);
+    overview.push(vec![Cell::text("平均评分"), s.average_rating.map(Cell::decimal).unwrap_or(Cell::Empty)]);
+    overview.push(vec![Cell::text("有评分"), Cell::Int(s.rated as i64)]);
+    let mut per_year = Sheet::new("每年发行", &["年份", "作品数"]);
+    for (year, n) in &s.per_year {
+        per_year.push(vec![Cell::text(year), Cell::Int(*n as i64)]);
+    }
+    let by_code: HashMap<String, &AvDetail> = details.iter().map(|d| (d.code.clone(), d)).collect();
+
+    let mut book = Workbook::default();
+    book.add(overview);
+    book.add(per_year);
+    book.add(counts_sheet("类别", &s.genres, s.with_details));
+    book.add(counts_sheet("片商", &s.studios, s.with_details));
+    book.add(xlsx::titles_sheet("作品", items, &by_code));
+    if book.save(path)? {
+        eprintln!("{} {}（{} 部作品，{} 部有详情）", "已导出表格:".green().bold(), path.display(), s.works, s.with_details);
+    }
+    Ok(())
+}
+
+/// Stats over `items`, the actor's (already filtered) listing; written to `xlsx` instead
+/// of printed when given
+pub async fn run(actor: &str, items: &[AvItem], xlsx: Option<&Path>, json: bool) -> Result<()> {
+    if items.is_empty() {
+        bail!("没有找到 {} 的作品", actor);
+    }
+    let details = scraper::fetch_details(items.iter().map(|i| i.code.clone()).collect()).await;
+    if details.is_empty() {
+        bail!("未能获取 {} 的任何作品详情", actor);
+    }
+    let s = stats(actor, items.len(), &details);
+    if let Some(path) = xlsx {
+        return save_xlsx(&s, items, &details, path);
+    }
+    if json {
+        util::print_output(&s, true);
+        return Ok(());
+    }
+    println!("{}  {}", s.actor.bold(), format!("{} 部作品（{} 部有详情）", s.works, s.with_details).dimmed());
+    if let (Some(first), Some(latest)) = (&s.first_release, &s.latest_release) {
+        println!("发行: {} 至 {}", dates::render(first), dates::render(latest));
+    }
+    match s.average_rating {
+        Some(r) => println!("平均评分: {:.2}（{} 部有评分）", r, s.rated),
+        None => println!("平均评分: -"),
+    }
+    if !s.per_year.is_empty() {
+        println!("\n{}", "每年发行".green().bold());
+        let max = s.per_year.values().copied().max().unwrap_or(1);
+        for (year, n) in &s.per_year {
+            println!("  {}  {:>3}  {}", year, n, bar(*n, max).cyan());
+        }
+    }
+    print_counts("类别", &s.genres, s.with_details);
+    print_counts("片商", &s.studios, s.with_details);
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::scraper;
+use crate::sources::gfriends;
+use crate::types::ActorItem;
+use crate::util;
+
+const STYLE: &str = "body{margin:0;padding:24px;background:#141414;color:#eee;font-family:system-ui,sans-serif}\
+h1{font-size:20px;font-weight:600;margin:0 0 16px}\
+.grid{display:grid;grid-template-columns:repeat(auto-fill,minmax(140px,1fr));gap:16px}\
+.actor{display:block;color:inherit;text-decoration:none;text-align:center}\
+.actor:hover .name{text-decoration:underline}\
+.avatar{width:100%;aspect-ratio:1;object-fit:cover;border-radius:50%;background:#2a2a2a}\
+.placeholder{display:flex;align-items:center;justify-content:center;font-size:40px;color:#777}\
+.name{margin-top:8px;font-size:14px}\
+.meta{font-size:12px;color:#999}";
+
+/// Actors from `pages` ranking pages, fetched `--jobs` at a time; first occurrence wins
+async fn collect(pages: usize, per_page: usize, uncen: bool) -> Vec<ActorItem> {
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for page in 1..=pages.max(1) {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (page, scraper::actors(page, per_page, uncen).await)
+        });
+    }
+    let mut done = tasks.join_all().await;
+    done.sort_by_key(|(page, _)| *page);
+    let mut all: Vec<ActorItem> = Vec::new();
+    for (page, result) in done {
+        match result {
+            Ok(found) => {
+                for a in found.actors {
+                    if !all.iter().any(|x| x.name == a.name) {
+                        all.push(a);
+                    }
+                }
+            }
+            Err(e) => eprintln!("[WARN] 演员列表第 {} 页获取失败: {:#}", page, e),
+        }
+    }
+    all
+}
+
+fn render(actors: &[ActorItem]) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    out.push_str("<title>av actors</title>\n");
+    out.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", STYLE));
+    out.push_str(&format!("<h1>演员 · {}</h1>\n<div class=\"grid\">\n", actors.len()));
+    for a in actors {
+        let name = util::xml_escape(&a.name);
+        // The avatar CDN rejects hotlinks that carry a foreign Referer
+        let avatar = match &a.avatar_url {
+            Some(src) => format!(
+                "<img class=\"avatar\" src=\"{}\" alt=\"{}\" loading=\"lazy\" referrerpolicy=\"no-referrer\">",
+                util::xml_escape(src),
+                name
+            ),
+            None => format!(
+                "<div class=\"avatar placeholder\">{}</div>",
+                a.name.chars().next().map(|c| util::xml_escape(&c.to_string())).unwrap_or_default()
+            ),
+        };
+        let mut meta = Vec::new();
+        if let Some(r) = a.rank {
+            meta.push(format!("#{}", r));
+        }
+        if let Some(w) = a.works_count {
+            meta.push(format!("{} 部", w));
+        }
+        let href = a.url.clone().unwrap_or_else(|| scraper::javdb_search_url(&a.name));
+        out.push_str(&format!(
+            "<a class=\"actor\" href=\"{}\" target=\"_blank\" rel=\"noopener\">{}<div class=\"name\">{}</div><div class=\"meta\">{}</div></a>\n",
+            util::xml_escape(&href),
+            avatar,
+            name,
+            meta.join(" · ")
+        ));
+    }
+    out.push_str("</div>\n</body>\n</html>\n");
+    out
+}
+
+/// `av actors export-html`: a browsable grid of actor portraits linking to their filmographies
+pub async fn export_html(pages: usize, per_page: usize, uncen: bool, out: &Path) -> Result<()> {
+    let mut actors = collect(pages, per_page, uncen).await;
+    if actors.is_empty() {
+        bail!("没有获取到任何演员");
+    }
+    if actors.iter().any(|a| a.avatar_url.is_none()) {
+        match gfriends::index(false).await {
+            Ok(index) => {
+                for a in actors.iter_mut().filter(|a| a.avatar_url.is_none()) {
+                    a.avatar_url = index.avatar_url(&a.name);
+                }
+            }
+            Err(e) => eprintln!("[WARN] 无法用 gfriends 补全头像: {:#}", e),
+        }
+    }
+    let html = render(&actors);
+    if util::dry_run_skip(format!("将写入 {}（{} 位演员）", out.display(), actors.len())) {
+        return Ok(());
+    }
+    std::fs::write(out, html).with_context(|| format!("写入失败: {}", out.display()))?;
+    util::note(format!("{} {} 位演员 → {}", "已导出".green().bold(), actors.len(), out.display()));
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use crate::code;
+use crate::config::MetadataLang;
+use crate::scraper;
+use crate::types::{AvDetail, AvItem};
+use crate::util;
+
+/// A genre as the sources name it in Japanese (JavDB, DMM), Chinese and English
+/// (JavLibrary's cn/en pages), plus the other spellings seen in the wild (traditional
+/// Chinese from JavDB's UI, older JavLibrary wording).
+pub struct Genre {
+    pub id: &'static str,
+    pub ja: &'static str,
+    pub zh: &'static str,
+    pub en: &'static str,
+    pub aliases: &'static [&'static str],
+}
+
+impl Genre {
+    pub fn name(&self, lang: MetadataLang) -> &'static str {
+        match lang {
+            MetadataLang::Ja => self.ja,
+            MetadataLang::Zh => self.zh,
+            MetadataLang::En => self.en,
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let n = name.trim();
+        n.eq_ignore_ascii_case(self.id)
+            || [self.ja, self.zh, self.en].iter().chain(self.aliases).any(|v| v.eq_ignore_ascii_case(n))
+    }
+}
+
+macro_rules! genre {
+    ($id:literal, $ja:literal, $zh:literal, $en:literal $(, $alias:literal)* $(,)?) => {
+        Genre { id: $id, ja: $ja, zh: $zh, en: $en, aliases: &[$($alias),*] }
+    };
+}
+
+static GENRES: &[Genre] = &[
+    genre!("creampie", "中出し", "中出", "Creampie", "内射", "Cream Pie"),
+    genre!("big-tits", "巨乳", "巨乳", "Big Tits", "Big Breasts", "Busty"),
+    genre!("small-tits", "貧乳・微乳", "贫乳、微乳", "Small Tits", "貧乳、微乳", "微乳", "貧乳"),
+    genre!("beautiful-girl", "美少女", "美少女", "Beautiful Girl", "美少女電影"),
+    genre!("amateur", "素人", "素人", "Amateur"),
+    genre!("married-woman", "人妻・主婦", "人妻", "Married Woman", "人妻", "主婦", "Housewife"),
+    genre!("mature-woman", "熟女", "熟女", "Mature Woman"),
+    genre!("solowork", "単体作品", "单体作品", "Solowork", "單體作品", "Solo Work"),
+    genre!("debut", "デビュー作品", "出道作品", "Debut Production", "Debut"),
+    genre!("hi-def", "ハイビジョン", "高画质", "High Definition", "高畫質", "HD", "Hi-Def"),
+    genre!("4k", "4K", "4K", "4K"),
+    genre!("vr", "VR専用", "VR", "VR", "ハイクオリティVR", "VR專用"),
+    genre!("digital-mosaic", "デジモ", "数位马赛克", "Digital Mosaic", "數位馬賽克"),
+    genre!("uncensored", "無修正", "无码", "Uncensored", "無碼", "无修正"),
+    genre!("drama", "ドラマ", "剧情", "Drama", "劇情"),
+    genre!("documentary", "ドキュメンタリー", "纪录片", "Documentary", "紀錄片"),
+    genre!("planning", "企画", "企画", "Planning", "企劃"),
+    genre!("best-omnibus", "ベスト・総集編", "精选、综合", "Best, Omnibus", "精選、綜合", "Best", "Omnibus"),
+    genre!("cosplay", "コスプレ", "角色扮演", "Cosplay", "Cosplay服裝"),
+    genre!("school-girl", "女子校生", "女高中生", "School Girls", "女子高生", "女校生", "女學生"),
+    genre!("office-lady", "OL", "OL", "Office Lady", "OFFICE LADY"),
+    genre!("nurse", "看護婦・ナース", "护士", "Nurse", "護士", "ナース"),
+    genre!("gal", "ギャル", "辣妹", "Gal", "黑辣妹"),
+    genre!("slender", "スレンダー", "苗条", "Slender", "苗條"),
+    genre!("lesbian", "レズビアン", "女同性恋", "Lesbian", "レズ", "女同性戀"),
+    genre!("threesome", "3P・4P", "3P、4P", "Threesome / Foursome", "3P", "4P", "多P"),
+    genre!("squirting", "潮吹き", "潮吹", "Squirting"),
+    genre!("blowjob", "フェラ", "口交", "Blowjob", "Fellatio"),
+    genre!("titty-fuck", "パイズリ", "乳交", "Titty Fuck", "Paizuri"),
+    genre!("handjob", "手コキ", "手淫", "Handjob", "手交"),
+    genre!("facial", "顔射", "颜射", "Facials", "顏射", "Facial"),
+    genre!("butt", "尻フェチ", "屁股", "Butt", "美臀", "Big Ass"),
+    genre!("pantyhose", "パンスト・タイツ", "连裤袜", "Pantyhose", "連褲襪", "Tights"),
+    genre!("massage", "マッサージ", "按摩", "Massage", "マッサージ・リフレ"),
+    genre!("outdoor", "野外・露出", "户外、露出", "Outdoor", "戶外、露出", "野外", "露出"),
+];
+
+/// The canonical genre for any known spelling (id, ja/zh/en name or alias)
+pub fn lookup(name: &str) -> Option<&'static Genre> {
+    GENRES.iter().find(|g| g.matches(name))
+}
+
+/// Fill `genre_ids` and, when a metadata language is set, rename known genres to it
+pub fn normalize(d: &mut AvDetail, lang: Option<MetadataLang>) {
+    let mut ids = Vec::new();
+    let mut names = Vec::new();
+    for raw in &d.genres {
+        match lookup(raw) {
+            Some(g) => {
+                if !ids.contains(&g.id.to_string()) {
+                    ids.push(g.id.to_string());
+                    names.push(lang.map(|l| g.name(l).to_string()).unwrap_or_else(|| raw.clone()));
+                }
+            }
+            None => names.push(raw.clone()),
+        }
+    }
+    d.genre_ids = ids;
+    d.genres = names;
+}
+
+/// Whether the detail carries `query`, matched by canonical id so `巨乳`, `Big Tits` and
+/// `big-tits` are the same filter; unknown names compare case-insensitively
+pub fn has_genre(d: &AvDetail, query: &str) -> bool {
+    match lookup(query) {
+        Some(g) => d.genre_ids.iter().any(|id| id == g.id) || d.genres.iter().any(|n| g.matches(n)),
+        None => d.genres.iter().any(|n| n.trim().eq_ignore_ascii_case(query.trim())),
+    }
+}
+
+/// `--genre`: keep the items whose details carry the genre (fetches each item's detail)
+pub async fn filter_items(items: Vec<AvItem>, query: &str) -> Vec<AvItem> {
+    let codes: Vec<String> = items.iter().map(|i| i.code.clone()).collect();
+    util::debug(format!("genre filter: fetching {} details for {}", codes.len(), query));
+    let keep: HashSet<String> = scraper::fetch_details(codes)
+        .await
+        .into_iter()
+        .filter(|d| has_genre(d, query))
+        .map(|d| code::normalize(&d.code))
+        .collect();
+    items.into_iter().filter(|i| keep.contains(&code::normalize(&i.code))).collect()
+}
@@ -0,0 +1,121 @@
+//! Golden-file tests of the detail parsers. Every page saved under
+//! `tests/fixtures/<source>/` (an API item for DMM) is parsed offline and the `AvDetail`
+//! it gives compared field for field with the `<name>.expected.json` next to it; the file
+//! name is the code. After an intended parser or selector change, rewrite the snapshots
+//! with `AV_UPDATE_GOLDEN=1 cargo test golden` and review their diff before committing.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::scraper;
+use crate::sources::{dmm, javlibrary};
+use crate::types::AvDetail;
+
+const EXPECTED: &str = ".expected.json";
+
+fn fixture_dir(source: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(source)
+}
+
+/// The source's fixtures with `ext`, by name
+fn fixtures(source: &str, ext: &str) -> Vec<PathBuf> {
+    let dir = fixture_dir(source);
+    let mut out: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e))
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            name.ends_with(ext) && !name.ends_with(EXPECTED)
+        })
+        .collect();
+    out.sort();
+    assert!(!out.is_empty(), "{} 下没有 *{} fixture", dir.display(), ext);
+    out
+}
+
+/// `ssis-001.html` → `SSIS-001`
+fn code_of(path: &Path) -> String {
+    path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_uppercase()
+}
+
+fn expected_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    path.with_file_name(format!("{}{}", stem, EXPECTED))
+}
+
+/// The top-level fields where `actual` and `expected` differ, each with both values
+fn differences(actual: &Value, expected: &Value) -> Vec<String> {
+    let (Some(a), Some(e)) = (actual.as_object(), expected.as_object()) else {
+        return vec![format!("期望 {}，实际 {}", expected, actual)];
+    };
+    let mut keys: Vec<&String> = a.keys().chain(e.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|k| a.get(*k) != e.get(*k))
+        .map(|k| {
+            let show = |v: Option<&Value>| v.map(Value::to_string).unwrap_or_else(|| "(无)".to_string());
+            format!("  {}: 期望 {}，实际 {}", k, show(e.get(k)), show(a.get(k)))
+        })
+        .collect()
+}
+
+/// Compare `detail` with the fixture's snapshot (or rewrite it); a description of the
+/// mismatch on failure
+fn check(fixture: &Path, detail: Result<AvDetail>) -> Option<String> {
+    let name = fixture.display();
+    let detail = match detail {
+        Ok(d) => d,
+        Err(e) => return Some(format!("{}: 解析失败: {:#}", name, e)),
+    };
+    let actual = serde_json::to_value(&detail).expect("AvDetail serializes");
+    let path = expected_path(fixture);
+    if std::env::var_os("AV_UPDATE_GOLDEN").is_some() {
+        let text = serde_json::to_string_pretty(&actual).expect("AvDetail serializes") + "\n";
+        std::fs::write(&path, text).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        return None;
+    }
+    let expected: Value = match std::fs::read_to_string(&path).context("读取失败").and_then(|t| Ok(serde_json::from_str(&t)?)) {
+        Ok(v) => v,
+        Err(e) => return Some(format!("{}: {:#}（AV_UPDATE_GOLDEN=1 生成）", path.display(), e)),
+    };
+    (actual != expected).then(|| format!("{} 与 {} 不一致:\n{}", name, path.display(), differences(&actual, &expected).join("\n")))
+}
+
+/// Run `parse` over the source's fixtures and fail with every mismatch at once
+fn golden(source: &str, ext: &str, parse: impl Fn(&str, &str) -> Result<AvDetail>) {
+    let failures: Vec<String> = fixtures(source, ext)
+        .iter()
+        .filter_map(|f| {
+            let body = std::fs::read_to_string(f).unwrap_or_else(|e| panic!("{}: {}", f.display(), e));
+            check(f, parse(&body, &code_of(f)))
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[test]
+fn javdb_detail_pages() {
+    golden("javdb", ".html", |body, code| {
+        scraper::parse_javdb_page(body, &format!("https://javdb.com/v/{}", code.to_lowercase()))
+    });
+}
+
+#[test]
+fn javlibrary_detail_pages() {
+    golden("javlibrary", ".html", |body, code| {
+        javlibrary::parse_detail_page(body, "https://www.javlibrary.com/en/?v=fixture", code)
+    });
+}
+
+#[test]
+fn sukebei_view_pages() {
+    golden("sukebei", ".html", |body, code| Ok(scraper::parse_sukebei_page(body, code, code)));
+}
+
+#[test]
+fn dmm_api_items() {
+    // The sample player is resolved over the network, so the snapshot has only the guess
+    golden("dmm", ".json", |body, code| Ok(dmm::detail_from_item(code, &serde_json::from_str(body)?, None)));
+}
@@ -0,0 +1,274 @@
+//! `POST /graphql` in serve mode: a GraphQL view of the same lookups the REST endpoints
+//! make, so a frontend can ask for exactly the fields it shows — a detail with its magnets,
+//! an actor with their filmography, each search hit's detail — in one round trip.
+//!
+//! The schema is async-graphql's: the object types are the API types themselves
+//! (`types.rs` derives them, with the JSON API's field names), and the resolvers below call
+//! the `scraper` functions the REST handlers call. Nested lookups (`Item.detail`,
+//! `Detail.actors`, `Actor.filmography`) only run when selected, `--jobs` at a time.
+
+use async_graphql::{ComplexObject, Context, EmptyMutation, EmptySubscription, Error, Number, Object, Result, Schema, Value};
+use tokio::sync::Semaphore;
+
+use crate::scraper;
+use crate::types::{ActorItem, ActorsEnvelope, AvDetail, AvItem};
+use crate::util;
+
+pub type ApiSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Selections nested deeper than this are refused, as each level can fan out lookups.
+/// Introspection counts too, and the standard introspection query goes 13 levels deep.
+const MAX_DEPTH: usize = 16;
+/// Every selected field costs 1; a bound on how much one query can ask for
+const MAX_COMPLEXITY: usize = 10_000;
+
+/// `serve --uncen`, for fields whose `uncen` argument isn't given
+struct Defaults {
+    uncen: bool,
+}
+
+/// One per request: the lookups it may run at once
+struct Permits(Semaphore);
+
+pub fn schema(uncen: bool) -> ApiSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(Defaults { uncen })
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+pub async fn execute(schema: &ApiSchema, req: async_graphql::Request) -> async_graphql::Response {
+    util::debug(format!("api: graphql {}", util::truncate_chars(&req.query.split_whitespace().collect::<Vec<_>>().join(" "), 120)));
+    let mut resp = schema.execute(req.data(Permits(Semaphore::new(util::jobs())))).await;
+    shorten_floats(&mut resp.data);
+    resp
+}
+
+/// Every `Float` here is an `f32` field, which async-graphql widens to `f64`, so a score of
+/// `65.4` would come out as `65.4000015258789`; print them as the REST API does
+fn shorten_floats(v: &mut Value) {
+    match v {
+        Value::Number(n) if !n.is_i64() && !n.is_u64() => {
+            let short = n.as_f64().and_then(|f| (f as f32).to_string().parse::<f64>().ok()).and_then(Number::from_f64);
+            if let Some(short) = short {
+                *n = short;
+            }
+        }
+        Value::List(items) => items.iter_mut().for_each(shorten_floats),
+        Value::Object(fields) => fields.values_mut().for_each(shorten_floats),
+        _ => {}
+    }
+}
+
+/// A lookup's failure as the field's error, with the whole anyhow chain
+fn upstream(e: anyhow::Error) -> Error {
+    Error::new(format!("{:#}", e))
+}
+
+fn required(name: &str, value: &str) -> Result<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(Error::new(format!("argument \"{}\" must be a non-empty string", name)));
+    }
+    Ok(value.to_string())
+}
+
+fn wants_uncen(ctx: &Context<'_>, flag: Option<bool>) -> bool {
+    flag.unwrap_or_else(|| ctx.data_unchecked::<Defaults>().uncen)
+}
+
+fn filtered(mut items: Vec<AvItem>, uncen: bool) -> Vec<AvItem> {
+    if uncen {
+        items.retain(|i| util::looks_uncensored(&i.title));
+    }
+    items
+}
+
+/// Run `f` once one of the request's permits is free
+async fn limited<T>(ctx: &Context<'_>, f: impl std::future::Future<Output = anyhow::Result<T>>) -> Result<T> {
+    let _permit = ctx.data_unchecked::<Permits>().0.acquire().await?;
+    f.await.map_err(upstream)
+}
+
+async fn filmography(ctx: &Context<'_>, name: &str, limit: Option<usize>, uncen: Option<bool>) -> Result<Vec<AvItem>> {
+    let mut found = limited(ctx, scraper::list_actor_titles(name)).await?;
+    if let Some(limit) = limit {
+        found.truncate(limit);
+    }
+    Ok(filtered(found, wants_uncen(ctx, uncen)))
+}
+
+pub struct Query;
+
+#[Object(rename_args = "snake_case")]
+impl Query {
+    /// A title's merged detail, as `GET /detail/{code}`
+    async fn detail(&self, ctx: &Context<'_>, code: String) -> Result<Option<AvDetail>> {
+        let code = required("code", &code)?;
+        limited(ctx, scraper::fetch_detail(&code)).await.map(Some)
+    }
+
+    /// A keyword or code search, as `GET /search`
+    async fn search(&self, ctx: &Context<'_>, query: String, uncen: Option<bool>) -> Result<Vec<AvItem>> {
+        let query = required("query", &query)?;
+        let found = limited(ctx, scraper::search(&query)).await?;
+        Ok(filtered(found, wants_uncen(ctx, uncen)))
+    }
+
+    /// The ranking, as `GET /top`
+    async fn top(&self, ctx: &Context<'_>, #[graphql(default = 20)] limit: usize, uncen: Option<bool>) -> Result<Vec<AvItem>> {
+        let found = limited(ctx, scraper::top(limit)).await?;
+        Ok(filtered(found, wants_uncen(ctx, uncen)))
+    }
+
+    /// An actor by name; nothing is looked up until `filmography` is selected
+    async fn actor(&self, name: String) -> Result<Actor> {
+        Ok(Actor { name: required("name", &name)? })
+    }
+
+    /// One page of the actors ranking, as `GET /actors`
+    async fn actors(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: usize,
+        #[graphql(default = 50)] per_page: usize,
+        uncen: Option<bool>,
+    ) -> Result<ActorsEnvelope> {
+        let (page, per_page) = (page.max(1), per_page.max(1));
+        let found = limited(ctx, scraper::actors(page, per_page, wants_uncen(ctx, uncen))).await?;
+        Ok(found.envelope(page, per_page, || None))
+    }
+}
+
+pub struct Actor {
+    name: String,
+}
+
+#[Object]
+impl Actor {
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Their titles, newest first
+    async fn filmography(&self, ctx: &Context<'_>, limit: Option<usize>, uncen: Option<bool>) -> Result<Vec<AvItem>> {
+        filmography(ctx, &self.name, limit, uncen).await
+    }
+}
+
+#[ComplexObject]
+impl AvItem {
+    /// The merged detail, looked up when selected
+    async fn detail(&self, ctx: &Context<'_>) -> Result<Option<AvDetail>> {
+        limited(ctx, scraper::fetch_detail(&self.code)).await.map(Some)
+    }
+}
+
+#[ComplexObject]
+impl AvDetail {
+    /// The cast, each with their filmography when selected
+    async fn actors(&self) -> Vec<Actor> {
+        self.actor_names.iter().map(|n| Actor { name: n.clone() }).collect()
+    }
+}
+
+#[ComplexObject]
+impl ActorItem {
+    /// Their titles, newest first
+    async fn filmography(&self, ctx: &Context<'_>, limit: Option<usize>, uncen: Option<bool>) -> Result<Vec<AvItem>> {
+        filmography(ctx, &self.name, limit, uncen).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn run(query: &str) -> serde_json::Value {
+        let resp = execute(&schema(false), async_graphql::Request::new(query)).await;
+        serde_json::to_value(resp).unwrap()
+    }
+
+    fn errors(v: &serde_json::Value) -> Vec<String> {
+        v["errors"].as_array().into_iter().flatten().map(|e| e["message"].as_str().unwrap_or_default().to_string()).collect()
+    }
+
+    #[test]
+    fn schema_uses_api_names() {
+        let sdl = schema(false).sdl();
+        for needle in [
+            "detail(code: String!): Detail",
+            "top(limit: Int! = 20, uncen: Boolean): [Item!]!",
+            "actors(page: Int! = 1, per_page: Int! = 50, uncen: Boolean): ActorsPage!",
+            "type Magnet",
+            "quality_score: Float",
+            "magnet_infos: [Magnet!]!",
+            "actors: [Actor!]!",
+            "filmography(limit: Int, uncen: Boolean): [Item!]!",
+            "has_next: Boolean!",
+        ] {
+            assert!(sdl.contains(needle), "schema lacks {:?}:\n{}", needle, sdl);
+        }
+        assert!(!sdl.contains("type Mutation"));
+    }
+
+    #[tokio::test]
+    async fn queries_without_lookups() {
+        let v = run(r#"query Q($n: String!) { a: actor(name: $n) { name __typename } }"#).await;
+        assert!(errors(&v).iter().any(|e| e.contains("Variable n")), "{}", v);
+
+        let v = run(r#"{ a: actor(name: " Yua ") { ...F } b: actor(name: "x") @skip(if: true) { name } }
+            fragment F on Actor { name __typename }"#)
+        .await;
+        assert_eq!(v["data"], json!({ "a": { "name": "Yua", "__typename": "Actor" } }), "{}", v);
+
+        let v = run(r#"{ actor(name: "  ") { name } }"#).await;
+        assert!(errors(&v)[0].contains("non-empty"), "{}", v);
+    }
+
+    #[tokio::test]
+    async fn validation() {
+        for (query, fragment) in [
+            ("{ actor(name: \"x\") { nope } }", "nope"),
+            ("{ top(limit: -1) { code } }", "Int"),
+            ("{ detail { code } }", "code"),
+            ("mutation { top { code } }", "mutation"),
+            ("{ actor(name: \"x\") { ...Missing } }", "Missing"),
+            ("{ actor(name: \"x\") { name } ", "expected"),
+        ] {
+            let v = run(query).await;
+            let errs = errors(&v);
+            assert!(errs.iter().any(|e| e.to_lowercase().contains(&fragment.to_lowercase())), "{}: {:?}", query, errs);
+            assert!(v["data"].is_null(), "{}", query);
+        }
+
+        // 17 levels: one past the limit, refused before anything is looked up
+        let deep = format!("{{ actor(name: \"x\") {{ {}name{} }} }}", "filmography { detail { actors { ".repeat(5), " } } }".repeat(5));
+        let errs = errors(&run(&deep).await);
+        assert!(errs.iter().any(|e| e.contains("nested too deep")), "{:?}", errs);
+    }
+
+    #[test]
+    fn floats_print_as_f32() {
+        let mut v = Value::from_json(json!({ "a": [f64::from(65.4f32), 2.5, 7], "b": { "c": f64::from(0.1f32) } })).unwrap();
+        shorten_floats(&mut v);
+        assert_eq!(v.into_json().unwrap(), json!({ "a": [65.4, 2.5, 7], "b": { "c": 0.1 } }));
+    }
+
+    #[tokio::test]
+    async fn introspection() {
+        let v = run(r#"{ __type(name: "Detail") { fields { name description type { kind ofType { name } } } } }"#).await;
+        let fields = v["data"]["__type"]["fields"].as_array().unwrap();
+        let actors = fields.iter().find(|f| f["name"] == "actors").unwrap();
+        assert_eq!(actors["description"], "The cast, each with their filmography when selected");
+        assert_eq!(actors["type"]["kind"], "NON_NULL");
+        assert!(fields.iter().any(|f| f["name"] == "sample_video_url"));
+
+        let full = include_str!("../tests/fixtures/graphql/introspection.graphql");
+        let v = run(full).await;
+        assert!(errors(&v).is_empty(), "{:?}", errors(&v));
+        assert!(v["data"]["__schema"]["types"].as_array().unwrap().iter().any(|t| t["name"] == "RankedActor"));
+    }
+}
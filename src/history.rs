@@ -0,0 +1,96 @@
+use anyhow::Result;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::store;
+use crate::util;
+
+const HISTORY: &str = "history";
+/// Oldest queries beyond this are forgotten
+const MAX_ENTRIES: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub query: String,
+    pub count: u32,
+    /// Local time of the latest use, `YYYY-MM-DD HH:MM`
+    pub last_used: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct History {
+    /// Most recent first, one entry per query (case-insensitive)
+    searches: Vec<Entry>,
+}
+
+/// Remember a successful search. Failures only warn: history must never break a search.
+pub fn record(query: &str) {
+    let query = query.trim();
+    if query.is_empty() || util::is_dry_run() {
+        return;
+    }
+    let mut h: History = match store::load(HISTORY) {
+        Ok(h) => h,
+        Err(e) => {
+            eprintln!("[WARN] 读取搜索历史失败: {:#}", e);
+            return;
+        }
+    };
+    let count = match h.searches.iter().position(|e| e.query.to_lowercase() == query.to_lowercase()) {
+        Some(i) => h.searches.remove(i).count + 1,
+        None => 1,
+    };
+    let last_used = chrono::Local::now().format("%Y-%m-%d %H:%M").to_string();
+    h.searches.insert(0, Entry { query: query.to_string(), count, last_used });
+    h.searches.truncate(MAX_ENTRIES);
+    if let Err(e) = store::save(HISTORY, &h) {
+        eprintln!("[WARN] 保存搜索历史失败: {:#}", e);
+    }
+}
+
+/// Past queries, most recent first, optionally only those starting with `prefix`
+pub fn recent(prefix: Option<&str>) -> Result<Vec<Entry>> {
+    let h: History = store::load(HISTORY)?;
+    let prefix = prefix.map(|p| p.trim().to_lowercase()).unwrap_or_default();
+    Ok(h.searches.into_iter().filter(|e| e.query.to_lowercase().starts_with(&prefix)).collect())
+}
+
+/// After a search that found nothing, point at past queries that extend `query`
+/// (e.g. a full actor name when only the surname was typed)
+pub fn suggest(query: &str) {
+    let Ok(entries) = recent(Some(query)) else { return };
+    let hints: Vec<&str> = entries
+        .iter()
+        .map(|e| e.query.as_str())
+        .filter(|q| !q.eq_ignore_ascii_case(query.trim()))
+        .take(5)
+        .collect();
+    if !hints.is_empty() {
+        println!("{} {}", "最近搜索过:".yellow(), hints.join("、"));
+    }
+}
+
+pub fn clear() -> Result<()> {
+    if util::dry_run_skip("将清空搜索历史") {
+        return Ok(());
+    }
+    store::save(HISTORY, &History::default())
+}
+
+/// `av search --recent [PREFIX]`
+pub fn run(prefix: Option<&str>, json: bool) -> Result<()> {
+    let entries = recent(prefix)?;
+    if json {
+        util::print_output(&entries, true);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("{}", "没有搜索历史".yellow());
+        return Ok(());
+    }
+    for (i, e) in entries.iter().enumerate() {
+        let times = if e.count > 1 { format!(" ×{}", e.count) } else { String::new() };
+        println!("{:>3}. {}{}  {}", i + 1, e.query.bold(), times, e.last_used.dimmed());
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+//! User scripts run after an action. Each hook command gets the same envelope webhooks
+//! receive on stdin and `AV_HOOK=<hook name>` in its environment:
+//!
+//! `{"event": "detail", "timestamp": 1700000000, "version": "0.1.0", "data": {...}}`
+//!
+//! Hooks run one after another and are awaited, so a one-shot command doesn't exit before
+//! they finish. A failing hook is warned about and never fails the action itself.
+//!
+//! Two hooks run before instead of after: `transform_magnet` rewrites the magnets of every
+//! fetched detail, and `validate` commands can veto a download, a stream rip, moving or
+//! linking videos into the library (and undoing that), a JavDB list change or a
+//! self-update by exiting non-zero.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::{self, HookConfig};
+use crate::notify::{webhook_payload, Event};
+use crate::types::AvDetail;
+use crate::util;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Hook {
+    /// `av detail` fetched a detail
+    Detail,
+    /// `av install` listed the magnets for a code
+    Install,
+    /// A watch job found a code it had not seen before
+    NewRelease,
+    /// A dispatched download finished
+    DownloadComplete,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::Detail => "on_detail",
+            Hook::Install => "on_install",
+            Hook::NewRelease => "on_new_release",
+            Hook::DownloadComplete => "on_download_complete",
+        }
+    }
+
+    fn event(self) -> &'static str {
+        match self {
+            Hook::Detail => "detail",
+            Hook::Install => "install",
+            Hook::NewRelease => "new_release",
+            Hook::DownloadComplete => "download_complete",
+        }
+    }
+
+    fn commands(self) -> &'static [HookConfig] {
+        let h = &config::get().hooks;
+        match self {
+            Hook::Detail => &h.on_detail,
+            Hook::Install => &h.on_install,
+            Hook::NewRelease => &h.on_new_release,
+            Hook::DownloadComplete => &h.on_download_complete,
+        }
+    }
+}
+
+/// Start `h`, feed it `payload` and wait for it within its timeout. The exit status is
+/// left to the caller.
+async fn run(name: &str, h: &HookConfig, payload: &Value, env: &[(&str, &str)]) -> Result<Output> {
+    util::debug(format!("hook {}: {}", name, h.command));
+    let mut child = tokio::process::Command::new(&h.command)
+        .args(&h.args)
+        .env("AV_HOOK", name)
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("无法启动 {} 钩子 {}", name, h.command))?;
+    let mut stdin = child.stdin.take().context("钩子 stdin 不可用")?;
+    // A script that never reads stdin closes the pipe early; that's fine
+    let _ = stdin.write_all(payload.to_string().as_bytes()).await;
+    let _ = stdin.write_all(b"\n").await;
+    drop(stdin);
+    let output = tokio::time::timeout(Duration::from_secs(h.timeout_secs.max(1)), child.wait_with_output())
+        .await
+        .with_context(|| format!("{} 钩子 {} 超时（{} 秒）", name, h.command, h.timeout_secs))??;
+    for (stream, bytes) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        let text = String::from_utf8_lossy(bytes);
+        if !text.trim().is_empty() {
+            util::debug(format!("hook {} {}: {}", h.command, stream, text.trim()));
+        }
+    }
+    Ok(output)
+}
+
+async fn call(hook: Hook, h: &HookConfig, payload: &Value) -> Result<()> {
+    let output = run(hook.name(), h, payload, &[]).await?;
+    if !output.status.success() {
+        bail!("{} 钩子 {} 退出码: {:?}", hook.name(), h.command, output.status.code());
+    }
+    Ok(())
+}
+
+/// Run every command configured for `hook` with `data` as the payload
+pub async fn fire<T: Serialize>(hook: Hook, data: &T) {
+    let commands = hook.commands();
+    if commands.is_empty() {
+        return;
+    }
+    let payload = webhook_payload(hook.event(), data);
+    for h in commands {
+        if util::dry_run_skip(format!("将运行 {} 钩子: {}", hook.name(), h.command)) {
+            continue;
+        }
+        if let Err(e) = call(hook, h, &payload).await {
+            eprintln!("[WARN] {:#}", e);
+        }
+    }
+}
+
+/// Hooks that mirror notification events
+pub async fn fire_event(event: &Event) {
+    let hook = match event {
+        Event::NewRelease { .. } => Hook::NewRelease,
+        Event::DownloadComplete { .. } => Hook::DownloadComplete,
+        Event::Birthday { .. } | Event::Test { .. } => return,
+    };
+    fire(hook, event).await;
+}
+
+/// Pass the detail's magnets through `hooks.transform_magnet`, which answers with a JSON
+/// array of the rewritten magnets in the same order. Anything else keeps the originals.
+pub async fn transform_magnets(d: &mut AvDetail) {
+    let Some(h) = &config::get().hooks.transform_magnet else { return };
+    let mut magnets: Vec<String> = d.magnets.clone();
+    for m in &d.magnet_infos {
+        if !magnets.contains(&m.url) {
+            magnets.push(m.url.clone());
+        }
+    }
+    if magnets.is_empty() {
+        return;
+    }
+    let payload = webhook_payload("transform_magnet", &json!({ "code": d.code, "magnets": magnets }));
+    let rewritten = match run("transform_magnet", h, &payload, &[]).await {
+        Ok(out) if out.status.success() => serde_json::from_slice::<Vec<String>>(&out.stdout)
+            .map_err(|e| anyhow!("transform_magnet 钩子的输出不是磁力数组: {}", e)),
+        Ok(out) => Err(anyhow!("transform_magnet 钩子 {} 退出码: {:?}", h.command, out.status.code())),
+        Err(e) => Err(e),
+    };
+    let rewritten = match rewritten {
+        Ok(r) if r.len() == magnets.len() => r,
+        Ok(r) => {
+            eprintln!("[WARN] transform_magnet 钩子返回了 {} 个磁力（应为 {} 个），保持原样", r.len(), magnets.len());
+            return;
+        }
+        Err(e) => {
+            eprintln!("[WARN] {:#}，保持原样", e);
+            return;
+        }
+    };
+    let map = |url: &str| magnets.iter().position(|m| m == url).map(|i| rewritten[i].clone());
+    for m in &mut d.magnets {
+        if let Some(new) = map(m) {
+            *m = new;
+        }
+    }
+    for info in &mut d.magnet_infos {
+        if let Some(new) = map(&info.url) {
+            info.url = new;
+        }
+    }
+}
+
+/// Ask every `hooks.validate` command whether `action` may go ahead. Any non-zero exit
+/// vetoes it, with the hook's stderr as the reason; a hook that can't be run or times out
+/// vetoes too, so a broken guard never waves something through.
+pub async fn validate<T: Serialize>(action: &str, data: &T) -> Result<()> {
+    validate_with(&config::get().hooks.validate, action, data).await
+}
+
+/// `validate` with the given guards rather than the configured ones
+pub(crate) async fn validate_with<T: Serialize>(guards: &[HookConfig], action: &str, data: &T) -> Result<()> {
+    if guards.is_empty() {
+        return Ok(());
+    }
+    let payload = webhook_payload("validate", &json!({ "action": action, "details": data }));
+    for h in guards {
+        let out = run("validate", h, &payload, &[("AV_ACTION", action)])
+            .await
+            .with_context(|| format!("{} 被拒绝：validate 钩子无法完成", action))?;
+        if !out.status.success() {
+            let reason = String::from_utf8_lossy(&out.stderr).trim().to_string();
+            if reason.is_empty() {
+                bail!("{} 被 validate 钩子 {} 否决", action, h.command);
+            }
+            bail!("{} 被 validate 钩子 {} 否决: {}", action, h.command, reason);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,327 @@
+use anyhow::{bail, Context, Result};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cancel;
+use crate::config::{self, Http2Mode};
+use crate::doh;
+use crate::metrics;
+use crate::mirrors;
+use crate::util;
+
+/// Longest wait between two attempts, and for a `Retry-After` asking for more
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Seconds a source request may take in all, 0 for no limit; `--timeout` or `[network]`
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// Attempts after the first; `--retries` or `[network]`
+static RETRIES: AtomicU32 = AtomicU32::new(0);
+
+/// `AV_HTTP_PROXY`, else `[network] proxy` from the config file
+pub fn proxy_url() -> Option<String> {
+    std::env::var("AV_HTTP_PROXY")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .or_else(|| config::get().network.proxy.clone().filter(|p| !p.trim().is_empty()))
+}
+
+pub fn is_socks(url: &str) -> bool {
+    url.trim().to_lowercase().starts_with("socks")
+}
+
+fn is_direct(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "" | "direct" | "none")
+}
+
+/// http(s)://, socks5:// (local DNS) and socks5h:// (DNS through the proxy) are
+/// accepted; socks needs the `socks` cargo feature.
+pub fn parse_proxy(url: &str) -> Result<Url> {
+    if is_socks(url) && !cfg!(feature = "socks") {
+        bail!("代理 {} 需要 SOCKS 支持，请使用 --features socks 重新编译", url);
+    }
+    let parsed = Url::parse(url.trim()).with_context(|| format!("代理地址无效: {}", url))?;
+    reqwest::Proxy::all(parsed.clone()).with_context(|| format!("代理地址无效: {}", url))?;
+    Ok(parsed)
+}
+
+/// Where each source's traffic goes: `None` means direct
+struct Routes {
+    default: Option<Url>,
+    per_source: HashMap<String, Option<Url>>,
+}
+
+impl Routes {
+    /// The source's own entry, else direct for hosts on this machine or the LAN (download
+    /// clients, media servers, a local plugin's API), else the default proxy
+    fn route(&self, url: &Url) -> Option<Url> {
+        let source = metrics::source_of(url.as_str());
+        match self.per_source.get(&source) {
+            Some(route) => route.clone(),
+            None if url.host_str().is_some_and(is_local_host) => None,
+            None => self.default.clone(),
+        }
+    }
+}
+
+/// Loopback, private and link-local addresses, and names only a local resolver knows:
+/// `localhost`, single labels (`nas`) and `.local`, `.lan`, `.home.arpa` names
+pub fn is_local_host(host: &str) -> bool {
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified(),
+        Ok(IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            ip.is_loopback() || ip.is_unspecified() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+        }
+        Err(_) => {
+            let name = host.trim_end_matches('.').to_lowercase();
+            !name.contains('.') || [".localhost", ".local", ".lan", ".home.arpa"].iter().any(|s| name.ends_with(s))
+        }
+    }
+}
+
+/// `[network.proxies]` entries (e.g. `javdb = "socks5h://..."`, `sukebei = "direct"`)
+/// override the default proxy for that source.
+fn routes() -> Result<Routes> {
+    let default = proxy_url().map(|u| parse_proxy(&u)).transpose()?;
+    let mut per_source = HashMap::new();
+    for (source, value) in &config::get().network.proxies {
+        let route = if is_direct(value) {
+            None
+        } else {
+            Some(parse_proxy(value).with_context(|| format!("[network.proxies] {}", source))?)
+        };
+        per_source.insert(source.to_lowercase(), route);
+    }
+    Ok(Routes { default, per_source })
+}
+
+/// The raw proxy setting that applies to `source` (`None` = direct), before validation
+pub fn configured_route(source: &str) -> Option<String> {
+    let per_source = config::get()
+        .network
+        .proxies
+        .iter()
+        .find(|(s, _)| s.to_lowercase() == source)
+        .map(|(_, v)| v.clone());
+    match per_source {
+        Some(v) if is_direct(&v) => None,
+        Some(v) => Some(v),
+        None => proxy_url(),
+    }
+}
+
+/// Validate proxy, `[network.hosts]` and `[network] impersonate` settings; called once at startup so mistakes fail loudly
+pub fn check_network() -> Result<()> {
+    routes()?;
+    host_overrides()?;
+    if let Some(setting) = config::get().network.impersonate.as_deref().filter(|s| !s.trim().is_empty()) {
+        #[cfg(feature = "impersonate")]
+        crate::impersonate::resolve(setting)?;
+        #[cfg(not(feature = "impersonate"))]
+        bail!("[network] impersonate = \"{}\" 需要 impersonate 功能，请使用 --features impersonate 重新编译", setting);
+    }
+    Ok(())
+}
+
+fn host_of(url: &str) -> Option<String> {
+    Url::parse(url).ok()?.host_str().map(str::to_string)
+}
+
+/// Host names a source is fetched from, for `[network.hosts]` keys naming a source
+fn source_hosts(source: &str) -> Vec<String> {
+    let fixed: &[&str] = match source {
+        "javdb" => return mirrors::list().iter().filter_map(|m| Url::parse(m).ok()?.host_str().map(str::to_string)).collect(),
+        "sukebei" => &["sukebei.nyaa.si"],
+        "javlibrary" => &["www.javlibrary.com", "javlibrary.com"],
+        "dmm" => &["api.dmm.com"],
+        "minnano" => &["www.minnano-av.com"],
+        "btsow" => return host_of(&crate::sources::btsow::base()).into_iter().collect(),
+        "torrentkitty" => return host_of(&crate::sources::torrentkitty::base()).into_iter().collect(),
+        _ => &[],
+    };
+    fixed.iter().map(|h| h.to_string()).collect()
+}
+
+/// `[network.hosts]` as host → addresses. Entries for a host name win over the entry for
+/// its source.
+pub fn host_overrides() -> Result<Vec<(String, Vec<SocketAddr>)>> {
+    let mut by_host: HashMap<String, (bool, Vec<SocketAddr>)> = HashMap::new();
+    for (key, addrs) in &config::get().network.hosts {
+        let key = key.trim().to_lowercase();
+        let parsed = addrs
+            .list()
+            .iter()
+            .map(|a| a.trim().parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 0)))
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("[network.hosts] {} 的 IP 地址无效", key))?;
+        if parsed.is_empty() {
+            bail!("[network.hosts] {} 没有地址", key);
+        }
+        let is_host = key.contains('.');
+        let hosts = if is_host { vec![key.clone()] } else { source_hosts(&key) };
+        if hosts.is_empty() {
+            bail!("[network.hosts] {} 既不是域名也不是已知数据源（javdb、sukebei、javlibrary、dmm、minnano、btsow、torrentkitty）", key);
+        }
+        for host in hosts {
+            match by_host.get(&host) {
+                Some((true, _)) if !is_host => {}
+                _ => {
+                    by_host.insert(host, (is_host, parsed.clone()));
+                }
+            }
+        }
+    }
+    Ok(by_host.into_iter().map(|(host, (_, addrs))| (host, addrs)).collect())
+}
+
+/// Route a client through the configured proxies and resolve names with `[network.hosts]`
+/// and, when `[network] doh` is set, DNS-over-HTTPS (LAN names still through the system).
+/// Source, download-client, media-server and notifier clients are all built here.
+pub fn with_proxy(builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    let builder = with_routes(tuned(builder));
+    let mut builder = match doh::resolver() {
+        Some(r) => builder.dns_resolver(Arc::new(r)),
+        None => builder,
+    };
+    for (host, addrs) in host_overrides().unwrap_or_default() {
+        builder = builder.resolve_to_addrs(&host, &addrs);
+    }
+    builder
+}
+
+/// `[network.client]`: protocol, compression, pooling and keepalive settings
+fn tuned(builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    let t = &config::get().network.client;
+    let secs = |s: u64| (s > 0).then(|| Duration::from_secs(s));
+    let mut builder = builder
+        .gzip(t.compression)
+        .brotli(t.compression)
+        .deflate(t.compression)
+        .pool_idle_timeout(secs(t.pool_idle_timeout_secs))
+        .pool_max_idle_per_host(t.pool_max_idle_per_host)
+        .tcp_keepalive(secs(t.tcp_keepalive_secs));
+    if let Some(timeout) = secs(t.connect_timeout_secs) {
+        builder = builder.connect_timeout(timeout);
+    }
+    match t.http2 {
+        Http2Mode::Off => builder.http1_only(),
+        mode => {
+            let builder = if mode == Http2Mode::PriorKnowledge { builder.http2_prior_knowledge() } else { builder };
+            // Large windows for big listing pages and images over one multiplexed connection
+            builder.http2_adaptive_window(true).http2_keep_alive_interval(secs(t.tcp_keepalive_secs))
+        }
+    }
+}
+
+/// Just the proxies, picking one per request by source. Invalid settings are reported at
+/// startup (`check_network`), so here they are skipped.
+pub fn with_routes(builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    let routes = match routes() {
+        Ok(r) => r,
+        Err(_) => return builder,
+    };
+    if routes.default.is_none() && routes.per_source.values().all(|r| r.is_none()) {
+        return builder;
+    }
+    let routes = Arc::new(routes);
+    builder.proxy(reqwest::Proxy::custom(move |url| routes.route(url)))
+}
+
+pub fn set_timeout(secs: Option<u64>) {
+    TIMEOUT_SECS.store(secs.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// The limit on a whole source request, `None` to wait as long as the server keeps the
+/// connection open. Set per request, so `av shell` lines can change it
+pub fn request_timeout() -> Option<Duration> {
+    Some(TIMEOUT_SECS.load(Ordering::Relaxed)).filter(|s| *s > 0).map(Duration::from_secs)
+}
+
+/// `req` with `request_timeout()`, when there is one
+pub fn with_timeout(req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match request_timeout() {
+        Some(t) => req.timeout(t),
+        None => req,
+    }
+}
+
+pub fn set_retries(n: u32) {
+    RETRIES.store(n, Ordering::Relaxed);
+}
+
+pub fn retries() -> u32 {
+    RETRIES.load(Ordering::Relaxed)
+}
+
+/// Failures that may not happen again: the connection failed or timed out, or the server
+/// was rate limiting, overloaded or briefly down
+pub fn is_transient(result: &reqwest::Result<reqwest::Response>) -> bool {
+    match result {
+        Err(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+        Ok(resp) => is_transient_status(resp.status().as_u16()),
+    }
+}
+
+pub fn is_transient_status(status: u16) -> bool {
+    matches!(status, 429 | 500 | 502 | 503 | 504)
+}
+
+/// The wait before retry `attempt` (from 1): 1s, 2s, 4s ..., or a `Retry-After` given in seconds
+pub fn backoff(attempt: u32, retry_after: Option<&reqwest::header::HeaderValue>) -> Duration {
+    let asked = retry_after.and_then(|v| v.to_str().ok()?.trim().parse::<u64>().ok()).map(Duration::from_secs);
+    asked.unwrap_or_else(|| Duration::from_secs(1 << (attempt - 1).min(5))).min(MAX_BACKOFF)
+}
+
+/// `send` again while it fails in passing, up to `retries()` more times; `what` names the
+/// request in the debug log. Every source request goes through here, so `--retries`
+/// applies to all of them
+pub async fn with_retries<F, Fut>(what: &str, mut send: F) -> reqwest::Result<reqwest::Response>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = reqwest::Result<reqwest::Response>>,
+{
+    let mut attempt = 0;
+    loop {
+        let result = send().await;
+        if attempt >= retries() || !is_transient(&result) || cancel::is_cancelled() {
+            return result;
+        }
+        attempt += 1;
+        let (failure, wait) = match &result {
+            Ok(resp) => (format!("HTTP {}", resp.status().as_u16()), backoff(attempt, resp.headers().get(reqwest::header::RETRY_AFTER))),
+            Err(e) => (e.to_string(), backoff(attempt, None)),
+        };
+        util::debug(format!("{}: {}, retry {}/{} in {:?}", what, failure, attempt, retries(), wait));
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_hosts_go_direct() {
+        for host in ["localhost", "127.0.0.1", "[::1]", "nas", "nas.lan", "tv.local", "box.home.arpa", "10.0.0.2", "172.20.1.1", "192.168.1.10", "169.254.0.1", "[fd00::1]", "[fe80::1]"] {
+            assert!(is_local_host(host), "{}", host);
+        }
+        for host in ["javdb.com", "sukebei.nyaa.si", "8.8.8.8", "172.32.0.1", "[2001:db8::1]", "local.example.com"] {
+            assert!(!is_local_host(host), "{}", host);
+        }
+
+        let proxy = Url::parse("http://proxy.example:3128").unwrap();
+        let routes = Routes { default: Some(proxy.clone()), per_source: HashMap::from([("192.168.1.10".to_string(), Some(proxy.clone()))]) };
+        let route = |u: &str| routes.route(&Url::parse(u).unwrap());
+        assert_eq!(route("http://localhost:8080/api/v2/auth/login"), None);
+        assert_eq!(route("http://nas.lan:8112/json"), None);
+        assert_eq!(route("https://sukebei.nyaa.si/"), Some(proxy.clone()));
+        // An entry naming the host still applies
+        assert_eq!(route("http://192.168.1.10:8096/"), Some(proxy));
+    }
+}
@@ -0,0 +1,191 @@
+//! Browser TLS impersonation through curl-impersonate. Some JavDB mirrors (or the
+//! Cloudflare rule in front of them) reject rustls' ClientHello no matter what headers
+//! come with it; `[network] impersonate = "chrome"` sends page fetches through one of
+//! curl-impersonate's `curl_<browser><version>` wrappers instead, which carry the real
+//! browser's TLS and HTTP/2 fingerprint along with its headers.
+//!
+//! Only page fetches (`scraper::send`) go this way. The proxy, `[network.hosts]`, DoH and
+//! the per-source cookie jars still apply.
+
+use anyhow::{bail, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, SET_COOKIE};
+use reqwest::cookie::CookieStore;
+use reqwest::{ResponseBuilderExt, Url};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::LazyLock;
+use which::which;
+
+use crate::config;
+use crate::cookies;
+use crate::http;
+use crate::util;
+
+const CONNECT_TIMEOUT_SECS: &str = "15";
+const MAX_TIME_SECS: &str = "60";
+
+/// Wrapper name prefixes for a browser family; the original project and its maintained
+/// fork name Firefox differently
+fn prefixes(browser: &str) -> &'static [&'static str] {
+    match browser {
+        "chrome" => &["curl_chrome"],
+        "edge" => &["curl_edge"],
+        "firefox" | "ff" => &["curl_firefox", "curl_ff"],
+        "safari" => &["curl_safari"],
+        _ => &[],
+    }
+}
+
+/// `131` / `15_5` → comparable numbers
+fn version_of(suffix: &str) -> Option<Vec<u32>> {
+    suffix.split('_').map(|p| p.parse().ok()).collect()
+}
+
+/// The newest `curl_<browser><version>` on `PATH`
+fn newest_wrapper(browser: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    let mut best: Option<(Vec<u32>, PathBuf)> = None;
+    for dir in std::env::split_paths(&path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let stem = name.strip_suffix(".exe").unwrap_or(&name);
+            for prefix in prefixes(browser) {
+                let Some(version) = stem.strip_prefix(prefix).and_then(version_of) else { continue };
+                if best.as_ref().is_none_or(|(v, _)| version > *v) {
+                    best = Some((version, entry.path()));
+                }
+            }
+        }
+    }
+    best.map(|(_, p)| p)
+}
+
+/// The program `[network] impersonate` names: a browser family ("chrome", "edge",
+/// "firefox", "safari") picks its newest wrapper; anything else is a program name or path
+pub fn resolve(setting: &str) -> Result<PathBuf> {
+    let setting = setting.trim();
+    let browser = setting.to_lowercase();
+    if !prefixes(&browser).is_empty() {
+        return newest_wrapper(&browser).with_context(|| {
+            format!("[network] impersonate = \"{}\"，但 PATH 中没有 {}* （curl-impersonate）", setting, prefixes(&browser)[0])
+        });
+    }
+    which(setting).with_context(|| format!("[network] impersonate: 未找到程序 {}", setting))
+}
+
+static PROGRAM: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    let setting = config::get().network.impersonate.clone().filter(|s| !s.trim().is_empty())?;
+    resolve(&setting).ok()
+});
+
+/// The wrapper to fetch pages with, when impersonation is on (startup already checked it
+/// can be found)
+pub fn program() -> Option<&'static Path> {
+    PROGRAM.as_deref()
+}
+
+/// `host:port:addr` entries for curl's `--resolve`
+fn resolve_args() -> Vec<String> {
+    let mut args = Vec::new();
+    for (host, addrs) in http::host_overrides().unwrap_or_default() {
+        let addrs: Vec<String> = addrs
+            .iter()
+            .map(|a| if a.is_ipv6() { format!("[{}]", a.ip()) } else { a.ip().to_string() })
+            .collect();
+        for port in [443, 80] {
+            args.push(format!("{}:{}:{}", host, port, addrs.join(",")));
+        }
+    }
+    args
+}
+
+/// Header blocks as curl's `-D` writes them, one per response along the redirect chain:
+/// the headers of the last one, and every `Set-Cookie` seen
+fn parse_headers(raw: &str) -> (HeaderMap, Vec<HeaderValue>) {
+    let mut last = HeaderMap::new();
+    let mut set_cookies = Vec::new();
+    for line in raw.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with("HTTP/") {
+            last = HeaderMap::new();
+            continue;
+        }
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.trim().as_bytes()), HeaderValue::from_str(value.trim())) else {
+            continue;
+        };
+        if name == SET_COOKIE {
+            set_cookies.push(value.clone());
+        }
+        last.append(name, value);
+    }
+    (last, set_cookies)
+}
+
+/// GET `url` through the wrapper. `cookie` replaces the source's jar (`AV_JAVDB_COOKIE`).
+/// The wrapper sends its own browser's User-Agent and client hints, which have to match
+/// the TLS fingerprint, so only Referer and Cookie are added.
+pub async fn get(program: &Path, url: &str, source: &str, referer: Option<&HeaderValue>, cookie: Option<HeaderValue>) -> Result<reqwest::Response> {
+    let parsed = Url::parse(url).with_context(|| format!("URL 无效: {}", url))?;
+    let jars = cookies::provider();
+    let cookie = cookie.or_else(|| jars.cookies(&parsed));
+    let tmp = tempfile::tempdir().context("创建临时目录失败")?;
+    let (body_path, headers_path) = (tmp.path().join("body"), tmp.path().join("headers"));
+
+    // `--timeout` / `[network] timeout_secs` when set
+    let max_time = http::request_timeout().map_or_else(|| MAX_TIME_SECS.to_string(), |t| t.as_secs().max(1).to_string());
+    let mut cmd = tokio::process::Command::new(program);
+    cmd.args(["-sS", "-L", "--max-redirs", "10", "--compressed"])
+        .args(["--connect-timeout", CONNECT_TIMEOUT_SECS, "--max-time", &max_time])
+        .arg("-o")
+        .arg(&body_path)
+        .arg("-D")
+        .arg(&headers_path)
+        .args(["-w", "%{http_code} %{url_effective}"]);
+    for (name, value) in [("Referer", referer.cloned()), ("Cookie", cookie)] {
+        if let Some(v) = value.and_then(|v| v.to_str().ok().map(str::to_string)) {
+            cmd.arg("-H").arg(format!("{}: {}", name, v));
+        }
+    }
+    if let Some(proxy) = http::configured_route(source) {
+        cmd.arg("-x").arg(proxy.trim());
+    }
+    for entry in resolve_args() {
+        cmd.arg("--resolve").arg(entry);
+    }
+    if let Some(doh) = config::get().network.doh.as_deref().filter(|d| !d.trim().is_empty()) {
+        cmd.arg("--doh-url").arg(doh.trim());
+    }
+    util::debug(format!("impersonate: {} {}", program.display(), url));
+    let out = cmd
+        .arg(url)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("启动 {} 失败", program.display()))?;
+    if !out.status.success() {
+        bail!("{} 请求失败: {}", program.display(), String::from_utf8_lossy(&out.stderr).trim());
+    }
+
+    let written = String::from_utf8_lossy(&out.stdout).to_string();
+    let (status, final_url) = written.trim().split_once(' ').context("curl-impersonate 输出格式错误")?;
+    let status: u16 = status.parse().context("curl-impersonate 输出格式错误")?;
+    let final_url = Url::parse(final_url).unwrap_or(parsed);
+    let raw_headers = std::fs::read_to_string(&headers_path).unwrap_or_default();
+    let (headers, set_cookies) = parse_headers(&raw_headers);
+    if !set_cookies.is_empty() {
+        jars.set_cookies(&mut set_cookies.iter(), &final_url);
+    }
+    let body = std::fs::read(&body_path).unwrap_or_default();
+
+    let mut builder = ::http::Response::builder().status(status).url(final_url);
+    for (name, value) in &headers {
+        // curl already decoded the body
+        if name != reqwest::header::CONTENT_ENCODING && name != reqwest::header::CONTENT_LENGTH {
+            builder = builder.header(name, value);
+        }
+    }
+    Ok(reqwest::Response::from(builder.body(body).context("构造响应失败")?))
+}
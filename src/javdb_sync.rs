@@ -0,0 +1,181 @@
+use anyhow::{bail, Context, Result};
+use scraper::{Html, Selector};
+use std::sync::LazyLock;
+
+use crate::cancel;
+use crate::code;
+use crate::hooks;
+use crate::library::Library;
+use crate::scraper as av_scraper;
+use crate::selectors;
+use crate::types::AvItem;
+use crate::util;
+
+/// Safety stop for list pagination
+const MAX_PAGES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum List {
+    /// 想看
+    Wanted,
+    /// 看過
+    Watched,
+}
+
+impl List {
+    fn path(self) -> &'static str {
+        match self {
+            List::Wanted => "/users/want_watch_videos",
+            List::Watched => "/users/watched_videos",
+        }
+    }
+
+    fn review_status(self) -> &'static str {
+        match self {
+            List::Wanted => "want_to_watch",
+            List::Watched => "watched",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            List::Wanted => "想看",
+            List::Watched => "看過",
+        }
+    }
+}
+
+fn require_login() -> Result<()> {
+    if !av_scraper::javdb_logged_in() {
+        bail!("需要先登录 JavDB（av login javdb）");
+    }
+    Ok(())
+}
+
+fn session_expired(resp: &reqwest::Response) -> bool {
+    resp.url().path().starts_with("/login")
+}
+
+async fn fetch_page(c: reqwest::Client, list: List, page: usize) -> Result<String> {
+    let url = format!("{}{}?page={}", av_scraper::javdb_base(), list.path(), page);
+    util::debug(format!("JavDB {} page: {}", list.label(), url));
+    let resp = av_scraper::send(&c, &url).await?;
+    if session_expired(&resp) {
+        bail!("JavDB 会话已失效，请重新登录（av login javdb）");
+    }
+    Ok(resp.error_for_status()?.text().await?)
+}
+
+/// Read every page of one of the user's JavDB lists. The page count is unknown up front,
+/// so pages are requested `--jobs` at a time and anything past the last page is dropped.
+pub async fn fetch_list(list: List) -> Result<Vec<AvItem>> {
+    require_login()?;
+    let c = av_scraper::client();
+    let mut items: Vec<AvItem> = Vec::new();
+    let mut page = 1;
+    while page <= MAX_PAGES {
+        let last = (page + util::jobs() - 1).min(MAX_PAGES);
+        let mut tasks = tokio::task::JoinSet::new();
+        for p in page..=last {
+            let c = c.clone();
+            tasks.spawn(async move { (p, fetch_page(c, list, p).await) });
+        }
+        let mut bodies = tasks.join_all().await;
+        bodies.sort_by_key(|(p, _)| *p);
+        for (_, body) in bodies {
+            let body = match body {
+                // Keep the pages read so far; `pull` still merges and saves them
+                Err(_) if cancel::is_cancelled() => return Ok(items),
+                r => r?,
+            };
+            let before = items.len();
+            for card in av_scraper::javdb_cards(&body) {
+                if !items.iter().any(|i| i.code == card.code) {
+                    items.push(card);
+                }
+            }
+            if items.len() == before || !has_next_page(&body) {
+                return Ok(items);
+            }
+        }
+        page = last + 1;
+    }
+    Ok(items)
+}
+
+static NEXT_PAGE: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a.pagination-next, a[rel='next']").unwrap());
+
+fn has_next_page(body: &str) -> bool {
+    Html::parse_document(body).select(&NEXT_PAGE).next().is_some()
+}
+
+/// Merge a remote list into the local library; returns (remote total, newly added locally)
+pub async fn pull(list: List) -> Result<(usize, usize)> {
+    let items = fetch_list(list).await?;
+    let mut lib = Library::load()?;
+    let mut added = 0;
+    for item in &items {
+        let changed = match list {
+            List::Wanted => lib.mark_wanted(&item.code, Some(&item.title)),
+            List::Watched => lib.mark_watched(&item.code, Some(&item.title)),
+        };
+        if changed {
+            added += 1;
+        }
+    }
+    lib.save()?;
+    Ok((items.len(), added))
+}
+
+/// Put a code on one of the user's JavDB lists (the video page's review form), then
+/// mirror the change locally.
+pub async fn push(list: List, code: &str) -> Result<String> {
+    require_login()?;
+    let c = av_scraper::client();
+    let video_url = av_scraper::javdb_video_url(&c, code).await?;
+    let (token, title) = {
+        let body = av_scraper::get_text(&c, &video_url).await?;
+        let doc = Html::parse_document(&body);
+        let title_sel = selectors::javdb("title");
+        let token = doc
+            .select(selectors::javdb("csrf_meta"))
+            .next()
+            .and_then(|m| m.value().attr("content"))
+            .map(|s| s.to_string())
+            .context("页面中未找到 csrf-token（会话可能已失效）")?;
+        let title = doc.select(title_sel).next().map(|n| n.text().collect::<String>());
+        (token, title)
+    };
+    let code = code::normalize(code);
+    if util::dry_run_skip(format!("将在 JavDB 上把 {}（{}）标记为「{}」", code, video_url, list.label())) {
+        return Ok(format!("未修改 {}（dry-run）", code));
+    }
+    hooks::validate("javdb_mark", &serde_json::json!({ "code": code, "list": list.label() })).await?;
+    let review_url = format!("{}/reviews", video_url.trim_end_matches('/'));
+    let mut post = c.post(&review_url).header("x-csrf-token", &token);
+    if let Some(cookie) = av_scraper::javdb_cookie_for(&review_url) {
+        post = post.header(reqwest::header::COOKIE, cookie);
+    }
+    let resp = post
+        .form(&[
+            ("authenticity_token", token.as_str()),
+            ("video_review[status]", list.review_status()),
+            ("video_review[score]", ""),
+            ("video_review[content]", ""),
+        ])
+        .send()
+        .await
+        .context("JavDB 请求失败")?;
+    if session_expired(&resp) {
+        bail!("JavDB 会话已失效，请重新登录（av login javdb）");
+    }
+    resp.error_for_status().context("JavDB 拒绝了该操作")?;
+
+    let mut lib = Library::load()?;
+    match list {
+        List::Wanted => lib.mark_wanted(&code, title.as_deref()),
+        List::Watched => lib.mark_watched(&code, title.as_deref()),
+    };
+    lib.save()?;
+    Ok(format!("已将 {} 标记为「{}」", code, list.label()))
+}
@@ -0,0 +1,224 @@
+//! Every dispatch of a magnet to a downloader (aria2c, a torrent client, the remote node,
+//! the system BT client) with what came of it, so failed ones can be found and sent again
+//! with `av jobs replay`.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::scraper;
+use crate::store;
+use crate::util;
+
+const JOURNAL: &str = "journal";
+/// Oldest dispatches beyond this are forgotten
+const MAX_ENTRIES: usize = 1000;
+
+/// Serializes the load-modify-save of concurrent dispatches
+static LOCK: Mutex<()> = Mutex::new(());
+/// The entry being replayed, recorded on the dispatches it makes
+static REPLAYING: Mutex<Option<u64>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// Handed to a client that downloads on its own
+    Added,
+    /// Downloaded to the end (aria2c, or a client that was waited for)
+    Done,
+    Failed,
+}
+
+impl Outcome {
+    fn label(self) -> colored::ColoredString {
+        match self {
+            Outcome::Added => "已添加".cyan(),
+            Outcome::Done => "已完成".green(),
+            Outcome::Failed => "失败".red(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: u64,
+    /// Local time, `YYYY-MM-DD HH:MM:SS`
+    pub at: String,
+    /// Empty for a bare magnet whose name carries no code
+    pub code: String,
+    pub magnet: String,
+    /// `aria2c`, `qbittorrent`, `deluge`, `rtorrent`, `ssh` or `system`
+    pub client: String,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_of: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Journal {
+    next_id: u64,
+    /// Oldest first
+    entries: Vec<Entry>,
+}
+
+/// Record a dispatch of `magnet` to `client` and how it went; `finished` says a success
+/// was a completed download rather than a hand-over. Failures only warn: the journal must
+/// never fail a download.
+pub fn record(code: &str, magnet: &str, client: &str, result: &Result<()>, finished: bool) {
+    let (outcome, error) = match result {
+        Ok(()) if finished => (Outcome::Done, None),
+        Ok(()) => (Outcome::Added, None),
+        Err(e) => (Outcome::Failed, Some(format!("{:#}", e))),
+    };
+    let _guard = LOCK.lock().unwrap();
+    let mut j: Journal = match store::load(JOURNAL) {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("[WARN] 读取下载记录失败: {:#}", e);
+            return;
+        }
+    };
+    j.next_id = j.next_id.max(j.entries.last().map_or(0, |e| e.id)) + 1;
+    j.entries.push(Entry {
+        id: j.next_id,
+        at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        code: code.to_string(),
+        magnet: magnet.to_string(),
+        client: client.to_string(),
+        outcome,
+        error,
+        replay_of: *REPLAYING.lock().unwrap(),
+    });
+    let excess = j.entries.len().saturating_sub(MAX_ENTRIES);
+    j.entries.drain(..excess);
+    if let Err(e) = store::save(JOURNAL, &j) {
+        eprintln!("[WARN] 保存下载记录失败: {:#}", e);
+    }
+}
+
+fn entries() -> Result<Vec<Entry>> {
+    Ok(store::load::<Journal>(JOURNAL)?.entries)
+}
+
+/// Lower-case infohash → code (empty for a bare magnet) of every magnet handed over
+/// successfully, the latest dispatch winning
+pub fn dispatched() -> Result<HashMap<String, String>> {
+    Ok(entries()?
+        .into_iter()
+        .filter(|e| e.outcome != Outcome::Failed)
+        .filter_map(|e| Some((util::magnet_infohash(&e.magnet)?.to_lowercase(), e.code)))
+        .collect())
+}
+
+/// Failed dispatches with no later success for the same magnet or code (as when the
+/// fallback's next magnet went through)
+fn unresolved(all: &[Entry]) -> Vec<&Entry> {
+    let resolves = |e: &Entry, later: &Entry| {
+        later.outcome != Outcome::Failed && (later.magnet == e.magnet || (!e.code.is_empty() && later.code == e.code))
+    };
+    all.iter()
+        .enumerate()
+        .filter(|(i, e)| e.outcome == Outcome::Failed && !all[i + 1..].iter().any(|l| resolves(e, l)))
+        .map(|(_, e)| e)
+        .collect()
+}
+
+/// `av jobs list`: the latest `limit` dispatches, newest first
+pub fn list(failed: bool, limit: usize, json: bool) -> Result<()> {
+    let all = entries()?;
+    let mut shown: Vec<&Entry> = if failed { unresolved(&all) } else { all.iter().collect() };
+    shown.reverse();
+    shown.truncate(limit);
+    if json {
+        util::print_output(&shown, true);
+        return Ok(());
+    }
+    if shown.is_empty() {
+        println!("{}", if failed { "没有未解决的失败下载" } else { "还没有下载记录" }.yellow());
+        return Ok(());
+    }
+    for e in shown {
+        let code = if e.code.is_empty() { "-" } else { e.code.as_str() };
+        println!("{:>5}  {}  {}  {}  {}", e.id.to_string().bold(), e.at, util::pad(code, 12), util::pad(&e.client, 11), e.outcome.label());
+    }
+    Ok(())
+}
+
+fn find(all: &[Entry], id: u64) -> Result<&Entry> {
+    all.iter().find(|e| e.id == id).with_context(|| format!("没有编号为 {} 的下载记录", id))
+}
+
+/// `av jobs show ID`
+pub fn show(id: u64, json: bool) -> Result<()> {
+    let all = entries()?;
+    let e = find(&all, id)?;
+    if json {
+        util::print_output(e, true);
+        return Ok(());
+    }
+    println!("{} {}", "编号:".bold(), e.id);
+    println!("{} {}", "时间:".bold(), e.at);
+    if !e.code.is_empty() {
+        println!("{} {}", "番号:".bold(), e.code);
+    }
+    println!("{} {}", "下载器:".bold(), e.client);
+    println!("{} {}", "结果:".bold(), e.outcome.label());
+    if let Some(err) = &e.error {
+        println!("{} {}", "错误:".bold(), err.red());
+    }
+    if let Some(of) = e.replay_of {
+        println!("{} #{}", "重试自:".bold(), of);
+    }
+    println!("{} {}", "磁力:".bold(), e.magnet.cyan());
+    Ok(())
+}
+
+/// `av jobs replay ID... | --failed`: dispatch the same magnets again, through whatever
+/// client is configured now, placed by the code's detail when there is a code
+pub async fn replay(ids: &[u64], failed: bool) -> Result<()> {
+    let all = entries()?;
+    let targets: Vec<Entry> = if failed {
+        // A code whose fallback magnets all failed is sent again once, from its first choice
+        let mut seen = std::collections::HashSet::new();
+        unresolved(&all)
+            .into_iter()
+            .filter(|e| seen.insert(if e.code.is_empty() { e.magnet.clone() } else { e.code.clone() }))
+            .cloned()
+            .collect()
+    } else {
+        ids.iter().map(|&id| find(&all, id).cloned()).collect::<Result<_>>()?
+    };
+    if targets.is_empty() {
+        println!("{}", "没有需要重试的下载".yellow());
+        return Ok(());
+    }
+    let mut errors = 0;
+    for e in &targets {
+        crate::cancel::check()?;
+        *REPLAYING.lock().unwrap() = Some(e.id);
+        let result = async {
+            if e.code.is_empty() {
+                return util::download_magnet(&e.magnet).await;
+            }
+            let d = scraper::fetch_detail(&e.code).await?;
+            util::download_detail(&d, Some(e.magnet.clone()), None).await
+        }
+        .await;
+        *REPLAYING.lock().unwrap() = None;
+        if let Err(err) = result {
+            if crate::cancel::is_cancelled() {
+                return Err(err);
+            }
+            eprintln!("[WARN] #{} {} 重试失败: {:#}", e.id, e.code, err);
+            errors += 1;
+        }
+    }
+    if errors > 0 {
+        bail!("{}/{} 个下载重试失败", errors, targets.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,292 @@
+//! Credentials kept in the OS keychain instead of the environment or `config.toml`, through
+//! the `keyring` crate: the macOS Keychain, the Secret Service on Linux (GNOME Keyring,
+//! KWallet; spoken over D-Bus in Rust, no libdbus) and the Windows Credential Manager. A
+//! variable or config value still wins; the keychain is asked only for what neither sets.
+//! The names stored are listed in the data directory (never the values), so a run doesn't
+//! query the keychain for a secret that was never saved. `av secret set` reads the value
+//! from stdin only, never from the command line where shell history and `ps` keep it.
+//! Cookie jars holding a login (JavDB's, after `av login javdb`) are kept here as well, as
+//! their JSON, with the file under `cookies/` only as the fallback when there is no keychain.
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use crate::config;
+use crate::store;
+use crate::util;
+
+/// The keychain item's service; the secret's name is its account
+const SERVICE: &str = "av";
+
+const STORE: &str = "keychain";
+
+/// Set once a write failed: there is no usable keychain this run (no Secret Service, a
+/// locked or headless session), and cookie jars go to their files instead
+static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// Lookups already made this run
+static CACHE: LazyLock<Mutex<HashMap<Secret, Option<String>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Secret {
+    /// DMM 的 API ID（DMM_API_ID）
+    DmmApiId,
+    /// DMM 的 Affiliate ID（DMM_AFFILIATE_ID）
+    DmmAffiliateId,
+    /// JavDB 的 Cookie 头（AV_JAVDB_COOKIE）
+    JavdbCookie,
+    /// [qbittorrent] password
+    QbittorrentPassword,
+    /// [deluge] password
+    DelugePassword,
+    /// [rtorrent] password
+    RtorrentPassword,
+}
+
+impl Secret {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Secret::DmmApiId => "dmm-api-id",
+            Secret::DmmAffiliateId => "dmm-affiliate-id",
+            Secret::JavdbCookie => "javdb-cookie",
+            Secret::QbittorrentPassword => "qbittorrent-password",
+            Secret::DelugePassword => "deluge-password",
+            Secret::RtorrentPassword => "rtorrent-password",
+        }
+    }
+
+    /// Where it is set in the clear, which takes precedence: `env:<VAR>` or the config key
+    fn plain(self) -> &'static str {
+        match self {
+            Secret::DmmApiId => "env:DMM_API_ID",
+            Secret::DmmAffiliateId => "env:DMM_AFFILIATE_ID",
+            Secret::JavdbCookie => "env:AV_JAVDB_COOKIE",
+            Secret::QbittorrentPassword => "qbittorrent.password",
+            Secret::DelugePassword => "deluge.password",
+            Secret::RtorrentPassword => "rtorrent.password",
+        }
+    }
+
+    /// Whether the variable or config key is set
+    fn plain_set(self) -> bool {
+        let cfg = config::get();
+        let set = |v: Option<&String>| v.is_some_and(|v| !v.trim().is_empty());
+        match self.plain().strip_prefix("env:") {
+            Some(var) => set(std::env::var(var).ok().as_ref()),
+            None => match self {
+                Secret::QbittorrentPassword => set(cfg.qbittorrent.as_ref().and_then(|c| c.password.as_ref())),
+                Secret::DelugePassword => set(cfg.deluge.as_ref().and_then(|c| c.password.as_ref())),
+                Secret::RtorrentPassword => set(cfg.rtorrent.as_ref().and_then(|c| c.password.as_ref())),
+                _ => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    /// Names saved with `av secret set`
+    stored: BTreeSet<String>,
+}
+
+fn index() -> Index {
+    store::load(STORE).unwrap_or_else(|e| {
+        util::debug(format!("keychain: {:#}", e));
+        Index::default()
+    })
+}
+
+/// Record whether the item `account` is in the keychain
+fn update_index(account: &str, stored: bool) -> Result<()> {
+    let mut index = index();
+    let changed = if stored { index.stored.insert(account.to_string()) } else { index.stored.remove(account) };
+    if changed {
+        store::save(STORE, &index)?;
+    }
+    Ok(())
+}
+
+fn entry(account: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(SERVICE, account).with_context(|| format!("无法访问系统钥匙串（{}）", account))
+}
+
+/// The value from the keychain, `None` when there is no such item
+fn lookup(account: &str) -> Result<Option<String>> {
+    match entry(account)?.get_password() {
+        Ok(v) => Ok(Some(v).filter(|v| !v.is_empty())),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e).context("读取钥匙串失败"),
+    }
+}
+
+fn store_value(account: &str, value: &str) -> Result<()> {
+    entry(account)?.set_password(value).context("写入钥匙串失败")
+}
+
+/// Whether there was an item to remove
+fn remove(account: &str) -> Result<bool> {
+    match entry(account)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(e).context("删除钥匙串条目失败"),
+    }
+}
+
+/// The keychain's value for `secret`, for callers whose variable or config key is unset;
+/// looked up at most once a run and only when `av secret set` saved it
+pub fn get(secret: Secret) -> Option<String> {
+    if let Some(cached) = CACHE.lock().unwrap().get(&secret) {
+        return cached.clone();
+    }
+    let value = if index().stored.contains(secret.as_str()) {
+        lookup(secret.as_str()).unwrap_or_else(|e| {
+            util::debug(format!("keychain {}: {:#}", secret.as_str(), e));
+            None
+        })
+    } else {
+        None
+    };
+    CACHE.lock().unwrap().insert(secret, value.clone());
+    value
+}
+
+/// A line from stdin, not echoed when it is a terminal
+fn read_value(secret: Secret) -> Result<String> {
+    use std::io::IsTerminal;
+    let tty = std::io::stdin().is_terminal();
+    if tty {
+        eprint!("{} 的值（不回显）: ", secret.as_str());
+        std::io::stderr().flush()?;
+    }
+    let echo_off = tty && cfg!(unix) && Command::new("stty").arg("-echo").stdin(Stdio::inherit()).status().is_ok_and(|s| s.success());
+    let mut line = String::new();
+    let read = std::io::stdin().read_line(&mut line);
+    if echo_off {
+        let _ = Command::new("stty").arg("echo").stdin(Stdio::inherit()).status();
+        eprintln!();
+    }
+    read.context("读取输入失败")?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+pub fn set(secret: Secret) -> Result<()> {
+    let value = read_value(secret)?;
+    if value.trim().is_empty() {
+        bail!("值为空，未保存（删除请用 av secret delete {}）", secret.as_str());
+    }
+    if util::dry_run_skip(format!("将把 {} 存入钥匙串", secret.as_str())) {
+        return Ok(());
+    }
+    store_value(secret.as_str(), value.trim())?;
+    update_index(secret.as_str(), true)?;
+    util::note(format!("已将 {} 存入钥匙串", secret.as_str()));
+    if secret.plain_set() {
+        eprintln!("[WARN] {} 仍然设置着，优先于钥匙串；可以删掉它了", secret.plain().trim_start_matches("env:"));
+    }
+    Ok(())
+}
+
+/// `av secret get`: the value itself on stdout, for scripts
+pub fn print(secret: Secret) -> Result<()> {
+    match lookup(secret.as_str())? {
+        Some(v) => {
+            println!("{}", v);
+            Ok(())
+        }
+        None => bail!("钥匙串中没有 {}", secret.as_str()),
+    }
+}
+
+pub fn delete(secret: Secret) -> Result<()> {
+    if util::dry_run_skip(format!("将从钥匙串删除 {}", secret.as_str())) {
+        return Ok(());
+    }
+    let removed = remove(secret.as_str())?;
+    update_index(secret.as_str(), false)?;
+    util::note(if removed { format!("已从钥匙串删除 {}", secret.as_str()) } else { format!("钥匙串中没有 {}", secret.as_str()) });
+    Ok(())
+}
+
+/// The item holding `source`'s cookie jar
+fn jar_account(source: &str) -> String {
+    format!("cookies-{}", source)
+}
+
+/// `source`'s cookie jar as saved by `save_jar`, `None` when it isn't in the keychain
+pub fn jar(source: &str) -> Option<String> {
+    let account = jar_account(source);
+    if !index().stored.contains(&account) {
+        return None;
+    }
+    lookup(&account).unwrap_or_else(|e| {
+        util::debug(format!("keychain {}: {:#}", account, e));
+        None
+    })
+}
+
+/// Keep `source`'s cookie jar (its JSON) in the keychain. An error means there is no
+/// keychain to keep it in, and the caller writes the file instead.
+pub fn save_jar(source: &str, json: &str) -> Result<()> {
+    if UNAVAILABLE.load(Ordering::Relaxed) {
+        bail!("系统钥匙串不可用");
+    }
+    let account = jar_account(source);
+    if let Err(e) = store_value(&account, json) {
+        UNAVAILABLE.store(true, Ordering::Relaxed);
+        return Err(e);
+    }
+    update_index(&account, true)
+}
+
+/// Remove `source`'s cookie jar from the keychain; whether there was one
+pub fn delete_jar(source: &str) -> Result<bool> {
+    let account = jar_account(source);
+    if !index().stored.contains(&account) {
+        return Ok(false);
+    }
+    let removed = remove(&account)?;
+    update_index(&account, false)?;
+    Ok(removed)
+}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    name: &'static str,
+    /// `env:<VAR>` or the config key that takes precedence
+    plain: &'static str,
+    plain_set: bool,
+    in_keychain: bool,
+}
+
+/// `av secret list`: every secret, and whether it is set in the clear or in the keychain
+pub fn list(json: bool) -> Result<()> {
+    let stored = index().stored;
+    let entries: Vec<Entry> = Secret::value_variants()
+        .iter()
+        .map(|&s| Entry { name: s.as_str(), plain: s.plain(), plain_set: s.plain_set(), in_keychain: stored.contains(s.as_str()) })
+        .collect();
+    if json {
+        util::print_output(&entries, true);
+        return Ok(());
+    }
+    let yes_no = |b: bool| if b { "是" } else { "-" }.to_string();
+    let rows = entries
+        .iter()
+        .map(|e| {
+            let used = match (e.plain_set, e.in_keychain) {
+                (true, _) => "明文",
+                (false, true) => "钥匙串",
+                (false, false) => "未设置",
+            };
+            vec![e.name.to_string(), e.plain.trim_start_matches("env:").to_string(), yes_no(e.plain_set), yes_no(e.in_keychain), used.to_string()]
+        })
+        .collect();
+    crate::table::print_rows(&["名称", "明文设置", "已设置", "钥匙串", "生效"], rows);
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::code;
+use crate::store;
+use crate::util;
+
+const LIBRARY: &str = "library";
+
+/// Personal state for one code: on the watchlist ("wanted") and/or in the watch history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Entry {
+    pub title: Option<String>,
+    pub wanted: bool,
+    pub watched: bool,
+    /// Unix seconds
+    pub watched_at: Option<u64>,
+    /// Personal rating, 1..=5
+    pub rating: Option<u8>,
+    pub added_at: Option<u64>,
+}
+
+/// Local watchlist and history, keyed by upper-case code
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Library {
+    pub entries: BTreeMap<String, Entry>,
+}
+
+impl Library {
+    pub fn load() -> Result<Library> {
+        store::load(LIBRARY)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if util::dry_run_skip("将更新本地片单（未保存）") {
+            return Ok(());
+        }
+        store::save(LIBRARY, self)
+    }
+
+    pub fn entry(&mut self, code: &str) -> &mut Entry {
+        self.entries.entry(code::normalize(code)).or_insert_with(|| Entry {
+            added_at: Some(util::now_secs()),
+            ..Entry::default()
+        })
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Entry> {
+        self.entries.get(&code::normalize(code))
+    }
+
+    pub fn is_watched(&self, code: &str) -> bool {
+        self.get(code).is_some_and(|e| e.watched)
+    }
+
+    /// Short status shown next to titles in tables, e.g. "已看 ★4" or "想看"
+    pub fn badge(&self, code: &str) -> Option<String> {
+        let e = self.get(code)?;
+        let mut parts = Vec::new();
+        if e.watched {
+            parts.push("已看".to_string());
+        } else if e.wanted {
+            parts.push("想看".to_string());
+        }
+        if let Some(r) = e.rating {
+            parts.push(format!("★{}", r));
+        }
+        if parts.is_empty() { None } else { Some(parts.join(" ")) }
+    }
+
+    pub fn unmark_watched(&mut self, code: &str) {
+        let e = self.entry(code);
+        e.watched = false;
+        e.watched_at = None;
+    }
+
+    /// Returns true when the code was not already wanted
+    pub fn mark_wanted(&mut self, code: &str, title: Option<&str>) -> bool {
+        let e = self.entry(code);
+        if e.title.is_none() {
+            e.title = title.map(|t| t.trim().to_string());
+        }
+        !std::mem::replace(&mut e.wanted, true)
+    }
+
+    /// Returns true when the code was not already watched. Watching something takes it
+    /// off the watchlist.
+    pub fn mark_watched(&mut self, code: &str, title: Option<&str>) -> bool {
+        let e = self.entry(code);
+        if e.title.is_none() {
+            e.title = title.map(|t| t.trim().to_string());
+        }
+        e.wanted = false;
+        if e.watched {
+            return false;
+        }
+        e.watched = true;
+        e.watched_at = Some(util::now_secs());
+        true
+    }
+}
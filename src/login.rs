@@ -0,0 +1,199 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::OnceLock;
+
+use crate::cookies;
+use crate::scraper as av_scraper;
+use crate::selectors;
+use crate::store;
+use crate::util;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Site {
+    Javdb,
+}
+
+const JAVDB_SESSION: &str = "javdb_session";
+
+/// Who is logged in to JavDB. The session cookies themselves live in the javdb cookie jar
+/// (see `cookies`), next to Cloudflare clearance.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JavdbSession {
+    pub username: Option<String>,
+    pub logged_in_at: Option<u64>,
+}
+
+static SESSION: OnceLock<Option<JavdbSession>> = OnceLock::new();
+
+/// Saved JavDB session, read once per process
+pub fn javdb_session() -> Option<&'static JavdbSession> {
+    SESSION
+        .get_or_init(|| {
+            if !store::path(JAVDB_SESSION).exists() {
+                return None;
+            }
+            store::load::<JavdbSession>(JAVDB_SESSION).ok()
+        })
+        .as_ref()
+}
+
+fn save_session(session: &JavdbSession) -> Result<()> {
+    store::save(JAVDB_SESSION, session)
+}
+
+/// Record a login whose cookies came from elsewhere (e.g. imported from the browser)
+pub fn record_javdb_session(username: Option<String>) -> Result<()> {
+    save_session(&JavdbSession { username, logged_in_at: Some(util::now_secs()) })
+}
+
+fn javdb_host() -> Result<String> {
+    let base = av_scraper::javdb_base();
+    reqwest::Url::parse(&base)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .with_context(|| format!("无效的 JavDB 地址: {}", base))
+}
+
+
+fn prompt(label: &str) -> Result<String> {
+    eprint!("{}", label);
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).context("读取输入失败")?;
+    Ok(line.trim().to_string())
+}
+
+/// A value that shouldn't be echoed: prompted for without echo on a terminal, otherwise one
+/// line of stdin (`pass show javdb | av login javdb`). Never a command-line argument, where
+/// shell history and `ps` would keep it.
+fn secret(label: &str) -> Result<String> {
+    use std::io::IsTerminal;
+    if std::io::stdin().is_terminal() {
+        return rpassword::prompt_password(label).context("读取输入失败");
+    }
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).context("读取输入失败")?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+pub struct LoginArgs {
+    pub username: Option<String>,
+    /// Save a pasted Cookie header instead of logging in with a password
+    pub cookie: bool,
+    pub status: bool,
+}
+
+pub async fn login(site: Site, args: LoginArgs) -> Result<()> {
+    match site {
+        Site::Javdb => login_javdb(args).await,
+    }
+}
+
+pub fn logout(site: Site) -> Result<()> {
+    match site {
+        Site::Javdb => {
+            let p = store::path(JAVDB_SESSION);
+            let was_logged_in = p.exists();
+            if was_logged_in {
+                std::fs::remove_file(&p).with_context(|| format!("删除会话文件失败: {}", p.display()))?;
+            }
+            cookies::clear_source("javdb")?;
+            util::note(if was_logged_in { "已退出 JavDB 登录" } else { "当前未登录 JavDB" });
+            Ok(())
+        }
+    }
+}
+
+async fn login_javdb(args: LoginArgs) -> Result<()> {
+    if args.status {
+        match javdb_session() {
+            Some(s) => util::note(format!(
+                "已登录 JavDB{}（Cookie: {}）",
+                s.username.as_deref().map(|u| format!(": {}", u)).unwrap_or_default(),
+                cookies::location("javdb")
+            )),
+            None => util::note("当前未登录 JavDB"),
+        }
+        return Ok(());
+    }
+
+    // Manual flow: paste the Cookie header copied from a logged-in browser
+    if args.cookie {
+        let cookie = secret("从浏览器复制的 JavDB Cookie（不回显）: ")?;
+        let host = javdb_host()?;
+        let parsed = cookies::parse_header(&host, &cookie);
+        if parsed.is_empty() {
+            bail!("Cookie 不能为空");
+        }
+        let n = cookies::import(&host, &parsed)?;
+        record_javdb_session(args.username)?;
+        util::note(format!("已保存 {} 个 JavDB Cookie", n));
+        return Ok(());
+    }
+
+    let username = match args.username {
+        Some(u) => u,
+        None => prompt("JavDB 邮箱/用户名: ")?,
+    };
+    let password = match std::env::var("AV_JAVDB_PASSWORD").ok().filter(|p| !p.is_empty()) {
+        Some(p) => p,
+        None => secret("JavDB 密码: ")?,
+    };
+    if username.is_empty() || password.is_empty() {
+        bail!("用户名和密码不能为空");
+    }
+    password_login(&username, &password).await?;
+    record_javdb_session(Some(username.clone()))?;
+    util::note(format!("已登录 JavDB: {}", username));
+    Ok(())
+}
+
+fn csrf_token(body: &str) -> Option<String> {
+    let doc = Html::parse_document(body);
+    doc.select(selectors::javdb("csrf_input"))
+        .next()
+        .and_then(|n| n.value().attr("value"))
+        .or_else(|| doc.select(selectors::javdb("csrf_meta")).next().and_then(|n| n.value().attr("content")))
+        .map(|s| s.to_string())
+}
+
+fn flash_message(body: &str) -> Option<String> {
+    let doc = Html::parse_document(body);
+    doc.select(selectors::javdb("flash"))
+        .map(|n| n.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|s| !s.is_empty())
+}
+
+/// Rails-style form login: fetch the CSRF token from /login and post credentials to
+/// /user_sessions. The session cookies the site hands back land in the javdb jar.
+async fn password_login(username: &str, password: &str) -> Result<()> {
+    let base = av_scraper::javdb_base();
+    let c = av_scraper::client_builder().cookie_provider(cookies::provider()).build().context("client build")?;
+
+    let login_page = av_scraper::get_text(&c, &format!("{}/login", base)).await.context("打开 JavDB 登录页失败")?;
+    let token = csrf_token(&login_page).context("登录页中未找到 authenticity_token（可能被 Cloudflare 拦截）")?;
+
+    util::debug("JavDB: posting credentials");
+    let resp = c
+        .post(format!("{}/user_sessions", base))
+        .form(&[
+            ("email", username),
+            ("password", password),
+            ("remember", "1"),
+            ("authenticity_token", token.as_str()),
+        ])
+        .send()
+        .await
+        .context("JavDB 登录请求失败")?;
+    let final_path = resp.url().path().to_string();
+    let body = resp.text().await.unwrap_or_default();
+    if final_path.starts_with("/login") || final_path.starts_with("/user_sessions") {
+        let reason = flash_message(&body).unwrap_or_else(|| "用户名或密码错误，或需要验证码".to_string());
+        bail!("JavDB 登录失败: {}（可改用 --cookie 手动导入浏览器 Cookie）", reason);
+    }
+    Ok(())
+}
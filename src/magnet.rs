@@ -0,0 +1,475 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use crate::config::{self, ScoreWeights};
+use crate::scraper;
+use crate::types::{AvDetail, MagnetInfo};
+use crate::util;
+
+/// Components of a `magnet:?xt=urn:btih:...` URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Magnet {
+    /// 40-char upper-case hex; base32 hashes are converted
+    pub infohash: String,
+    /// `dn`, percent-decoded
+    pub display_name: Option<String>,
+    /// `tr` parameters in order, percent-decoded
+    pub trackers: Vec<String>,
+}
+
+const BASE32: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn base32_to_hex(s: &str) -> Option<String> {
+    let mut bits: u64 = 0;
+    let mut nbits = 0;
+    let mut out = Vec::with_capacity(20);
+    for ch in s.bytes() {
+        let v = BASE32.iter().position(|&b| b == ch.to_ascii_uppercase())? as u64;
+        bits = (bits << 5) | v;
+        nbits += 5;
+        if nbits >= 8 {
+            nbits -= 8;
+            out.push((bits >> nbits) as u8);
+            bits &= (1 << nbits) - 1;
+        }
+    }
+    (out.len() == 20).then(|| hex::encode_upper(out))
+}
+
+fn decode(v: &str) -> String {
+    let v = v.replace('+', " ");
+    urlencoding::decode(&v).map(|c| c.into_owned()).unwrap_or(v)
+}
+
+/// Parse and validate a magnet URI; fails unless it carries a well-formed BitTorrent infohash
+pub fn parse(uri: &str) -> Result<Magnet> {
+    let query = uri
+        .trim()
+        .strip_prefix("magnet:?")
+        .with_context(|| format!("不是磁力链接: {}", uri))?;
+    let mut infohash = None;
+    let mut display_name = None;
+    let mut trackers = Vec::new();
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match key {
+            "xt" => {
+                let Some(hash) = value.strip_prefix("urn:btih:").or_else(|| value.strip_prefix("urn%3Abtih%3A")) else { continue };
+                let hex = match hash.len() {
+                    40 if hash.bytes().all(|b| b.is_ascii_hexdigit()) => Some(hash.to_uppercase()),
+                    32 => base32_to_hex(hash),
+                    _ => None,
+                };
+                match hex {
+                    Some(h) => infohash = Some(h),
+                    None => bail!("磁力链接的 infohash 无效: {}", hash),
+                }
+            }
+            "dn" if !value.is_empty() => display_name = Some(decode(value)),
+            "tr" if !value.is_empty() => trackers.push(decode(value)),
+            _ => {}
+        }
+    }
+    let infohash = infohash.with_context(|| format!("磁力链接缺少 urn:btih: {}", uri))?;
+    Ok(Magnet { infohash, display_name, trackers })
+}
+
+/// A `MagnetInfo` for `uri` with the parsed components filled in and nothing else known
+/// yet; `None` (logged) for links that don't validate, so they never reach the output
+pub fn info(uri: &str) -> Option<MagnetInfo> {
+    match parse(uri) {
+        Ok(m) => Some(MagnetInfo {
+            url: uri.to_string(),
+            name: None,
+            size: None,
+            size_bytes: None,
+            date: None,
+            seeders: None,
+            leechers: None,
+            downloads: None,
+            resolution: None,
+            codec: None,
+            avg_bitrate_mbps: None,
+            part: None,
+            infohash: Some(m.infohash),
+            display_name: m.display_name,
+            trackers: m.trackers,
+            quality_score: None,
+        }),
+        Err(e) => {
+            util::debug(format!("skipping magnet: {:#}", e));
+            None
+        }
+    }
+}
+
+static RESOLUTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(2160|1080|720|480)[pi]|\b(4K|UHD|FHD|HD)\b").unwrap());
+/// The keywords, or `-C` / `-CH` right after the code's number (`ABP-123-C.mp4`); a lone `C`
+/// elsewhere in a name (`Part2C`, `… C …`) says nothing about subtitles
+static SUBTITLES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)中文字幕|中字|字幕|\bSUBS?\b|\d-CH?(?:\b|_)").unwrap());
+static LEAK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)leak|流出|破解|decensored|\bUC\b|-U\b").unwrap());
+
+/// Resolution class found in `text` and its weight in the score
+fn resolution_class(text: &str) -> Option<(&'static str, f32)> {
+    let c = RESOLUTION.captures(text)?;
+    match c.get(1).or(c.get(2)).map(|m| m.as_str().to_uppercase()).as_deref() {
+        Some("2160") | Some("4K") | Some("UHD") => Some(("4K", 1.0)),
+        Some("1080") | Some("FHD") => Some(("1080p", 0.75)),
+        Some("720") | Some("HD") => Some(("720p", 0.45)),
+        Some("480") => Some(("480p", 0.2)),
+        _ => None,
+    }
+}
+
+/// `4K` / `1080p` / `720p` / `480p` tagged in a file or release name
+pub fn resolution_in(text: &str) -> Option<&'static str> {
+    resolution_class(text).map(|(label, _)| label)
+}
+
+/// The torrent's name plus the link's `dn`, where release tags live
+fn tag_text(m: &MagnetInfo) -> String {
+    format!("{} {}", m.name.as_deref().unwrap_or(""), m.display_name.as_deref().unwrap_or(""))
+}
+
+/// `4K` / `1080p` / `720p` / `480p`, from the source's resolution field or the name
+pub fn resolution(m: &MagnetInfo) -> Option<&'static str> {
+    resolution_in(&format!("{} {}", m.resolution.as_deref().unwrap_or(""), tag_text(m)))
+}
+
+/// The source's average bitrate, else size over the detail's duration
+pub fn bitrate_mbps(m: &MagnetInfo, duration_minutes: Option<u32>) -> Option<f32> {
+    m.avg_bitrate_mbps.or_else(|| {
+        let bytes = size_bytes(m)?;
+        let minutes = duration_minutes.filter(|d| *d > 0)?;
+        Some((bytes as f64 * 8.0 / (minutes as f64 * 60.0) / 1_000_000.0) as f32)
+    })
+}
+
+/// Chinese subtitles tagged in the name (`ABP-123-C`, `中文字幕`, ...)
+pub fn has_subtitles(m: &MagnetInfo) -> bool {
+    SUBTITLES.is_match(&tag_text(m))
+}
+
+/// Leaked / decensored release
+pub fn is_leak(m: &MagnetInfo) -> bool {
+    LEAK.is_match(&tag_text(m))
+}
+
+/// `quality_score` for one magnet; `duration_minutes` comes from the detail when the
+/// magnet has no bitrate of its own
+pub fn score(m: &MagnetInfo, duration_minutes: Option<u32>, w: &ScoreWeights) -> f32 {
+    let seeders = m.seeders.map(|s| ((1.0 + s as f32).ln() / 101f32.ln()).min(1.0)).unwrap_or(0.0);
+    let bitrate = bitrate_mbps(m, duration_minutes).map(|b| (b / 8.0).min(1.0)).unwrap_or(0.0);
+    let resolution = resolution_class(&format!("{} {}", m.resolution.as_deref().unwrap_or(""), tag_text(m)))
+        .map(|(_, f)| f)
+        .unwrap_or(0.0);
+    let subtitles = if has_subtitles(m) { 1.0 } else { 0.0 };
+    let leak = if is_leak(m) { 1.0 } else { 0.0 };
+    let total = w.seeders * seeders + w.bitrate * bitrate + w.resolution * resolution + w.subtitles * subtitles + w.leak * leak;
+    (total * 10.0).round() / 10.0
+}
+
+/// Fill in every magnet's `size_bytes` and `quality_score`, and `avg_bitrate_mbps` from the
+/// merged detail's duration when the torrent's own page gave none. A part of a multi-part
+/// release covers only some of that duration, so it gets no bitrate from it.
+pub fn score_all(d: &mut AvDetail, w: &ScoreWeights) {
+    for m in &mut d.magnet_infos {
+        let duration = d.duration_minutes.filter(|_| m.part.is_none());
+        m.size_bytes = m.size.as_deref().and_then(scraper::parse_size_to_bytes).map(|(b, _)| b);
+        m.avg_bitrate_mbps = bitrate_mbps(m, duration);
+        m.quality_score = Some(score(m, duration, w));
+    }
+}
+
+/// `size_bytes`, or parsed from `size` for entries cached before it existed
+pub fn size_bytes(m: &MagnetInfo) -> Option<u64> {
+    m.size_bytes.or_else(|| scraper::parse_size_to_bytes(m.size.as_deref()?).map(|(b, _)| b))
+}
+
+/// `4617089843` → `4.3 GiB`, the way Sukebei writes sizes, for sources that give bytes
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    if value < 1024.0 {
+        return format!("{} B", bytes);
+    }
+    let mut unit = "B";
+    for u in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = u;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+/// `2GB`, `700 MiB`, `1.5G` or a plain byte count, for `--min-size` / `--max-size`
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    if let Ok(n) = t.parse::<u64>() {
+        return Ok(n);
+    }
+    // parse_size_to_bytes wants `GB` / `GiB`; people type `2gb`, `2G` and `2GIB` too
+    let mut upper = t.to_uppercase().replace("IB", "iB");
+    if !upper.ends_with('B') {
+        upper.push('B');
+    }
+    let well_formed = upper.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.').trim_start().len() <= 3;
+    scraper::parse_size_to_bytes(&upper)
+        .filter(|_| well_formed)
+        .map(|(b, _)| b)
+        .ok_or_else(|| format!("无效的大小: {}（如 2GB、700MB、1.5GiB）", s))
+}
+
+/// The same torrent: same infohash, or the same link when one has no infohash
+pub fn same(a: &MagnetInfo, b: &MagnetInfo) -> bool {
+    match (&a.infohash, &b.infohash) {
+        (Some(x), Some(y)) => x == y,
+        _ => a.url == b.url,
+    }
+}
+
+/// `--all-magnets`
+static ALL_SOURCES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_all_sources(on: bool) {
+    ALL_SOURCES.store(on, Ordering::Relaxed);
+}
+
+/// Every magnet source is asked for every code (`--all-magnets` / `[magnets] all_sources`)
+pub fn all_sources() -> bool {
+    ALL_SOURCES.load(Ordering::Relaxed) || config::get().magnets.all_sources
+}
+
+/// Add another source's magnets to `d`, skipping torrents it already has (by infohash).
+/// A known torrent keeps its details, with gaps filled from the other source; a bare
+/// link gives way to the same torrent with details. Returns how many were new.
+pub fn union(d: &mut AvDetail, magnets: Vec<String>, infos: Vec<MagnetInfo>) -> usize {
+    let hash = |u: &str| parse(u).ok().map(|m| m.infohash);
+    let mut added = 0;
+    for info in infos {
+        if let Some(m) = d.magnet_infos.iter_mut().find(|m| same(m, &info)) {
+            m.name = m.name.take().or(info.name);
+            m.size = m.size.take().or(info.size);
+            m.date = m.date.take().or(info.date);
+            m.seeders = m.seeders.max(info.seeders);
+            m.leechers = m.leechers.max(info.leechers);
+            m.downloads = m.downloads.max(info.downloads);
+            m.resolution = m.resolution.take().or(info.resolution);
+            m.codec = m.codec.take().or(info.codec);
+            m.avg_bitrate_mbps = m.avg_bitrate_mbps.or(info.avg_bitrate_mbps);
+            continue;
+        }
+        let h = info.infohash.clone().or_else(|| hash(&info.url));
+        match d.magnets.iter_mut().find(|u| *u == &info.url || (h.is_some() && hash(u) == h)) {
+            Some(bare) => *bare = info.url.clone(),
+            None => {
+                d.magnets.push(info.url.clone());
+                added += 1;
+            }
+        }
+        d.magnet_infos.push(info);
+    }
+    for url in magnets {
+        let h = hash(&url);
+        let known = d.magnets.iter().any(|u| *u == url || (h.is_some() && hash(u) == h))
+            || d.magnet_infos.iter().any(|m| m.url == url || (h.is_some() && m.infohash == h));
+        if !known {
+            d.magnets.push(url);
+            added += 1;
+        }
+    }
+    added
+}
+
+/// A detail holding nothing but `infos`, for the magnet-only indexes (BTSOW, TorrentKitty)
+pub fn detail_of(code: &str, infos: Vec<MagnetInfo>) -> AvDetail {
+    AvDetail {
+        code: code.to_string(),
+        title: infos.first().and_then(|m| m.name.clone()).unwrap_or_else(|| code.to_string()),
+        actor_names: Vec::new(),
+        release_date: None,
+        cover_url: None,
+        plot: None,
+        duration_minutes: None,
+        director: None,
+        studio: None,
+        label: None,
+        series: None,
+        genres: Vec::new(),
+        genre_ids: Vec::new(),
+        rating: None,
+        ratings: Vec::new(),
+        popularity: None,
+        preview_images: Vec::new(),
+        trailer_url: None,
+        sample_video_url: None,
+        amateur: false,
+        magnets: infos.iter().map(|m| m.url.clone()).collect(),
+        magnet_infos: infos,
+    }
+}
+
+/// `--min-size` / `--max-size`
+static SIZE_RANGE: Mutex<(Option<u64>, Option<u64>)> = Mutex::new((None, None));
+
+pub fn set_size_range(min: Option<u64>, max: Option<u64>) {
+    *SIZE_RANGE.lock().unwrap() = (min, max);
+}
+
+/// Drop the magnets whose size falls outside `--min-size` / `--max-size`; magnets of
+/// unknown size stay, as the bare links without details do
+pub fn filter_by_size(d: &mut AvDetail) {
+    let (min, max) = *SIZE_RANGE.lock().unwrap();
+    if min.is_none() && max.is_none() {
+        return;
+    }
+    let outside = |m: &MagnetInfo| size_bytes(m).is_some_and(|b| min.is_some_and(|min| b < min) || max.is_some_and(|max| b > max));
+    let dropped: Vec<String> = d.magnet_infos.iter().filter(|m| outside(m)).map(|m| m.url.clone()).collect();
+    if dropped.is_empty() {
+        return;
+    }
+    util::debug(format!("{}: {} magnet(s) outside the size range", d.code, dropped.len()));
+    d.magnet_infos.retain(|m| !dropped.contains(&m.url));
+    d.magnets.retain(|m| !dropped.contains(m));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> MagnetInfo {
+        let mut m = info("magnet:?xt=urn:btih:0123456789ABCDEF0123456789ABCDEF01234567").unwrap();
+        m.name = Some(name.to_string());
+        m
+    }
+
+    #[test]
+    fn subtitle_markers() {
+        let cases: &[(&str, bool)] = &[
+            ("ABP-123-C.mp4", true),
+            ("abp-123-c", true),
+            ("ABP-123-CH.mkv", true),
+            ("ABP-123-C_HD.mp4", true),
+            ("[FHD] ABP-123-C 1080p", true),
+            ("ABP-123 中文字幕", true),
+            ("ABP-123 [中字]", true),
+            ("ABP-123 subs", true),
+            ("ABP-123", false),
+            ("ABP-123-CD2.mp4", false),
+            ("ABP-123 Part2C.mp4", false),
+            ("ABP-123 C 1080p", false),
+            ("ABP-123 CH", false),
+            ("Vol.3C.mp4", false),
+            ("ABP-123 Class.mp4", false),
+            ("ABP-123.subsequent", false),
+        ];
+        for (name, want) in cases {
+            assert_eq!(has_subtitles(&named(name)), *want, "has_subtitles({:?})", name);
+        }
+    }
+
+    #[test]
+    fn resolution_factors() {
+        let cases: &[(&str, Option<(&str, f32)>)] = &[
+            ("ABP-123 2160p", Some(("4K", 1.0))),
+            ("ABP-123 4K", Some(("4K", 1.0))),
+            ("abp-123 uhd", Some(("4K", 1.0))),
+            ("ABP-123.1080p.mp4", Some(("1080p", 0.75))),
+            ("ABP-123 1080i", Some(("1080p", 0.75))),
+            ("[FHD] ABP-123", Some(("1080p", 0.75))),
+            ("ABP-123 720p", Some(("720p", 0.45))),
+            ("ABP-123 HD", Some(("720p", 0.45))),
+            ("ABP-123 480p", Some(("480p", 0.2))),
+            ("ABP-123", None),
+            ("ABP-1080", None),
+            ("ABP-123 HDR", None),
+        ];
+        for (text, want) in cases {
+            assert_eq!(resolution_class(text), *want, "resolution_class({:?})", text);
+        }
+        let mut m = named("ABP-123");
+        m.resolution = Some("1920x1080 1080p".into());
+        assert_eq!(resolution(&m), Some("1080p"));
+    }
+
+    #[test]
+    fn scores() {
+        let w = ScoreWeights::default();
+        assert_eq!(score(&named("ABP-123"), None, &w), 0.0);
+
+        // 100 seeders, 8 Mbps, 4K, subtitled and leaked: full marks everywhere
+        let mut best = named("ABP-123-C 4K leaked");
+        best.seeders = Some(100);
+        best.avg_bitrate_mbps = Some(8.0);
+        assert_eq!(score(&best, None, &w), 100.0);
+
+        // 9 seeders is half the log scale; 1080p; bitrate from size over the duration
+        let mut m = named("ABP-123 1080p");
+        m.seeders = Some(9);
+        m.size = Some("3.6 GB".into());
+        let half_seeders = 40.0 * (10f32.ln() / 101f32.ln());
+        let bitrate = bitrate_mbps(&m, Some(120)).unwrap();
+        assert!((bitrate - 3.6 * 1024f32.powi(3) * 8.0 / 7200.0 / 1e6).abs() < 0.01, "bitrate {}", bitrate);
+        let want = half_seeders + 25.0 * bitrate / 8.0 + 20.0 * 0.75;
+        assert!((score(&m, Some(120), &w) - want).abs() <= 0.05 + 1e-4);
+        // without a duration there is no bitrate to go by
+        assert!((score(&m, None, &w) - (half_seeders + 15.0)).abs() <= 0.05 + 1e-4);
+
+        // a standalone C no longer earns the subtitle points
+        assert_eq!(score(&named("ABP-123 Part2C"), None, &w), 0.0);
+        assert_eq!(score(&named("ABP-123-C"), None, &w), 10.0);
+    }
+
+    #[test]
+    fn configured_weights() {
+        let w: ScoreWeights = toml::from_str("subtitles = 0\nleak = -30\nresolution = 50").unwrap();
+        assert_eq!((w.seeders, w.bitrate), (40.0, 25.0));
+        assert_eq!(score(&named("ABP-123-C"), None, &w), 0.0);
+        assert_eq!(score(&named("ABP-123 leak"), None, &w), -30.0);
+        assert_eq!(score(&named("ABP-123 720p"), None, &w), 22.5);
+
+        let mut d = detail_of("ABP-123", vec![named("ABP-123-C 1080p"), named("ABP-123 Part2C")]);
+        d.magnet_infos[0].size = Some("1.5 GB".into());
+        d.duration_minutes = Some(50);
+        score_all(&mut d, &w);
+        let first = &d.magnet_infos[0];
+        assert_eq!(first.size_bytes, scraper::parse_size_to_bytes("1.5 GB").map(|(b, _)| b));
+        assert_eq!(first.avg_bitrate_mbps, bitrate_mbps(first, Some(50)));
+        assert!(first.quality_score.is_some_and(|q| q > 50.0 * 0.75));
+        assert_eq!(d.magnet_infos[1].quality_score, Some(0.0));
+    }
+
+    #[test]
+    fn parts_get_no_bitrate_from_the_full_duration() {
+        let w = ScoreWeights::default();
+        let mut whole = named("ABP-123 1080p");
+        whole.size = Some("2 GB".into());
+        let mut part = whole.clone();
+        part.part = Some(1);
+        let mut d = detail_of("ABP-123", vec![whole, part]);
+        d.duration_minutes = Some(120);
+        score_all(&mut d, &w);
+        let (whole, part) = (&d.magnet_infos[0], &d.magnet_infos[1]);
+        assert!(whole.avg_bitrate_mbps.is_some());
+        assert_eq!(part.avg_bitrate_mbps, None);
+        assert_eq!(part.quality_score, Some(15.0));
+    }
+
+    #[test]
+    fn ranked_by_score_then_seeders() {
+        let magnet = |hash: char, seeders: u32, score: Option<f32>| {
+            let mut m = info(&format!("magnet:?xt=urn:btih:{}", hash.to_string().repeat(40))).unwrap();
+            (m.seeders, m.quality_score) = (Some(seeders), score);
+            m
+        };
+        let d = detail_of("ABP-123", vec![magnet('A', 500, None), magnet('B', 3, Some(70.0)), magnet('C', 90, Some(40.0)), magnet('D', 800, None)]);
+        let order: Vec<String> = util::ranked_magnets(&d).iter().map(|u| u[20..21].to_string()).collect();
+        assert_eq!(order, ["B", "C", "D", "A"]);
+        assert_eq!(util::best_magnet(&d), util::ranked_magnets(&d).into_iter().next());
+    }
+}
@@ -1,14 +1,100 @@
-use anyhow::{Result};
+use anyhow::{bail, Context, Result};
 use colored::Colorize;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use chrono::NaiveDate;
 
+mod actress;
+mod alias;
+mod amateur;
+mod artwork;
+mod batch;
+mod bench;
+mod browser_cookies;
+mod cache;
+mod calendar;
+mod cancel;
+mod code;
+#[cfg(feature = "cassette")]
+mod cassette;
+mod compare;
+mod config;
+mod cookies;
+mod daemon;
+mod dates;
+mod doctor;
+mod doh;
+mod downloader;
+mod embed;
+mod errors;
+mod fc2;
+mod feed;
+mod filmography;
+mod gallery;
+mod genre;
+#[cfg(test)]
+mod golden;
+mod graphql;
+mod history;
+mod hooks;
+mod http;
+#[cfg(feature = "impersonate")]
+mod impersonate;
+mod javdb_sync;
+mod journal;
+mod keychain;
+mod library;
+mod login;
+mod magnet;
+mod media_server;
+mod mcp;
+mod metrics;
+mod mirrors;
+mod nfo;
+mod notify;
+mod organize;
+mod pick;
+mod pipeline;
+mod platform;
+mod player;
+mod price;
+mod progress;
+mod query;
+mod rating;
+mod reviews;
+mod rip;
+mod sanity;
+mod scan;
 mod scraper;
+mod seeders;
+mod selectors;
+mod settings;
+mod setup;
+mod server;
+mod shell;
 mod types;
+mod ua;
+mod update;
 mod util;
+mod verify;
+mod vr;
+mod whisper;
+mod who;
+mod xlsx;
+mod source_diff;
 mod sources;
+mod store;
+mod stream;
+mod subscribe;
+mod subtitles;
+mod table;
+mod thumbs;
+mod tracker;
+mod trailer;
+mod translate;
+mod trending;
 
 #[derive(Parser, Debug)]
-#[command(name = "av", version, about = "AV CLI: 搜索、查看与下载番号和演员作品", long_about = None)]
+#[command(name = "av", version, about = "AV CLI: 搜索、查看与下载番号和演员作品", long_about = None, args_override_self = true)]
 struct Cli {
     /// 统一输出为 JSON
     #[arg(long, global = true)]
@@ -22,6 +108,114 @@ struct Cli {
     #[arg(long = "uncen", short = 'u', alias = "nomo", global = true)]
     uncen: bool,
 
+    /// 只显示 VR 作品（search/list/top 按番号前缀与标题判断；install 等只保留 VR 磁力）
+    #[arg(long, global = true, conflicts_with = "no_vr")]
+    vr: bool,
+
+    /// 排除 VR 作品及其磁力
+    #[arg(long, global = true)]
+    no_vr: bool,
+
+    /// 欧美模式：把 Studio.YY.MM.DD.Performer 形式的场景名也当作番号，交给 ThePornDB 查询（scan/organize/verify 等同样识别）
+    #[arg(long, global = true)]
+    western: bool,
+
+    /// 只显示素人作品（search/list/top，按 SIRO、LUXU、MAAN 等番号前缀与标题判断）
+    #[arg(long, global = true, conflicts_with = "no_amateur")]
+    amateur: bool,
+
+    /// 排除素人作品
+    #[arg(long, global = true)]
+    no_amateur: bool,
+
+    /// 隐藏本地已标记为已看的番号（search/list/top）
+    #[arg(long, global = true)]
+    unwatched: bool,
+
+    /// 只打印将要执行的下载、推送与写入，不实际执行
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// 使用内置的模拟数据代替真实站点（演示、CI）
+    #[arg(long, global = true, env = "AV_MOCK")]
+    mock: bool,
+
+    /// 批量操作的并发数（批量详情、列表翻页、订阅检查），默认 4
+    #[arg(long, short = 'j', global = true, env = "AV_JOBS", value_parser = clap::value_parser!(u16).range(1..=32))]
+    jobs: Option<u16>,
+
+    /// 每个数据源请求的超时秒数，覆盖 [network] timeout_secs（默认不限）
+    #[arg(long, global = true, env = "AV_TIMEOUT", value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..=3600))]
+    timeout: Option<u64>,
+
+    /// 数据源请求失败（连接错误、超时、HTTP 429/5xx）时的重试次数，覆盖 [network] retries（默认 0）
+    #[arg(long, global = true, env = "AV_RETRIES", value_name = "N", value_parser = clap::value_parser!(u32).range(0..=10))]
+    retries: Option<u32>,
+
+    /// 使用配置文件中的 [profiles.<名称>]，覆盖其中设置的代理、来源与下载方式
+    #[arg(long, global = true, env = "AV_PROFILE", value_name = "NAME")]
+    profile: Option<String>,
+
+    /// 元数据的首选语言（标题、类别），决定 JavLibrary 的语言版本与合并时优先采用的来源
+    #[arg(long, global = true, env = "AV_LANG", value_enum)]
+    lang: Option<config::MetadataLang>,
+
+    /// 只使用指定来源、跳过多来源合并（detail/search/install 等获取详情的命令）
+    #[arg(long, global = true, value_enum)]
+    source: Option<scraper::Source>,
+
+    /// 只显示带有该类别的番号（search/list/top，会逐条获取详情；中日英名称均可，如 巨乳 / Big Tits）
+    #[arg(long, global = true)]
+    genre: Option<String>,
+
+    /// 用 jq 子集表达式筛选 JSON 输出（隐含 --json），如 '.magnet_infos[] | select(.seeders > 10) | .url'
+    #[arg(long = "query", short = 'q', global = true, value_name = "EXPR")]
+    output_query: Option<String>,
+
+    /// 表格显示的列，逗号分隔（list/search/top: index,code,title,date,actors,studio,duration,rating,seeders,magnets,genres,badges；actors: index,name,rank,works,views,url）
+    #[arg(long, global = true, value_delimiter = ',', value_name = "COLS")]
+    columns: Vec<String>,
+
+    /// 表格中标题的最大显示宽度，超出以省略号截断
+    #[arg(long, global = true, value_name = "N")]
+    title_width: Option<usize>,
+
+    /// 发行日期的显示方式（JSON 始终为 ISO）
+    #[arg(long, global = true, value_enum)]
+    date_format: Option<dates::DateFormat>,
+
+    /// 每个番号都向所有磁力来源查询并合并去重（默认只在元数据来源没有磁力时查 Sukebei，再无则依次查 BTSOW、TorrentKitty）
+    #[arg(long, global = true)]
+    all_magnets: bool,
+
+    /// 只保留不小于该大小的磁力，如 2GB、700MB（大小未知的保留）
+    #[arg(long, global = true, value_name = "SIZE", value_parser = magnet::parse_size)]
+    min_size: Option<u64>,
+
+    /// 只保留不大于该大小的磁力，如 8GB
+    #[arg(long, global = true, value_name = "SIZE", value_parser = magnet::parse_size)]
+    max_size: Option<u64>,
+
+    /// 下载时按顺序获取分块，便于边下边播（aria2c 只优先首尾分块）
+    #[arg(long, global = true)]
+    sequential: bool,
+
+    /// 分享率达到该值后停止做种，如 1.5
+    #[arg(long, global = true, value_name = "RATIO")]
+    ratio_limit: Option<f64>,
+
+    /// 每个下载的上传限速（每秒），如 500KB、2MB
+    #[arg(long, global = true, value_name = "SPEED", value_parser = magnet::parse_size)]
+    upload_limit: Option<u64>,
+
+    /// 做种多少分钟后停止（aria2c 缺省不做种）
+    #[arg(long, global = true, value_name = "MIN")]
+    seed_time: Option<u64>,
+
+    /// 本次忽略配置中 [defaults] 为各命令设定的默认参数
+    #[arg(long, global = true)]
+    no_defaults: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -30,43 +224,985 @@ struct Cli {
 enum Commands {
     /// 获取该番号对应的磁力链接
     #[command(visible_alias = "get")]
-    Install { code: String },
+    Install {
+        code: String,
+        /// 用 aria2c 下载，完成后按配置 [pipeline] 重命名、写 NFO、保存封面并刷新媒体库
+        #[arg(long, conflicts_with = "pick")]
+        full: bool,
+        /// --full 整理时以硬链接/符号链接放入媒体库，原文件留在下载目录继续做种
+        #[arg(long, value_enum, requires = "full")]
+        link: Option<config::LinkMode>,
+        /// 把做种最多的磁力复制到剪贴板
+        #[arg(long, conflicts_with_all = ["full", "pick"])]
+        copy: bool,
+        #[command(flatten)]
+        pick: pick::PickArgs,
+    },
+
+    /// 重新抓取已缓存番号的磁力（做种数、大小），不重新获取元数据
+    Refresh {
+        #[arg(required_unless_present = "all")]
+        codes: Vec<String>,
+        /// 刷新详情缓存中的全部番号
+        #[arg(long, conflicts_with = "codes")]
+        all: bool,
+    },
+
+    /// 预先获取并缓存一批番号的详情（最新榜单、演员作品），之后的查询直接读缓存
+    #[command(group(clap::ArgGroup::new("targets").required(true).multiple(true).args(["codes", "top", "actor"])))]
+    Prefetch {
+        /// 要预取的番号
+        codes: Vec<String>,
+        /// 最新榜单的前 N 部
+        #[arg(long)]
+        top: Option<usize>,
+        /// 该演员的作品（可重复）
+        #[arg(long)]
+        actor: Vec<String>,
+        /// 两次查询之间等待的秒数，避免被限流
+        #[arg(long, default_value_t = cache::PREFETCH_DELAY_SECS)]
+        delay: u64,
+    },
+
+    /// 向 tracker 查询磁力的做种情况，下载前确认资源是否存活
+    CheckMagnet {
+        /// 番号或磁力链接
+        target: String,
+        /// 只检测第 N 个磁力（序号同 av install），缺省检测全部
+        index: Option<usize>,
+        /// 每个 tracker 的超时秒数
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+    },
 
     /// 展示该番号的详细信息
-    Detail { code: String },
+    Detail {
+        code: String,
+        /// 将标题与剧情翻译为指定语言（需在配置文件 [translate] 中设置翻译服务）
+        #[arg(long, value_enum)]
+        translate: Option<translate::Lang>,
+        /// 分别从每个已启用的来源获取（不合并），逐字段对比差异，用于排查合并结果
+        #[arg(long, conflicts_with = "translate")]
+        compare_sources: bool,
+        /// 只输出这些字段的 JSON（隐含 --json），逗号分隔，可用点号选子字段，如 code,title,magnet_infos.url
+        #[arg(long, value_delimiter = ',', conflicts_with = "compare_sources")]
+        fields: Vec<String>,
+    },
+
+    /// 查看 JavDB / JavLibrary 上的用户评论
+    Reviews {
+        code: String,
+        /// 每个来源最多显示的条数
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
+        /// 将评论翻译为指定语言（需在配置文件 [translate] 中设置翻译服务）
+        #[arg(long, value_enum)]
+        translate: Option<translate::Lang>,
+    },
 
     /// 列出该演员的所有番号
-    #[command(visible_alias = "ls")]
-    List { actor: String },
+    #[command(visible_aliases = ["ls", "actor"])]
+    List {
+        actor: String,
+        /// 统计作品的类别、片商、每年发行数与平均评分（会逐条获取详情）
+        #[arg(long, conflicts_with = "pick")]
+        stats: bool,
+        /// 只列出该日期及之后发行的番号（含当天），如 2023-01-01
+        #[arg(long, value_name = "DATE", value_parser = dates::parse_arg)]
+        from: Option<NaiveDate>,
+        /// 只列出该日期及之前发行的番号（含当天）
+        #[arg(long, value_name = "DATE", value_parser = dates::parse_arg)]
+        to: Option<NaiveDate>,
+        /// 将作品（含详情、封面与磁力链接）导出为 Excel 表格；与 --stats 同用时附带统计表
+        #[arg(long, value_name = "PATH", conflicts_with = "pick")]
+        xlsx: Option<std::path::PathBuf>,
+        #[command(flatten)]
+        pick: pick::PickArgs,
+    },
 
     /// 搜索演员或番号
-    Search { query: String },
+    Search {
+        /// 搜索词；与 --recent 同用时作为前缀筛选历史
+        #[arg(required_unless_present_any = ["recent", "clear_recent"])]
+        query: Option<String>,
+        /// 列出最近的搜索（最近的在前）
+        #[arg(long)]
+        recent: bool,
+        /// 清空搜索历史
+        #[arg(long, conflicts_with_all = ["recent", "query"])]
+        clear_recent: bool,
+        /// 只输出每条结果的这些字段的 JSON（隐含 --json），逗号分隔，如 code,title
+        #[arg(long, value_delimiter = ',', conflicts_with_all = ["recent", "clear_recent"])]
+        fields: Vec<String>,
+        /// 只查询这些来源，逗号分隔：javdb、sukebei、theporndb 或插件名；默认全部同时查询
+        #[arg(long, value_delimiter = ',', conflicts_with_all = ["recent", "clear_recent"])]
+        sources: Vec<String>,
+        /// 将结果（含详情、封面与磁力链接）导出为 Excel 表格
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["recent", "clear_recent", "fields", "pick"])]
+        xlsx: Option<std::path::PathBuf>,
+        #[command(flatten)]
+        pick: pick::PickArgs,
+    },
 
     /// 查看最新的番（默认 20 条）
-    Top { #[arg(short, long, default_value_t = 20)] limit: usize },
+    Top {
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+        /// 保存今天的榜单快照（与 --diff 同用时先对比再保存）
+        #[arg(long)]
+        snapshot: bool,
+        /// 显示与上一次快照相比新上榜、已下榜的番号
+        #[arg(long, conflicts_with = "pick")]
+        diff: bool,
+        /// 只看该片商的最新作品（JavDB 片商页；找不到时按详情中的片商筛选最新列表）
+        #[arg(long, conflicts_with_all = ["snapshot", "diff"])]
+        studio: Option<String>,
+        #[command(flatten)]
+        pick: pick::PickArgs,
+    },
+
+    /// 根据榜单快照统计上升中的演员、片商或类别（快照来自 top --snapshot 或 top_snapshot 定时任务）
+    Trends {
+        /// 统计维度
+        #[arg(value_enum, default_value = "actor")]
+        by: trending::Dimension,
+        /// 统计最近多少天的快照
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
+    },
 
-    /// 演员热度排行榜（分页）
-    Actors { #[arg(short, long, default_value_t = 1)] page: usize, #[arg(short='n', long, default_value_t = 50)] per_page: usize },
+    /// 演员热度排行榜（分页）；`actors find` 按身材、出道年份等条件查找女优
+    #[command(args_conflicts_with_subcommands = true)]
+    Actors {
+        #[command(subcommand)]
+        action: Option<ActorsAction>,
+        #[arg(short, long, default_value_t = 1)]
+        page: usize,
+        #[arg(short = 'n', long, default_value_t = 50)]
+        per_page: usize,
+        /// 读取全部排行（热度、周榜、月榜）的每一页，去重后边读边输出；--json 时每行一个演员
+        #[arg(long, conflicts_with_all = ["page", "per_page"])]
+        all: bool,
+        /// 只保留作品数不少于 N 的演员（排行页没有作品数时读取演员页）
+        #[arg(long)]
+        min_works: Option<u32>,
+        /// 只保留该年份及之后出道的演员（出道年份来自资料缓存或 xslist）
+        #[arg(long)]
+        debut_after: Option<u16>,
+        /// 只保留一年内有新作品的演员（读取演员页）
+        #[arg(long)]
+        active_only: bool,
+    },
+
+    /// 并排对比两个番号（各取评分最高的磁力），或同一番号的两个磁力（序号见 install）
+    Compare {
+        /// 番号
+        first: String,
+        /// 第二个番号，或第一个磁力的序号
+        second: String,
+        /// 第二个磁力的序号（比较同一番号的两个磁力时）
+        third: Option<String>,
+    },
 
     /// 在浏览器中打开观看视频
     #[command(visible_alias = "see")]
-    View { code: String },
+    View {
+        code: String,
+        /// 打开第 N 个候选地址（序号见列表，默认第 1 个）
+        #[arg(long, value_name = "N")]
+        pick: Option<usize>,
+        /// 解析出直接的视频流并用本地播放器播放（默认取 [view] player）
+        #[arg(long, value_enum)]
+        player: Option<config::Player>,
+        /// 只打印解析出的 m3u8/视频流地址（供 ffmpeg / yt-dlp / mpv 使用），不打开任何东西
+        #[arg(long, conflicts_with = "player")]
+        url_only: bool,
+    },
+
+    /// 在浏览器中打开番号的详情页（默认 JavDB，--source 指定其他来源），便于查看评论与截图
+    Open {
+        code: String,
+        /// 只打印地址，不打开浏览器
+        #[arg(long)]
+        print: bool,
+    },
+
+    /// 不走 BT：解析 view 的视频流并用 ffmpeg（或 yt-dlp）下载，中断后可续传
+    Rip {
+        code: String,
+        /// 保存目录，文件名为 <番号>.mp4
+        #[arg(long, short = 'o', default_value = ".")]
+        out: std::path::PathBuf,
+        /// 画质：best、worst，或最高高度如 1080、720
+        #[arg(long, default_value = "best")]
+        quality: rip::Quality,
+        /// 只用第 N 个候选地址（序号见 av view）
+        #[arg(long, value_name = "N")]
+        pick: Option<usize>,
+    },
+
+    /// 获取官方预告片（样片）地址，可直接打开或用 ffmpeg 下载
+    Trailer {
+        code: String,
+        /// 在浏览器 / 默认播放器中打开
+        #[arg(long)]
+        open: bool,
+        /// 用 ffmpeg 下载到文件（缺省为 <番号>-trailer.mp4）
+        #[arg(long, value_name = "FILE")]
+        download: Option<Option<std::path::PathBuf>>,
+    },
+
+    /// 下载字幕（SubtitleCat），按语言（--lang）与下载数挑选，统一转为 UTF-8 的 .srt
+    Sub {
+        code: String,
+        /// 保存位置：目录（文件名为 <番号>.<语言>.srt）或文件；缺省为当前目录
+        #[arg(long, short = 'o')]
+        out: Option<std::path::PathBuf>,
+        /// 只列出找到的字幕，不下载
+        #[arg(long)]
+        list: bool,
+        /// 下载第 N 个字幕（序号见 --list）
+        #[arg(long, value_name = "N")]
+        pick: Option<usize>,
+        /// 在线没有字幕时，用 whisper.cpp 识别本地视频生成日文字幕
+        #[arg(long, conflicts_with_all = ["list", "pick"])]
+        generate: bool,
+        /// whisper 模型：tiny、base、small、medium、large-v3 等，或模型文件路径（缺省为 [subtitles] model）
+        #[arg(long, requires = "generate")]
+        model: Option<String>,
+        /// 要识别的视频文件（缺省在媒体库、下载目录与当前目录中查找）
+        #[arg(long, requires = "generate")]
+        file: Option<std::path::PathBuf>,
+        /// 同时把生成的字幕机器翻译为指定语言（需配置 [translate]）
+        #[arg(long, value_enum, requires = "generate")]
+        translate: Option<translate::Lang>,
+    },
+
+    /// 查询作品的出演者（AV-Wiki），适用于素人系列等未标注演员的番号
+    Who {
+        code: String,
+    },
+
+    /// 查询 DMM 上的当前售价 / 租赁价与促销活动（需设置 DMM_API_ID 与 DMM_AFFILIATE_ID，或存入钥匙串）
+    Price {
+        code: String,
+    },
+
+    /// 按 Kodi / Jellyfin 的命名保存图片：fanart.jpg、竖版海报 poster.jpg、<视频名>-thumb.jpg，以及 extrafanart/ 下的预览图
+    Artwork {
+        code: String,
+        /// 保存目录（缺省为 <番号>）
+        #[arg(short, long)]
+        out: Option<std::path::PathBuf>,
+    },
+
+    /// 下载该番号的全部预览图，可拼成一张缩略图总览
+    Previews {
+        code: String,
+        /// 保存目录（缺省为 <番号>-previews）
+        #[arg(short, long)]
+        out: Option<std::path::PathBuf>,
+        /// 同时生成拼图 sheet.jpg
+        #[arg(long)]
+        sheet: bool,
+        /// 拼图每行的图片数
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=16))]
+        per_row: u32,
+    },
+
+    /// 启动本地 HTTP JSON API 服务（--torznab 额外提供索引器接口）
+    Serve {
+        /// 启用 Torznab 兼容接口（/api）
+        #[arg(long)]
+        torznab: bool,
+        /// 监听地址
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        bind: String,
+        /// 访问所需的 API Key（也可用环境变量 AV_SERVE_API_KEY）
+        #[arg(long, env = "AV_SERVE_API_KEY")]
+        api_key: Option<String>,
+        /// 同时提供网页界面（/）：搜索、详情与一键发送到下载器
+        #[arg(long)]
+        web: bool,
+    },
+
+    /// 生成最新发布的 RSS/Atom 订阅（条目链接为磁力，可供 BT 客户端 RSS 下载器使用）
+    Rss {
+        /// 输出文件（默认输出到标准输出）
+        #[arg(short, long)]
+        out: Option<std::path::PathBuf>,
+        /// 订阅格式
+        #[arg(long, value_enum, default_value_t = feed::FeedFormat::Rss)]
+        format: feed::FeedFormat,
+        /// 最新发布及每位演员各取的条目数
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+        /// 同时收录这些演员的作品（可重复）
+        #[arg(long = "actor")]
+        actors: Vec<String>,
+    },
+
+    /// 订阅的演员/片商/系列即将发售及近期发售的作品；--ics 导出为日历文件（可导入 Google 日历、Thunderbird）
+    Calendar {
+        /// 写入 iCalendar (.ics) 文件
+        #[arg(long, value_name = "FILE")]
+        ics: Option<std::path::PathBuf>,
+        /// 同时收录最近多少天内已发售的作品
+        #[arg(long, default_value_t = 14)]
+        past: u32,
+    },
+
+    /// FC2 Contents Market：按卖家浏览作品
+    Fc2 {
+        #[command(subcommand)]
+        action: Fc2Action,
+    },
+
+    /// 通知推送（Webhook 等，在配置文件 [notify] 中设置）
+    Notify {
+        #[command(subcommand)]
+        action: NotifyAction,
+    },
+
+    /// 登录站点并保存会话（部分 JavDB 磁力/无码/FC2 页面需登录才显示）
+    Login {
+        #[arg(value_enum)]
+        site: login::Site,
+        /// 账号（邮箱或用户名），缺省时交互输入
+        #[arg(long)]
+        username: Option<String>,
+        /// 改为保存从浏览器复制的 Cookie 头（遇到验证码时使用），交互输入（不回显）或从 stdin 读取
+        #[arg(long)]
+        cookie: bool,
+        /// 仅显示当前登录状态
+        #[arg(long)]
+        status: bool,
+    },
+
+    /// 退出登录并删除已保存的会话
+    Logout {
+        #[arg(value_enum)]
+        site: login::Site,
+    },
+
+    /// 标记番号的个人观看状态与评分（本地保存，显示在 search/list/top 结果中）
+    Mark {
+        code: String,
+        /// 标记为已看
+        #[arg(long)]
+        watched: bool,
+        /// 取消已看标记
+        #[arg(long, conflicts_with = "watched")]
+        unwatch: bool,
+        /// 加入想看清单
+        #[arg(long)]
+        want: bool,
+        /// 评分（1-5）
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        rating: Option<u8>,
+        /// 清除该番号的所有本地标记
+        #[arg(long, conflicts_with_all = ["watched", "unwatch", "want", "rating"])]
+        clear: bool,
+    },
+
+    /// 管理各站点保存在本地的 Cookie（Cloudflare 验证、登录会话等）
+    Cookies {
+        #[command(subcommand)]
+        action: CookiesAction,
+    },
+
+    /// 把 DMM API Key、JavDB Cookie 与下载客户端密码存进系统钥匙串，代替明文的环境变量或配置
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+
+    /// 探测所有 JavDB 镜像的可用性与延迟（[javdb] mirrors）
+    Mirrors,
+
+    /// 用一组样例番号测试各来源与 JavDB 镜像：延迟 p50/p95、成功率、命中率与解析完整度
+    Bench {
+        /// 用这些番号代替内置样例
+        codes: Vec<String>,
+        /// 每个番号查询的轮数
+        #[arg(long, default_value_t = 1)]
+        rounds: usize,
+        /// 只测试这些来源（可重复；javdb 包括镜像）
+        #[arg(long)]
+        only: Vec<String>,
+    },
+
+    /// 交互式设置向导：代理、数据源、下载客户端与媒体库，写入配置文件后检查连通性
+    Init,
+
+    /// 诊断运行环境：外部工具、配置文件、数据源 DNS、目录写权限（proxy 子命令检查网络访问）
+    Doctor {
+        #[command(subcommand)]
+        check: Option<doctor::Check>,
+    },
+
+    /// 显示当前生效的 CSS 选择器（内置默认值合并 [selectors] 配置后）
+    Selectors,
+
+    /// 显示最终生效的设置（配置文件、profile、环境变量与命令行合并后）及每项的来源
+    Env {
+        /// 只显示这个键及其下的设置，如 network 或 network.proxy
+        key: Option<String>,
+        /// 也列出未改动的默认值
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// 同步 JavDB 账号的「想看 / 看過」清单（需先 av login javdb）
+    Javdb {
+        #[command(subcommand)]
+        action: JavdbAction,
+    },
+
+    /// 以 MCP (Model Context Protocol) 服务运行在 stdio 上，供 AI 助手调用
+    Mcp,
+
+    /// 常驻运行配置文件中的定时任务（[[daemon.jobs]]），同一时间只允许一个实例
+    Daemon {
+        /// 所有任务各运行一次后退出
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// 订阅演员、片商或系列的新作品：记录当前作品列表，检查时报告新增的番号
+    Subscribe {
+        #[command(subcommand)]
+        action: SubscribeAction,
+    },
+
+    /// 列出 get --full 的整理记录（重命名、移动、链接），--undo 撤销其中一次
+    Organize {
+        /// 撤销该次整理：移回视频、删除链接
+        #[arg(long, value_name = "RUN")]
+        undo: Option<String>,
+    },
+
+    /// 继续被中断的批量任务（--pick 多行下载、subscribe check --queue、refresh --all、prefetch 跳过已完成的番号，actors --all 从下一页读起）；不带 ID 时列出未完成的任务
+    Resume {
+        id: Option<String>,
+        /// 删除该任务而不继续
+        #[arg(long, requires = "id")]
+        drop: bool,
+    },
+
+    /// 下载记录：每次把磁力交给下载器（aria2c、qBittorrent 等）的番号、磁力、时间与结果，可重试失败的下载
+    Jobs {
+        #[command(subcommand)]
+        action: JobsAction,
+    },
+
+    /// 实时查看 av 交给下载器（qBittorrent 或 aria2 RPC）的下载：进度、速度、剩余时间与做种数，可暂停、继续或取消
+    Progress {
+        /// 显示下载器中的全部下载，而不只是 av 添加的
+        #[arg(long)]
+        all: bool,
+        /// 刷新间隔（秒）
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+        /// 只打印一次表格
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// 交互式命令行：连续输入 search/detail/get 等命令，复用连接、Cookie 与已抓取的页面
+    Shell,
+
+    /// 扫描目录中的视频文件，按番号分组并识别多段发布（CD1/CD2、A/B、part1/part2）
+    Scan {
+        /// 要扫描的目录（包含子目录）
+        #[arg(default_value = ".")]
+        dir: std::path::PathBuf,
+    },
+
+    /// 用 ffprobe 检查已下载的视频：时长是否短于资料、分辨率是否与文件名标注不符
+    Verify {
+        /// 视频文件或目录（包含子目录）
+        path: std::path::PathBuf,
+    },
+
+    /// 用 ffmpeg 为本地视频截取均匀分布的画面并拼成一张图，快速检查下载内容
+    Thumbs {
+        /// 视频文件或目录（包含子目录）
+        path: std::path::PathBuf,
+        /// 每行截图数
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=16))]
+        per_row: u32,
+        /// 行数
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=16))]
+        rows: u32,
+        /// 保存到数据目录的 thumbs/ 下，而不是视频旁的 <文件名>.thumbs.jpg
+        #[arg(long)]
+        cache: bool,
+        /// 已有拼图时重新生成
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// 用 ffmpeg 把标题、演员、日期、类别等写入 MP4/MKV 文件自身的元数据（供不读 NFO 的播放器使用）
+    Embed {
+        /// 视频文件或目录（包含子目录），番号取自文件名
+        path: std::path::PathBuf,
+        /// 文件名中没有番号时手动指定（仅限单个文件）
+        #[arg(long)]
+        code: Option<String>,
+    },
 
     /// 自动更新到最新版本
     #[command(name = "update", visible_alias = "self-update")]
-    SelfUpdate,
+    SelfUpdate {
+        /// 安装指定版本（如 0.2.0 或 v0.2.0），可用于降级；缺省为 [update] pin
+        #[arg(long, value_name = "VERSION")]
+        to: Option<String>,
+        /// 更新渠道：stable 只装正式版，nightly 也装预发布版；缺省为 [update] channel
+        #[arg(long, value_enum)]
+        channel: Option<config::UpdateChannel>,
+        /// 只检查是否有可用更新，不安装
+        #[arg(long)]
+        check: bool,
+        /// 即使已是最新版本也重新安装
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum JobsAction {
+    /// 列出最近的下载记录（最新的在前）
+    #[command(visible_alias = "ls")]
+    List {
+        /// 只列出之后没有成功过的失败下载
+        #[arg(long)]
+        failed: bool,
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// 显示一条记录的详情（含错误信息与磁力）
+    Show { id: u64 },
+    /// 用当前配置的下载器重新下载这些记录的磁力
+    Replay {
+        #[arg(required_unless_present = "failed")]
+        ids: Vec<u64>,
+        /// 重试所有之后没有成功过的失败下载（每个番号一次）
+        #[arg(long, conflicts_with = "ids")]
+        failed: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SubscribeAction {
+    /// 订阅演员，记录其当前作品列表（加 --uncen 只关注无码作品）
+    Actor {
+        name: String,
+        #[command(flatten)]
+        filters: subscribe::Filters,
+    },
+    /// 订阅片商（JavDB 上的片商名）
+    Studio {
+        name: String,
+        #[command(flatten)]
+        filters: subscribe::Filters,
+    },
+    /// 订阅系列（JavDB 上的系列名）
+    Series {
+        name: String,
+        #[command(flatten)]
+        filters: subscribe::Filters,
+    },
+    /// 取消订阅
+    Remove {
+        name: String,
+        /// 只取消该类型的订阅（同名的演员/片商/系列）
+        #[arg(long, value_enum)]
+        kind: Option<subscribe::Kind>,
+    },
+    /// 列出所有订阅
+    List,
+    /// 重新获取各订阅的作品列表，报告通过筛选的新作品
+    Check {
+        /// 把新作品做种最多的磁力交给下载器（aria2c 或系统默认的 BT 客户端）
+        #[arg(long)]
+        queue: bool,
+        /// 以 new_release 事件通知新作品（见 [notify] 配置）
+        #[arg(long)]
+        notify: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ActorsAction {
+    /// 按罩杯、身高、出道年份查找女优（资料来自 minnano-av）
+    Find {
+        /// 罩杯：单个（E）、以上（E+）或范围（D-F）
+        #[arg(long)]
+        cup: Option<actress::CupRange>,
+        /// 最低身高（cm）
+        #[arg(long)]
+        min_height: Option<u16>,
+        /// 最高身高（cm）
+        #[arg(long)]
+        max_height: Option<u16>,
+        /// 出道年份不早于（含）
+        #[arg(long)]
+        debut_after: Option<u16>,
+        /// 出道年份不晚于（含）
+        #[arg(long)]
+        debut_before: Option<u16>,
+        /// 最多显示多少位
+        #[arg(short, long, default_value_t = 50)]
+        limit: usize,
+        /// 扫描女优列表的页数
+        #[arg(long, default_value_t = 5)]
+        pages: u32,
+    },
+    /// 查看女优资料：简介、三围、生日与各语言的名字和别名（资料来自 xslist.org）
+    Profile {
+        name: String,
+    },
+    /// 查找女优在 gfriends 头像库中的头像链接
+    Avatar {
+        name: String,
+        /// 先重新下载 gfriends 索引（缺省按 [sources.gfriends] max_age_days 过期后更新）
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// 把 gfriends 头像上传给 Jellyfin/Emby 中没有头像的演员（[media_server]）
+    PushImages {
+        /// 已有头像的演员也覆盖
+        #[arg(long)]
+        force: bool,
+    },
+    /// 列出本地已缓存女优资料中的生日与年龄（缺省为本月）
+    Birthdays {
+        /// 月份（1-12）
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=12))]
+        month: Option<u32>,
+        /// 只列出今天生日的女优
+        #[arg(long, conflicts_with = "month")]
+        today: bool,
+        /// 先重新扫描女优列表、更新本地缓存（缓存为空时自动扫描）
+        #[arg(long)]
+        refresh: bool,
+        /// 扫描女优列表的页数
+        #[arg(long, default_value_t = 5)]
+        pages: u32,
+        /// 将今天生日的女优推送到通知端（birthday 事件）
+        #[arg(long)]
+        notify: bool,
+    },
+    /// 导出演员头像墙 HTML（头像、名字，链接到各自的作品列表）
+    ExportHtml {
+        /// 抓取排行榜的页数
+        #[arg(long, default_value_t = 5)]
+        pages: usize,
+        #[arg(short = 'n', long, default_value_t = 50)]
+        per_page: usize,
+        /// 输出文件
+        #[arg(short, long, default_value = "actors.html")]
+        out: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum Fc2Action {
+    /// 列出某个卖家的全部作品（最新在前），含发行日期与价格
+    Seller {
+        /// 卖家 ID，或卖家页面链接（adult.contents.fc2.com/users/<ID>/）
+        id: String,
+        /// 最多读取的列表页数
+        #[arg(long, default_value_t = 20)]
+        pages: u32,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum NotifyAction {
+    /// 向所有已配置的通知端发送一条测试消息；指定番号时以该番号发送一条 new_release 示例
+    Test { code: Option<String> },
+    /// 运行 Telegram 机器人，接受 /get CODE 等远程命令（需 allow_commands = true）
+    TelegramBot,
+    /// 发送到期的邮件摘要（可放进 daemon 的 command 任务定时运行）
+    Digest {
+        /// 不等到期，立即发送已收集的新作品
+        #[arg(long)]
+        now: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CookiesAction {
+    /// 列出已保存 Cookie 的站点
+    List,
+    /// 删除某个站点（缺省为全部）已保存的 Cookie
+    Clear { source: Option<String> },
+    /// 从本机浏览器导入 Cookie（免去手动设置 AV_JAVDB_COOKIE）
+    Import {
+        #[arg(long, value_enum)]
+        browser: browser_cookies::Browser,
+        /// 要导入的域名（含子域名）
+        #[arg(long, default_value = "javdb.com")]
+        domain: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretAction {
+    /// 列出可存入钥匙串的凭据，以及各自当前从哪里读取
+    List,
+    /// 存入钥匙串；值从标准输入读取（终端中不回显），不接受命令行参数
+    Set {
+        #[arg(value_enum)]
+        name: keychain::Secret,
+    },
+    /// 输出钥匙串中保存的值
+    Get {
+        #[arg(value_enum)]
+        name: keychain::Secret,
+    },
+    /// 从钥匙串删除
+    #[command(visible_alias = "rm")]
+    Delete {
+        #[arg(value_enum)]
+        name: keychain::Secret,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum JavdbAction {
+    /// 拉取 JavDB「想看」清单并合并到本地片单
+    PullWanted,
+    /// 拉取 JavDB「看過」清单并合并到本地观看记录
+    PullWatched,
+    /// 在 JavDB 上将番号标记为「看過」，并同步到本地
+    MarkWatched { code: String },
+    /// 在 JavDB 上将番号加入「想看」，并同步到本地
+    Want { code: String },
+}
+
+impl Cli {
+    /// Global flags typed on an `av shell` line add to those the shell was started with
+    fn inherit(mut self, base: &Cli) -> Cli {
+        self.json |= base.json;
+        self.debug |= base.debug;
+        self.uncen |= base.uncen;
+        self.unwatched |= base.unwatched;
+        self.vr |= base.vr;
+        self.no_vr |= base.no_vr;
+        self.western |= base.western;
+        self.amateur |= base.amateur;
+        self.no_amateur |= base.no_amateur;
+        self.dry_run |= base.dry_run;
+        self.mock |= base.mock;
+        self.jobs = self.jobs.or(base.jobs);
+        self.timeout = self.timeout.or(base.timeout);
+        self.retries = self.retries.or(base.retries);
+        self.lang = self.lang.or(base.lang);
+        self.source = self.source.or(base.source);
+        self.genre = self.genre.take().or_else(|| base.genre.clone());
+        if self.columns.is_empty() {
+            self.columns = base.columns.clone();
+        }
+        self.title_width = self.title_width.or(base.title_width);
+        self.date_format = self.date_format.or(base.date_format);
+        self.all_magnets |= base.all_magnets;
+        self.min_size = self.min_size.or(base.min_size);
+        self.max_size = self.max_size.or(base.max_size);
+        self.sequential |= base.sequential;
+        self.ratio_limit = self.ratio_limit.or(base.ratio_limit);
+        self.upload_limit = self.upload_limit.or(base.upload_limit);
+        self.seed_time = self.seed_time.or(base.seed_time);
+        self
+    }
+
+    /// JSON output was asked for, directly or through `--query` / `--fields`
+    fn wants_json(&self) -> bool {
+        self.json
+            || self.output_query.is_some()
+            || matches!(&self.command, Commands::Detail { fields, .. } | Commands::Search { fields, .. } if !fields.is_empty())
+    }
+
+    /// Runs that shouldn't stop for the first-run questions: scripted, protocol and
+    /// long-running ones, and those that work without a config file by design
+    fn skips_setup(&self) -> bool {
+        self.wants_json()
+            || self.dry_run
+            || self.mock
+            || matches!(
+                self.command,
+                Commands::Mcp
+                    | Commands::Serve { .. }
+                    | Commands::Daemon { .. }
+                    | Commands::Doctor { .. }
+                    | Commands::Env { .. }
+                    | Commands::SelfUpdate { .. }
+            )
+    }
+
+    /// Process-wide switches behind the global flags; needs the config loaded
+    fn apply(&mut self) -> Result<()> {
+        // The profile was merged in before parsing; one that only an alias adds came too late
+        if let Some(p) = self.profile.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+            if config::loaded() && config::active_profile() != Some(p) {
+                bail!(errors::Tagged::new("config", "--profile 需直接写在命令行上，不能放在别名中"));
+            }
+        }
+        let query = self
+            .output_query
+            .as_deref()
+            .map(query::parse)
+            .transpose()
+            .context(errors::Tagged::new("query", "--query 表达式无效"))?;
+        self.json |= query.is_some();
+        util::set_query(query);
+        table::set_overrides(self.columns.clone(), self.title_width);
+        dates::set_format(self.date_format);
+        util::set_json(self.wants_json());
+        util::set_debug(self.debug);
+        util::set_dry_run(self.dry_run);
+        sources::mock::set_enabled(self.mock);
+        util::set_jobs(self.jobs.map(usize::from).or(config::get().network.jobs).unwrap_or(util::DEFAULT_JOBS));
+        util::set_lang(self.lang.or(config::get().metadata.lang));
+        http::set_timeout(self.timeout.or(config::get().network.timeout_secs));
+        http::set_retries(self.retries.or(config::get().network.retries).unwrap_or(0));
+        scraper::set_source(self.source);
+        magnet::set_size_range(self.min_size, self.max_size);
+        magnet::set_all_sources(self.all_magnets);
+        downloader::set_options(downloader::Options {
+            sequential: self.sequential,
+            ratio_limit: self.ratio_limit,
+            upload_limit: self.upload_limit,
+            seed_minutes: self.seed_time,
+        })?;
+        code::set_western(self.western || config::get().metadata.western);
+        vr::set_filter(if self.vr { Some(true) } else if self.no_vr { Some(false) } else { None });
+        amateur::set_filter(if self.amateur { Some(true) } else if self.no_amateur { Some(false) } else { None });
+        Ok(())
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
-    util::set_debug(cli.debug);
+    platform::init();
+    // A broken config file is one of the things the doctor diagnoses; it runs on defaults
+    config::select_profile(&std::env::args_os().collect::<Vec<_>>());
+    // Before the config is loaded, so this very run uses what the wizard writes; with no
+    // file yet there are no aliases to expand either
+    let wrote_config = match Cli::try_parse_from(std::env::args_os()) {
+        Ok(early) if matches!(early.command, Commands::Init) => setup::wizard().await?,
+        Ok(early) if setup::first_run() && !early.skips_setup() => setup::offer().await?,
+        _ => false,
+    };
+    let config_error = config::init().err();
+    if wrote_config && config_error.is_none() {
+        setup::check().await;
+    }
+    let args = alias::expand(std::env::args_os().collect())?;
+    batch::set_args(&args);
+    let cli = Cli::parse_from(args);
+    let json = cli.wants_json();
+    let result = start(cli, config_error).await;
+    if cancel::is_cancelled() {
+        match &result {
+            Err(e) if json => errors::print_json(e),
+            Err(e) => {
+                util::debug(format!("cancelled: {:#}", e));
+                eprintln!("已取消");
+            }
+            Ok(()) => eprintln!("已取消"),
+        }
+        std::process::exit(130);
+    }
+    match result {
+        Err(e) if json => {
+            errors::print_json(&e);
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+async fn start(mut cli: Cli, config_error: Option<anyhow::Error>) -> Result<()> {
+    if let Some(e) = config_error {
+        if !matches!(cli.command, Commands::Doctor { check: None }) {
+            return Err(e);
+        }
+    }
+    cli.apply()?;
+    // The doctor reports broken proxy settings itself, with suggestions
+    if !matches!(cli.command, Commands::Doctor { .. } | Commands::Env { .. }) {
+        http::check_network()?;
+    }
+    cancel::install();
+    // Long-running and protocol commands never reach a point to show the notice
+    let long_running = matches!(
+        cli.command,
+        Commands::SelfUpdate { .. } | Commands::Serve { .. } | Commands::Mcp | Commands::Daemon { .. } | Commands::Shell | Commands::Progress { .. }
+    );
+    let update_check = if long_running { None } else { update::start_check() };
 
+    let result = run(cli).await.and_then(|()| util::take_query_error().map_or(Ok(()), Err));
+    if result.is_ok() && !long_running && !cancel::is_cancelled() {
+        update::notice(update_check).await;
+    }
+    result
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    if let Commands::Resume { id: Some(id), drop: false } = &cli.command {
+        let job = batch::Job::load(id)?;
+        // The job's own flags (--mock, --source, download options, ...) apply again, with
+        // the ones given to `resume` on top
+        let mut original = Cli::try_parse_from(&job.args).context("无法解析该任务的命令行")?.inherit(&cli);
+        original.apply()?;
+        if job.kind == batch::Kind::Actors {
+            let Commands::Actors { action: None, all: true, min_works, debut_after, active_only, .. } = original.command else {
+                bail!("任务 {} 不是 actors --all", job.id);
+            };
+            let filter = actress::RankingFilter { min_works, debut_after, active_only };
+            return actress::crawl(&filter, original.uncen, original.wants_json(), job).await;
+        }
+        return batch::resume(job, original.wants_json()).await;
+    }
     match cli.command {
-        Commands::Install { code } => {
+        Commands::Install { code, full: true, link, .. } => pipeline::full(&code, link, cli.json).await,
+        Commands::Install { code, copy: true, .. } => {
+            let detail = scraper::fetch_detail(&code).await?;
+            let Some(magnet) = util::ranked_magnets(&detail).into_iter().next() else {
+                bail!("未找到 {} 的磁力链接", detail.code);
+            };
+            platform::copy_to_clipboard(&magnet).await?;
+            util::note(format!("{} {}", "已复制到剪贴板:".green().bold(), magnet.cyan()));
+            Ok(())
+        }
+        Commands::Install { code, pick, .. } => {
             let detail = scraper::fetch_detail(&code).await?;
+            if let Some(rows) = &pick.pick {
+                return pick::magnets(&detail, rows, pick.then, cli.json).await;
+            }
             
-            // 显示所有可用的磁力链接，按种子数排序
+            if util::is_json() {
+                #[derive(serde::Serialize, Debug)]
+                struct Magnets<'a> {
+                    code: &'a str,
+                    title: &'a str,
+                    magnet_infos: &'a [types::MagnetInfo],
+                    magnets: &'a [String],
+                }
+                if detail.magnet_infos.is_empty() && detail.magnets.is_empty() {
+                    util::note(format!("未找到 {} 的磁力链接", detail.code));
+                }
+                util::print_output(&Magnets { code: &detail.code, title: &detail.title, magnet_infos: &detail.magnet_infos, magnets: &detail.magnets }, true);
+                hooks::fire(hooks::Hook::Install, &detail).await;
+                return Ok(());
+            }
+
+            // 显示所有可用的磁力链接，按质量评分排序
             println!("番号: {} - {}", code.bold(), detail.title);
             
             if detail.magnet_infos.is_empty() && detail.magnets.is_empty() {
@@ -76,19 +1212,21 @@ async fn main() -> Result<()> {
                 
                 // 先显示有详细信息的磁力链接
                 if !detail.magnet_infos.is_empty() {
-                    // 按种子数排序
+                    // 按质量评分排序，没有评分的按种子数
                     let mut sorted_magnets = detail.magnet_infos.clone();
-                    sorted_magnets.sort_by(|a, b| b.seeders.unwrap_or(0).cmp(&a.seeders.unwrap_or(0)));
+                    sorted_magnets.sort_by(types::MagnetInfo::ranking);
                     
                     for (i, m) in sorted_magnets.iter().enumerate() {
                         let mut info = String::new();
                         if let Some(name) = &m.name { info.push_str(&format!(" | {}", name)); }
+                        if let Some(p) = m.part { info.push_str(&format!(" | CD{}", p)); }
                         if let Some(size) = &m.size { info.push_str(&format!(" | {}", size)); }
                         if let Some(res) = &m.resolution { info.push_str(&format!(" | {}", res)); }
                         if let Some(codec) = &m.codec { info.push_str(&format!(" | {}", codec)); }
                         if let Some(b) = m.avg_bitrate_mbps { info.push_str(&format!(" | ~{:.2} Mbps", b)); }
                         if let Some(s) = m.seeders { info.push_str(&format!(" | S:{}", s)); }
                         if let Some(lc) = m.leechers { info.push_str(&format!(" | L:{}", lc)); }
+                        if let Some(q) = m.quality_score { info.push_str(&format!(" | Q:{:.1}", q)); }
                         
                         println!("{}. {}{}", i+1, m.url.cyan(), info);
                     }
@@ -99,78 +1237,458 @@ async fn main() -> Result<()> {
                     }
                 }
                 
-                println!("\n{}", "使用方法:".yellow().bold());
-                println!("- 复制链接到您的 BT 客户端");
-                println!("- 或使用命令行工具: aria2c \"<磁力链接>\"");
+                util::note(format!("\n{}", "使用方法:".yellow().bold()));
+                util::note("- 复制链接到您的 BT 客户端");
+                util::note("- 或使用命令行工具: aria2c \"<磁力链接>\"");
             }
-            
+            hooks::fire(hooks::Hook::Install, &detail).await;
             Ok(())
         }
-        Commands::Detail { code } => {
+        Commands::Detail { code, compare_sources: true, .. } => source_diff::run(&code, cli.json).await,
+        Commands::Detail { code, translate, fields, .. } => {
             util::debug(format!("detail: fetching {}", code));
             let detail = scraper::fetch_detail(&code).await?;
-            if cli.json {
-                util::print_output(&detail, true);
+            let translation = match translate {
+                Some(lang) => Some(translate::detail(&detail, lang).await?),
+                None => None,
+            };
+            if cli.json || !fields.is_empty() {
+                #[derive(serde::Serialize, Debug)]
+                struct Translated<'a> {
+                    #[serde(flatten)]
+                    detail: &'a types::AvDetail,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    translation: Option<translate::Translation>,
+                }
+                let out = Translated { detail: &detail, translation };
+                if fields.is_empty() {
+                    util::print_output(&out, true);
+                } else {
+                    util::print_fields(&out, &fields)?;
+                }
             } else {
                 util::print_detail_human(&detail);
+                if let Some(t) = translation {
+                    println!("{}", format!("翻译（{}）：", t.lang.as_str()).bold());
+                    println!("标题： {}", t.title);
+                    if let Some(plot) = t.plot {
+                        println!("剧情：\n{}", plot);
+                    }
+                }
             }
+            hooks::fire(hooks::Hook::Detail, &detail).await;
             Ok(())
         }
-        Commands::List { actor } => {
-            let mut items = scraper::list_actor_titles(&actor).await?;
+        Commands::Refresh { codes, all } => cache::refresh(&codes, all, cli.json).await,
+        Commands::Prefetch { codes, top, actor, delay } => cache::prefetch(&codes, top, &actor, delay, cli.json).await,
+        Commands::CheckMagnet { target, index, timeout } => tracker::check(&target, index, timeout, cli.json).await,
+        Commands::Reviews { code, limit, translate } => reviews::run(&code, limit, translate, cli.json).await,
+        Commands::List { actor, stats, from, to, xlsx, pick } => {
+            let mut items = scraper::list_actor_titles_in(&actor, &dates::DateRange { from, to }).await?;
             if cli.uncen {
                 items.retain(|i| util::looks_uncensored(&i.title));
             }
+            vr::filter_items(&mut items);
+            amateur::filter_items(&mut items);
+            if cli.unwatched {
+                let lib = library::Library::load()?;
+                items.retain(|i| !lib.is_watched(&i.code));
+            }
+            if let Some(g) = &cli.genre {
+                items = genre::filter_items(items, g).await;
+            }
+            if stats {
+                return filmography::run(&actor, &items, xlsx.as_deref(), cli.json).await;
+            }
+            if let Some(path) = &xlsx {
+                return xlsx::export_items(&items, &actor, path).await;
+            }
+            if let Some(rows) = &pick.pick {
+                return pick::items(&items, rows, pick.then, cli.json).await;
+            }
             if cli.json {
                 util::print_output(&items, true);
             } else {
-                util::print_items_table(&items);
+                table::print_items(&items).await?;
             }
             Ok(())
         }
-        Commands::Search { query } => {
-            let mut items = scraper::search(&query).await?;
+        Commands::Search { clear_recent: true, .. } => history::clear(),
+        Commands::Search { query, recent: true, .. } => history::run(query.as_deref(), cli.json),
+        Commands::Search { query, fields, sources, xlsx, pick, .. } => {
+            let query = query.unwrap_or_default();
+            let mut items = scraper::search_in(&query, &sources).await?;
+            history::record(&query);
             if cli.uncen {
                 items.retain(|i| util::looks_uncensored(&i.title));
             }
-            if cli.json {
+            vr::filter_items(&mut items);
+            amateur::filter_items(&mut items);
+            if cli.unwatched {
+                let lib = library::Library::load()?;
+                items.retain(|i| !lib.is_watched(&i.code));
+            }
+            if let Some(g) = &cli.genre {
+                items = genre::filter_items(items, g).await;
+            }
+            if let Some(rows) = &pick.pick {
+                return pick::items(&items, rows, pick.then, cli.json).await;
+            }
+            if let Some(path) = &xlsx {
+                return xlsx::export_items(&items, &query, path).await;
+            }
+            if !fields.is_empty() {
+                util::print_fields(&items, &fields)?;
+            } else if cli.json {
                 util::print_output(&items, true);
             } else {
-                util::print_items_table(&items);
+                table::print_items(&items).await?;
+                if items.is_empty() {
+                    history::suggest(&query);
+                }
             }
             Ok(())
         }
-        Commands::Top { limit } => {
-            let mut items = scraper::top(limit).await?;
+        Commands::Top { limit, snapshot, diff, studio, pick } => {
+            let mut items = match &studio {
+                Some(name) => scraper::top_by_studio(name, limit).await?,
+                None => scraper::top(limit).await?,
+            };
+            // Snapshots and diffs cover the listing itself; --uncen/--vr/--amateur/--unwatched/--genre only filter the table
+            if diff {
+                trending::diff(&items, cli.json)?;
+            }
+            if snapshot {
+                let n = trending::snapshot(&items).await?;
+                if n > 0 {
+                    eprintln!("{}（{} 部）", "已保存今天的榜单快照".green(), n);
+                }
+            }
+            if diff {
+                return Ok(());
+            }
             if cli.uncen {
                 items.retain(|i| util::looks_uncensored(&i.title));
             }
+            vr::filter_items(&mut items);
+            amateur::filter_items(&mut items);
+            if cli.unwatched {
+                let lib = library::Library::load()?;
+                items.retain(|i| !lib.is_watched(&i.code));
+            }
+            if let Some(g) = &cli.genre {
+                items = genre::filter_items(items, g).await;
+            }
+            if let Some(rows) = &pick.pick {
+                return pick::items(&items, rows, pick.then, cli.json).await;
+            }
             if cli.json {
                 util::print_output(&items, true);
             } else {
-                util::print_items_table(&items);
+                table::print_items(&items).await?;
+            }
+            Ok(())
+        }
+        Commands::Trends { by, days, limit } => trending::run(by, days, limit, cli.json),
+        Commands::Actors {
+            action: Some(ActorsAction::Find { cup, min_height, max_height, debut_after, debut_before, limit, pages }),
+            ..
+        } => {
+            let filter = actress::Filter { cup, min_height, max_height, debut_after, debut_before };
+            actress::run(filter, pages, limit, cli.json).await
+        }
+        Commands::Actors { action: Some(ActorsAction::Profile { name }), .. } => actress::profile(&name, cli.json).await,
+        Commands::Actors { action: Some(ActorsAction::Avatar { name, refresh }), .. } => actress::avatar(&name, refresh, cli.json).await,
+        Commands::Actors { action: Some(ActorsAction::PushImages { force }), .. } => actress::push_images(force, cli.json).await,
+        Commands::Actors { action: Some(ActorsAction::Birthdays { month, today, refresh, pages, notify }), .. } => {
+            actress::birthdays(month, today, refresh, pages, notify, cli.json).await
+        }
+        Commands::Actors { action: Some(ActorsAction::ExportHtml { pages, per_page, out }), .. } => {
+            gallery::export_html(pages, per_page, cli.uncen, &out).await
+        }
+        Commands::Actors { action: None, all: true, min_works, debut_after, active_only, .. } => {
+            let filter = actress::RankingFilter { min_works, debut_after, active_only };
+            actress::crawl(&filter, cli.uncen, cli.json, batch::Job::crawl()?).await
+        }
+        Commands::Actors { action: None, page, per_page, min_works, debut_after, active_only, .. } => {
+            let filter = actress::RankingFilter { min_works, debut_after, active_only };
+            let mut found = scraper::actors(page, per_page, cli.uncen).await?;
+            found.actors = actress::filter_ranking(&filter, found.actors).await?;
+            if cli.json {
+                let next = found.next_url.clone();
+                util::print_output(&found.envelope(page, per_page, || next), true);
+            } else if found.actors.is_empty() && filter.is_set() {
+                println!("{}", "本页没有符合条件的演员".yellow());
+            } else {
+                table::print_actors(&found, page, per_page)?;
+            }
+            Ok(())
+        }
+        Commands::Compare { first, second, third } => compare::run(&first, &second, third.as_deref(), cli.json).await,
+        Commands::Open { code, print } => {
+            let (source, url) = scraper::source_page_url(&code).await?;
+            let url = url.with_context(|| format!("{} 没有 {} 的详情页", source.as_str(), code))?;
+            if cli.json {
+                util::print_output(&serde_json::json!({ "code": code, "source": source.as_str(), "url": url }), true);
+            } else {
+                println!("{}", url);
+            }
+            if !print {
+                util::open_browser_url(&url).await?;
+            }
+            Ok(())
+        }
+        Commands::View { code, pick, player, url_only } => {
+            util::debug(format!("view: finding play URLs for {}", code));
+            let candidates = scraper::play_candidates(&code).await?;
+            let index = pick.unwrap_or(1);
+            if index == 0 || index > candidates.len() {
+                bail!("--pick 超出范围：共 {} 个候选地址", candidates.len());
+            }
+            // Without --pick any candidate that yields a stream will do
+            let tried = if pick.is_some() { &candidates[index - 1..index] } else { &candidates[..] };
+            if url_only {
+                let s = stream::first(tried).await?;
+                if cli.json {
+                    util::print_output(&s, true);
+                } else {
+                    println!("{}", s.url);
+                }
+                return Ok(());
+            }
+            if cli.json {
+                util::print_output(&candidates, true);
+                // Scripts get the list; only an explicit --pick opens anything
+                if pick.is_none() {
+                    return Ok(());
+                }
+            } else {
+                if candidates.len() == 1 && candidates[0].source == "search" {
+                    println!("{}", "未找到播放链接，将打开 JavDB 搜索页".yellow());
+                }
+                for (i, c) in candidates.iter().enumerate() {
+                    let marker = if i + 1 == index { "→" } else { " " };
+                    println!("{} {}. [{}] {}", marker, i + 1, c.source, c.url.cyan());
+                }
+            }
+            if let Some(player) = player.or(config::get().view.player) {
+                return player::play(player, &code, tried).await;
+            }
+            let play_url = &candidates[index - 1].url;
+            if !cli.json {
+                println!("Opening browser to watch: {}", play_url);
+            }
+            util::open_browser_url(play_url).await?;
+            Ok(())
+        }
+        Commands::Rip { code, out, quality, pick } => rip::run(&code, out, quality, pick, cli.json).await,
+        Commands::Trailer { code, open, download } => trailer::run(&code, open, download, cli.json).await,
+        Commands::Price { code } => price::run(&code, cli.json).await,
+        Commands::Sub { code, out, list, pick, generate, model, file, translate } => {
+            let opts = subtitles::SubOptions { out, list, pick, generate, model, file, translate };
+            subtitles::run(&code, opts, cli.json).await
+        }
+        Commands::Who { code } => who::run(&code, cli.json).await,
+        Commands::Artwork { code, out } => artwork::artwork(&code, out, cli.json).await,
+        Commands::Previews { code, out, sheet, per_row } => artwork::previews(&code, out, sheet, per_row, cli.json).await,
+        Commands::Serve { torznab, bind, api_key, web } => {
+            server::serve(server::ServeOptions { bind, torznab, api_key, uncen: cli.uncen, web }).await
+        }
+        Commands::Rss { out, format, limit, actors } => {
+            let opts = feed::FeedOptions { limit, actors, uncen: cli.uncen };
+            let body = feed::build(&opts, format).await?;
+            match out {
+                Some(path) => {
+                    if !util::dry_run_skip(format!("将写入 {}（{} 字节）", path.display(), body.len())) {
+                        std::fs::write(&path, body).with_context(|| format!("写入订阅文件失败: {}", path.display()))?;
+                        eprintln!("{} {}", "已生成订阅:".green().bold(), path.display());
+                    }
+                }
+                None => print!("{}", body),
+            }
+            Ok(())
+        }
+        Commands::Calendar { ics, past } => calendar::run(ics.as_deref(), past, cli.uncen, cli.json).await,
+        Commands::Fc2 { action: Fc2Action::Seller { id, pages } } => fc2::seller(&id, pages, cli.json).await,
+        Commands::Notify { action: NotifyAction::Test { code } } => {
+            let event = match code {
+                Some(code) => notify::Event::NewRelease { source: "test".to_string(), detail: Box::new(scraper::fetch_detail(&code).await?) },
+                None => notify::Event::Test { message: "av 通知测试".to_string() },
+            };
+            let configured = notify::configured_count();
+            if configured == 0 {
+                util::note("未配置任何通知端（见配置文件 [notify]）".yellow().to_string());
+                return Ok(());
+            }
+            let delivered = notify::emit(&event).await;
+            util::note(format!("已成功推送 {}/{} 个通知端", delivered, configured));
+            Ok(())
+        }
+        Commands::Notify { action: NotifyAction::TelegramBot } => notify::telegram::run_bot().await,
+        Commands::Notify { action: NotifyAction::Digest { now } } => notify::email::digest(now).await,
+        Commands::Login { site, username, cookie, status } => {
+            login::login(site, login::LoginArgs { username, cookie, status }).await
+        }
+        Commands::Logout { site } => login::logout(site),
+        Commands::Scan { dir } => scan::run(&dir, cli.json),
+        Commands::Verify { path } => verify::run(&path, cli.json).await,
+        Commands::Thumbs { path, per_row, rows, cache, force } => {
+            thumbs::run(&path, thumbs::ThumbsOptions { columns: per_row, rows, cache, force }, cli.json).await
+        }
+        Commands::Embed { path, code } => embed::run(&path, code.as_deref(), cli.json).await,
+        Commands::Mark { code, watched, unwatch, want, rating, clear } => {
+            let mut lib = library::Library::load()?;
+            let key = code::normalize(&code);
+            if clear {
+                lib.entries.remove(&key);
+            } else {
+                if watched {
+                    lib.mark_watched(&key, None);
+                }
+                if unwatch {
+                    lib.unmark_watched(&key);
+                }
+                if want {
+                    lib.mark_wanted(&key, None);
+                }
+                if let Some(r) = rating {
+                    lib.entry(&key).rating = Some(r);
+                }
+            }
+            lib.save()?;
+            if cli.json {
+                util::print_output(&lib.get(&key), true);
+            } else {
+                match lib.badge(&key) {
+                    Some(badge) => println!("{}: {}", key.bold(), badge),
+                    None => println!("{}: 无标记", key.bold()),
+                }
             }
             Ok(())
         }
-        Commands::Actors { page, per_page } => {
-            let (actors, total) = scraper::actors(page, per_page, cli.uncen).await?;
+        Commands::Cookies { action: CookiesAction::List } => {
+            let jars = cookies::list();
             if cli.json {
-                util::print_output(&(actors, total), true);
+                util::print_output(&jars, true);
+            } else if jars.is_empty() {
+                println!("没有已保存的 Cookie（{}）", cookies::dir().display());
             } else {
-                util::print_actors_table(&actors, page, per_page, total);
+                for (source, count) in jars {
+                    println!("{:<12} {} 个", source, count);
+                }
+            }
+            Ok(())
+        }
+        Commands::Cookies { action: CookiesAction::Clear { source } } => {
+            let removed = cookies::clear(source.as_deref())?;
+            util::note(format!("已删除 {} 个站点的 Cookie", removed));
+            Ok(())
+        }
+        Commands::Cookies { action: CookiesAction::Import { browser, domain } } => {
+            let found = browser_cookies::read(browser, &domain)?;
+            if found.is_empty() {
+                anyhow::bail!("浏览器中没有 {} 的 Cookie（请先在浏览器中打开并登录该站点）", domain);
             }
+            let n = cookies::import(&domain, &found)?;
+            if domain.contains("javdb") && found.iter().any(|c| c.name == "remember_me_token" || c.name == "_jdb_session") {
+                login::record_javdb_session(None)?;
+            }
+            util::note(format!("已从 {:?} 导入 {} 个 {} 的 Cookie", browser, n, domain));
             Ok(())
         }
-        Commands::View { code } => {
-            util::debug(format!("view: finding play URL for {}", code));
-            let play_url = scraper::get_play_url(&code).await?;
-            println!("Opening browser to watch: {}", play_url);
-            util::open_browser_url(&play_url).await?;
+        Commands::Secret { action } => match action {
+            SecretAction::List => keychain::list(cli.json),
+            SecretAction::Set { name } => keychain::set(name),
+            SecretAction::Get { name } => keychain::print(name),
+            SecretAction::Delete { name } => keychain::delete(name),
+        },
+        Commands::Mirrors => {
+            let c = scraper::client();
+            let current = mirrors::current();
+            let mut rows = Vec::new();
+            for m in mirrors::list() {
+                let latency = mirrors::probe(&c, &m).await;
+                rows.push(serde_json::json!({
+                    "mirror": m,
+                    "current": m == current,
+                    "ok": latency.is_some(),
+                    "latency_ms": latency.map(|d| d.as_millis() as u64),
+                }));
+                if !cli.json {
+                    let mark = if m == current { "*" } else { " " };
+                    match latency {
+                        Some(d) => println!("{} {:<40} {} {}ms", mark, m, "OK".green(), d.as_millis()),
+                        None => println!("{} {:<40} {}", mark, m, "不可用".red()),
+                    }
+                }
+            }
+            if cli.json {
+                util::print_output(&rows, true);
+            }
             Ok(())
         }
-        Commands::SelfUpdate => {
-            util::self_update().await?;
+        Commands::Bench { codes, rounds, only } => bench::run(&codes, rounds, &only, cli.json).await,
+        // The wizard ran before the config was loaded
+        Commands::Init => Ok(()),
+        Commands::Doctor { check } => doctor::run(check, cli.json).await,
+        Commands::Selectors => {
+            selectors::print(cli.json);
             Ok(())
         }
+        Commands::Env { key, all } => {
+            // Parsed again for where each value came from, which the derived struct drops
+            let matches = Cli::command().try_get_matches_from(alias::expand(std::env::args_os().collect())?)?;
+            let flags: Vec<&str> = ["jobs", "lang", "profile", "timeout", "retries"]
+                .into_iter()
+                .filter(|id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine))
+                .collect();
+            settings::run(key.as_deref(), all, &flags, cli.json)
+        }
+        Commands::Javdb { action } => {
+            let pulled = match action {
+                JavdbAction::PullWanted => javdb_sync::pull(javdb_sync::List::Wanted).await?,
+                JavdbAction::PullWatched => javdb_sync::pull(javdb_sync::List::Watched).await?,
+                JavdbAction::MarkWatched { code } => {
+                    util::note(javdb_sync::push(javdb_sync::List::Watched, &code).await?);
+                    return Ok(());
+                }
+                JavdbAction::Want { code } => {
+                    util::note(javdb_sync::push(javdb_sync::List::Wanted, &code).await?);
+                    return Ok(());
+                }
+            };
+            let (total, added) = pulled;
+            if cli.json {
+                util::print_output(&serde_json::json!({ "total": total, "added": added }), true);
+            } else {
+                println!("JavDB 共 {} 条，本地新增 {} 条", total, added);
+            }
+            Ok(())
+        }
+        Commands::Mcp => mcp::run_stdio().await,
+        Commands::Daemon { once } => daemon::run(once).await,
+        Commands::Subscribe { action } => match action {
+            SubscribeAction::Actor { name, filters } => subscribe::add(subscribe::Kind::Actor, &name, filters.with_uncen(cli.uncen)).await,
+            SubscribeAction::Studio { name, filters } => subscribe::add(subscribe::Kind::Studio, &name, filters.with_uncen(cli.uncen)).await,
+            SubscribeAction::Series { name, filters } => subscribe::add(subscribe::Kind::Series, &name, filters.with_uncen(cli.uncen)).await,
+            SubscribeAction::Remove { name, kind } => subscribe::remove(&name, kind),
+            SubscribeAction::List => subscribe::list(cli.json),
+            SubscribeAction::Check { queue, notify } => subscribe::check(queue, notify, cli.uncen, cli.json).await,
+        },
+        Commands::Organize { undo: None } => organize::list(cli.json),
+        Commands::Organize { undo: Some(run) } => organize::undo(&run).await,
+        Commands::Resume { id: None, .. } => batch::list(cli.json),
+        Commands::Resume { id: Some(id), drop: true } => batch::drop_job(&id),
+        Commands::Resume { id: Some(_), drop: false } => unreachable!("handled above"),
+        Commands::Jobs { action } => match action {
+            JobsAction::List { failed, limit } => journal::list(failed, limit, cli.json),
+            JobsAction::Show { id } => journal::show(id, cli.json),
+            JobsAction::Replay { ids, failed } => journal::replay(&ids, failed).await,
+        },
+        Commands::Progress { all, interval, once } => progress::run(all, interval, once, cli.json).await,
+        Commands::Shell => shell::run(cli).await,
+        Commands::SelfUpdate { to, channel, check, force } => {
+            update::run(update::UpdateOptions { to, channel, check, force }, cli.json).await
+        }
     }
 }
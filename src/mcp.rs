@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::scraper;
+use crate::types::MagnetInfo;
+use crate::util;
+
+/// Latest MCP revision we speak; older clients get their own version echoed back.
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Run a Model Context Protocol server over stdio (newline-delimited JSON-RPC 2.0).
+/// stdout carries protocol messages only; diagnostics go to stderr via util::debug.
+pub async fn run_stdio() -> Result<()> {
+    // Notes from the tools (downloads and the like) move to stderr too
+    util::set_json(true);
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await.context("读取 stdin 失败")? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        util::debug(format!("mcp <- {}", line));
+        let reply = match serde_json::from_str::<Value>(line) {
+            Ok(msg) => handle_message(msg).await,
+            Err(e) => Some(rpc_error(Value::Null, -32700, &format!("parse error: {}", e))),
+        };
+        if let Some(reply) = reply {
+            let out = serde_json::to_string(&reply)?;
+            util::debug(format!("mcp -> {}", out));
+            stdout.write_all(out.as_bytes()).await?;
+            stdout.write_all(b"\n").await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+async fn handle_message(msg: Value) -> Option<Value> {
+    let method = msg.get("method").and_then(|m| m.as_str()).unwrap_or("").to_string();
+    // Notifications carry no id and never get a response
+    let id = msg.get("id").cloned()?;
+    let params = msg.get("params").cloned().unwrap_or(Value::Null);
+    let result = match method.as_str() {
+        "initialize" => Ok(initialize_result(&params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+        "tools/call" => Ok(call_tool(&params).await),
+        _ => Err((-32601, format!("method not found: {}", method))),
+    };
+    Some(match result {
+        Ok(r) => json!({ "jsonrpc": "2.0", "id": id, "result": r }),
+        Err((code, message)) => rpc_error(id, code, &message),
+    })
+}
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn initialize_result(params: &Value) -> Value {
+    let version = params
+        .get("protocolVersion")
+        .and_then(|v| v.as_str())
+        .unwrap_or(PROTOCOL_VERSION);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": { "listChanged": false } },
+        "serverInfo": { "name": "av", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn string_schema(name: &str, description: &str) -> Value {
+    json!({
+        "type": "object",
+        "properties": { name: { "type": "string", "description": description } },
+        "required": [name],
+    })
+}
+
+fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "search_code",
+            "description": "Search JAV titles by code or actor name; returns a list of {code, title}.",
+            "inputSchema": string_schema("query", "A code such as FSDSS-351, or an actor name"),
+        },
+        {
+            "name": "get_detail",
+            "description": "Fetch full metadata for a code: title, actors, release date, studio, genres, rating, cover, previews and magnets.",
+            "inputSchema": string_schema("code", "Release code, e.g. FSDSS-351"),
+        },
+        {
+            "name": "list_actor",
+            "description": "List all codes for an actor.",
+            "inputSchema": string_schema("actor", "Actor name"),
+        },
+        {
+            "name": "get_magnets",
+            "description": "Get magnet links for a code, best first (quality score, then seeders).",
+            "inputSchema": string_schema("code", "Release code, e.g. FSDSS-351"),
+        },
+        {
+            "name": "top",
+            "description": "List the latest releases.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "limit": { "type": "integer", "description": "Number of items (default 20)" } },
+            },
+        },
+    ])
+}
+
+fn str_arg(args: &Value, name: &str) -> Result<String> {
+    args.get(name)
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .with_context(|| format!("missing argument: {}", name))
+}
+
+async fn call_tool(params: &Value) -> Value {
+    let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+    let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+    util::debug(format!("mcp tool call: {} {}", name, args));
+    // Tool failures are reported in-band so the model can see and react to them
+    match run_tool(name, &args).await {
+        Ok(v) => json!({
+            "content": [{ "type": "text", "text": serde_json::to_string_pretty(&v).unwrap_or_default() }],
+            "isError": false,
+        }),
+        Err(e) => json!({
+            "content": [{ "type": "text", "text": format!("{:#}", e) }],
+            "isError": true,
+        }),
+    }
+}
+
+async fn run_tool(name: &str, args: &Value) -> Result<Value> {
+    match name {
+        "search_code" => {
+            let items = scraper::search(&str_arg(args, "query")?).await?;
+            Ok(serde_json::to_value(items)?)
+        }
+        "get_detail" => {
+            let detail = scraper::fetch_detail(&str_arg(args, "code")?).await?;
+            Ok(serde_json::to_value(detail)?)
+        }
+        "list_actor" => {
+            let items = scraper::list_actor_titles(&str_arg(args, "actor")?).await?;
+            Ok(serde_json::to_value(items)?)
+        }
+        "get_magnets" => {
+            let detail = scraper::fetch_detail(&str_arg(args, "code")?).await?;
+            if detail.magnet_infos.is_empty() {
+                return Ok(serde_json::to_value(detail.magnets)?);
+            }
+            let mut magnets = detail.magnet_infos;
+            magnets.sort_by(MagnetInfo::ranking);
+            Ok(serde_json::to_value(magnets)?)
+        }
+        "top" => {
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+            let items = scraper::top(limit).await?;
+            Ok(serde_json::to_value(items)?)
+        }
+        _ => anyhow::bail!("unknown tool: {}", name),
+    }
+}
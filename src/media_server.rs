@@ -0,0 +1,203 @@
+//! Scoped rescans on Jellyfin, Emby and Plex: after files are placed only their folder is
+//! scanned, instead of the whole library. Jellyfin and Emby can also be given the gfriends
+//! portraits of the people they list.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::config::{self, MediaServerConfig, MediaServerKind};
+use crate::http;
+use crate::sources::gfriends;
+use crate::util;
+
+fn client() -> Result<reqwest::Client> {
+    http::with_proxy(reqwest::Client::builder()).timeout(Duration::from_secs(20)).build().context("client build")
+}
+
+/// `dir` as the server sees it: absolute, with `[pipeline] library_dir` swapped for
+/// `library_path` when that is set
+fn server_path(dir: &Path, cfg: &MediaServerConfig) -> String {
+    let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let local = config::get().pipeline.library_dir.as_ref().map(|l| std::path::absolute(l).unwrap_or_else(|_| l.clone()));
+    match (&cfg.library_path, local) {
+        (Some(remote), Some(local)) => match dir.strip_prefix(&local) {
+            Ok(rest) if rest.as_os_str().is_empty() => remote.clone(),
+            Ok(rest) => format!("{}/{}", remote.trim_end_matches('/'), rest.to_string_lossy().replace('\\', "/")),
+            Err(_) => dir.to_string_lossy().into_owned(),
+        },
+        _ => dir.to_string_lossy().into_owned(),
+    }
+}
+
+async fn check(resp: reqwest::Response, what: &str) -> Result<reqwest::Response> {
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        bail!("{} 返回 HTTP {}: {}", what, status.as_u16(), util::truncate_chars(body.trim(), 200));
+    }
+    Ok(resp)
+}
+
+/// Jellyfin and Emby share the "media updated" endpoint, which scans just that path
+async fn jellyfin(c: &reqwest::Client, cfg: &MediaServerConfig, path: &str) -> Result<()> {
+    let url = format!("{}/Library/Media/Updated", cfg.url.trim_end_matches('/'));
+    let resp = c
+        .post(&url)
+        .header("X-Emby-Token", &cfg.token)
+        .json(&json!({ "Updates": [{ "Path": path, "UpdateType": "Created" }] }))
+        .send()
+        .await
+        .context("请求媒体服务器失败")?;
+    check(resp, "媒体服务器").await?;
+    Ok(())
+}
+
+/// The Plex section whose folders contain `path`, the most specific one when they nest
+async fn plex_section(c: &reqwest::Client, cfg: &MediaServerConfig, path: &str) -> Result<String> {
+    let url = format!("{}/library/sections", cfg.url.trim_end_matches('/'));
+    let resp = c
+        .get(&url)
+        .query(&[("X-Plex-Token", cfg.token.as_str())])
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .context("请求 Plex 失败")?;
+    let v: Value = check(resp, "Plex").await?.json().await.context("Plex 返回格式错误")?;
+    let sections = v["MediaContainer"]["Directory"].as_array().cloned().unwrap_or_default();
+    sections
+        .iter()
+        .flat_map(|s| {
+            let key = s["key"].as_str().unwrap_or_default().to_string();
+            s["Location"].as_array().cloned().unwrap_or_default().into_iter().filter_map(move |l| {
+                let root = l["path"].as_str()?.trim_end_matches('/').to_string();
+                (path == root || path.starts_with(&format!("{}/", root))).then(|| (root.len(), key.clone()))
+            })
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, key)| key)
+        .with_context(|| format!("Plex 中没有包含 {} 的媒体库，请设置 [media_server] section", path))
+}
+
+async fn plex(c: &reqwest::Client, cfg: &MediaServerConfig, path: &str) -> Result<()> {
+    let section = match &cfg.section {
+        Some(s) => s.clone(),
+        None => plex_section(c, cfg, path).await?,
+    };
+    let url = format!("{}/library/sections/{}/refresh", cfg.url.trim_end_matches('/'), section);
+    let resp = c
+        .get(&url)
+        .query(&[("path", path), ("X-Plex-Token", cfg.token.as_str())])
+        .send()
+        .await
+        .context("请求 Plex 失败")?;
+    check(resp, "Plex").await?;
+    Ok(())
+}
+
+/// Ask the configured server to scan `dir`
+pub async fn refresh(cfg: &MediaServerConfig, dir: &Path) -> Result<()> {
+    let path = server_path(dir, cfg);
+    util::debug(format!("media server: {:?} scan {}", cfg.kind, path));
+    let c = client()?;
+    match cfg.kind {
+        MediaServerKind::Jellyfin | MediaServerKind::Emby => jellyfin(&c, cfg, &path).await,
+        MediaServerKind::Plex => plex(&c, cfg, &path).await,
+    }
+}
+
+/// One person `push_people_images` went through
+#[derive(Debug, Serialize)]
+pub struct PersonImage {
+    pub name: String,
+    /// The portrait uploaded; `None` when gfriends has none or it was left alone
+    pub avatar_url: Option<String>,
+    /// Already had a picture (no `--force`)
+    pub skipped: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The server's people, as (id, name, has a primary picture)
+async fn persons(c: &reqwest::Client, cfg: &MediaServerConfig) -> Result<Vec<(String, String, bool)>> {
+    let url = format!("{}/Persons", cfg.url.trim_end_matches('/'));
+    let resp = c
+        .get(&url)
+        .header("X-Emby-Token", &cfg.token)
+        .query(&[("EnableImages", "true"), ("EnableImageTypes", "Primary")])
+        .send()
+        .await
+        .context("请求媒体服务器失败")?;
+    let v: Value = check(resp, "媒体服务器").await?.json().await.context("媒体服务器返回格式错误")?;
+    Ok(v["Items"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|p| {
+            let id = p["Id"].as_str()?.to_string();
+            let name = p["Name"].as_str()?.trim().to_string();
+            (!name.is_empty()).then(|| (id, name, p["ImageTags"]["Primary"].is_string()))
+        })
+        .collect())
+}
+
+/// Jellyfin and Emby take an uploaded image as its base64 text
+async fn upload_primary(c: &reqwest::Client, cfg: &MediaServerConfig, id: &str, image: &[u8]) -> Result<()> {
+    let url = format!("{}/Items/{}/Images/Primary", cfg.url.trim_end_matches('/'), id);
+    let resp = c
+        .post(&url)
+        .header("X-Emby-Token", &cfg.token)
+        .header("Content-Type", "image/jpeg")
+        .body(BASE64.encode(image))
+        .send()
+        .await
+        .context("请求媒体服务器失败")?;
+    check(resp, "媒体服务器").await?;
+    Ok(())
+}
+
+/// Give the server's people their gfriends portrait, those without a picture or every one
+/// with `force`, `--jobs` at a time
+pub async fn push_people_images(cfg: &MediaServerConfig, force: bool) -> Result<Vec<PersonImage>> {
+    if cfg.kind == MediaServerKind::Plex {
+        bail!("Plex 不支持上传演员头像，仅支持 Jellyfin 和 Emby");
+    }
+    let c = client()?;
+    let people = persons(&c, cfg).await?;
+    let index = gfriends::index(false).await?;
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, (id, name, has_image)) in people.into_iter().enumerate() {
+        let avatar_url = index.avatar_url(&name);
+        if has_image && !force || avatar_url.is_none() {
+            tasks.spawn(async move { (idx, PersonImage { name, avatar_url: None, skipped: has_image, error: None }) });
+            continue;
+        }
+        let (c, cfg, permits) = (c.clone(), cfg.clone(), permits.clone());
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = async {
+                let url = avatar_url.clone().unwrap_or_default();
+                if util::dry_run_skip(format!("将上传 {} 的头像 {}", name, url)) {
+                    return Ok(());
+                }
+                let image = gfriends::avatar(&url).await?;
+                upload_primary(&c, &cfg, &id, &image).await
+            }
+            .await;
+            let error = result.err().map(|e| format!("{:#}", e));
+            (idx, PersonImage { name, avatar_url, skipped: false, error })
+        });
+    }
+    let mut done = tasks.join_all().await;
+    crate::cancel::check()?;
+    done.sort_by_key(|(idx, _)| *idx);
+    Ok(done.into_iter().map(|(_, p)| p).collect())
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use crate::mirrors;
+
+/// Upper bounds (seconds) of the upstream latency histogram
+const LATENCY_BUCKETS: [f64; 9] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchOutcome {
+    Ok,
+    Error,
+    /// 403/429/503: the site (or Cloudflare in front of it) is refusing us rather than failing
+    Blocked,
+}
+
+impl FetchOutcome {
+    pub fn of(result: &reqwest::Result<reqwest::Response>) -> FetchOutcome {
+        match result {
+            Ok(resp) => FetchOutcome::of_status(resp.status().as_u16()),
+            Err(_) => FetchOutcome::Error,
+        }
+    }
+
+    pub fn of_status(status: u16) -> FetchOutcome {
+        match status {
+            200..=299 => FetchOutcome::Ok,
+            403 | 429 | 503 => FetchOutcome::Blocked,
+            _ => FetchOutcome::Error,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SourceStats {
+    requests: u64,
+    errors: u64,
+    blocked: u64,
+    latency_sum: f64,
+    /// Cumulative counts per `LATENCY_BUCKETS` entry
+    buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+#[derive(Default)]
+struct Registry {
+    http_requests: BTreeMap<(String, u16), u64>,
+    sources: BTreeMap<String, SourceStats>,
+    jobs: BTreeMap<(String, &'static str), u64>,
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(|| Mutex::new(Registry::default()));
+
+fn with_registry(f: impl FnOnce(&mut Registry)) {
+    if let Ok(mut r) = REGISTRY.lock() {
+        f(&mut r);
+    }
+}
+
+/// Map an upstream URL to a stable source label
+pub fn source_of(url: &str) -> String {
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+        .unwrap_or_default();
+    for known in ["javdb", "sukebei", "javlibrary", "dmm", "btsow", "torrentkitty", "theporndb", "fc2", "1pondo", "caribbeancom", "heyzo", "tokyo-hot", "sokmil", "av-wiki", "xslist", "javtrailers", "subtitlecat", "telegram", "discord"] {
+        if host.contains(known) {
+            return known.to_string();
+        }
+    }
+    // JavDB mirrors carry arbitrary host names
+    if !host.is_empty() && mirrors::is_javdb_host(&host) {
+        return "javdb".to_string();
+    }
+    // So can the magnet indexes' configured mirrors and the notification endpoints
+    let mut configured = vec![("btsow", crate::sources::btsow::base()), ("torrentkitty", crate::sources::torrentkitty::base())];
+    configured.extend(crate::sources::torznab::indexers().iter().map(|t| ("torznab", t.url.clone())));
+    let notify = &crate::config::get().notify;
+    configured.extend(notify.webhooks.iter().map(|w| ("webhook", w.url.clone())));
+    configured.extend(notify.ntfy.iter().map(|n| ("ntfy", n.server.clone())));
+    configured.extend(notify.gotify.iter().map(|g| ("gotify", g.url.clone())));
+    for (source, base) in configured {
+        if !host.is_empty() && reqwest::Url::parse(&base).ok().and_then(|u| u.host_str().map(|h| h.to_lowercase())) == Some(host.clone()) {
+            return source.to_string();
+        }
+    }
+    if host.is_empty() { "unknown".to_string() } else { host }
+}
+
+pub fn record_http_request(route: &str, status: u16) {
+    with_registry(|r| *r.http_requests.entry((route.to_string(), status)).or_default() += 1);
+}
+
+pub fn record_fetch(source: &str, elapsed: Duration, outcome: FetchOutcome) {
+    let secs = elapsed.as_secs_f64();
+    with_registry(|r| {
+        let s = r.sources.entry(source.to_string()).or_default();
+        s.requests += 1;
+        s.latency_sum += secs;
+        for (i, bound) in LATENCY_BUCKETS.iter().enumerate() {
+            if secs <= *bound {
+                s.buckets[i] += 1;
+            }
+        }
+        match outcome {
+            FetchOutcome::Ok => {}
+            FetchOutcome::Error => s.errors += 1,
+            FetchOutcome::Blocked => {
+                s.errors += 1;
+                s.blocked += 1;
+            }
+        }
+    });
+}
+
+pub fn record_job(name: &str, ok: bool) {
+    let result = if ok { "ok" } else { "error" };
+    with_registry(|r| *r.jobs.entry((name.to_string(), result)).or_default() += 1);
+}
+
+fn label(v: &str) -> String {
+    v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Prometheus text exposition format (version 0.0.4)
+pub fn render() -> String {
+    let r = match REGISTRY.lock() {
+        Ok(r) => r,
+        Err(p) => p.into_inner(),
+    };
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# HELP av_build_info Build information.");
+    let _ = writeln!(out, "# TYPE av_build_info gauge");
+    let _ = writeln!(out, "av_build_info{{version=\"{}\"}} 1", env!("CARGO_PKG_VERSION"));
+
+    let _ = writeln!(out, "# HELP av_http_requests_total HTTP API requests served, by route and status.");
+    let _ = writeln!(out, "# TYPE av_http_requests_total counter");
+    for ((route, status), n) in &r.http_requests {
+        let _ = writeln!(out, "av_http_requests_total{{route=\"{}\",status=\"{}\"}} {}", label(route), status, n);
+    }
+
+    let _ = writeln!(out, "# HELP av_upstream_requests_total Requests made to upstream sources.");
+    let _ = writeln!(out, "# TYPE av_upstream_requests_total counter");
+    for (src, s) in &r.sources {
+        let _ = writeln!(out, "av_upstream_requests_total{{source=\"{}\"}} {}", label(src), s.requests);
+    }
+    let _ = writeln!(out, "# HELP av_upstream_errors_total Failed upstream requests (including blocks).");
+    let _ = writeln!(out, "# TYPE av_upstream_errors_total counter");
+    for (src, s) in &r.sources {
+        let _ = writeln!(out, "av_upstream_errors_total{{source=\"{}\"}} {}", label(src), s.errors);
+    }
+    let _ = writeln!(out, "# HELP av_upstream_blocked_total Upstream requests refused by the site (403/429/503).");
+    let _ = writeln!(out, "# TYPE av_upstream_blocked_total counter");
+    for (src, s) in &r.sources {
+        let _ = writeln!(out, "av_upstream_blocked_total{{source=\"{}\"}} {}", label(src), s.blocked);
+    }
+    let _ = writeln!(out, "# HELP av_upstream_request_duration_seconds Upstream request latency.");
+    let _ = writeln!(out, "# TYPE av_upstream_request_duration_seconds histogram");
+    for (src, s) in &r.sources {
+        let src = label(src);
+        for (bound, n) in LATENCY_BUCKETS.iter().zip(s.buckets.iter()) {
+            let _ = writeln!(out, "av_upstream_request_duration_seconds_bucket{{source=\"{}\",le=\"{}\"}} {}", src, bound, n);
+        }
+        let _ = writeln!(out, "av_upstream_request_duration_seconds_bucket{{source=\"{}\",le=\"+Inf\"}} {}", src, s.requests);
+        let _ = writeln!(out, "av_upstream_request_duration_seconds_sum{{source=\"{}\"}} {}", src, s.latency_sum);
+        let _ = writeln!(out, "av_upstream_request_duration_seconds_count{{source=\"{}\"}} {}", src, s.requests);
+    }
+
+    let _ = writeln!(out, "# HELP av_daemon_job_runs_total Daemon job runs, by job and result.");
+    let _ = writeln!(out, "# TYPE av_daemon_job_runs_total counter");
+    for ((job, result), n) in &r.jobs {
+        let _ = writeln!(out, "av_daemon_job_runs_total{{job=\"{}\",result=\"{}\"}} {}", label(job), result, n);
+    }
+    out
+}
+
+/// Standalone `/metrics` listener for `av daemon`
+pub async fn serve(bind: &str) -> anyhow::Result<()> {
+    use anyhow::Context;
+    let app = axum::Router::new().route("/metrics", axum::routing::get(|| async { metrics_response() }));
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("无法监听地址 {}", bind))?;
+    axum::serve(listener, app).await.context("metrics 服务异常退出")?;
+    Ok(())
+}
+
+pub fn metrics_response() -> axum::response::Response {
+    use axum::response::IntoResponse;
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], render()).into_response()
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::store;
+use crate::util;
+
+const DEFAULT_BASE: &str = "https://javdb.com";
+const STATE: &str = "javdb_mirror";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(8);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct MirrorState {
+    /// Base URL of the mirror that last answered
+    last_good: Option<String>,
+    at: Option<u64>,
+}
+
+fn normalize(base: &str) -> String {
+    base.trim().trim_end_matches('/').to_string()
+}
+
+/// Ordered candidates: `AV_JAVDB_BASE` first, then `[javdb] mirrors`, else javdb.com
+pub fn list() -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    if let Ok(env) = std::env::var("AV_JAVDB_BASE") {
+        if !env.trim().is_empty() {
+            out.push(normalize(&env));
+        }
+    }
+    for m in &config::get().javdb.mirrors {
+        let m = normalize(m);
+        if !m.is_empty() && !out.contains(&m) {
+            out.push(m);
+        }
+    }
+    if out.is_empty() {
+        out.push(DEFAULT_BASE.to_string());
+    }
+    out
+}
+
+/// Start from the mirror that worked last time, unless `AV_JAVDB_BASE` pins one
+static CURRENT: LazyLock<RwLock<String>> = LazyLock::new(|| {
+    let mirrors = list();
+    let pinned = std::env::var("AV_JAVDB_BASE").map(|v| !v.trim().is_empty()).unwrap_or(false);
+    let remembered = store::load::<MirrorState>(STATE)
+        .ok()
+        .and_then(|s| s.last_good)
+        .filter(|m| mirrors.contains(m));
+    let start = match remembered {
+        Some(m) if !pinned => m,
+        _ => mirrors[0].clone(),
+    };
+    RwLock::new(start)
+});
+
+pub fn current() -> String {
+    CURRENT.read().map(|c| c.clone()).unwrap_or_else(|_| DEFAULT_BASE.to_string())
+}
+
+fn host_of(url: &str) -> Option<String> {
+    reqwest::Url::parse(url).ok().and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+}
+
+/// Whether a host belongs to any configured JavDB mirror
+pub fn is_javdb_host(host: &str) -> bool {
+    list().iter().any(|m| host_of(m).as_deref() == Some(host))
+}
+
+fn switch_to(base: &str) {
+    if let Ok(mut cur) = CURRENT.write() {
+        *cur = base.to_string();
+    }
+    let state = MirrorState { last_good: Some(base.to_string()), at: Some(util::now_secs()) };
+    if let Err(e) = store::save(STATE, &state) {
+        util::debug(format!("mirrors: failed to record {}: {:#}", base, e));
+    }
+}
+
+/// Move a URL built against an older mirror onto the current one, so a switch made
+/// earlier in the command sticks for URLs computed before it.
+pub fn rebase(url: &str) -> String {
+    let current = current();
+    if url.starts_with(&current) {
+        return url.to_string();
+    }
+    match list().iter().find(|m| url.starts_with(m.as_str())) {
+        Some(old) => format!("{}{}", current, &url[old.len()..]),
+        None => url.to_string(),
+    }
+}
+
+/// Geo-blocks show up as connection failures or edge errors rather than clean 4xx
+pub fn should_failover(result: &reqwest::Result<reqwest::Response>) -> bool {
+    match result {
+        Err(e) => e.is_connect() || e.is_timeout(),
+        Ok(resp) => is_block_status(resp.status().as_u16()),
+    }
+}
+
+/// Statuses a geo-blocked or down mirror (or Cloudflare in front of it) answers with
+pub fn is_block_status(status: u16) -> bool {
+    matches!(status, 403 | 451 | 502 | 503 | 520..=530)
+}
+
+/// GET the mirror's front page; returns the latency on a successful answer
+pub async fn probe(c: &reqwest::Client, base: &str) -> Option<Duration> {
+    let started = Instant::now();
+    let resp = c.get(format!("{}/", base)).timeout(PROBE_TIMEOUT).send().await.ok()?;
+    resp.status().is_success().then(|| started.elapsed())
+}
+
+/// `url` failed on the current mirror: probe the others in order, switch to the first
+/// healthy one and return `url` rewritten onto it.
+pub async fn failover(c: &reqwest::Client, url: &str) -> Option<String> {
+    let mirrors = list();
+    if mirrors.len() < 2 {
+        return None;
+    }
+    let failed = mirrors.iter().find(|m| url.starts_with(m.as_str()))?.clone();
+    let path = &url[failed.len()..];
+    for candidate in mirrors.iter().filter(|m| **m != failed) {
+        util::debug(format!("mirrors: {} failed, probing {}", failed, candidate));
+        if probe(c, candidate).await.is_some() {
+            eprintln!("[WARN] JavDB 镜像 {} 不可用，已切换到 {}", failed, candidate);
+            switch_to(candidate);
+            return Some(format!("{}{}", candidate, path));
+        }
+    }
+    None
+}
@@ -0,0 +1,65 @@
+//! Kodi/Jellyfin movie NFO files, so media servers show the scraped metadata for a
+//! local video without scraping it again themselves.
+
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::rating;
+use crate::types::AvDetail;
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn tag(out: &mut String, name: &str, value: &str) {
+    if !value.trim().is_empty() {
+        let _ = writeln!(out, "  <{name}>{}</{name}>", escape(value.trim()));
+    }
+}
+
+/// The `<movie>` document for `d`
+pub fn render(d: &AvDetail) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<movie>\n");
+    tag(&mut out, "title", &d.title);
+    tag(&mut out, "originaltitle", &d.title);
+    tag(&mut out, "sorttitle", &d.code);
+    tag(&mut out, "plot", d.plot.as_deref().unwrap_or(""));
+    if let Some(m) = d.duration_minutes {
+        tag(&mut out, "runtime", &m.to_string());
+    }
+    if let Some(date) = &d.release_date {
+        tag(&mut out, "premiered", date);
+        tag(&mut out, "year", date.get(..4).unwrap_or(""));
+    }
+    // Kodi rates out of 10
+    if let Some(r) = d.rating {
+        tag(&mut out, "rating", &format!("{:.1}", r * 10.0 / rating::SCALE));
+    }
+    tag(&mut out, "studio", d.studio.as_deref().unwrap_or(""));
+    tag(&mut out, "director", d.director.as_deref().unwrap_or(""));
+    if let Some(series) = d.series.as_deref().filter(|s| !s.trim().is_empty()) {
+        let _ = writeln!(out, "  <set>\n    <name>{}</name>\n  </set>", escape(series.trim()));
+    }
+    for g in &d.genres {
+        tag(&mut out, "genre", g);
+    }
+    tag(&mut out, "tag", d.label.as_deref().unwrap_or(""));
+    for a in &d.actor_names {
+        let _ = writeln!(out, "  <actor>\n    <name>{}</name>\n  </actor>", escape(a.trim()));
+    }
+    if let Some(cover) = &d.cover_url {
+        let _ = writeln!(out, "  <thumb aspect=\"poster\">{}</thumb>", escape(cover));
+    }
+    let _ = writeln!(out, "  <uniqueid type=\"code\" default=\"true\">{}</uniqueid>", escape(&d.code));
+    out.push_str("</movie>\n");
+    out
+}
+
+/// Write `<CODE>.nfo` into `dir`
+pub fn write(d: &AvDetail, dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+    let path = dir.join(format!("{}.nfo", d.code));
+    std::fs::write(&path, render(d)).with_context(|| format!("写入失败: {}", path.display()))?;
+    Ok(path)
+}
@@ -0,0 +1,90 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::Event;
+use crate::config::DiscordConfig;
+use crate::http;
+use crate::scraper;
+use crate::types::{AvDetail, MagnetInfo};
+use crate::util;
+
+/// Discord rejects embed fields longer than this
+const FIELD_LIMIT: usize = 1024;
+const COLOR_NEW: u32 = 0xE0_40_40;
+const COLOR_DONE: u32 = 0x40_C0_60;
+
+fn field(name: &str, value: &str, inline: bool) -> Value {
+    json!({ "name": name, "value": util::truncate_chars(value, FIELD_LIMIT), "inline": inline })
+}
+
+fn release_embed(d: &AvDetail) -> Value {
+    let mut fields = Vec::new();
+    if !d.actor_names.is_empty() {
+        fields.push(field("演员", &d.actor_names.join(", "), true));
+    }
+    if let Some(date) = &d.release_date {
+        fields.push(field("发行", date, true));
+    }
+    if let Some(studio) = &d.studio {
+        fields.push(field("片商", studio, true));
+    }
+    if let Some(m) = d.magnet_infos.iter().min_by(|a, b| MagnetInfo::ranking(a, b)) {
+        let mut spec = Vec::new();
+        if let Some(size) = &m.size { spec.push(size.clone()); }
+        if let Some(res) = &m.resolution { spec.push(res.clone()); }
+        if let Some(s) = m.seeders { spec.push(format!("S:{}", s)); }
+        let value = format!("{}\n```{}```", spec.join(" | "), util::truncate_chars(&m.url, FIELD_LIMIT - 64));
+        fields.push(field("最佳磁力", value.trim_start(), false));
+    } else if let Some(m) = d.magnets.first() {
+        fields.push(field("磁力", &format!("```{}```", util::truncate_chars(m, FIELD_LIMIT - 8)), false));
+    }
+    let mut embed = json!({
+        "title": util::truncate_chars(&format!("{} {}", d.code, d.title.trim()), 256),
+        "url": scraper::javdb_search_url(&d.code),
+        "color": COLOR_NEW,
+        "fields": fields,
+    });
+    if let Some(cover) = &d.cover_url {
+        embed["image"] = json!({ "url": cover });
+    }
+    embed
+}
+
+fn payload(event: &Event) -> Value {
+    let embed = match event {
+        Event::NewRelease { detail, .. } => release_embed(detail),
+        Event::DownloadComplete { code, magnet } => json!({
+            "title": format!("下载完成 {}", code),
+            "color": COLOR_DONE,
+            "description": format!("```{}```", util::truncate_chars(magnet, 2000)),
+        }),
+        Event::Birthday { name, age, url, .. } => json!({
+            "title": format!("🎂 {} 今天 {} 岁生日", name, age),
+            "url": url,
+            "color": COLOR_NEW,
+        }),
+        Event::Test { message } => json!({ "title": message }),
+    };
+    json!({ "username": "av", "embeds": [embed] })
+}
+
+pub async fn send(dc: &DiscordConfig, event: &Event) -> Result<()> {
+    let client = http::with_proxy(reqwest::Client::builder())
+        .timeout(Duration::from_secs(15))
+        .build()
+        .context("client build")?;
+    let resp = client
+        .post(&dc.webhook_url)
+        .json(&payload(event))
+        .send()
+        .await
+        .context("Discord 请求失败")?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        bail!("Discord HTTP {}: {}", status, util::truncate_chars(&body, 200));
+    }
+    util::debug(format!("discord delivered ({})", status));
+    Ok(())
+}
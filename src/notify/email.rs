@@ -0,0 +1,356 @@
+//! Email digests. New releases aren't mailed one by one: they are collected in
+//! `email_digest.json` and sent as one HTML message (covers, titles, magnet links) once a
+//! day or a week has passed since the last one, checked whenever a release comes in and
+//! by `av notify digest`. SMTP is spoken here directly: SMTPS, STARTTLS or plain, with
+//! AUTH PLAIN only over TLS. Every read and write has `IO_TIMEOUT`, so a stalled server
+//! fails the send instead of hanging the daemon.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls;
+
+use super::Event;
+use crate::config::{DigestPeriod, EmailConfig, SmtpSecurity};
+use crate::scraper;
+use crate::store;
+use crate::types::AvDetail;
+use crate::util;
+
+const DIGEST: &str = "email_digest";
+/// Longest wait for the server to accept a write or answer a command
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+/// A digest lists at most this many releases; the rest wait for the next one
+const MAX_HITS: usize = 200;
+
+/// Serializes the load-modify-save of releases coming in together
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Hit {
+    /// Where it came from: `subscribe:actor:NAME`, a daemon job's name, ...
+    source: String,
+    code: String,
+    title: String,
+    #[serde(default)]
+    actors: Vec<String>,
+    #[serde(default)]
+    release_date: Option<String>,
+    #[serde(default)]
+    cover_url: Option<String>,
+    #[serde(default)]
+    magnet: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Digest {
+    /// Unix time the current period started (the last digest, or the first release queued)
+    #[serde(default)]
+    since: Option<u64>,
+    pending: Vec<Hit>,
+}
+
+fn period_secs(every: DigestPeriod) -> u64 {
+    match every {
+        DigestPeriod::Daily => 24 * 3600,
+        DigestPeriod::Weekly => 7 * 24 * 3600,
+    }
+}
+
+/// Add a release to the next digest, then send it if it is due
+pub async fn queue(cfg: &EmailConfig, source: &str, d: &AvDetail) -> Result<()> {
+    {
+        let _guard = LOCK.lock().unwrap();
+        let mut digest: Digest = store::load(DIGEST)?;
+        digest.since.get_or_insert_with(util::now_secs);
+        if !digest.pending.iter().any(|h| h.code == d.code) {
+            digest.pending.push(Hit {
+                source: source.to_string(),
+                code: d.code.clone(),
+                // Titles often repeat the code, which the digest shows anyway
+                title: d.title.trim().strip_prefix(d.code.as_str()).unwrap_or(&d.title).trim().to_string(),
+                actors: d.actor_names.clone(),
+                release_date: d.release_date.clone(),
+                cover_url: d.cover_url.clone(),
+                magnet: util::best_magnet(d),
+            });
+        }
+        store::save(DIGEST, &digest)?;
+    }
+    // `av notify test CODE` should show what a digest looks like now, not in a day
+    send_digest(cfg, source == "test").await.map(|_| ())
+}
+
+/// Send the collected releases when the period is over (or `force`); `Ok(false)` when
+/// there was nothing to send yet
+pub async fn send_digest(cfg: &EmailConfig, force: bool) -> Result<bool> {
+    let hits = {
+        let _guard = LOCK.lock().unwrap();
+        let digest: Digest = store::load(DIGEST)?;
+        let due = digest.since.is_none_or(|s| util::now_secs() >= s + period_secs(cfg.every));
+        if digest.pending.is_empty() || !(due || force) {
+            return Ok(false);
+        }
+        digest.pending
+    };
+    let sending: Vec<Hit> = hits.iter().take(MAX_HITS).cloned().collect();
+    let subject = format!("av 订阅新作品 {} 部", sending.len());
+    send(cfg, &subject, &text_body(&sending), &html_body(&sending)).await?;
+    let _guard = LOCK.lock().unwrap();
+    let mut digest: Digest = store::load(DIGEST)?;
+    // Releases queued while sending stay for the next digest
+    digest.pending.retain(|h| !sending.iter().any(|s| s.code == h.code));
+    digest.since = Some(util::now_secs());
+    store::save(DIGEST, &digest)?;
+    util::debug(format!("email digest of {} release(s) sent", sending.len()));
+    Ok(true)
+}
+
+/// `av notify digest [--now]`
+pub async fn digest(now: bool) -> Result<()> {
+    let Some(cfg) = &crate::config::get().notify.email else {
+        bail!("未配置邮件通知（见配置文件 [notify.email]）");
+    };
+    let pending = store::load::<Digest>(DIGEST)?.pending.len();
+    if util::is_dry_run() {
+        util::dry_run_skip(format!("将发送邮件摘要（{} 部新作品）到 {}", pending, cfg.to.join(", ")));
+        return Ok(());
+    }
+    if send_digest(cfg, now).await? {
+        util::note(format!("已发送邮件摘要：{} 部新作品", pending.min(MAX_HITS)));
+    } else if pending == 0 {
+        util::note("没有待发送的新作品");
+    } else {
+        util::note(format!("{} 部新作品等待下一次摘要（--now 立即发送）", pending));
+    }
+    Ok(())
+}
+
+/// `av notify test` without a code: a one-line message right away
+pub async fn send_test(cfg: &EmailConfig, event: &Event) -> Result<()> {
+    let Event::Test { message } = event else { return Ok(()) };
+    let html = format!("<p>{}</p>", util::xml_escape(message));
+    send(cfg, "av 测试消息", message, &html).await
+}
+
+/// `subscribe:actor:NAME` as the digest shows it
+fn source_label(source: &str) -> String {
+    match source.strip_prefix("subscribe:").and_then(|s| s.split_once(':')) {
+        Some(("actor", name)) => format!("演员 {}", name),
+        Some(("studio", name)) => format!("片商 {}", name),
+        Some(("series", name)) => format!("系列 {}", name),
+        _ => source.to_string(),
+    }
+}
+
+fn text_body(hits: &[Hit]) -> String {
+    let mut out = String::new();
+    for h in hits {
+        out.push_str(&format!("{} {}\n", h.code, h.title));
+        if !h.actors.is_empty() {
+            out.push_str(&format!("演员: {}\n", h.actors.join(", ")));
+        }
+        if let Some(m) = &h.magnet {
+            out.push_str(&format!("{}\n", m));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn html_body(hits: &[Hit]) -> String {
+    let esc = util::xml_escape;
+    let mut rows = String::new();
+    for h in hits {
+        let cover = h
+            .cover_url
+            .as_deref()
+            .map(|c| format!(r#"<img src="{}" width="240" style="display:block;border-radius:4px">"#, esc(c)))
+            .unwrap_or_default();
+        let mut lines = vec![format!(
+            r#"<a href="{}" style="font-size:16px;font-weight:bold;color:#222;text-decoration:none">{}</a> {}"#,
+            esc(&scraper::javdb_search_url(&h.code)),
+            esc(&h.code),
+            esc(&h.title)
+        )];
+        if !h.actors.is_empty() {
+            lines.push(format!("演员: {}", esc(&h.actors.join(", "))));
+        }
+        if let Some(date) = &h.release_date {
+            lines.push(format!("发行: {}", esc(date)));
+        }
+        lines.push(format!(r#"<span style="color:#888">{}</span>"#, esc(&source_label(&h.source))));
+        if let Some(m) = &h.magnet {
+            lines.push(format!(r#"<a href="{}">磁力链接</a>"#, esc(m)));
+        }
+        rows.push_str(&format!(
+            r#"<tr><td style="padding:8px;vertical-align:top">{}</td><td style="padding:8px;vertical-align:top;line-height:1.6">{}</td></tr>"#,
+            cover,
+            lines.join("<br>")
+        ));
+    }
+    format!(
+        r#"<!DOCTYPE html><html><body style="font-family:sans-serif"><h2>订阅新作品 {} 部</h2><table style="border-collapse:collapse">{}</table></body></html>"#,
+        hits.len(),
+        rows
+    )
+}
+
+/// The bare address of `Name <addr>` (or `addr`)
+fn address(s: &str) -> &str {
+    match (s.rfind('<'), s.rfind('>')) {
+        (Some(a), Some(b)) if a < b => s[a + 1..b].trim(),
+        _ => s.trim(),
+    }
+}
+
+/// A header value in RFC 2047 encoded words when it isn't ASCII
+fn header_text(s: &str) -> String {
+    if s.is_ascii() {
+        s.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", BASE64.encode(s.as_bytes()))
+    }
+}
+
+/// Base64 in lines of 76, as MIME wants
+fn base64_lines(s: &str) -> String {
+    let encoded = BASE64.encode(s.as_bytes());
+    let mut out = String::new();
+    for chunk in encoded.as_bytes().chunks(76) {
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn message(cfg: &EmailConfig, subject: &str, text: &str, html: &str) -> String {
+    let boundary = format!("av-{:016x}", fastrand::u64(..));
+    let domain = address(&cfg.from).rsplit('@').next().unwrap_or("localhost");
+    let headers = [
+        format!("From: {}", cfg.from.trim()),
+        format!("To: {}", cfg.to.join(", ")),
+        format!("Subject: {}", header_text(subject)),
+        format!("Date: {}", chrono::Local::now().to_rfc2822()),
+        format!("Message-ID: <{}.{}@{}>", util::now_secs(), fastrand::u32(..), domain),
+        "MIME-Version: 1.0".to_string(),
+        format!("Content-Type: multipart/alternative; boundary=\"{}\"", boundary),
+    ];
+    let part = |kind: &str, body: &str| {
+        format!(
+            "--{}\r\nContent-Type: text/{}; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+            boundary,
+            kind,
+            base64_lines(body)
+        )
+    };
+    format!("{}\r\n\r\n{}{}--{}--\r\n", headers.join("\r\n"), part("plain", text), part("html", html), boundary)
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+struct Smtp {
+    conn: BufReader<Box<dyn Io>>,
+}
+
+impl Smtp {
+    /// The server's reply code, its (last) line of text with it
+    async fn reply(&mut self) -> Result<(u16, String)> {
+        loop {
+            let mut line = String::new();
+            let read = tokio::time::timeout(IO_TIMEOUT, self.conn.read_line(&mut line)).await.context("等待 SMTP 响应超时")?;
+            if read.context("读取 SMTP 响应失败")? == 0 {
+                bail!("SMTP 服务器关闭了连接");
+            }
+            let line = line.trim_end();
+            let code = line.get(..3).and_then(|c| c.parse::<u16>().ok()).with_context(|| format!("无效的 SMTP 响应: {}", line))?;
+            // `250-...` lines continue, `250 ...` ends the reply
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, line.get(4..).unwrap_or("").to_string()));
+            }
+        }
+    }
+
+    /// Send `line` and expect a reply in `ok`
+    async fn command(&mut self, line: &str, ok: &[u16], what: &str) -> Result<()> {
+        let conn = self.conn.get_mut();
+        let write = async {
+            conn.write_all(format!("{}\r\n", line).as_bytes()).await?;
+            conn.flush().await
+        };
+        tokio::time::timeout(IO_TIMEOUT, write).await.context("写入 SMTP 连接超时")?.context("写入 SMTP 连接失败")?;
+        match self.reply().await? {
+            (code, _) if ok.contains(&code) => Ok(()),
+            (code, text) => bail!("SMTP {}失败: {} {}", what, code, text),
+        }
+    }
+}
+
+async fn tls(host: &str, stream: Box<dyn Io>) -> Result<Box<dyn Io>> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = rustls::pki_types::ServerName::try_from(host.to_string()).with_context(|| format!("无效的主机名: {}", host))?;
+    let handshake = tokio_rustls::TlsConnector::from(Arc::new(config)).connect(name, stream);
+    let stream = tokio::time::timeout(IO_TIMEOUT, handshake).await.context("SMTP TLS 握手超时")?.context("SMTP TLS 握手失败")?;
+    Ok(Box::new(stream))
+}
+
+async fn send(cfg: &EmailConfig, subject: &str, text: &str, html: &str) -> Result<()> {
+    if cfg.to.is_empty() {
+        bail!("[notify.email] 没有设置收件人 to");
+    }
+    let host = cfg.smtp_host.trim();
+    let port = cfg.smtp_port.unwrap_or(match cfg.security {
+        SmtpSecurity::Tls => 465,
+        SmtpSecurity::Starttls => 587,
+        SmtpSecurity::None => 25,
+    });
+    let tcp = tokio::time::timeout(Duration::from_secs(20), TcpStream::connect((host, port)))
+        .await
+        .with_context(|| format!("连接 SMTP 服务器 {}:{} 超时", host, port))?
+        .with_context(|| format!("无法连接 SMTP 服务器 {}:{}", host, port))?;
+    let mut stream: Box<dyn Io> = Box::new(tcp);
+    if cfg.security == SmtpSecurity::Tls {
+        stream = tls(host, stream).await?;
+    }
+    let mut smtp = Smtp { conn: BufReader::new(stream) };
+    let (code, greeting) = smtp.reply().await?;
+    if code != 220 {
+        bail!("SMTP 服务器拒绝连接: {} {}", code, greeting);
+    }
+    smtp.command("EHLO av", &[250], "EHLO ").await?;
+    if cfg.security == SmtpSecurity::Starttls {
+        smtp.command("STARTTLS", &[220], "STARTTLS ").await?;
+        let plain = smtp.conn.into_inner();
+        smtp = Smtp { conn: BufReader::new(tls(host, plain).await?) };
+        smtp.command("EHLO av", &[250], "EHLO ").await?;
+    }
+    if let Some(user) = cfg.username.as_deref().filter(|u| !u.is_empty()) {
+        if cfg.security == SmtpSecurity::None {
+            bail!("[notify.email] security = \"none\" 时不会在明文连接上发送密码；改用 starttls 或 tls，或去掉 username");
+        }
+        let token = BASE64.encode(format!("\0{}\0{}", user, cfg.password.as_deref().unwrap_or("")));
+        smtp.command(&format!("AUTH PLAIN {}", token), &[235], "登录").await?;
+    }
+    smtp.command(&format!("MAIL FROM:<{}>", address(&cfg.from)), &[250], "发件人 ").await?;
+    for to in &cfg.to {
+        smtp.command(&format!("RCPT TO:<{}>", address(to)), &[250, 251], &format!("收件人 {} ", to)).await?;
+    }
+    smtp.command("DATA", &[354], "DATA ").await?;
+    // Lines starting with a dot get another one (RFC 5321 transparency)
+    let body = message(cfg, subject, text, html).replace("\r\n.", "\r\n..");
+    smtp.command(&format!("{}\r\n.", body.trim_end_matches("\r\n")), &[250], "发送").await?;
+    let _ = smtp.command("QUIT", &[221], "QUIT ").await;
+    util::debug(format!("email sent to {}", cfg.to.join(", ")));
+    Ok(())
+}
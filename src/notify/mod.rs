@@ -0,0 +1,167 @@
+use serde::Serialize;
+use serde_json::json;
+
+use crate::config;
+use crate::hooks;
+use crate::types::AvDetail;
+use crate::util;
+
+pub mod discord;
+pub mod email;
+pub mod push;
+pub mod telegram;
+pub mod webhook;
+
+/// Something worth telling the user about. Serialized as the `data` of a notification payload.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Event {
+    /// A watched listing produced a code that was not seen before
+    NewRelease { source: String, detail: Box<AvDetail> },
+    /// A dispatched download finished
+    DownloadComplete { code: String, magnet: String },
+    /// An actress in the profile cache has her birthday today (`av actors birthdays --notify`)
+    Birthday { name: String, birth_date: String, age: i32, url: Option<String> },
+    /// Sent by `av notify test`
+    Test { message: String },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::NewRelease { .. } => "new_release",
+            Event::DownloadComplete { .. } => "download_complete",
+            Event::Birthday { .. } => "birthday",
+            Event::Test { .. } => "test",
+        }
+    }
+}
+
+/// Number of notification endpoints present in the config
+pub fn configured_count() -> usize {
+    let n = &config::get().notify;
+    n.webhooks.len()
+        + n.discord.len()
+        + usize::from(n.telegram.is_some())
+        + usize::from(n.ntfy.is_some())
+        + usize::from(n.gotify.is_some())
+        + usize::from(n.email.is_some())
+}
+
+/// Deliver an event to every configured backend that subscribes to it and return how many
+/// deliveries succeeded. Failures are reported but never abort the caller's command.
+/// Matching hook scripts run first; they don't count as deliveries.
+pub async fn emit(event: &Event) -> usize {
+    hooks::fire_event(event).await;
+    let cfg = &config::get().notify;
+    if util::is_dry_run() {
+        let targets = subscribed(event);
+        for t in &targets {
+            util::dry_run_skip(format!("将推送 {} 事件到 {}", event.name(), t));
+        }
+        return targets.len();
+    }
+    let mut delivered = 0;
+    for hook in &cfg.webhooks {
+        if !wants(&hook.events, event.name()) {
+            continue;
+        }
+        match webhook::send(hook, event).await {
+            Ok(()) => delivered += 1,
+            Err(e) => eprintln!("[WARN] webhook {} 推送失败: {:#}", hook.url, e),
+        }
+    }
+    if let Some(tg) = &cfg.telegram {
+        if wants(&tg.events, event.name()) {
+            match telegram::send(tg, event).await {
+                Ok(()) => delivered += 1,
+                Err(e) => eprintln!("[WARN] Telegram 推送失败: {:#}", e),
+            }
+        }
+    }
+    for dc in &cfg.discord {
+        if !wants(&dc.events, event.name()) {
+            continue;
+        }
+        match discord::send(dc, event).await {
+            Ok(()) => delivered += 1,
+            Err(e) => eprintln!("[WARN] Discord 推送失败: {:#}", e),
+        }
+    }
+    if let Some(ntfy) = &cfg.ntfy {
+        if wants(&ntfy.events, event.name()) {
+            match push::send_ntfy(ntfy, event).await {
+                Ok(()) => delivered += 1,
+                Err(e) => eprintln!("[WARN] ntfy 推送失败: {:#}", e),
+            }
+        }
+    }
+    if let Some(gotify) = &cfg.gotify {
+        if wants(&gotify.events, event.name()) {
+            match push::send_gotify(gotify, event).await {
+                Ok(()) => delivered += 1,
+                Err(e) => eprintln!("[WARN] Gotify 推送失败: {:#}", e),
+            }
+        }
+    }
+    if let Some(email) = cfg.email.as_ref().filter(|e| wants(&e.events, event.name())) {
+        // Releases wait for the digest; everything else but the test has no place in it
+        let queued = match event {
+            Event::NewRelease { source, detail } => email::queue(email, source, detail).await,
+            Event::Test { .. } => email::send_test(email, event).await,
+            _ => Ok(()),
+        };
+        match queued {
+            Ok(()) if email_wants(event) => delivered += 1,
+            Ok(()) => {}
+            Err(e) => eprintln!("[WARN] 邮件推送失败: {:#}", e),
+        }
+    }
+    delivered
+}
+
+/// The email digest only carries new releases (and the test message)
+fn email_wants(event: &Event) -> bool {
+    matches!(event, Event::NewRelease { .. } | Event::Test { .. })
+}
+
+/// Backends that would receive `event`, for `--dry-run`
+fn subscribed(event: &Event) -> Vec<String> {
+    let cfg = &config::get().notify;
+    let name = event.name();
+    let mut out: Vec<String> = cfg
+        .webhooks
+        .iter()
+        .filter(|h| wants(&h.events, name))
+        .map(|h| format!("webhook {}", h.url))
+        .collect();
+    if cfg.telegram.as_ref().is_some_and(|t| wants(&t.events, name)) {
+        out.push("Telegram".to_string());
+    }
+    out.extend(cfg.discord.iter().filter(|d| wants(&d.events, name)).map(|_| "Discord".to_string()));
+    if cfg.ntfy.as_ref().is_some_and(|n| wants(&n.events, name)) {
+        out.push("ntfy".to_string());
+    }
+    if cfg.gotify.as_ref().is_some_and(|g| wants(&g.events, name)) {
+        out.push("Gotify".to_string());
+    }
+    if cfg.email.as_ref().is_some_and(|e| wants(&e.events, name)) && email_wants(event) {
+        out.push("邮件摘要".to_string());
+    }
+    out
+}
+
+/// Empty filter or the test event always matches
+fn wants(filter: &[String], name: &str) -> bool {
+    name == "test" || filter.is_empty() || filter.iter().any(|e| e == name)
+}
+
+/// The envelope webhooks and hooks both receive: `event` names what `data` is
+pub fn webhook_payload<T: Serialize>(event: &str, data: &T) -> serde_json::Value {
+    json!({
+        "event": event,
+        "timestamp": util::now_secs(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "data": data,
+    })
+}
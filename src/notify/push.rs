@@ -0,0 +1,129 @@
+//! Lightweight push backends: ntfy topics and Gotify servers.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::json;
+use std::time::Duration;
+
+use super::Event;
+use crate::config::{GotifyConfig, NtfyConfig};
+use crate::http;
+use crate::scraper;
+use crate::util;
+
+struct Message {
+    title: String,
+    body: String,
+    click: Option<String>,
+    image: Option<String>,
+}
+
+fn message(event: &Event) -> Message {
+    match event {
+        Event::NewRelease { detail, .. } => {
+            let mut lines = Vec::new();
+            if !detail.actor_names.is_empty() {
+                lines.push(format!("演员: {}", detail.actor_names.join(", ")));
+            }
+            if let Some(date) = &detail.release_date {
+                lines.push(format!("发行: {}", date));
+            }
+            if let Some(m) = util::best_magnet(detail) {
+                lines.push(m);
+            }
+            Message {
+                title: util::truncate_chars(&format!("{} {}", detail.code, detail.title.trim()), 200),
+                body: lines.join("\n"),
+                click: Some(scraper::javdb_search_url(&detail.code)),
+                image: detail.cover_url.clone(),
+            }
+        }
+        Event::DownloadComplete { code, magnet } => Message {
+            title: format!("下载完成 {}", code),
+            body: magnet.clone(),
+            click: None,
+            image: None,
+        },
+        Event::Birthday { name, birth_date, age, url } => Message {
+            title: format!("🎂 {} 生日", name),
+            body: format!("今天 {} 岁（{}）", age, birth_date),
+            click: url.clone(),
+            image: None,
+        },
+        Event::Test { message } => Message { title: "av".to_string(), body: message.clone(), click: None, image: None },
+    }
+}
+
+fn client() -> Result<reqwest::Client> {
+    http::with_proxy(reqwest::Client::builder())
+        .timeout(Duration::from_secs(15))
+        .build()
+        .context("client build")
+}
+
+/// HTTP headers only carry ASCII reliably; ntfy decodes RFC 2047 encoded words
+fn header_safe(s: &str) -> String {
+    if s.is_ascii() {
+        s.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", BASE64.encode(s.as_bytes()))
+    }
+}
+
+pub async fn send_ntfy(cfg: &NtfyConfig, event: &Event) -> Result<()> {
+    let msg = message(event);
+    let url = format!("{}/{}", cfg.server.trim_end_matches('/'), cfg.topic);
+    let mut req = client()?
+        .post(&url)
+        .header("Title", header_safe(&msg.title))
+        .header("Tags", event.name());
+    if let Some(p) = cfg.priority {
+        req = req.header("Priority", p.to_string());
+    }
+    if let Some(token) = &cfg.token {
+        req = req.bearer_auth(token);
+    }
+    if let Some(click) = &msg.click {
+        req = req.header("Click", click);
+    }
+    if let Some(image) = &msg.image {
+        req = req.header("Attach", image);
+    }
+    let resp = req.body(msg.body).send().await.context("ntfy 请求失败")?;
+    if !resp.status().is_success() {
+        bail!("ntfy HTTP {}", resp.status());
+    }
+    util::debug(format!("ntfy delivered to {}", url));
+    Ok(())
+}
+
+pub async fn send_gotify(cfg: &GotifyConfig, event: &Event) -> Result<()> {
+    let msg = message(event);
+    let url = format!("{}/message", cfg.url.trim_end_matches('/'));
+    let mut extras = json!({ "client::display": { "contentType": "text/plain" } });
+    if let Some(click) = &msg.click {
+        extras["client::notification"] = json!({ "click": { "url": click } });
+    }
+    if let Some(image) = &msg.image {
+        extras["client::notification"]["bigImageUrl"] = json!(image);
+    }
+    let body = json!({
+        "title": msg.title,
+        "message": msg.body,
+        "priority": cfg.priority.unwrap_or(5),
+        "extras": extras,
+    });
+    let resp = client()?
+        .post(&url)
+        .header("X-Gotify-Key", &cfg.token)
+        .json(&body)
+        .send()
+        .await
+        .context("Gotify 请求失败")?;
+    if !resp.status().is_success() {
+        bail!("Gotify HTTP {}", resp.status());
+    }
+    util::debug(format!("gotify delivered to {}", url));
+    Ok(())
+}
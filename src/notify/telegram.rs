@@ -0,0 +1,324 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::Event;
+use crate::config::{self, TelegramConfig};
+use crate::http;
+use crate::scraper;
+use crate::store;
+use crate::types::AvDetail;
+use crate::util;
+
+/// Photo captions are capped by the Bot API
+const CAPTION_LIMIT: usize = 1024;
+
+const BOT_STORE: &str = "telegram_bot";
+
+/// Where `run_bot` left off, so a restart doesn't handle the same updates again
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BotState {
+    /// The bot's numeric id, the token's part before `:`; update ids are per bot
+    bot: String,
+    /// The next update to ask for
+    offset: i64,
+}
+
+fn bot_id(tg: &TelegramConfig) -> String {
+    tg.bot_token.split(':').next().unwrap_or_default().to_string()
+}
+
+fn saved_offset(tg: &TelegramConfig) -> i64 {
+    match store::load::<BotState>(BOT_STORE) {
+        Ok(state) if state.bot == bot_id(tg) => state.offset,
+        Ok(_) => 0,
+        Err(e) => {
+            util::debug(format!("telegram: {:#}", e));
+            0
+        }
+    }
+}
+
+fn api_url(tg: &TelegramConfig, method: &str) -> String {
+    format!("https://api.telegram.org/bot{}/{}", tg.bot_token, method)
+}
+
+fn client(timeout: Duration) -> Result<reqwest::Client> {
+    http::with_proxy(reqwest::Client::builder()).timeout(timeout).build().context("client build")
+}
+
+async fn call(c: &reqwest::Client, tg: &TelegramConfig, method: &str, body: &Value) -> Result<Value> {
+    let v: Value = c
+        .post(api_url(tg, method))
+        .json(body)
+        .send()
+        .await
+        .with_context(|| format!("Telegram {} 请求失败", method))?
+        .json()
+        .await
+        .with_context(|| format!("Telegram {} 响应解析失败", method))?;
+    if v.get("ok").and_then(|b| b.as_bool()) != Some(true) {
+        let desc = v.get("description").and_then(|d| d.as_str()).unwrap_or("unknown error");
+        bail!("Telegram {}: {}", method, desc);
+    }
+    Ok(v.get("result").cloned().unwrap_or(Value::Null))
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Short `magnet:?xt=urn:btih:<hash>` form; full links with trackers easily blow the caption limit
+fn short_magnet(url: &str) -> String {
+    util::magnet_infohash(url)
+        .map(|h| format!("magnet:?xt=urn:btih:{}", h))
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Telegram applies `CAPTION_LIMIT` to the text left after entity parsing, so the budget is
+/// counted in plain characters and only unescaped text is ever cut: the title is shortened to
+/// fit, and any later line that doesn't fit whole is dropped rather than split mid-tag.
+fn detail_caption(d: &AvDetail) -> String {
+    let code = d.code.trim();
+    let room = CAPTION_LIMIT.saturating_sub(code.chars().count() + 1);
+    let title = util::truncate_chars(d.title.trim(), room);
+    let mut caption = format!("<b>{}</b> {}", html_escape(code), html_escape(&title));
+    let mut used = code.chars().count() + 1 + title.chars().count();
+    let mut lines: Vec<(String, &str, &str)> = Vec::new();
+    if !d.actor_names.is_empty() {
+        lines.push((format!("演员: {}", d.actor_names.join(", ")), "", ""));
+    }
+    if let Some(date) = &d.release_date {
+        lines.push((format!("发行: {}", date), "", ""));
+    }
+    if let Some(studio) = &d.studio {
+        lines.push((format!("片商: {}", studio), "", ""));
+    }
+    if let Some(m) = util::best_magnet(d) {
+        lines.push((short_magnet(&m), "<code>", "</code>"));
+    }
+    for (plain, open, close) in lines {
+        let len = plain.chars().count() + 1;
+        if used + len > CAPTION_LIMIT {
+            continue;
+        }
+        used += len;
+        caption.push('\n');
+        caption.push_str(open);
+        caption.push_str(&html_escape(&plain));
+        caption.push_str(close);
+    }
+    caption
+}
+
+/// Inline buttons: a download trigger (handled by `av notify telegram-bot`) when remote
+/// commands are enabled, plus a link to the search page.
+fn detail_keyboard(tg: &TelegramConfig, d: &AvDetail) -> Value {
+    let mut row = Vec::new();
+    if tg.allow_commands && util::best_magnet(d).is_some() {
+        row.push(json!({ "text": "⬇ 下载", "callback_data": format!("get:{}", d.code) }));
+    }
+    row.push(json!({ "text": "JavDB", "url": scraper::javdb_search_url(&d.code) }));
+    json!({ "inline_keyboard": [row] })
+}
+
+pub async fn send(tg: &TelegramConfig, event: &Event) -> Result<()> {
+    let c = client(Duration::from_secs(20))?;
+    match event {
+        Event::NewRelease { detail, .. } => {
+            let caption = detail_caption(detail);
+            let keyboard = detail_keyboard(tg, detail);
+            if let Some(cover) = &detail.cover_url {
+                let photo = json!({
+                    "chat_id": tg.chat_id,
+                    "photo": cover,
+                    "caption": caption,
+                    "parse_mode": "HTML",
+                    "reply_markup": keyboard,
+                });
+                match call(&c, tg, "sendPhoto", &photo).await {
+                    Ok(_) => return Ok(()),
+                    // Telegram fetches the image itself and may be blocked by the CDN; fall back to text
+                    Err(e) => util::debug(format!("telegram sendPhoto failed, falling back: {:#}", e)),
+                }
+            }
+            let msg = json!({ "chat_id": tg.chat_id, "text": caption, "parse_mode": "HTML", "reply_markup": keyboard });
+            call(&c, tg, "sendMessage", &msg).await?;
+        }
+        Event::DownloadComplete { code, magnet } => {
+            let text = format!("✅ 下载完成 <b>{}</b>\n<code>{}</code>", html_escape(code), html_escape(&short_magnet(magnet)));
+            call(&c, tg, "sendMessage", &json!({ "chat_id": tg.chat_id, "text": text, "parse_mode": "HTML" })).await?;
+        }
+        Event::Birthday { name, birth_date, age, .. } => {
+            let text = format!("🎂 <b>{}</b> 今天 {} 岁生日（{}）", html_escape(name), age, html_escape(birth_date));
+            call(&c, tg, "sendMessage", &json!({ "chat_id": tg.chat_id, "text": text, "parse_mode": "HTML" })).await?;
+        }
+        Event::Test { message } => {
+            call(&c, tg, "sendMessage", &json!({ "chat_id": tg.chat_id, "text": message })).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn reply(c: &reqwest::Client, tg: &TelegramConfig, text: &str) {
+    if let Err(e) = call(c, tg, "sendMessage", &json!({ "chat_id": tg.chat_id, "text": text })).await {
+        eprintln!("[WARN] Telegram 回复失败: {:#}", e);
+    }
+}
+
+/// Fetch the code and hand its best magnet to the local downloader
+async fn remote_get(code: &str) -> Result<String> {
+    let detail = scraper::fetch_detail(code).await?;
+    if util::best_magnet(&detail).is_none() {
+        bail!("{} 没有可用的磁力链接", detail.code);
+    }
+    let code = detail.code.clone();
+    // aria2c runs in the foreground until done; keep the poll loop responsive
+    tokio::spawn(async move {
+        if let Err(e) = util::download_detail(&detail, None, None).await {
+            eprintln!("[WARN] 下载失败: {:#}", e);
+        }
+    });
+    Ok(format!("已开始下载 {}", code))
+}
+
+/// Long-poll the Bot API and execute `/get CODE` (and download button presses) from the
+/// configured chat. Messages from any other chat are ignored.
+pub async fn run_bot() -> Result<()> {
+    let tg = config::get()
+        .notify
+        .telegram
+        .clone()
+        .context("未配置 Telegram（见配置文件 [notify.telegram]）")?;
+    if !tg.allow_commands {
+        bail!("请在 [notify.telegram] 中设置 allow_commands = true 以启用远程命令");
+    }
+    let poll_secs = 50;
+    let c = client(Duration::from_secs(poll_secs + 10))?;
+    let mut offset = saved_offset(&tg);
+    println!("Telegram 机器人已启动，等待命令（/get CODE, /detail CODE）...");
+    loop {
+        let body = json!({ "offset": offset, "timeout": poll_secs, "allowed_updates": ["message", "callback_query"] });
+        let updates = match call(&c, &tg, "getUpdates", &body).await {
+            Ok(v) => v.as_array().cloned().unwrap_or_default(),
+            Err(e) => {
+                eprintln!("[WARN] {:#}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        for up in updates {
+            // Saved before handling, so a command that brings the bot down isn't run again
+            if let Some(id) = up.get("update_id").and_then(|v| v.as_i64()) {
+                offset = offset.max(id + 1);
+                if let Err(e) = store::save(BOT_STORE, &BotState { bot: bot_id(&tg), offset }) {
+                    eprintln!("[WARN] {:#}", e);
+                }
+            }
+            if let Some(cb) = up.get("callback_query") {
+                handle_callback(&c, &tg, cb).await;
+            } else if let Some(msg) = up.get("message") {
+                handle_message(&c, &tg, msg).await;
+            }
+        }
+    }
+}
+
+fn from_configured_chat(tg: &TelegramConfig, chat: Option<&Value>) -> bool {
+    let id = chat.and_then(|c| c.get("id")).map(|v| v.to_string()).unwrap_or_default();
+    id == tg.chat_id.trim()
+}
+
+async fn handle_message(c: &reqwest::Client, tg: &TelegramConfig, msg: &Value) {
+    if !from_configured_chat(tg, msg.get("chat")) {
+        util::debug("telegram: ignoring message from unknown chat");
+        return;
+    }
+    let text = msg.get("text").and_then(|t| t.as_str()).unwrap_or("").trim();
+    let mut parts = text.split_whitespace();
+    // Commands may be addressed as /get@botname in groups
+    let cmd = parts.next().unwrap_or("").split('@').next().unwrap_or("");
+    let arg = parts.next().unwrap_or("");
+    match (cmd, arg) {
+        ("/get", code) if !code.is_empty() => match remote_get(code).await {
+            Ok(m) => reply(c, tg, &m).await,
+            Err(e) => reply(c, tg, &format!("失败: {:#}", e)).await,
+        },
+        ("/detail", code) if !code.is_empty() => match scraper::fetch_detail(code).await {
+            Ok(d) => {
+                let event = Event::NewRelease { source: "telegram".to_string(), detail: Box::new(d) };
+                if let Err(e) = send(tg, &event).await {
+                    reply(c, tg, &format!("失败: {:#}", e)).await;
+                }
+            }
+            Err(e) => reply(c, tg, &format!("失败: {:#}", e)).await,
+        },
+        ("/start", _) | ("/help", _) => reply(c, tg, "命令: /get CODE 下载, /detail CODE 查看详情").await,
+        _ => {}
+    }
+}
+
+async fn handle_callback(c: &reqwest::Client, tg: &TelegramConfig, cb: &Value) {
+    let chat = cb.get("message").and_then(|m| m.get("chat"));
+    let id = cb.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let data = cb.get("data").and_then(|v| v.as_str()).unwrap_or("");
+    let text = if !from_configured_chat(tg, chat) {
+        "未授权".to_string()
+    } else if let Some(code) = data.strip_prefix("get:") {
+        match remote_get(code).await {
+            Ok(m) => m,
+            Err(e) => format!("失败: {:#}", e),
+        }
+    } else {
+        String::new()
+    };
+    let _ = call(c, tg, "answerCallbackQuery", &json!({ "callback_query_id": id, "text": util::truncate_chars(&text, 200) })).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(caption: &str) -> String {
+        let mut out = String::new();
+        let mut in_tag = false;
+        for ch in caption.chars() {
+            match ch {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                _ if !in_tag => out.push(ch),
+                _ => {}
+            }
+        }
+        out.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+    }
+
+    #[test]
+    fn caption_budget() {
+        let mut d = crate::magnet::detail_of("ABP-123", Vec::new());
+        d.title = "A&B <x> ".repeat(40);
+        d.studio = Some("S&M".into());
+        let c = detail_caption(&d);
+        assert!(c.starts_with("<b>ABP-123</b> A&amp;B &lt;x&gt;"));
+        assert!(c.ends_with("片商: S&amp;M"));
+
+        // an escaped title far over the limit is cut before escaping, and later lines go whole
+        d.title = "&".repeat(2000);
+        d.actor_names = vec!["<甲>".into()];
+        let c = detail_caption(&d);
+        assert_eq!(plain(&c).chars().count(), CAPTION_LIMIT);
+        assert!(c.ends_with("&amp;…"));
+        assert!(!c.contains("演员"));
+        assert_eq!(c.matches("<b>").count(), c.matches("</b>").count());
+
+        // a line that doesn't fit is dropped, a shorter one after it still goes in
+        d.title = "t".repeat(CAPTION_LIMIT - 30);
+        d.studio = None;
+        d.actor_names = vec!["x".repeat(30)];
+        d.release_date = Some("2024-01-01".into());
+        let c = detail_caption(&d);
+        assert!(!c.contains("演员") && c.ends_with("\n发行: 2024-01-01"));
+        assert!(plain(&c).chars().count() <= CAPTION_LIMIT);
+    }
+}
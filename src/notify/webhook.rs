@@ -0,0 +1,53 @@
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+use super::{webhook_payload, Event};
+use crate::config::WebhookConfig;
+use crate::http;
+use crate::util;
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub async fn send(hook: &WebhookConfig, event: &Event) -> Result<()> {
+    let body = serde_json::to_vec(&webhook_payload(event.name(), event))?;
+    let client = http::with_proxy(reqwest::Client::builder())
+        .timeout(Duration::from_secs(15))
+        .build()
+        .context("client build")?;
+    let attempts = hook.retries + 1;
+    let mut last_err = None;
+    for attempt in 1..=attempts {
+        let mut req = client
+            .post(&hook.url)
+            .header("content-type", "application/json")
+            .header("user-agent", concat!("av/", env!("CARGO_PKG_VERSION")))
+            .header("x-av-event", event.name());
+        if let Some(secret) = &hook.secret {
+            req = req.header("x-av-signature", sign(secret, &body));
+        }
+        match req.body(body.clone()).send().await {
+            Ok(resp) if resp.status().is_success() => {
+                util::debug(format!("webhook {} delivered ({})", hook.url, resp.status()));
+                return Ok(());
+            }
+            // 4xx other than rate limiting will not get better by retrying
+            Ok(resp) if resp.status().is_client_error() && resp.status().as_u16() != 429 => {
+                bail!("HTTP {}", resp.status());
+            }
+            Ok(resp) => last_err = Some(anyhow::anyhow!("HTTP {}", resp.status())),
+            Err(e) => last_err = Some(e.into()),
+        }
+        if attempt < attempts {
+            let backoff = Duration::from_secs(1 << (attempt - 1).min(5));
+            util::debug(format!("webhook {} attempt {} failed, retrying in {:?}", hook.url, attempt, backoff));
+            tokio::time::sleep(backoff).await;
+        }
+    }
+    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("webhook delivery failed")))
+}
@@ -0,0 +1,341 @@
+//! Moving downloaded videos into the library under their canonical names (see
+//! `scan::file_name`), one folder per code. With a link mode the videos stay where they
+//! were downloaded, for the torrent client to go on seeding, and the library gets hard or
+//! symbolic links under the new names.
+//!
+//! Every run is written to an undo log (`organize_undo.json`) as it goes, so a title
+//! matched to the wrong code can be put back with `av organize --undo <run>`.
+
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::code;
+use crate::config::{self, HookConfig, LinkMode};
+use crate::hooks;
+use crate::scan;
+use crate::store;
+use crate::util;
+
+const UNDO_LOG: &str = "organize_undo";
+/// Oldest runs beyond this are forgotten
+const MAX_RUNS: usize = 500;
+
+/// Serializes the load-modify-save of runs finishing together
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Moved {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// `rename`, or copy and delete when `to` is on another filesystem
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to).with_context(|| format!("复制失败: {} → {}", from.display(), to.display()))?;
+    std::fs::remove_file(from).with_context(|| format!("删除原文件失败: {}", from.display()))
+}
+
+/// Link `to` to `from`; a symlink points at `from`'s absolute path, so it still resolves
+/// from the library folder
+fn link_file(from: &Path, to: &Path, mode: LinkMode) -> Result<()> {
+    match mode {
+        LinkMode::Hard => std::fs::hard_link(from, to).with_context(|| {
+            format!("创建硬链接失败: {} → {}（不在同一文件系统时可改用 --link sym）", from.display(), to.display())
+        }),
+        LinkMode::Sym => {
+            let target = std::fs::canonicalize(from).with_context(|| format!("无法读取 {}", from.display()))?;
+            #[cfg(unix)]
+            let made = std::os::unix::fs::symlink(&target, to);
+            #[cfg(windows)]
+            let made = std::os::windows::fs::symlink_file(&target, to);
+            made.with_context(|| format!("创建符号链接失败: {} → {}", to.display(), target.display()))
+        }
+    }
+}
+
+/// What would be moved where: `code`'s videos under `src` (all parts of a split release),
+/// or the only video there when none is named after the code
+pub fn plan(code: &str, src: &Path, dest: &Path) -> Result<Vec<Moved>> {
+    let code = code::normalize(code);
+    let result = scan::scan(src)?;
+    let files: Vec<(PathBuf, String)> = match result.groups.into_iter().find(|g| code::same_release(&g.code, &code)) {
+        Some(g) => g.files.into_iter().map(|f| (f.path, f.name)).collect(),
+        None if result.unmatched.len() == 1 => {
+            let path = result.unmatched.into_iter().next().unwrap_or_default();
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("mp4").to_lowercase();
+            vec![(path, scan::file_name(&code, None, false, &ext))]
+        }
+        None => bail!("{} 下没有找到 {} 的视频文件", src.display(), code),
+    };
+    Ok(files.into_iter().map(|(from, name)| Moved { from, to: dest.join(name) }).collect())
+}
+
+/// How one file was placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpKind {
+    Move,
+    Hard,
+    Sym,
+    /// A file the pipeline wrote into the run's new folder (NFO, artwork); it has no `from`
+    Write,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Op {
+    pub kind: OpKind,
+    #[serde(default, skip_serializing_if = "no_path")]
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+fn no_path(p: &Path) -> bool {
+    p.as_os_str().is_empty()
+}
+
+/// One `place`, as the undo log keeps it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub id: String,
+    /// Local time, `YYYY-MM-DD HH:MM:SS`
+    pub at: String,
+    pub code: String,
+    /// The library folder, when `place` created it (undo removes it again once empty)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_dir: Option<PathBuf>,
+    /// In the order they were done
+    pub ops: Vec<Op>,
+}
+
+fn load_runs() -> Result<Vec<Run>> {
+    store::load(UNDO_LOG)
+}
+
+/// Write `run` over its earlier state in the log, appending it the first time
+fn save_run(run: &Run) -> Result<()> {
+    let _guard = LOCK.lock().unwrap();
+    let mut runs = load_runs()?;
+    match runs.iter_mut().find(|r| r.id == run.id) {
+        Some(r) => *r = run.clone(),
+        None => runs.push(run.clone()),
+    }
+    let excess = runs.len().saturating_sub(MAX_RUNS);
+    runs.drain(..excess);
+    store::save(UNDO_LOG, &runs)
+}
+
+fn new_run_id() -> Result<String> {
+    let runs = load_runs()?;
+    let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut id = stamp.clone();
+    let mut n = 1;
+    while runs.iter().any(|r| r.id == id) {
+        n += 1;
+        id = format!("{}-{}", stamp, n);
+    }
+    Ok(id)
+}
+
+/// What `place` did: the files, and the undo log entry they are recorded under
+#[derive(Debug, Clone, Default)]
+pub struct Placed {
+    pub moved: Vec<Moved>,
+    pub run: Option<String>,
+}
+
+/// Carry out `plan`, moving the files or, with `link`, linking them; an existing file at a
+/// target is never overwritten. Each file is logged for undo once it is in place, so a run
+/// that fails halfway can still be reverted.
+/// `hooks.validate` is asked first, as action `organize`, with the planned moves.
+pub async fn place(code: &str, src: &Path, dest: &Path, link: Option<LinkMode>) -> Result<Placed> {
+    place_with(&config::get().hooks.validate, code, src, dest, link).await
+}
+
+async fn place_with(guards: &[HookConfig], code: &str, src: &Path, dest: &Path, link: Option<LinkMode>) -> Result<Placed> {
+    let moves = plan(code, src, dest)?;
+    if let Some(m) = moves.iter().find(|m| m.to.exists() && m.to != m.from) {
+        bail!("目标文件已存在: {}", m.to.display());
+    }
+    let details = serde_json::json!({ "code": code::normalize(code), "moves": moves, "link": link });
+    hooks::validate_with(guards, "organize", &details).await?;
+    let created_dir = (!dest.exists()).then(|| dest.to_path_buf());
+    std::fs::create_dir_all(dest).with_context(|| format!("创建目录失败: {}", dest.display()))?;
+    let mut run = Run {
+        id: new_run_id()?,
+        at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        code: code::normalize(code),
+        created_dir,
+        ops: Vec::new(),
+    };
+    for m in &moves {
+        if m.from == m.to {
+            continue;
+        }
+        let kind = match link {
+            Some(LinkMode::Hard) => OpKind::Hard,
+            Some(LinkMode::Sym) => OpKind::Sym,
+            None => OpKind::Move,
+        };
+        let done = match link {
+            Some(mode) => link_file(&m.from, &m.to, mode),
+            None => move_file(&m.from, &m.to),
+        };
+        if let Err(e) = done {
+            if !run.ops.is_empty() {
+                eprintln!("已完成的 {} 项可用 av organize --undo {} 撤销", run.ops.len(), run.id);
+            }
+            return Err(e);
+        }
+        run.ops.push(Op { kind, from: m.from.clone(), to: m.to.clone() });
+        save_run(&run)?;
+    }
+    let run = (!run.ops.is_empty()).then_some(run.id);
+    Ok(Placed { moved: moves, run })
+}
+
+/// Add `files`, written next to run `id`'s videos after it, to the run, so undoing it
+/// removes them too. Only done when the run created the folder: anything in one that was
+/// already there may have been the user's own.
+pub fn record_written(id: &str, files: &[PathBuf]) -> Result<()> {
+    let Some(mut run) = load_runs()?.into_iter().find(|r| r.id == id) else { return Ok(()) };
+    if run.created_dir.is_none() {
+        return Ok(());
+    }
+    run.ops.extend(files.iter().map(|f| Op { kind: OpKind::Write, from: PathBuf::new(), to: f.clone() }));
+    save_run(&run)
+}
+
+/// `av organize`: the runs that can be undone, newest first
+pub fn list(json: bool) -> Result<()> {
+    let mut runs = load_runs()?;
+    runs.reverse();
+    if json {
+        util::print_output(&runs, true);
+        return Ok(());
+    }
+    if runs.is_empty() {
+        println!("{}", "还没有可撤销的整理记录".yellow());
+        return Ok(());
+    }
+    for r in &runs {
+        let dest = r.ops.first().and_then(|o| o.to.parent()).map(|p| p.display().to_string()).unwrap_or_default();
+        let videos = r.ops.iter().filter(|o| o.kind != OpKind::Write).count();
+        println!("{}  {}  {}  {} 个视频 → {}", r.id.bold(), r.at, util::pad(&r.code, 12), videos, dest);
+    }
+    Ok(())
+}
+
+/// Put one file back: a moved file returns to where it was, a link is removed (the
+/// original it points at was never touched)
+fn revert(op: &Op) -> Result<()> {
+    match op.kind {
+        OpKind::Move => {
+            if op.from.exists() {
+                bail!("原位置已有文件: {}", op.from.display());
+            }
+            if let Some(parent) = op.from.parent() {
+                std::fs::create_dir_all(parent).with_context(|| format!("创建目录失败: {}", parent.display()))?;
+            }
+            move_file(&op.to, &op.from)
+        }
+        OpKind::Hard | OpKind::Sym => {
+            let meta = std::fs::symlink_metadata(&op.to).with_context(|| format!("链接已不存在: {}", op.to.display()))?;
+            if op.kind == OpKind::Sym && !meta.file_type().is_symlink() {
+                bail!("{} 已不是符号链接，未删除", op.to.display());
+            }
+            // A hard link is only removed while the original still holds the data
+            if op.kind == OpKind::Hard && !op.from.exists() {
+                bail!("原文件 {} 已不存在，未删除硬链接 {}", op.from.display(), op.to.display());
+            }
+            std::fs::remove_file(&op.to).with_context(|| format!("删除失败: {}", op.to.display()))
+        }
+        OpKind::Write => match std::fs::remove_file(&op.to) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).with_context(|| format!("删除失败: {}", op.to.display())),
+            _ => Ok(()),
+        },
+    }
+}
+
+fn describe_revert(op: &Op) -> String {
+    match op.kind {
+        OpKind::Move => format!("移回 {} → {}", op.to.display(), op.from.display()),
+        OpKind::Hard | OpKind::Sym => format!("删除链接 {}", op.to.display()),
+        OpKind::Write => format!("删除 {}", op.to.display()),
+    }
+}
+
+/// `av organize --undo RUN`: revert the run's operations, last first. Ones that can't be
+/// reverted are reported and stay in the log for another try. `hooks.validate` is asked
+/// first, as action `organize_undo`.
+pub async fn undo(id: &str) -> Result<()> {
+    let mut run = load_runs()?
+        .into_iter()
+        .find(|r| r.id == id)
+        .with_context(|| format!("没有整理记录 {}，用 av organize 查看全部", id))?;
+    if util::dry_run_skip(format!("将撤销整理 {}（{} 的 {} 个文件）", run.id, run.code, run.ops.len())) {
+        for op in run.ops.iter().rev() {
+            eprintln!("  {}", describe_revert(op));
+        }
+        return Ok(());
+    }
+    hooks::validate("organize_undo", &serde_json::json!({ "run": run.id, "code": run.code, "ops": run.ops })).await?;
+    let mut left = Vec::new();
+    for op in run.ops.iter().rev() {
+        match revert(op) {
+            Ok(()) => util::note(format!("已{}", describe_revert(op))),
+            Err(e) => {
+                eprintln!("[WARN] {:#}", e);
+                left.push(op.clone());
+            }
+        }
+    }
+    left.reverse();
+    let failed = left.len();
+    let reverted = std::mem::replace(&mut run.ops, left);
+    if failed > 0 {
+        save_run(&run)?;
+        bail!("{} 个文件未能撤销，处理后可再次运行 av organize --undo {}", failed, run.id);
+    }
+    // The folder goes too when nothing else has been put in it since (extrafanart/ first)
+    if let Some(dir) = &run.created_dir {
+        let subdirs: Vec<&Path> = reverted.iter().filter_map(|o| o.to.parent()).filter(|p| p != dir && p.starts_with(dir)).collect();
+        for sub in subdirs {
+            let _ = std::fs::remove_dir(sub);
+        }
+        if std::fs::remove_dir(dir).is_ok() {
+            util::note(format!("已删除空目录 {}", dir.display()));
+        }
+    }
+    let _guard = LOCK.lock().unwrap();
+    let mut runs = load_runs()?;
+    runs.retain(|r| r.id != run.id);
+    store::save(UNDO_LOG, &runs)?;
+    util::note(format!("已撤销整理 {}", run.id));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn vetoed_move_leaves_the_video() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let video = src.path().join("ABP-123.mp4");
+        std::fs::write(&video, b"video").unwrap();
+        let veto = HookConfig { command: "false".into(), args: Vec::new(), timeout_secs: 5 };
+
+        let placed = place_with(&[veto], "ABP-123", src.path(), &dest.path().join("ABP-123"), None).await;
+        assert!(placed.is_err());
+        assert!(video.exists());
+        assert!(!dest.path().join("ABP-123").exists());
+    }
+}
@@ -0,0 +1,178 @@
+//! `--pick ROWS [--then ACTION]`: act on rows of a listing in the same invocation instead
+//! of re-typing the codes or magnets. Rows are the `#` numbers the listing prints; on
+//! `search`/`list`/`top` a row is a release, on `install` a magnet.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+
+use crate::batch;
+use crate::platform;
+use crate::scraper;
+use crate::types::{AvDetail, AvItem, MagnetInfo};
+use crate::util;
+
+/// 1-based row numbers in the order given, duplicates dropped
+#[derive(Debug, Clone)]
+pub struct Rows(Vec<usize>);
+
+/// `3`, `1,3`, `2-5` or a mix (`1,4-6`)
+pub fn parse_rows(spec: &str) -> Result<Rows, String> {
+    let mut rows = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let number = |s: &str| match s.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("无效的行号: {}（从 1 开始，如 1,3 或 2-5）", s.trim())),
+        };
+        let range = match part.split_once('-') {
+            Some((a, b)) => number(a)?..=number(b)?,
+            None => number(part)?..=number(part)?,
+        };
+        if range.is_empty() {
+            return Err(format!("无效的范围: {}", part));
+        }
+        for n in range {
+            if !rows.contains(&n) {
+                rows.push(n);
+            }
+        }
+    }
+    if rows.is_empty() {
+        return Err("至少指定一行".to_string());
+    }
+    Ok(Rows(rows))
+}
+
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct PickArgs {
+    /// 直接处理列表中的这些行（search/list/top 为番号，install 为磁力），如 1,3 或 2-4
+    #[arg(long, value_name = "ROWS", value_parser = parse_rows)]
+    pub pick: Option<Rows>,
+    /// 对 --pick 选中的行执行的操作
+    #[arg(long, value_enum, requires = "pick", default_value_t)]
+    pub then: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Action {
+    /// 显示详情（install 中为磁力信息）
+    #[default]
+    Detail,
+    /// 复制最佳磁力（install 中为选中的磁力）到剪贴板
+    Copy,
+    /// 下载
+    Download,
+}
+
+fn select<'a, T>(all: &'a [T], rows: &Rows, what: &str) -> Result<Vec<&'a T>> {
+    rows.0
+        .iter()
+        .map(|&n| all.get(n - 1).with_context(|| format!("第 {} 行不存在（共 {} {}）", n, all.len(), what)))
+        .collect()
+}
+
+/// Act on the picked releases of a `search`/`list`/`top` listing
+pub async fn items(all: &[AvItem], rows: &Rows, action: Action, json: bool) -> Result<()> {
+    let picked = select(all, rows, "条结果")?;
+    if action == Action::Download {
+        // Downloads go one by one as a resumable job; nothing is fetched up front
+        let entries = picked.iter().map(|i| batch::Entry::new(&i.code, None)).collect();
+        return batch::download(&mut batch::Job::start(batch::Kind::Download, entries, false)?).await;
+    }
+    let mut details = Vec::new();
+    for item in picked {
+        details.push(scraper::fetch_detail(&item.code).await?);
+    }
+    if action == Action::Copy {
+        let mut magnets = Vec::new();
+        for d in &details {
+            match util::ranked_magnets(d).into_iter().next() {
+                Some(m) => magnets.push(m),
+                None => eprintln!("[WARN] 未找到 {} 的磁力链接", d.code),
+            }
+        }
+        return copy(&magnets).await;
+    }
+    if json {
+        util::print_output(&details, true);
+        return Ok(());
+    }
+    for (i, d) in details.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        util::print_detail_human(d);
+    }
+    Ok(())
+}
+
+/// Magnets in the order `av install` numbers them: the detailed ones best ranked first,
+/// or the bare links when there are none
+fn install_order(d: &AvDetail) -> Vec<(String, Option<&MagnetInfo>)> {
+    if d.magnet_infos.is_empty() {
+        return d.magnets.iter().map(|m| (m.clone(), None)).collect();
+    }
+    let mut infos: Vec<_> = d.magnet_infos.iter().collect();
+    infos.sort_by(|a, b| MagnetInfo::ranking(a, b));
+    infos.into_iter().map(|m| (m.url.clone(), Some(m))).collect()
+}
+
+/// Act on the picked magnets of `av install CODE`
+pub async fn magnets(d: &AvDetail, rows: &Rows, action: Action, json: bool) -> Result<()> {
+    let all = install_order(d);
+    if all.is_empty() {
+        bail!("未找到 {} 的磁力链接", d.code);
+    }
+    let picked = select(&all, rows, "个磁力")?;
+    match action {
+        Action::Detail if json => {
+            #[derive(serde::Serialize, Debug)]
+            #[serde(untagged)]
+            enum Picked<'a> {
+                Info(&'a MagnetInfo),
+                Bare { url: &'a str },
+            }
+            let out: Vec<Picked> = picked
+                .iter()
+                .map(|(url, info)| match info {
+                    Some(m) => Picked::Info(m),
+                    None => Picked::Bare { url },
+                })
+                .collect();
+            util::print_output(&out, true);
+        }
+        Action::Detail => {
+            for (url, info) in picked {
+                println!("{}", url.cyan());
+                let Some(m) = info else { continue };
+                let mut parts = Vec::new();
+                if let Some(name) = &m.name { parts.push(name.clone()); }
+                if let Some(size) = &m.size { parts.push(size.clone()); }
+                if let Some(res) = &m.resolution { parts.push(res.clone()); }
+                if let Some(s) = m.seeders { parts.push(format!("S:{}", s)); }
+                if let Some(q) = m.quality_score { parts.push(format!("Q:{:.1}", q)); }
+                if !parts.is_empty() {
+                    println!("   {}", parts.join(" | "));
+                }
+            }
+        }
+        Action::Copy => copy(&picked.iter().map(|(url, _)| url.clone()).collect::<Vec<_>>()).await?,
+        Action::Download => {
+            let entries = picked.iter().map(|(url, _)| batch::Entry::new(&d.code, Some(url.clone()))).collect();
+            batch::download(&mut batch::Job::start(batch::Kind::Download, entries, false)?).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Several magnets go to the clipboard one per line
+async fn copy(magnets: &[String]) -> Result<()> {
+    if magnets.is_empty() {
+        bail!("没有可复制的磁力链接");
+    }
+    platform::copy_to_clipboard(&magnets.join("\n")).await?;
+    util::note(format!("{} {} 个磁力", "已复制到剪贴板:".green().bold(), magnets.len()));
+    for m in magnets {
+        util::note(m.cyan().to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! `av get CODE --full`: download with aria2c, then run the `[pipeline]` steps (rename,
+//! NFO, artwork, media server rescan of the title's folder) so the title ends up ready to play in the library.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::artwork;
+use crate::config::{self, LinkMode, PipelineStep};
+use crate::http;
+use crate::media_server;
+use crate::nfo;
+use crate::organize::{self, Moved};
+use crate::scraper;
+use crate::types::AvDetail;
+use crate::util;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub code: String,
+    /// Where the videos and their metadata ended up
+    pub dir: PathBuf,
+    pub moved: Vec<Moved>,
+    /// How `moved` were placed, when linked rather than moved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkMode>,
+    /// The undo log entry of the rename (`av organize --undo`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undo: Option<String>,
+    pub nfo: Option<PathBuf>,
+    pub artwork: Vec<PathBuf>,
+    pub refreshed: bool,
+    /// Steps that failed without stopping the rest
+    pub warnings: Vec<String>,
+}
+
+fn step_name(s: PipelineStep) -> &'static str {
+    match s {
+        PipelineStep::Rename => "rename",
+        PipelineStep::Nfo => "nfo",
+        PipelineStep::Artwork => "artwork",
+        PipelineStep::Refresh => "refresh",
+    }
+}
+
+async fn refresh_library(url: &str, method: &str) -> Result<()> {
+    let method = reqwest::Method::from_bytes(method.trim().to_uppercase().as_bytes()).context("refresh_method 无效")?;
+    let c = http::with_proxy(reqwest::Client::builder()).timeout(Duration::from_secs(20)).build().context("client build")?;
+    let resp = c.request(method, url).send().await.context("请求媒体库刷新失败")?;
+    if !resp.status().is_success() {
+        bail!("媒体库刷新返回 HTTP {}", resp.status().as_u16());
+    }
+    Ok(())
+}
+
+/// Files a step wrote go into the rename's undo log entry, so undoing it removes them too
+fn written(report: &Report, files: &[PathBuf]) {
+    if let Some(run) = &report.undo {
+        if let Err(e) = organize::record_written(run, files) {
+            eprintln!("[WARN] 写入整理记录失败: {:#}", e);
+        }
+    }
+}
+
+/// Post-process `d`'s finished download in `download`, moving it to `dest` (or linking it
+/// there, with `link`) when the steps include `rename`. A failed rename stops the run; any
+/// other step only warns.
+pub async fn run(d: &AvDetail, download: &Path, dest: &Path, link: Option<LinkMode>) -> Result<Report> {
+    let cfg = &config::get().pipeline;
+    let renames = cfg.steps.contains(&PipelineStep::Rename);
+    let link = link.filter(|_| renames);
+    let mut report = Report { code: d.code.clone(), dir: if renames { dest } else { download }.to_path_buf(), link, ..Report::default() };
+    for &step in &cfg.steps {
+        util::debug(format!("pipeline: {} {}", d.code, step_name(step)));
+        let res = match step {
+            PipelineStep::Rename => {
+                let placed = organize::place(&d.code, download, dest, link).await?;
+                report.moved = placed.moved;
+                report.undo = placed.run;
+                Ok(())
+            }
+            PipelineStep::Nfo => nfo::write(d, &report.dir).map(|p| {
+                written(&report, std::slice::from_ref(&p));
+                report.nfo = Some(p);
+            }),
+            PipelineStep::Artwork => artwork::save_cover_art(d, &report.dir).await.map(|files| {
+                written(&report, &files);
+                report.artwork = files;
+            }),
+            PipelineStep::Refresh => match (&config::get().media_server, &cfg.refresh_url) {
+                (Some(server), _) => media_server::refresh(server, &report.dir).await.map(|()| report.refreshed = true),
+                (None, Some(url)) => refresh_library(url, &cfg.refresh_method).await.map(|()| report.refreshed = true),
+                (None, None) => {
+                    util::debug("pipeline: no media server or refresh_url, skipping refresh");
+                    Ok(())
+                }
+            },
+        };
+        if let Err(e) = res {
+            let msg = format!("{}: {:#}", step_name(step), e);
+            eprintln!("[WARN] {} {}", d.code, msg);
+            report.warnings.push(msg);
+        }
+    }
+    Ok(report)
+}
+
+/// `av get CODE --full [--link hard|sym]`
+pub async fn full(code: &str, link: Option<LinkMode>, json: bool) -> Result<()> {
+    let d = scraper::fetch_detail(code).await?;
+    let cfg = &config::get().pipeline;
+    let link = link.or(cfg.link);
+    let download = cfg.download_dir.clone().unwrap_or_else(|| PathBuf::from(".")).join(&d.code);
+    let dest = cfg.library_dir.as_ref().map(|l| l.join(&d.code)).unwrap_or_else(|| download.clone());
+    let steps: Vec<&str> = cfg.steps.iter().map(|s| step_name(*s)).collect();
+    if util::dry_run_skip(format!("将下载 {} 到 {}，完成后执行 [{}]，整理到 {}", d.code, download.display(), steps.join(", "), dest.display())) {
+        return Ok(());
+    }
+    util::download_detail(&d, None, Some(&download)).await?;
+    let report = run(&d, &download, &dest, link).await?;
+
+    if json {
+        util::print_output(&report, true);
+        return Ok(());
+    }
+    println!("{} {}", format!("{} 已整理到", report.code).green().bold(), report.dir.display());
+    let placed = match report.link {
+        Some(LinkMode::Hard) => "硬链接",
+        Some(LinkMode::Sym) => "符号链接",
+        None => "视频",
+    };
+    for m in &report.moved {
+        println!("  {} {} → {}", placed, m.from.display(), m.to.display());
+    }
+    if let Some(p) = &report.nfo {
+        println!("  NFO  {}", p.display());
+    }
+    for p in &report.artwork {
+        println!("  图片 {}", p.display());
+    }
+    if report.refreshed {
+        println!("  {}", "已通知媒体库刷新".green());
+    }
+    if let Some(run) = &report.undo {
+        println!("  {}", format!("整理有误时可用 av organize --undo {} 撤销", run).dimmed());
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! OS-specific glue: opening URIs, the clipboard, locating tools that aren't on `PATH`,
+//! and the Windows console setup (UTF-8 output, ANSI colors).
+
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use which::which;
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn SetConsoleOutputCP(code_page: u32) -> i32;
+    fn SetConsoleCP(code_page: u32) -> i32;
+}
+
+/// Once at startup. On Windows: switch the console to UTF-8 so Chinese output isn't
+/// mangled by the OEM code page when PowerShell or cmd captures it, enable ANSI colors on
+/// older consoles, and remove the binary `av update` had to leave behind.
+pub fn init() {
+    #[cfg(windows)]
+    {
+        const CP_UTF8: u32 = 65001;
+        // SAFETY: plain Win32 calls without pointers; failure (no console) is harmless
+        unsafe {
+            SetConsoleOutputCP(CP_UTF8);
+            SetConsoleCP(CP_UTF8);
+        }
+        let _ = colored::control::set_virtual_terminal(true);
+        if let Ok(exe) = std::env::current_exe() {
+            let mut old = exe.into_os_string();
+            old.push(".old");
+            let _ = std::fs::remove_file(old);
+        }
+    }
+}
+
+/// The command that hands `uri` to the system's default handler
+pub fn open_command(uri: &str) -> tokio::process::Command {
+    let mut c = if cfg!(windows) {
+        // `cmd /C start` would split a magnet's query at every `&`; the URL protocol
+        // handler gets the link untouched
+        let mut c = tokio::process::Command::new("rundll32");
+        c.arg("url.dll,FileProtocolHandler");
+        c
+    } else if cfg!(target_os = "macos") {
+        tokio::process::Command::new("open")
+    } else {
+        tokio::process::Command::new("xdg-open")
+    };
+    c.arg(uri);
+    c
+}
+
+/// Clipboard program and arguments, with the bytes it expects on stdin
+fn clipboard_command(text: &str) -> Result<(PathBuf, Vec<&'static str>, Vec<u8>)> {
+    if cfg!(windows) {
+        // clip.exe reads the console code page unless the input is UTF-16 with a BOM
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        return Ok((which("clip").context("未找到 clip.exe")?, vec![], bytes));
+    }
+    if cfg!(target_os = "macos") {
+        return Ok((which("pbcopy").context("未找到 pbcopy")?, vec![], text.as_bytes().to_vec()));
+    }
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let candidates: [(&str, &[&'static str], bool); 3] =
+        [("wl-copy", &[], wayland), ("xclip", &["-selection", "clipboard"], true), ("xsel", &["--clipboard", "--input"], true)];
+    for (program, args, usable) in candidates {
+        if let (true, Ok(path)) = (usable, which(program)) {
+            return Ok((path, args.to_vec(), text.as_bytes().to_vec()));
+        }
+    }
+    bail!("未找到剪贴板工具，请安装 wl-clipboard、xclip 或 xsel")
+}
+
+pub async fn copy_to_clipboard(text: &str) -> Result<()> {
+    let (program, args, input) = clipboard_command(text)?;
+    let mut child = tokio::process::Command::new(&program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("启动 {} 失败", program.display()))?;
+    let mut stdin = child.stdin.take().context("无法写入剪贴板")?;
+    stdin.write_all(&input).await.context("写入剪贴板失败")?;
+    drop(stdin);
+    let status = child.wait().await.context("写入剪贴板失败")?;
+    if !status.success() {
+        bail!("{} 退出码: {:?}", program.display(), status.code());
+    }
+    Ok(())
+}
+
+/// `program` on `PATH`, else where its Windows installer puts it (the VLC and mpv
+/// installers don't add themselves to `PATH`)
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if let Ok(p) = which(program) {
+        return Some(p);
+    }
+    if !cfg!(windows) {
+        return None;
+    }
+    let dirs: &[&str] = match program {
+        "vlc" => &["VideoLAN\\VLC"],
+        "mpv" => &["mpv"],
+        _ => &[],
+    };
+    ["ProgramFiles", "ProgramFiles(x86)", "LOCALAPPDATA"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .flat_map(|root| dirs.iter().map(move |d| PathBuf::from(&root).join(d).join(format!("{}.exe", program))))
+        .find(|p| p.is_file())
+}
+
+/// The `tar` to unpack release archives with. On Windows that's the bundled bsdtar, which
+/// reads zip; Git's GNU tar, often first on `PATH`, doesn't.
+pub fn tar() -> PathBuf {
+    if cfg!(windows) {
+        if let Some(root) = std::env::var_os("SystemRoot") {
+            let bundled = PathBuf::from(root).join("System32").join("tar.exe");
+            if bundled.is_file() {
+                return bundled;
+            }
+        }
+    }
+    PathBuf::from("tar")
+}
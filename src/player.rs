@@ -0,0 +1,62 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::process::Stdio;
+
+use crate::config::{self, Player};
+use crate::platform;
+use crate::stream::{self, Stream};
+use crate::types::PlayCandidate;
+use crate::util;
+
+impl Player {
+    fn command(self) -> &'static str {
+        match self {
+            Player::Mpv => "mpv",
+            Player::Vlc => "vlc",
+        }
+    }
+
+    /// Player options that send the page's Referer and our User-Agent with the stream request
+    fn header_args(self, referer: &str, user_agent: &str, title: &str) -> Vec<String> {
+        match self {
+            Player::Mpv => vec![
+                format!("--referrer={}", referer),
+                format!("--user-agent={}", user_agent),
+                format!("--force-media-title={}", title),
+            ],
+            Player::Vlc => vec![
+                format!("--http-referrer={}", referer),
+                format!("--http-user-agent={}", user_agent),
+                format!("--meta-title={}", title),
+            ],
+        }
+    }
+}
+
+async fn launch(player: Player, s: &Stream, title: &str) -> Result<()> {
+    if util::dry_run_skip(format!("将用 {} 播放: {}", player.command(), s.url)) {
+        return Ok(());
+    }
+    let Some(program) = platform::find_program(player.command()) else {
+        bail!("未检测到 {}，请先安装并确保它在 PATH 中", player.command());
+    };
+    let status = tokio::process::Command::new(program)
+        .args(player.header_args(&s.referer, &s.user_agent, title))
+        .args(&config::get().view.player_args)
+        .arg(&s.url)
+        .stdin(Stdio::null())
+        .status()
+        .await
+        .with_context(|| format!("启动 {} 失败", player.command()))?;
+    if !status.success() {
+        bail!("{} 退出码: {:?}", player.command(), status.code());
+    }
+    Ok(())
+}
+
+/// Play the first of `candidates` that yields a direct stream in `player`
+pub async fn play(player: Player, code: &str, candidates: &[PlayCandidate]) -> Result<()> {
+    let s = stream::first(candidates).await?;
+    util::note(format!("{} {} {}", format!("{} 播放", player.command()).green().bold(), s.url, format!("（来自 {}）", s.source).dimmed()));
+    launch(player, &s, code).await
+}
@@ -0,0 +1,56 @@
+//! `av price`: what a title costs on DMM right now, per floor and delivery, with running
+//! campaigns, to weigh buying or renting it.
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+
+use crate::sources::dmm::{self, Prices};
+use crate::table;
+use crate::util;
+
+fn yen(p: Option<u32>) -> String {
+    p.map(|p| format!("{}円", p)).unwrap_or_else(|| "-".to_string())
+}
+
+/// `-40%` off the list price
+fn discount(price: Option<u32>, list: Option<u32>) -> String {
+    match (price, list) {
+        (Some(p), Some(l)) if l > p && l > 0 => format!("-{}%", (l - p) * 100 / l),
+        _ => "-".to_string(),
+    }
+}
+
+fn print(p: &Prices) {
+    let floor = p.floor_name.clone().unwrap_or_else(|| p.floor.clone());
+    println!("{} {}  {}", floor.bold(), p.title, p.url.as_deref().unwrap_or("").dimmed());
+    let rows = p.deliveries.iter().map(|d| vec![d.kind.clone(), yen(d.price), yen(d.list_price), discount(d.price, d.list_price)]).collect::<Vec<_>>();
+    if rows.is_empty() {
+        println!("  价格： {}", yen(p.price));
+    } else {
+        table::print_rows(&["方式", "价格", "原价", "折扣"], rows);
+    }
+    for c in &p.campaigns {
+        let until = c.date_end.as_deref().map(|d| format!("（至 {}）", d.split(' ').next().unwrap_or(d))).unwrap_or_default();
+        println!("  {} {}{}", "活动：".yellow(), c.title, until);
+    }
+}
+
+/// `av price <code>`
+pub async fn run(code: &str, json: bool) -> Result<()> {
+    let found = dmm::prices(code).await?;
+    if json {
+        util::print_output(&found, true);
+        return Ok(());
+    }
+    if found.is_empty() {
+        bail!("DMM 上没有找到 {}（已查询 [sources.dmm] floors 中的全部分类）", crate::code::normalize(code));
+    }
+    println!("{}", found[0].code.bold());
+    for (i, p) in found.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print(p);
+    }
+    Ok(())
+}
@@ -0,0 +1,343 @@
+//! `av progress`: a live table of the downloads av handed to qBittorrent, or to an aria2c
+//! daemon over RPC, with keys to pause, resume and cancel them. Transfers are told apart
+//! from the client's other torrents by infohash, through the journal; `a` (or `--all`)
+//! shows everything the client has. Not on a terminal (or with `--once`/`--json`) it
+//! prints the table once.
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use console::{Key, Term};
+use std::time::Duration;
+
+use crate::cancel;
+use crate::config;
+use crate::downloader::{self, aria2, qbittorrent, Transfer, TransferState};
+use crate::errors::Tagged;
+use crate::journal;
+use crate::magnet::format_size;
+use crate::table;
+use crate::util;
+
+const HEADERS: [&str; 9] = ["番号", "名称", "进度", "大小", "下载", "上传", "剩余", "做种/用户", "状态"];
+/// Terminal columns of every column but the name, whose width is what is left
+const WIDTHS: [usize; 9] = [12, 0, 17, 10, 12, 12, 8, 9, 8];
+const MIN_NAME_WIDTH: usize = 16;
+/// Lines around the table: title, blank, header, blank, status, keys
+const CHROME_LINES: usize = 6;
+const KEYS_HELP: &str = "↑↓ 选择  p 暂停/继续  c 取消  a 全部/仅 av  r 刷新  q 退出";
+
+enum Watched {
+    Qbittorrent(qbittorrent::Session),
+    Aria2(aria2::Rpc),
+}
+
+impl Watched {
+    async fn connect() -> Result<Watched> {
+        match downloader::selected()? {
+            Some(downloader::Selected::Qbittorrent(q)) => return Ok(Watched::Qbittorrent(qbittorrent::Session::open(q).await?)),
+            Some(other) => bail!(Tagged::new("config", format!("av progress 暂只支持 qBittorrent 和 aria2 RPC，当前的下载器是 {}", other.name()))),
+            None => {}
+        }
+        match config::get().aria2.as_ref().filter(|a| !a.rpc_url.trim().is_empty()) {
+            Some(a) => Ok(Watched::Aria2(aria2::Rpc::new(a)?)),
+            None => bail!(Tagged::new(
+                "config",
+                "没有可查看的下载器：配置 [qbittorrent]，或在 [aria2] rpc_url 填写以 --enable-rpc 运行的 aria2c 地址",
+            )),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Watched::Qbittorrent(_) => "qBittorrent",
+            Watched::Aria2(_) => "aria2",
+        }
+    }
+
+    async fn transfers(&mut self) -> Result<Vec<Transfer>> {
+        match self {
+            Watched::Qbittorrent(s) => s.transfers().await,
+            Watched::Aria2(r) => r.transfers().await,
+        }
+    }
+
+    /// Resume it when paused (or stopped after seeding), pause it otherwise; what was done
+    async fn toggle(&mut self, t: &Transfer) -> Result<&'static str> {
+        let resume = matches!(t.state, TransferState::Paused | TransferState::Complete);
+        match (self, resume) {
+            (Watched::Qbittorrent(s), true) => s.resume(&t.id).await?,
+            (Watched::Qbittorrent(s), false) => s.pause(&t.id).await?,
+            (Watched::Aria2(r), true) => r.resume(&t.id).await?,
+            (Watched::Aria2(r), false) => r.pause(&t.id).await?,
+        }
+        Ok(if resume { "已继续" } else { "已暂停" })
+    }
+
+    async fn remove(&mut self, t: &Transfer) -> Result<()> {
+        match self {
+            Watched::Qbittorrent(s) => s.remove(&t.id).await,
+            Watched::Aria2(r) => r.remove(&t.id, t.state).await,
+        }
+    }
+}
+
+/// The client's transfers with their codes, only av's unless `all`
+async fn snapshot(w: &mut Watched, all: bool) -> Result<Vec<Transfer>> {
+    let codes = journal::dispatched()?;
+    let mut list = w.transfers().await?;
+    for t in &mut list {
+        if let Some(code) = t.infohash.as_ref().and_then(|h| codes.get(h)) {
+            t.code = code.clone();
+        }
+    }
+    if !all {
+        list.retain(|t| t.infohash.as_ref().is_some_and(|h| codes.contains_key(h)));
+    }
+    Ok(list)
+}
+
+/// `1:02:03`, `4:05`
+fn format_eta(secs: u64) -> String {
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, m, s) => format!("{}:{:02}", m, s),
+        (h, m, s) => format!("{}:{:02}:{:02}", h, m, s),
+    }
+}
+
+fn speed(bytes: u64) -> String {
+    if bytes == 0 { "-".to_string() } else { format!("{}/s", format_size(bytes)) }
+}
+
+fn cells(t: &Transfer) -> Vec<String> {
+    let p = t.progress();
+    let filled = (p * 10.0).round() as usize;
+    let downloading = matches!(t.state, TransferState::Downloading | TransferState::Stalled);
+    vec![
+        if t.code.is_empty() { "-".to_string() } else { t.code.clone() },
+        t.name.clone(),
+        format!("{}{} {:>5.1}%", "█".repeat(filled), "░".repeat(10 - filled), p * 100.0),
+        if t.size == 0 { "-".to_string() } else { format_size(t.size) },
+        speed(t.down_speed),
+        speed(t.up_speed),
+        t.eta_secs.filter(|_| downloading).map(format_eta).unwrap_or_else(|| "-".to_string()),
+        match (t.seeders, t.peers) {
+            (Some(s), Some(p)) => format!("{}/{}", s, p),
+            (Some(s), None) => s.to_string(),
+            _ => "-".to_string(),
+        },
+        t.state.label().to_string(),
+    ]
+}
+
+/// `av progress`
+pub async fn run(all: bool, interval: u64, once: bool, json: bool) -> Result<()> {
+    let mut watched = Watched::connect().await?;
+    let term = Term::stdout();
+    if once || json || !term.is_term() {
+        let list = snapshot(&mut watched, all).await?;
+        if json {
+            util::print_output(&list, true);
+        } else if list.is_empty() {
+            let hint = if all { "" } else { "（加 --all 查看下载器中的全部）" };
+            println!("{}", format!("{} 中没有 av 添加的下载{}", watched.name(), hint).yellow());
+        } else {
+            table::print_rows(&HEADERS, list.iter().map(cells).collect());
+        }
+        return Ok(());
+    }
+    live(&term, watched, all, Duration::from_secs(interval.max(1))).await
+}
+
+struct View {
+    client: &'static str,
+    all: bool,
+    list: Vec<Transfer>,
+    selected: usize,
+    /// `y` removes this transfer (by id)
+    confirm: Option<String>,
+    status: Option<(String, bool)>,
+    updated: String,
+}
+
+impl View {
+    fn current(&self) -> Option<&Transfer> {
+        self.list.get(self.selected)
+    }
+
+    fn say(&mut self, text: impl Into<String>, error: bool) {
+        self.status = Some((text.into(), error));
+    }
+
+    fn render(&self, term: &Term) -> Result<()> {
+        let (rows, cols) = term.size();
+        let (rows, cols) = (rows as usize, cols as usize);
+        // The selection marker and the gaps, short of the last column (a full line wraps)
+        let fixed: usize = WIDTHS.iter().sum::<usize>() + 2 * WIDTHS.len() + 1;
+        let name_width = cols.saturating_sub(fixed).max(MIN_NAME_WIDTH);
+        let line = |marker: &str, cells: &[String]| {
+            let parts: Vec<String> = cells
+                .iter()
+                .zip(WIDTHS)
+                .map(|(c, w)| {
+                    let w = if w == 0 { name_width } else { w };
+                    util::pad(&util::truncate_width(c, w), w)
+                })
+                .collect();
+            util::truncate_width(format!("{}{}", marker, parts.join("  ")).trim_end(), cols.saturating_sub(1))
+        };
+
+        let scope = if self.all { "全部下载" } else { "av 添加的下载" };
+        let mut out = vec![
+            format!("{} · {}（{}）  {}", self.client.bold(), scope, self.list.len(), format!("更新于 {}", self.updated).dimmed()),
+            String::new(),
+            line("  ", &HEADERS.map(String::from)).bold().to_string(),
+        ];
+        let shown = rows.saturating_sub(CHROME_LINES).max(1);
+        let first = self.selected.saturating_sub(shown - 1);
+        for (i, t) in self.list.iter().enumerate().skip(first).take(shown) {
+            let text = line(if i == self.selected { "> " } else { "  " }, &cells(t));
+            out.push(if i == self.selected { text.reversed().to_string() } else { text });
+        }
+        if self.list.is_empty() {
+            out.push(if self.all { "下载器中没有下载".to_string() } else { "没有 av 添加的下载，按 a 查看全部".to_string() }.dimmed().to_string());
+        }
+        out.push(String::new());
+        out.push(match &self.status {
+            Some((text, true)) => text.red().to_string(),
+            Some((text, false)) => text.green().to_string(),
+            None => String::new(),
+        });
+        out.push(KEYS_HELP.dimmed().to_string());
+        // Home, each line over the old one, then clear what is left of the last frame
+        let frame: String = out.iter().map(|l| format!("{}\x1b[K\n", l)).collect();
+        term.write_str(&format!("\x1b[H{}\x1b[J", frame.trim_end_matches('\n')))?;
+        Ok(())
+    }
+}
+
+/// The alternate screen with the cursor hidden, given back on drop whatever ends the view
+struct Screen<'a>(&'a Term);
+
+impl<'a> Screen<'a> {
+    fn enter(term: &'a Term) -> Result<Screen<'a>> {
+        term.write_str("\x1b[?1049h")?;
+        term.hide_cursor()?;
+        Ok(Screen(term))
+    }
+}
+
+impl Drop for Screen<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.show_cursor();
+        let _ = self.0.write_str("\x1b[?1049l");
+    }
+}
+
+/// Keys from a thread of their own, as reading one blocks. It stops after a quit key (or
+/// Ctrl-C, which `read_key` turns back into SIGINT), so the terminal is never left raw.
+fn read_keys() -> tokio::sync::mpsc::UnboundedReceiver<Key> {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        let term = Term::stdout();
+        while let Ok(key) = term.read_key() {
+            let quit = matches!(key, Key::Char('q') | Key::Escape);
+            if tx.send(key).is_err() || quit {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+enum After {
+    Redraw,
+    Refresh,
+    Quit,
+}
+
+async fn handle(w: &mut Watched, view: &mut View, key: Key) -> After {
+    if let Some(id) = view.confirm.take() {
+        let Some(t) = view.list.iter().find(|t| t.id == id).cloned() else { return After::Redraw };
+        if !matches!(key, Key::Char('y' | 'Y')) {
+            view.say("已放弃取消", false);
+            return After::Redraw;
+        }
+        match w.remove(&t).await {
+            Ok(()) => view.say(format!("已取消 {}", t.name), false),
+            Err(e) => view.say(format!("取消失败: {:#}", e), true),
+        }
+        return After::Refresh;
+    }
+    match key {
+        Key::Char('q') | Key::Escape | Key::CtrlC => return After::Quit,
+        Key::ArrowUp | Key::Char('k') => view.selected = view.selected.saturating_sub(1),
+        Key::ArrowDown | Key::Char('j') => view.selected = (view.selected + 1).min(view.list.len().saturating_sub(1)),
+        Key::Home => view.selected = 0,
+        Key::End => view.selected = view.list.len().saturating_sub(1),
+        Key::Char('p' | ' ') => {
+            let Some(t) = view.current().cloned() else { return After::Redraw };
+            match w.toggle(&t).await {
+                Ok(done) => view.say(format!("{} {}", done, t.name), false),
+                Err(e) => view.say(format!("操作失败: {:#}", e), true),
+            }
+            return After::Refresh;
+        }
+        Key::Char('c') | Key::Del => {
+            let Some(t) = view.current() else { return After::Redraw };
+            let (id, name) = (t.id.clone(), t.name.clone());
+            view.confirm = Some(id);
+            view.say(format!("取消 {}？按 y 确认（已下载的文件会保留）", name), true);
+        }
+        Key::Char('a') => {
+            view.all = !view.all;
+            view.selected = 0;
+            return After::Refresh;
+        }
+        Key::Char('r') => return After::Refresh,
+        _ => {}
+    }
+    After::Redraw
+}
+
+async fn live(term: &Term, mut w: Watched, all: bool, interval: Duration) -> Result<()> {
+    let _screen = Screen::enter(term)?;
+    let mut keys = read_keys();
+    let token = cancel::token();
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick is now; this loop refreshes before drawing anyway
+    ticker.tick().await;
+    let mut view = View { client: w.name(), all, list: Vec::new(), selected: 0, confirm: None, status: None, updated: "-".to_string() };
+    let mut refresh = true;
+    loop {
+        if refresh {
+            refresh = false;
+            let fetched = tokio::select! {
+                r = snapshot(&mut w, view.all) => r,
+                _ = token.cancelled() => break,
+            };
+            match fetched {
+                Ok(list) => {
+                    view.list = list;
+                    view.selected = view.selected.min(view.list.len().saturating_sub(1));
+                    view.updated = chrono::Local::now().format("%H:%M:%S").to_string();
+                }
+                Err(e) => view.say(format!("刷新失败: {:#}", e), true),
+            }
+        }
+        view.render(term)?;
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = ticker.tick() => refresh = true,
+            key = keys.recv() => match key {
+                None => break,
+                Some(key) => match handle(&mut w, &mut view, key).await {
+                    After::Quit => break,
+                    After::Refresh => refresh = true,
+                    After::Redraw => {}
+                },
+            },
+        }
+    }
+    Ok(())
+}
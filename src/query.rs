@@ -0,0 +1,978 @@
+//! `--query`: a small jq subset applied to a command's JSON output, for scripts on machines
+//! without jq. Supported: paths (`.a.b`, `."key"`, `.[0]`, `.[-1]`, `.[2:5]`, `.[]`), `|`,
+//! `,`, `[...]` and `{...}` construction, `== != < <= > >=`, `and` / `or`, `//`,
+//! `+ - * /`, and the builtins listed in `call`.
+//!
+//! Results are printed one per line, pretty JSON except strings, which come out bare like
+//! `jq -r` so they can go straight into a shell variable.
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    /// `.name` / `."name"`
+    Field(String),
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Punct(&'static str),
+}
+
+const PUNCTS: [&str; 20] = ["//", "==", "!=", "<=", ">=", "|", ",", "(", ")", "[", "]", "{", "}", ":", ";", "<", ">", "+", "-", "*"];
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn string_literal(chars: &[char], i: &mut usize) -> Result<String> {
+    // chars[*i] is the opening quote
+    *i += 1;
+    let mut s = String::new();
+    while *i < chars.len() {
+        match chars[*i] {
+            '"' => {
+                *i += 1;
+                return Ok(s);
+            }
+            '\\' => {
+                *i += 1;
+                match chars.get(*i) {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some(&c) => s.push(c),
+                    None => break,
+                }
+            }
+            c => s.push(c),
+        }
+        *i += 1;
+    }
+    bail!("字符串缺少结尾的引号")
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '.' && chars.get(i + 1).is_some_and(|&n| is_ident_start(n)) {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && is_ident(chars[i]) {
+                i += 1;
+            }
+            out.push(Token::Field(chars[start..i].iter().collect()));
+        } else if c == '.' && chars.get(i + 1) == Some(&'"') {
+            i += 1;
+            out.push(Token::Field(string_literal(&chars, &mut i)?));
+        } else if c == '.' && !chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()) {
+            out.push(Token::Dot);
+            i += 1;
+        } else if c == '"' {
+            out.push(Token::Str(string_literal(&chars, &mut i)?));
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // `1e3`, `2.5E-4`
+            if matches!(chars.get(i), Some('e' | 'E')) {
+                let digits = if matches!(chars.get(i + 1), Some('+' | '-')) { i + 2 } else { i + 1 };
+                if chars.get(digits).is_some_and(|d| d.is_ascii_digit()) {
+                    i = digits;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            out.push(Token::Num(text.parse().with_context(|| format!("数字无效: {}", text))?));
+        } else if is_ident_start(c) {
+            let start = i;
+            while i < chars.len() && is_ident(chars[i]) {
+                i += 1;
+            }
+            out.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '/' {
+            let p = if chars.get(i + 1) == Some(&'/') { "//" } else { "/" };
+            i += p.len();
+            out.push(Token::Punct(p));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let Some(p) = PUNCTS.iter().find(|p| rest.starts_with(**p)) else {
+                bail!("无法识别的字符: {}", c);
+            };
+            i += p.chars().count();
+            out.push(Token::Punct(p));
+        }
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Alt,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr(Node);
+
+#[derive(Debug, Clone)]
+enum Node {
+    Identity,
+    Literal(Value),
+    Index(Box<Node>, Box<Node>),
+    Slice(Box<Node>, Option<Box<Node>>, Option<Box<Node>>),
+    Iterate(Box<Node>),
+    Pipe(Box<Node>, Box<Node>),
+    Comma(Box<Node>, Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+    Array(Option<Box<Node>>),
+    Object(Vec<(Node, Node)>),
+    Call(String, Vec<Node>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, p: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(q)) if *q == p) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_word(&mut self, w: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(q)) if q == w) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, p: &str) -> Result<()> {
+        if !self.eat(p) {
+            bail!("此处应为 {}，实际为 {}", p, self.describe());
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            None => "结尾".to_string(),
+            Some(Token::Dot) => ".".to_string(),
+            Some(Token::Field(f)) => format!(".{}", f),
+            Some(Token::Ident(s)) => s.clone(),
+            Some(Token::Str(s)) => format!("\"{}\"", s),
+            Some(Token::Num(n)) => n.to_string(),
+            Some(Token::Punct(p)) => p.to_string(),
+        }
+    }
+
+    fn pipe(&mut self) -> Result<Node> {
+        let mut left = self.comma()?;
+        while self.eat("|") {
+            left = Node::Pipe(Box::new(left), Box::new(self.comma()?));
+        }
+        Ok(left)
+    }
+
+    fn comma(&mut self) -> Result<Node> {
+        let mut left = self.alt()?;
+        while self.eat(",") {
+            left = Node::Comma(Box::new(left), Box::new(self.alt()?));
+        }
+        Ok(left)
+    }
+
+    fn binary(&mut self, next: fn(&mut Parser) -> Result<Node>, ops: &[(&str, Op)]) -> Result<Node> {
+        let mut left = next(self)?;
+        'outer: loop {
+            for &(text, op) in ops {
+                let matched = if text.chars().all(char::is_alphabetic) { self.eat_word(text) } else { self.eat(text) };
+                if matched {
+                    left = Node::Binary(op, Box::new(left), Box::new(next(self)?));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn alt(&mut self) -> Result<Node> {
+        self.binary(Parser::or, &[("//", Op::Alt)])
+    }
+
+    fn or(&mut self) -> Result<Node> {
+        self.binary(Parser::and, &[("or", Op::Or)])
+    }
+
+    fn and(&mut self) -> Result<Node> {
+        self.binary(Parser::compare, &[("and", Op::And)])
+    }
+
+    fn compare(&mut self) -> Result<Node> {
+        let ops = [("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)];
+        let left = self.additive()?;
+        for (text, op) in ops {
+            if self.eat(text) {
+                return Ok(Node::Binary(op, Box::new(left), Box::new(self.additive()?)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Node> {
+        self.binary(Parser::multiplicative, &[("+", Op::Add), ("-", Op::Sub)])
+    }
+
+    fn multiplicative(&mut self) -> Result<Node> {
+        self.binary(Parser::postfix, &[("*", Op::Mul), ("/", Op::Div)])
+    }
+
+    fn postfix(&mut self) -> Result<Node> {
+        let mut node = self.primary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Field(f)) => {
+                    let f = f.clone();
+                    self.pos += 1;
+                    node = Node::Index(Box::new(node), Box::new(Node::Literal(Value::String(f))));
+                }
+                Some(Token::Dot) if matches!(self.tokens.get(self.pos + 1), Some(Token::Punct("["))) => {
+                    // `.a.[0]`, same as `.a[0]`
+                    self.pos += 1;
+                }
+                Some(Token::Punct("[")) => {
+                    self.pos += 1;
+                    node = self.bracket(node)?;
+                }
+                _ => return Ok(node),
+            }
+        }
+    }
+
+    /// After `[` following an expression: `]`, `e]`, `e:e]`, `:e]`, `e:]`
+    fn bracket(&mut self, target: Node) -> Result<Node> {
+        if self.eat("]") {
+            return Ok(Node::Iterate(Box::new(target)));
+        }
+        let from = if matches!(self.peek(), Some(Token::Punct(":"))) { None } else { Some(Box::new(self.pipe()?)) };
+        if self.eat(":") {
+            let to = if matches!(self.peek(), Some(Token::Punct("]"))) { None } else { Some(Box::new(self.pipe()?)) };
+            self.expect("]")?;
+            return Ok(Node::Slice(Box::new(target), from, to));
+        }
+        self.expect("]")?;
+        let index = from.context("[] 中缺少下标")?;
+        Ok(Node::Index(Box::new(target), index))
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        let token = self.peek().cloned().context("表达式不完整")?;
+        self.pos += 1;
+        Ok(match token {
+            Token::Dot => Node::Identity,
+            Token::Field(f) => Node::Index(Box::new(Node::Identity), Box::new(Node::Literal(Value::String(f)))),
+            Token::Str(s) => Node::Literal(Value::String(s)),
+            Token::Num(n) => Node::Literal(number(n)),
+            Token::Ident(w) => match w.as_str() {
+                "true" => Node::Literal(Value::Bool(true)),
+                "false" => Node::Literal(Value::Bool(false)),
+                "null" => Node::Literal(Value::Null),
+                _ => {
+                    let mut args = Vec::new();
+                    if self.eat("(") {
+                        loop {
+                            args.push(self.pipe()?);
+                            if !self.eat(";") {
+                                break;
+                            }
+                        }
+                        self.expect(")")?;
+                    }
+                    Node::Call(w, args)
+                }
+            },
+            Token::Punct("(") => {
+                let inner = self.pipe()?;
+                self.expect(")")?;
+                inner
+            }
+            Token::Punct("[") => {
+                if self.eat("]") {
+                    Node::Array(None)
+                } else {
+                    let inner = self.pipe()?;
+                    self.expect("]")?;
+                    Node::Array(Some(Box::new(inner)))
+                }
+            }
+            Token::Punct("{") => self.object()?,
+            Token::Punct("-") => Node::Binary(Op::Sub, Box::new(Node::Literal(number(0.0))), Box::new(self.postfix()?)),
+            _ => {
+                self.pos -= 1;
+                bail!("此处不应出现 {}", self.describe());
+            }
+        })
+    }
+
+    /// After `{`: `key: value`, `key` (short for `key: .key`), `"key": v`, `(expr): v`
+    fn object(&mut self) -> Result<Node> {
+        let mut entries = Vec::new();
+        if self.eat("}") {
+            return Ok(Node::Object(entries));
+        }
+        loop {
+            let token = self.peek().cloned().context("对象不完整")?;
+            self.pos += 1;
+            let (key, shorthand) = match token {
+                Token::Ident(k) | Token::Str(k) => (Node::Literal(Value::String(k.clone())), Some(k)),
+                Token::Punct("(") => {
+                    let k = self.pipe()?;
+                    self.expect(")")?;
+                    (k, None)
+                }
+                _ => {
+                    self.pos -= 1;
+                    bail!("对象的键无效: {}", self.describe());
+                }
+            };
+            let value = if self.eat(":") {
+                self.alt()?
+            } else {
+                let k = shorthand.context("(表达式) 作为键时必须带值")?;
+                Node::Index(Box::new(Node::Identity), Box::new(Node::Literal(Value::String(k))))
+            };
+            entries.push((key, value));
+            if self.eat("}") {
+                return Ok(Node::Object(entries));
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<Expr> {
+    let mut p = Parser { tokens: tokenize(src)?, pos: 0 };
+    let node = p.pipe()?;
+    if p.peek().is_some() {
+        bail!("多余的内容: {}", p.describe());
+    }
+    Ok(Expr(node))
+}
+
+/// Whole numbers stay integers, as jq prints them
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 9e15 {
+        Value::from(n as i64)
+    } else {
+        serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
+
+fn truthy(v: &Value) -> bool {
+    !matches!(v, Value::Null | Value::Bool(false))
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// jq's order: null < false < true < numbers < strings < arrays < objects
+fn compare(a: &Value, b: &Value) -> Ordering {
+    let rank = |v: &Value| match v {
+        Value::Null => 0,
+        Value::Bool(false) => 1,
+        Value::Bool(true) => 2,
+        Value::Number(_) => 3,
+        Value::String(_) => 4,
+        Value::Array(_) => 5,
+        Value::Object(_) => 6,
+    };
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64().unwrap_or(0.0).total_cmp(&y.as_f64().unwrap_or(0.0)),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Array(x), Value::Array(y)) => {
+            x.iter().zip(y).map(|(p, q)| compare(p, q)).find(|o| o.is_ne()).unwrap_or(x.len().cmp(&y.len()))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            let mut xk: Vec<&String> = x.keys().collect();
+            let mut yk: Vec<&String> = y.keys().collect();
+            xk.sort();
+            yk.sort();
+            xk.cmp(&yk).then_with(|| xk.iter().map(|k| compare(&x[*k], &y[*k])).find(|o| o.is_ne()).unwrap_or(Ordering::Equal))
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn arith(op: Op, a: &Value, b: &Value) -> Result<Value> {
+    Ok(match (op, a, b) {
+        (Op::Add, Value::Null, v) | (Op::Add, v, Value::Null) => v.clone(),
+        (Op::Add, Value::String(x), Value::String(y)) => Value::String(format!("{}{}", x, y)),
+        (Op::Add, Value::Array(x), Value::Array(y)) => Value::Array(x.iter().chain(y).cloned().collect()),
+        (Op::Add, Value::Object(x), Value::Object(y)) => {
+            let mut m = x.clone();
+            m.extend(y.iter().map(|(k, v)| (k.clone(), v.clone())));
+            Value::Object(m)
+        }
+        (Op::Sub, Value::Array(x), Value::Array(y)) => Value::Array(x.iter().filter(|v| !y.contains(v)).cloned().collect()),
+        (_, Value::Number(x), Value::Number(y)) => {
+            let (x, y) = (x.as_f64().unwrap_or(0.0), y.as_f64().unwrap_or(0.0));
+            match op {
+                Op::Add => number(x + y),
+                Op::Sub => number(x - y),
+                Op::Mul => number(x * y),
+                Op::Div if y == 0.0 => bail!("除以零"),
+                _ => number(x / y),
+            }
+        }
+        _ => bail!("{} 与 {} 不能做此运算", type_name(a), type_name(b)),
+    })
+}
+
+fn index(target: &Value, key: &Value) -> Result<Value> {
+    Ok(match (target, key) {
+        (Value::Null, _) => Value::Null,
+        (Value::Object(m), Value::String(k)) => m.get(k).cloned().unwrap_or(Value::Null),
+        (Value::Array(a), Value::Number(n)) => {
+            let n = n.as_f64().unwrap_or(0.0) as i64;
+            let i = if n < 0 { a.len() as i64 + n } else { n };
+            usize::try_from(i).ok().and_then(|i| a.get(i)).cloned().unwrap_or(Value::Null)
+        }
+        (t, k) => bail!("不能用 {} 索引 {}", type_name(k), type_name(t)),
+    })
+}
+
+fn slice_bounds(len: usize, from: Option<&Value>, to: Option<&Value>) -> (usize, usize) {
+    let clamp = |v: Option<&Value>, default: usize| match v.and_then(Value::as_f64) {
+        Some(n) if n < 0.0 => (len as f64 + n).max(0.0) as usize,
+        Some(n) => (n as usize).min(len),
+        None => default,
+    };
+    let (a, b) = (clamp(from, 0), clamp(to, len));
+    (a, b.max(a))
+}
+
+fn iterate(v: &Value) -> Result<Vec<Value>> {
+    match v {
+        Value::Array(a) => Ok(a.clone()),
+        Value::Object(m) => Ok(m.values().cloned().collect()),
+        other => bail!("不能遍历 {}", type_name(other)),
+    }
+}
+
+/// Every combination of the outputs of `a` and `b` on `input`
+fn pairs(a: &Node, b: &Node, input: &Value) -> Result<Vec<(Value, Value)>> {
+    let bs = eval(b, input)?;
+    let mut out = Vec::new();
+    for x in eval(a, input)? {
+        for y in &bs {
+            out.push((x.clone(), y.clone()));
+        }
+    }
+    Ok(out)
+}
+
+fn eval(node: &Node, input: &Value) -> Result<Vec<Value>> {
+    Ok(match node {
+        Node::Identity => vec![input.clone()],
+        Node::Literal(v) => vec![v.clone()],
+        Node::Index(target, key) => pairs(target, key, input)?.iter().map(|(t, k)| index(t, k)).collect::<Result<_>>()?,
+        Node::Slice(target, from, to) => {
+            let mut out = Vec::new();
+            for t in eval(target, input)? {
+                let from = from.as_ref().map(|f| eval(f, input)).transpose()?.and_then(|v| v.into_iter().next());
+                let to = to.as_ref().map(|f| eval(f, input)).transpose()?.and_then(|v| v.into_iter().next());
+                out.push(match &t {
+                    Value::Null => Value::Null,
+                    Value::Array(a) => {
+                        let (a0, b0) = slice_bounds(a.len(), from.as_ref(), to.as_ref());
+                        Value::Array(a[a0..b0].to_vec())
+                    }
+                    Value::String(s) => {
+                        let chars: Vec<char> = s.chars().collect();
+                        let (a0, b0) = slice_bounds(chars.len(), from.as_ref(), to.as_ref());
+                        Value::String(chars[a0..b0].iter().collect())
+                    }
+                    other => bail!("不能切片 {}", type_name(other)),
+                });
+            }
+            out
+        }
+        Node::Iterate(target) => {
+            let mut out = Vec::new();
+            for t in eval(target, input)? {
+                out.extend(iterate(&t)?);
+            }
+            out
+        }
+        Node::Pipe(a, b) => {
+            let mut out = Vec::new();
+            for v in eval(a, input)? {
+                out.extend(eval(b, &v)?);
+            }
+            out
+        }
+        Node::Comma(a, b) => {
+            let mut out = eval(a, input)?;
+            out.extend(eval(b, input)?);
+            out
+        }
+        Node::Binary(Op::And, a, b) | Node::Binary(Op::Or, a, b) => {
+            let is_and = matches!(node, Node::Binary(Op::And, ..));
+            let mut out = Vec::new();
+            for x in eval(a, input)? {
+                if truthy(&x) != is_and {
+                    out.push(Value::Bool(!is_and));
+                    continue;
+                }
+                out.extend(eval(b, input)?.iter().map(|y| Value::Bool(truthy(y))));
+            }
+            out
+        }
+        Node::Binary(Op::Alt, a, b) => {
+            let good: Vec<Value> = eval(a, input).unwrap_or_default().into_iter().filter(truthy).collect();
+            if good.is_empty() {
+                eval(b, input)?
+            } else {
+                good
+            }
+        }
+        Node::Binary(op, a, b) => {
+            let mut out = Vec::new();
+            for (x, y) in pairs(a, b, input)? {
+                let ord = compare(&x, &y);
+                out.push(match op {
+                    Op::Eq => Value::Bool(ord.is_eq()),
+                    Op::Ne => Value::Bool(ord.is_ne()),
+                    Op::Lt => Value::Bool(ord.is_lt()),
+                    Op::Le => Value::Bool(ord.is_le()),
+                    Op::Gt => Value::Bool(ord.is_gt()),
+                    Op::Ge => Value::Bool(ord.is_ge()),
+                    _ => arith(*op, &x, &y)?,
+                });
+            }
+            out
+        }
+        Node::Array(None) => vec![Value::Array(Vec::new())],
+        Node::Array(Some(inner)) => vec![Value::Array(eval(inner, input)?)],
+        Node::Object(entries) => {
+            let mut objects = vec![Map::new()];
+            for (k, v) in entries {
+                let mut next = Vec::new();
+                for (key, value) in pairs(k, v, input)? {
+                    let Value::String(key) = key else { bail!("对象的键必须是字符串，实际为 {}", type_name(&key)) };
+                    for obj in &objects {
+                        let mut obj = obj.clone();
+                        obj.insert(key.clone(), value.clone());
+                        next.push(obj);
+                    }
+                }
+                objects = next;
+            }
+            objects.into_iter().map(Value::Object).collect()
+        }
+        Node::Call(name, args) => call(name, args, input)?,
+    })
+}
+
+fn one(node: &Node, input: &Value) -> Result<Value> {
+    Ok(eval(node, input)?.into_iter().next().unwrap_or(Value::Null))
+}
+
+/// Patterns `test` has compiled, so `.[] | select(.title | test("..."))` compiles its
+/// pattern once rather than once per item. Patterns built from the data could be endless,
+/// so the cache starts over once it holds `REGEX_CACHE_MAX`.
+static REGEXES: LazyLock<Mutex<HashMap<String, Regex>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+const REGEX_CACHE_MAX: usize = 64;
+
+fn regex(pattern: &str) -> Result<Regex> {
+    let mut cache = REGEXES.lock().unwrap();
+    if let Some(re) = cache.get(pattern) {
+        return Ok(re.clone());
+    }
+    let re = Regex::new(pattern).context("test 的正则表达式无效")?;
+    if cache.len() >= REGEX_CACHE_MAX {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), re.clone());
+    Ok(re)
+}
+
+fn string_arg(name: &str, node: &Node, input: &Value) -> Result<String> {
+    match one(node, input)? {
+        Value::String(s) => Ok(s),
+        other => bail!("{} 的参数必须是字符串，实际为 {}", name, type_name(&other)),
+    }
+}
+
+fn contains(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(x), Value::String(y)) => x.contains(y.as_str()),
+        (Value::Array(x), Value::Array(y)) => y.iter().all(|q| x.iter().any(|p| contains(p, q))),
+        (Value::Object(x), Value::Object(y)) => y.iter().all(|(k, q)| x.get(k).is_some_and(|p| contains(p, q))),
+        _ => a == b,
+    }
+}
+
+fn sorted_by_keys(items: Vec<Value>, keys: Vec<Value>) -> Vec<Value> {
+    let mut pairs: Vec<(Value, Value)> = keys.into_iter().zip(items).collect();
+    pairs.sort_by(|a, b| compare(&a.0, &b.0));
+    pairs.into_iter().map(|(_, v)| v).collect()
+}
+
+fn call(name: &str, args: &[Node], input: &Value) -> Result<Vec<Value>> {
+    let arity = |n: usize| -> Result<()> {
+        if args.len() != n {
+            bail!("{} 需要 {} 个参数", name, n);
+        }
+        Ok(())
+    };
+    let array = || -> Result<&Vec<Value>> {
+        input.as_array().with_context(|| format!("{} 只能用于数组，实际为 {}", name, type_name(input)))
+    };
+    let text = || -> Result<&str> {
+        input.as_str().with_context(|| format!("{} 只能用于字符串，实际为 {}", name, type_name(input)))
+    };
+    Ok(vec![match name {
+        "empty" => return Ok(Vec::new()),
+        "not" => Value::Bool(!truthy(input)),
+        "length" => match input {
+            Value::Null => number(0.0),
+            Value::Bool(_) => bail!("boolean 没有 length"),
+            Value::Number(n) => number(n.as_f64().unwrap_or(0.0).abs()),
+            Value::String(s) => number(s.chars().count() as f64),
+            Value::Array(a) => number(a.len() as f64),
+            Value::Object(m) => number(m.len() as f64),
+        },
+        "keys" => match input {
+            Value::Object(m) => {
+                let mut keys: Vec<&String> = m.keys().collect();
+                keys.sort();
+                Value::Array(keys.into_iter().map(|k| Value::String(k.clone())).collect())
+            }
+            Value::Array(a) => Value::Array((0..a.len()).map(|i| number(i as f64)).collect()),
+            other => bail!("{} 没有 keys", type_name(other)),
+        },
+        "has" => {
+            arity(1)?;
+            let key = one(&args[0], input)?;
+            Value::Bool(match (input, &key) {
+                (Value::Object(m), Value::String(k)) => m.contains_key(k),
+                (Value::Array(a), Value::Number(n)) => n.as_f64().is_some_and(|n| n >= 0.0 && (n as usize) < a.len()),
+                _ => bail!("{} 不能用 {} 调用 has", type_name(input), type_name(&key)),
+            })
+        }
+        "select" => {
+            arity(1)?;
+            return Ok(eval(&args[0], input)?.iter().filter(|v| truthy(v)).map(|_| input.clone()).collect());
+        }
+        "map" => {
+            arity(1)?;
+            let mut out = Vec::new();
+            for v in iterate(input)? {
+                out.extend(eval(&args[0], &v)?);
+            }
+            Value::Array(out)
+        }
+        "first" if args.is_empty() => index(input, &number(0.0))?,
+        "last" if args.is_empty() => index(input, &number(-1.0))?,
+        "first" => {
+            arity(1)?;
+            return Ok(eval(&args[0], input)?.into_iter().take(1).collect());
+        }
+        "reverse" => match input {
+            Value::String(s) => Value::String(s.chars().rev().collect()),
+            Value::Null => Value::Array(Vec::new()),
+            _ => Value::Array(array()?.iter().rev().cloned().collect()),
+        },
+        "sort" => {
+            let items = array()?.clone();
+            Value::Array(sorted_by_keys(items.clone(), items))
+        }
+        "sort_by" => {
+            arity(1)?;
+            let items = array()?.clone();
+            let keys = items.iter().map(|v| eval(&args[0], v).map(Value::Array)).collect::<Result<Vec<_>>>()?;
+            Value::Array(sorted_by_keys(items, keys))
+        }
+        "unique" => {
+            let mut items = array()?.clone();
+            items.sort_by(compare);
+            items.dedup();
+            Value::Array(items)
+        }
+        "min" | "max" => {
+            let items = array()?;
+            let pick = if name == "min" { items.iter().min_by(|a, b| compare(a, b)) } else { items.iter().max_by(|a, b| compare(a, b)) };
+            pick.cloned().unwrap_or(Value::Null)
+        }
+        "add" => {
+            let mut acc = Value::Null;
+            for v in iterate(input)? {
+                acc = arith(Op::Add, &acc, &v)?;
+            }
+            acc
+        }
+        "join" => {
+            arity(1)?;
+            let sep = string_arg(name, &args[0], input)?;
+            let parts = array()?
+                .iter()
+                .map(|v| match v {
+                    Value::Null => Ok(String::new()),
+                    Value::String(s) => Ok(s.clone()),
+                    Value::Number(_) | Value::Bool(_) => Ok(v.to_string()),
+                    other => bail!("join 不能连接 {}", type_name(other)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Value::String(parts.join(&sep))
+        }
+        "contains" => {
+            arity(1)?;
+            Value::Bool(contains(input, &one(&args[0], input)?))
+        }
+        "test" => {
+            arity(1)?;
+            let re = regex(&string_arg(name, &args[0], input)?)?;
+            Value::Bool(re.is_match(text()?))
+        }
+        "startswith" => {
+            arity(1)?;
+            Value::Bool(text()?.starts_with(&string_arg(name, &args[0], input)?))
+        }
+        "endswith" => {
+            arity(1)?;
+            Value::Bool(text()?.ends_with(&string_arg(name, &args[0], input)?))
+        }
+        "ascii_downcase" => Value::String(text()?.to_ascii_lowercase()),
+        "ascii_upcase" => Value::String(text()?.to_ascii_uppercase()),
+        "tostring" => match input {
+            Value::String(s) => Value::String(s.clone()),
+            other => Value::String(other.to_string()),
+        },
+        "tonumber" => match input {
+            Value::Number(_) => input.clone(),
+            Value::String(s) => number(s.trim().parse().with_context(|| format!("无法转换为数字: {}", s))?),
+            other => bail!("{} 不能转换为数字", type_name(other)),
+        },
+        "type" => Value::String(type_name(input).to_string()),
+        _ => bail!("不支持的函数: {}", name),
+    }])
+}
+
+impl Expr {
+    pub fn eval(&self, input: &Value) -> Result<Vec<Value>> {
+        eval(&self.0, input)
+    }
+}
+
+/// The query's results as printed: one per line, strings bare
+pub fn render(results: &[Value]) -> Result<String> {
+    let mut lines = Vec::new();
+    for v in results {
+        lines.push(match v {
+            Value::String(s) => s.clone(),
+            other => serde_json::to_string_pretty(other)?,
+        });
+    }
+    Ok(lines.join("\n"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn doc() -> Value {
+        json!({
+            "code": "ABP-123",
+            "title": "T & <x>",
+            "rating": 4.5,
+            "actors": ["Aoi", "Yui"],
+            "magnets": [
+                { "size": 2.5, "seeders": 10, "name": "abp-123-C" },
+                { "size": 1.2, "seeders": 30, "name": "abp-123" }
+            ],
+            "meta": { "studio": "Prestige", "year": 2024 },
+            "empty": null
+        })
+    }
+
+    /// Results as `jq -c` prints them, on one line
+    fn run(src: &str, input: &Value) -> Result<String> {
+        let results = parse(src)?.eval(input)?;
+        Ok(results.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "))
+    }
+
+    /// Expected outputs are what jq itself prints for the same query and input
+    #[test]
+    fn matches_jq() {
+        let cases: &[(&str, &str)] = &[
+            (".code", "\"ABP-123\""),
+            (".\"code\"", "\"ABP-123\""),
+            (".meta.studio", "\"Prestige\""),
+            (".missing", "null"),
+            (".missing.deeper", "null"),
+            (".actors[0]", "\"Aoi\""),
+            (".actors[-1]", "\"Yui\""),
+            (".actors[5]", "null"),
+            (".actors[0:1]", "[\"Aoi\"]"),
+            (".actors[1:]", "[\"Yui\"]"),
+            (".actors[:-1]", "[\"Aoi\"]"),
+            (".code[0:3]", "\"ABP\""),
+            (".actors[]", "\"Aoi\" \"Yui\""),
+            (".meta[]", "\"Prestige\" 2024"),
+            (".magnets[].name", "\"abp-123-C\" \"abp-123\""),
+            (".magnets | length", "2"),
+            (".code, .rating", "\"ABP-123\" 4.5"),
+            ("[.actors[] | ascii_upcase]", "[\"AOI\",\"YUI\"]"),
+            ("{code, n: (.actors | length)}", "{\"code\":\"ABP-123\",\"n\":2}"),
+            ("{(.code): .rating}", "{\"ABP-123\":4.5}"),
+            (".magnets[] | select(.seeders > 20) | .name", "\"abp-123\""),
+            (".magnets | map(.size) | add", "3.7"),
+            (".magnets | sort_by(.seeders) | map(.seeders)", "[10,30]"),
+            (".magnets | sort_by(-.seeders) | first | .name", "\"abp-123\""),
+            (".rating == 4.5", "true"),
+            (".rating != 4.5", "false"),
+            (".rating < 5 and .code == \"ABP-123\"", "true"),
+            (".empty or false", "false"),
+            ("not", "false"),
+            (".empty // \"none\"", "\"none\""),
+            (".missing // .code", "\"ABP-123\""),
+            ("(.rating // 0) * 2", "9"),
+            (".meta.year - 2000", "24"),
+            ("10 / 4", "2.5"),
+            ("1 + 2 * 3", "7"),
+            ("(1 + 2) * 3", "9"),
+            (".code + \"-C\"", "\"ABP-123-C\""),
+            (".actors + [\"Mei\"]", "[\"Aoi\",\"Yui\",\"Mei\"]"),
+            ("[1,2,3,2] - [2]", "[1,3]"),
+            (".meta + {year: 2025}", "{\"studio\":\"Prestige\",\"year\":2025}"),
+            (".actors | join(\", \")", "\"Aoi, Yui\""),
+            (".meta | keys", "[\"studio\",\"year\"]"),
+            (".meta | has(\"studio\")", "true"),
+            (".actors | has(1)", "true"),
+            (".actors | contains([\"Ao\"])", "true"),
+            (".title | contains(\"&\")", "true"),
+            (".code | test(\"^ABP\")", "true"),
+            (".code | startswith(\"AB\")", "true"),
+            (".code | endswith(\"3\")", "true"),
+            (".code | ascii_downcase", "\"abp-123\""),
+            (".rating | tostring", "\"4.5\""),
+            ("\"42\" | tonumber", "42"),
+            ("[.[] | type]", "[\"string\",\"string\",\"number\",\"array\",\"array\",\"object\",\"null\"]"),
+            (".actors | reverse", "[\"Yui\",\"Aoi\"]"),
+            (".code | reverse", "\"321-PBA\""),
+            ("[3,1,2] | sort", "[1,2,3]"),
+            ("[1,1,2,null,\"a\",false] | unique", "[null,false,1,2,\"a\"]"),
+            ("[3,1,2] | min", "1"),
+            ("[3,1,2] | max", "3"),
+            ("[] | max", "null"),
+            (".actors | first", "\"Aoi\""),
+            (".actors | last", "\"Yui\""),
+            ("first(.actors[])", "\"Aoi\""),
+            ("[.actors[] | empty]", "[]"),
+            (".code | length", "7"),
+            (".meta | length", "2"),
+            (".empty | length", "0"),
+            ("-3 | length", "3"),
+            ("[.magnets[] | {n: .name, s: .size}]", "[{\"n\":\"abp-123-C\",\"s\":2.5},{\"n\":\"abp-123\",\"s\":1.2}]"),
+            ("[.actors[], .code] | length", "3"),
+            ("\"a\\tb\"", "\"a\\tb\""),
+            ("[1, 2.5, 1e3]", "[1,2.5,1000]"),
+            ("[2.5E-1, 1e+2]", "[0.25,100]"),
+            ("{\"a b\": 1}.\"a b\"", "1"),
+            ("[null, false, 0, \"\"] | map(not)", "[true,true,false,false]"),
+        ];
+        let input = doc();
+        for (src, want) in cases {
+            match run(src, &input) {
+                Ok(got) => assert_eq!(got, *want, "query {:?}", src),
+                Err(e) => panic!("query {:?} failed: {:#}", src, e),
+            }
+        }
+        assert_eq!(run(".", &input).unwrap(), input.to_string());
+    }
+
+    #[test]
+    fn rejects() {
+        let syntax = ["", ".[", ".a |", "{code", "[1,]", "(.a", ".a b", "\"open", ".a ==", "@"];
+        for src in syntax {
+            assert!(parse(src).is_err(), "parse({:?}) should fail", src);
+        }
+        let runtime = [
+            ".code[0]",
+            ".actors.name",
+            ".rating[]",
+            ".code | keys",
+            "true | length",
+            ".actors | join(1)",
+            "1 / 0",
+            ".code - 1",
+            "{(.rating): 1}",
+            ".code | tonumber",
+            ".rating | test(\"a\")",
+            ".code | nosuchfn",
+            ".actors | has(\"a\")",
+            "select(1; 2)",
+        ];
+        let input = doc();
+        for src in runtime {
+            assert!(run(src, &input).is_err(), "{:?} should fail", src);
+        }
+    }
+
+    #[test]
+    fn renders_strings_bare() {
+        let out = render(&[json!("ABP-123"), json!(4.5), json!({ "a": [1] })]).unwrap();
+        assert_eq!(out, "ABP-123\n4.5\n{\n  \"a\": [\n    1\n  ]\n}");
+        assert_eq!(render(&[]).unwrap(), "");
+    }
+}
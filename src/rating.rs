@@ -0,0 +1,51 @@
+//! Ratings as each source shows them, normalized to a 0–5 scale, and the weighted average
+//! that `AvDetail::rating` carries. Weights come from `[metadata.rating_weights]`.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::config;
+use crate::types::{AvDetail, SourceRating};
+
+/// Scale everything is normalized to, JavDB's and DMM's own
+pub const SCALE: f32 = 5.0;
+
+/// Vote counts next to a score: "由1234人評價", "1,234 users", "(56 votes)"
+static VOTES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)由\s*([\d,]+)\s*人|([\d,]+)\s*(?:人評價|人评价|人評分|人评分|users?|votes?)").unwrap());
+
+pub fn votes_in(text: &str) -> Option<u32> {
+    let caps = VOTES.captures(text)?;
+    caps.get(1).or(caps.get(2))?.as_str().replace(',', "").parse().ok()
+}
+
+/// `raw` out of `scale` from `source`; `None` for scores outside the scale (unrated titles
+/// often show 0)
+pub fn from_source(source: &str, raw: f32, scale: f32, votes: Option<u32>) -> Option<SourceRating> {
+    if !(raw > 0.0 && raw <= scale) {
+        return None;
+    }
+    Some(SourceRating { source: source.to_string(), value: raw * SCALE / scale, raw, scale, votes })
+}
+
+/// Add the ratings of sources `into` has none from
+pub fn merge(into: &mut Vec<SourceRating>, from: Vec<SourceRating>) {
+    for r in from {
+        if !into.iter().any(|x| x.source == r.source) {
+            into.push(r);
+        }
+    }
+}
+
+/// Set `d.rating` to the weighted average of `d.ratings`. Sources missing from the weights
+/// count 1.0, weight 0 leaves a source out. Without per-source ratings `d.rating` is kept.
+pub fn aggregate(d: &mut AvDetail) {
+    let weights = &config::get().metadata.rating_weights;
+    let (sum, total) = d.ratings.iter().fold((0.0, 0.0), |(sum, total), r| {
+        let w = weights.get(&r.source).copied().unwrap_or(1.0).max(0.0);
+        (sum + r.value * w, total + w)
+    });
+    if total > 0.0 {
+        d.rating = Some((sum / total * 100.0).round() / 100.0);
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{bail, Result};
+use colored::Colorize;
+use scraper::{ElementRef, Html};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::code;
+use crate::scraper::{self as av_scraper, Source};
+use crate::selectors;
+use crate::sources::{javlibrary, mock};
+use crate::translate::{self, Lang};
+use crate::types::Review;
+use crate::util;
+
+#[derive(Debug, Serialize)]
+struct ReviewsOutput {
+    code: String,
+    reviews: Vec<Review>,
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn javdb_review(item: ElementRef) -> Option<Review> {
+    let text = collapse(&item.select(selectors::javdb("review_content")).next()?.text().collect::<Vec<_>>().join(" "));
+    if text.is_empty() {
+        return None;
+    }
+    // The title row also holds the stars and time; the name is its first text
+    let author = item
+        .select(selectors::javdb("review_author"))
+        .next()
+        .and_then(|t| t.text().map(str::trim).find(|s| !s.is_empty()))
+        .unwrap_or_default()
+        .to_string();
+    let stars = item.select(selectors::javdb("review_star")).count();
+    Some(Review {
+        source: "javdb".to_string(),
+        author,
+        date: item.select(selectors::javdb("review_time")).next().map(|t| collapse(&t.text().collect::<String>())),
+        score: (stars > 0).then_some(stars as f32),
+        text,
+        translation: None,
+    })
+}
+
+/// Up to `limit` of the newest reviews on the JavDB video page
+async fn javdb(code: &str, limit: usize) -> Result<Vec<Review>> {
+    let c = av_scraper::client();
+    let url = format!("{}/reviews/lastest", av_scraper::javdb_video_url(&c, code).await?);
+    util::debug(format!("JavDB reviews: {}", url));
+    let body = av_scraper::get_text(&c, &url).await?;
+    let doc = Html::parse_document(&body);
+    Ok(doc.select(selectors::javdb("review_item")).filter_map(javdb_review).take(limit).collect())
+}
+
+/// Reviews from `--source`, or JavDB and JavLibrary side by side; one failing only warns
+async fn fetch(code: &str, limit: usize) -> Result<Vec<Review>> {
+    if mock::is_enabled() {
+        let mut per_source: HashMap<String, usize> = HashMap::new();
+        let mut reviews = mock::reviews(code)?;
+        reviews.retain(|r| {
+            let n = per_source.entry(r.source.clone()).or_default();
+            *n += 1;
+            *n <= limit
+        });
+        return Ok(reviews);
+    }
+    match av_scraper::forced_source() {
+        Some(Source::Javdb) => return javdb(code, limit).await,
+        Some(Source::Javlibrary) => return javlibrary::reviews(code, limit).await,
+        Some(other) => bail!("{} 没有用户评论，评论仅支持 javdb / javlibrary", other.as_str()),
+        None => {}
+    }
+    let (db, jl) = tokio::join!(javdb(code, limit), javlibrary::reviews(code, limit));
+    let mut out = Vec::new();
+    let mut errors = Vec::new();
+    for (name, res) in [("JavDB", db), ("JavLibrary", jl)] {
+        match res {
+            Ok(r) => out.extend(r),
+            Err(e) => {
+                eprintln!("[WARN] 获取 {} 评论失败: {:#}", name, e);
+                errors.push(name);
+            }
+        }
+    }
+    if out.is_empty() && errors.len() == 2 {
+        bail!("无法获取 {} 的评论", code);
+    }
+    Ok(out)
+}
+
+/// `av reviews CODE`: user reviews, at most `limit` per source, optionally translated
+pub async fn run(code: &str, limit: usize, to: Option<Lang>, json: bool) -> Result<()> {
+    let code = code::normalize(code);
+    let mut reviews = fetch(&code, limit).await?;
+    if let (Some(lang), false) = (to, reviews.is_empty()) {
+        let texts: Vec<String> = reviews.iter().map(|r| r.text.clone()).collect();
+        for (r, t) in reviews.iter_mut().zip(translate::texts(&texts, lang).await?) {
+            r.translation = Some(t);
+        }
+    }
+    if json {
+        util::print_output(&ReviewsOutput { code, reviews }, true);
+        return Ok(());
+    }
+    if reviews.is_empty() {
+        println!("{}", format!("{} 暂无用户评论", code).yellow());
+        return Ok(());
+    }
+    println!("{}", format!("{} 的用户评论（{}）", code, reviews.len()).bold());
+    for r in &reviews {
+        let author = if r.author.is_empty() { "匿名" } else { r.author.as_str() };
+        let score = r.score.map(|s| format!(" {}", "★".repeat(s.round() as usize))).unwrap_or_default();
+        let date = r.date.as_deref().map(|d| format!("  {}", d)).unwrap_or_default();
+        println!();
+        println!("{} {}{}{}", format!("[{}]", r.source).dimmed(), author.cyan(), score.yellow(), date.dimmed());
+        println!("{}", r.text);
+        if let Some(t) = &r.translation {
+            println!("{} {}", "译：".green(), t);
+        }
+    }
+    Ok(())
+}
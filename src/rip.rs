@@ -0,0 +1,349 @@
+//! `av rip`: download the stream `av view` would play, without a torrent.
+//!
+//! Unencrypted HLS is fetched segment by segment into a hidden parts directory next to
+//! the output and remuxed with ffmpeg; segments already on disk are skipped, so an
+//! interrupted rip picks up where it stopped. A direct MP4 is fetched with HTTP ranges
+//! into a `.part` file for the same reason. Encrypted or byte-range playlists, and any
+//! case ffmpeg can't handle, go to yt-dlp instead.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use reqwest::header::{HeaderMap, HeaderValue, RANGE};
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use which::which;
+
+use crate::cancel;
+use crate::hooks;
+use crate::scraper;
+use crate::stream::{self, Stream};
+use crate::util;
+
+/// Which HLS variant to rip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Best,
+    Worst,
+    /// The best variant at most this many lines tall
+    Max(u32),
+}
+
+impl FromStr for Quality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "best" => Ok(Quality::Best),
+            "worst" => Ok(Quality::Worst),
+            other => other
+                .trim_end_matches('p')
+                .parse::<u32>()
+                .map(Quality::Max)
+                .map_err(|_| format!("无效的画质: {}（可用 best、worst 或 1080、720 等）", s)),
+        }
+    }
+}
+
+impl Quality {
+    /// yt-dlp `-f` expression for the same choice
+    fn ytdlp_format(self) -> String {
+        match self {
+            Quality::Best => "bv*+ba/b".to_string(),
+            Quality::Worst => "wv*+wa/w".to_string(),
+            Quality::Max(h) => format!("bv*[height<={h}]+ba/b[height<={h}]/wv*+ba/w", h = h),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Variant {
+    bandwidth: u64,
+    height: Option<u32>,
+    url: String,
+}
+
+#[derive(Debug, Default)]
+struct MediaPlaylist {
+    /// `#EXT-X-MAP` initialization segment of fMP4 streams
+    init: Option<String>,
+    segments: Vec<String>,
+    /// Encryption or byte ranges, which the native downloader doesn't do
+    unsupported: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+struct RipOutput {
+    code: String,
+    file: PathBuf,
+    stream: String,
+    /// "hls", "http" or "yt-dlp"
+    method: &'static str,
+}
+
+fn join(base: &str, uri: &str) -> String {
+    Url::parse(base).and_then(|b| b.join(uri.trim())).map(|u| u.to_string()).unwrap_or_else(|_| uri.trim().to_string())
+}
+
+/// `KEY=value` from an HLS attribute list, quotes stripped
+fn attr<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = line.split_once(':')?.1;
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, ch) in rest.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&rest[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&rest[start..]);
+    parts.into_iter().find_map(|p| p.split_once('=').filter(|(k, _)| k.trim() == key).map(|(_, v)| v.trim().trim_matches('"')))
+}
+
+/// Variants of a master playlist; empty for a media playlist
+fn parse_master(body: &str, base: &str) -> Vec<Variant> {
+    let mut out = Vec::new();
+    let mut lines = body.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        if !line.starts_with("#EXT-X-STREAM-INF") {
+            continue;
+        }
+        let Some(uri) = lines.by_ref().find(|l| !l.is_empty() && !l.starts_with('#')) else { break };
+        out.push(Variant {
+            bandwidth: attr(line, "BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0),
+            height: attr(line, "RESOLUTION").and_then(|r| r.split_once('x')).and_then(|(_, h)| h.parse().ok()),
+            url: join(base, uri),
+        });
+    }
+    out
+}
+
+fn choose(variants: &[Variant], q: Quality) -> Option<&Variant> {
+    let key = |v: &&Variant| (v.height.unwrap_or(0), v.bandwidth);
+    match q {
+        Quality::Best => variants.iter().max_by_key(key),
+        Quality::Worst => variants.iter().min_by_key(key),
+        // Nothing small enough: the smallest there is
+        Quality::Max(h) => variants
+            .iter()
+            .filter(|v| v.height.is_some_and(|vh| vh <= h))
+            .max_by_key(key)
+            .or_else(|| variants.iter().min_by_key(key)),
+    }
+}
+
+fn parse_media(body: &str, base: &str) -> MediaPlaylist {
+    let mut p = MediaPlaylist::default();
+    for line in body.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.starts_with("#EXT-X-KEY") {
+            if attr(line, "METHOD").is_some_and(|m| m != "NONE") {
+                p.unsupported = Some("加密的 HLS");
+            }
+        } else if line.starts_with("#EXT-X-BYTERANGE") {
+            p.unsupported = Some("按字节范围切片的 HLS");
+        } else if line.starts_with("#EXT-X-MAP") {
+            p.init = attr(line, "URI").map(|u| join(base, u));
+        } else if !line.starts_with('#') {
+            p.segments.push(join(base, line));
+        }
+    }
+    p
+}
+
+/// Fetch `url` into `path`, resuming a partial file with a Range request
+async fn fetch_resumable(s: &Stream, url: &str, path: &Path) -> Result<()> {
+    let have = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let mut extra = HeaderMap::new();
+    if have > 0 {
+        extra.insert(RANGE, HeaderValue::from_str(&format!("bytes={}-", have))?);
+    }
+    let mut resp = scraper::send_from(&scraper::client(), url, &s.referer, extra).await?;
+    let append = match resp.status() {
+        StatusCode::PARTIAL_CONTENT => true,
+        // Already complete
+        StatusCode::RANGE_NOT_SATISFIABLE if have > 0 => return Ok(()),
+        st if st.is_success() => false,
+        st => bail!("下载失败 {}: HTTP {}", url, st),
+    };
+    if have > 0 {
+        util::debug(format!("rip: {} {} from byte {}", if append { "resuming" } else { "restarting" }, url, have));
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .with_context(|| format!("写入失败: {}", path.display()))?;
+    while let Some(chunk) = resp.chunk().await? {
+        file.write_all(&chunk).with_context(|| format!("写入失败: {}", path.display()))?;
+        cancel::check()?;
+    }
+    Ok(())
+}
+
+/// Download every segment missing from `parts` (`--jobs` at a time), then join them in order
+async fn download_segments(s: &Stream, p: &MediaPlaylist, parts: &Path, joined: &Path) -> Result<()> {
+    std::fs::create_dir_all(parts).with_context(|| format!("创建目录失败: {}", parts.display()))?;
+    let urls: Vec<String> = p.init.iter().chain(&p.segments).cloned().collect();
+    let total = urls.len();
+    let present = (0..total).filter(|i| parts.join(format!("{:05}.seg", i)).exists()).count();
+    if present > 0 {
+        eprintln!("继续下载：已有 {}/{} 个分片", present, total);
+    }
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, url) in urls.iter().cloned().enumerate() {
+        let done = parts.join(format!("{:05}.seg", i));
+        if done.exists() {
+            continue;
+        }
+        let (permits, s) = (permits.clone(), s.clone());
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            // Written under a temporary name so only complete segments count on resume
+            let tmp = done.with_extension("tmp");
+            let result = fetch_resumable(&s, &url, &tmp).await.and_then(|_| {
+                std::fs::rename(&tmp, &done).with_context(|| format!("写入失败: {}", done.display()))
+            });
+            (i, result)
+        });
+    }
+    let mut failed = 0;
+    for (i, result) in tasks.join_all().await {
+        if let Err(e) = result {
+            failed += 1;
+            util::debug(format!("rip: segment {} failed: {:#}", i, e));
+        }
+    }
+    if cancel::is_cancelled() {
+        bail!("已取消；再次运行相同命令会从 {} 继续", parts.display());
+    }
+    if failed > 0 {
+        bail!("{} 个分片下载失败；再次运行相同命令只会补齐缺少的分片", failed);
+    }
+    let mut out = std::fs::File::create(joined).with_context(|| format!("写入失败: {}", joined.display()))?;
+    for i in 0..total {
+        let seg = std::fs::read(parts.join(format!("{:05}.seg", i)))?;
+        out.write_all(&seg).with_context(|| format!("写入失败: {}", joined.display()))?;
+    }
+    Ok(())
+}
+
+async fn remux(input: &Path, out: &Path) -> Result<()> {
+    let status = tokio::process::Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-c", "copy"])
+        .arg(out)
+        .stdin(Stdio::null())
+        .status()
+        .await
+        .context("启动 ffmpeg 失败")?;
+    if !status.success() {
+        bail!("ffmpeg 转封装失败，退出码: {:?}", status.code());
+    }
+    Ok(())
+}
+
+async fn ytdlp(s: &Stream, quality: Quality, dir: &Path, code: &str) -> Result<PathBuf> {
+    if which("yt-dlp").is_err() {
+        bail!("未检测到 yt-dlp，请先安装: brew install yt-dlp");
+    }
+    let template = dir.join(format!("{}.%(ext)s", code));
+    let status = tokio::process::Command::new("yt-dlp")
+        .args(["--continue", "--no-part", "--merge-output-format", "mp4", "-f"])
+        .arg(quality.ytdlp_format())
+        .arg("--referer")
+        .arg(&s.referer)
+        .arg("--user-agent")
+        .arg(&s.user_agent)
+        .arg("-o")
+        .arg(&template)
+        .arg(&s.url)
+        .stdin(Stdio::null())
+        // Keeps stdout clean for --json
+        .stdout(std::io::stderr())
+        .status()
+        .await
+        .context("启动 yt-dlp 失败")?;
+    if !status.success() {
+        bail!("yt-dlp 下载失败，退出码: {:?}", status.code());
+    }
+    Ok(dir.join(format!("{}.mp4", code)))
+}
+
+/// Rip `s` into `dir/<code>.mp4`, returning the file and the method used
+async fn rip(s: &Stream, quality: Quality, dir: &Path, code: &str) -> Result<(PathBuf, &'static str)> {
+    let out = dir.join(format!("{}.mp4", code));
+    let has_ffmpeg = which("ffmpeg").is_ok();
+    if !s.url.contains(".m3u8") {
+        let part = dir.join(format!("{}.mp4.part", code));
+        fetch_resumable(s, &s.url, &part).await?;
+        std::fs::rename(&part, &out).with_context(|| format!("写入失败: {}", out.display()))?;
+        return Ok((out, "http"));
+    }
+
+    let c = scraper::client();
+    let body = scraper::get_text_from(&c, &s.url, &s.referer).await?;
+    let variants = parse_master(&body, &s.url);
+    let (media_url, body) = match choose(&variants, quality) {
+        Some(v) => {
+            util::debug(format!("rip: variant {:?} ({} bps) of {}", v.height, v.bandwidth, variants.len()));
+            (v.url.clone(), scraper::get_text_from(&c, &v.url, &s.referer).await?)
+        }
+        None => (s.url.clone(), body),
+    };
+    let playlist = parse_media(&body, &media_url);
+    let reason = match (playlist.unsupported, has_ffmpeg) {
+        _ if playlist.segments.is_empty() => Some("播放列表中没有分片"),
+        (Some(r), _) => Some(r),
+        (None, false) => Some("未检测到 ffmpeg"),
+        (None, true) => None,
+    };
+    if let Some(reason) = reason {
+        eprintln!("{}，改用 yt-dlp", reason);
+        return Ok((ytdlp(s, quality, dir, code).await?, "yt-dlp"));
+    }
+    let parts = dir.join(format!(".{}.parts", code));
+    let joined = parts.join("joined.ts");
+    eprintln!("下载 {} 个分片 → {}", playlist.segments.len(), out.display());
+    download_segments(s, &playlist, &parts, &joined).await?;
+    remux(&joined, &out).await?;
+    std::fs::remove_dir_all(&parts).with_context(|| format!("删除失败: {}", parts.display()))?;
+    Ok((out, "hls"))
+}
+
+/// `av rip`: resolve the stream of `code` and download it into `out`
+pub async fn run(code: &str, out: PathBuf, quality: Quality, pick: Option<usize>, json: bool) -> Result<()> {
+    let candidates = scraper::play_candidates(code).await?;
+    let tried = match pick {
+        Some(n) if n == 0 || n > candidates.len() => bail!("--pick 超出范围：共 {} 个候选地址", candidates.len()),
+        Some(n) => &candidates[n - 1..n],
+        None => &candidates[..],
+    };
+    let s = stream::first(tried).await?;
+    let code = crate::code::normalize(code);
+    if util::dry_run_skip(format!("将下载 {} 的视频流到 {}: {}", code, out.join(format!("{}.mp4", code)).display(), s.url)) {
+        return Ok(());
+    }
+    hooks::validate("rip", &serde_json::json!({ "code": code, "stream": s.url, "out": out })).await?;
+    std::fs::create_dir_all(&out).with_context(|| format!("创建目录失败: {}", out.display()))?;
+    let (file, method) = rip(&s, quality, &out, &code).await?;
+    if json {
+        util::print_output(&RipOutput { code, file, stream: s.url, method }, true);
+        return Ok(());
+    }
+    println!("{} {}", "已保存".green().bold(), file.display());
+    Ok(())
+}
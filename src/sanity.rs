@@ -0,0 +1,167 @@
+//! Checks on fetched bodies before they reach a parser: size limits, and challenge, block
+//! and error pages that arrive in place of the real page. Without them a Cloudflare
+//! interstitial parses into an `AvDetail` with every field empty and looks like a hit.
+//!
+//! The errors are a `FetchError` inside the `anyhow::Error`, for callers that want to tell
+//! a block apart from a broken page or a missing title (`err.downcast_ref::<FetchError>()`).
+
+use regex::Regex;
+use std::fmt;
+use std::sync::LazyLock;
+
+/// HTML pages; the largest real listing is a few hundred KB
+pub const MAX_PAGE_BYTES: usize = 8 << 20;
+/// Covers, previews and other binary bodies
+pub const MAX_BINARY_BYTES: usize = 32 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchError {
+    /// The site (or the CDN in front of it) answered with a challenge or block page
+    Blocked { url: String, reason: String, status: Option<u16> },
+    /// A body that can't be the page asked for: empty, oversized, a server error page
+    Unexpected { url: String, reason: String, status: Option<u16> },
+    /// The site answered, but has no entry for what was asked
+    NotFound { source: String, reason: String },
+}
+
+impl FetchError {
+    pub fn not_found(source: &str, reason: impl Into<String>) -> Self {
+        FetchError::NotFound { source: source.to_string(), reason: reason.into() }
+    }
+
+    /// The HTTP status (or the one an error page shows), when it says something
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            FetchError::Blocked { status, .. } | FetchError::Unexpected { status, .. } => *status,
+            FetchError::NotFound { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Blocked { url, reason, .. } => write!(
+                f,
+                "请求被拦截（{}）: {}；可尝试更换代理出口、用 av cookies import 导入浏览器 Cookie，或开启 [network] impersonate",
+                reason, url
+            ),
+            FetchError::Unexpected { url, reason, .. } => write!(f, "响应异常（{}）: {}", reason, url),
+            FetchError::NotFound { reason, .. } => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+fn blocked(url: &str, reason: impl Into<String>) -> FetchError {
+    FetchError::Blocked { url: url.to_string(), reason: reason.into(), status: None }
+}
+
+pub fn unexpected(url: &str, reason: impl Into<String>) -> FetchError {
+    FetchError::Unexpected { url: url.to_string(), reason: reason.into(), status: None }
+}
+
+/// Read `resp`'s body, giving up once it passes `limit` bytes
+pub async fn read_limited(mut resp: reqwest::Response, limit: usize) -> anyhow::Result<Vec<u8>> {
+    let url = resp.url().to_string();
+    let too_large = || unexpected(&url, format!("响应超过 {} MB", limit >> 20));
+    if resp.content_length().is_some_and(|n| n > limit as u64) {
+        return Err(too_large().into());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large().into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// The `<title>` text, lowercased
+fn title_of(body: &str) -> Option<String> {
+    let lower = body.to_lowercase();
+    let start = lower.find("<title")?;
+    let open_end = start + lower[start..].find('>')? + 1;
+    let close = open_end + lower[open_end..].find("</title")?;
+    Some(lower[open_end..close].trim().to_string())
+}
+
+/// A challenge/block page, or the server's own error page, served instead of `url`.
+/// Only the title and markers unique to interstitials are looked at: real pages embed
+/// Turnstile widgets and mention Cloudflare too.
+pub fn check_page(url: &str, body: &str) -> Result<(), FetchError> {
+    if body.trim().is_empty() {
+        return Err(unexpected(url, "空响应"));
+    }
+    if body.contains("window._cf_chl_opt") || body.contains("cf-browser-verification") {
+        return Err(blocked(url, "Cloudflare 验证页"));
+    }
+    let Some(title) = title_of(body) else { return Ok(()) };
+    let challenges = [
+        ("just a moment", "Cloudflare 验证页"),
+        ("checking your browser", "浏览器验证页"),
+        ("attention required", "Cloudflare 拦截页"),
+        ("ddos-guard", "DDoS-Guard 验证页"),
+        ("access denied", "拒绝访问页"),
+    ];
+    if let Some((_, reason)) = challenges.iter().find(|(marker, _)| title.contains(marker)) {
+        return Err(blocked(url, *reason));
+    }
+    // "502 Bad Gateway", or Cloudflare's "javdb.com | 522: Connection timed out"
+    let status_title = title.split(" | ").last().unwrap_or(&title).trim();
+    let code = status_title.get(..3).filter(|c| c.chars().all(|ch| ch.is_ascii_digit()));
+    if let Some(code) = code.filter(|c| c.starts_with('5') || c.starts_with('4')) {
+        if status_title[3..].starts_with([' ', ':']) {
+            let status = code.parse().ok();
+            return Err(FetchError::Unexpected { url: url.to_string(), reason: format!("错误页 {}", code), status });
+        }
+    }
+    Ok(())
+}
+
+static META_CHARSET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?i)<meta[^>]+charset=["']?([A-Za-z0-9_-]+)"#).unwrap());
+
+/// `bytes` as text in the charset the Content-Type header or a `<meta>` tag names (some
+/// Japanese studio sites still serve EUC-JP or Shift_JIS), UTF-8 otherwise
+fn decode(bytes: &[u8], content_type: Option<&str>) -> String {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(2048)]);
+    let label = content_type
+        .and_then(|ct| ct.split(';').find_map(|p| p.trim().strip_prefix("charset=")).map(|c| c.trim_matches('"').to_string()))
+        .or_else(|| META_CHARSET.captures(&head).map(|c| c[1].to_string()));
+    match label.and_then(|l| encoding_rs::Encoding::for_label(l.as_bytes())) {
+        Some(enc) if enc != encoding_rs::UTF_8 => enc.decode(bytes).0.into_owned(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// A body is still checked when the status is 403/429/503, since that's how challenge
+/// pages arrive; other failures keep reqwest's status error
+pub async fn page_text(resp: reqwest::Response) -> anyhow::Result<String> {
+    let status = resp.status().as_u16();
+    let resp = if matches!(status, 403 | 429 | 503) { resp } else { resp.error_for_status()? };
+    let url = resp.url().to_string();
+    let content_type = resp.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let body = decode(&read_limited(resp, MAX_PAGE_BYTES).await?, content_type.as_deref());
+    let success = (200..300).contains(&status);
+    match check_page(&url, &body) {
+        Ok(()) | Err(FetchError::Unexpected { .. }) if !success => {
+            Err(FetchError::Blocked { url, reason: format!("HTTP {}", status), status: Some(status) }.into())
+        }
+        Err(FetchError::Blocked { url, reason, .. }) if !success => Err(FetchError::Blocked { url, reason, status: Some(status) }.into()),
+        Err(e) => Err(e.into()),
+        Ok(()) => Ok(body),
+    }
+}
+
+/// A non-empty binary body within `MAX_BINARY_BYTES`
+pub async fn binary(resp: reqwest::Response) -> anyhow::Result<Vec<u8>> {
+    let resp = resp.error_for_status()?;
+    let url = resp.url().to_string();
+    let body = read_limited(resp, MAX_BINARY_BYTES).await?;
+    if body.is_empty() {
+        return Err(unexpected(&url, "空响应").into());
+    }
+    Ok(body)
+}
@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::code;
+use crate::util;
+
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "avi", "wmv", "mov", "m4v", "ts", "flv", "rmvb", "iso", "webm"];
+
+#[derive(Debug, Serialize)]
+pub struct ScanFile {
+    pub path: PathBuf,
+    /// 1-based part index for multi-part releases (CD1/CD2, A/B, part1/part2)
+    pub part: Option<u32>,
+    /// Canonical file name, `ABP-123.mp4` or `ABP-123-cd2.mp4` (the Kodi/Jellyfin stacking form)
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanGroup {
+    pub code: String,
+    /// In part order
+    pub files: Vec<ScanFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanResult {
+    pub groups: Vec<ScanGroup>,
+    /// Videos without a recognizable code
+    pub unmatched: Vec<PathBuf>,
+}
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+fn walk(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = std::fs::read_dir(dir).with_context(|| format!("读取目录失败: {}", dir.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            walk(&path, out)?;
+        } else if is_video(&path) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Canonical name for one file of `code`; the part suffix is only added when the release
+/// really is split, so a lone `ABP-123-A.mp4` becomes plain `ABP-123.mp4`
+pub fn file_name(code: &str, part: Option<u32>, multi_part: bool, ext: &str) -> String {
+    match part {
+        Some(p) if multi_part => format!("{}-cd{}.{}", code, p, ext),
+        _ => format!("{}.{}", code, ext),
+    }
+}
+
+/// Find video files under `dir` and group them by code, multi-part releases in part order
+pub fn scan(dir: &Path) -> Result<ScanResult> {
+    let mut paths = Vec::new();
+    walk(dir, &mut paths)?;
+    paths.sort();
+
+    let mut by_code: BTreeMap<String, Vec<(PathBuf, Option<u32>)>> = BTreeMap::new();
+    let mut unmatched = Vec::new();
+    for path in paths {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        match code::find(stem) {
+            Some(c) => by_code.entry(c.to_string()).or_default().push((path.clone(), code::part(stem))),
+            None => unmatched.push(path),
+        }
+    }
+
+    let groups = by_code
+        .into_iter()
+        .map(|(code, mut files)| {
+            files.sort_by_key(|(_, part)| *part);
+            let multi_part = files.len() > 1 && files.iter().all(|(_, p)| p.is_some());
+            let files = files
+                .into_iter()
+                .map(|(path, part)| {
+                    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("mp4").to_lowercase();
+                    let name = file_name(&code, part, multi_part, &ext);
+                    ScanFile { path, part: part.filter(|_| multi_part), name }
+                })
+                .collect();
+            ScanGroup { code, files }
+        })
+        .collect();
+    Ok(ScanResult { groups, unmatched })
+}
+
+pub fn run(dir: &Path, json: bool) -> Result<()> {
+    let result = scan(dir)?;
+    if json {
+        util::print_output(&result, true);
+        return Ok(());
+    }
+    println!("{} {} 个番号，{} 个文件未识别", "共".bold(), result.groups.len(), result.unmatched.len());
+    for g in &result.groups {
+        let parts = if g.files.iter().any(|f| f.part.is_some()) { format!("（{} 段）", g.files.len()) } else { String::new() };
+        println!("{}{}", g.code.green().bold(), parts);
+        for f in &g.files {
+            let part = f.part.map(|p| format!("CD{} ", p)).unwrap_or_default();
+            println!("  {}{} → {}", part, f.path.display(), f.name);
+        }
+    }
+    if !result.unmatched.is_empty() {
+        println!("{}", "未识别:".yellow().bold());
+        for p in &result.unmatched {
+            println!("  {}", p.display());
+        }
+    }
+    Ok(())
+}
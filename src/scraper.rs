@@ -1,53 +1,540 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Result};
 use regex::Regex;
-use reqwest::header::{HeaderMap, HeaderValue, HeaderName, ACCEPT, ACCEPT_LANGUAGE, REFERER, USER_AGENT};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, ACCEPT_LANGUAGE, COOKIE, REFERER};
 use scraper::{Html, Selector};
 use urlencoding::encode;
 
-use crate::types::{AvDetail, AvItem, MagnetInfo, ActorItem};
-use std::collections::HashMap;
-use crate::sources::{dmm, javlibrary};
+use crate::types::{AvDetail, AvItem, MagnetInfo, ActorItem, PlayCandidate, Popularity, RankingPage};
+use crate::sources::{avwiki, btsow, dmm, javlibrary, mock, plugin, sokmil, theporndb, torrentkitty, torznab, uncensored, xslist};
+use crate::amateur;
+use crate::batch;
+use crate::cache;
+use crate::cancel;
+use crate::code;
+use crate::config::{self, MetadataLang, PluginMode};
+use crate::cookies;
+use crate::dates::{self, DateRange};
+use crate::genre;
+use crate::hooks;
+use crate::http;
+use crate::login;
+use crate::magnet;
+use crate::metrics::{self, FetchOutcome};
+use crate::mirrors;
+use crate::rating;
+use crate::sanity::{self, FetchError};
+use crate::seeders;
+use crate::selectors;
+use crate::ua;
 use crate::util;
-
-const UA: &str =
-    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0 Safari/537.36";
+use crate::vr;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+use tokio::sync::Semaphore;
 
 fn default_headers() -> HeaderMap {
-    let mut headers = HeaderMap::new();
-    headers.insert(USER_AGENT, HeaderValue::from_static(UA));
+    let mut headers = ua::navigation_headers(ua::session_profile());
     headers.insert(ACCEPT, HeaderValue::from_static("text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8"));
     headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-US,en;q=0.9,ja;q=0.8,zh-CN;q=0.7"));
-    let referer = format!("{}/", javdb_base());
-    if let Ok(hv) = HeaderValue::from_str(&referer) { headers.insert(REFERER, hv); }
-    if let Some(cookie) = std::env::var("AV_JAVDB_COOKIE").ok() {
-        let name = HeaderName::from_static("cookie");
-        if let Ok(val) = HeaderValue::from_str(cookie.trim()) {
-            headers.insert(name, val);
-        }
-    }
     headers
 }
 
-fn client() -> reqwest::Client {
-    let mut builder = reqwest::Client::builder()
+/// Same-origin Referer, matching the `sec-fetch-site` we send; set per request because the
+/// shared client outlives mirror switches.
+fn referer_for(url: &str) -> Option<HeaderValue> {
+    let u = reqwest::Url::parse(url).ok()?;
+    HeaderValue::from_str(&format!("{}/", u.origin().ascii_serialization())).ok()
+}
+
+/// An explicit `AV_JAVDB_COOKIE` header (or the keychain's `javdb-cookie`) replaces the
+/// cookie jar for JavDB requests
+fn javdb_cookie() -> Option<String> {
+    std::env::var("AV_JAVDB_COOKIE")
+        .ok()
+        .filter(|c| !c.trim().is_empty())
+        .or_else(|| crate::keychain::get(crate::keychain::Secret::JavdbCookie))
+}
+
+/// The `Cookie` header for a request to `url`: the explicit JavDB cookie, and only for
+/// JavDB, since the shared client fetches Sukebei and other sites too
+pub(crate) fn javdb_cookie_for(url: &str) -> Option<HeaderValue> {
+    if metrics::source_of(url) != "javdb" {
+        return None;
+    }
+    javdb_cookie().and_then(|c| HeaderValue::from_str(c.trim()).ok())
+}
+
+/// Either `AV_JAVDB_COOKIE` or a session from `av login javdb`
+pub(crate) fn javdb_logged_in() -> bool {
+    javdb_cookie().is_some() || login::javdb_session().is_some()
+}
+
+/// Browser-like client settings. The explicit JavDB cookie is added per request
+/// (`javdb_cookie_for`), never as a default header.
+pub(crate) fn client_builder() -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder()
         .default_headers(default_headers())
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .cookie_store(true)
-        ;
-    if let Some(proxy) = std::env::var("AV_HTTP_PROXY").ok() {
-        if let Ok(px) = reqwest::Proxy::all(proxy) { builder = builder.proxy(px); }
+        .redirect(reqwest::redirect::Policy::limited(10));
+    http::with_proxy(builder)
+}
+
+/// Built once and shared, so consecutive requests reuse pooled connections and TLS sessions.
+/// Proxies and cookie jars are still picked per source inside it.
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| client_builder().cookie_provider(cookies::provider()).build().expect("client build"));
+
+pub(crate) fn client() -> reqwest::Client {
+    CLIENT.clone()
+}
+
+/// One URL, without mirror failover; attempts that fail in passing are repeated up to
+/// `--retries` times, each recorded on its own
+async fn send_once(c: &reqwest::Client, url: &str, source: &str) -> reqwest::Result<reqwest::Response> {
+    http::with_retries(url, || async {
+        let started = Instant::now();
+        let mut headers = ua::request_headers();
+        if let Some(referer) = referer_for(url) {
+            headers.insert(REFERER, referer);
+        }
+        // Takes the jar's place: reqwest leaves a request's own `Cookie` header alone
+        if let Some(cookie) = javdb_cookie_for(url) {
+            headers.insert(COOKIE, cookie);
+        }
+        let result = http::with_timeout(c.get(url).headers(headers)).send().await;
+        metrics::record_fetch(source, started.elapsed(), FetchOutcome::of(&result));
+        result
+    })
+    .await
+}
+
+/// GET `url`, recording per-source latency and error/block counters for `/metrics`.
+/// JavDB requests that look geo-blocked are retried once on the next healthy mirror.
+/// Fails fast once the operation has been cancelled (Ctrl-C).
+pub(crate) async fn send(c: &reqwest::Client, url: &str) -> Result<reqwest::Response> {
+    cancel::check()?;
+    #[cfg(feature = "cassette")]
+    if let Some(resp) = crate::cassette::replay(url)? {
+        return Ok(resp);
+    }
+    let token = cancel::token();
+    let resp = tokio::select! {
+        result = send_with_failover(c, url) => result,
+        _ = token.cancelled() => Err(cancel::error()),
+    }?;
+    #[cfg(feature = "cassette")]
+    let resp = crate::cassette::record(url, resp).await?;
+    Ok(resp)
+}
+
+async fn send_with_failover(c: &reqwest::Client, url: &str) -> Result<reqwest::Response> {
+    let source = metrics::source_of(url);
+    let url = if source == "javdb" { mirrors::rebase(url) } else { url.to_string() };
+    #[cfg(feature = "impersonate")]
+    if let Some(program) = crate::impersonate::program() {
+        return send_impersonated(c, program, &url, &source).await;
+    }
+    let result = send_once(c, &url, &source).await;
+    if source == "javdb" && mirrors::should_failover(&result) {
+        if let Some(retry_url) = mirrors::failover(c, &url).await {
+            return Ok(send_once(c, &retry_url, &source).await?);
+        }
+    }
+    Ok(result?)
+}
+
+/// `send_with_failover` through curl-impersonate. Transport errors are all treated like
+/// connection failures, since curl doesn't say which kind it was in a way worth parsing.
+#[cfg(feature = "impersonate")]
+async fn send_impersonated(c: &reqwest::Client, program: &std::path::Path, url: &str, source: &str) -> Result<reqwest::Response> {
+    let fetch = |url: String| async move {
+        let mut attempt = 0;
+        loop {
+            let cookie = javdb_cookie_for(&url);
+            let started = Instant::now();
+            let result = crate::impersonate::get(program, &url, source, referer_for(&url).as_ref(), cookie).await;
+            let outcome = match &result {
+                Ok(resp) => FetchOutcome::of_status(resp.status().as_u16()),
+                Err(_) => FetchOutcome::Error,
+            };
+            metrics::record_fetch(source, started.elapsed(), outcome);
+            // As `http::with_retries`, with every curl failure counted as passing
+            let transient = result.as_ref().map_or(true, |r| http::is_transient_status(r.status().as_u16()));
+            if attempt >= http::retries() || !transient || cancel::is_cancelled() {
+                return result;
+            }
+            attempt += 1;
+            let wait = http::backoff(attempt, result.as_ref().ok().and_then(|r| r.headers().get(reqwest::header::RETRY_AFTER)));
+            util::debug(format!("{}: retry {}/{} in {:?}", url, attempt, http::retries(), wait));
+            tokio::time::sleep(wait).await;
+        }
+    };
+    let result = fetch(url.to_string()).await;
+    let blocked = match &result {
+        Ok(resp) => mirrors::is_block_status(resp.status().as_u16()),
+        Err(_) => true,
+    };
+    if source == "javdb" && blocked {
+        if let Some(retry_url) = mirrors::failover(c, url).await {
+            return fetch(retry_url).await;
+        }
+    }
+    result
+}
+
+/// Pages already fetched by this process, by URL. Only `av shell` turns it on: one-shot
+/// commands never fetch a page twice, and the page must not go stale under a daemon.
+static PAGE_CACHE: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+pub(crate) fn enable_page_cache() {
+    PAGE_CACHE.lock().unwrap().get_or_insert_with(HashMap::new);
+}
+
+/// Forget cached pages (`av shell`'s `refresh`); the cache stays on
+pub(crate) fn clear_page_cache() {
+    if let Some(cache) = PAGE_CACHE.lock().unwrap().as_mut() {
+        cache.clear();
+    }
+}
+
+pub(crate) async fn get_text(c: &reqwest::Client, url: &str) -> Result<String> {
+    if let Some(body) = PAGE_CACHE.lock().unwrap().as_ref().and_then(|cache| cache.get(url).cloned()) {
+        util::debug(format!("page cache: {}", url));
+        return Ok(body);
+    }
+    let body = sanity::page_text(send(c, url).await?).await?;
+    if let Some(cache) = PAGE_CACHE.lock().unwrap().as_mut() {
+        cache.insert(url.to_string(), body.clone());
+    }
+    Ok(body)
+}
+
+/// GET a third-party URL with an explicit Referer (a player embedded in `referer`, a stream
+/// segment) and optional extra headers such as `Range`. No mirror failover, since these
+/// are never JavDB.
+pub(crate) async fn send_from(c: &reqwest::Client, url: &str, referer: &str, extra: HeaderMap) -> Result<reqwest::Response> {
+    cancel::check()?;
+    #[cfg(feature = "cassette")]
+    if let Some(resp) = crate::cassette::replay(url)? {
+        return Ok(resp);
+    }
+    let mut headers = ua::request_headers();
+    if let Ok(v) = HeaderValue::from_str(referer) {
+        headers.insert(REFERER, v);
+    }
+    headers.extend(extra);
+    if let Some(cookie) = javdb_cookie_for(url) {
+        headers.insert(COOKIE, cookie);
+    }
+    let source = metrics::source_of(url);
+    let result = http::with_retries(url, || async {
+        let started = Instant::now();
+        let result = http::with_timeout(c.get(url).headers(headers.clone())).send().await;
+        metrics::record_fetch(&source, started.elapsed(), FetchOutcome::of(&result));
+        result
+    })
+    .await;
+    Ok(result?)
+}
+
+pub(crate) async fn get_text_from(c: &reqwest::Client, url: &str, referer: &str) -> Result<String> {
+    sanity::page_text(send_from(c, url, referer, HeaderMap::new()).await?).await
+}
+
+pub(crate) async fn get_bytes(c: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    sanity::binary(send(c, url).await?).await
+}
+
+pub(crate) fn javdb_base() -> String {
+    mirrors::current()
+}
+
+/// A single metadata/magnet source for `--source`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Source {
+    Javdb,
+    Javlibrary,
+    Dmm,
+    Sukebei,
+    Btsow,
+    Torrentkitty,
+    /// The `[[sources.torznab]]` indexers
+    Torznab,
+    /// ThePornDB's API, for western scenes
+    Theporndb,
+    /// The uncensored studios' own sites (1Pondo, Caribbeancom, HEYZO, Tokyo-Hot)
+    Uncensored,
+    /// The Sokmil retail store
+    Sokmil,
+}
+
+impl Source {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::Javdb => "javdb",
+            Source::Javlibrary => "javlibrary",
+            Source::Dmm => "dmm",
+            Source::Sukebei => "sukebei",
+            Source::Btsow => "btsow",
+            Source::Torrentkitty => "torrentkitty",
+            Source::Torznab => "torznab",
+            Source::Theporndb => "theporndb",
+            Source::Uncensored => "uncensored",
+            Source::Sokmil => "sokmil",
+        }
+    }
+}
+
+static SOURCE: Mutex<Option<Source>> = Mutex::new(None);
+
+pub fn set_source(source: Option<Source>) {
+    *SOURCE.lock().unwrap() = source;
+}
+
+/// `--source`: the one source to use instead of the merge chain
+pub fn forced_source() -> Option<Source> {
+    *SOURCE.lock().unwrap()
+}
+
+/// The detail as one source alone has it; `Ok(None)` when it has no entry for the code
+async fn fetch_detail_from(source: Source, code: &str) -> Result<Option<AvDetail>> {
+    match source {
+        Source::Javdb => fetch_detail_from_javdb(code).await.map(Some),
+        Source::Javlibrary => javlibrary::fetch_detail_from_javlibrary(code).await,
+        Source::Dmm => {
+            if !dmm::dmm_enabled() {
+                bail!("DMM 未启用，请设置 DMM_API_ID 与 DMM_AFFILIATE_ID（或用 av secret set 存入钥匙串）");
+            }
+            dmm::fetch_detail_from_dmm(code).await
+        }
+        Source::Sukebei => fetch_detail_from_sukebei(code).await.map(Some),
+        Source::Btsow => btsow::fetch_detail(code).await,
+        Source::Torrentkitty => torrentkitty::fetch_detail(code).await,
+        Source::Torznab => {
+            if torznab::indexers().is_empty() {
+                bail!("没有配置 Torznab 索引器，请在配置文件中添加 [[sources.torznab]]");
+            }
+            let infos = torznab::magnets(code).await;
+            Ok((!infos.is_empty()).then(|| magnet::detail_of(code, infos)))
+        }
+        Source::Theporndb => theporndb::fetch_detail(code).await,
+        Source::Uncensored => {
+            if uncensored::studio_of(code).is_none() {
+                bail!("{} 不是 1Pondo / Caribbeancom / HEYZO / Tokyo-Hot 的番号", code);
+            }
+            uncensored::fetch_detail(code).await
+        }
+        Source::Sokmil => sokmil::fetch_detail(code).await,
     }
-    builder.build().expect("client build")
 }
 
-fn javdb_base() -> String {
-    std::env::var("AV_JAVDB_BASE").unwrap_or_else(|_| "https://javdb.com".to_string())
+/// The detail page `av open` shows: `--source`'s if given, JavDB's otherwise.
+/// `Ok(None)` when the source has no entry for the code.
+pub async fn source_page_url(code: &str) -> Result<(Source, Option<String>)> {
+    let source = forced_source().unwrap_or(Source::Javdb);
+    if mock::is_enabled() {
+        return Ok((source, Some(mock::page_url(code)?)));
+    }
+    let code = code::normalize(code);
+    let url = match source {
+        Source::Javdb => Some(javdb_video_url(&client(), &code).await?),
+        Source::Javlibrary => javlibrary::detail_url(&code).await?,
+        Source::Dmm => {
+            if !dmm::dmm_enabled() {
+                bail!("DMM 未启用，请设置 DMM_API_ID 与 DMM_AFFILIATE_ID（或用 av secret set 存入钥匙串）");
+            }
+            dmm::detail_url(&code).await?
+        }
+        Source::Sukebei => Some(sukebei_first_result(&client(), &code).await?.0),
+        Source::Btsow => Some(btsow::search_url(&code)),
+        Source::Torrentkitty => Some(torrentkitty::search_url(&code)),
+        // An API, not pages to look at
+        Source::Torznab => None,
+        Source::Theporndb => theporndb::detail_url(&code).await?,
+        Source::Uncensored => uncensored::page_url(&code),
+        Source::Sokmil => sokmil::detail_url(&code).await?,
+    };
+    Ok((source, url))
 }
 
 pub async fn fetch_detail(code: &str) -> Result<AvDetail> {
+    // The cache holds merged details only; mock and `--source` lookups bypass it
+    let cacheable = !mock::is_enabled() && forced_source().is_none();
+    if cacheable {
+        if let Some(mut d) = cache::get(code) {
+            // Entries cached by older versions may predate the canonical order and `amateur`
+            d.sort_stable();
+            d.amateur = amateur::is_amateur(&d);
+            magnet::filter_by_size(&mut d);
+            vr::filter_magnets(&mut d);
+            return Ok(d);
+        }
+    }
+    let mut detail = if mock::is_enabled() {
+        mock::detail(code)?
+    } else if let Some(source) = forced_source() {
+        let code = code::normalize(code);
+        util::debug(format!("fetch_detail: {} only", source.as_str()));
+        fetch_detail_from(source, &code)
+            .await?
+            .ok_or_else(|| FetchError::not_found(source.as_str(), format!("{} 没有 {} 的结果", source.as_str(), code)))?
+    } else {
+        fetch_detail_merged(code).await?
+    };
+    magnet::score_all(&mut detail, &config::get().magnets.score);
+    genre::normalize(&mut detail, util::lang());
+    rating::aggregate(&mut detail);
+    detail.amateur = amateur::is_amateur(&detail);
+    hooks::transform_magnets(&mut detail).await;
+    seeders::record(&detail.magnet_infos);
+    detail.sort_stable();
+    if cacheable {
+        cache::put(&detail);
+    }
+    magnet::filter_by_size(&mut detail);
+    vr::filter_magnets(&mut detail);
+    Ok(detail)
+}
+
+/// Names of the sources `fetch_detail_by_source` asks about `code`, in its order
+pub fn detail_sources(code: &str) -> Vec<String> {
+    if mock::is_enabled() {
+        return vec!["mock".to_string()];
+    }
+    let mut out = Vec::new();
+    if dmm::dmm_enabled() {
+        out.push("dmm".to_string());
+    }
+    out.extend(["javdb", "javlibrary", "sokmil", "sukebei", "btsow", "torrentkitty"].map(str::to_string));
+    if theporndb::enabled() {
+        out.push("theporndb".to_string());
+    }
+    if let Some((studio, _)) = uncensored::studio_of(&code::normalize(code)) {
+        out.push(studio.name().to_string());
+    }
+    out.extend(torznab::indexers().iter().map(|t| t.name.clone()));
+    out.extend(plugin::plugins().iter().map(|p| p.name.clone()));
+    out
+}
+
+/// One source's own detail, as `fetch_detail_by_source` gets it but unscored; `source`
+/// is a name from `detail_sources`
+pub async fn fetch_detail_named(source: &str, code: &str) -> Result<Option<AvDetail>> {
+    let code = code::normalize(code);
+    if source == "mock" {
+        return mock::detail(&code).map(Some);
+    }
+    if let Ok(s) = <Source as clap::ValueEnum>::from_str(source, true) {
+        return fetch_detail_from(s, &code).await;
+    }
+    if uncensored::studio_of(&code).is_some_and(|(s, _)| s.name() == source) {
+        return fetch_detail_from(Source::Uncensored, &code).await;
+    }
+    if let Some(t) = torznab::indexers().iter().find(|t| t.name == source) {
+        let infos = torznab::search(t, &code).await?;
+        return Ok((!infos.is_empty()).then(|| magnet::detail_of(&code, infos)));
+    }
+    match plugin::plugins().iter().find(|p| p.name == source) {
+        Some(p) => plugin::detail(p, &code).await,
+        None => bail!("未知的来源: {}", source),
+    }
+}
+
+/// Every enabled source's own detail, unmerged (`detail --compare-sources`), in merge
+/// priority order. `Ok(None)`: the source has no entry for the code.
+pub async fn fetch_detail_by_source(code: &str) -> Vec<(String, Result<Option<AvDetail>>)> {
+    if mock::is_enabled() {
+        return vec![("mock".to_string(), mock::detail(code).map(Some))];
+    }
+    let code = code::normalize(code);
+    let dmm = async {
+        if dmm::dmm_enabled() { Some(dmm::fetch_detail_from_dmm(&code).await) } else { None }
+    };
+    let (javdb, jl, dmm, sukebei, bt, tk, sm) = tokio::join!(
+        fetch_detail_from_javdb(&code),
+        javlibrary::fetch_detail_from_javlibrary(&code),
+        dmm,
+        fetch_detail_from_sukebei(&code),
+        btsow::fetch_detail(&code),
+        torrentkitty::fetch_detail(&code),
+        sokmil::fetch_detail(&code),
+    );
+    let mut out = Vec::new();
+    if let Some(d) = dmm {
+        out.push(("dmm".to_string(), d));
+    }
+    out.push(("javdb".to_string(), javdb.map(Some)));
+    out.push(("javlibrary".to_string(), jl));
+    out.push(("sokmil".to_string(), sm));
+    out.push(("sukebei".to_string(), sukebei.map(Some)));
+    out.push(("btsow".to_string(), bt));
+    out.push(("torrentkitty".to_string(), tk));
+    if theporndb::enabled() {
+        out.push(("theporndb".to_string(), theporndb::fetch_detail(&code).await));
+    }
+    if let Some((studio, _)) = uncensored::studio_of(&code) {
+        out.push((studio.name().to_string(), uncensored::fetch_detail(&code).await));
+    }
+    for t in torznab::indexers() {
+        let infos = torznab::search(t, &code).await;
+        out.push((t.name.clone(), infos.map(|infos| (!infos.is_empty()).then(|| magnet::detail_of(&code, infos)))));
+    }
+    for p in plugin::plugins() {
+        out.push((p.name.clone(), plugin::detail(p, &code).await));
+    }
+    for (_, d) in &mut out {
+        if let Ok(Some(d)) = d {
+            magnet::score_all(d, &config::get().magnets.score);
+            genre::normalize(d, util::lang());
+            rating::aggregate(d);
+            d.amateur = amateur::is_amateur(d);
+            d.sort_stable();
+        }
+    }
+    out
+}
+
+async fn fetch_detail_merged(code: &str) -> Result<AvDetail> {
+    let builtin = fetch_detail_builtin(code).await;
+    let mut merged = if plugin::plugins().is_empty() { builtin } else { plugin::extend(&code::normalize(code), builtin).await }?;
+    if merged.actor_names.is_empty() && !code::is_scene(&merged.code) {
+        fill_cast(&mut merged).await;
+    }
+    Ok(merged)
+}
+
+/// Amateur labels' entries often name nobody; AV-Wiki's post on the title does
+async fn fill_cast(d: &mut AvDetail) {
+    match avwiki::lookup(&d.code).await {
+        Ok(Some(found)) => {
+            util::debug(format!("AV-Wiki names {} for {}", found.actresses.join(", "), d.code));
+            d.actor_names = found.actresses;
+        }
+        Ok(None) => util::debug(format!("AV-Wiki has no cast for {}", d.code)),
+        Err(e) => util::debug(format!("AV-Wiki for {}: {:#}", d.code, e)),
+    }
+}
+
+async fn fetch_detail_builtin(code: &str) -> Result<AvDetail> {
     // Prefer JavDB native scraping by default; DMM is opt-in via env AV_USE_DMM=1
-    let code_upper = code.to_uppercase();
+    let code_upper = code::normalize(code);
     util::debug(format!("fetch_detail start for {}", code_upper));
+    if code::is_scene(&code_upper) {
+        return scene_detail(&code_upper).await;
+    }
+    // Scene names of western studios aren't codes any JAV source knows
+    if !looks_like_code(&code_upper) && theporndb::enabled() {
+        match theporndb::fetch_detail(&code_upper).await {
+            Ok(Some(mut d)) => {
+                util::debug("ThePornDB hit");
+                let code = d.code.clone();
+                add_torznab_magnets(&mut d, &code).await;
+                return Ok(d);
+            }
+            Ok(None) => util::debug("ThePornDB has no matching scene"),
+            Err(e) => util::debug(format!("ThePornDB: {:#}", e)),
+        }
+    }
     if std::env::var("AV_USE_DMM").ok().as_deref() == Some("1") && dmm::dmm_enabled() {
         if let Some(mut d) = dmm::fetch_detail_from_dmm(&code_upper).await? {
             util::debug("DMM hit");
@@ -60,12 +547,12 @@ pub async fn fetch_detail(code: &str) -> Result<AvDetail> {
                 // Prefer DMM release_date/duration if present; else copy from JavDB
                 if d.release_date.is_none() { d.release_date = j.release_date; }
                 if d.duration_minutes.is_none() { d.duration_minutes = j.duration_minutes; }
+                if d.trailer_url.is_none() { d.trailer_url = j.trailer_url; }
+                rating::merge(&mut d.ratings, j.ratings);
+                if d.popularity.is_none() { d.popularity = j.popularity; }
+                if magnet::all_sources() { magnet::union(&mut d, j.magnets, j.magnet_infos); }
             }
-            // Always merge magnets from Sukebei
-            if let Ok(s) = fetch_detail_from_sukebei(&code_upper).await {
-                if d.magnets.is_empty() { d.magnets = s.magnets; }
-                if d.magnet_infos.is_empty() { d.magnet_infos = s.magnet_infos; }
-            }
+            add_magnets(&mut d, &code_upper).await;
             return Ok(d);
         }
     }
@@ -74,6 +561,12 @@ pub async fn fetch_detail(code: &str) -> Result<AvDetail> {
         // Merge extra metadata from JavLibrary even when JavDB succeeds
         if let Ok(Some(jl)) = javlibrary::fetch_detail_from_javlibrary(&code_upper).await {
             util::debug("Merging with JavLibrary after JavDB");
+            // JavDB carries the original Japanese title and genre names; JavLibrary's
+            // locale pages are the localized ones
+            if matches!(util::lang(), Some(MetadataLang::Zh) | Some(MetadataLang::En)) {
+                if !jl.title.trim().is_empty() { detail.title = jl.title.clone(); }
+                if !jl.genres.is_empty() { detail.genres = jl.genres.clone(); }
+            }
             if detail.plot.is_none() && jl.plot.is_some() { detail.plot = jl.plot; }
             if detail.actor_names.is_empty() && !jl.actor_names.is_empty() { detail.actor_names = jl.actor_names; }
             if detail.release_date.is_none() && jl.release_date.is_some() { detail.release_date = jl.release_date; }
@@ -85,57 +578,449 @@ pub async fn fetch_detail(code: &str) -> Result<AvDetail> {
             if detail.series.is_none() && jl.series.is_some() { detail.series = jl.series; }
             if detail.genres.is_empty() && !jl.genres.is_empty() { detail.genres = jl.genres; }
             if detail.preview_images.is_empty() && !jl.preview_images.is_empty() { detail.preview_images = jl.preview_images; }
+            rating::merge(&mut detail.ratings, jl.ratings);
+            if detail.popularity.is_none() { detail.popularity = jl.popularity; }
         }
-        if detail.magnets.is_empty() {
-            if let Ok(s_detail) = fetch_detail_from_sukebei(&code_upper).await {
-                if !s_detail.magnets.is_empty() {
-                    detail.magnets = s_detail.magnets;
-                }
-            }
+        if let Some(studio) = studio_detail(&code_upper).await {
+            util::debug("Merging with the studio's own site after JavDB");
+            fill_missing(&mut detail, studio);
         }
+        add_magnets(&mut detail, &code_upper).await;
         return Ok(detail);
     }
+    if let Some(mut studio) = studio_detail(&code_upper).await {
+        util::debug("Studio site hit (fallback)");
+        add_magnets(&mut studio, &code_upper).await;
+        return Ok(studio);
+    }
     // Try JavLibrary
     if let Ok(Some(mut jl)) = javlibrary::fetch_detail_from_javlibrary(&code_upper).await {
         util::debug("JavLibrary hit (fallback)");
-        if let Ok(s) = fetch_detail_from_sukebei(&code_upper).await {
-            if jl.magnets.is_empty() { jl.magnets = s.magnets; }
-            if jl.magnet_infos.is_empty() { jl.magnet_infos = s.magnet_infos; }
+        // It has no plots
+        if let Some(s) = sokmil_detail(&code_upper).await {
+            util::debug("Merging with Sokmil after JavLibrary");
+            fill_missing(&mut jl, s);
         }
+        add_magnets(&mut jl, &code_upper).await;
         return Ok(jl);
     }
-    util::debug("Falling back to Sukebei only detail");
-    fetch_detail_from_sukebei(&code_upper).await
+    if let Some(mut s) = sokmil_detail(&code_upper).await {
+        util::debug("Sokmil hit (fallback)");
+        add_magnets(&mut s, &code_upper).await;
+        return Ok(s);
+    }
+    util::debug("Falling back to magnet sources only detail");
+    magnet_sources_detail(&code_upper).await
+}
+
+/// The uncensored studio's own detail for its codes; `None` for other codes or on failure
+async fn studio_detail(code: &str) -> Option<AvDetail> {
+    uncensored::studio_of(code)?;
+    match uncensored::fetch_detail(code).await {
+        Ok(d) => d,
+        Err(e) => {
+            util::debug(format!("studio site for {}: {:#}", code, e));
+            None
+        }
+    }
+}
+
+/// Sokmil's detail; `None` when it has none or can't be reached
+async fn sokmil_detail(code: &str) -> Option<AvDetail> {
+    match sokmil::fetch_detail(code).await {
+        Ok(d) => d,
+        Err(e) => {
+            util::debug(format!("Sokmil for {}: {:#}", code, e));
+            None
+        }
+    }
+}
+
+/// Fill what `d` lacks from a second source: JavDB's uncensored entries are often bare,
+/// so the studio's site supplies the cast, cover, duration and the rest; JavLibrary has
+/// no plots, which Sokmil's retail page does
+fn fill_missing(d: &mut AvDetail, s: AvDetail) {
+    if d.title.trim().is_empty() || d.title.trim() == d.code { d.title = s.title; }
+    if d.actor_names.is_empty() { d.actor_names = s.actor_names; }
+    if d.release_date.is_none() { d.release_date = s.release_date; }
+    if d.cover_url.is_none() { d.cover_url = s.cover_url; }
+    if d.plot.is_none() { d.plot = s.plot; }
+    if d.duration_minutes.is_none() { d.duration_minutes = s.duration_minutes; }
+    if d.director.is_none() { d.director = s.director; }
+    if d.studio.is_none() { d.studio = s.studio; }
+    if d.label.is_none() { d.label = s.label; }
+    if d.series.is_none() { d.series = s.series; }
+    if d.genres.is_empty() { d.genres = s.genres; }
+    if d.preview_images.is_empty() { d.preview_images = s.preview_images; }
+    if d.trailer_url.is_none() { d.trailer_url = s.trailer_url; }
+    rating::merge(&mut d.ratings, s.ratings);
+}
+
+/// A western-mode scene code: ThePornDB's scene, with magnets only from Torznab (the
+/// public magnet sources are JAV indexes)
+async fn scene_detail(code: &str) -> Result<AvDetail> {
+    if !theporndb::enabled() {
+        bail!("{} 是欧美场景名，需要 ThePornDB：请在配置文件的 [sources.theporndb] 中设置 token", code);
+    }
+    let mut d = theporndb::fetch_detail(code)
+        .await?
+        .ok_or_else(|| FetchError::not_found("theporndb", format!("ThePornDB 没有 {} 的结果", code)))?;
+    add_torznab_magnets(&mut d, code).await;
+    Ok(d)
+}
+
+fn has_magnets(d: &AvDetail) -> bool {
+    !d.magnets.is_empty() || !d.magnet_infos.is_empty()
+}
+
+/// The magnet indexes asked after Sukebei, in order
+const INDEXES: &[Source] = &[Source::Btsow, Source::Torrentkitty];
+
+/// Sukebei's detail, with the indexes' magnets when it has none (or always with
+/// `--all-magnets`); failing with Sukebei's error only when no index has any either
+async fn magnet_sources_detail(code: &str) -> Result<AvDetail> {
+    let (mut d, err) = match fetch_detail_from_sukebei(code).await {
+        Ok(d) => (d, None),
+        Err(e) => (magnet::detail_of(code, Vec::new()), Some(e)),
+    };
+    add_index_magnets(&mut d, code).await;
+    add_torznab_magnets(&mut d, code).await;
+    match err {
+        Some(e) if !has_magnets(&d) => Err(e),
+        _ => Ok(d),
+    }
+}
+
+/// The magnet sources' magnets for a detail from a metadata source: Sukebei's, then each
+/// index's, each only while there are none yet, or with `--all-magnets` all folded in
+/// alongside its own. Configured Torznab indexers are always asked.
+async fn add_magnets(d: &mut AvDetail, code: &str) {
+    add_torznab_magnets(d, code).await;
+    let all = magnet::all_sources();
+    if has_magnets(d) && !all {
+        return;
+    }
+    match fetch_detail_from_sukebei(code).await {
+        Ok(s) if all => {
+            let added = magnet::union(d, s.magnets, s.magnet_infos);
+            util::debug(format!("{} new magnet(s) from Sukebei", added));
+        }
+        Ok(s) => {
+            d.magnets = s.magnets;
+            d.magnet_infos = s.magnet_infos;
+        }
+        Err(e) => util::debug(format!("Sukebei magnets for {}: {:#}", code, e)),
+    }
+    add_index_magnets(d, code).await;
+}
+
+async fn add_torznab_magnets(d: &mut AvDetail, code: &str) {
+    if torznab::indexers().is_empty() {
+        return;
+    }
+    let infos = torznab::magnets(code).await;
+    let urls = infos.iter().map(|m| m.url.clone()).collect();
+    let added = magnet::union(d, urls, infos);
+    util::debug(format!("{} new magnet(s) from Torznab", added));
+}
+
+async fn add_index_magnets(d: &mut AvDetail, code: &str) {
+    let all = magnet::all_sources();
+    for &source in INDEXES {
+        if has_magnets(d) && !all {
+            break;
+        }
+        let found = match source {
+            Source::Btsow => btsow::magnets(code).await,
+            Source::Torrentkitty => torrentkitty::magnets(code).await,
+            _ => continue,
+        };
+        match found {
+            Ok(infos) => {
+                let urls = infos.iter().map(|m| m.url.clone()).collect();
+                let added = magnet::union(d, urls, infos);
+                util::debug(format!("{} new magnet(s) from {}", added, source.as_str()));
+            }
+            Err(e) => util::debug(format!("{} magnets for {}: {:#}", source.as_str(), code, e)),
+        }
+    }
+}
+
+/// Fetch details for many codes, `--jobs` at a time, preserving input order.
+/// Codes that fail to resolve are skipped (logged in debug mode).
+pub async fn fetch_details(codes: Vec<String>) -> Vec<AvDetail> {
+    let total = codes.len();
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, code) in codes.into_iter().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (idx, fetch_detail(&code).await)
+        });
+    }
+    let mut details: Vec<(usize, AvDetail)> = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((idx, Ok(d))) => details.push((idx, d)),
+            Ok((_, Err(e))) => util::debug(format!("fetch_details: {}", e)),
+            Err(e) => util::debug(format!("fetch_details: task failed: {}", e)),
+        }
+    }
+    if cancel::is_cancelled() {
+        eprintln!("[WARN] 已取消，仅获取到 {}/{} 条", details.len(), total);
+    }
+    details.sort_by_key(|(idx, _)| *idx);
+    details.into_iter().map(|(_, d)| d).collect()
 }
 
 pub async fn search(query: &str) -> Result<Vec<AvItem>> {
+    search_in(query, &[]).await
+}
+
+/// The sources a keyword search queries, in merge order: JavDB, Sukebei, ThePornDB once it
+/// has a token, then the `[[sources.plugins]]` by name
+pub fn search_sources() -> Vec<String> {
+    let mut out = vec![Source::Javdb.as_str().to_string(), Source::Sukebei.as_str().to_string()];
+    if theporndb::enabled() {
+        out.push(Source::Theporndb.as_str().to_string());
+    }
+    out.extend(plugin::plugins().iter().map(|p| p.name.clone()));
+    out
+}
+
+/// `search --sources` names as `search_sources()` spells them, each once; ThePornDB
+/// without a token is an error rather than a silent empty result
+pub fn resolve_search_sources(names: &[String]) -> Result<Vec<String>> {
+    let known = search_sources();
+    let mut out: Vec<String> = Vec::new();
+    for name in names.iter().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        if name.eq_ignore_ascii_case(Source::Theporndb.as_str()) && !theporndb::enabled() {
+            bail!(crate::errors::Tagged::new("config", "ThePornDB 需要先设置 [sources.theporndb] token"));
+        }
+        let Some(k) = known.iter().find(|k| k.eq_ignore_ascii_case(name)) else {
+            bail!(crate::errors::Tagged::new("config", format!("未知的搜索来源: {}（可用: {}）", name, known.join(", "))));
+        };
+        if !out.contains(k) {
+            out.push(k.clone());
+        }
+    }
+    Ok(out)
+}
+
+async fn search_one(source: &str, query: &str) -> Result<Vec<AvItem>> {
+    match source {
+        "javdb" => search_javdb(query).await,
+        "sukebei" => search_sukebei(query).await,
+        "theporndb" => theporndb::search(query).await,
+        name => match plugin::plugins().iter().find(|p| p.name == name) {
+            Some(p) => plugin::search(p, query).await,
+            None => Ok(Vec::new()),
+        },
+    }
+}
+
+/// `[FHD]`, `【4K】`, `1080p`, `.mp4`: a torrent's release name rather than the title
+static RELEASE_NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*[\[【(（]|\.(?:mp4|mkv|avi|wmv|ts)\b|\b(?:2160p|1080p|720p|4k|fhd|uhd|hevc|x26[45]|h\.?26[45])\b").unwrap()
+});
+
+/// The title without its code, case, brackets or punctuation, to tell whether two sources
+/// give the same title
+fn title_key(title: &str, code: &str) -> String {
+    let plain: String = title.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+    let code: String = code.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+    if code.is_empty() { plain } else { plain.replacen(&code, "", 1) }
+}
+
+/// Add `title` to `item`'s titles unless it has it already: a release name only ever as an
+/// alternate, a real title in place of a release name
+fn merge_title(item: &mut AvItem, title: String) {
+    let key = title_key(&title, &item.code);
+    let known = |t: &String| title_key(t, &item.code) == key;
+    if key.is_empty() || known(&item.title) || item.alt_titles.iter().any(known) {
+        return;
+    }
+    if RELEASE_NAME.is_match(&item.title) && !RELEASE_NAME.is_match(&title) {
+        let previous = std::mem::replace(&mut item.title, title);
+        item.alt_titles.insert(0, previous);
+    } else {
+        item.alt_titles.push(title);
+    }
+}
+
+/// Items of the sources' result lists, one per code: the first source to list a code
+/// gives its place and fields, later ones fill in what it lacks, add their names and
+/// their titles when those differ
+fn merge_search(results: Vec<(String, Vec<AvItem>)>) -> Vec<AvItem> {
+    let mut out: Vec<AvItem> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (source, items) in results {
+        for mut item in items {
+            let key = code::normalize(&item.code);
+            match index.get(&key) {
+                Some(&i) => {
+                    let merged = &mut out[i];
+                    if !merged.sources.contains(&source) {
+                        merged.sources.push(source.clone());
+                    }
+                    merged.release_date = merged.release_date.take().or(item.release_date);
+                    merged.rating = merged.rating.or(item.rating);
+                    merged.thumbnail_url = merged.thumbnail_url.take().or(item.thumbnail_url);
+                    merged.has_magnets = merged.has_magnets.or(item.has_magnets);
+                    merged.has_subtitles = merged.has_subtitles.or(item.has_subtitles);
+                    for title in std::iter::once(item.title).chain(item.alt_titles) {
+                        merge_title(merged, title);
+                    }
+                }
+                None => {
+                    index.insert(key, out.len());
+                    // A plugin's own alternates are checked against its title like any other
+                    let alts = std::mem::take(&mut item.alt_titles);
+                    let mut first = AvItem { sources: vec![source.clone()], ..item };
+                    for title in alts {
+                        merge_title(&mut first, title);
+                    }
+                    out.push(first);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `sources` searched all at once and merged; the error is the last one, when every
+/// source failed
+async fn search_all(q: &str, sources: Vec<String>) -> (Vec<AvItem>, Option<anyhow::Error>) {
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, source) in sources.into_iter().enumerate() {
+        let q = q.to_string();
+        tasks.spawn(async move {
+            let found = search_one(&source, &q).await;
+            (idx, source, found)
+        });
+    }
+    let mut done = tasks.join_all().await;
+    done.sort_by_key(|(idx, ..)| *idx);
+    let mut results = Vec::new();
+    let mut last_error = None;
+    for (_, source, found) in done {
+        match found {
+            Ok(items) => results.push((source, items)),
+            Err(e) => {
+                // The built-in sites fail quietly, as they did as fallbacks; a configured one says so
+                if source == "javdb" || source == "sukebei" {
+                    util::debug(format!("search {}: {:#}", source, e));
+                } else {
+                    eprintln!("[WARN] {}: {:#}", source, e);
+                }
+                last_error = Some(e);
+            }
+        }
+    }
+    let error = last_error.filter(|_| results.is_empty());
+    (merge_search(results), error)
+}
+
+/// Keyword search of `sources`, or by default of JavDB, Sukebei, ThePornDB and the `merge`
+/// plugins, all at once and merged by code; the `fallback` plugins are asked in turn only
+/// when those find nothing. A code is looked up as a detail first, unless the sources are
+/// given. Sources that fail are skipped; only when all of them do is it an error.
+pub async fn search_in(query: &str, sources: &[String]) -> Result<Vec<AvItem>> {
+    if mock::is_enabled() {
+        return Ok(mock::search(query).into_iter().map(|i| AvItem { sources: vec!["mock".to_string()], ..i }).collect());
+    }
     let q = query.trim();
-    if looks_like_code(q) {
-        if let Ok(detail) = fetch_detail(q).await {
-            return Ok(vec![AvItem { code: detail.code, title: detail.title }]);
+    if !sources.is_empty() && forced_source().is_some() {
+        bail!(crate::errors::Tagged::new("config", "--source 与 --sources 不能同时使用"));
+    }
+    if looks_like_code(q) && sources.is_empty() {
+        if let Ok(detail) = fetch_detail(&code::normalize(q)).await {
+            return Ok(vec![AvItem::from(&detail)]);
         }
     }
-    let mut items = search_javdb(q).await.unwrap_or_default();
-    if items.is_empty() {
-        items = search_sukebei(q).await.unwrap_or_default();
+    match forced_source() {
+        Some(Source::Javdb) => return search_javdb(q).await,
+        Some(Source::Sukebei) => return search_sukebei(q).await,
+        Some(Source::Theporndb) => return theporndb::search(q).await,
+        Some(s @ (Source::Javlibrary | Source::Dmm | Source::Btsow | Source::Torrentkitty | Source::Torznab | Source::Uncensored | Source::Sokmil)) if !looks_like_code(q) => {
+            bail!("{} 不支持关键词搜索，只能按番号查询", s.as_str())
+        }
+        Some(_) => return Ok(Vec::new()),
+        None => {}
     }
-    Ok(items)
+    if !sources.is_empty() {
+        let (items, error) = search_all(q, resolve_search_sources(sources)?).await;
+        cancel::check()?;
+        return error.map_or(Ok(items), Err);
+    }
+    let fallback = |name: &String| plugin::plugins().iter().any(|p| p.name == *name && p.mode == PluginMode::Fallback);
+    let (items, error) = search_all(q, search_sources().into_iter().filter(|s| !fallback(s)).collect()).await;
+    cancel::check()?;
+    if !items.is_empty() {
+        return Ok(items);
+    }
+    for p in plugin::plugins().iter().filter(|p| p.mode == PluginMode::Fallback) {
+        match plugin::search(p, q).await {
+            Ok(found) if !found.is_empty() => return Ok(merge_search(vec![(p.name.clone(), found)])),
+            Ok(_) => {}
+            Err(e) => eprintln!("[WARN] {:#}", e),
+        }
+    }
+    error.map_or(Ok(items), Err)
 }
 
 pub async fn list_actor_titles(actor: &str) -> Result<Vec<AvItem>> {
-    let mut items = list_actor_javdb(actor).await.unwrap_or_default();
+    list_actor_titles_in(actor, &DateRange::default()).await
+}
+
+/// `list --from/--to`: JavDB's listing is paged further back until it passes `range.from`
+pub async fn list_actor_titles_in(actor: &str, range: &DateRange) -> Result<Vec<AvItem>> {
+    if mock::is_enabled() {
+        return Ok(range.filter(mock::list_actor(actor)));
+    }
+    let mut items = list_actor_javdb(actor, range).await.unwrap_or_default();
+    // JavDB may know her by another name or script (`Yua Mikami` for 三上悠亜)
+    if items.is_empty() {
+        for alias in xslist::aliases(actor).await {
+            util::debug(format!("list: trying {}'s alias {}", actor, alias));
+            items = list_actor_javdb(&alias, range).await.unwrap_or_default();
+            if !items.is_empty() {
+                break;
+            }
+        }
+    }
     if items.is_empty() {
         items = list_actor_sukebei(actor).await.unwrap_or_default();
     }
-    Ok(items)
+    Ok(range.filter(items))
+}
+
+/// `list_actor_titles` for several actors, `--jobs` at a time, in input order
+pub async fn list_actors_titles(actors: &[String]) -> Vec<(String, Result<Vec<AvItem>>)> {
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, actor) in actors.iter().cloned().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let items = list_actor_titles(&actor).await;
+            (idx, actor, items)
+        });
+    }
+    let mut out = tasks.join_all().await;
+    out.sort_by_key(|(idx, _, _)| *idx);
+    out.into_iter().map(|(_, actor, items)| (actor, items)).collect()
 }
 
-fn looks_like_code(s: &str) -> bool {
-    let re = Regex::new(r"(?i)^[a-z]{2,5}-?\d{2,5}").unwrap();
-    re.is_match(s)
+pub fn looks_like_code(s: &str) -> bool {
+    code::parse(s).is_some()
 }
 
 pub async fn top(limit: usize) -> Result<Vec<AvItem>> {
+    if mock::is_enabled() {
+        return Ok(mock::top(limit));
+    }
     // Try multiple ordering pages on JavDB: most recent, trending, etc.
     let c = client();
     let mut items: Vec<AvItem> = Vec::new();
@@ -143,152 +1028,313 @@ pub async fn top(limit: usize) -> Result<Vec<AvItem>> {
         format!("{}/videos?o=mr", javdb_base()), // most recent
         format!("{}/videos?o=tr", javdb_base()), // trending
     ];
-    let card_sel = Selector::parse(".movie-list .item a.box.cover, .movie-list a[href^='/v/'], a.box[href^='/v/']").unwrap();
-    let title_sel = Selector::parse(".video-title").unwrap();
     for url in &endpoints {
         util::debug(format!("JavDB top page: {}", url));
-        let body = c.get(url).send().await?.error_for_status()?.text().await?;
-        let doc = Html::parse_document(&body);
-        for a in doc.select(&card_sel) {
-            let href = a.value().attr("href").unwrap_or("");
-            let title = a.select(&title_sel).next().map(|n| n.text().collect::<String>()).unwrap_or_else(|| a.text().collect::<String>());
-            let code = extract_code_from_title(&title).unwrap_or_else(|| href.split('/').last().unwrap_or("").to_string());
-            if !code.is_empty() && !title.is_empty() {
-                items.push(AvItem { code: code.to_uppercase(), title });
-                if items.len() >= limit { return Ok(items); }
+        let body = get_text(&c, url).await?;
+        for item in javdb_cards(&body) {
+            items.push(item);
+            if items.len() >= limit { return Ok(items); }
+        }
+    }
+    Ok(items)
+}
+
+/// Pages of a maker's JavDB listing `top --studio` reads at most, about 40 titles each
+const STUDIO_PAGES: usize = 5;
+
+/// `top --studio`: the maker's newest titles from its JavDB listing. When JavDB has no
+/// page for the name, the regular latest releases are kept whose detail (mostly cached)
+/// names that studio.
+pub async fn top_by_studio(name: &str, limit: usize) -> Result<Vec<AvItem>> {
+    if mock::is_enabled() {
+        let mut items = mock::list_studio_or_series(name, false);
+        items.truncate(limit);
+        return Ok(items);
+    }
+    let url = match javdb_listing_url("studio_link", name).await {
+        Ok(url) => url,
+        Err(e) => {
+            util::debug(format!("top --studio: no JavDB listing ({:#}), filtering the latest releases", e));
+            let latest = top(usize::MAX).await?;
+            let keep: Vec<String> = fetch_details(latest.iter().map(|i| i.code.clone()).collect())
+                .await
+                .into_iter()
+                .filter(|d| d.studio.as_deref().is_some_and(|s| s.trim().eq_ignore_ascii_case(name.trim())))
+                .map(|d| d.code)
+                .collect();
+            return Ok(latest.into_iter().filter(|i| keep.contains(&i.code)).take(limit).collect());
+        }
+    };
+    let c = client();
+    let mut items: Vec<AvItem> = Vec::new();
+    let sep = if url.contains('?') { '&' } else { '?' };
+    for page in 1..=STUDIO_PAGES {
+        cancel::check()?;
+        let page_url = format!("{}{}page={}", url, sep, page);
+        util::debug(format!("JavDB studio page: {}", page_url));
+        let cards = javdb_cards(&get_text(&c, &page_url).await?);
+        if cards.is_empty() {
+            break;
+        }
+        for item in cards {
+            if !items.iter().any(|i| i.code == item.code) {
+                items.push(item);
             }
         }
+        if items.len() >= limit {
+            break;
+        }
     }
+    items.truncate(limit);
     Ok(items)
 }
 
+static CARD_SCORE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d(?:\.\d+)?)\s*分").unwrap());
+
+/// What a JavDB listing card shows besides the code and title: the release date, the
+/// "4.47分, 由595人評價" score, the thumbnail and the magnet / subtitle badges
+fn card_fields(card: scraper::ElementRef) -> AvItem {
+    let text = |key: &str| card.select(selectors::javdb(key)).next().map(|n| n.text().collect::<String>());
+    let thumbnail_url = card
+        .select(selectors::javdb("movie_thumbnail"))
+        .next()
+        .and_then(|img| img.value().attr("data-src").or_else(|| img.value().attr("src")))
+        .filter(|s| !s.is_empty() && !s.starts_with("data:"))
+        .map(|s| match s {
+            s if s.starts_with("//") => format!("https:{}", s),
+            s if s.starts_with("http") => s.to_string(),
+            s => format!("{}/{}", javdb_base(), s.trim_start_matches('/')),
+        });
+    let tags: Vec<String> = card.select(selectors::javdb("movie_tag")).map(|t| t.text().collect::<String>()).collect();
+    AvItem {
+        release_date: text("movie_date").and_then(|t| dates::parse(&t)).map(|d| d.to_string()),
+        rating: text("movie_score")
+            .and_then(|t| CARD_SCORE.captures(&t).and_then(|c| c[1].parse().ok()))
+            .filter(|r| (0.0..=5.0).contains(r)),
+        thumbnail_url,
+        has_magnets: Some(tags.iter().any(|t| t.contains("磁鏈") || t.contains("磁链"))),
+        has_subtitles: Some(tags.iter().any(|t| t.contains("中字"))),
+        ..AvItem::default()
+    }
+}
+
+/// Movie cards on JavDB listing pages (latest, rankings, user lists)
+pub(crate) fn javdb_cards(body: &str) -> Vec<AvItem> {
+    let doc = Html::parse_document(body);
+    let card_sel = selectors::javdb("movie_card");
+    let title_sel = selectors::javdb("movie_title");
+    let mut items = Vec::new();
+    for a in doc.select(card_sel) {
+        let href = a.value().attr("href").unwrap_or("");
+        let title = a.select(title_sel).next().map(|n| n.text().collect::<String>()).unwrap_or_else(|| a.text().collect::<String>());
+        let code = extract_code_from_title(&title).unwrap_or_else(|| href.split('/').next_back().unwrap_or("").to_string());
+        if !code.is_empty() && !title.is_empty() {
+            items.push(AvItem { code: code.to_uppercase(), title, ..card_fields(a) });
+        }
+    }
+    items
+}
+
+/// Resolve a code to its JavDB video page URL via search
+pub(crate) async fn javdb_video_url(c: &reqwest::Client, code: &str) -> Result<String> {
+    let url = javdb_search_url(code);
+    let body = get_text(c, &url).await?;
+    let href = first_javdb_result_href(&Html::parse_document(&body)).ok_or_else(|| FetchError::not_found("javdb", "JavDB 未找到该番号"))?;
+    Ok(if href.starts_with("http") { href } else { format!("{}{}", javdb_base(), href) })
+}
+
+/// Public JavDB search page for a code, suitable for handing to users
+pub fn javdb_search_url(code: &str) -> String {
+    format!("{}/search?q={}&f=all", javdb_base(), encode(code))
+}
+
 async fn fetch_detail_from_javdb(code: &str) -> Result<AvDetail> {
     let c = client();
     let url = format!("{}/search?q={}&f=all", javdb_base(), encode(code));
     util::debug(format!("JavDB search: {}", url));
-    let body = c.get(&url).send().await?.error_for_status()?.text().await?;
-    let doc = Html::parse_document(&body);
-    // If search redirected or rendered directly to detail page
-    if doc.select(&Selector::parse(".video-meta-panel").unwrap()).next().is_some() {
-        util::debug("JavDB: search rendered detail page directly");
-        return parse_javdb_detail(&c, &url).await;
-    }
-    // Try several selectors to find the first result link
-    let candidates = [
-        ".movie-list .item a.box.cover",
-        ".movie-list a[href^='/v/']",
-        "a.box[href^='/v/']",
-        "a[href^='/v/']",
-    ];
-    let mut href: Option<String> = None;
-    for sel in candidates {
-        let s = Selector::parse(sel).unwrap();
-        if let Some(a) = doc.select(&s).next() {
-            if let Some(h) = a.value().attr("href") {
-                href = Some(h.to_string());
-                util::debug(format!("JavDB: picked result via selector '{}' => {}", sel, h));
-                break;
-            }
+    let body = get_text(&c, &url).await?;
+    // Parsed documents are not Send; keep them out of scope across awaits
+    let href = {
+        let doc = Html::parse_document(&body);
+        // If search redirected or rendered directly to detail page
+        if doc.select(selectors::javdb("detail_marker")).next().is_some() {
+            None
+        } else {
+            Some(first_javdb_result_href(&doc).ok_or_else(|| FetchError::not_found("javdb", "JavDB 未找到该番号"))?)
         }
-    }
-    let href = href.context("JavDB 未找到该番号")?;
+    };
+    let href = match href {
+        Some(h) => h,
+        None => {
+            util::debug("JavDB: search rendered detail page directly");
+            return parse_javdb_detail(&c, &url).await;
+        }
+    };
     let detail_url = if href.starts_with("http") { href.to_string() } else { format!("{}{}", javdb_base(), href) };
     util::debug(format!("JavDB detail: {}", detail_url));
     parse_javdb_detail(&c, &detail_url).await
 }
 
-pub async fn get_play_url(code: &str) -> Result<String> {
-    let c = client();
-    let url = format!("{}/search?q={}&f=all", javdb_base(), encode(code));
-    util::debug(format!("JavDB search for play: {}", url));
-    let body = c.get(&url).send().await?.error_for_status()?.text().await?;
-    let doc = Html::parse_document(&body);
-    
-    // If search redirected or rendered directly to detail page
-    let play_sel = Selector::parse(".cover-container[href*='play'], a.cover-container[href*='play'], a[href*='play']").unwrap();
-    if let Some(play) = doc.select(&play_sel).next().and_then(|a| a.value().attr("href")) {
-        let play_url = if play.starts_with("http") { play.to_string() } else { format!("{}{}", javdb_base(), play) };
-        util::debug(format!("JavDB play URL: {}", play_url));
-        return Ok(play_url);
-    }
-    
-    // Try to get detail page first, then look for play link
-    let candidates = [
-        ".movie-list .item a.box.cover",
-        ".movie-list a[href^='/v/']",
-        "a.box[href^='/v/']",
-        "a[href^='/v/']",
-    ];
-    let mut href: Option<String> = None;
-    for sel in candidates {
-        let s = Selector::parse(sel).unwrap();
-        if let Some(a) = doc.select(&s).next() {
+static ANY_DETAIL: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a[href^='/v/']").unwrap());
+
+fn first_javdb_result_href(doc: &Html) -> Option<String> {
+    // The result cards first, then any detail link on the page
+    for (name, s) in [("movie_card", selectors::javdb("movie_card")), ("a[href^='/v/']", &*ANY_DETAIL)] {
+        if let Some(a) = doc.select(s).next() {
             if let Some(h) = a.value().attr("href") {
-                href = Some(h.to_string());
-                break;
+                util::debug(format!("JavDB: picked result via selector '{}' => {}", name, h));
+                return Some(h.to_string());
             }
         }
     }
-    
+    None
+}
+
+/// JavDB's play links for `code`, from the search page or else the first result's detail page
+async fn javdb_play_urls(c: &reqwest::Client, code: &str) -> Result<Vec<String>> {
+    let url = javdb_search_url(code);
+    util::debug(format!("JavDB search for play: {}", url));
+    let body = get_text(c, &url).await?;
+
+    // If search redirected or rendered directly to detail page
+    let (mut plays, href) = {
+        let doc = Html::parse_document(&body);
+        let plays = find_play_hrefs(&doc);
+        // Try to get detail page first, then look for play link
+        let href = if plays.is_empty() { first_javdb_result_href(&doc) } else { None };
+        (plays, href)
+    };
     if let Some(href) = href {
         let detail_url = if href.starts_with("http") { href } else { format!("{}{}", javdb_base(), href) };
-        let detail_body = c.get(&detail_url).send().await?.error_for_status()?.text().await?;
-        let detail_doc = Html::parse_document(&detail_body);
-        
-        // Look for play button on detail page
-        if let Some(play) = detail_doc.select(&play_sel).next().and_then(|a| a.value().attr("href")) {
-            let play_url = if play.starts_with("http") { play.to_string() } else { format!("{}{}", javdb_base(), play) };
-            util::debug(format!("JavDB play URL from detail: {}", play_url));
-            return Ok(play_url);
+        let detail_body = get_text(c, &detail_url).await?;
+        plays = find_play_hrefs(&Html::parse_document(&detail_body));
+    }
+    let plays: Vec<String> = plays
+        .into_iter()
+        .map(|p| if p.starts_with("http") { p } else { format!("{}{}", javdb_base(), p) })
+        .collect();
+    util::debug(format!("JavDB play URLs: {:?}", plays));
+    Ok(plays)
+}
+
+/// `[view] streaming` templates filled in for `code`
+fn streaming_urls(code: &str) -> Vec<PlayCandidate> {
+    config::get()
+        .view
+        .streaming
+        .iter()
+        .map(|t| {
+            let url = t.replace("{code_lower}", &encode(&code.to_lowercase())).replace("{code}", &encode(code));
+            let source = reqwest::Url::parse(&url)
+                .ok()
+                .and_then(|u| u.host_str().map(|h| h.trim_start_matches("www.").to_string()))
+                .unwrap_or_else(|| "streaming".to_string());
+            PlayCandidate { source, url }
+        })
+        .collect()
+}
+
+/// Every page `av view` can open for `code`: JavDB's play links, then the configured
+/// streaming sites. When neither yields anything, the JavDB search page is the one entry.
+/// A JavDB failure is only an error when there are no streaming sites to fall back on.
+pub async fn play_candidates(code: &str) -> Result<Vec<PlayCandidate>> {
+    let code = code::normalize(code);
+    let streaming = streaming_urls(&code);
+    let javdb = if mock::is_enabled() {
+        vec![mock::play_url(&code)?]
+    } else {
+        match javdb_play_urls(&client(), &code).await {
+            Ok(urls) => urls,
+            Err(e) if !streaming.is_empty() => {
+                eprintln!("[WARN] JavDB 播放链接获取失败: {:#}", e);
+                Vec::new()
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    let mut out: Vec<PlayCandidate> = javdb.into_iter().map(|url| PlayCandidate { source: "javdb".to_string(), url }).collect();
+    out.extend(streaming);
+    if out.is_empty() {
+        out.push(PlayCandidate { source: "search".to_string(), url: javdb_search_url(&code) });
+    }
+    Ok(out)
+}
+
+fn find_play_hrefs(doc: &Html) -> Vec<String> {
+    let mut hrefs: Vec<String> = Vec::new();
+    for h in doc.select(selectors::javdb("play_link")).filter_map(|a| a.value().attr("href")) {
+        if !h.trim().is_empty() && !hrefs.iter().any(|x| x == h) {
+            hrefs.push(h.to_string());
         }
     }
-    
-    // Fallback: just return the search URL
-    Ok(url)
+    hrefs
 }
 
+// Generic selectors and the detail page's fallbacks, compiled once rather than per page
+static LINK: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a").unwrap());
+static CELL: LazyLock<Selector> = LazyLock::new(|| Selector::parse("td").unwrap());
+static IMG: LazyLock<Selector> = LazyLock::new(|| Selector::parse("img").unwrap());
+static PAGE_TITLE: LazyLock<Selector> = LazyLock::new(|| Selector::parse("title").unwrap());
+static OG_IMAGE: LazyLock<Selector> = LazyLock::new(|| Selector::parse("meta[property='og:image']").unwrap());
+static MINUTES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{2,3})").unwrap());
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([0-9]+(?:\.[0-9]+)?)").unwrap());
+static BODY_MINUTES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{2,3})\s*min").unwrap());
+static BODY_MINUTES_ANY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{2,3})\s*(分钟|分|min|MIN)").unwrap());
+static BODY_RATING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"Rating\s*([0-9]+(?:\.[0-9]+)?)").unwrap());
+static BODY_SCORE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"评分\s*([0-9]+(?:\.[0-9]+)?)|Score\s*([0-9]+(?:\.[0-9]+)?)").unwrap());
+static BODY_DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(20\d{2}-\d{2}-\d{2})").unwrap());
+
 async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail> {
-    let body = c.get(url).send().await?.error_for_status()?.text().await?;
-    let doc = Html::parse_document(&body);
-    let title_sel = Selector::parse(".title strong, h2.title").unwrap();
+    let body = get_text(c, url).await?;
+    parse_javdb_page(&body, url)
+}
+
+/// A JavDB detail page's fields, unmerged; `url` only goes into the error for a page
+/// that isn't one
+pub(crate) fn parse_javdb_page(body: &str, url: &str) -> Result<AvDetail> {
+    let doc = Html::parse_document(body);
+    let title_sel = selectors::javdb("title");
     let title = doc
-        .select(&title_sel)
+        .select(title_sel)
         .next()
         .map(|n| n.text().collect::<String>())
         .unwrap_or_else(|| {
-            doc.select(&Selector::parse("title").unwrap())
+            doc.select(&PAGE_TITLE)
                 .next()
                 .map(|n| n.text().collect::<String>())
                 .unwrap_or_default()
         });
 
-    let meta_sel = Selector::parse(".panel-block .value").unwrap();
+    let meta_sel = selectors::javdb("meta_value");
     let mut code = String::new();
     let mut date: Option<String> = None;
-    for val in doc.select(&meta_sel) {
+    for val in doc.select(meta_sel) {
         let txt = val.text().collect::<String>().trim().to_string();
-        if code.is_empty() && looks_like_code(&txt) { code = txt.to_uppercase(); }
+        if code.is_empty() && looks_like_code(&txt) { code = code::normalize(&txt); }
         if txt.contains('-') && txt.len() == 10 && txt.chars().nth(4) == Some('-') { date = Some(txt); }
     }
+    if code.is_empty() && doc.select(selectors::javdb("info_block")).next().is_none() {
+        bail!(sanity::unexpected(url, "页面中没有番号和影片信息，不是详情页"));
+    }
 
-    let cover_sel = Selector::parse("img.video-cover, .video-cover img").unwrap();
+    let cover_sel = selectors::javdb("cover");
     let mut cover_url = doc
-        .select(&cover_sel)
+        .select(cover_sel)
         .next()
         .and_then(|n| n.value().attr("src"))
         .map(|s| s.to_string());
     if cover_url.is_none() {
-        let og_sel = Selector::parse("meta[property='og:image']").unwrap();
         cover_url = doc
-            .select(&og_sel)
+            .select(&OG_IMAGE)
             .next()
             .and_then(|n| n.value().attr("content"))
             .map(|s| s.to_string());
     }
 
-    let actor_sel = Selector::parse(".panel-block a[href*='/actors/'], a[href*='/actors/']").unwrap();
+    let actor_sel = selectors::javdb("actor_link");
     let mut actor_names = doc
-        .select(&actor_sel)
+        .select(actor_sel)
         .map(|n| n.text().collect::<String>().trim().to_string())
         .filter(|s| !s.is_empty())
         .collect::<Vec<_>>();
@@ -301,19 +1347,20 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
     let mut series: Option<String> = None;
     let mut genres: Vec<String> = Vec::new();
     let mut rating: Option<f32> = None;
+    let mut votes: Option<u32> = None;
 
     // Parse structured blocks in the movie info panel
-    let block_sel = Selector::parse("nav.panel.movie-panel-info .panel-block").unwrap();
-    let strong_sel = Selector::parse("strong").unwrap();
-    let value_sel = Selector::parse(".value").unwrap();
-    for bl in doc.select(&block_sel) {
+    let block_sel = selectors::javdb("info_block");
+    let strong_sel = selectors::javdb("info_label");
+    let value_sel = selectors::javdb("info_value");
+    for bl in doc.select(block_sel) {
         let label_text = bl
-            .select(&strong_sel)
+            .select(strong_sel)
             .next()
             .map(|n| n.text().collect::<String>())
             .unwrap_or_default()
             .to_lowercase();
-        let value_node = bl.select(&value_sel).next();
+        let value_node = bl.select(value_sel).next();
         let value_text = value_node
             .as_ref()
             .map(|n| n.text().collect::<String>().trim().to_string())
@@ -322,44 +1369,45 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
         if label_text.contains("id") && code.is_empty() {
             let raw = value_text.replace('\n', " ");
             let raw = raw.trim();
-            if looks_like_code(raw) { code = raw.to_uppercase(); }
+            if looks_like_code(raw) { code = code::normalize(raw); }
         }
-        if label_text.contains("released") {
-            if !value_text.is_empty() { date = Some(value_text.clone()); }
+        if label_text.contains("released") && !value_text.is_empty() {
+            date = Some(value_text.clone());
         }
         if label_text.contains("duration") {
-            if let Some(m) = Regex::new(r"(\d{2,3})").unwrap().captures(&value_text).and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<u32>().ok()) {
+            if let Some(m) = MINUTES.captures(&value_text).and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<u32>().ok()) {
                 duration_minutes = Some(m);
             }
         }
         if label_text.contains("director") {
-            if let Some(a) = value_node.as_ref().and_then(|n| n.select(&Selector::parse("a").unwrap()).next()) {
+            if let Some(a) = value_node.as_ref().and_then(|n| n.select(&LINK).next()) {
                 let name = a.text().collect::<String>().trim().to_string();
                 if !name.is_empty() { director = Some(name); }
             }
         }
         if label_text.contains("maker") {
-            if let Some(a) = value_node.as_ref().and_then(|n| n.select(&Selector::parse("a").unwrap()).next()) {
+            if let Some(a) = value_node.as_ref().and_then(|n| n.select(&LINK).next()) {
                 let name = a.text().collect::<String>().trim().to_string();
                 if !name.is_empty() { studio = Some(name); }
             }
         }
         if label_text.contains("rating") {
-            if let Some(v) = Regex::new(r"([0-9]+(?:\.[0-9]+)?)").unwrap().captures(&value_text).and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<f32>().ok()) {
+            if let Some(v) = NUMBER.captures(&value_text).and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<f32>().ok()) {
                 rating = Some(v);
+                votes = rating::votes_in(&value_text);
             }
         }
         if label_text.contains("tags") {
             let tags = value_node
                 .as_ref()
-                .map(|n| n.select(&Selector::parse("a").unwrap()).map(|a| a.text().collect::<String>().trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>())
+                .map(|n| n.select(&LINK).map(|a| a.text().collect::<String>().trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>())
                 .unwrap_or_default();
             if !tags.is_empty() { genres = tags; }
         }
         if label_text.contains("actor") {
             let names = value_node
                 .as_ref()
-                .map(|n| n.select(&Selector::parse("a").unwrap()).map(|a| a.text().collect::<String>().trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>())
+                .map(|n| n.select(&LINK).map(|a| a.text().collect::<String>().trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>())
                 .unwrap_or_default();
             if !names.is_empty() { actor_names = names; }
         }
@@ -368,30 +1416,29 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
     // Init advanced fields before filling (already declared above)
 
     // Additional named links
-    let get_one_text = |selector: &str| -> Option<String> {
-        let s = Selector::parse(selector).ok()?;
-        doc.select(&s)
+    let get_one_text = |key: &str| -> Option<String> {
+        doc.select(selectors::javdb(key))
             .next()
             .map(|n| n.text().collect::<String>().trim().to_string())
             .filter(|t| !t.is_empty())
     };
 
-    if let Some(v) = get_one_text("a[href*='/directors/']") { director = Some(v); }
-    if let Some(v) = get_one_text("a[href*='/studios/']") { studio = Some(v); }
-    if let Some(v) = get_one_text("a[href*='/labels/']") { label = Some(v); }
-    if let Some(v) = get_one_text("a[href*='/series/']") { series = Some(v); }
+    if let Some(v) = get_one_text("director_link") { director = Some(v); }
+    if let Some(v) = get_one_text("studio_link") { studio = Some(v); }
+    if let Some(v) = get_one_text("label_link") { label = Some(v); }
+    if let Some(v) = get_one_text("series_link") { series = Some(v); }
 
-    let plot_sel = Selector::parse(".panel-block .value pre, .panel-block .value p").unwrap();
+    let plot_sel = selectors::javdb("plot");
     let mut plot = doc
-        .select(&plot_sel)
+        .select(plot_sel)
         .map(|n| n.text().collect::<String>().trim().to_string())
         .find(|s| s.len() > 10);
 
     // Parse key/value meta rows (JavDB often uses dl/dt/dd or blocks). We'll look for dt labels.
 
     // Fallback: scan labeled anchors
-    let label_link_sel = Selector::parse(".panel-block a.tag, .panel-block a[href*='/tags/']").unwrap();
-    for a in doc.select(&label_link_sel) {
+    let label_link_sel = selectors::javdb("tag_link");
+    for a in doc.select(label_link_sel) {
         let t = a.text().collect::<String>().trim().to_string();
         if !t.is_empty() {
             genres.push(t);
@@ -402,8 +1449,7 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
 
     // Heuristics for duration and rating
     let body_text = doc.root_element().text().collect::<String>();
-    if let Some(mins) = Regex::new(r"(\d{2,3})\s*min")
-        .unwrap()
+    if let Some(mins) = BODY_MINUTES
         .captures(&body_text)
         .and_then(|c| c.get(1))
         .and_then(|m| m.as_str().parse::<u32>().ok())
@@ -411,8 +1457,7 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
         duration_minutes = Some(mins);
     }
     if duration_minutes.is_none() {
-        if let Some(mins2) = Regex::new(r"(\d{2,3})\s*(分钟|分|min|MIN)")
-            .unwrap()
+        if let Some(mins2) = BODY_MINUTES_ANY
             .captures(&body_text)
             .and_then(|c| c.get(1))
             .and_then(|m| m.as_str().parse::<u32>().ok())
@@ -420,8 +1465,7 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
             duration_minutes = Some(mins2);
         }
     }
-    if let Some(r) = Regex::new(r"Rating\s*([0-9]+(?:\.[0-9]+)?)")
-        .unwrap()
+    if let Some(r) = BODY_RATING
         .captures(&body_text)
         .and_then(|c| c.get(1))
         .and_then(|m| m.as_str().parse::<f32>().ok())
@@ -429,8 +1473,7 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
         rating = Some(r);
     }
     if rating.is_none() {
-        if let Some(r2) = Regex::new(r"评分\s*([0-9]+(?:\.[0-9]+)?)|Score\s*([0-9]+(?:\.[0-9]+)?)")
-            .unwrap()
+        if let Some(r2) = BODY_SCORE
             .captures(&body_text)
             .and_then(|c| c.get(1).or(c.get(2)))
             .and_then(|m| m.as_str().parse::<f32>().ok())
@@ -441,8 +1484,7 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
 
     // Release date robust regex
     if date.is_none() {
-        if let Some(d) = Regex::new(r"(20\d{2}-\d{2}-\d{2})")
-            .unwrap()
+        if let Some(d) = BODY_DATE
             .captures(&body_text)
             .and_then(|c| c.get(1))
             .map(|m| m.as_str().to_string())
@@ -452,53 +1494,63 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
     }
 
     // Try to parse some named fields by nearby labels
-    let meta_row_sel = Selector::parse(".panel-block").unwrap();
-    for row in doc.select(&meta_row_sel) {
+    let meta_row_sel = selectors::javdb("meta_row");
+    for row in doc.select(meta_row_sel) {
         let label_text = row
-            .select(&Selector::parse(".header, dt").unwrap())
+            .select(selectors::javdb("meta_row_label"))
             .next()
             .map(|n| n.text().collect::<String>())
             .unwrap_or_default();
         let value_text = row
-            .select(&Selector::parse(".value, dd").unwrap())
+            .select(selectors::javdb("meta_row_value"))
             .next()
             .map(|n| n.text().collect::<String>().trim().to_string())
             .unwrap_or_default();
         let lt = label_text.trim();
-        if lt.contains("导演") || lt.contains("Director") {
-            if !value_text.is_empty() { director = Some(value_text.clone()); }
+        if (lt.contains("导演") || lt.contains("Director")) && !value_text.is_empty() {
+            director = Some(value_text.clone());
         }
-        if lt.contains("片商") || lt.contains("Studio") {
-            if !value_text.is_empty() { studio = Some(value_text.clone()); }
+        if (lt.contains("片商") || lt.contains("Studio")) && !value_text.is_empty() {
+            studio = Some(value_text.clone());
         }
-        if lt.contains("厂牌") || lt.contains("Label") {
-            if !value_text.is_empty() { label = Some(value_text.clone()); }
+        if (lt.contains("厂牌") || lt.contains("Label")) && !value_text.is_empty() {
+            label = Some(value_text.clone());
         }
-        if lt.contains("系列") || lt.contains("Series") {
-            if !value_text.is_empty() { series = Some(value_text.clone()); }
+        if (lt.contains("系列") || lt.contains("Series")) && !value_text.is_empty() {
+            series = Some(value_text.clone());
         }
         if lt.contains("时长") || lt.contains("Length") {
-            if let Some(m) = Regex::new(r"(\d{2,3})").unwrap().captures(&value_text).and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<u32>().ok()) {
+            if let Some(m) = MINUTES.captures(&value_text).and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<u32>().ok()) {
                 duration_minutes = Some(m);
             }
         }
         if lt.contains("评分") || lt.contains("Rating") {
-            if let Some(v) = Regex::new(r"([0-9]+(?:\.[0-9]+)?)").unwrap().captures(&value_text).and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<f32>().ok()) {
+            if let Some(v) = NUMBER.captures(&value_text).and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<f32>().ok()) {
                 rating = Some(v);
+                votes = rating::votes_in(&value_text);
             }
         }
     }
 
     // Preview images
-    let preview_sel = Selector::parse(".preview-images img, .samples .column img, .tile.is-child img, .sample-box img").unwrap();
+    let preview_sel = selectors::javdb("preview_image");
     let mut preview_images = doc
-        .select(&preview_sel)
+        .select(preview_sel)
         .filter_map(|img| img.value().attr("src"))
         .map(|s| s.to_string())
         .collect::<Vec<_>>();
 
-    let magnets = extract_magnets_from_text(&body);
+    let trailer_url = doc
+        .select(selectors::javdb("trailer"))
+        .filter_map(|v| v.value().attr("src"))
+        .map(|s| if s.starts_with("//") { format!("https:{}", s) } else { s.to_string() })
+        .find(|s| !s.trim().is_empty());
+
+    let magnets = extract_magnets_from_text(body);
     let magnet_infos = extract_magnet_infos_from_javdb(&doc, &magnets);
+    if magnets.is_empty() && !javdb_logged_in() && body.contains("/login") {
+        util::debug("JavDB: no magnets on page; some titles only show them when logged in (av login javdb)");
+    }
 
     // Try JSON-LD for richer metadata
     let (ld_plot, ld_minutes, ld_actors, ld_images, ld_studio) = extract_ld_json_metadata(&doc);
@@ -507,6 +1559,8 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
     if actor_names.is_empty() && !ld_actors.is_empty() { actor_names = ld_actors; }
     if preview_images.is_empty() && !ld_images.is_empty() { preview_images = ld_images; }
     if studio.is_none() && ld_studio.is_some() { studio = ld_studio; }
+    let ratings = rating.and_then(|r| rating::from_source("javdb", r, rating::SCALE, votes)).into_iter().collect();
+    let popularity = javdb_popularity(&body_text);
     Ok(AvDetail {
         code,
         title,
@@ -520,108 +1574,139 @@ async fn parse_javdb_detail(c: &reqwest::Client, url: &str) -> Result<AvDetail>
         label,
         series,
         genres,
+        genre_ids: Vec::new(),
         rating,
+        ratings,
+        popularity,
         preview_images,
+        trailer_url,
+        sample_video_url: None,
+        amateur: false,
         magnet_infos,
         magnets,
     })
 }
 
-async fn fetch_detail_from_sukebei(code: &str) -> Result<AvDetail> {
-    let c = client();
+/// `code`'s first Sukebei search hit: its view page, torrent title and the row's magnet
+async fn sukebei_first_result(c: &reqwest::Client, code: &str) -> Result<(String, String, Option<MagnetInfo>)> {
     let url = format!("https://sukebei.nyaa.si/?f=0&c=0_0&q={}", encode(code));
-    let body = c.get(&url).send().await?.error_for_status()?.text().await?;
-    let doc = Html::parse_document(&body);
-    let row_sel = Selector::parse("table.torrent-list tbody tr").unwrap();
-    let title_sel = Selector::parse("td[colspan] a, td:nth-child(2) a").unwrap();
-    let mut first_link: Option<String> = None;
-    let mut first_title: String = String::new();
-    let mut first_row_html: Option<scraper::element_ref::ElementRef> = None;
-    for row in doc.select(&row_sel) {
-        if let Some(a) = row.select(&title_sel).next() {
-            let t = a.text().collect::<String>();
-            if t.to_uppercase().contains(code) {
-                if let Some(href) = a.value().attr("href") {
-                    first_link = Some(href.to_string());
-                    first_title = t;
-                    first_row_html = Some(row);
-                    break;
+    let body = get_text(c, &url).await?;
+    let (first_link, first_title, row_info) = {
+        let doc = Html::parse_document(&body);
+        let row_sel = selectors::sukebei("row");
+        let title_sel = selectors::sukebei("row_title");
+        let mut first_link: Option<String> = None;
+        let mut first_title: String = String::new();
+        let mut row_info: Option<MagnetInfo> = None;
+        for row in doc.select(row_sel) {
+            if let Some(a) = row.select(title_sel).next() {
+                let t = a.text().collect::<String>();
+                if t.to_uppercase().contains(code) {
+                    if let Some(href) = a.value().attr("href") {
+                        first_link = Some(href.to_string());
+                        row_info = sukebei_row_magnet_info(row, &t);
+                        first_title = t;
+                        break;
+                    }
                 }
             }
         }
-    }
-    let page_url = first_link.context("Sukebei 未找到该番号")?;
+        (first_link, first_title, row_info)
+    };
+    let page_url = first_link.ok_or_else(|| FetchError::not_found("sukebei", "Sukebei 未找到该番号"))?;
     let detail_url = if page_url.starts_with("http") { page_url } else { format!("https://sukebei.nyaa.si{}", page_url) };
+    Ok((detail_url, first_title, row_info))
+}
+
+/// Current magnets for `code` (Sukebei's rows carry the seeders and sizes; the indexes'
+/// when Sukebei has none, or all with `--all-magnets`), without touching the metadata sources
+pub async fn fetch_magnets(code: &str) -> Result<(Vec<String>, Vec<MagnetInfo>)> {
+    if mock::is_enabled() {
+        let d = mock::detail(code)?;
+        return Ok((d.magnets, d.magnet_infos));
+    }
+    let d = magnet_sources_detail(&code::normalize(code)).await?;
+    Ok((d.magnets, d.magnet_infos))
+}
+
+async fn fetch_detail_from_sukebei(code: &str) -> Result<AvDetail> {
+    let c = client();
+    let (detail_url, first_title, row_info) = sukebei_first_result(&c, code).await?;
     let mut detail = parse_sukebei_detail(&c, &detail_url, code, &first_title).await?;
 
     // Try to enrich magnet_infos from the row
-    if let Some(row) = first_row_html {
-        let tds: Vec<_> = row.select(&Selector::parse("td").unwrap()).collect();
-        let magnet = row
-            .select(&Selector::parse("a[href^='magnet:']").unwrap())
-            .next()
-            .and_then(|a| a.value().attr("href"))
-            .map(|s| s.to_string());
-        let size = tds.get(3).map(|n| n.text().collect::<String>().trim().to_string());
-        let date = tds.get(4).map(|n| n.text().collect::<String>().trim().to_string());
-        let seeders = tds
-            .get(5)
-            .and_then(|n| n.text().collect::<String>().trim().parse::<u32>().ok());
-        let leechers = tds
-            .get(6)
-            .and_then(|n| n.text().collect::<String>().trim().parse::<u32>().ok());
-        let downloads = tds
-            .get(7)
-            .and_then(|n| n.text().collect::<String>().trim().parse::<u32>().ok());
-
-        if let Some(mag) = magnet.clone() {
-            let mi = MagnetInfo {
-                url: mag.clone(),
-                name: Some(first_title.clone()),
-                size,
-                date,
-                seeders,
-                leechers,
-                downloads,
-                resolution: None,
-                codec: None,
-                avg_bitrate_mbps: None,
-            };
-            // insert if not exists
-            let exists = detail.magnet_infos.iter().any(|x| x.url == mi.url);
-            if !exists {
-                detail.magnet_infos.push(mi);
-            }
-            // also ensure magnets list contains it
-            if !detail.magnets.iter().any(|m| m == &mag) {
-                detail.magnets.push(mag);
-            }
+    if let Some(mi) = row_info {
+        let mag = mi.url.clone();
+        // insert if not exists
+        let exists = detail.magnet_infos.iter().any(|x| x.url == mi.url);
+        if !exists {
+            detail.magnet_infos.push(mi);
+        }
+        // also ensure magnets list contains it
+        if !detail.magnets.iter().any(|m| m == &mag) {
+            detail.magnets.push(mag);
         }
     }
 
     Ok(detail)
 }
 
+fn sukebei_row_magnet_info(row: scraper::element_ref::ElementRef, title: &str) -> Option<MagnetInfo> {
+    let tds: Vec<_> = row.select(&CELL).collect();
+    let magnet = row
+        .select(selectors::sukebei("magnet_link"))
+        .next()
+        .and_then(|a| a.value().attr("href"))
+        .map(|s| s.to_string())?;
+    let size = tds.get(3).map(|n| n.text().collect::<String>().trim().to_string());
+    let date = tds.get(4).map(|n| n.text().collect::<String>().trim().to_string());
+    let seeders = tds
+        .get(5)
+        .and_then(|n| n.text().collect::<String>().trim().parse::<u32>().ok());
+    let leechers = tds
+        .get(6)
+        .and_then(|n| n.text().collect::<String>().trim().parse::<u32>().ok());
+    let downloads = tds
+        .get(7)
+        .and_then(|n| n.text().collect::<String>().trim().parse::<u32>().ok());
+    Some(MagnetInfo {
+        name: Some(title.to_string()),
+        size,
+        date,
+        seeders,
+        leechers,
+        downloads,
+        part: code::part(title),
+        ..magnet::info(&magnet)?
+    })
+}
+
 async fn parse_sukebei_detail(c: &reqwest::Client, url: &str, code: &str, title_guess: &str) -> Result<AvDetail> {
-    let body = c.get(url).send().await?.error_for_status()?.text().await?;
-    let doc = Html::parse_document(&body);
-    let title_sel = Selector::parse(".torrent-name").unwrap();
+    let body = get_text(c, url).await?;
+    Ok(parse_sukebei_page(&body, code, title_guess))
+}
+
+/// A Sukebei view page's torrent as a detail; `title_guess` (the search row's title) stands
+/// in when the page has no torrent name
+pub(crate) fn parse_sukebei_page(body: &str, code: &str, title_guess: &str) -> AvDetail {
+    let doc = Html::parse_document(body);
+    let title_sel = selectors::sukebei("torrent_name");
     let title_text = doc
-        .select(&title_sel)
+        .select(title_sel)
         .next()
         .map(|n| n.text().collect::<String>())
         .filter(|s| !s.trim().is_empty())
         .unwrap_or_else(|| title_guess.to_string());
 
-    let magnet_sel = Selector::parse("a[href^='magnet:']").unwrap();
+    let magnet_sel = selectors::sukebei("magnet_link");
     let magnets = doc
-        .select(&magnet_sel)
+        .select(magnet_sel)
         .filter_map(|n| n.value().attr("href"))
         .map(|s| s.to_string())
         .collect::<Vec<_>>();
     let magnet_infos = extract_magnet_infos_from_sukebei(&doc, &magnets);
 
-    Ok(AvDetail {
+    AvDetail {
         code: code.to_uppercase(),
         title: title_text,
         actor_names: vec![],
@@ -634,27 +1719,33 @@ async fn parse_sukebei_detail(c: &reqwest::Client, url: &str, code: &str, title_
         label: None,
         series: None,
         genres: Vec::new(),
+        genre_ids: Vec::new(),
         rating: None,
+        ratings: Vec::new(),
+        popularity: None,
         preview_images: Vec::new(),
+        trailer_url: None,
+        sample_video_url: None,
+        amateur: false,
         magnet_infos,
         magnets,
-    })
+    }
 }
 
 async fn search_javdb(query: &str) -> Result<Vec<AvItem>> {
     let c = client();
     let url = format!("{}/search?q={}&f=all", javdb_base(), encode(query));
-    let body = c.get(&url).send().await?.error_for_status()?.text().await?;
+    let body = get_text(&c, &url).await?;
     let doc = Html::parse_document(&body);
-    let card_sel = Selector::parse(".movie-list .item a.box.cover, .movie-list a[href^='/v/'], a.box[href^='/v/']").unwrap();
-    let title_sel = Selector::parse(".video-title").unwrap();
+    let card_sel = selectors::javdb("movie_card");
+    let title_sel = selectors::javdb("movie_title");
     let mut items = Vec::new();
-    for a in doc.select(&card_sel) {
+    for a in doc.select(card_sel) {
         let href = a.value().attr("href").unwrap_or("");
-        let title = a.select(&title_sel).next().map(|n| n.text().collect::<String>()).unwrap_or_else(|| a.text().collect::<String>());
-        let code = extract_code_from_title(&title).unwrap_or_else(|| href.split('/').last().unwrap_or("").to_string());
+        let title = a.select(title_sel).next().map(|n| n.text().collect::<String>()).unwrap_or_else(|| a.text().collect::<String>());
+        let code = extract_code_from_title(&title).unwrap_or_else(|| href.split('/').next_back().unwrap_or("").to_string());
         if !code.is_empty() && !title.is_empty() {
-            items.push(AvItem { code: code.to_uppercase(), title });
+            items.push(AvItem { code: code.to_uppercase(), title, ..card_fields(a) });
         }
     }
     Ok(items)
@@ -663,143 +1754,433 @@ async fn search_javdb(query: &str) -> Result<Vec<AvItem>> {
 async fn search_sukebei(query: &str) -> Result<Vec<AvItem>> {
     let c = client();
     let url = format!("https://sukebei.nyaa.si/?f=0&c=0_0&q={}", encode(query));
-    let body = c.get(&url).send().await?.error_for_status()?.text().await?;
+    let body = get_text(&c, &url).await?;
     let doc = Html::parse_document(&body);
-    let row_sel = Selector::parse("table.torrent-list tbody tr").unwrap();
-    let title_sel = Selector::parse("td[colspan] a, td:nth-child(2) a").unwrap();
+    let row_sel = selectors::sukebei("row");
+    let title_sel = selectors::sukebei("row_title");
     let mut items = Vec::new();
-    for row in doc.select(&row_sel) {
-        if let Some(a) = row.select(&title_sel).next() {
+    for row in doc.select(row_sel) {
+        if let Some(a) = row.select(title_sel).next() {
             let title = a.text().collect::<String>();
             if let Some(code) = extract_code_from_title(&title) {
-                items.push(AvItem { code: code.to_uppercase(), title });
+                items.push(AvItem { code: code.to_uppercase(), title, ..AvItem::default() });
             }
         }
     }
     Ok(items)
 }
 
-async fn list_actor_javdb(actor: &str) -> Result<Vec<AvItem>> {
+/// Pages of an actor's JavDB listing `list --from/--to` reads at most
+const ACTOR_PAGES: usize = 10;
+
+/// The first page, or with a date range the pages back to `range.from`
+async fn list_actor_javdb(actor: &str, range: &DateRange) -> Result<Vec<AvItem>> {
     let c = client();
-    let url = format!("{}/search?q={}&f=actor", javdb_base(), encode(actor));
-    let body = c.get(&url).send().await?.error_for_status()?.text().await?;
-    let doc = Html::parse_document(&body);
-    let card_sel = Selector::parse(".movie-list .item a.box.cover").unwrap();
-    let title_sel = Selector::parse(".video-title").unwrap();
-    let mut items = Vec::new();
-    for a in doc.select(&card_sel) {
-        let title = a
-            .select(&title_sel)
-            .next()
-            .map(|n| n.text().collect::<String>())
-            .unwrap_or_default();
-        if let Some(code) = extract_code_from_title(&title) {
-            items.push(AvItem { code: code.to_uppercase(), title });
+    let card_sel = selectors::javdb("actor_movie_card");
+    let title_sel = selectors::javdb("movie_title");
+    let mut items: Vec<AvItem> = Vec::new();
+    let pages = if range.is_set() { ACTOR_PAGES } else { 1 };
+    for page in 1..=pages {
+        cancel::check()?;
+        let mut url = format!("{}/search?q={}&f=actor", javdb_base(), encode(actor));
+        if page > 1 {
+            url.push_str(&format!("&page={}", page));
+        }
+        let body = get_text(&c, &url).await?;
+        let doc = Html::parse_document(&body);
+        let mut found = Vec::new();
+        for a in doc.select(card_sel) {
+            let title = a
+                .select(title_sel)
+                .next()
+                .map(|n| n.text().collect::<String>())
+                .unwrap_or_default();
+            if let Some(code) = extract_code_from_title(&title) {
+                found.push(AvItem { code: code.to_uppercase(), title, ..card_fields(a) });
+            }
+        }
+        // Newest first: once a whole page predates the range, the rest does too
+        let dates: Vec<_> = found.iter().filter_map(|i| i.release_date.as_deref().and_then(dates::parse)).collect();
+        let past = range.from.is_some_and(|from| !dates.is_empty() && dates.iter().all(|d| *d < from));
+        let empty = found.is_empty();
+        for item in found {
+            if !items.iter().any(|seen| seen.code == item.code) {
+                items.push(item);
+            }
+        }
+        if empty || past {
+            break;
         }
     }
     Ok(items)
 }
 
+static JAVDB_WANTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([\d,]+)\s*人想看").unwrap());
+static JAVDB_WATCHED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([\d,]+)\s*人看[過过]").unwrap());
+
+/// "1234人想看, 567人看過" under JavDB's detail panel
+fn javdb_popularity(text: &str) -> Option<Popularity> {
+    let count = |re: &Regex| re.captures(text).and_then(|c| c[1].replace(',', "").parse().ok());
+    let (wanted, watched) = (count(&JAVDB_WANTED), count(&JAVDB_WATCHED));
+    (wanted.is_some() || watched.is_some()).then(|| Popularity {
+        source: "javdb".to_string(),
+        wanted,
+        watched,
+        ..Popularity::default()
+    })
+}
+
+/// JavDB page listing every title of the studio or series called `name`. JavDB has no
+/// search for those, so the `link_key` links (`studio_link` / `series_link`) on the detail
+/// pages of a plain name search are looked at for one whose text is the name.
+pub async fn javdb_listing_url(link_key: &str, name: &str) -> Result<String> {
+    let c = client();
+    let name = name.trim();
+    let url = format!("{}/search?q={}&f=all", javdb_base(), encode(name));
+    let body = get_text(&c, &url).await?;
+    let hrefs: Vec<String> = Html::parse_document(&body)
+        .select(selectors::javdb("movie_card"))
+        .filter_map(|a| a.value().attr("href").map(str::to_string))
+        .take(3)
+        .collect();
+    for href in hrefs {
+        let page = if href.starts_with("http") { href } else { format!("{}{}", javdb_base(), href) };
+        let body = match get_text(&c, &page).await {
+            Ok(b) => b,
+            Err(e) => {
+                util::debug(format!("javdb_listing_url: {} failed: {:#}", page, e));
+                continue;
+            }
+        };
+        let doc = Html::parse_document(&body);
+        let link = doc
+            .select(selectors::javdb(link_key))
+            .find(|a| a.text().collect::<String>().trim().eq_ignore_ascii_case(name))
+            .and_then(|a| a.value().attr("href"));
+        if let Some(link) = link {
+            return Ok(if link.starts_with("http") { link.to_string() } else { format!("{}{}", javdb_base(), link) });
+        }
+    }
+    bail!(FetchError::not_found("javdb", format!("JavDB 上没有找到 {}", name)))
+}
+
+/// Titles on the first page of a JavDB listing (studio, series)
+pub async fn list_javdb_page(url: &str) -> Result<Vec<AvItem>> {
+    Ok(javdb_cards(&get_text(&client(), url).await?))
+}
+
 async fn list_actor_sukebei(actor: &str) -> Result<Vec<AvItem>> {
     search_sukebei(actor).await
 }
 
-pub async fn actors(page: usize, per_page: usize, uncensored_only: bool) -> Result<(Vec<ActorItem>, usize)> {
-    // Prefer uncensored actors grid when requested
-    let c = client();
-    let endpoints = if uncensored_only {
-        vec![format!("{}/actors/uncensored?page={}", javdb_base(), page)]
+static ACTOR_WORKS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(\d[\d,]*)\s*(?:部影片|部作品|部|videos?|movies?|works?)").unwrap());
+static ACTOR_VIEWS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(\d[\d,.]*\s*[万萬k]?)\s*(?:次觀看|次观看|次|views?)").unwrap());
+
+/// "12,345" / "1.2萬" / "3k" → 12345 / 12000 / 3000
+fn parse_count(s: &str) -> Option<u64> {
+    let s = s.trim().replace(',', "");
+    let (num, mult) = match s.chars().last()? {
+        '万' | '萬' => (&s[..s.len() - '万'.len_utf8()], 10_000.0),
+        'k' | 'K' => (&s[..s.len() - 1], 1_000.0),
+        _ => (s.as_str(), 1.0),
+    };
+    num.trim().parse::<f64>().ok().map(|n| (n * mult).round() as u64)
+}
+
+/// Absolute actor page and avatar image for an actor link
+fn actor_links(a: scraper::ElementRef) -> (Option<String>, Option<String>) {
+    let absolute = |h: &str| if h.starts_with("http") { h.to_string() } else { format!("{}/{}", javdb_base(), h.trim_start_matches('/')) };
+    let url = a.value().attr("href").filter(|h| !h.is_empty()).map(absolute);
+    let avatar = a
+        .select(&IMG)
+        .next()
+        .and_then(|img| img.value().attr("data-src").or_else(|| img.value().attr("src")))
+        .filter(|s| !s.is_empty() && !s.starts_with("data:"))
+        .map(|s| if s.starts_with("//") { format!("https:{}", s) } else { absolute(s) });
+    (url, avatar)
+}
+
+/// Work count, view count and explicit ranking number shown in an actor box, when the
+/// layout has them. The name itself is skipped so numbers in names aren't picked up.
+fn actor_metrics(a: scraper::ElementRef, name: &str) -> (Option<u32>, Option<u64>, Option<u32>) {
+    let rank_sel = selectors::javdb("actor_rank");
+    let text: String = a.text().collect::<Vec<_>>().join(" ").replacen(name, " ", 1);
+    let works = ACTOR_WORKS.captures(&text).and_then(|c| parse_count(&c[1])).and_then(|n| u32::try_from(n).ok());
+    let views = ACTOR_VIEWS.captures(&text).and_then(|c| parse_count(&c[1]));
+    let rank = a
+        .select(rank_sel)
+        .filter_map(|n| n.text().collect::<String>().trim().trim_start_matches(['#', 'N', 'o', '.']).trim().parse::<u32>().ok())
+        .next();
+    (works, views, rank)
+}
+
+/// The ranking pages listing actors, as (label, URL of page `page`, whether list position is
+/// a real rank). The uncensored grid is not ranked.
+fn actor_endpoints(page: usize, uncensored_only: bool) -> Vec<(&'static str, String, bool)> {
+    if uncensored_only {
+        vec![("无码", format!("{}/actors/uncensored?page={}", javdb_base(), page), false)]
     } else {
         vec![
-            format!("{}/actors?o=tr&page={}", javdb_base(), page),
-            format!("{}/rankings/actors?period=w&page={}", javdb_base(), page),
-            format!("{}/rankings/actors?period=m&page={}", javdb_base(), page),
+            ("热度", format!("{}/actors?o=tr&page={}", javdb_base(), page), true),
+            ("周榜", format!("{}/rankings/actors?period=w&page={}", javdb_base(), page), true),
+            ("月榜", format!("{}/rankings/actors?period=m&page={}", javdb_base(), page), true),
         ]
+    }
+}
+
+/// The actors on ranking page `page`, and the highest page number its pagination shows
+static ACTOR_LINK: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a[href^='/actors/']").unwrap());
+
+fn parse_actors_page(body: &str, page: usize, ranked: bool) -> (Vec<ActorItem>, Option<usize>) {
+    let doc = Html::parse_document(body);
+    let pages = doc
+        .select(selectors::javdb("pagination"))
+        .filter_map(|n| n.text().collect::<String>().trim().parse::<usize>().ok())
+        .max();
+    // Absolute position on a ranking page: JavDB pages hold however many boxes it renders
+    let position = |idx: usize, page_len: usize| ranked.then(|| ((page - 1) * page_len + idx + 1) as u32);
+
+    // Prefer the actors grid structure: #actors .actor-box a strong
+    let grid_sel = selectors::javdb("actor_box");
+    let strong_sel = selectors::javdb("actor_name");
+    let boxes: Vec<_> = doc.select(grid_sel).collect();
+    let mut grid: Vec<ActorItem> = Vec::new();
+    for (idx, a) in boxes.iter().enumerate() {
+        let name_strong = a.select(strong_sel).next().map(|n| n.text().collect::<String>().trim().to_string());
+        let title_attr = a.value().attr("title").map(|s| s.to_string());
+        // Some title has multiple names separated by comma; pick first
+        let name_from_title = title_attr.clone().and_then(|t| t.split(',').next().map(|s| s.trim().to_string()));
+        let name = name_strong.filter(|s| !s.is_empty()).or(name_from_title).unwrap_or_default();
+        if name.is_empty() { continue; }
+        let (works_count, views, rank) = actor_metrics(*a, &name);
+        let (url, avatar_url) = actor_links(*a);
+        grid.push(ActorItem { name, rank: rank.or(position(idx, boxes.len())), works_count, views, url, avatar_url, debut_year: None, latest_release: None });
+    }
+    if !grid.is_empty() {
+        return (grid, pages);
+    }
+
+    // Fallback: anchors-based heuristic (older layout); keep each name's first occurrence
+    let mut seen: Vec<ActorItem> = Vec::new();
+    for a in doc.select(&ACTOR_LINK) {
+        let name = a.text().collect::<String>().trim().to_string();
+        if name.is_empty() || seen.iter().any(|s| s.name == name) { continue; }
+        let (works_count, views, rank) = actor_metrics(a, &name);
+        let (url, avatar_url) = actor_links(a);
+        seen.push(ActorItem { name, rank, works_count, views, url, avatar_url, debut_year: None, latest_release: None });
+    }
+    let len = seen.len();
+    for (idx, a) in seen.iter_mut().enumerate() {
+        a.rank = a.rank.or(position(idx, len));
+    }
+    (seen, pages)
+}
+
+/// Ranking totals worked out this run, by the ranking's first page, with when; reused for
+/// `TOTAL_FRESH` so paging through a ranking doesn't count it again on every page
+static RANKING_TOTALS: LazyLock<Mutex<HashMap<String, RankingTotal>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+/// (worked out at, actors, pages)
+type RankingTotal = (Instant, usize, usize);
+const TOTAL_FRESH: std::time::Duration = std::time::Duration::from_secs(600);
+/// Pages read past the one asked for to find a ranking's end
+const TOTAL_HOPS: usize = 20;
+
+async fn ranking_page(c: &reqwest::Client, ranking: usize, page: usize, uncensored_only: bool) -> Result<(Vec<ActorItem>, Option<usize>)> {
+    let (_, url, ranked) = actor_endpoints(page, uncensored_only).swap_remove(ranking);
+    util::debug(format!("JavDB actors page: {}", url));
+    Ok(parse_actors_page(&get_text(c, &url).await?, page, ranked))
+}
+
+/// (actors, pages) of ranking `ranking`, given page `page` with `len` actors on it and
+/// `shown` the highest page its pagination links to. JavDB prints no count, and its
+/// pagination only links to pages near the current one, so the highest page linked is read
+/// until one links nowhere further: that is the last page, and the total is the full pages
+/// before it plus what it holds.
+async fn ranking_total(c: &reqwest::Client, ranking: usize, uncensored_only: bool, page: usize, len: usize, shown: Option<usize>) -> (usize, usize) {
+    let key = actor_endpoints(1, uncensored_only).swap_remove(ranking).1;
+    if let Some((at, total, pages)) = RANKING_TOTALS.lock().unwrap().get(&key).copied() {
+        if at.elapsed() < TOTAL_FRESH {
+            return (total, pages);
+        }
+    }
+    let mut last = shown.unwrap_or(page).max(page);
+    let full = if last > page {
+        len
+    } else if page == 1 {
+        return (len, 1);
+    } else {
+        // The asked-for page is the last one; the first says how many a full page holds
+        match ranking_page(c, ranking, 1, uncensored_only).await {
+            Ok((first, _)) => first.len().max(len),
+            Err(e) => {
+                util::debug(format!("actors total: {:#}", e));
+                len
+            }
+        }
     };
-    let mut all: Vec<ActorItem> = Vec::new();
-    let mut total_pages: Option<usize> = None;
+    let mut last_len = len;
+    for _ in 0..TOTAL_HOPS {
+        if last == page {
+            break;
+        }
+        match ranking_page(c, ranking, last, uncensored_only).await {
+            Ok((found, further)) if further.is_some_and(|p| p > last) && !found.is_empty() => last = further.unwrap_or(last),
+            Ok((found, _)) if found.is_empty() => {
+                // Past the end after all: count whole pages up to it
+                last -= 1;
+                last_len = full;
+                break;
+            }
+            Ok((found, _)) => {
+                last_len = found.len();
+                break;
+            }
+            Err(e) => {
+                util::debug(format!("actors total, page {}: {:#}", last, e));
+                last_len = full;
+                break;
+            }
+        }
+    }
+    let total = (last.max(1) - 1) * full + last_len;
+    RANKING_TOTALS.lock().unwrap().insert(key, (Instant::now(), total, last.max(1)));
+    (total, last.max(1))
+}
 
-    for url in &endpoints {
+/// Page `page` of the actors ranking, keeping its first `per_page`; the total is worked
+/// out from the ranking's last page
+pub async fn actors(page: usize, per_page: usize, uncensored_only: bool) -> Result<RankingPage> {
+    if mock::is_enabled() {
+        return Ok(mock::actors(page, per_page));
+    }
+    // Prefer uncensored actors grid when requested; otherwise the first ranking that lists anyone
+    let c = client();
+    for (ranking, (_, url, ranked)) in actor_endpoints(page, uncensored_only).into_iter().enumerate() {
         util::debug(format!("JavDB actors page: {}", url));
-        let resp = c.get(url).send().await?;
+        let resp = send(&c, &url).await?;
         if !resp.status().is_success() { continue; }
         let body = resp.text().await?;
-        let doc = Html::parse_document(&body);
-
-        // Estimate total pages
-        if total_pages.is_none() {
-            let pages = doc
-                .select(&Selector::parse(".pagination-list a.pagination-link").unwrap())
-                .filter_map(|n| n.text().collect::<String>().trim().parse::<usize>().ok())
-                .max();
-            if let Some(p) = pages { total_pages = Some(p); }
-        }
-
-        // Prefer the actors grid structure: #actors .actor-box a strong
-        let grid_sel = Selector::parse("#actors .actor-box a, .actors .actor-box a").unwrap();
-        let strong_sel = Selector::parse("strong").unwrap();
-        let mut grid: Vec<ActorItem> = Vec::new();
-        for (idx, a) in doc.select(&grid_sel).enumerate() {
-            let name_strong = a.select(&strong_sel).next().map(|n| n.text().collect::<String>().trim().to_string());
-            let title_attr = a.value().attr("title").map(|s| s.to_string());
-            // Some title has multiple names separated by comma; pick first
-            let name_from_title = title_attr.clone().and_then(|t| t.split(',').next().map(|s| s.trim().to_string()));
-            let name = name_strong.filter(|s| !s.is_empty()).or(name_from_title).unwrap_or_default();
-            if name.is_empty() { continue; }
-            // If no explicit hot metric, use order (descending)
-            let hot_rank = (per_page as i64 - idx as i64).max(1) as u32;
-            grid.push(ActorItem { name, hot: hot_rank });
-        }
-        if !grid.is_empty() {
+        let (mut found, shown) = parse_actors_page(&body, page, ranked);
+        if !found.is_empty() {
+            let (total, pages) = ranking_total(&c, ranking, uncensored_only, page, found.len(), shown).await;
             // apply per_page limit locally
-            let mut limited = grid;
-            if limited.len() > per_page { limited.truncate(per_page); }
-            all = limited;
-            break;
+            found.truncate(per_page);
+            let next_url = (page < pages).then(|| actor_endpoints(page + 1, uncensored_only).swap_remove(ranking).1);
+            return Ok(RankingPage { actors: found, total, pages, next_url });
         }
+    }
+    // Past the end, or nothing we can parse
+    Ok(RankingPage::default())
+}
 
-        // Fallback: anchors-based heuristic (older layout)
-        let a_sel = Selector::parse("a[href^='/actors/']").unwrap();
-        let mut seen: HashMap<String, u32> = HashMap::new();
-        for (idx, a) in doc.select(&a_sel).enumerate() {
-            let name = a.text().collect::<String>().trim().to_string();
-            if name.is_empty() { continue; }
-            let hot_rank = (per_page as i64 - idx as i64).max(1) as u32;
-            let entry = seen.entry(name).or_insert(0);
-            if hot_rank > *entry { *entry = hot_rank; }
-        }
-        if !seen.is_empty() {
-            let mut v = seen.into_iter().map(|(name, hot)| ActorItem { name, hot }).collect::<Vec<_>>();
-            v.sort_by(|a, b| b.hot.cmp(&a.hot).then_with(|| a.name.cmp(&b.name)));
-            all = v;
-            break;
+/// Pages `actors --all` reads of one ranking at most, in case a mirror keeps serving pages
+const MAX_RANKING_PAGES: usize = 500;
+
+/// Who an actor is across rankings: her JavDB page, or her name when the page has no link
+fn actor_key(a: &ActorItem) -> String {
+    a.url.clone().unwrap_or_else(|| a.name.clone())
+}
+
+/// `actors --all`: every page of every ranking (trending, then weekly and monthly; or the
+/// uncensored grid), handing each page's actors not seen on an earlier page to `on_page`
+/// with the ranking's label and page number as soon as it is read. A ranking ends at an
+/// empty page, a missing one, or a page with nobody new to it (mirrors that serve the last
+/// page for any number past it). Pages go through the session page cache, so `av shell`
+/// doesn't read them twice. The walk starts at `cursor` (a resumed `actors --all` job) and
+/// `on_page` also gets the cursor moved past its page, to record. Returns how many distinct actors were found.
+pub async fn actors_all(
+    uncensored_only: bool,
+    cursor: batch::Cursor,
+    mut on_page: impl AsyncFnMut(&str, usize, Vec<ActorItem>, batch::Cursor) -> Result<()>,
+) -> Result<usize> {
+    let mut seen: HashSet<String> = cursor.seen.iter().cloned().collect();
+    let at = |ranking: usize, page: usize, seen: &HashSet<String>| batch::Cursor {
+        ranking,
+        page,
+        seen: seen.iter().cloned().collect(),
+        kept: 0,
+    };
+    if mock::is_enabled() {
+        for page in cursor.page.max(1).. {
+            let found = mock::actors(page, 50);
+            let fresh: Vec<ActorItem> = found.actors.into_iter().filter(|a| seen.insert(actor_key(a))).collect();
+            on_page("热度", page, fresh, at(0, page + 1, &seen)).await?;
+            if page >= found.pages { break; }
         }
+        return Ok(seen.len());
     }
+    let c = client();
+    let labels = actor_endpoints(1, uncensored_only).len();
+    for ranking in cursor.ranking..labels {
+        let label = actor_endpoints(1, uncensored_only)[ranking].0;
+        // Actors of this ranking so far, to tell a repeated page from a new one
+        let mut listed: HashSet<String> = HashSet::new();
+        let first = if ranking == cursor.ranking { cursor.page.max(1) } else { 1 };
+        for page in first..=MAX_RANKING_PAGES {
+            let found = match ranking_page(&c, ranking, page, uncensored_only).await {
+                Ok((found, _)) => found,
+                Err(e) => {
+                    cancel::check()?;
+                    // A page past the end is a 404 on JavDB; a ranking that can't be read at
+                    // all leaves the others to be walked
+                    if page == 1 {
+                        eprintln!("[WARN] {} 排行读取失败: {:#}", label, e);
+                    } else {
+                        util::debug(format!("{} page {}: {:#}", label, page, e));
+                    }
+                    break;
+                }
+            };
+            let new_here = found.iter().filter(|a| listed.insert(actor_key(a))).count();
+            if new_here == 0 { break; }
+            let fresh: Vec<ActorItem> = found.into_iter().filter(|a| seen.insert(actor_key(a))).collect();
+            on_page(label, page, fresh, at(ranking, page + 1, &seen)).await?;
+        }
+    }
+    Ok(seen.len())
+}
 
-    // Fallback: return empty with total estimation if none found
-    let total_pages = total_pages.unwrap_or(page);
-    // If we have items count for this page, approximate total items
-    let total_items = total_pages * per_page;
-    Ok((all, total_items))
+/// The works count and newest release date an actor's JavDB page (her filmography, newest
+/// first) shows; `(None, None)` for an entry without a page
+pub async fn actor_stats(actor: &ActorItem) -> Result<(Option<u32>, Option<String>)> {
+    if mock::is_enabled() {
+        let items = mock::list_actor(&actor.name);
+        let latest = items.iter().filter_map(|i| i.release_date.clone()).max();
+        return Ok((u32::try_from(items.len()).ok().filter(|n| *n > 0), latest));
+    }
+    let Some(url) = &actor.url else { return Ok((None, None)) };
+    let body = get_text(&client(), url).await?;
+    let works = Html::parse_document(&body)
+        .select(selectors::javdb("actor_page_meta"))
+        .find_map(|n| ACTOR_WORKS.captures(&n.text().collect::<String>()).and_then(|c| parse_count(&c[1])))
+        .and_then(|n| u32::try_from(n).ok());
+    let latest = javdb_cards(&body)
+        .into_iter()
+        .filter_map(|i| i.release_date.as_deref().and_then(dates::parse))
+        .max()
+        .map(|d| d.format("%Y-%m-%d").to_string());
+    Ok((works, latest))
 }
 
 fn extract_code_from_title(title: &str) -> Option<String> {
-    let re = Regex::new(r"(?i)([a-z]{2,5})[-_ ]?(\d{2,5})").unwrap();
-    if let Some(caps) = re.captures(title) {
-        let code = format!("{}-{}", &caps[1].to_uppercase(), &caps[2]);
-        return Some(code);
-    }
-    None
+    code::find(title).map(|c| c.to_string())
 }
 
+static MAGNET_URI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"magnet:\?xt=urn:[^"'\s<>]+"#).unwrap());
+
 fn extract_magnets_from_text(body: &str) -> Vec<String> {
-    let re = Regex::new(r#"magnet:\?xt=urn:[^"'\s<>]+"#).unwrap();
-    re.find_iter(body).map(|m| m.as_str().to_string()).collect()
+    MAGNET_URI.find_iter(body).map(|m| m.as_str().to_string()).collect()
 }
 
-fn extract_ld_json_metadata(doc: &Html) -> (Option<String>, Option<u32>, Vec<String>, Vec<String>, Option<String>) {
-    let script_sel = Selector::parse("script[type='application/ld+json']").unwrap();
-    for sc in doc.select(&script_sel) {
+/// (plot, duration_minutes, actors, images, studio)
+type LdJsonMetadata = (Option<String>, Option<u32>, Vec<String>, Vec<String>, Option<String>);
+
+static LD_JSON: LazyLock<Selector> = LazyLock::new(|| Selector::parse("script[type='application/ld+json']").unwrap());
+
+fn extract_ld_json_metadata(doc: &Html) -> LdJsonMetadata {
+    for sc in doc.select(&LD_JSON) {
         let text = sc.text().collect::<String>();
         if text.trim().is_empty() { continue; }
         if let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) {
@@ -822,42 +2203,45 @@ fn extract_ld_json_metadata(doc: &Html) -> (Option<String>, Option<u32>, Vec<Str
     (None, None, Vec::new(), Vec::new(), None)
 }
 
+/// PT1H40M or PT100M
+static ISO_DURATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^PT(?:(\d+)H)?(?:(\d+)M)?$").unwrap());
+
 fn parse_iso8601_duration_minutes(s: &str) -> Option<u32> {
-    // PT1H40M or PT100M
-    let re = Regex::new(r"^PT(?:(\d+)H)?(?:(\d+)M)?$").ok()?;
-    let caps = re.captures(s)?;
+    let caps = ISO_DURATION.captures(s)?;
     let h = caps.get(1).and_then(|m| m.as_str().parse::<u32>().ok()).unwrap_or(0);
     let m = caps.get(2).and_then(|m| m.as_str().parse::<u32>().ok()).unwrap_or(0);
     Some(h * 60 + m)
 }
-fn extract_magnet_infos_from_javdb(_doc: &Html, magnets: &Vec<String>) -> Vec<MagnetInfo> {
+fn extract_magnet_infos_from_javdb(_doc: &Html, magnets: &[String]) -> Vec<MagnetInfo> {
     // JavDB may not expose table data for magnets in HTML, so primarily return URLs
     magnets
         .iter()
-        .map(|m| MagnetInfo { url: m.clone(), name: None, size: None, date: None, seeders: None, leechers: None, downloads: None, resolution: None, codec: None, avg_bitrate_mbps: None })
+        .filter_map(|m| magnet::info(m))
         .collect()
 }
 
-fn extract_magnet_infos_from_sukebei(doc: &Html, magnets: &Vec<String>) -> Vec<MagnetInfo> {
+static TITLE_RESOLUTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{3,4}p|\d{3,4}x\d{3,4})").unwrap());
+static TITLE_CODEC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(H\.264|H\.265|AVC|HEVC|x264|x265)").unwrap());
+static PAGE_MINUTES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{2,3})\s*(min|分钟)").unwrap());
+
+fn extract_magnet_infos_from_sukebei(doc: &Html, magnets: &[String]) -> Vec<MagnetInfo> {
     // sukebei detail page has a table with info, but mapping rows to magnets can be complex; best-effort
     let mut infos: Vec<MagnetInfo> = Vec::new();
     // Try to read title to infer resolution/codec/bitrate hints
     let title = doc
-        .select(&Selector::parse(".torrent-name").unwrap())
+        .select(selectors::sukebei("torrent_name"))
         .next()
         .map(|n| n.text().collect::<String>())
         .unwrap_or_default();
-    let res = Regex::new(r"(\d{3,4}p|\d{3,4}x\d{3,4})").ok()
-        .and_then(|re| re.captures(&title)).map(|c| c.get(1).unwrap().as_str().to_string());
-    let codec = Regex::new(r"(H\.264|H\.265|AVC|HEVC|x264|x265)").ok()
-        .and_then(|re| re.captures(&title)).map(|c| c.get(1).unwrap().as_str().to_string());
+    let res = TITLE_RESOLUTION.captures(&title).map(|c| c[1].to_string());
+    let codec = TITLE_CODEC.captures(&title).map(|c| c[1].to_string());
     let mut size_text: Option<String> = None;
     let mut seeders: Option<u32> = None;
     let mut leechers: Option<u32> = None;
     let mut downloads: Option<u32> = None;
     // Table columns often: Category | Name | Link | Size | Date | S | L | C
-    if let Some(row) = doc.select(&Selector::parse("table.torrent-list tbody tr").unwrap()).next() {
-        let tds: Vec<_> = row.select(&Selector::parse("td").unwrap()).collect();
+    if let Some(row) = doc.select(selectors::sukebei("row")).next() {
+        let tds: Vec<_> = row.select(&CELL).collect();
         size_text = tds.get(3).map(|n| n.text().collect::<String>().trim().to_string());
         seeders = tds.get(5).and_then(|n| n.text().collect::<String>().trim().parse::<u32>().ok());
         leechers = tds.get(6).and_then(|n| n.text().collect::<String>().trim().parse::<u32>().ok());
@@ -869,8 +2253,8 @@ fn extract_magnet_infos_from_sukebei(doc: &Html, magnets: &Vec<String>) -> Vec<M
     if let Some(size_s) = size_text.clone() {
         if let Some((bytes, _unit)) = parse_size_to_bytes(&size_s) {
             let body_text = doc.root_element().text().collect::<String>();
-            if let Some(dur_min) = Regex::new(r"(\d{2,3})\s*(min|分钟)").ok()
-                .and_then(|re| re.captures(&body_text))
+            if let Some(dur_min) = PAGE_MINUTES
+                .captures(&body_text)
                 .and_then(|c| c.get(1)).and_then(|m| m.as_str().parse::<u32>().ok())
             {
                 let bits = (bytes as f64) * 8.0;
@@ -881,9 +2265,8 @@ fn extract_magnet_infos_from_sukebei(doc: &Html, magnets: &Vec<String>) -> Vec<M
         }
     }
 
-    for m in magnets {
+    for base in magnets.iter().filter_map(|m| magnet::info(m)) {
         infos.push(MagnetInfo {
-            url: m.clone(),
             name: Some(title.clone()).filter(|s| !s.is_empty()),
             size: size_text.clone(),
             date: None,
@@ -893,14 +2276,17 @@ fn extract_magnet_infos_from_sukebei(doc: &Html, magnets: &Vec<String>) -> Vec<M
             resolution: res.clone(),
             codec: codec.clone(),
             avg_bitrate_mbps,
+            part: code::part(&title),
+            ..base
         });
     }
     infos
 }
 
-fn parse_size_to_bytes(s: &str) -> Option<(u64, String)> {
-    let re = Regex::new(r"([0-9]+(?:\.[0-9]+)?)\s*([KMGT]i?B)").ok()?;
-    let caps = re.captures(s)?;
+static SIZE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([0-9]+(?:\.[0-9]+)?)\s*([KMGT]i?B)").unwrap());
+
+pub fn parse_size_to_bytes(s: &str) -> Option<(u64, String)> {
+    let caps = SIZE.captures(s)?;
     let num: f64 = caps.get(1)?.as_str().parse().ok()?;
     let unit = caps.get(2)?.as_str().to_uppercase();
     let mult = match unit.as_str() {
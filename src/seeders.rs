@@ -0,0 +1,96 @@
+//! Seeder/leecher counts per infohash, sampled every time a source reports them, so the
+//! detail view can show whether a torrent is gaining peers or dying out.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::sources::mock;
+use crate::store;
+use crate::types::MagnetInfo;
+use crate::util;
+
+const HISTORY: &str = "seeders";
+/// Samples kept per infohash, oldest dropped first
+const MAX_SAMPLES: usize = 30;
+/// Torrents tracked at most; the ones not seen for longest are forgotten
+const MAX_TORRENTS: usize = 10000;
+/// A new sample within this many seconds of the last one replaces it
+const MIN_INTERVAL: u64 = 3600;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Sample {
+    /// Unix seconds
+    pub at: u64,
+    pub seeders: u32,
+    pub leechers: Option<u32>,
+}
+
+/// Samples per upper-case infohash, oldest first
+pub type History = BTreeMap<String, Vec<Sample>>;
+
+/// Add a sample for every magnet that has an infohash and a seeder count. Failures
+/// only warn: the history must never break a lookup.
+pub fn record(magnets: &[MagnetInfo]) {
+    if util::is_dry_run() || mock::is_enabled() {
+        return;
+    }
+    let now = util::now_secs();
+    let fresh: Vec<(&String, Sample)> = magnets
+        .iter()
+        .filter_map(|m| Some((m.infohash.as_ref()?, Sample { at: now, seeders: m.seeders?, leechers: m.leechers })))
+        .collect();
+    if fresh.is_empty() {
+        return;
+    }
+    let _guard = LOCK.lock().unwrap();
+    let mut h: History = match store::load(HISTORY) {
+        Ok(h) => h,
+        Err(e) => {
+            eprintln!("[WARN] 读取做种历史失败: {:#}", e);
+            return;
+        }
+    };
+    for (hash, sample) in fresh {
+        let samples = h.entry(hash.clone()).or_default();
+        if samples.last().is_some_and(|s| now.saturating_sub(s.at) < MIN_INTERVAL) {
+            samples.pop();
+        }
+        samples.push(sample);
+        let excess = samples.len().saturating_sub(MAX_SAMPLES);
+        samples.drain(..excess);
+    }
+    while h.len() > MAX_TORRENTS {
+        let Some(stale) = h.iter().min_by_key(|(_, s)| s.last().map_or(0, |s| s.at)).map(|(k, _)| k.clone()) else { break };
+        h.remove(&stale);
+    }
+    if let Err(e) = store::save(HISTORY, &h) {
+        eprintln!("[WARN] 保存做种历史失败: {:#}", e);
+    }
+}
+
+/// The recorded history, empty when it can't be read
+pub fn load() -> History {
+    store::load(HISTORY).unwrap_or_default()
+}
+
+/// e.g. "▁▃▅█ 12→40（7天）" over the last dozen samples of a torrent sampled at least twice
+pub fn trend(h: &History, infohash: &str) -> Option<String> {
+    let samples = h.get(infohash).filter(|s| s.len() >= 2)?;
+    let shown = &samples[samples.len().saturating_sub(12)..];
+    let (first, last) = (shown.first()?, shown.last()?);
+    let min = shown.iter().map(|s| s.seeders).min()?;
+    let max = shown.iter().map(|s| s.seeders).max()?;
+    let spark: String = shown
+        .iter()
+        .map(|s| {
+            let i = if max == min { SPARKS.len() / 2 } else { ((s.seeders - min) as usize * (SPARKS.len() - 1)) / (max - min) as usize };
+            SPARKS[i]
+        })
+        .collect();
+    let days = last.at.saturating_sub(first.at).div_ceil(86400).max(1);
+    Some(format!("{} {}→{}（{}天）", spark, first.seeders, last.seeders, days))
+}
@@ -0,0 +1,132 @@
+//! CSS selectors used to pick data out of JavDB, Sukebei, BTSOW, TorrentKitty, FC2, Sokmil,
+//! AV-Wiki, xslist, JavTrailers, SubtitleCat and the uncensored studios' pages.
+//!
+//! The defaults live in `selectors.toml` next to this file and are compiled into the
+//! binary. `[selectors.<source>]` tables in `config.toml` override single keys, so when a
+//! site changes its markup the extraction can be hotfixed without a new release.
+
+use scraper::Selector;
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use crate::config;
+use crate::util;
+
+type Table = BTreeMap<String, BTreeMap<String, String>>;
+
+static DEFAULTS: LazyLock<Table> =
+    LazyLock::new(|| toml::from_str(include_str!("selectors.toml")).expect("bundled selectors.toml is valid"));
+
+struct Compiled {
+    text: String,
+    selector: Selector,
+}
+
+static COMPILED: LazyLock<BTreeMap<(String, String), Compiled>> = LazyLock::new(compile);
+
+/// Default selectors with the config overrides applied. Overrides that don't parse, or
+/// name a key that doesn't exist, are warned about and ignored.
+fn compile() -> BTreeMap<(String, String), Compiled> {
+    let overrides = &config::get().selectors;
+    for (source, keys) in overrides {
+        for key in keys.keys() {
+            if !DEFAULTS.get(source).is_some_and(|d| d.contains_key(key)) {
+                eprintln!("[WARN] 未知的选择器 selectors.{}.{}，已忽略", source, key);
+            }
+        }
+    }
+    let mut out = BTreeMap::new();
+    for (source, keys) in DEFAULTS.iter() {
+        for (key, default) in keys {
+            let custom = overrides.get(source).and_then(|o| o.get(key));
+            let parsed = custom.and_then(|text| match Selector::parse(text) {
+                Ok(selector) => Some(Compiled { text: text.clone(), selector }),
+                Err(e) => {
+                    eprintln!("[WARN] 选择器 selectors.{}.{} 无效（{:?}），使用默认值", source, key, e);
+                    None
+                }
+            });
+            let compiled = parsed.unwrap_or_else(|| Compiled {
+                text: default.clone(),
+                selector: Selector::parse(default).expect("bundled selector is valid"),
+            });
+            out.insert((source.clone(), key.clone()), compiled);
+        }
+    }
+    out
+}
+
+/// The selector for `source`/`key`. Keys are fixed at compile time, so an unknown one
+/// is a bug rather than a user error.
+pub fn get(source: &str, key: &str) -> &'static Selector {
+    match COMPILED.get(&(source.to_string(), key.to_string())) {
+        Some(c) => &c.selector,
+        None => panic!("selectors.toml has no {}.{}", source, key),
+    }
+}
+
+pub fn javdb(key: &str) -> &'static Selector {
+    get("javdb", key)
+}
+
+pub fn sukebei(key: &str) -> &'static Selector {
+    get("sukebei", key)
+}
+
+pub fn btsow(key: &str) -> &'static Selector {
+    get("btsow", key)
+}
+
+pub fn torrentkitty(key: &str) -> &'static Selector {
+    get("torrentkitty", key)
+}
+
+pub fn fc2(key: &str) -> &'static Selector {
+    get("fc2", key)
+}
+
+pub fn caribbeancom(key: &str) -> &'static Selector {
+    get("caribbeancom", key)
+}
+
+pub fn heyzo(key: &str) -> &'static Selector {
+    get("heyzo", key)
+}
+
+pub fn tokyohot(key: &str) -> &'static Selector {
+    get("tokyohot", key)
+}
+
+pub fn sokmil(key: &str) -> &'static Selector {
+    get("sokmil", key)
+}
+
+pub fn avwiki(key: &str) -> &'static Selector {
+    get("avwiki", key)
+}
+
+pub fn xslist(key: &str) -> &'static Selector {
+    get("xslist", key)
+}
+
+pub fn javtrailers(key: &str) -> &'static Selector {
+    get("javtrailers", key)
+}
+
+pub fn subtitlecat(key: &str) -> &'static Selector {
+    get("subtitlecat", key)
+}
+
+/// `av selectors`: print the selectors in effect, as a config.toml fragment
+pub fn print(json: bool) {
+    let mut table: Table = BTreeMap::new();
+    for ((source, key), c) in COMPILED.iter() {
+        table.entry(source.clone()).or_default().insert(key.clone(), c.text.clone());
+    }
+    if json {
+        util::print_output(&table, true);
+        return;
+    }
+    let wrapped = BTreeMap::from([("selectors", table)]);
+    print!("{}", toml::to_string(&wrapped).unwrap_or_default());
+}
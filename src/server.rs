@@ -0,0 +1,503 @@
+use anyhow::{Context, Result};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::extract::{MatchedPath, Path, Query, Request, State};
+use axum::middleware::{self, Next};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::cancel;
+use crate::code;
+use crate::downloader;
+use crate::graphql;
+use crate::magnet;
+use crate::metrics;
+use crate::scraper;
+use crate::feed::{self, FeedFormat, FeedOptions};
+use crate::types::{ActorsEnvelope, AvDetail, AvItem};
+use crate::util;
+
+/// Torznab category for adult content (XXX)
+const TORZNAB_CATEGORY: u32 = 6000;
+const TORZNAB_DEFAULT_LIMIT: usize = 20;
+const TORZNAB_MAX_LIMIT: usize = 50;
+/// Searches by actor/keyword fan out to one detail fetch per hit; keep that bounded
+const TORZNAB_MAX_CODES: usize = 10;
+/// `POST /download` answers once the client has the magnet; one that goes on to wait for
+/// the download to finish is left to it after this long
+const DOWNLOAD_ANSWER_SECS: u64 = 15;
+
+/// The `--web` frontend, built into the binary
+const WEB_INDEX: &str = include_str!("web/index.html");
+const WEB_SCRIPT: &str = include_str!("web/app.js");
+const WEB_STYLE: &str = include_str!("web/style.css");
+
+pub struct ServeOptions {
+    pub bind: String,
+    pub torznab: bool,
+    pub api_key: Option<String>,
+    pub uncen: bool,
+    pub web: bool,
+}
+
+struct ServeState {
+    api_key: Option<String>,
+    uncen: bool,
+    schema: graphql::ApiSchema,
+}
+
+pub async fn serve(opts: ServeOptions) -> Result<()> {
+    let state = Arc::new(ServeState { api_key: opts.api_key.clone(), uncen: opts.uncen, schema: graphql::schema(opts.uncen) });
+    let mut app = Router::new()
+        .route("/detail/{code}", get(api_detail))
+        .route("/search", get(api_search))
+        .route("/list/{actor}", get(api_list))
+        .route("/top", get(api_top))
+        .route("/actors", get(api_actors))
+        .route("/rss", get(feed_rss))
+        .route("/atom", get(feed_atom))
+        .route("/graphql", get(graphql_handler).post(graphql_handler))
+        .route("/graphql/schema", get(graphql_schema))
+        .route("/download", post(api_download))
+        .route("/metrics", get(metrics_endpoint));
+    if opts.web {
+        app = app
+            .route("/", get(|| async { Html(WEB_INDEX) }))
+            .route("/app.js", get(|| async { ([(CONTENT_TYPE, "text/javascript; charset=utf-8")], WEB_SCRIPT) }))
+            .route("/style.css", get(|| async { ([(CONTENT_TYPE, "text/css; charset=utf-8")], WEB_STYLE) }));
+    }
+    if opts.torznab {
+        app = app
+            .route("/api", get(torznab_api))
+            .route("/torznab/api", get(torznab_api));
+    }
+    let app = app.layer(middleware::from_fn(track_requests)).with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&opts.bind)
+        .await
+        .with_context(|| format!("无法监听地址 {}", opts.bind))?;
+    util::note(format!("HTTP API 已启动: http://{}", opts.bind));
+    if opts.web {
+        let key = opts.api_key.as_ref().map(|_| "/?apikey=<API Key>").unwrap_or("/");
+        util::note(format!("网页界面已启动: http://{}{}", opts.bind, key));
+    }
+    if opts.torznab {
+        util::note(format!("Torznab 索引器已启动: http://{}/api", opts.bind));
+    }
+    if opts.api_key.is_some() {
+        util::note("已启用 API Key 校验");
+    }
+    let token = cancel::token();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { token.cancelled().await })
+        .await
+        .context("服务异常退出")?;
+    Ok(())
+}
+
+/// Count every request by its route template (not the raw path, to keep label cardinality low)
+async fn track_requests(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let resp = next.run(req).await;
+    metrics::record_http_request(&route, resp.status().as_u16());
+    resp
+}
+
+async fn metrics_endpoint(State(state): State<Arc<ServeState>>, Query(k): Query<KeyQuery>) -> Response {
+    if let Err(e) = check_api_key(&state, k.apikey.as_deref()) {
+        return e.into_response();
+    }
+    metrics::metrics_response()
+}
+
+/// REST error body: `{"error": "..."}`
+#[derive(Serialize)]
+struct ApiError {
+    error: String,
+}
+
+type ApiResult<T> = std::result::Result<Json<T>, (StatusCode, Json<ApiError>)>;
+
+fn api_error(status: StatusCode, msg: impl Into<String>) -> (StatusCode, Json<ApiError>) {
+    (status, Json(ApiError { error: msg.into() }))
+}
+
+fn upstream_error(e: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    api_error(StatusCode::BAD_GATEWAY, format!("{:#}", e))
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyQuery {
+    apikey: Option<String>,
+}
+
+fn check_api_key(state: &ServeState, given: Option<&str>) -> std::result::Result<(), (StatusCode, Json<ApiError>)> {
+    match &state.api_key {
+        Some(expected) if given != Some(expected.as_str()) => Err(api_error(StatusCode::UNAUTHORIZED, "invalid api key")),
+        _ => Ok(()),
+    }
+}
+
+fn wants_uncen(state: &ServeState, flag: Option<bool>) -> bool {
+    flag.unwrap_or(state.uncen)
+}
+
+async fn api_detail(State(state): State<Arc<ServeState>>, Path(code): Path<String>, Query(k): Query<KeyQuery>) -> ApiResult<AvDetail> {
+    check_api_key(&state, k.apikey.as_deref())?;
+    util::debug(format!("api: detail {}", code));
+    scraper::fetch_detail(&code).await.map(Json).map_err(upstream_error)
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    q: Option<String>,
+    uncen: Option<bool>,
+    apikey: Option<String>,
+}
+
+async fn api_search(State(state): State<Arc<ServeState>>, Query(params): Query<SearchQuery>) -> ApiResult<Vec<AvItem>> {
+    check_api_key(&state, params.apikey.as_deref())?;
+    let q = params.q.as_deref().unwrap_or("").trim().to_string();
+    if q.is_empty() {
+        return Err(api_error(StatusCode::BAD_REQUEST, "missing query parameter q"));
+    }
+    let mut items = scraper::search(&q).await.map_err(upstream_error)?;
+    if wants_uncen(&state, params.uncen) {
+        items.retain(|i| util::looks_uncensored(&i.title));
+    }
+    Ok(Json(items))
+}
+
+#[derive(Debug, Deserialize)]
+struct FilterQuery {
+    uncen: Option<bool>,
+    apikey: Option<String>,
+}
+
+async fn api_list(State(state): State<Arc<ServeState>>, Path(actor): Path<String>, Query(params): Query<FilterQuery>) -> ApiResult<Vec<AvItem>> {
+    check_api_key(&state, params.apikey.as_deref())?;
+    let mut items = scraper::list_actor_titles(&actor).await.map_err(upstream_error)?;
+    if wants_uncen(&state, params.uncen) {
+        items.retain(|i| util::looks_uncensored(&i.title));
+    }
+    Ok(Json(items))
+}
+
+#[derive(Debug, Deserialize)]
+struct TopQuery {
+    limit: Option<usize>,
+    uncen: Option<bool>,
+    apikey: Option<String>,
+}
+
+async fn api_top(State(state): State<Arc<ServeState>>, Query(params): Query<TopQuery>) -> ApiResult<Vec<AvItem>> {
+    check_api_key(&state, params.apikey.as_deref())?;
+    let mut items = scraper::top(params.limit.unwrap_or(20)).await.map_err(upstream_error)?;
+    if wants_uncen(&state, params.uncen) {
+        items.retain(|i| util::looks_uncensored(&i.title));
+    }
+    Ok(Json(items))
+}
+
+#[derive(Debug, Deserialize)]
+struct ActorsQuery {
+    page: Option<usize>,
+    per_page: Option<usize>,
+    uncen: Option<bool>,
+    apikey: Option<String>,
+}
+
+async fn api_actors(State(state): State<Arc<ServeState>>, Query(params): Query<ActorsQuery>) -> ApiResult<ActorsEnvelope> {
+    check_api_key(&state, params.apikey.as_deref())?;
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(50).max(1);
+    let found = scraper::actors(page, per_page, wants_uncen(&state, params.uncen))
+        .await
+        .map_err(upstream_error)?;
+    // Without the API key, which the caller adds back
+    let uncen = params.uncen.map(|u| format!("&uncen={}", u)).unwrap_or_default();
+    Ok(Json(found.envelope(page, per_page, || Some(format!("/actors?page={}&per_page={}{}", page + 1, per_page, uncen)))))
+}
+
+#[derive(Debug, Deserialize)]
+struct DownloadRequest {
+    code: String,
+    /// One of the title's magnets; the best-ranked one when absent
+    magnet: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DownloadSent {
+    code: String,
+    magnet: String,
+    client: &'static str,
+    /// `added`, or `waiting` while a client that waits for completion is still downloading
+    status: &'static str,
+}
+
+/// Hand a title's magnet to the configured torrent client. Only a client: aria2c and
+/// the system BT client would download on the serving machine, outside anyone's view.
+async fn api_download(State(state): State<Arc<ServeState>>, Query(k): Query<KeyQuery>, Json(req): Json<DownloadRequest>) -> ApiResult<DownloadSent> {
+    check_api_key(&state, k.apikey.as_deref())?;
+    let client = match downloader::selected() {
+        Ok(Some(c)) => c.name(),
+        Ok(None) => return Err(api_error(StatusCode::CONFLICT, "no download client configured ([qbittorrent], [deluge], [rtorrent] or [remote])")),
+        Err(e) => return Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))),
+    };
+    let d = scraper::fetch_detail(req.code.trim()).await.map_err(upstream_error)?;
+    let magnet = match req.magnet.filter(|m| !m.trim().is_empty()) {
+        Some(m) if d.magnets.contains(&m) => m,
+        Some(_) => return Err(api_error(StatusCode::BAD_REQUEST, format!("magnet is not one of {}'s", d.code))),
+        None => util::ranked_magnets(&d).into_iter().next().ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("{} has no magnets", d.code)))?,
+    };
+    util::debug(format!("api: download {} via {}", d.code, client));
+    let sent = DownloadSent { code: d.code.clone(), magnet: magnet.clone(), client, status: "added" };
+    let task = tokio::spawn(async move { util::dispatch(&magnet, &d.code, Some(&d)).await });
+    match tokio::time::timeout(std::time::Duration::from_secs(DOWNLOAD_ANSWER_SECS), task).await {
+        Ok(Ok(Ok(_))) => Ok(Json(sent)),
+        Ok(Ok(Err(e))) => Err(upstream_error(e)),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(_) => Ok(Json(DownloadSent { status: "waiting", ..sent })),
+    }
+}
+
+/// `GET /graphql?query=...&variables=...` and `POST /graphql`, parsed by async-graphql-axum;
+/// `apikey` rides in the query string either way
+async fn graphql_handler(State(state): State<Arc<ServeState>>, Query(k): Query<KeyQuery>, req: GraphQLRequest) -> Response {
+    if let Err(e) = check_api_key(&state, k.apikey.as_deref()) {
+        return e.into_response();
+    }
+    GraphQLResponse::from(graphql::execute(&state.schema, req.into_inner()).await).into_response()
+}
+
+async fn graphql_schema(State(state): State<Arc<ServeState>>, Query(k): Query<KeyQuery>) -> Response {
+    if let Err(e) = check_api_key(&state, k.apikey.as_deref()) {
+        return e.into_response();
+    }
+    ([(CONTENT_TYPE, "text/plain; charset=utf-8")], state.schema.sdl()).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedQuery {
+    limit: Option<usize>,
+    /// Comma-separated actor names to include alongside newest releases
+    actors: Option<String>,
+    uncen: Option<bool>,
+    apikey: Option<String>,
+}
+
+async fn feed_rss(State(state): State<Arc<ServeState>>, Query(params): Query<FeedQuery>) -> Response {
+    feed_response(&state, params, FeedFormat::Rss).await
+}
+
+async fn feed_atom(State(state): State<Arc<ServeState>>, Query(params): Query<FeedQuery>) -> Response {
+    feed_response(&state, params, FeedFormat::Atom).await
+}
+
+async fn feed_response(state: &ServeState, params: FeedQuery, format: FeedFormat) -> Response {
+    if let Err(e) = check_api_key(state, params.apikey.as_deref()) {
+        return e.into_response();
+    }
+    let opts = FeedOptions {
+        limit: params.limit.unwrap_or(20),
+        actors: params
+            .actors
+            .as_deref()
+            .map(|a| a.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default(),
+        uncen: wants_uncen(state, params.uncen),
+    };
+    match feed::build(&opts, format).await {
+        Ok(body) => ([(CONTENT_TYPE, format.content_type())], body).into_response(),
+        Err(e) => upstream_error(e).into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TorznabQuery {
+    t: Option<String>,
+    q: Option<String>,
+    apikey: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+async fn torznab_api(State(state): State<Arc<ServeState>>, Query(params): Query<TorznabQuery>) -> Response {
+    if let Some(expected) = &state.api_key {
+        if params.apikey.as_deref() != Some(expected.as_str()) {
+            return xml_response(torznab_error(100, "Incorrect user credentials"));
+        }
+    }
+    let func = params.t.as_deref().unwrap_or("");
+    util::debug(format!("torznab: t={} q={:?}", func, params.q));
+    match func {
+        "caps" => xml_response(torznab_caps()),
+        "search" | "movie" => {
+            let limit = params.limit.unwrap_or(TORZNAB_DEFAULT_LIMIT).clamp(1, TORZNAB_MAX_LIMIT);
+            let offset = params.offset.unwrap_or(0);
+            let q = params.q.as_deref().unwrap_or("").trim().to_string();
+            match torznab_search(&q, state.uncen).await {
+                Ok(items) => {
+                    let total = items.len();
+                    let items = items.into_iter().skip(offset).take(limit).collect::<Vec<_>>();
+                    xml_response(torznab_feed(&items, offset, total))
+                }
+                Err(e) => xml_response(torznab_error(900, &e.to_string())),
+            }
+        }
+        _ => xml_response(torznab_error(202, "No such function")),
+    }
+}
+
+fn xml_response(body: String) -> Response {
+    ([(CONTENT_TYPE, "application/xml; charset=utf-8")], body).into_response()
+}
+
+struct TorznabItem {
+    code: String,
+    title: String,
+    magnet: String,
+    infohash: Option<String>,
+    size_bytes: Option<u64>,
+    seeders: Option<u32>,
+    leechers: Option<u32>,
+    grabs: Option<u32>,
+    pub_date: Option<String>,
+}
+
+/// Every release for `q` (its first `TORZNAB_MAX_CODES` titles), so the feed can say how
+/// many there are; the details are all fetched either way
+async fn torznab_search(q: &str, uncen: bool) -> Result<Vec<TorznabItem>> {
+    // Empty query is how indexer managers probe for "latest" releases
+    let codes: Vec<String> = if q.is_empty() {
+        scraper::top(TORZNAB_MAX_CODES).await?.into_iter().map(|i| i.code).collect()
+    } else if scraper::looks_like_code(q) {
+        vec![code::normalize(q)]
+    } else {
+        scraper::search(q).await?.into_iter().map(|i| i.code).take(TORZNAB_MAX_CODES).collect()
+    };
+
+    let details = scraper::fetch_details(codes).await;
+
+    let mut items = Vec::new();
+    for d in &details {
+        items.extend(items_from_detail(d).into_iter().filter(|i| !uncen || util::looks_uncensored(&i.title)));
+    }
+    Ok(items)
+}
+
+fn items_from_detail(d: &AvDetail) -> Vec<TorznabItem> {
+    let fallback_title = format!("{} {}", d.code, d.title.trim());
+    if !d.magnet_infos.is_empty() {
+        return d
+            .magnet_infos
+            .iter()
+            .map(|m| TorznabItem {
+                code: d.code.clone(),
+                title: m.name.clone().filter(|n| !n.trim().is_empty()).unwrap_or_else(|| fallback_title.clone()),
+                magnet: m.url.clone(),
+                infohash: m.infohash.clone().or_else(|| util::magnet_infohash(&m.url)),
+                size_bytes: magnet::size_bytes(m),
+                seeders: m.seeders,
+                leechers: m.leechers,
+                grabs: m.downloads,
+                pub_date: m.date.clone().or_else(|| d.release_date.clone()),
+            })
+            .collect();
+    }
+    d.magnets
+        .iter()
+        .map(|url| TorznabItem {
+            code: d.code.clone(),
+            title: fallback_title.clone(),
+            magnet: url.clone(),
+            infohash: util::magnet_infohash(url),
+            size_bytes: None,
+            seeders: None,
+            leechers: None,
+            grabs: None,
+            pub_date: d.release_date.clone(),
+        })
+        .collect()
+}
+
+fn torznab_caps() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<caps>
+  <server version="{version}" title="av" />
+  <limits max="{max}" default="{default}" />
+  <searching>
+    <search available="yes" supportedParams="q" />
+    <tv-search available="no" supportedParams="q" />
+    <movie-search available="yes" supportedParams="q" />
+  </searching>
+  <categories>
+    <category id="{cat}" name="XXX" />
+  </categories>
+</caps>
+"#,
+        version = env!("CARGO_PKG_VERSION"),
+        max = TORZNAB_MAX_LIMIT,
+        default = TORZNAB_DEFAULT_LIMIT,
+        cat = TORZNAB_CATEGORY,
+    )
+}
+
+fn torznab_error(code: u32, description: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<error code=\"{}\" description=\"{}\" />\n",
+        code,
+        util::xml_escape(description)
+    )
+}
+
+/// One page of results; `total` is the count over all pages, which indexer managers page by
+fn torznab_feed(items: &[TorznabItem], offset: usize, total: usize) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:torznab=\"http://torznab.com/schemas/2015/feed\">\n");
+    out.push_str("<channel>\n<title>av</title>\n<description>av Torznab feed</description>\n");
+    out.push_str(&format!("<torznab:response offset=\"{}\" total=\"{}\" />\n", offset, total));
+    for it in items {
+        let title = util::xml_escape(&it.title);
+        let magnet = util::xml_escape(&it.magnet);
+        let guid = it.infohash.clone().unwrap_or_else(|| it.magnet.clone());
+        out.push_str("<item>\n");
+        out.push_str(&format!("<title>{}</title>\n", title));
+        out.push_str(&format!("<guid isPermaLink=\"false\">{}</guid>\n", util::xml_escape(&guid)));
+        out.push_str(&format!("<link>{}</link>\n", magnet));
+        out.push_str(&format!("<comments>{}</comments>\n", util::xml_escape(&it.code)));
+        if let Some(date) = it.pub_date.as_deref().and_then(util::rfc2822_from_date) {
+            out.push_str(&format!("<pubDate>{}</pubDate>\n", date));
+        }
+        out.push_str(&format!("<category>{}</category>\n", TORZNAB_CATEGORY));
+        let size = it.size_bytes.unwrap_or(0);
+        out.push_str(&format!("<size>{}</size>\n", size));
+        out.push_str(&format!("<enclosure url=\"{}\" length=\"{}\" type=\"application/x-bittorrent\" />\n", magnet, size));
+        out.push_str(&format!("<torznab:attr name=\"category\" value=\"{}\" />\n", TORZNAB_CATEGORY));
+        out.push_str(&format!("<torznab:attr name=\"magneturl\" value=\"{}\" />\n", magnet));
+        if let Some(h) = &it.infohash {
+            out.push_str(&format!("<torznab:attr name=\"infohash\" value=\"{}\" />\n", h));
+        }
+        if let Some(s) = it.seeders {
+            out.push_str(&format!("<torznab:attr name=\"seeders\" value=\"{}\" />\n", s));
+            let peers = s + it.leechers.unwrap_or(0);
+            out.push_str(&format!("<torznab:attr name=\"peers\" value=\"{}\" />\n", peers));
+        }
+        if let Some(g) = it.grabs {
+            out.push_str(&format!("<torznab:attr name=\"grabs\" value=\"{}\" />\n", g));
+        }
+        out.push_str("</item>\n");
+    }
+    out.push_str("</channel>\n</rss>\n");
+    out
+}
@@ -0,0 +1,334 @@
+//! `av init`, also offered on the first run without a config file: a few questions —
+//! proxy, sources, download client, library — answered into `config.toml`, followed by the
+//! connectivity check of `av doctor proxy`. It runs before the config is loaded, so the
+//! command that triggered it already runs on the new file.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config;
+use crate::http;
+use crate::store;
+use crate::util;
+
+/// Remembers a declined first-run offer, so it is made once
+const STORE: &str = "setup";
+
+/// Local ports of the usual proxy clients (Clash, Clash Verge, v2rayN), offered as the answer
+const LOCAL_PROXIES: [(&str, u16); 5] =
+    [("http", 7890), ("http", 7897), ("http", 10809), ("socks5h", 10808), ("socks5h", 1080)];
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    declined_at: Option<u64>,
+}
+
+fn interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// No config file yet, someone at the terminal, and the offer not declined before
+pub fn first_run() -> bool {
+    if config::config_path().is_none_or(|p| p.exists()) || !interactive() {
+        return false;
+    }
+    !store::load::<State>(STORE).is_ok_and(|s| s.declined_at.is_some())
+}
+
+fn read_line() -> Result<String> {
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line).context("读取输入失败")? == 0 {
+        bail!("输入已结束，设置向导中止");
+    }
+    Ok(line.trim().to_string())
+}
+
+/// Free text; Enter takes `default`, `-` clears it (`None`)
+fn ask(label: &str, default: Option<&str>) -> Result<Option<String>> {
+    match default {
+        Some(d) => eprint!("{} [{}]: ", label, d),
+        None => eprint!("{}: ", label),
+    }
+    let line = read_line()?;
+    Ok(match line.as_str() {
+        "" => default.map(str::to_string),
+        "-" => None,
+        _ => Some(line),
+    })
+}
+
+fn confirm(label: &str, default: bool) -> Result<bool> {
+    loop {
+        eprint!("{} {}: ", label, if default { "[Y/n]" } else { "[y/N]" });
+        match read_line()?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" | "是" => return Ok(true),
+            "n" | "no" | "否" => return Ok(false),
+            _ => eprintln!("请输入 y 或 n"),
+        }
+    }
+}
+
+/// One of `options` by its number, `default` (0-based) on Enter
+fn choose(label: &str, options: &[&str], default: usize) -> Result<usize> {
+    eprintln!("{}", label);
+    for (idx, option) in options.iter().enumerate() {
+        eprintln!("  {}) {}", idx + 1, option);
+    }
+    loop {
+        eprint!("选择 [{}]: ", default + 1);
+        let line = read_line()?;
+        if line.is_empty() {
+            return Ok(default);
+        }
+        match line.parse::<usize>() {
+            Ok(n) if (1..=options.len()).contains(&n) => return Ok(n - 1),
+            _ => eprintln!("请输入 1 到 {} 之间的数字", options.len()),
+        }
+    }
+}
+
+fn lookup<'a>(table: &'a toml::Table, path: &[&str]) -> Option<&'a toml::Value> {
+    let (last, parents) = path.split_last()?;
+    let mut cur = table;
+    for part in parents {
+        cur = cur.get(*part)?.as_table()?;
+    }
+    cur.get(*last)
+}
+
+fn lookup_str<'a>(table: &'a toml::Table, path: &[&str]) -> Option<&'a str> {
+    lookup(table, path).and_then(toml::Value::as_str)
+}
+
+/// Set (or with `None`, remove) `path`, creating the tables above it
+fn set(table: &mut toml::Table, path: &[&str], value: Option<toml::Value>) {
+    let Some((last, parents)) = path.split_last() else { return };
+    let mut cur = table;
+    for part in parents {
+        let entry = cur.entry(part.to_string()).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        let Some(t) = entry.as_table_mut() else { return };
+        cur = t;
+    }
+    match value {
+        Some(v) => {
+            cur.insert(last.to_string(), v);
+        }
+        None => {
+            cur.remove(*last);
+        }
+    }
+}
+
+fn set_str(table: &mut toml::Table, path: &[&str], value: Option<String>) {
+    set(table, path, value.map(toml::Value::String));
+}
+
+/// The first of the usual local proxy ports something listens on
+async fn detect_proxy() -> Option<String> {
+    for (scheme, port) in LOCAL_PROXIES {
+        if scheme.starts_with("socks") && !cfg!(feature = "socks") {
+            continue;
+        }
+        let connect = tokio::net::TcpStream::connect(("127.0.0.1", port));
+        if matches!(tokio::time::timeout(PROBE_TIMEOUT, connect).await, Ok(Ok(_))) {
+            return Some(format!("{}://127.0.0.1:{}", scheme, port));
+        }
+    }
+    None
+}
+
+async fn ask_proxy(table: &mut toml::Table) -> Result<()> {
+    eprintln!("\n{}", "1. 代理".bold());
+    let current = lookup_str(table, &["network", "proxy"]).map(str::to_string);
+    let detected = if current.is_none() { detect_proxy().await } else { None };
+    if let Some(d) = &detected {
+        eprintln!("检测到本机代理端口: {}", d);
+    }
+    let default = current.or(detected);
+    loop {
+        let answer = ask("代理地址（http://、socks5h://，- 为直连）", default.as_deref())?;
+        if let Some(Err(e)) = answer.as_deref().map(http::parse_proxy) {
+            eprintln!("{} {:#}", "无效的代理:".red(), e);
+            continue;
+        }
+        set_str(table, &["network", "proxy"], answer);
+        return Ok(());
+    }
+}
+
+fn ask_sources(table: &mut toml::Table) -> Result<()> {
+    eprintln!("\n{}", "2. 数据源".bold());
+    let langs = ["不指定（按数据源原样合并）", "中文", "日文", "英文"];
+    let current = match lookup_str(table, &["metadata", "lang"]) {
+        Some("zh") => 1,
+        Some("ja") => 2,
+        Some("en") => 3,
+        _ => 0,
+    };
+    let lang = match choose("标题与类别优先使用的语言:", &langs, current)? {
+        1 => Some("zh"),
+        2 => Some("ja"),
+        3 => Some("en"),
+        _ => None,
+    };
+    set_str(table, &["metadata", "lang"], lang.map(str::to_string));
+
+    let all = lookup(table, &["magnets", "all_sources"]).and_then(toml::Value::as_bool).unwrap_or(false);
+    let all = confirm("磁力链接查询所有来源并合并（更全，但更慢）？", all)?;
+    set(table, &["magnets", "all_sources"], all.then_some(toml::Value::Boolean(true)));
+
+    let western = lookup(table, &["metadata", "western"]).and_then(toml::Value::as_bool).unwrap_or(false);
+    let western = confirm("也用来查欧美作品（ThePornDB，需要 API token）？", western)?;
+    set(table, &["metadata", "western"], western.then_some(toml::Value::Boolean(true)));
+    if western {
+        let token = ask("ThePornDB API token", lookup_str(table, &["sources", "theporndb", "token"]))?;
+        set_str(table, &["sources", "theporndb", "token"], token);
+    }
+    Ok(())
+}
+
+fn ask_download(table: &mut toml::Table) -> Result<()> {
+    eprintln!("\n{}", "3. 下载".bold());
+    let clients = ["aria2c（本机）", "qBittorrent", "Deluge", "rTorrent"];
+    let current = match lookup_str(table, &["download", "client"]) {
+        Some("qbittorrent") => 1,
+        Some("deluge") => 2,
+        Some("rtorrent") => 3,
+        _ => 0,
+    };
+    let picked = choose("下载客户端（通过 SSH 的 [remote] 请直接编辑配置文件）:", &clients, current)?;
+    let (kind, default_url) = match picked {
+        1 => ("qbittorrent", Some("http://localhost:8080")),
+        2 => ("deluge", Some("http://localhost:8112")),
+        3 => ("rtorrent", None),
+        _ => ("aria2", None),
+    };
+    set_str(table, &["download", "client"], Some(kind.to_string()));
+    if kind == "aria2" {
+        if which::which("aria2c").is_err() {
+            eprintln!("{} 未检测到 aria2c，请先安装: brew install aria2 / apt install aria2 / scoop install aria2", "[WARN]".yellow());
+        }
+        return Ok(());
+    }
+    let url = loop {
+        let default = lookup_str(table, &[kind, "url"]).or(default_url);
+        match ask(&format!("{} 地址", clients[picked]), default)? {
+            Some(u) if reqwest::Url::parse(&u).is_ok() => break u,
+            Some(u) => eprintln!("{} {}", "无效的地址:".red(), u),
+            None => eprintln!("{} 需要地址", clients[picked]),
+        }
+    };
+    set_str(table, &[kind, "url"], Some(url));
+    if kind != "deluge" {
+        let username = ask("用户名（- 为不需要）", lookup_str(table, &[kind, "username"]))?;
+        set_str(table, &[kind, "username"], username);
+    }
+    // Echoed as typed; the file holds it in plain text either way
+    let password = ask("密码（明文保存在配置文件中，- 为不需要）", lookup_str(table, &[kind, "password"]))?;
+    set_str(table, &[kind, "password"], password);
+    Ok(())
+}
+
+/// `~/x` → `<home>/x`
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/").or(if path == "~" { Some("") } else { None }), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn ask_library(table: &mut toml::Table) -> Result<()> {
+    eprintln!("\n{}", "4. 媒体库".bold());
+    let answer = ask("媒体库目录（整理后的影片存放处，- 为不设置）", lookup_str(table, &["pipeline", "library_dir"]))?;
+    let dir = answer.map(|a| expand_home(&a));
+    if let Some(d) = dir.as_ref().filter(|d| !d.is_dir()) {
+        eprintln!("{} 目录 {} 还不存在，整理时会自动创建", "[WARN]".yellow(), d.display());
+    }
+    set_str(table, &["pipeline", "library_dir"], dir.map(|d| d.display().to_string()));
+    Ok(())
+}
+
+/// Drop the tables a cleared answer left empty
+fn prune(table: &mut toml::Table) {
+    for (_, value) in table.iter_mut() {
+        if let toml::Value::Table(t) = value {
+            prune(t);
+        }
+    }
+    table.retain(|_, v| !v.as_table().is_some_and(toml::Table::is_empty));
+}
+
+fn write(path: &Path, table: &mut toml::Table) -> Result<()> {
+    prune(table);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("创建配置目录失败: {}", dir.display()))?;
+    }
+    if path.exists() {
+        let backup = path.with_extension("toml.bak");
+        std::fs::copy(path, &backup).with_context(|| format!("备份配置文件失败: {}", backup.display()))?;
+        util::note(format!("原配置文件已备份为 {}", backup.display()));
+    }
+    let body = format!("# 由 av init 生成；其余选项见 README 的 Configuration 一节\n\n{}", toml::to_string(table)?);
+    std::fs::write(path, body).with_context(|| format!("写入配置文件失败: {}", path.display()))?;
+    Ok(())
+}
+
+/// The questions, then the answers written over the current file (other settings kept);
+/// `false` when nothing was written
+pub async fn wizard() -> Result<bool> {
+    if !interactive() {
+        bail!("设置向导需要在终端中交互运行；也可以直接编辑配置文件");
+    }
+    let path = config::config_path().context("无法确定配置目录，请用 AV_CONFIG 指定配置文件路径")?;
+    let mut table = if path.exists() {
+        let text = std::fs::read_to_string(&path).with_context(|| format!("读取配置文件失败: {}", path.display()))?;
+        let table: toml::Table = toml::from_str(&text).with_context(|| format!("配置文件格式错误，请先修正: {}", path.display()))?;
+        eprintln!("将更新已有的配置文件 {}：回答的设置被替换，其余设置保留，但注释会丢失", path.display());
+        if !confirm("继续？", true)? {
+            return Ok(false);
+        }
+        table
+    } else {
+        toml::Table::new()
+    };
+    eprintln!("直接回车接受 [] 中的默认值，输入 - 清空");
+    ask_proxy(&mut table).await?;
+    ask_sources(&mut table)?;
+    ask_download(&mut table)?;
+    ask_library(&mut table)?;
+    write(&path, &mut table)?;
+    eprintln!("\n{} {}", "配置已写入".green().bold(), path.display());
+    Ok(true)
+}
+
+/// The first-run offer; a refusal is remembered and `av init` mentioned instead
+pub async fn offer() -> Result<bool> {
+    eprintln!("{}", "欢迎使用 av！还没有配置文件。".bold());
+    if confirm("现在运行设置向导（代理、数据源、下载客户端、媒体库）？", true)? {
+        return wizard().await;
+    }
+    if let Err(e) = store::save(STORE, &State { declined_at: Some(util::now_secs()) }) {
+        util::debug(format!("setup: {:#}", e));
+    }
+    util::note("已跳过，之后可随时运行 av init");
+    Ok(false)
+}
+
+/// `av doctor proxy` on the settings just written; failures are only reported
+pub async fn check() {
+    eprintln!("\n{}", "检查数据源连通性…".bold());
+    if let Err(e) = crate::doctor::run(Some(crate::doctor::Check::Proxy), false).await {
+        eprintln!("{} {:#}；调整 [network] 设置后可运行 av doctor proxy 再次检查", "[WARN]".yellow(), e);
+    }
+}
@@ -0,0 +1,153 @@
+//! `av shell`: a readline loop over the regular subcommands. One process serves every
+//! line, so the HTTP client with its pooled connections and cookie jars is reused, and
+//! pages fetched once (search results, detail pages) are answered from memory afterwards.
+
+use anyhow::Result;
+use clap::Parser;
+use colored::Colorize;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::ffi::OsString;
+
+use crate::alias;
+use crate::cancel;
+use crate::errors;
+use crate::scraper;
+use crate::store;
+use crate::util;
+use crate::{Cli, Commands};
+
+const PROMPT: &str = "av> ";
+/// Line history, in the data dir next to the other state
+const HISTORY_FILE: &str = "shell_history";
+
+/// Split a line into arguments: whitespace separated, with '…' / "…" quoting and `\` escapes
+pub(crate) fn split(line: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                cur.push(chars.next()?);
+                in_arg = true;
+            }
+            (Some(_), c) => cur.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(ch);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut cur));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                cur.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    if in_arg {
+        args.push(cur);
+    }
+    Some(args)
+}
+
+fn print_help() {
+    println!("输入任意子命令（不带 av 前缀），例如:");
+    println!("  search 三上悠亜");
+    println!("  detail SSIS-001 --json");
+    println!("  get SSIS-001");
+    println!("内置命令:");
+    println!("  help      显示本帮助（子命令列表见 --help）");
+    println!("  refresh   清空本次会话缓存的页面");
+    println!("  exit      退出（也可按 Ctrl-D）");
+}
+
+/// Run the shell; `base` carries the global flags it was started with (`av --mock shell`)
+pub async fn run(base: Cli) -> Result<()> {
+    scraper::enable_page_cache();
+    let mut rl = DefaultEditor::new()?;
+    let history = store::data_dir().join(HISTORY_FILE);
+    let _ = rl.load_history(&history);
+    println!("{}", "av shell — 输入 help 查看用法，exit 或 Ctrl-D 退出".dimmed());
+    loop {
+        let line = match tokio::task::block_in_place(|| rl.readline(PROMPT)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = rl.add_history_entry(line);
+        match line {
+            "exit" | "quit" => break,
+            "help" | "?" => {
+                print_help();
+                continue;
+            }
+            "refresh" => {
+                scraper::clear_page_cache();
+                println!("已清空页面缓存");
+                continue;
+            }
+            _ => {}
+        }
+        let Some(words) = split(line) else {
+            eprintln!("{}", "引号未闭合".red());
+            continue;
+        };
+        let words = words.into_iter().skip_while(|w| w == "av");
+        let args = match alias::expand(std::iter::once("av".to_string()).chain(words).map(OsString::from).collect()) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("{} {:#}", "Error:".red().bold(), e);
+                continue;
+            }
+        };
+        crate::batch::set_args(&args);
+        let mut cli = match Cli::try_parse_from(args) {
+            Ok(cli) => cli.inherit(&base),
+            Err(e) => {
+                let _ = e.print();
+                continue;
+            }
+        };
+        if matches!(cli.command, Commands::Shell) {
+            println!("已在 av shell 中");
+            continue;
+        }
+        let json = cli.wants_json();
+        let report = |e: anyhow::Error| {
+            if json {
+                errors::print_json(&e);
+            } else {
+                eprintln!("{} {:#}", "Error:".red().bold(), e);
+            }
+        };
+        if let Err(e) = cli.apply() {
+            report(e);
+            continue;
+        }
+        let result = Box::pin(crate::run(cli)).await.and_then(|()| util::take_query_error().map_or(Ok(()), Err));
+        if let Err(e) = result {
+            report(e);
+        }
+        cancel::reset();
+    }
+    if std::fs::create_dir_all(store::data_dir()).is_ok() {
+        let _ = rl.save_history(&history);
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::scraper;
+use crate::types::AvDetail;
+use crate::util;
+
+#[derive(Debug, Serialize)]
+struct SourceResult {
+    source: String,
+    /// Set when the request failed; `detail` is then empty
+    error: Option<String>,
+    detail: Option<AvDetail>,
+}
+
+#[derive(Debug, Serialize)]
+struct SourceDiff {
+    code: String,
+    sources: Vec<SourceResult>,
+    /// What `av detail` shows after merging
+    merged: Option<AvDetail>,
+    /// Fields on which the sources that have a value disagree
+    differing: Vec<&'static str>,
+}
+
+/// (json name, label) of every compared field, in the order `values` returns them
+const FIELDS: [(&str, &str); 16] = [
+    ("title", "标题"),
+    ("actor_names", "演员"),
+    ("release_date", "发行"),
+    ("duration_minutes", "时长"),
+    ("director", "导演"),
+    ("studio", "片商"),
+    ("label", "厂牌"),
+    ("series", "系列"),
+    ("genres", "类别"),
+    ("rating", "评分"),
+    ("cover_url", "封面"),
+    ("plot", "剧情"),
+    ("preview_images", "预览图"),
+    ("trailer_url", "预告片"),
+    ("sample_video_url", "样片"),
+    ("magnets", "磁力"),
+];
+
+/// Display value of each field in `FIELDS`; `None` when the source has nothing
+fn values(d: &AvDetail) -> [Option<String>; 16] {
+    let list = |v: &[String]| Some(v.join(", ")).filter(|s| !s.is_empty());
+    let count = |n: usize, unit: &str| Some(n).filter(|n| *n > 0).map(|n| format!("{} {}", n, unit));
+    [
+        Some(d.title.trim().to_string()).filter(|s| !s.is_empty()),
+        list(&d.actor_names),
+        d.release_date.clone(),
+        d.duration_minutes.map(|m| format!("{} 分钟", m)),
+        d.director.clone(),
+        d.studio.clone(),
+        d.label.clone(),
+        d.series.clone(),
+        list(&d.genres),
+        d.rating.map(|r| format!("{:.2}", r)),
+        d.cover_url.clone(),
+        d.plot.as_deref().map(|p| util::truncate_chars(p.trim(), 60)),
+        count(d.preview_images.len(), "张"),
+        d.trailer_url.clone(),
+        d.sample_video_url.clone(),
+        count(d.magnet_infos.len().max(d.magnets.len()), "个"),
+    ]
+}
+
+/// Share of the compared fields the source filled in, 0.0 ..= 1.0 (`av bench`)
+pub fn completeness(d: &AvDetail) -> f32 {
+    let filled = values(d).iter().filter(|v| v.is_some()).count();
+    filled as f32 / FIELDS.len() as f32
+}
+
+/// Comparison key: case, spacing and list order don't count as differences
+fn canonical(field: &str, v: &str) -> String {
+    let v = v.trim().to_lowercase();
+    if matches!(field, "actor_names" | "genres") {
+        let mut parts: Vec<&str> = v.split(", ").map(str::trim).collect();
+        parts.sort_unstable();
+        return parts.join(",");
+    }
+    v.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// `av detail CODE --compare-sources`: each source's own fields next to the merged result
+pub async fn run(code: &str, json: bool) -> Result<()> {
+    let (per_source, merged) = tokio::join!(scraper::fetch_detail_by_source(code), scraper::fetch_detail(code));
+    let merged = match merged {
+        Ok(d) => Some(d),
+        Err(e) => {
+            eprintln!("[WARN] 合并结果获取失败: {:#}", e);
+            None
+        }
+    };
+    let sources: Vec<SourceResult> = per_source
+        .into_iter()
+        .map(|(source, r)| match r {
+            Ok(detail) => SourceResult { source, error: None, detail },
+            Err(e) => SourceResult { source, error: Some(format!("{:#}", e)), detail: None },
+        })
+        .collect();
+
+    let hits: Vec<(&str, [Option<String>; 16])> =
+        sources.iter().filter_map(|s| s.detail.as_ref().map(|d| (s.source.as_str(), values(d)))).collect();
+    let differing: Vec<&'static str> = FIELDS
+        .iter()
+        .enumerate()
+        .filter(|(i, (f, _))| {
+            let mut distinct: Vec<String> =
+                hits.iter().filter_map(|(_, vs)| vs[*i].as_deref().map(|v| canonical(f, v))).collect();
+            distinct.sort();
+            distinct.dedup();
+            distinct.len() > 1
+        })
+        .map(|(_, (f, _))| *f)
+        .collect();
+
+    if json {
+        let code = merged.as_ref().map(|d| d.code.clone()).unwrap_or_else(|| crate::code::normalize(code));
+        util::print_output(&SourceDiff { code, sources, merged, differing }, true);
+        return Ok(());
+    }
+
+    for s in &sources {
+        let status = match (&s.error, &s.detail) {
+            (Some(e), _) => format!("失败: {}", util::truncate_chars(e, 80)).red().to_string(),
+            (None, Some(_)) => "命中".green().to_string(),
+            (None, None) => "无结果".dimmed().to_string(),
+        };
+        println!("{:<11} {}", s.source.bold(), status);
+    }
+    if hits.is_empty() {
+        println!("{}", "没有任何来源返回结果".yellow());
+        return Ok(());
+    }
+    let merged_values = merged.as_ref().map(values);
+    for (i, (f, label)) in FIELDS.iter().enumerate() {
+        let marker = if differing.contains(f) { " ≠".yellow().bold().to_string() } else { String::new() };
+        println!("\n{}{}", label.bold(), marker);
+        for (source, vs) in &hits {
+            println!("  {:<11} {}", source, vs[i].as_deref().unwrap_or("-"));
+        }
+        if let Some(mv) = &merged_values {
+            println!("  {:<11} {}", "merged".cyan(), mv[i].as_deref().unwrap_or("-"));
+        }
+    }
+    Ok(())
+}
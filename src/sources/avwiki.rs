@@ -0,0 +1,58 @@
+//! AV-Wiki (av-wiki.net), whose posts name the performers of amateur-label titles
+//! (`SIRO-`, `259LUXU-`, `300MIUM-` ...) that JavDB and the retailers list without a cast.
+//! Only asked when the other sources found nobody.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::LazyLock;
+use urlencoding::encode;
+
+use crate::code;
+use crate::selectors;
+use crate::sources::{absolute, text};
+use crate::util;
+
+const BASE: &str = "https://av-wiki.net";
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::scraper::client_builder().build().expect("client build"));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Identification {
+    pub code: String,
+    pub actresses: Vec<String>,
+    /// The post naming them
+    pub url: String,
+}
+
+pub fn search_url(code: &str) -> String {
+    format!("{}/?s={}&post_type=product", BASE, encode(code))
+}
+
+/// The post about `code` among the search results
+async fn post_url(code: &str) -> Result<Option<String>> {
+    let body = crate::scraper::get_text(&CLIENT, &search_url(code)).await?;
+    let doc = scraper::Html::parse_document(&body);
+    Ok(doc
+        .select(selectors::avwiki("result"))
+        .filter(|card| code::find(&text(*card)).is_some_and(|c| code::same_release(&c.to_string(), code)))
+        .find_map(|card| card.select(selectors::avwiki("result_link")).find_map(|a| a.value().attr("href")).map(|h| absolute(BASE, h))))
+}
+
+/// Who `code`'s post says is in it; `Ok(None)` without a post or names in it
+pub async fn lookup(code: &str) -> Result<Option<Identification>> {
+    let code = code::normalize(code);
+    let Some(url) = post_url(&code).await? else { return Ok(None) };
+    util::debug(format!("AV-Wiki: {}", url));
+    let body = crate::scraper::get_text(&CLIENT, &url).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let mut actresses: Vec<String> = Vec::new();
+    for name in doc.select(selectors::avwiki("actress")).map(text) {
+        // `名前（なまえ）` → `名前`
+        let name = name.split(['（', '(']).next().unwrap_or_default().trim().to_string();
+        if !name.is_empty() && !actresses.contains(&name) {
+            actresses.push(name);
+        }
+    }
+    Ok((!actresses.is_empty()).then_some(Identification { code, actresses, url }))
+}
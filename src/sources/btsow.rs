@@ -0,0 +1,97 @@
+//! BTSOW, a DHT index: magnets with names, sizes and dates (and seeders where a mirror
+//! shows them) for a code or an infohash. It knows nothing about the releases themselves,
+//! so it only ever adds magnets: as a fallback after Sukebei, alongside the others with
+//! `--all-magnets`, or alone with `--source btsow`.
+//!
+//! The site moves between domains; `AV_BTSOW_BASE` or `[magnets] btsow_base` point it at
+//! the current one.
+
+use anyhow::Result;
+use std::sync::LazyLock;
+use urlencoding::encode;
+
+use crate::code;
+use crate::config;
+use crate::magnet;
+use crate::selectors;
+use crate::types::{AvDetail, MagnetInfo};
+use crate::util;
+
+const DEFAULT_BASE: &str = "https://btsow.com";
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    crate::scraper::client_builder().cookie_provider(crate::cookies::provider()).build().expect("client build")
+});
+
+pub fn base() -> String {
+    std::env::var("AV_BTSOW_BASE")
+        .ok()
+        .or_else(|| config::get().magnets.btsow_base.clone())
+        .filter(|b| !b.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_BASE.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+pub fn search_url(query: &str) -> String {
+    format!("{}/search/{}", base(), encode(query.trim()))
+}
+
+fn is_infohash(s: &str) -> bool {
+    s.len() == 40 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The 40-hex infohash at the end of a `/magnet/detail/hash/<HASH>` link
+fn hash_of(href: &str) -> Option<String> {
+    let last = href.trim_end_matches('/').rsplit('/').next()?;
+    is_infohash(last).then(|| last.to_uppercase())
+}
+
+/// Magnets for `query`, a code or an infohash. For a code only the rows whose name
+/// carries that code are kept, since the index matches names loosely.
+pub async fn magnets(query: &str) -> Result<Vec<MagnetInfo>> {
+    let query = query.trim();
+    let by_code = !is_infohash(query);
+    let url = search_url(query);
+    util::debug(format!("BTSOW search: {}", url));
+    let body = crate::scraper::get_text(&CLIENT, &url).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let text = |row: scraper::ElementRef, key: &str| {
+        row.select(selectors::btsow(key))
+            .next()
+            .map(|n| n.text().collect::<String>().trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+    let mut out: Vec<MagnetInfo> = Vec::new();
+    for row in doc.select(selectors::btsow("row")) {
+        let Some(link) = row.select(selectors::btsow("link")).next() else { continue };
+        let Some(hash) = link.value().attr("href").and_then(hash_of) else { continue };
+        let name = link.value().attr("title").map(str::to_string).or_else(|| text(row, "name"));
+        if by_code && !name.as_deref().is_some_and(|n| code::is_named(n, query)) {
+            continue;
+        }
+        let uri = match &name {
+            Some(n) => format!("magnet:?xt=urn:btih:{}&dn={}", hash, encode(n)),
+            None => format!("magnet:?xt=urn:btih:{}", hash),
+        };
+        let Some(base) = magnet::info(&uri) else { continue };
+        if out.iter().any(|m| magnet::same(m, &base)) {
+            continue;
+        }
+        out.push(MagnetInfo {
+            part: name.as_deref().and_then(code::part),
+            name,
+            size: text(row, "size"),
+            date: text(row, "date"),
+            seeders: text(row, "seeders").and_then(|s| s.replace(',', "").parse().ok()),
+            ..base
+        });
+    }
+    Ok(out)
+}
+
+/// `--source btsow`; `None` when it has no magnets for the code
+pub async fn fetch_detail(code: &str) -> Result<Option<AvDetail>> {
+    let infos = magnets(code).await?;
+    Ok((!infos.is_empty()).then(|| magnet::detail_of(code, infos)))
+}
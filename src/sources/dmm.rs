@@ -1,28 +1,85 @@
 use anyhow::{Context, Result};
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderValue, COOKIE};
 use reqwest::Url;
+use serde::Serialize;
 use serde_json::Value;
+use std::sync::LazyLock;
 
+use crate::code;
+use crate::config;
+use crate::keychain::{self, Secret};
+use crate::metrics::{self, FetchOutcome};
+use crate::rating;
 use crate::types::AvDetail;
+use crate::util;
 
-fn env_api_id() -> Option<String> {
-    std::env::var("DMM_API_ID").ok().filter(|s| !s.is_empty())
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::http::with_proxy(reqwest::Client::builder()).build().expect("client build"));
+
+/// The HTML5 player the `sampleMovieURL` pages embed
+static PLAYER_FRAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<iframe[^>]+src="([^"]+)""#).unwrap());
+/// The player's arguments list the sample at each bitrate, `"bitrate":3000,"src":"\/\/cc3001..mp4"`
+static PLAYER_BITRATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""bitrate"\s*:\s*(\d+)\s*,\s*"src"\s*:\s*"([^"]+\.mp4)""#).unwrap());
+static PLAYER_SRC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""src"\s*:\s*"([^"]+\.mp4)""#).unwrap());
+
+/// `DMM_API_ID`, else the keychain's (`av secret set dmm-api-id`)
+fn api_id() -> Option<String> {
+    std::env::var("DMM_API_ID").ok().filter(|s| !s.is_empty()).or_else(|| keychain::get(Secret::DmmApiId))
 }
 
-fn env_affiliate_id() -> Option<String> {
-    std::env::var("DMM_AFFILIATE_ID").ok().filter(|s| !s.is_empty())
+fn affiliate_id() -> Option<String> {
+    std::env::var("DMM_AFFILIATE_ID").ok().filter(|s| !s.is_empty()).or_else(|| keychain::get(Secret::DmmAffiliateId))
 }
 
 pub fn dmm_enabled() -> bool {
-    env_api_id().is_some() && env_affiliate_id().is_some()
+    api_id().is_some() && affiliate_id().is_some()
 }
 
-pub async fn fetch_detail_from_dmm(code: &str) -> Result<Option<AvDetail>> {
+/// `(service, floor)` pairs from `[sources.dmm] floors`, in order
+fn floors() -> Vec<(String, String)> {
+    let cfg = &config::get().sources.dmm;
+    cfg.floors
+        .iter()
+        .map(|f| f.trim())
+        .filter(|f| !f.is_empty())
+        .map(|f| match f.split_once('/') {
+            Some((service, floor)) => (service.trim().to_string(), floor.trim().to_string()),
+            None => (cfg.service.trim().to_string(), f.to_string()),
+        })
+        .collect()
+}
+
+/// The first ItemList hit for `code`, `None` when DMM is disabled or has no match. The
+/// configured floors are asked in turn: VR titles sit with the regular ones on `videoa`,
+/// amateur ones on `videoc`, and rental-only releases on the rental service.
+async fn first_item(code: &str) -> Result<Option<Value>> {
     if !dmm_enabled() {
         return Ok(None);
     }
+    let mut answered = false;
+    let mut last_err = None;
+    for (service, floor) in floors() {
+        match floor_item(code, &service, &floor).await {
+            Ok(Some(item)) => return Ok(Some(item)),
+            Ok(None) => answered = true,
+            // One floor failing (a name the site doesn't have) shouldn't hide the others
+            Err(e) => {
+                util::debug(format!("DMM {}/{}: {:#}", service, floor, e));
+                last_err = Some(e);
+            }
+        }
+    }
+    match last_err {
+        Some(e) if !answered => Err(e),
+        _ => Ok(None),
+    }
+}
 
-    let api_id = env_api_id().unwrap();
-    let affiliate_id = env_affiliate_id().unwrap();
+async fn floor_item(code: &str, service: &str, floor: &str) -> Result<Option<Value>> {
+    let api_id = api_id().unwrap();
+    let affiliate_id = affiliate_id().unwrap();
 
     // Build ItemList API URL
     // See DMM Web Service docs; we search by keyword = code
@@ -30,17 +87,23 @@ pub async fn fetch_detail_from_dmm(code: &str) -> Result<Option<AvDetail>> {
     url.query_pairs_mut()
         .append_pair("api_id", &api_id)
         .append_pair("affiliate_id", &affiliate_id)
-        .append_pair("site", "DMM")
-        .append_pair("service", "digital")
-        .append_pair("floor", "videoa")
+        .append_pair("site", config::get().sources.dmm.site.trim())
+        .append_pair("service", service)
+        .append_pair("floor", floor)
         .append_pair("hits", "1")
         .append_pair("sort", "-date")
         .append_pair("keyword", code);
+    util::debug(format!("DMM: {}/{} {}", service, floor, code));
 
-    let resp_text = reqwest::Client::new()
-        .get(url)
-        .send()
-        .await
+    // Logged by name, not URL: the query carries the API keys
+    let resp = crate::http::with_retries("DMM API", || async {
+        let started = std::time::Instant::now();
+        let resp = crate::http::with_timeout(CLIENT.get(url.clone())).send().await;
+        metrics::record_fetch("dmm", started.elapsed(), FetchOutcome::of(&resp));
+        resp
+    })
+    .await;
+    let resp_text = resp
         .context("DMM request failed")?
         .error_for_status()
         .context("DMM non-success status")?
@@ -55,11 +118,177 @@ pub async fn fetch_detail_from_dmm(code: &str) -> Result<Option<AvDetail>> {
         .and_then(|x| x.as_array())
         .cloned()
         .unwrap_or_default();
-    if items.is_empty() {
-        return Ok(None);
+    Ok(items.into_iter().next())
+}
+
+/// One way to buy or rent a title, as the API's `prices.deliveries` lists them
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    /// `stream`, `download`, `hd`, `4k`, `iosdl`, `androiddl`, ...
+    pub kind: String,
+    /// In yen
+    pub price: Option<u32>,
+    /// Before the campaign discount, when there is one
+    pub list_price: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Campaign {
+    pub title: String,
+    pub date_begin: Option<String>,
+    pub date_end: Option<String>,
+}
+
+/// A title's prices on one floor
+#[derive(Debug, Clone, Serialize)]
+pub struct Prices {
+    pub code: String,
+    pub title: String,
+    pub url: Option<String>,
+    /// `digital/videoa`, `rental/rental_dvd`, ...
+    pub floor: String,
+    /// The service and floor as DMM names them (`動画 / ビデオ`)
+    pub floor_name: Option<String>,
+    /// The lowest delivery price (the API's `300~`)
+    pub price: Option<u32>,
+    pub list_price: Option<u32>,
+    pub deliveries: Vec<Delivery>,
+    pub campaigns: Vec<Campaign>,
+}
+
+/// `300~` / `1,980` → yen
+fn yen(v: Option<&Value>) -> Option<u32> {
+    let v = v?;
+    if let Some(n) = v.as_u64() {
+        return u32::try_from(n).ok();
+    }
+    let digits: String = v.as_str()?.chars().filter(|c| *c != ',').skip_while(|c| !c.is_ascii_digit()).take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+fn prices_of(code: &str, service: &str, floor: &str, it: &Value) -> Prices {
+    let s = |v: &Value, key: &str| v.get(key).and_then(Value::as_str).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let prices = it.get("prices");
+    let deliveries = prices
+        .and_then(|p| p.get("deliveries"))
+        .and_then(|d| d.get("delivery"))
+        .and_then(Value::as_array)
+        .map(|list| {
+            list.iter()
+                .map(|d| Delivery {
+                    kind: s(d, "type").unwrap_or_default(),
+                    price: yen(d.get("price")),
+                    list_price: yen(d.get("list_price")).filter(|l| Some(*l) != yen(d.get("price"))),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let campaigns = it
+        .get("campaign")
+        .and_then(Value::as_array)
+        .map(|list| {
+            list.iter()
+                .filter_map(|c| Some(Campaign { title: s(c, "title")?, date_begin: s(c, "date_begin"), date_end: s(c, "date_end") }))
+                .collect()
+        })
+        .unwrap_or_default();
+    let floor_name = match (s(it, "service_name"), s(it, "floor_name")) {
+        (Some(a), Some(b)) => Some(format!("{} / {}", a, b)),
+        (a, b) => a.or(b),
+    };
+    Prices {
+        code: code.to_string(),
+        title: s(it, "title").unwrap_or_default(),
+        url: s(it, "URL"),
+        floor: format!("{}/{}", service, floor),
+        floor_name,
+        price: prices.and_then(|p| yen(p.get("price"))),
+        list_price: prices.and_then(|p| yen(p.get("list_price"))),
+        deliveries,
+        campaigns,
     }
-    let it = &items[0];
+}
+
+/// `code`'s prices on every configured floor that sells or rents it. The keyword search
+/// is loose, so hits whose content id is another title are dropped.
+pub async fn prices(code: &str) -> Result<Vec<Prices>> {
+    if !dmm_enabled() {
+        anyhow::bail!("DMM 未启用，请设置 DMM_API_ID 与 DMM_AFFILIATE_ID（或用 av secret set 存入钥匙串）");
+    }
+    let code = code::normalize(code);
+    let mut out = Vec::new();
+    for (service, floor) in floors() {
+        match floor_item(&code, &service, &floor).await {
+            Ok(Some(it)) => {
+                let cid = it.get("content_id").and_then(Value::as_str).unwrap_or_default();
+                if code::parse(cid).is_some() && !code::same_release(cid, &code) {
+                    util::debug(format!("DMM {}/{}: {} is another title", service, floor, cid));
+                    continue;
+                }
+                out.push(prices_of(&code, &service, &floor, &it));
+            }
+            Ok(None) => {}
+            Err(e) => util::debug(format!("DMM {}/{}: {:#}", service, floor, e)),
+        }
+    }
+    Ok(out)
+}
+
+/// The largest of the `sampleMovieURL` player pages (`size_720_480`, `size_644_414`, ...)
+fn sample_player(it: &Value) -> Option<String> {
+    let sizes = it.get("sampleMovieURL")?.as_object()?;
+    sizes
+        .iter()
+        .filter_map(|(k, v)| Some((k.strip_prefix("size_")?.split('_').next()?.parse::<u32>().ok()?, v.as_str()?)))
+        .max_by_key(|(width, _)| *width)
+        .map(|(_, url)| url.to_string())
+}
+
+fn unescape_url(s: &str) -> String {
+    let s = s.replace("\\/", "/");
+    if s.starts_with("//") { format!("https:{}", s) } else { s }
+}
 
+/// The direct mp4 behind a sample player page, at the highest bitrate listed: the page
+/// frames DMM's HTML5 player, whose inline arguments carry the file URLs
+async fn resolve_sample(player: &str) -> Result<Option<String>> {
+    let mut headers = HeaderMap::new();
+    // Without it every dmm.co.jp page is the age check
+    headers.insert(COOKIE, HeaderValue::from_static("age_check_done=1"));
+    let page = crate::sanity::page_text(crate::scraper::send_from(&CLIENT, player, "https://www.dmm.co.jp/", headers.clone()).await?).await?;
+    let Some(frame) = PLAYER_FRAME.captures(&page).map(|c| unescape_url(&c[1].replace("&amp;", "&"))) else { return Ok(None) };
+    util::debug(format!("DMM sample player: {}", frame));
+    let body = crate::sanity::page_text(crate::scraper::send_from(&CLIENT, &frame, player, headers).await?).await?;
+    let best = PLAYER_BITRATE
+        .captures_iter(&body)
+        .filter_map(|c| Some((c[1].parse::<u32>().ok()?, c[2].to_string())))
+        .max_by_key(|(bitrate, _)| *bitrate)
+        .map(|(_, src)| src)
+        .or_else(|| PLAYER_SRC.captures(&body).map(|c| c[1].to_string()));
+    Ok(best.map(|s| unescape_url(&s)))
+}
+
+/// The product page on DMM for `code`
+pub async fn detail_url(code: &str) -> Result<Option<String>> {
+    Ok(first_item(code).await?.and_then(|it| it.get("URL").and_then(|u| u.as_str()).map(|s| s.to_string())))
+}
+
+pub async fn fetch_detail_from_dmm(code: &str) -> Result<Option<AvDetail>> {
+    let Some(item) = first_item(code).await? else { return Ok(None) };
+    // Sample movie: the API only links DMM's embed player, so the mp4 is read out of the
+    // player
+    let sample_video_url = match sample_player(&item) {
+        Some(p) => resolve_sample(&p).await.unwrap_or_else(|e| {
+            util::debug(format!("DMM sample player {}: {:#}", p, e));
+            None
+        }),
+        None => None,
+    };
+    Ok(Some(detail_from_item(code, &item, sample_video_url)))
+}
+
+/// An API item as a detail; `sample_video_url` is the mp4 read out of its sample player
+pub(crate) fn detail_from_item(code: &str, it: &Value, sample_video_url: Option<String>) -> AvDetail {
     // Helper closures for safe extraction
     let pick_string = |obj: &Value, path: &[&str]| -> Option<String> {
         let mut cur = obj;
@@ -95,13 +324,15 @@ pub async fn fetch_detail_from_dmm(code: &str) -> Result<Option<AvDetail>> {
             }
         }
     }
-    let director = it.get("iteminfo").and_then(|x| x.get("director")).and_then(|x| x.as_array()).and_then(|arr| arr.get(0)).and_then(|d| pick_string(d, &["name"]));
-    let studio = it.get("iteminfo").and_then(|x| x.get("maker")).and_then(|x| x.as_array()).and_then(|arr| arr.get(0)).and_then(|d| pick_string(d, &["name"]));
-    let label = it.get("iteminfo").and_then(|x| x.get("label")).and_then(|x| x.as_array()).and_then(|arr| arr.get(0)).and_then(|d| pick_string(d, &["name"]));
-    let series = it.get("iteminfo").and_then(|x| x.get("series")).and_then(|x| x.as_array()).and_then(|arr| arr.get(0)).and_then(|d| pick_string(d, &["name"]));
+    let director = it.get("iteminfo").and_then(|x| x.get("director")).and_then(|x| x.as_array()).and_then(|arr| arr.first()).and_then(|d| pick_string(d, &["name"]));
+    let studio = it.get("iteminfo").and_then(|x| x.get("maker")).and_then(|x| x.as_array()).and_then(|arr| arr.first()).and_then(|d| pick_string(d, &["name"]));
+    let label = it.get("iteminfo").and_then(|x| x.get("label")).and_then(|x| x.as_array()).and_then(|arr| arr.first()).and_then(|d| pick_string(d, &["name"]));
+    let series = it.get("iteminfo").and_then(|x| x.get("series")).and_then(|x| x.as_array()).and_then(|arr| arr.first()).and_then(|d| pick_string(d, &["name"]));
 
     // Rating (average)
     let rating = pick_string(it, &["review", "average"]).and_then(|s| s.parse::<f32>().ok());
+    let votes = it.get("review").and_then(|r| r.get("count")).and_then(Value::as_u64).and_then(|n| u32::try_from(n).ok());
+    let ratings = rating.and_then(|r| rating::from_source("dmm", r, rating::SCALE, votes)).into_iter().collect();
 
     // Preview images (sample)
     let mut preview_images: Vec<String> = Vec::new();
@@ -113,10 +344,22 @@ pub async fn fetch_detail_from_dmm(code: &str) -> Result<Option<AvDetail>> {
         }
     }
 
+    // Failing the player's mp4, the sample is guessed under litevideo/freepv (laid out by
+    // the content id's first one and three characters)
+    let player = sample_player(it);
+    let trailer_url = sample_video_url
+        .clone()
+        .or_else(|| {
+            pick_string(it, &["content_id"])
+                .filter(|cid| cid.len() >= 3 && cid.is_ascii())
+                .map(|cid| format!("https://cc3001.dmm.co.jp/litevideo/freepv/{}/{}/{}/{}_dmb_w.mp4", &cid[..1], &cid[..3], cid, cid))
+        })
+        .or(player);
+
     // Code: DMM may not echo vendor code. Fall back to the provided code.
     let code_upper = code.to_uppercase();
 
-    let detail = AvDetail {
+    AvDetail {
         code: code_upper,
         title,
         actor_names,
@@ -129,13 +372,17 @@ pub async fn fetch_detail_from_dmm(code: &str) -> Result<Option<AvDetail>> {
         label,
         series,
         genres,
+        genre_ids: Vec::new(),
         rating,
+        ratings,
+        popularity: None,
         preview_images,
+        trailer_url,
+        sample_video_url,
+        amateur: false,
         magnet_infos: Vec::new(),
         magnets: Vec::new(),
-    };
-
-    Ok(Some(detail))
+    }
 }
 
 
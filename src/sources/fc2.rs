@@ -0,0 +1,178 @@
+//! The FC2 Contents Market, where amateur titles are listed per seller rather than per
+//! studio: a seller's article list pages, and the article pages for the release dates and
+//! prices the list's cards leave out.
+//!
+//! `AV_FC2_BASE` points it at a mirror.
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::Semaphore;
+
+use crate::cancel;
+use crate::code::Code;
+use crate::selectors;
+use crate::sources::{absolute, text};
+use crate::util;
+
+const DEFAULT_BASE: &str = "https://adult.contents.fc2.com";
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    crate::scraper::client_builder().cookie_provider(crate::cookies::provider()).build().expect("client build")
+});
+
+static ARTICLE_ID: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/article/(\d{5,8})").unwrap());
+static SELLER_ID: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/users/([A-Za-z0-9_-]+)").unwrap());
+static DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{4})[/.-](\d{1,2})[/.-](\d{1,2})").unwrap());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Article {
+    /// `FC2-PPV-<article id>`
+    pub code: String,
+    pub title: String,
+    pub release_date: Option<String>,
+    /// In points (1 pt = 1 yen)
+    pub price: Option<u32>,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Seller {
+    pub id: String,
+    pub name: Option<String>,
+    pub url: String,
+    /// Newest first
+    pub articles: Vec<Article>,
+}
+
+pub fn base() -> String {
+    std::env::var("AV_FC2_BASE")
+        .ok()
+        .filter(|b| !b.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_BASE.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// A seller id as given, or taken from a seller page URL
+pub fn seller_id(input: &str) -> Result<String> {
+    let input = input.trim();
+    if let Some(c) = SELLER_ID.captures(input) {
+        return Ok(c[1].to_string());
+    }
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Ok(input.to_string());
+    }
+    bail!("无效的 FC2 卖家: {}（填写卖家 ID，或 {}/users/<ID>/ 形式的链接）", input, base())
+}
+
+fn seller_url(id: &str) -> String {
+    format!("{}/users/{}/", base(), id)
+}
+
+fn field(el: scraper::ElementRef, key: &str) -> Option<String> {
+    el.select(selectors::fc2(key)).next().map(text).filter(|t| !t.is_empty())
+}
+
+/// `2024/01/05` anywhere in `s` (`販売日 : 2024/01/05`), as `YYYY-MM-DD`
+fn date_in(s: &str) -> Option<String> {
+    let c = DATE.captures(s)?;
+    let date = chrono::NaiveDate::from_ymd_opt(c[1].parse().ok()?, c[2].parse().ok()?, c[3].parse().ok()?)?;
+    Some(date.format("%Y-%m-%d").to_string())
+}
+
+/// `1,500pt` / `¥1,500` → 1500
+fn price_in(s: &str) -> Option<u32> {
+    let digits: String = s.chars().skip_while(|c| !c.is_ascii_digit()).take_while(|c| c.is_ascii_digit() || *c == ',').filter(|c| *c != ',').collect();
+    digits.parse().ok()
+}
+
+/// The seller's name, when the page shows one, and the page's article cards
+fn parse_list(body: &str) -> (Option<String>, Vec<Article>) {
+    let doc = scraper::Html::parse_document(body);
+    let name = doc.select(selectors::fc2("seller_name")).next().map(|n| n.text().collect::<String>().trim().to_string()).filter(|n| !n.is_empty());
+    let mut out = Vec::new();
+    for card in doc.select(selectors::fc2("article_card")) {
+        let Some(href) = card.select(selectors::fc2("article_link")).find_map(|a| a.value().attr("href")) else { continue };
+        let Some(id) = ARTICLE_ID.captures(href).map(|c| c[1].to_string()) else { continue };
+        let thumbnail_url = card
+            .select(selectors::fc2("article_thumbnail"))
+            .next()
+            .and_then(|img| img.value().attr("data-src").or(img.value().attr("src")))
+            .map(|src| absolute(&base(), src));
+        out.push(Article {
+            code: Code::Fc2(id.clone()).to_string(),
+            title: field(card, "article_title").unwrap_or_default(),
+            release_date: field(card, "article_date").as_deref().and_then(date_in),
+            price: field(card, "article_price").as_deref().and_then(price_in),
+            url: format!("{}/article/{}/", base(), id),
+            thumbnail_url,
+        });
+    }
+    (name, out)
+}
+
+/// Release date and price from the article page
+async fn fill_from_article(a: &mut Article) -> Result<()> {
+    let body = crate::scraper::get_text(&CLIENT, &a.url).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let root = doc.root_element();
+    if a.release_date.is_none() {
+        a.release_date = field(root, "release_date").as_deref().and_then(date_in);
+    }
+    if a.price.is_none() {
+        a.price = field(root, "price").as_deref().and_then(price_in);
+    }
+    Ok(())
+}
+
+/// Up to `pages` pages of the seller's articles, newest first
+pub async fn seller(id: &str, pages: u32) -> Result<Seller> {
+    let mut name = None;
+    let mut articles: Vec<Article> = Vec::new();
+    for page in 1..=pages.max(1) {
+        cancel::check()?;
+        let url = format!("{}/users/{}/articles?sort=date&order=desc&page={}", base(), id, page);
+        util::debug(format!("FC2 seller page: {}", url));
+        let (page_name, cards) = parse_list(&crate::scraper::get_text(&CLIENT, &url).await?);
+        name = name.or(page_name);
+        let before = articles.len();
+        for a in cards {
+            if !articles.iter().any(|o| o.code == a.code) {
+                articles.push(a);
+            }
+        }
+        // Past the last page FC2 repeats the last one or shows none
+        if articles.len() == before {
+            break;
+        }
+    }
+    if articles.is_empty() {
+        bail!("没有找到 FC2 卖家 {} 的作品（卖家不存在，或页面结构有变，可用 [selectors.fc2] 覆盖选择器）", id);
+    }
+
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, mut a) in articles.into_iter().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            if a.release_date.is_none() || a.price.is_none() {
+                let _permit = permits.acquire_owned().await;
+                if let Err(e) = fill_from_article(&mut a).await {
+                    util::debug(format!("FC2 article {}: {:#}", a.url, e));
+                }
+            }
+            (idx, a)
+        });
+    }
+    let mut filled = tasks.join_all().await;
+    cancel::check()?;
+    filled.sort_by_key(|(idx, _)| *idx);
+    let mut articles: Vec<Article> = filled.into_iter().map(|(_, a)| a).collect();
+    // Undated ones keep their place in the list's order, after the dated
+    articles.sort_by(|a, b| b.release_date.is_some().cmp(&a.release_date.is_some()).then_with(|| b.release_date.cmp(&a.release_date)));
+    Ok(Seller { id: id.to_string(), name, url: seller_url(id), articles })
+}
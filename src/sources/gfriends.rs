@@ -0,0 +1,130 @@
+//! gfriends, the community repository of actress portraits on GitHub. Its `Filetree.json`
+//! lists every picture by company folder; the index built from it is kept in the data
+//! directory and fetched again only once it is `[sources.gfriends] max_age_days` old, so
+//! lookups work offline and a failed refresh falls back to the copy on disk.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use urlencoding::encode;
+
+use crate::config;
+use crate::store;
+use crate::util;
+
+const STORE: &str = "gfriends";
+
+/// Marks the upscaled copy gfriends keeps of a low-resolution picture
+const AI_FIX: &str = "AI-Fix-";
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::scraper::client_builder().build().expect("client build"));
+
+/// The index once loaded, for the rest of the run
+static LOADED: Mutex<Option<Arc<Index>>> = Mutex::new(None);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    pub fetched_at: u64,
+    /// Lookup key (see `key`) → `Company/File.jpg?t=…`, already URL-encoded
+    pub avatars: BTreeMap<String, String>,
+}
+
+fn root() -> String {
+    config::get().sources.gfriends.url.trim().trim_end_matches('/').to_string()
+}
+
+/// Names are matched without their spacing, which the repository and the sites disagree on
+fn key(name: &str) -> String {
+    name.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// `{"Content": {company: {"Name.jpg": "Name.jpg?t=…"}}}` → one picture per name; the first
+/// company folder listing a name wins, unless a later one has its AI-Fix copy
+fn parse(tree: &Value) -> BTreeMap<String, String> {
+    let mut avatars = BTreeMap::new();
+    let mut fixed = std::collections::HashSet::new();
+    let Some(companies) = tree.get("Content").and_then(Value::as_object) else { return avatars };
+    for (company, files) in companies {
+        let Some(files) = files.as_object() else { continue };
+        for (file, stamped) in files {
+            let stem = file.rsplit_once('.').map_or(file.as_str(), |(stem, _)| stem);
+            let (name, is_fix) = match stem.strip_prefix(AI_FIX) {
+                Some(name) => (name, true),
+                None => (stem, false),
+            };
+            let k = key(name);
+            if k.is_empty() || fixed.contains(&k) || (!is_fix && avatars.contains_key(&k)) {
+                continue;
+            }
+            let query = stamped.as_str().and_then(|s| s.split_once('?')).map(|(_, q)| format!("?{}", q)).unwrap_or_default();
+            avatars.insert(k.clone(), format!("{}/{}{}", encode(company), encode(file), query));
+            if is_fix {
+                fixed.insert(k);
+            }
+        }
+    }
+    avatars
+}
+
+async fn download() -> Result<Index> {
+    let url = format!("{}/Filetree.json", root());
+    util::debug(format!("gfriends: {}", url));
+    let body = crate::scraper::get_text_from(&CLIENT, &url, "https://github.com/").await?;
+    let tree: Value = serde_json::from_str(&body).context("gfriends 的 Filetree.json 不是有效的 JSON")?;
+    let avatars = parse(&tree);
+    if avatars.is_empty() {
+        bail!("gfriends 的 Filetree.json 中没有任何头像（格式有变？）");
+    }
+    Ok(Index { fetched_at: util::now_secs(), avatars })
+}
+
+/// The index on disk when it is fresh enough, else a new download (`refresh` forces one);
+/// a failed download falls back to the stale copy
+pub async fn index(refresh: bool) -> Result<Arc<Index>> {
+    if !refresh {
+        if let Some(loaded) = LOADED.lock().unwrap().clone() {
+            return Ok(loaded);
+        }
+    }
+    let cached: Index = store::load(STORE).unwrap_or_else(|e| {
+        util::debug(format!("gfriends: {:#}", e));
+        Index::default()
+    });
+    let max_age = config::get().sources.gfriends.max_age_days * 86_400;
+    let fresh = !cached.avatars.is_empty() && util::now_secs().saturating_sub(cached.fetched_at) < max_age;
+    let index = if fresh && !refresh {
+        cached
+    } else {
+        match download().await {
+            Ok(index) => {
+                if let Err(e) = store::save(STORE, &index) {
+                    eprintln!("[WARN] gfriends 索引保存失败: {:#}", e);
+                }
+                index
+            }
+            Err(e) if !cached.avatars.is_empty() => {
+                eprintln!("[WARN] gfriends 索引更新失败，使用本地缓存: {:#}", e);
+                cached
+            }
+            Err(e) => return Err(e.context("下载 gfriends 头像索引失败")),
+        }
+    };
+    let index = Arc::new(index);
+    *LOADED.lock().unwrap() = Some(index.clone());
+    Ok(index)
+}
+
+impl Index {
+    /// The portrait's URL under the repository root, for `name` as any site spells it
+    pub fn avatar_url(&self, name: &str) -> Option<String> {
+        self.avatars.get(&key(name)).map(|path| format!("{}/Content/{}", root(), path))
+    }
+}
+
+/// The portrait itself
+pub async fn avatar(url: &str) -> Result<Vec<u8>> {
+    crate::scraper::get_bytes(&CLIENT, url).await
+}
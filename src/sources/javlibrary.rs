@@ -1,115 +1,228 @@
 use anyhow::Result;
 use regex::Regex;
-use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
 use scraper::{Html, Selector};
+use std::sync::LazyLock;
 
-use crate::types::AvDetail;
+use crate::config::MetadataLang;
+use crate::rating;
+use crate::sanity;
+use crate::types::{AvDetail, Popularity, Review};
 use crate::util;
 
-const UA: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125 Safari/537.36";
-
-fn client() -> reqwest::Client {
-    let mut headers = HeaderMap::new();
-    headers.insert(USER_AGENT, HeaderValue::from_static(UA));
-    reqwest::Client::builder()
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    let headers = crate::ua::navigation_headers(crate::ua::session_profile());
+    crate::http::with_proxy(reqwest::Client::builder())
         .default_headers(headers)
-        .cookie_store(true)
+        .cookie_provider(crate::cookies::provider())
         .build()
         .expect("client build")
+});
+
+fn client() -> reqwest::Client {
+    CLIENT.clone()
 }
 
-pub async fn fetch_detail_from_javlibrary(code: &str) -> Result<Option<AvDetail>> {
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d[\d,]*").unwrap());
+
+static MINUTES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{2,3})").unwrap());
+
+/// The search, detail and review pages' selectors, parsed once
+struct Selectors {
+    result_link: Selector,
+    title: Selector,
+    id: Selector,
+    date: Selector,
+    jacket: Selector,
+    cast: Selector,
+    maker: Selector,
+    label: Selector,
+    series: Selector,
+    length: Selector,
+    genres: Selector,
+    wanted: Selector,
+    watched: Selector,
+    owned: Selector,
+    review_count: Selector,
+    score: Selector,
+    review: Selector,
+    review_text: Selector,
+    review_user: Selector,
+    review_date: Selector,
+}
+
+static SEL: LazyLock<Selectors> = LazyLock::new(|| {
+    let s = |css: &str| Selector::parse(css).unwrap();
+    Selectors {
+        result_link: s(".video a[href*='?v=']"),
+        title: s("#video_title"),
+        id: s("#video_id .text"),
+        date: s("#video_date .text"),
+        jacket: s("#video_jacket_img"),
+        cast: s("#video_cast .star a"),
+        maker: s("#video_maker .text a"),
+        label: s("#video_label .text a"),
+        series: s("#video_series .text a"),
+        length: s("#video_length .text"),
+        genres: s("#video_genres .genre a"),
+        wanted: s("#subscribed a, a[href*='userswanted.php']"),
+        watched: s("#watched a, a[href*='userswatched.php']"),
+        owned: s("#owned a, a[href*='usersowned.php']"),
+        review_count: s("a[href*='videoreviews.php']"),
+        score: s("#video_review .score"),
+        review: s("table.review"),
+        review_text: s(".text"),
+        review_user: s(".userid"),
+        review_date: s(".date"),
+    }
+});
+
+fn first_number(text: &str) -> Option<u32> {
+    NUMBER.find(text)?.as_str().replace(',', "").parse().ok()
+}
+
+/// Site locales to try, the preferred language's first
+fn locales() -> [&'static str; 3] {
+    match util::lang() {
+        Some(MetadataLang::Zh) => ["cn", "en", "ja"],
+        Some(MetadataLang::Ja) => ["ja", "en", "cn"],
+        Some(MetadataLang::En) | None => ["en", "cn", "ja"],
+    }
+}
+
+/// The detail page of `code`'s first search hit, `None` when JavLibrary has no entry
+pub async fn detail_url(code: &str) -> Result<Option<String>> {
     let c = client();
     // Try multiple locales for better hit rate
-    let locales = ["en", "cn", "ja"];
     let mut body = String::new();
-    let mut found = false;
-    for loc in &locales {
+    let mut found = None;
+    for loc in locales() {
         let url = format!("https://www.javlibrary.com/{}/vl_searchbyid.php?keyword={}", loc, code);
         util::debug(format!("JavLibrary search: {}", url));
-        let resp = c.get(&url).send().await?;
+        let resp = crate::scraper::send(&c, &url).await?;
         if resp.status().is_success() {
             body = resp.text().await?;
-            found = true;
+            found = Some(loc);
             break;
         }
     }
-    if !found { return Ok(None); }
-    let doc = Html::parse_document(&body);
-    let first_link = doc
-        .select(&Selector::parse(".video a[href*='?v=']").unwrap())
+    let Some(locale) = found else { return Ok(None) };
+    let first_link = Html::parse_document(&body)
+        .select(&SEL.result_link)
         .next()
         .and_then(|a| a.value().attr("href"))
         .map(|s| s.to_string());
     let href = match first_link { Some(h) => h, None => return Ok(None) };
-    let detail_url = if href.starts_with("http") { href } else { format!("https://www.javlibrary.com/en/{}", href.trim_start_matches('/')) };
+    Ok(Some(if href.starts_with("http") { href } else { format!("https://www.javlibrary.com/{}/{}", locale, href.trim_start_matches('/')) }))
+}
+
+pub async fn fetch_detail_from_javlibrary(code: &str) -> Result<Option<AvDetail>> {
+    let c = client();
+    let Some(detail_url) = detail_url(code).await? else { return Ok(None) };
     util::debug(format!("JavLibrary detail: {}", detail_url));
 
-    let body = c.get(&detail_url).send().await?.error_for_status()?.text().await?;
-    let doc = Html::parse_document(&body);
+    let body = crate::scraper::get_text(&c, &detail_url).await?;
+    parse_detail_page(&body, &detail_url, code).map(Some)
+}
+
+/// A detail page's fields; `code` stands in when the page shows none, `url` only goes into
+/// the error for a page that isn't one
+pub(crate) fn parse_detail_page(body: &str, url: &str, code: &str) -> Result<AvDetail> {
+    let doc = Html::parse_document(body);
 
     let title = doc
-        .select(&Selector::parse("#video_title").unwrap())
+        .select(&SEL.title)
         .next()
         .map(|n| n.text().collect::<String>().trim().to_string())
         .unwrap_or_default();
+    if title.is_empty() {
+        return Err(sanity::unexpected(url, "页面中没有 #video_title，不是详情页").into());
+    }
 
     let code_text = doc
-        .select(&Selector::parse("#video_id .text").unwrap())
+        .select(&SEL.id)
         .next()
         .map(|n| n.text().collect::<String>().trim().to_string())
         .unwrap_or_else(|| code.to_uppercase());
 
     let date = doc
-        .select(&Selector::parse("#video_date .text").unwrap())
+        .select(&SEL.date)
         .next()
         .map(|n| n.text().collect::<String>().trim().to_string());
 
     let cover_url = doc
-        .select(&Selector::parse("#video_jacket_img").unwrap())
+        .select(&SEL.jacket)
         .next()
         .and_then(|n| n.value().attr("src"))
         .map(|s| s.to_string());
 
     let actor_names = doc
-        .select(&Selector::parse("#video_cast .star a").unwrap())
+        .select(&SEL.cast)
         .map(|n| n.text().collect::<String>().trim().to_string())
         .filter(|s| !s.is_empty())
         .collect::<Vec<_>>();
 
     let studio = doc
-        .select(&Selector::parse("#video_maker .text a").unwrap())
+        .select(&SEL.maker)
         .next()
         .map(|n| n.text().collect::<String>().trim().to_string());
 
     let label = doc
-        .select(&Selector::parse("#video_label .text a").unwrap())
+        .select(&SEL.label)
         .next()
         .map(|n| n.text().collect::<String>().trim().to_string());
 
     let series = doc
-        .select(&Selector::parse("#video_series .text a").unwrap())
+        .select(&SEL.series)
         .next()
         .map(|n| n.text().collect::<String>().trim().to_string());
 
     let mut duration_minutes = None;
     if let Some(t) = doc
-        .select(&Selector::parse("#video_length .text").unwrap())
+        .select(&SEL.length)
         .next()
         .map(|n| n.text().collect::<String>())
     {
-        if let Some(cap) = Regex::new(r"(\d{2,3})").unwrap().captures(&t) {
+        if let Some(cap) = MINUTES.captures(&t) {
             duration_minutes = cap.get(1).and_then(|m| m.as_str().parse::<u32>().ok());
         }
     }
 
     // Genres
     let genres = doc
-        .select(&Selector::parse("#video_genres .genre a").unwrap())
+        .select(&SEL.genres)
         .map(|n| n.text().collect::<String>().trim().to_string())
         .collect::<Vec<_>>();
 
-    Ok(Some(AvDetail {
+    // User counters: "123 users want this" and the like, each number linking to the user list,
+    // plus the review count on the reviews tab
+    let counter = |sel: &Selector| {
+        doc.select(sel)
+            .next()
+            .and_then(|n| first_number(&n.text().collect::<String>()))
+    };
+    let popularity = Popularity {
+        source: "javlibrary".to_string(),
+        wanted: counter(&SEL.wanted),
+        watched: counter(&SEL.watched),
+        owned: counter(&SEL.owned),
+        reviews: counter(&SEL.review_count),
+    };
+    let popularity = [popularity.wanted, popularity.watched, popularity.owned, popularity.reviews]
+        .iter()
+        .any(Option::is_some)
+        .then_some(popularity);
+
+    // User score, shown as "(7.80)" out of 10; its votes are the written reviews
+    let ratings = doc
+        .select(&SEL.score)
+        .next()
+        .map(|n| n.text().collect::<String>())
+        .and_then(|t| t.trim().trim_matches(|c| c == '(' || c == ')').parse::<f32>().ok())
+        .and_then(|r| rating::from_source("javlibrary", r, 10.0, popularity.as_ref().and_then(|p| p.reviews)))
+        .into_iter()
+        .collect();
+
+    Ok(AvDetail {
         code: code_text,
         title,
         actor_names,
@@ -122,11 +235,48 @@ pub async fn fetch_detail_from_javlibrary(code: &str) -> Result<Option<AvDetail>
         label,
         series,
         genres,
+        genre_ids: Vec::new(),
         rating: None,
+        ratings,
+        popularity,
         preview_images: Vec::new(),
+        trailer_url: None,
+        sample_video_url: None,
+        amateur: false,
         magnet_infos: Vec::new(),
         magnets: Vec::new(),
-    }))
+    })
 }
 
+/// Text of the first `sel` match below `el`, whitespace collapsed
+fn text_of(el: scraper::ElementRef, sel: &Selector) -> Option<String> {
+    let t = el.select(sel).next()?.text().collect::<Vec<_>>().join(" ");
+    let t = t.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!t.is_empty()).then_some(t)
+}
 
+/// Up to `limit` user reviews of `code`, newest first; empty when JavLibrary has no entry
+pub async fn reviews(code: &str, limit: usize) -> Result<Vec<Review>> {
+    let Some(detail_url) = detail_url(code).await? else { return Ok(Vec::new()) };
+    // mode=2 lists the newest reviews first
+    let url = format!("{}&mode=2", detail_url.replacen("?v=", "videoreviews.php?v=", 1));
+    util::debug(format!("JavLibrary reviews: {}", url));
+    let body = crate::scraper::get_text(&client(), &url).await?;
+    let doc = Html::parse_document(&body);
+    let out = doc
+        .select(&SEL.review)
+        .filter_map(|row| {
+            let text = text_of(row, &SEL.review_text)?;
+            Some(Review {
+                source: "javlibrary".to_string(),
+                author: text_of(row, &SEL.review_user).unwrap_or_default(),
+                date: text_of(row, &SEL.review_date),
+                score: None,
+                text,
+                translation: None,
+            })
+        })
+        .take(limit)
+        .collect();
+    Ok(out)
+}
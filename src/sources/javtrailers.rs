@@ -0,0 +1,69 @@
+//! JavTrailers (javtrailers.com), which mirrors the studios' official trailers outside
+//! DMM's region lock. The trailer command's second provider: asked when DMM has no sample
+//! or its file can't be reached from here.
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use std::sync::LazyLock;
+use urlencoding::encode;
+
+use crate::code;
+use crate::selectors;
+use crate::sources::{absolute, text};
+use crate::util;
+
+const BASE: &str = "https://javtrailers.com";
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::scraper::client_builder().build().expect("client build"));
+
+/// Stream URLs anywhere in the page, the player's inline state included (`\/` or `/`
+/// escaped there)
+static STREAM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"https?:(?:\\?/|\\u002F){2}[^"'\s<>]+?\.(?:m3u8|mp4)"#).unwrap());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Trailer {
+    pub code: String,
+    /// An mp4, or an HLS playlist (ffmpeg downloads either)
+    pub url: String,
+    /// The title's page on JavTrailers
+    pub page: String,
+}
+
+pub fn search_url(code: &str) -> String {
+    format!("{}/search/{}", BASE, encode(code))
+}
+
+/// The title page among the search results whose card names `code`
+async fn video_url(code: &str) -> Result<Option<String>> {
+    let body = crate::scraper::get_text(&CLIENT, &search_url(code)).await?;
+    let doc = scraper::Html::parse_document(&body);
+    Ok(doc
+        .select(selectors::javtrailers("result"))
+        .filter(|card| code::find(&text(*card)).is_some_and(|c| code::same_release(&c.to_string(), code)))
+        .find_map(|card| card.value().attr("href").map(|h| absolute(BASE, h))))
+}
+
+/// The page's trailer streams, ones off DMM's (region-locked) hosts first
+fn streams(body: &str) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    for m in STREAM.find_iter(body) {
+        let url = m.as_str().replace("\\u002F", "/").replace("\\/", "/");
+        if !found.contains(&url) {
+            found.push(url);
+        }
+    }
+    found.sort_by_key(|u| u.contains("dmm.co.jp"));
+    found
+}
+
+/// `code`'s trailer; `Ok(None)` when JavTrailers doesn't list the title or has no video for it
+pub async fn trailer(code: &str) -> Result<Option<Trailer>> {
+    let code = code::normalize(code);
+    let Some(page) = video_url(&code).await? else { return Ok(None) };
+    util::debug(format!("JavTrailers: {}", page));
+    let body = crate::scraper::get_text(&CLIENT, &page).await?;
+    Ok(streams(&body).into_iter().next().map(|url| Trailer { code, url, page }))
+}
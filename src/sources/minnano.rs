@@ -0,0 +1,72 @@
+use anyhow::Result;
+use regex::Regex;
+use scraper::{Html, Selector};
+use std::sync::LazyLock;
+
+use crate::types::ActressProfile;
+use crate::util;
+
+const BASE: &str = "https://www.minnano-av.com";
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    let headers = crate::ua::navigation_headers(crate::ua::session_profile());
+    crate::http::with_proxy(reqwest::Client::builder())
+        .default_headers(headers)
+        .cookie_provider(crate::cookies::provider())
+        .build()
+        .expect("client build")
+});
+
+static HEIGHT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"T\s*(\d{3})").unwrap());
+static SIZES: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"B\s*(\d{2,3})\s*[(（]?\s*([A-Oa-o])?\s*(?:カップ)?\s*[)）]?\s*/?\s*W\s*(\d{2,3})\s*/?\s*H\s*(\d{2,3})").unwrap()
+});
+static BIRTH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{4})年\s*(\d{1,2})月\s*(\d{1,2})日").unwrap());
+static DEBUT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"デビュー[^\d]{0,12}(\d{4})|(\d{4})年[^\d]{0,8}デビュー").unwrap());
+
+static ROW: LazyLock<Selector> = LazyLock::new(|| Selector::parse("table.tbllist tr, .act-list li, .actress-list li").unwrap());
+static ACTRESS_LINK: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a[href*='actress']").unwrap());
+
+/// Pull the profile fields out of a result row's text, e.g.
+/// `1998年06月16日 T159 / B83(Eカップ) / W57 / H85 2017年デビュー`
+fn parse_profile(name: String, url: Option<String>, text: &str) -> ActressProfile {
+    let sizes = SIZES.captures(text);
+    let size = |i: usize| sizes.as_ref().and_then(|c| c.get(i)).and_then(|m| m.as_str().parse::<u16>().ok());
+    ActressProfile {
+        name,
+        url,
+        birth_date: BIRTH.captures(text).map(|c| format!("{}-{:0>2}-{:0>2}", &c[1], &c[2], &c[3])),
+        height_cm: HEIGHT.captures(text).and_then(|c| c[1].parse().ok()),
+        bust: size(1),
+        waist: size(3),
+        hip: size(4),
+        cup: sizes.as_ref().and_then(|c| c.get(2)).map(|m| m.as_str().to_ascii_uppercase()),
+        debut_year: DEBUT
+            .captures(text)
+            .and_then(|c| c.get(1).or_else(|| c.get(2)))
+            .and_then(|m| m.as_str().parse().ok()),
+    }
+}
+
+/// One page of minnano-av's actress list (newest debuts first), with each row's profile
+pub async fn actresses(page: u32) -> Result<Vec<ActressProfile>> {
+    let url = format!("{}/actress_list.php?page={}", BASE, page);
+    util::debug(format!("minnano-av actresses: {}", url));
+    let body = crate::scraper::get_text(&CLIENT, &url).await?;
+    let doc = Html::parse_document(&body);
+    let mut out: Vec<ActressProfile> = Vec::new();
+    for row in doc.select(&ROW) {
+        let Some(a) = row.select(&ACTRESS_LINK).find(|a| !a.text().collect::<String>().trim().is_empty()) else { continue };
+        let name = a.text().collect::<String>().trim().to_string();
+        if out.iter().any(|p| p.name == name) {
+            continue;
+        }
+        let href = a.value().attr("href").map(|h| {
+            if h.starts_with("http") { h.to_string() } else { format!("{}/{}", BASE, h.trim_start_matches('/')) }
+        });
+        let text = row.text().collect::<Vec<_>>().join(" ");
+        out.push(parse_profile(name, href, &text));
+    }
+    Ok(out)
+}
@@ -0,0 +1,135 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::LazyLock;
+
+use crate::code;
+use crate::magnet;
+use crate::sanity::FetchError;
+use crate::types::{ActorItem, ActressProfile, AvDetail, AvItem, RankingPage, Review};
+
+/// Offline stand-in for every site, backed by the bundled `mock.json` (demos, CI, screenshots)
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Deserialize)]
+struct Fixtures {
+    details: Vec<AvDetail>,
+    actors: Vec<ActorItem>,
+    actresses: Vec<ActressProfile>,
+    #[serde(default)]
+    reviews: Vec<MockReview>,
+}
+
+#[derive(Deserialize)]
+struct MockReview {
+    code: String,
+    #[serde(flatten)]
+    review: Review,
+}
+
+static FIXTURES: LazyLock<Fixtures> = LazyLock::new(|| {
+    let mut f: Fixtures = serde_json::from_str(include_str!("mock.json")).expect("bundled mock.json is valid");
+    // Fill in the parsed magnet fields the way the real scrapers do
+    for m in f.details.iter_mut().flat_map(|d| d.magnet_infos.iter_mut()) {
+        if let Some(parsed) = magnet::info(&m.url) {
+            m.infohash = parsed.infohash;
+            m.display_name = parsed.display_name;
+            m.trackers = parsed.trackers;
+        }
+    }
+    f
+});
+
+pub fn set_enabled(on: bool) {
+    ENABLED.store(on, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn detail(code: &str) -> Result<AvDetail> {
+    let code = code::normalize(code);
+    FIXTURES
+        .details
+        .iter()
+        .find(|d| d.code == code)
+        .cloned()
+        .ok_or_else(|| FetchError::not_found("mock", format!("模拟数据中没有 {}（可用: DEMO-001..003, SMPL-101..102）", code)).into())
+}
+
+pub fn search(query: &str) -> Vec<AvItem> {
+    let q = query.trim().to_lowercase();
+    FIXTURES
+        .details
+        .iter()
+        .filter(|d| {
+            d.code.to_lowercase().contains(&q)
+                || d.title.to_lowercase().contains(&q)
+                || d.actor_names.iter().any(|a| a.to_lowercase().contains(&q))
+        })
+        .map(AvItem::from)
+        .collect()
+}
+
+pub fn list_actor(actor: &str) -> Vec<AvItem> {
+    let a = actor.trim().to_lowercase();
+    FIXTURES
+        .details
+        .iter()
+        .filter(|d| d.actor_names.iter().any(|n| n.to_lowercase() == a))
+        .map(AvItem::from)
+        .collect()
+}
+
+/// Titles whose studio (or, with `series`, series) is `name`
+pub fn list_studio_or_series(name: &str, series: bool) -> Vec<AvItem> {
+    let name = name.trim().to_lowercase();
+    FIXTURES
+        .details
+        .iter()
+        .filter(|d| {
+            let field = if series { &d.series } else { &d.studio };
+            field.as_ref().is_some_and(|v| v.to_lowercase() == name)
+        })
+        .map(AvItem::from)
+        .collect()
+}
+
+/// Newest first
+pub fn top(limit: usize) -> Vec<AvItem> {
+    let mut details: Vec<&AvDetail> = FIXTURES.details.iter().collect();
+    details.sort_by(|a, b| b.release_date.cmp(&a.release_date));
+    details.into_iter().take(limit).map(AvItem::from).collect()
+}
+
+pub fn actors(page: usize, per_page: usize) -> RankingPage {
+    let all = &FIXTURES.actors;
+    let per_page = per_page.max(1);
+    let start = page.saturating_sub(1) * per_page;
+    let pages = all.len().div_ceil(per_page).max(1);
+    RankingPage {
+        actors: all.iter().skip(start).take(per_page).cloned().collect(),
+        total: all.len(),
+        pages,
+        next_url: (page < pages).then(|| format!("https://example.com/mock/actors?page={}", page + 1)),
+    }
+}
+
+pub fn actresses() -> Vec<ActressProfile> {
+    FIXTURES.actresses.clone()
+}
+
+/// Reviews of `code`, which has to be one of the fixtures
+pub fn reviews(code: &str) -> Result<Vec<Review>> {
+    let code = detail(code)?.code;
+    Ok(FIXTURES.reviews.iter().filter(|r| r.code == code).map(|r| r.review.clone()).collect())
+}
+
+pub fn play_url(code: &str) -> Result<String> {
+    Ok(format!("https://example.com/mock/play/{}", detail(code)?.code))
+}
+
+pub fn page_url(code: &str) -> Result<String> {
+    Ok(format!("https://example.com/mock/v/{}", detail(code)?.code))
+}
@@ -1,4 +1,33 @@
+pub mod avwiki;
+pub mod btsow;
 pub mod dmm;
+pub mod fc2;
+pub mod gfriends;
 pub mod javlibrary;
+pub mod javtrailers;
+pub mod minnano;
+pub mod mock;
+pub mod plugin;
+pub mod sokmil;
+pub mod subtitlecat;
+pub mod theporndb;
+pub mod torrentkitty;
+pub mod torznab;
+pub mod uncensored;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod xslist;
 
+/// `el`'s text with every run of whitespace collapsed to one space
+pub(crate) fn text(el: scraper::ElementRef) -> String {
+    el.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
 
+/// `href` as an absolute URL, relative ones taken against `base` (`https://host`)
+pub(crate) fn absolute(base: &str, href: &str) -> String {
+    match href {
+        h if h.starts_with("http") => h.to_string(),
+        h if h.starts_with("//") => format!("https:{}", h),
+        h => format!("{}/{}", base.trim_end_matches('/'), h.trim_start_matches('/')),
+    }
+}
@@ -0,0 +1,150 @@
+//! Third-party sources as external executables or WebAssembly modules (`module`, see
+//! `sources::wasm`). For every lookup av runs the plugin, writes one JSON request to its
+//! stdin and reads one JSON response from stdout:
+//!
+//! - `{"action": "detail", "code": "ABC-123"}` → an `AvDetail` object, or `null`
+//! - `{"action": "search", "query": "..."}` → `[{"code": ..., "title": ...}]`, or `null`
+//!
+//! Plugins may answer `null` to actions they don't support. A non-zero exit status is an
+//! error; stderr is shown in debug mode.
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::{self, PluginConfig, PluginMode};
+use crate::magnet;
+use crate::rating;
+use crate::types::{AvDetail, AvItem};
+use crate::util;
+
+pub fn plugins() -> &'static [PluginConfig] {
+    &config::get().sources.plugins
+}
+
+/// What a plugin run left: its exit status (`None` when killed) and output
+pub(crate) struct Output {
+    pub status: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+async fn run_command(p: &PluginConfig, request: &str) -> Result<Output> {
+    let mut child = tokio::process::Command::new(&p.command)
+        .args(&p.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("无法启动插件 {}（{}）", p.name, p.command))?;
+    let mut stdin = child.stdin.take().context("插件 stdin 不可用")?;
+    stdin.write_all(request.as_bytes()).await?;
+    drop(stdin);
+    let output = tokio::time::timeout(timeout(p), child.wait_with_output())
+        .await
+        .with_context(|| format!("插件 {} 超时（{} 秒）", p.name, p.timeout_secs))??;
+    Ok(Output { status: output.status.code(), stdout: output.stdout, stderr: output.stderr })
+}
+
+#[cfg(feature = "wasm")]
+async fn run_module(p: &PluginConfig, module: &std::path::Path, request: &str) -> Result<Output> {
+    crate::sources::wasm::run(p, module, request).await
+}
+
+#[cfg(not(feature = "wasm"))]
+async fn run_module(p: &PluginConfig, _module: &std::path::Path, _request: &str) -> Result<Output> {
+    bail!("插件 {} 是 WebAssembly 模块，需要 wasm 功能，请使用 --features wasm 重新编译", p.name)
+}
+
+pub(crate) fn timeout(p: &PluginConfig) -> Duration {
+    Duration::from_secs(p.timeout_secs.max(1))
+}
+
+async fn call<T: DeserializeOwned>(p: &PluginConfig, request: Value) -> Result<Option<T>> {
+    util::debug(format!("plugin {}: {}", p.name, request));
+    let request = format!("{}\n", request);
+    let output = match &p.module {
+        Some(_) if !p.command.trim().is_empty() => bail!("插件 {} 不能同时设置 command 和 module", p.name),
+        Some(module) => run_module(p, module, &request).await?,
+        None if p.command.trim().is_empty() => bail!("插件 {} 缺少 command 或 module", p.name),
+        None => run_command(p, &request).await?,
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        util::debug(format!("plugin {} stderr: {}", p.name, stderr.trim()));
+    }
+    if output.status != Some(0) {
+        bail!("插件 {} 退出码: {:?}", p.name, output.status);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(stdout.trim()).with_context(|| format!("插件 {} 的输出不是有效的 JSON", p.name))
+}
+
+pub async fn detail(p: &PluginConfig, code: &str) -> Result<Option<AvDetail>> {
+    let d: Option<AvDetail> = call(p, json!({ "action": "detail", "code": code })).await?;
+    Ok(d.filter(|d| !d.code.trim().is_empty()))
+}
+
+pub async fn search(p: &PluginConfig, query: &str) -> Result<Vec<AvItem>> {
+    Ok(call(p, json!({ "action": "search", "query": query })).await?.unwrap_or_default())
+}
+
+/// Copy the fields `d` is missing from `extra`
+fn fill_missing(d: &mut AvDetail, extra: AvDetail) {
+    if d.title.trim().is_empty() { d.title = extra.title; }
+    if d.actor_names.is_empty() { d.actor_names = extra.actor_names; }
+    if d.release_date.is_none() { d.release_date = extra.release_date; }
+    if d.cover_url.is_none() { d.cover_url = extra.cover_url; }
+    if d.plot.is_none() { d.plot = extra.plot; }
+    if d.duration_minutes.is_none() { d.duration_minutes = extra.duration_minutes; }
+    if d.director.is_none() { d.director = extra.director; }
+    if d.studio.is_none() { d.studio = extra.studio; }
+    if d.label.is_none() { d.label = extra.label; }
+    if d.series.is_none() { d.series = extra.series; }
+    if d.genres.is_empty() { d.genres = extra.genres; }
+    if d.rating.is_none() { d.rating = extra.rating; }
+    rating::merge(&mut d.ratings, extra.ratings);
+    if d.popularity.is_none() { d.popularity = extra.popularity; }
+    if d.preview_images.is_empty() { d.preview_images = extra.preview_images; }
+    if d.trailer_url.is_none() { d.trailer_url = extra.trailer_url; }
+    if magnet::all_sources() {
+        magnet::union(d, extra.magnets, extra.magnet_infos);
+        return;
+    }
+    if d.magnet_infos.is_empty() { d.magnet_infos = extra.magnet_infos; }
+    if d.magnets.is_empty() { d.magnets = extra.magnets; }
+}
+
+/// Run the configured plugins after the built-in chain: `merge` plugins always fill gaps,
+/// `fallback` plugins only when the built-in sources came up empty
+pub async fn extend(code: &str, builtin: Result<AvDetail>) -> Result<AvDetail> {
+    let mut result = builtin;
+    for p in plugins() {
+        if p.mode == PluginMode::Fallback && result.is_ok() {
+            continue;
+        }
+        match detail(p, code).await {
+            Ok(Some(mut found)) => {
+                util::debug(format!("plugin {} hit for {}", p.name, code));
+                // A plain `rating` counts as the plugin's own, out of 5
+                if found.ratings.is_empty() {
+                    found.ratings = found.rating.and_then(|r| rating::from_source(&p.name, r, rating::SCALE, None)).into_iter().collect();
+                }
+                match &mut result {
+                    Ok(d) => fill_missing(d, found),
+                    Err(_) => result = Ok(found),
+                }
+            }
+            Ok(None) => util::debug(format!("plugin {}: no result for {}", p.name, code)),
+            Err(e) => eprintln!("[WARN] {:#}", e),
+        }
+    }
+    result
+}
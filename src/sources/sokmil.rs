@@ -0,0 +1,129 @@
+//! Sokmil (sokmil.com), a Japanese retail store: its product pages carry the full plot text
+//! and large covers, for titles JavDB and DMM are missing or can't be reached for. Found by
+//! searching the code, then the product page whose 品番 matches is scraped.
+
+use anyhow::Result;
+use reqwest::header::{HeaderMap, HeaderValue, COOKIE};
+use std::sync::LazyLock;
+use urlencoding::encode;
+
+use crate::code;
+use crate::dates;
+use crate::selectors;
+use crate::sources::{absolute, text};
+use crate::types::AvDetail;
+use crate::util;
+
+const BASE: &str = "https://www.sokmil.com";
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    let mut headers = HeaderMap::new();
+    // Passes the age check every page otherwise redirects to
+    headers.insert(COOKIE, HeaderValue::from_static("AGEAUTH=ok"));
+    crate::scraper::client_builder().default_headers(headers).build().expect("client build")
+});
+
+pub fn search_url(code: &str) -> String {
+    format!("{}/av/_search/?keyword={}", BASE, encode(code))
+}
+
+fn items(value: scraper::ElementRef) -> Vec<String> {
+    let found: Vec<String> = value.select(selectors::sokmil("info_item")).map(text).filter(|t| !t.is_empty()).collect();
+    if !found.is_empty() {
+        return found;
+    }
+    text(value).split([',', '、', '/']).map(|t| t.trim().to_string()).filter(|t| !t.is_empty() && t != "-").collect()
+}
+
+/// The product page of the search hit for `code`; `None` when Sokmil has no such title
+pub async fn detail_url(code: &str) -> Result<Option<String>> {
+    let body = crate::scraper::get_text(&CLIENT, &search_url(code)).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let hits: Vec<(String, String)> = doc
+        .select(selectors::sokmil("result"))
+        .filter_map(|card| {
+            let href = card.select(selectors::sokmil("result_link")).find_map(|a| a.value().attr("href")).or(card.value().attr("href"))?;
+            Some((absolute(BASE, href), text(card)))
+        })
+        .collect();
+    // Cards that name a code name this one; the rest are kept as a last resort
+    Ok(hits
+        .iter()
+        .find(|(_, t)| code::find(t).is_some_and(|c| code::same_release(&c.to_string(), code)))
+        .or_else(|| hits.iter().find(|(_, t)| code::find(t).is_none()))
+        .map(|(u, _)| u.clone()))
+}
+
+/// `Ok(None)` when Sokmil has no product with `code`'s 品番
+pub async fn fetch_detail(code: &str) -> Result<Option<AvDetail>> {
+    let code = code::normalize(code);
+    let Some(url) = detail_url(&code).await? else { return Ok(None) };
+    util::debug(format!("Sokmil detail: {}", url));
+    let body = crate::scraper::get_text(&CLIENT, &url).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let sel = selectors::sokmil;
+    let mut d = AvDetail {
+        code: code.clone(),
+        title: doc.select(sel("title")).next().map(text).unwrap_or_default(),
+        actor_names: Vec::new(),
+        release_date: None,
+        cover_url: None,
+        plot: doc.select(sel("plot")).next().map(text).filter(|p| !p.is_empty()),
+        duration_minutes: None,
+        director: None,
+        studio: None,
+        label: None,
+        series: None,
+        genres: Vec::new(),
+        genre_ids: Vec::new(),
+        rating: None,
+        ratings: Vec::new(),
+        popularity: None,
+        preview_images: Vec::new(),
+        trailer_url: None,
+        sample_video_url: None,
+        amateur: false,
+        magnet_infos: Vec::new(),
+        magnets: Vec::new(),
+    };
+    if d.title.is_empty() {
+        return Ok(None);
+    }
+    let one = |v: scraper::ElementRef| Some(text(v)).filter(|s| !s.is_empty() && s != "-");
+    for row in doc.select(sel("info_row")) {
+        let (Some(label), Some(value)) = (row.select(sel("info_label")).next().map(text), row.select(sel("info_value")).next()) else { continue };
+        match label.as_str() {
+            l if l.contains("出演") => d.actor_names = items(value),
+            l if l.contains("発売日") || l.contains("配信開始日") => d.release_date = dates::parse(&text(value)).map(|d| d.format("%Y-%m-%d").to_string()),
+            // `120分`
+            l if l.contains("収録時間") => d.duration_minutes = text(value).chars().filter(char::is_ascii_digit).collect::<String>().parse().ok().filter(|m| *m > 0),
+            l if l.contains("監督") => d.director = one(value),
+            l if l.contains("メーカー") => d.studio = one(value),
+            l if l.contains("レーベル") => d.label = one(value),
+            l if l.contains("シリーズ") => d.series = one(value),
+            l if l.contains("ジャンル") => d.genres = items(value),
+            // A search hit for another title
+            l if l.contains("品番") => {
+                let id = text(value);
+                if code::parse(&id).is_some() && !code::same_release(&id, &code) {
+                    util::debug(format!("Sokmil: {} is {}, not {}", url, id, code));
+                    return Ok(None);
+                }
+            }
+            _ => {}
+        }
+    }
+    d.cover_url = doc
+        .select(sel("cover"))
+        .find_map(|el| el.value().attr("href").filter(|h| h.contains(".jpg")).or(el.value().attr("src")))
+        .map(|h| absolute(BASE, h));
+    for el in doc.select(sel("preview")) {
+        if let Some(href) = el.value().attr("href").or(el.value().attr("src")) {
+            let img = absolute(BASE, href);
+            if !d.preview_images.contains(&img) {
+                d.preview_images.push(img);
+            }
+        }
+    }
+    Ok(Some(d))
+}
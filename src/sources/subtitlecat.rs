@@ -0,0 +1,147 @@
+//! SubtitleCat (subtitlecat.com), a subtitle archive keyed by file name, JAV codes
+//! included. Each upload lists the languages it has been translated into, each one its own
+//! `.srt`; the search results carry how often an upload was downloaded.
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::Semaphore;
+use urlencoding::encode;
+
+use crate::code;
+use crate::selectors;
+use crate::sources::{absolute, text};
+use crate::util;
+
+const BASE: &str = "https://www.subtitlecat.com";
+
+/// Uploads whose pages are read, the most downloaded first
+const MAX_UPLOADS: usize = 5;
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::scraper::client_builder().build().expect("client build"));
+
+static DOWNLOADS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(\d[\d,]*)\s*(?:downloads?|次下载)").unwrap());
+/// `SSIS-001-zh-CN.srt` → `zh-CN`
+static FILE_LANG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"-([a-z]{2,3}(?:-[A-Za-z]{2,4})?)\.srt$").unwrap());
+static TRANSLATED_FROM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)translated from (\w+)").unwrap());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Subtitle {
+    /// The upload's title, usually the file name it was made for
+    pub title: String,
+    /// `zh-CN`, `en`, `ja` ..., as the file names it; the original upload's language when
+    /// it is not a translation
+    pub lang: String,
+    pub downloads: u32,
+    /// The `.srt` itself
+    pub url: String,
+    /// The upload's page
+    pub page: String,
+}
+
+struct Upload {
+    title: String,
+    page: String,
+    downloads: u32,
+    /// The language it was written in, from `translated from Japanese`
+    original: Option<String>,
+}
+
+/// `Japanese` → `ja`
+fn lang_code(name: &str) -> String {
+    match name.to_ascii_lowercase().as_str() {
+        "japanese" => "ja",
+        "chinese" => "zh",
+        "english" => "en",
+        "korean" => "ko",
+        other => return other.to_string(),
+    }
+    .to_string()
+}
+
+pub fn search_url(code: &str) -> String {
+    format!("{}/index.php?search={}", BASE, encode(code))
+}
+
+/// Uploads made for `code`, the most downloaded first
+async fn uploads(code: &str) -> Result<Vec<Upload>> {
+    let body = crate::scraper::get_text(&CLIENT, &search_url(code)).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let mut out: Vec<Upload> = Vec::new();
+    for row in doc.select(selectors::subtitlecat("result")) {
+        let Some(link) = row.select(selectors::subtitlecat("result_link")).next() else { continue };
+        let Some(href) = link.value().attr("href") else { continue };
+        let title = text(link);
+        // Searches match loosely: `SSIS-001` also finds `SSIS-0010` and other studios' `001`s
+        if !code::find(&title).is_some_and(|c| code::same_release(&c.to_string(), code)) {
+            continue;
+        }
+        let row_text = text(row);
+        let page = absolute(BASE, href);
+        if out.iter().any(|u| u.page == page) {
+            continue;
+        }
+        out.push(Upload {
+            title,
+            page,
+            downloads: DOWNLOADS.captures(&row_text).and_then(|c| c[1].replace(',', "").parse().ok()).unwrap_or(0),
+            original: TRANSLATED_FROM.captures(&row_text).map(|c| lang_code(&c[1])),
+        });
+    }
+    out.sort_by_key(|u| std::cmp::Reverse(u.downloads));
+    Ok(out)
+}
+
+/// Every language the upload's page offers a file for
+async fn files(upload: &Upload) -> Result<Vec<Subtitle>> {
+    let body = crate::scraper::get_text(&CLIENT, &upload.page).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let mut out: Vec<Subtitle> = Vec::new();
+    for item in doc.select(selectors::subtitlecat("language")) {
+        // Languages not translated yet only have a "translate" button
+        let Some(href) = item.select(selectors::subtitlecat("download")).find_map(|a| a.value().attr("href")) else { continue };
+        let url = absolute(BASE, href);
+        let lang = FILE_LANG
+            .captures(&url)
+            .map(|c| c[1].to_string())
+            .or_else(|| upload.original.clone())
+            .unwrap_or_else(|| "und".to_string());
+        if out.iter().any(|s| s.url == url) {
+            continue;
+        }
+        out.push(Subtitle { title: upload.title.clone(), lang, downloads: upload.downloads, url, page: upload.page.clone() });
+    }
+    Ok(out)
+}
+
+/// Subtitles for `code` in every language on offer, from its `MAX_UPLOADS` most downloaded
+/// uploads, read `--jobs` at a time; in the uploads' order
+pub async fn search(code: &str) -> Result<Vec<Subtitle>> {
+    let code = code::normalize(code);
+    let mut found = uploads(&code).await?;
+    found.truncate(MAX_UPLOADS);
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, upload) in found.into_iter().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = files(&upload).await;
+            if let Err(e) = &result {
+                util::debug(format!("SubtitleCat {}: {:#}", upload.page, e));
+            }
+            (idx, result.unwrap_or_default())
+        });
+    }
+    let mut done = tasks.join_all().await;
+    crate::cancel::check()?;
+    done.sort_by_key(|(idx, _)| *idx);
+    Ok(done.into_iter().flat_map(|(_, subs)| subs).collect())
+}
+
+/// The `.srt` as served, in whatever encoding it was uploaded
+pub async fn download(url: &str) -> Result<Vec<u8>> {
+    crate::scraper::get_bytes(&CLIENT, url).await
+}
@@ -0,0 +1,152 @@
+//! ThePornDB (metadataapi.net), for western studio scenes that have no JAV-style code.
+//! Needs a free API token under `[sources.theporndb]`. Scenes are matched with the API's
+//! own filename parser, so `Brazzers.23.05.12.Jane.Doe` and `brazzers jane doe` both find
+//! one, and each scene is keyed by the same `Site.YY.MM.DD.Performer` form it would be
+//! released under.
+
+use anyhow::{bail, Context, Result};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION};
+use serde_json::Value;
+use std::sync::LazyLock;
+use urlencoding::encode;
+
+use crate::code::Code;
+use crate::config;
+use crate::sanity;
+use crate::types::{AvDetail, AvItem};
+use crate::util;
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::scraper::client_builder().build().expect("client build"));
+
+/// Results asked for per keyword search
+const SEARCH_LIMIT: usize = 25;
+
+pub fn enabled() -> bool {
+    !config::get().sources.theporndb.token.trim().is_empty()
+}
+
+fn api_base() -> String {
+    config::get().sources.theporndb.url.trim().trim_end_matches('/').to_string()
+}
+
+/// GET `path` (with its query) from the API, as JSON
+async fn get(path: &str) -> Result<Value> {
+    if !enabled() {
+        bail!("ThePornDB 未启用，请在配置文件的 [sources.theporndb] 中设置 token");
+    }
+    let url = format!("{}{}", api_base(), path);
+    util::debug(format!("ThePornDB: {}", url));
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    let token = format!("Bearer {}", config::get().sources.theporndb.token.trim());
+    headers.insert(AUTHORIZATION, HeaderValue::from_str(&token).context("[sources.theporndb] token 含有无效字符")?);
+    let resp = crate::scraper::send_from(&CLIENT, &url, "https://theporndb.net/", headers).await?;
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+        bail!("ThePornDB 拒绝了 token（HTTP 401），请检查 [sources.theporndb] token");
+    }
+    let body = sanity::page_text(resp).await?;
+    serde_json::from_str(&body).context("ThePornDB 返回的不是 JSON")
+}
+
+fn text(v: &Value, path: &[&str]) -> Option<String> {
+    let mut cur = v;
+    for p in path {
+        cur = cur.get(*p)?;
+    }
+    cur.as_str().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn names(v: &Value, key: &str) -> Vec<String> {
+    v.get(key).and_then(Value::as_array).map(|a| a.iter().filter_map(|x| text(x, &["name"])).collect()).unwrap_or_default()
+}
+
+/// `Site.YY.MM.DD.First.Performer` (see `code::Code::Scene`), the scene-release naming
+/// western downloads carry; the API's slug when the scene has no date
+pub fn scene_code(scene: &Value) -> String {
+    let site = text(scene, &["site", "name"]).unwrap_or_default();
+    let date = text(scene, &["date"]).and_then(|d| chrono::NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok());
+    match date {
+        Some(date) if site.chars().any(char::is_alphanumeric) => {
+            let performer = names(scene, "performers").into_iter().next().unwrap_or_default();
+            Code::scene(&site, date, &performer).to_string()
+        }
+        _ => text(scene, &["slug"]).or_else(|| text(scene, &["id"])).unwrap_or_default(),
+    }
+}
+
+fn cover(scene: &Value) -> Option<String> {
+    text(scene, &["background", "large"]).or_else(|| text(scene, &["image"]))
+}
+
+fn to_detail(scene: &Value) -> AvDetail {
+    let site = text(scene, &["site", "name"]);
+    let network = text(scene, &["site", "network", "name"]).filter(|n| Some(n) != site.as_ref());
+    AvDetail {
+        code: scene_code(scene),
+        title: text(scene, &["title"]).unwrap_or_default(),
+        actor_names: names(scene, "performers"),
+        release_date: text(scene, &["date"]),
+        cover_url: cover(scene),
+        plot: text(scene, &["description"]),
+        // Seconds in the API
+        duration_minutes: scene.get("duration").and_then(Value::as_u64).filter(|s| *s > 0).map(|s| ((s + 30) / 60) as u32),
+        director: names(scene, "directors").into_iter().next(),
+        studio: site,
+        label: network,
+        series: None,
+        genres: names(scene, "tags"),
+        genre_ids: Vec::new(),
+        rating: None,
+        ratings: Vec::new(),
+        popularity: None,
+        preview_images: Vec::new(),
+        trailer_url: text(scene, &["trailer"]),
+        sample_video_url: None,
+        amateur: false,
+        magnet_infos: Vec::new(),
+        magnets: Vec::new(),
+    }
+}
+
+fn scenes(v: Value) -> Vec<Value> {
+    match v.get("data") {
+        Some(Value::Array(a)) => a.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// The scene the API's parser matches for `query`, a file name or scene name
+async fn best_match(query: &str) -> Result<Option<Value>> {
+    Ok(scenes(get(&format!("/scenes?parse={}&per_page=1", encode(query.trim()))).await?).into_iter().next())
+}
+
+/// `Ok(None)` when ThePornDB has no scene for `query`
+pub async fn fetch_detail(query: &str) -> Result<Option<AvDetail>> {
+    Ok(best_match(query).await?.map(|s| to_detail(&s)))
+}
+
+/// The scene's page on theporndb.net
+pub async fn detail_url(query: &str) -> Result<Option<String>> {
+    Ok(best_match(query).await?.and_then(|s| text(&s, &["slug"])).map(|slug| format!("https://theporndb.net/scenes/{}", slug)))
+}
+
+/// Keyword search over scene titles, performers and sites
+pub async fn search(query: &str) -> Result<Vec<AvItem>> {
+    let found = scenes(get(&format!("/scenes?q={}&per_page={}", encode(query.trim()), SEARCH_LIMIT)).await?);
+    Ok(found
+        .iter()
+        .map(|s| AvItem {
+            code: scene_code(s),
+            title: text(s, &["title"]).unwrap_or_default(),
+            release_date: text(s, &["date"]),
+            rating: None,
+            thumbnail_url: text(s, &["poster"]).or_else(|| cover(s)),
+            has_magnets: None,
+            has_subtitles: None,
+            sources: Vec::new(),
+            alt_titles: Vec::new(),
+        })
+        .filter(|i| !i.code.is_empty())
+        .collect())
+}
@@ -0,0 +1,122 @@
+//! TorrentKitty, another DHT index, asked after Sukebei and BTSOW for codes neither has
+//! magnets for. Search rows carry the name and the magnet; sizes and dates missing there
+//! come from the torrent's information page.
+//!
+//! `AV_TORRENTKITTY_BASE` or `[magnets] torrentkitty_base` point it at a mirror.
+
+use anyhow::Result;
+use std::sync::LazyLock;
+use urlencoding::encode;
+
+use crate::code;
+use crate::config;
+use crate::magnet;
+use crate::selectors;
+use crate::types::{AvDetail, MagnetInfo};
+use crate::util;
+
+const DEFAULT_BASE: &str = "https://www.torrentkitty.tv";
+
+/// Information pages fetched per search at most, for rows without a size or date
+const INFO_PAGES: usize = 5;
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    crate::scraper::client_builder().cookie_provider(crate::cookies::provider()).build().expect("client build")
+});
+
+pub fn base() -> String {
+    std::env::var("AV_TORRENTKITTY_BASE")
+        .ok()
+        .or_else(|| config::get().magnets.torrentkitty_base.clone())
+        .filter(|b| !b.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_BASE.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+pub fn search_url(query: &str) -> String {
+    format!("{}/search/{}/", base(), encode(query.trim()))
+}
+
+fn text(el: scraper::ElementRef, key: &str) -> Option<String> {
+    el.select(selectors::torrentkitty(key))
+        .next()
+        .map(|n| n.text().collect::<String>().trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Size and date from an information page's summary table
+async fn info_page(url: &str) -> Result<(Option<String>, Option<String>)> {
+    let body = crate::scraper::get_text(&CLIENT, url).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let (mut size, mut date) = (None, None);
+    for row in doc.select(selectors::torrentkitty("summary_row")) {
+        let (Some(label), value) = (text(row, "summary_label"), text(row, "summary_value")) else { continue };
+        let label = label.to_lowercase();
+        if label.contains("size") {
+            size = value;
+        } else if label.contains("created") || label.contains("date") {
+            date = value;
+        }
+    }
+    Ok((size, date))
+}
+
+/// Magnets whose names carry `code`
+pub async fn magnets(code: &str) -> Result<Vec<MagnetInfo>> {
+    let url = search_url(code);
+    util::debug(format!("TorrentKitty search: {}", url));
+    let body = crate::scraper::get_text(&CLIENT, &url).await?;
+    let mut out: Vec<(MagnetInfo, Option<String>)> = Vec::new();
+    {
+        let doc = scraper::Html::parse_document(&body);
+        for row in doc.select(selectors::torrentkitty("row")) {
+            let Some(uri) = row.select(selectors::torrentkitty("magnet")).next().and_then(|a| a.value().attr("href")) else {
+                continue;
+            };
+            let name = text(row, "name");
+            if !name.as_deref().is_some_and(|n| code::is_named(n, code)) {
+                continue;
+            }
+            let Some(base) = magnet::info(uri) else { continue };
+            if out.iter().any(|(m, _)| magnet::same(m, &base)) {
+                continue;
+            }
+            let info_url = row
+                .select(selectors::torrentkitty("info_link"))
+                .next()
+                .and_then(|a| a.value().attr("href"))
+                .map(|h| if h.starts_with("http") { h.to_string() } else { format!("{}{}", self::base(), h) });
+            let info = MagnetInfo {
+                part: name.as_deref().and_then(code::part),
+                name,
+                size: text(row, "size"),
+                date: text(row, "date"),
+                ..base
+            };
+            out.push((info, info_url));
+        }
+    }
+    let mut fetched = 0;
+    for (m, info_url) in &mut out {
+        if m.size.is_some() && m.date.is_some() {
+            continue;
+        }
+        let Some(url) = info_url.as_deref().filter(|_| fetched < INFO_PAGES) else { continue };
+        fetched += 1;
+        match info_page(url).await {
+            Ok((size, date)) => {
+                m.size = m.size.take().or(size);
+                m.date = m.date.take().or(date);
+            }
+            Err(e) => util::debug(format!("TorrentKitty information page {}: {:#}", url, e)),
+        }
+    }
+    Ok(out.into_iter().map(|(m, _)| m).collect())
+}
+
+/// `--source torrentkitty`; `None` when it has no magnets for the code
+pub async fn fetch_detail(code: &str) -> Result<Option<AvDetail>> {
+    let infos = magnets(code).await?;
+    Ok((!infos.is_empty()).then(|| magnet::detail_of(code, infos)))
+}
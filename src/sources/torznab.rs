@@ -0,0 +1,139 @@
+//! Torznab indexers, for people who already run Jackett or Prowlarr in front of private
+//! trackers: each `[[sources.torznab]]` entry is searched for the code and its results
+//! join the magnets from the public sources.
+//!
+//! The feed is RSS with `torznab:attr` extensions (seeders, peers, infohash, magneturl).
+//! Results that only offer a `.torrent` download and no infohash are left out, since
+//! everything downstream works with magnets.
+
+use anyhow::{bail, Result};
+use chrono::DateTime;
+use regex::Regex;
+use std::sync::LazyLock;
+use urlencoding::encode;
+
+use crate::code;
+use crate::config::{self, TorznabConfig};
+use crate::magnet;
+use crate::types::MagnetInfo;
+use crate::util;
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::scraper::client_builder().build().expect("client build"));
+
+static ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<item\b[^>]*>(.*?)</item>").unwrap());
+static ATTR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<(?:torznab|newznab):attr\s+name="([^"]*)"\s+value="([^"]*)"\s*/?>"#).unwrap());
+static ENCLOSURE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<enclosure\b[^>]*\burl="([^"]*)""#).unwrap());
+static ERROR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<error\b[^>]*\bdescription="([^"]*)""#).unwrap());
+
+pub fn indexers() -> &'static [TorznabConfig] {
+    &config::get().sources.torznab
+}
+
+/// `t=search` for `query`; without `with_key` the API key is left out, for logs
+fn search_url(t: &TorznabConfig, query: &str, with_key: bool) -> String {
+    let mut url = format!("{}{}t=search&q={}", t.url.trim(), if t.url.contains('?') { "&" } else { "?" }, encode(query));
+    if !t.categories.is_empty() {
+        let cats: Vec<String> = t.categories.iter().map(|c| c.to_string()).collect();
+        url.push_str(&format!("&cat={}", cats.join(",")));
+    }
+    if with_key {
+        url.push_str(&format!("&apikey={}", encode(t.api_key.trim())));
+    }
+    url
+}
+
+fn element(item: &str, name: &str) -> Option<String> {
+    let open = format!("<{}>", name);
+    let start = item.find(&open)? + open.len();
+    let end = item[start..].find(&format!("</{}>", name))? + start;
+    Some(util::xml_unescape(&item[start..end])).filter(|s| !s.is_empty())
+}
+
+/// One feed item as a magnet; `None` without a magnet link or infohash
+fn parse_item(item: &str) -> Option<MagnetInfo> {
+    let attrs: Vec<(String, String)> = ATTR.captures_iter(item).map(|c| (c[1].to_lowercase(), util::xml_unescape(&c[2]))).collect();
+    let attr = |name: &str| attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone()).filter(|v| !v.is_empty());
+    let count = |name: &str| attr(name).and_then(|v| v.parse::<u32>().ok());
+    let title = element(item, "title");
+    let is_magnet = |u: &String| u.starts_with("magnet:");
+    let uri = attr("magneturl")
+        .filter(is_magnet)
+        .or_else(|| element(item, "link").filter(is_magnet))
+        .or_else(|| ENCLOSURE.captures(item).map(|c| util::xml_unescape(&c[1])).filter(is_magnet))
+        .or_else(|| {
+            let hash = attr("infohash")?;
+            Some(match &title {
+                Some(t) => format!("magnet:?xt=urn:btih:{}&dn={}", hash, encode(t)),
+                None => format!("magnet:?xt=urn:btih:{}", hash),
+            })
+        })?;
+    let base = magnet::info(&uri)?;
+    let seeders = count("seeders");
+    let bytes = element(item, "size").or_else(|| attr("size")).and_then(|s| s.parse::<u64>().ok());
+    Some(MagnetInfo {
+        part: title.as_deref().and_then(code::part),
+        name: title,
+        size: bytes.map(magnet::format_size),
+        size_bytes: bytes,
+        date: element(item, "pubDate")
+            .and_then(|d| DateTime::parse_from_rfc2822(&d).ok())
+            .map(|d| d.format("%Y-%m-%d").to_string()),
+        seeders,
+        // Torznab's peers include the seeders
+        leechers: count("peers").map(|p| p.saturating_sub(seeders.unwrap_or(0))),
+        downloads: count("grabs"),
+        ..base
+    })
+}
+
+/// One indexer's magnets whose names carry `code`
+pub async fn search(t: &TorznabConfig, code: &str) -> Result<Vec<MagnetInfo>> {
+    if t.url.trim().is_empty() {
+        bail!("[[sources.torznab]] {} 没有设置 url", t.name);
+    }
+    util::debug(format!("Torznab {}: {}", t.name, search_url(t, code, false)));
+    let body = crate::scraper::get_text(&CLIENT, &search_url(t, code, true)).await?;
+    if let Some(e) = ERROR.captures(&body) {
+        bail!("Torznab {}: {}", t.name, util::xml_unescape(&e[1]));
+    }
+    let mut out: Vec<MagnetInfo> = Vec::new();
+    for item in ITEM.captures_iter(&body) {
+        let Some(m) = parse_item(&item[1]) else { continue };
+        if !m.name.as_deref().is_some_and(|n| code::is_named(n, code)) || out.iter().any(|o| magnet::same(o, &m)) {
+            continue;
+        }
+        out.push(m);
+    }
+    Ok(out)
+}
+
+/// Every configured indexer's magnets for `code`, asked together; failing indexers are
+/// warned about and skipped
+pub async fn magnets(code: &str) -> Vec<MagnetInfo> {
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, t) in indexers().iter().enumerate() {
+        let code = code.to_string();
+        tasks.spawn(async move { (idx, search(t, &code).await) });
+    }
+    let mut found = tasks.join_all().await;
+    found.sort_by_key(|(idx, _)| *idx);
+    let mut out: Vec<MagnetInfo> = Vec::new();
+    for (idx, result) in found {
+        let t = &indexers()[idx];
+        match result {
+            Ok(infos) => {
+                util::debug(format!("Torznab {}: {} magnet(s) for {}", t.name, infos.len(), code));
+                for m in infos {
+                    match out.iter_mut().find(|o| magnet::same(o, &m)) {
+                        Some(o) => o.seeders = o.seeders.max(m.seeders),
+                        None => out.push(m),
+                    }
+                }
+            }
+            Err(e) => eprintln!("[WARN] {:#}", e),
+        }
+    }
+    out
+}
@@ -0,0 +1,292 @@
+//! The uncensored studios' own sites, for codes JavDB often has little on: 1Pondo
+//! (`010124_001`), Caribbeancom (`010124-001`), HEYZO (`HEYZO-1234`) and Tokyo-Hot
+//! (`n1234`, `k1234`). 1Pondo has a JSON endpoint behind its pages; the others are
+//! scraped, Caribbeancom's in EUC-JP. Tokyo-Hot's product pages aren't named after the
+//! code, so it is searched for first.
+
+use anyhow::Result;
+use regex::Regex;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+use crate::code::{self, Code};
+use crate::dates;
+use crate::rating;
+use crate::selectors;
+use crate::sources::{absolute, text};
+use crate::types::AvDetail;
+use crate::util;
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::scraper::client_builder().build().expect("client build"));
+
+/// `01:02:03`, `62:03`, or ISO 8601's `PT1H2M3S` / `T01H02M03S`
+static CLOCK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?:(\d{1,2}):)?(\d{1,3}):(\d{2})").unwrap());
+static ISO_DURATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+)S)?").unwrap());
+static LD_DURATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""duration"\s*:\s*"([^"]+)""#).unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Studio {
+    OnePondo,
+    Caribbeancom,
+    Heyzo,
+    TokyoHot,
+}
+
+impl Studio {
+    pub fn name(self) -> &'static str {
+        match self {
+            Studio::OnePondo => "1pondo",
+            Studio::Caribbeancom => "caribbeancom",
+            Studio::Heyzo => "heyzo",
+            Studio::TokyoHot => "tokyo-hot",
+        }
+    }
+
+    /// The studio name as it goes into `studio`
+    fn label(self) -> &'static str {
+        match self {
+            Studio::OnePondo => "一本道",
+            Studio::Caribbeancom => "カリビアンコム",
+            Studio::Heyzo => "HEYZO",
+            Studio::TokyoHot => "東京熱",
+        }
+    }
+}
+
+/// The studio whose own site has `code`, and its id there
+pub fn studio_of(code: &str) -> Option<(Studio, String)> {
+    match code::parse(code)? {
+        // The separator is what tells the two apart
+        Code::Dated(id) if id.contains('_') => Some((Studio::OnePondo, id)),
+        Code::Dated(id) => Some((Studio::Caribbeancom, id)),
+        Code::Heyzo(id) => Some((Studio::Heyzo, id)),
+        // Lower case on the site: `n1234`
+        Code::TokyoHot(id) => Some((Studio::TokyoHot, id.to_lowercase())),
+        _ => None,
+    }
+}
+
+/// The title's page on the studio's site (Tokyo-Hot's search for it)
+pub fn page_url(code: &str) -> Option<String> {
+    let (studio, id) = studio_of(code)?;
+    Some(match studio {
+        Studio::OnePondo => format!("https://www.1pondo.tv/movies/{}/", id),
+        Studio::Caribbeancom => format!("https://www.caribbeancom.com/moviepages/{}/index.html", id),
+        Studio::Heyzo => format!("https://www.heyzo.com/moviepages/{}/index.html", id),
+        Studio::TokyoHot => tokyo_hot_search_url(&id),
+    })
+}
+
+/// Whole minutes, rounded, from a clock-style or ISO 8601 duration
+fn minutes(s: &str) -> Option<u32> {
+    let num = |m: Option<regex::Match>| m.and_then(|m| m.as_str().parse::<u32>().ok()).unwrap_or(0);
+    let secs = if let Some(c) = CLOCK.captures(s) {
+        num(c.get(1)) * 3600 + num(c.get(2)) * 60 + num(c.get(3))
+    } else {
+        let c = ISO_DURATION.captures(s)?;
+        num(c.get(1)) * 3600 + num(c.get(2)) * 60 + num(c.get(3))
+    };
+    (secs > 0).then(|| (secs + 30) / 60)
+}
+
+fn date(s: &str) -> Option<String> {
+    dates::parse(s).map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+fn blank(code: &str, studio: Studio) -> AvDetail {
+    AvDetail {
+        code: code.to_string(),
+        title: String::new(),
+        actor_names: Vec::new(),
+        release_date: None,
+        cover_url: None,
+        plot: None,
+        duration_minutes: None,
+        director: None,
+        studio: Some(studio.label().to_string()),
+        label: None,
+        series: None,
+        genres: Vec::new(),
+        genre_ids: Vec::new(),
+        rating: None,
+        ratings: Vec::new(),
+        popularity: None,
+        preview_images: Vec::new(),
+        trailer_url: None,
+        sample_video_url: None,
+        amateur: false,
+        magnet_infos: Vec::new(),
+        magnets: Vec::new(),
+    }
+}
+
+/// `Ok(None)` for codes of other studios, and for ids the studio doesn't have
+pub async fn fetch_detail(code: &str) -> Result<Option<AvDetail>> {
+    let Some((studio, id)) = studio_of(code) else { return Ok(None) };
+    let code = code::normalize(code);
+    util::debug(format!("{}: {}", studio.name(), id));
+    let found = match studio {
+        Studio::OnePondo => one_pondo(&code, &id).await,
+        Studio::Caribbeancom => caribbeancom(&code, &id).await,
+        Studio::Heyzo => heyzo(&code, &id).await,
+        Studio::TokyoHot => tokyo_hot(&code, &id).await,
+    };
+    match found {
+        // Missing titles are a 404, or a page without one
+        Err(e) if e.downcast_ref::<reqwest::Error>().and_then(|e| e.status()) == Some(reqwest::StatusCode::NOT_FOUND) => Ok(None),
+        Ok(Some(d)) if d.title.is_empty() => Ok(None),
+        other => other,
+    }
+}
+
+async fn one_pondo(code: &str, id: &str) -> Result<Option<AvDetail>> {
+    let url = format!("https://www.1pondo.tv/dyn/phpauto/movie_details/movie_id/{}.json", id);
+    let v: Value = serde_json::from_str(&crate::scraper::get_text(&CLIENT, &url).await?)?;
+    let s = |key: &str| v.get(key).and_then(Value::as_str).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let list = |key: &str| -> Vec<String> {
+        v.get(key).and_then(Value::as_array).map(|a| a.iter().filter_map(Value::as_str).map(|s| s.trim().to_string()).collect()).unwrap_or_default()
+    };
+    let mut d = blank(code, Studio::OnePondo);
+    d.title = s("Title").unwrap_or_default();
+    d.actor_names = list("ActressesJa");
+    if d.actor_names.is_empty() {
+        d.actor_names = s("Actor").map(|a| a.split([',', '、']).map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect()).unwrap_or_default();
+    }
+    d.release_date = s("Release").as_deref().and_then(date);
+    d.plot = s("Desc");
+    d.duration_minutes = v.get("Duration").and_then(Value::as_u64).filter(|s| *s > 0).map(|s| ((s + 30) / 60) as u32);
+    d.series = s("Series");
+    d.genres = list("UCNAME");
+    d.cover_url = s("ThumbUltra").or_else(|| s("ThumbHigh")).or_else(|| Some(format!("https://www.1pondo.tv/assets/sample/{}/str.jpg", id)));
+    // The sample files are listed smallest first
+    d.trailer_url = v
+        .get("SampleFiles")
+        .and_then(Value::as_array)
+        .and_then(|files| files.iter().filter_map(|f| f.get("URL").and_then(Value::as_str)).next_back())
+        .map(str::to_string);
+    d.rating = v.get("AvgRating").and_then(Value::as_f64).map(|r| r as f32);
+    d.ratings = d.rating.and_then(|r| rating::from_source("1pondo", r, rating::SCALE, None)).into_iter().collect();
+    Ok(Some(d))
+}
+
+/// The texts of `item`s inside `value`, or `value`'s own text split on commas when it
+/// has none
+fn items(value: scraper::ElementRef, item: &scraper::Selector) -> Vec<String> {
+    let found: Vec<String> = value.select(item).map(text).filter(|t| !t.is_empty()).collect();
+    if !found.is_empty() {
+        return found;
+    }
+    text(value).split([',', '、', '/']).map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()
+}
+
+async fn caribbeancom(code: &str, id: &str) -> Result<Option<AvDetail>> {
+    let body = crate::scraper::get_text(&CLIENT, &format!("https://www.caribbeancom.com/moviepages/{}/index.html", id)).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let sel = selectors::caribbeancom;
+    let mut d = blank(code, Studio::Caribbeancom);
+    d.title = doc.select(sel("title")).next().map(text).unwrap_or_default();
+    d.plot = doc.select(sel("plot")).next().map(text).filter(|p| !p.is_empty());
+    for row in doc.select(sel("spec_row")) {
+        let (Some(label), Some(value)) = (row.select(sel("spec_label")).next().map(text), row.select(sel("spec_value")).next()) else { continue };
+        match label.as_str() {
+            l if l.contains("出演") => d.actor_names = items(value, sel("spec_item")),
+            l if l.contains("配信日") || l.contains("販売日") => d.release_date = date(&text(value)),
+            l if l.contains("再生時間") => d.duration_minutes = minutes(&text(value)),
+            l if l.contains("シリーズ") => d.series = Some(text(value)).filter(|s| !s.is_empty()),
+            l if l.contains("タグ") || l.contains("カテゴリ") => d.genres = items(value, sel("spec_item")),
+            _ => {}
+        }
+    }
+    d.cover_url = Some(format!("https://www.caribbeancom.com/moviepages/{}/images/l_l.jpg", id));
+    d.trailer_url = Some(format!("https://smovie.caribbeancom.com/sample/movies/{}/1080p.mp4", id));
+    Ok(Some(d))
+}
+
+async fn heyzo(code: &str, id: &str) -> Result<Option<AvDetail>> {
+    let body = crate::scraper::get_text(&CLIENT, &format!("https://www.heyzo.com/moviepages/{}/index.html", id)).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let sel = selectors::heyzo;
+    let mut d = blank(code, Studio::Heyzo);
+    let heading = doc.select(sel("title")).next().map(text).unwrap_or_default();
+    d.plot = doc.select(sel("plot")).next().map(text).filter(|p| !p.is_empty());
+    for row in doc.select(sel("info_row")) {
+        let (Some(label), Some(value)) = (row.select(sel("info_label")).next().map(text), row.select(sel("info_value")).next()) else { continue };
+        match label.as_str() {
+            l if l.contains("出演") => d.actor_names = items(value, sel("info_item")),
+            l if l.contains("公開日") => d.release_date = date(&text(value)),
+            l if l.contains("シリーズ") => d.series = Some(text(value)).filter(|s| !s.is_empty() && s != "-"),
+            _ => {}
+        }
+    }
+    // The heading is `<title> - <actress>`
+    d.title = d.actor_names.iter().fold(heading.clone(), |t, a| t.trim_end_matches(a.as_str()).trim_end().trim_end_matches('-').trim().to_string());
+    if d.title.is_empty() {
+        d.title = heading;
+    }
+    d.genres = doc.select(sel("tag")).map(text).filter(|t| !t.is_empty()).collect();
+    d.duration_minutes = LD_DURATION.captures(&body).and_then(|c| minutes(&c[1]));
+    d.cover_url = Some(format!("https://www.heyzo.com/contents/3000/{}/images/player_thumbnail.jpg", id));
+    d.trailer_url = Some(format!("https://www.heyzo.com/contents/3000/{}/sample.mp4", id));
+    Ok(Some(d))
+}
+
+const TOKYO_HOT_BASE: &str = "https://my.tokyo-hot.com";
+
+fn tokyo_hot_search_url(id: &str) -> String {
+    format!("{}/product/?q={}&lang=ja", TOKYO_HOT_BASE, id)
+}
+
+async fn tokyo_hot(code: &str, id: &str) -> Result<Option<AvDetail>> {
+    let sel = selectors::tokyohot;
+    let results = crate::scraper::get_text(&CLIENT, &tokyo_hot_search_url(id)).await?;
+    let product = {
+        let doc = scraper::Html::parse_document(&results);
+        let links: Vec<(String, String)> = doc
+            .select(sel("result"))
+            .filter_map(|card| {
+                let href = card.select(sel("result_link")).find_map(|a| a.value().attr("href")).or(card.value().attr("href"))?;
+                Some((absolute(TOKYO_HOT_BASE, href), text(card).to_lowercase()))
+            })
+            .collect();
+        // The search is loose (`n123` also finds `n1234`): prefer the card naming the id
+        links.iter().find(|(_, t)| t.split(|c: char| !c.is_ascii_alphanumeric()).any(|w| w == id)).or(links.first()).map(|(u, _)| u.clone())
+    };
+    let Some(url) = product else { return Ok(None) };
+    util::debug(format!("tokyo-hot: {}", url));
+    let body = crate::scraper::get_text(&CLIENT, &url).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let mut d = blank(code, Studio::TokyoHot);
+    d.title = doc.select(sel("title")).next().map(text).unwrap_or_default();
+    d.plot = doc.select(sel("plot")).next().map(text).filter(|p| !p.is_empty());
+    // `<dl>` of alternating `<dt>` labels and `<dd>` values
+    for (label, value) in doc.select(sel("info_label")).map(text).zip(doc.select(sel("info_value"))) {
+        match label.as_str() {
+            l if l.contains("出演") => d.actor_names = items(value, sel("info_item")),
+            l if l.contains("配信開始日") || l.contains("配信日") => d.release_date = date(&text(value)),
+            l if l.contains("収録時間") => d.duration_minutes = minutes(&text(value)),
+            l if l.contains("シリーズ") => d.series = Some(text(value)).filter(|s| !s.is_empty()),
+            l if l.contains("レーベル") => d.label = Some(text(value)).filter(|s| !s.is_empty()),
+            l if l.contains("タグ") || l.contains("カテゴリ") => d.genres.extend(items(value, sel("info_item"))),
+            // A search hit for another title
+            l if l.contains("作品番号") && !text(value).eq_ignore_ascii_case(id) => return Ok(None),
+            _ => {}
+        }
+    }
+    d.cover_url = doc
+        .select(sel("cover"))
+        .find_map(|el| el.value().attr("poster").or(el.value().attr("href")).or(el.value().attr("src")))
+        .map(|h| absolute(TOKYO_HOT_BASE, h));
+    d.trailer_url = doc.select(sel("trailer")).find_map(|el| el.value().attr("src")).map(|h| absolute(TOKYO_HOT_BASE, h));
+    // The gallery: screen captures, then the video's own stills
+    for el in doc.select(sel("gallery")) {
+        if let Some(href) = el.value().attr("href") {
+            let img = absolute(TOKYO_HOT_BASE, href);
+            if !d.preview_images.contains(&img) {
+                d.preview_images.push(img);
+            }
+        }
+    }
+    Ok(Some(d))
+}
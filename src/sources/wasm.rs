@@ -0,0 +1,161 @@
+//! WebAssembly source plugins: a WASI command module (`wasm32-wasip1`, e.g. a Rust or Go
+//! program built for that target) run in-process by wasmtime, with the request on its stdin
+//! and the response read from its stdout — the same contract as an executable plugin (see
+//! `sources::plugin`). The module sees its `args` and nothing else: no environment, no
+//! files, no network. `timeout_secs` interrupts it wherever it is, and memory is capped.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+use wasmtime_wasi::p1::{self, WasiP1Ctx};
+use wasmtime_wasi::p2::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::{I32Exit, WasiCtxBuilder};
+
+use super::plugin::{self, Output};
+use crate::config::PluginConfig;
+
+/// Linear memory one run may grow to
+const MEMORY_LIMIT: usize = 256 << 20;
+/// stdout and stderr kept per run
+const OUTPUT_LIMIT: usize = 16 << 20;
+/// How often the engine's epoch ticks; timeouts are counted in these
+const TICK: Duration = Duration::from_millis(100);
+
+/// One engine for every plugin, with a thread ticking its epoch so running modules can be
+/// interrupted at their deadline
+static ENGINE: LazyLock<std::result::Result<Engine, String>> = LazyLock::new(|| {
+    let mut config = Config::new();
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config).map_err(|e| format!("{:#}", e))?;
+    let ticking = engine.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(TICK);
+        ticking.increment_epoch();
+    });
+    Ok(engine)
+});
+
+/// Compiled modules by path, so each is compiled once per run of av
+static MODULES: LazyLock<Mutex<HashMap<PathBuf, Module>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct State {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+fn engine() -> Result<&'static Engine> {
+    ENGINE.as_ref().map_err(|e| anyhow!("无法初始化 WebAssembly 引擎: {}", e))
+}
+
+fn module(p: &PluginConfig, path: &Path) -> Result<Module> {
+    if let Some(m) = MODULES.lock().unwrap().get(path) {
+        return Ok(m.clone());
+    }
+    let m = Module::from_file(engine()?, path).map_err(|e| anyhow!("{:#}", e)).with_context(|| format!("无法加载插件 {} 的模块 {}", p.name, path.display()))?;
+    MODULES.lock().unwrap().insert(path.to_path_buf(), m.clone());
+    Ok(m)
+}
+
+fn run_blocking(p: &PluginConfig, path: &Path, request: &str) -> Result<Output> {
+    let engine = engine()?;
+    let module = module(p, path)?;
+    let stdout = MemoryOutputPipe::new(OUTPUT_LIMIT);
+    let stderr = MemoryOutputPipe::new(OUTPUT_LIMIT);
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| p.name.clone());
+    let wasi = WasiCtxBuilder::new()
+        .stdin(MemoryInputPipe::new(request.as_bytes().to_vec()))
+        .stdout(stdout.clone())
+        .stderr(stderr.clone())
+        .arg(name)
+        .args(&p.args)
+        .build_p1();
+    let limits = StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build();
+    let mut store = Store::new(engine, State { wasi, limits });
+    store.limiter(|s| &mut s.limits);
+    let ticks = plugin::timeout(p).as_millis().div_ceil(TICK.as_millis()) as u64;
+    store.set_epoch_deadline(ticks);
+
+    let mut linker: Linker<State> = Linker::new(engine);
+    p1::add_to_linker_sync(&mut linker, |s: &mut State| &mut s.wasi).map_err(|e| anyhow!("{:#}", e))?;
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .map_err(|e| anyhow!("{:#}", e))
+        .with_context(|| format!("无法实例化插件 {}", p.name))?;
+    let start = instance
+        .get_typed_func::<(), ()>(&mut store, "_start")
+        .map_err(|_| anyhow!("插件 {} 不是 WASI 命令模块（缺少 _start）", p.name))?;
+    // `proc_exit` ends the run with an error carrying the status
+    let status = match start.call(&mut store, ()) {
+        Ok(()) => 0,
+        Err(e) => match e.downcast_ref::<I32Exit>() {
+            Some(exit) => exit.0,
+            None if e.downcast_ref::<Trap>() == Some(&Trap::Interrupt) => bail!("插件 {} 超时（{} 秒）", p.name, p.timeout_secs),
+            None => return Err(anyhow!("{:#}", e)).with_context(|| format!("插件 {} 运行出错", p.name)),
+        },
+    };
+    drop(store);
+    Ok(Output { status: Some(status), stdout: stdout.contents().to_vec(), stderr: stderr.contents().to_vec() })
+}
+
+pub async fn run(p: &PluginConfig, path: &Path, request: &str) -> Result<Output> {
+    let (p, path, request) = (p.clone(), path.to_path_buf(), request.to_string());
+    tokio::task::spawn_blocking(move || run_blocking(&p, &path, &request)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PluginMode;
+
+    /// Copies what it reads from stdin to stdout, then exits with `status`
+    fn echo(status: i32) -> String {
+        format!(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_read" (func $read (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_write" (func $write (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+                (memory (export "memory") 1)
+                (func (export "_start")
+                    (i32.store (i32.const 0) (i32.const 64))
+                    (i32.store (i32.const 4) (i32.const 1024))
+                    (drop (call $read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+                    (i32.store (i32.const 4) (i32.load (i32.const 8)))
+                    (drop (call $write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+                    (call $exit (i32.const {}))))"#,
+            status
+        )
+    }
+
+    fn plugin(dir: &Path, name: &str, source: &str, timeout_secs: u64) -> (PluginConfig, PathBuf) {
+        let path = dir.join(format!("{}.wat", name));
+        std::fs::write(&path, source).unwrap();
+        let p = PluginConfig { name: name.into(), command: String::new(), module: Some(path.clone()), args: Vec::new(), mode: PluginMode::Merge, timeout_secs };
+        (p, path)
+    }
+
+    #[test]
+    fn runs_wasi_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = "{\"action\":\"detail\",\"code\":\"ABP-123\"}\n";
+
+        let (p, path) = plugin(dir.path(), "echo", &echo(0), 5);
+        let out = run_blocking(&p, &path, request).unwrap();
+        assert_eq!((out.status, String::from_utf8(out.stdout).unwrap().as_str()), (Some(0), request));
+
+        let (p, path) = plugin(dir.path(), "failing", &echo(3), 5);
+        assert_eq!(run_blocking(&p, &path, request).unwrap().status, Some(3));
+
+        let (p, path) = plugin(dir.path(), "spinning", r#"(module (memory (export "memory") 1) (func (export "_start") (loop (br 0))))"#, 1);
+        let err = run_blocking(&p, &path, request).err().unwrap();
+        assert!(format!("{:#}", err).contains("超时"), "{:#}", err);
+
+        let (p, path) = plugin(dir.path(), "library", r#"(module (func (export "handle")))"#, 5);
+        assert!(format!("{:#}", run_blocking(&p, &path, request).err().unwrap()).contains("_start"));
+
+        let (p, path) = plugin(dir.path(), "sneaky", r#"(module (import "env" "system" (func)) (func (export "_start")))"#, 5);
+        assert!(run_blocking(&p, &path, request).is_err());
+    }
+}
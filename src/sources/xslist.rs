@@ -0,0 +1,155 @@
+//! xslist.org, an actress database with each profile in Japanese, English and Chinese:
+//! the bio, measurements, and the names each language knows her by. Backs `actors
+//! profile`, and gives `list` other names to try when JavDB doesn't know the one given.
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+use urlencoding::encode;
+
+use crate::selectors;
+use crate::sources::text;
+use crate::util;
+
+const BASE: &str = "https://xslist.org";
+
+/// The site's languages, the one whose page the measurements are read from first
+const LANGS: [&str; 3] = ["ja", "en", "zh"];
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| crate::scraper::client_builder().build().expect("client build"));
+
+static MODEL_ID: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/model/(\d+)\.html").unwrap());
+static DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{4})\s*[-/年]\s*(\d{1,2})\s*[-/月]\s*(\d{1,2})").unwrap());
+static HEIGHT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{3})\s*cm").unwrap());
+static SIZES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"B\s*(\d{2,3})[^W]{0,12}W\s*(\d{2,3})[^H]{0,12}H\s*(\d{2,3})").unwrap());
+static CUP: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(?:カップ|cup|罩杯)[^A-Za-z0-9]{0,6}([A-O])(?:[^A-Za-z]|$)").unwrap());
+static DEBUT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(?:デビュー|debut|出道)[^\d]{0,12}(\d{4})").unwrap());
+static ALIASES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(?:別名|aliases|别名)\s*[:：]\s*([^\n]+)").unwrap());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub url: String,
+    /// Her name on each language's page: `ja`, `en`, `zh`
+    pub names: BTreeMap<String, String>,
+    /// Every other name she is listed under, in any language
+    pub aliases: Vec<String>,
+    /// `YYYY-MM-DD`
+    pub birth_date: Option<String>,
+    pub height_cm: Option<u16>,
+    pub bust: Option<u16>,
+    pub waist: Option<u16>,
+    pub hip: Option<u16>,
+    pub cup: Option<String>,
+    pub debut_year: Option<u16>,
+    pub bio: Option<String>,
+}
+
+/// Keeps each paragraph on a line of its own, for the `label: value` lines
+fn lines(el: scraper::ElementRef) -> String {
+    el.text().map(str::trim).filter(|t| !t.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+/// The model id of the best hit for `name`: the one named exactly that, else the first
+async fn model_id(name: &str) -> Result<Option<String>> {
+    let url = format!("{}/search?query={}&lg=ja", BASE, encode(name.trim()));
+    util::debug(format!("xslist search: {}", url));
+    let body = crate::scraper::get_text(&CLIENT, &url).await?;
+    let doc = scraper::Html::parse_document(&body);
+    let hits: Vec<(String, String)> = doc
+        .select(selectors::xslist("result"))
+        .filter_map(|a| Some((MODEL_ID.captures(a.value().attr("href")?)?[1].to_string(), text(a))))
+        .collect();
+    let wanted = name.trim().to_lowercase();
+    Ok(hits.iter().find(|(_, t)| t.to_lowercase() == wanted).or(hits.first()).map(|(id, _)| id.clone()))
+}
+
+fn model_url(id: &str, lang: &str) -> String {
+    format!("{}/{}/model/{}.html", BASE, lang, id)
+}
+
+/// `name`'s profile; `Ok(None)` when xslist has nobody by that name
+pub async fn profile(name: &str) -> Result<Option<Profile>> {
+    let Some(id) = model_id(name).await? else { return Ok(None) };
+    let mut p = Profile {
+        name: name.trim().to_string(),
+        url: model_url(&id, LANGS[0]),
+        names: BTreeMap::new(),
+        aliases: Vec::new(),
+        birth_date: None,
+        height_cm: None,
+        bust: None,
+        waist: None,
+        hip: None,
+        cup: None,
+        debut_year: None,
+        bio: None,
+    };
+    for lang in LANGS {
+        let url = model_url(&id, lang);
+        let body = match crate::scraper::get_text(&CLIENT, &url).await {
+            Ok(b) => b,
+            // One language's page missing still leaves the others
+            Err(e) => {
+                util::debug(format!("xslist {}: {:#}", url, e));
+                continue;
+            }
+        };
+        let doc = scraper::Html::parse_document(&body);
+        if let Some(n) = doc.select(selectors::xslist("name")).next().map(text).filter(|n| !n.is_empty()) {
+            p.names.insert(lang.to_string(), n);
+        }
+        let info = doc.select(selectors::xslist("info")).map(lines).collect::<Vec<_>>().join("\n");
+        if let Some(c) = ALIASES.captures(&info) {
+            p.aliases.extend(c[1].split([',', '、', '/', '，']).map(|a| a.trim().to_string()).filter(|a| !a.is_empty()));
+        }
+        if p.birth_date.is_none() {
+            p.birth_date = DATE.captures(&info).map(|c| format!("{}-{:0>2}-{:0>2}", &c[1], &c[2], &c[3]));
+        }
+        if p.height_cm.is_none() {
+            p.height_cm = HEIGHT.captures(&info).and_then(|c| c[1].parse().ok());
+        }
+        if p.bust.is_none() {
+            if let Some(c) = SIZES.captures(&info) {
+                (p.bust, p.waist, p.hip) = (c[1].parse().ok(), c[2].parse().ok(), c[3].parse().ok());
+            }
+        }
+        if p.cup.is_none() {
+            p.cup = CUP.captures(&info).map(|c| c[1].to_ascii_uppercase());
+        }
+        if p.debut_year.is_none() {
+            p.debut_year = DEBUT.captures(&info).and_then(|c| c[1].parse().ok());
+        }
+        if p.bio.is_none() {
+            p.bio = doc.select(selectors::xslist("bio")).next().map(text).filter(|b| !b.is_empty());
+        }
+    }
+    if p.names.is_empty() && p.birth_date.is_none() {
+        return Ok(None);
+    }
+    // The other languages' names count as aliases too
+    let listed: Vec<String> = p.aliases.drain(..).chain(p.names.values().cloned()).collect();
+    let mut seen = vec![p.name.clone()];
+    for a in listed {
+        if !seen.contains(&a) {
+            seen.push(a.clone());
+            p.aliases.push(a);
+        }
+    }
+    Ok(Some(p))
+}
+
+/// Other names for `name`, to retry name lookups with; empty when xslist doesn't know her
+pub async fn aliases(name: &str) -> Vec<String> {
+    match profile(name).await {
+        Ok(Some(p)) => p.aliases,
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            util::debug(format!("xslist aliases for {}: {:#}", name, e));
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// `$AV_DATA_DIR`, else `<data dir>/av` (e.g. `~/.local/share/av`). On Windows that's
+/// `%LOCALAPPDATA%\av`, keeping caches out of the roaming profile, unless an
+/// `%APPDATA%\av` from an older version already exists.
+pub fn data_dir() -> PathBuf {
+    if let Ok(p) = std::env::var("AV_DATA_DIR") {
+        if !p.trim().is_empty() {
+            return PathBuf::from(p);
+        }
+    }
+    if cfg!(windows) {
+        let roaming = dirs::data_dir().map(|d| d.join("av"));
+        if let Some(r) = roaming.filter(|r| r.exists()) {
+            return r;
+        }
+        if let Some(local) = dirs::data_local_dir() {
+            return local.join("av");
+        }
+    }
+    dirs::data_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("av")
+}
+
+pub fn path(name: &str) -> PathBuf {
+    data_dir().join(format!("{}.json", name))
+}
+
+/// Load a JSON document from the data dir; a missing file yields `T::default()`.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> Result<T> {
+    let p = path(name);
+    if !p.exists() {
+        return Ok(T::default());
+    }
+    let text = std::fs::read_to_string(&p).with_context(|| format!("读取数据文件失败: {}", p.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("数据文件已损坏: {}", p.display()))
+}
+
+/// Write a JSON document atomically (temp file + rename) so a crash never leaves it half-written.
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let dir = data_dir();
+    std::fs::create_dir_all(&dir).with_context(|| format!("创建数据目录失败: {}", dir.display()))?;
+    let p = path(name);
+    let tmp = dir.join(format!(".{}.json.tmp", name));
+    let text = serde_json::to_string_pretty(value)?;
+    std::fs::write(&tmp, text).with_context(|| format!("写入数据文件失败: {}", tmp.display()))?;
+    std::fs::rename(&tmp, &p).with_context(|| format!("写入数据文件失败: {}", p.display()))?;
+    Ok(())
+}
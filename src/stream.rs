@@ -0,0 +1,128 @@
+//! Finds the video stream behind a watch page: an HLS playlist (or MP4) referenced in the
+//! page itself, or in an embedded player the page loads in an iframe. Embeds are followed a
+//! few levels deep, each fetched with its parent page as Referer the way a browser would.
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use reqwest::Url;
+use scraper::{Html, Selector};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use crate::scraper as av_scraper;
+use crate::types::PlayCandidate;
+use crate::ua;
+use crate::util;
+
+/// Embedded players followed below a watch page
+const MAX_DEPTH: usize = 3;
+
+/// HLS playlists and MP4 files referenced anywhere in a page, including inside scripts
+static STREAM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"https?://[^\s"'<>()\\]+?\.(m3u8|mp4)(\?[^\s"'<>()\\]*)?"#).unwrap());
+
+static EMBED: LazyLock<Selector> = LazyLock::new(|| Selector::parse("iframe[src], iframe[data-src], embed[src]").unwrap());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stream {
+    pub url: String,
+    /// The page the stream was found in; most hosts refuse the stream without it as Referer
+    pub referer: String,
+    /// The candidate watch page the search started from, and where it came from
+    pub page: String,
+    pub source: String,
+    /// Send this User-Agent too: some hosts bind the stream token to it
+    pub user_agent: String,
+}
+
+fn is_stream(url: &str) -> bool {
+    STREAM.find(url).is_some_and(|m| m.start() == 0 && m.end() == url.len())
+}
+
+/// First direct stream in a page body, HLS preferred. JSON-escaped slashes (`https:\/\/`)
+/// inside inline scripts are undone first.
+fn find_stream(body: &str) -> Option<String> {
+    let body = body.replace("\\/", "/");
+    let streams: Vec<&str> = STREAM.find_iter(&body).map(|m| m.as_str()).collect();
+    streams
+        .iter()
+        .find(|s| s.contains(".m3u8"))
+        .or_else(|| streams.first())
+        .map(|s| s.replace("&amp;", "&"))
+}
+
+/// Absolute URLs of the players embedded in a page
+fn embeds(body: &str, base: &str) -> Vec<String> {
+    let Ok(base) = Url::parse(base) else { return Vec::new() };
+    let doc = Html::parse_document(body);
+    let mut out: Vec<String> = Vec::new();
+    for el in doc.select(&EMBED) {
+        let Some(src) = el.value().attr("src").or_else(|| el.value().attr("data-src")) else { continue };
+        let Ok(url) = base.join(src.trim()) else { continue };
+        if matches!(url.scheme(), "http" | "https") && !out.contains(&url.to_string()) {
+            out.push(url.to_string());
+        }
+    }
+    out
+}
+
+/// Stream behind `page` and the page it was found in
+async fn resolve(page: &str) -> Result<Option<(String, String)>> {
+    if is_stream(page) {
+        return Ok(Some((page.to_string(), page.to_string())));
+    }
+    let c = av_scraper::client();
+    let mut seen: HashSet<String> = HashSet::from([page.to_string()]);
+    // (url, referer) pairs of the current level, breadth first
+    let mut level: Vec<(String, Option<String>)> = vec![(page.to_string(), None)];
+    for depth in 0..=MAX_DEPTH {
+        let mut next = Vec::new();
+        for (url, referer) in level {
+            let body = match &referer {
+                None => av_scraper::get_text(&c, &url).await?,
+                Some(r) => match av_scraper::get_text_from(&c, &url, r).await {
+                    Ok(b) => b,
+                    Err(e) => {
+                        util::debug(format!("stream: embed {} failed: {:#}", url, e));
+                        continue;
+                    }
+                },
+            };
+            if let Some(s) = find_stream(&body) {
+                util::debug(format!("stream: {} found in {} (depth {})", s, url, depth));
+                return Ok(Some((s, url)));
+            }
+            for e in embeds(&body, &url) {
+                if seen.insert(e.clone()) {
+                    next.push((e, Some(url.clone())));
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        level = next;
+    }
+    Ok(None)
+}
+
+/// The stream of the first candidate that has one
+pub async fn first(candidates: &[PlayCandidate]) -> Result<Stream> {
+    for c in candidates {
+        match resolve(&c.url).await {
+            Ok(Some((url, referer))) => {
+                return Ok(Stream {
+                    url,
+                    referer,
+                    page: c.url.clone(),
+                    source: c.source.clone(),
+                    user_agent: ua::session_profile().user_agent.clone(),
+                })
+            }
+            Ok(None) => util::debug(format!("stream: nothing in {}", c.url)),
+            Err(e) => eprintln!("[WARN] 无法读取 {}: {:#}", c.url, e),
+        }
+    }
+    bail!("未能从候选页面中解析出视频流，可去掉 --player / --url-only 在浏览器中打开")
+}
@@ -0,0 +1,357 @@
+//! Subscriptions to actors, studios and series: `av subscribe actor|studio|series NAME`
+//! keeps a snapshot of the listing, `av subscribe check` lists it again and reports what
+//! was not there before, after the subscription's own filters.
+
+use anyhow::{bail, Result};
+use chrono::Local;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::batch;
+use crate::magnet;
+use crate::notify;
+use crate::scraper;
+use crate::sources::mock;
+use crate::store;
+use crate::types::{AvDetail, AvItem, MagnetInfo};
+use crate::util;
+
+const SUBSCRIPTIONS: &str = "subscriptions";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Actor,
+    Studio,
+    Series,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Actor => "actor",
+            Kind::Studio => "studio",
+            Kind::Series => "series",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Actor => "演员",
+            Kind::Studio => "片商",
+            Kind::Series => "系列",
+        }
+    }
+}
+
+/// What a new title must satisfy before it is reported, notified or downloaded
+#[derive(Debug, Clone, Default, Serialize, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct Filters {
+    /// 只保留评分不低于该值的新作品（如 4.0）
+    #[arg(long, value_name = "RATING")]
+    pub min_rating: Option<f32>,
+    /// 只保留有中文字幕磁力的新作品
+    #[arg(long)]
+    pub subtitled: bool,
+    /// Taken from the global `--uncen` when subscribing
+    #[arg(skip)]
+    pub uncen: bool,
+}
+
+impl Filters {
+    pub fn with_uncen(self, uncen: bool) -> Filters {
+        Filters { uncen, ..self }
+    }
+
+    fn needs_detail(&self) -> bool {
+        self.min_rating.is_some() || self.subtitled
+    }
+
+    fn accepts_item(&self, item: &AvItem) -> bool {
+        !self.uncen || util::looks_uncensored(&item.title)
+    }
+
+    fn accepts(&self, d: &AvDetail) -> bool {
+        self.min_rating.is_none_or(|min| d.rating.is_some_and(|r| r >= min))
+            && (!self.subtitled || d.magnet_infos.iter().any(magnet::has_subtitles))
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(r) = self.min_rating {
+            parts.push(format!("评分≥{}", r));
+        }
+        if self.uncen {
+            parts.push("无码".to_string());
+        }
+        if self.subtitled {
+            parts.push("字幕".to_string());
+        }
+        parts.join("、")
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Subscription {
+    kind: Kind,
+    name: String,
+    /// JavDB listing page of a studio or series, resolved once when subscribing
+    url: Option<String>,
+    filters: Filters,
+    /// Local date the subscription was added, `YYYY-MM-DD`
+    since: String,
+    /// Local time of the last successful check
+    checked: Option<String>,
+    /// Every code listed so far, including those the filters dropped
+    codes: Vec<String>,
+}
+
+impl Subscription {
+    async fn listing(&self) -> Result<Vec<AvItem>> {
+        match (self.kind, &self.url) {
+            (Kind::Actor, _) => scraper::list_actor_titles(&self.name).await,
+            _ if mock::is_enabled() => Ok(mock::list_studio_or_series(&self.name, self.kind == Kind::Series)),
+            (_, Some(url)) => scraper::list_javdb_page(url).await,
+            (_, None) => bail!("{} {} 缺少列表地址，请重新订阅", self.kind.label(), self.name),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct NewTitles {
+    kind: Kind,
+    name: String,
+    items: Vec<AvItem>,
+}
+
+fn same(s: &Subscription, kind: Option<Kind>, name: &str) -> bool {
+    kind.is_none_or(|k| s.kind == k) && s.name.eq_ignore_ascii_case(name)
+}
+
+fn save(subs: &[Subscription]) -> Result<()> {
+    if util::dry_run_skip("将更新订阅（未保存）") {
+        return Ok(());
+    }
+    store::save(SUBSCRIPTIONS, &subs)
+}
+
+fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// `av subscribe actor|studio|series NAME`: record the current listing as already known
+pub async fn add(kind: Kind, name: &str, filters: Filters) -> Result<()> {
+    let name = name.trim();
+    let mut subs: Vec<Subscription> = store::load(SUBSCRIPTIONS)?;
+    if subs.iter().any(|s| same(s, Some(kind), name)) {
+        util::note(format!("已订阅{} {}", kind.label(), name.bold()));
+        return Ok(());
+    }
+    let url = match kind {
+        Kind::Studio if !mock::is_enabled() => Some(scraper::javdb_listing_url("studio_link", name).await?),
+        Kind::Series if !mock::is_enabled() => Some(scraper::javdb_listing_url("series_link", name).await?),
+        _ => None,
+    };
+    let mut sub = Subscription {
+        kind,
+        name: name.to_string(),
+        url,
+        filters,
+        since: Local::now().format("%Y-%m-%d").to_string(),
+        checked: Some(now()),
+        codes: Vec::new(),
+    };
+    let items = sub.listing().await?;
+    if items.is_empty() {
+        bail!("没有找到{} {} 的作品，请检查名称", kind.label(), name);
+    }
+    sub.codes = items.iter().map(|i| i.code.clone()).collect();
+    subs.push(sub);
+    save(&subs)?;
+    util::note(format!("{}{} {}（当前 {} 部作品）", "已订阅".green().bold(), kind.label(), name.bold(), items.len()));
+    Ok(())
+}
+
+/// `av subscribe remove NAME [--kind KIND]`
+pub fn remove(name: &str, kind: Option<Kind>) -> Result<()> {
+    let mut subs: Vec<Subscription> = store::load(SUBSCRIPTIONS)?;
+    let before = subs.len();
+    subs.retain(|s| !same(s, kind, name.trim()));
+    if subs.len() == before {
+        bail!("没有订阅 {}", name.trim());
+    }
+    save(&subs)?;
+    util::note(format!("已取消订阅 {}", name.trim().bold()));
+    Ok(())
+}
+
+/// `av subscribe list`
+pub fn list(json: bool) -> Result<()> {
+    let subs: Vec<Subscription> = store::load(SUBSCRIPTIONS)?;
+    if json {
+        util::print_output(&subs, true);
+        return Ok(());
+    }
+    if subs.is_empty() {
+        println!("{}", "还没有任何订阅，用 av subscribe actor|studio|series <名称> 添加".yellow());
+        return Ok(());
+    }
+    for s in &subs {
+        let checked = s.checked.as_deref().unwrap_or("-");
+        let filters = s.filters.describe();
+        let filters = if filters.is_empty() { String::new() } else { format!("  [{}]", filters) };
+        println!(
+            "{} {}  {} 部{}  {}",
+            s.kind.label(),
+            s.name.bold(),
+            s.codes.len(),
+            filters,
+            format!("订阅于 {}，上次检查 {}", s.since, checked).dimmed()
+        );
+    }
+    Ok(())
+}
+
+/// Best-ranked magnet, among the subtitled ones when the filter asks for subtitles
+fn pick_magnet(d: &AvDetail, filters: &Filters) -> Option<String> {
+    if filters.subtitled {
+        let m = d.magnet_infos.iter().filter(|m| magnet::has_subtitles(m)).min_by(|a, b| MagnetInfo::ranking(a, b));
+        if let Some(m) = m {
+            return Some(m.url.clone());
+        }
+    }
+    util::best_magnet(d)
+}
+
+/// List every subscription again, `--jobs` at a time, in subscription order
+async fn list_all(subs: &[Subscription]) -> Vec<(usize, Result<Vec<AvItem>>)> {
+    let permits = Arc::new(Semaphore::new(util::jobs()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (idx, sub) in subs.iter().cloned().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (idx, sub.listing().await)
+        });
+    }
+    let mut listed = tasks.join_all().await;
+    listed.sort_by_key(|(idx, _)| *idx);
+    listed
+}
+
+/// Each subscription's current listing after its filters, labelled `演员 NAME`, for
+/// `av calendar`. The snapshots are left alone; a listing that fails is warned about.
+pub async fn listings() -> Result<Vec<(String, Vec<AvItem>)>> {
+    let subs: Vec<Subscription> = store::load(SUBSCRIPTIONS)?;
+    if subs.is_empty() {
+        bail!("还没有任何订阅，用 av subscribe actor|studio|series <名称> 添加");
+    }
+    let mut out = Vec::new();
+    for (idx, items) in list_all(&subs).await {
+        let sub = &subs[idx];
+        match items {
+            Ok(items) => out.push((
+                format!("{} {}", sub.kind.label(), sub.name),
+                items.into_iter().filter(|i| sub.filters.accepts_item(i)).collect(),
+            )),
+            Err(e) => eprintln!("[WARN] 获取{} {} 的作品失败: {:#}", sub.kind.label(), sub.name, e),
+        }
+    }
+    Ok(out)
+}
+
+/// `av subscribe check`: list every subscription again (`--jobs` at a time) and add what
+/// is missing from its snapshot. New titles that pass the filters are reported, and with
+/// `notify` / `queue` announced as `new_release` and handed to the downloader. A listing
+/// that fails keeps its snapshot.
+pub async fn check(queue: bool, notify: bool, uncen: bool, json: bool) -> Result<()> {
+    let mut subs: Vec<Subscription> = store::load(SUBSCRIPTIONS)?;
+    if subs.is_empty() {
+        bail!("还没有任何订阅，用 av subscribe actor|studio|series <名称> 添加");
+    }
+    let listed = list_all(&subs).await;
+
+    let mut found = Vec::new();
+    for (idx, items) in listed {
+        let sub = &mut subs[idx];
+        let items = match items {
+            Ok(items) => items,
+            Err(e) => {
+                eprintln!("[WARN] 获取{} {} 的作品失败: {:#}", sub.kind.label(), sub.name, e);
+                continue;
+            }
+        };
+        let known: HashSet<&String> = sub.codes.iter().collect();
+        let fresh: Vec<AvItem> = items.into_iter().filter(|i| !known.contains(&i.code)).collect();
+        sub.codes.extend(fresh.iter().map(|i| i.code.clone()));
+        sub.checked = Some(now());
+        let fresh: Vec<AvItem> = fresh
+            .into_iter()
+            .filter(|i| sub.filters.accepts_item(i) && (!uncen || util::looks_uncensored(&i.title)))
+            .collect();
+        if fresh.is_empty() {
+            continue;
+        }
+        let details = if sub.filters.needs_detail() || queue || notify {
+            let details = scraper::fetch_details(fresh.iter().map(|i| i.code.clone()).collect()).await;
+            details.into_iter().filter(|d| sub.filters.accepts(d)).collect()
+        } else {
+            Vec::new()
+        };
+        let items = if sub.filters.needs_detail() {
+            details.iter().map(AvItem::from).collect()
+        } else {
+            fresh
+        };
+        if !items.is_empty() {
+            found.push((idx, NewTitles { kind: sub.kind, name: sub.name.clone(), items }, details));
+        }
+    }
+    save(&subs)?;
+
+    if json {
+        util::print_output(&found.iter().map(|(_, f, _)| f).collect::<Vec<_>>(), true);
+    } else if found.is_empty() {
+        println!("{}", "订阅没有新作品".yellow());
+    } else {
+        for (_, f, _) in &found {
+            println!("{} {} {}", f.kind.label(), f.name.green().bold(), format!("新作品 {} 部", f.items.len()).bold());
+            for i in &f.items {
+                println!("  {}  {}", i.code.bold(), i.title);
+            }
+        }
+    }
+    // A title can come up under several subscriptions (its actor and its studio); act on it once
+    let mut handled = HashSet::new();
+    let mut queued = Vec::new();
+    for (idx, f, details) in found {
+        let sub = &subs[idx];
+        for d in details {
+            if !handled.insert(d.code.clone()) {
+                continue;
+            }
+            if notify {
+                let source = format!("subscribe:{}:{}", f.kind.as_str(), f.name);
+                notify::emit(&notify::Event::NewRelease { source, detail: Box::new(d.clone()) }).await;
+            }
+            if queue {
+                match pick_magnet(&d, &sub.filters) {
+                    Some(magnet) => queued.push(batch::Entry::new(&d.code, Some(magnet))),
+                    None => eprintln!("[WARN] {} 没有可用的磁力链接", d.code),
+                }
+            }
+        }
+    }
+    // The new titles are already recorded as known, so the job is what keeps an
+    // interrupted queue from being lost
+    if !queued.is_empty() {
+        batch::download(&mut batch::Job::start(batch::Kind::Download, queued, true)?).await?;
+    }
+    Ok(())
+}
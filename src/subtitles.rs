@@ -0,0 +1,284 @@
+//! `av sub`: subtitles for a code from the online subtitle archives, ranked by language
+//! and popularity, saved as UTF-8 `.srt` next to where the video would be. With
+//! `--generate`, a code no archive has is transcribed locally with whisper.cpp instead,
+//! and optionally machine-translated.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::code;
+use crate::config::MetadataLang;
+use crate::sources::subtitlecat;
+use crate::table;
+use crate::translate;
+use crate::util;
+use crate::whisper;
+
+/// Cues sent to the translation backend per request
+const TRANSLATE_BATCH: usize = 50;
+
+pub struct SubOptions {
+    /// A directory, or the file to write
+    pub out: Option<PathBuf>,
+    pub list: bool,
+    pub pick: Option<usize>,
+    /// Transcribe the local video when no archive has a subtitle
+    pub generate: bool,
+    /// whisper model name or file; `[subtitles] model` when unset
+    pub model: Option<String>,
+    /// The video to transcribe, instead of looking for it
+    pub file: Option<PathBuf>,
+    /// Also write a machine translation of the generated subtitle
+    pub translate: Option<translate::Lang>,
+}
+
+#[derive(Debug, Serialize)]
+struct Candidate {
+    provider: &'static str,
+    #[serde(flatten)]
+    subtitle: subtitlecat::Subtitle,
+}
+
+#[derive(Debug, Serialize)]
+struct SubOutput {
+    code: String,
+    provider: &'static str,
+    lang: String,
+    url: String,
+    file: PathBuf,
+    /// The encoding the file was uploaded in
+    encoding: String,
+}
+
+#[derive(Debug, Serialize)]
+struct GeneratedOutput {
+    code: String,
+    video: PathBuf,
+    model: PathBuf,
+    file: PathBuf,
+    /// The machine translation, with `--translate`
+    translated: Option<PathBuf>,
+}
+
+/// Languages in the order they are preferred: `--lang` first, then Chinese, English and
+/// the Japanese originals
+fn preferred() -> Vec<&'static str> {
+    let mut langs = match util::lang() {
+        Some(MetadataLang::Ja) => vec!["ja"],
+        Some(MetadataLang::En) => vec!["en"],
+        Some(MetadataLang::Zh) | None => vec![],
+    };
+    for l in ["zh", "en", "ja"] {
+        if !langs.contains(&l) {
+            langs.push(l);
+        }
+    }
+    langs
+}
+
+/// Position of `lang` (`zh-CN`, `en` ...) in the preference; Simplified Chinese before
+/// Traditional, languages not asked for last
+fn lang_rank(lang: &str, preferred: &[&str]) -> (usize, bool) {
+    let base = lang.split('-').next().unwrap_or(lang).to_ascii_lowercase();
+    let pos = preferred.iter().position(|p| *p == base).unwrap_or(preferred.len());
+    (pos, base == "zh" && !matches!(lang.to_ascii_lowercase().as_str(), "zh" | "zh-cn" | "zh-hans"))
+}
+
+/// Every provider's subtitles for `code`, best first. SubtitleCat is the only provider so
+/// far; one that fails is warned about and the rest still count
+async fn candidates(code: &str) -> Result<Vec<Candidate>> {
+    let mut all: Vec<Candidate> = Vec::new();
+    match subtitlecat::search(code).await {
+        Ok(found) => all.extend(found.into_iter().map(|subtitle| Candidate { provider: "subtitlecat", subtitle })),
+        Err(e) => eprintln!("[WARN] SubtitleCat 搜索失败: {:#}", e),
+    }
+    let preferred = preferred();
+    // Stable: equally ranked files keep their provider's order
+    all.sort_by(|a, b| {
+        lang_rank(&a.subtitle.lang, &preferred)
+            .cmp(&lang_rank(&b.subtitle.lang, &preferred))
+            .then_with(|| b.subtitle.downloads.cmp(&a.subtitle.downloads))
+    });
+    Ok(all)
+}
+
+/// `bytes` as UTF-8 text, and the encoding it was in. A BOM decides; valid UTF-8 is kept;
+/// otherwise the legacy encodings are tried, the subtitle language's own first, and the
+/// first that decodes without errors wins
+fn decode(bytes: &[u8], lang: &str) -> (String, &'static str) {
+    if let Some((enc, _)) = encoding_rs::Encoding::for_bom(bytes) {
+        let (text, _) = enc.decode_with_bom_removal(bytes);
+        return (text.into_owned(), enc.name());
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return (text.to_string(), encoding_rs::UTF_8.name());
+    }
+    let legacy = if lang.starts_with("zh") {
+        [encoding_rs::GBK, encoding_rs::BIG5, encoding_rs::SHIFT_JIS]
+    } else {
+        [encoding_rs::SHIFT_JIS, encoding_rs::GBK, encoding_rs::BIG5]
+    };
+    for enc in legacy {
+        if let Some(text) = enc.decode_without_bom_handling_and_without_replacement(bytes) {
+            return (text.into_owned(), enc.name());
+        }
+    }
+    let (text, _, _) = legacy[0].decode(bytes);
+    (text.into_owned(), legacy[0].name())
+}
+
+/// `--out` as given when it names a file, else `<CODE>.<lang>.srt` (the name Jellyfin, Kodi
+/// and Plex pick up next to `<CODE>.mp4`) in it or the current directory
+fn target(out: Option<&Path>, code: &str, lang: &str) -> PathBuf {
+    let name = format!("{}.{}.srt", code, lang);
+    match out {
+        Some(p) if p.is_dir() => p.join(name),
+        Some(p) => p.to_path_buf(),
+        None => PathBuf::from(name),
+    }
+}
+
+/// A translation's file next to the original's: `<CODE>.zh.srt`, or `a.zh.srt` for `-o a.srt`
+fn translated_target(out: Option<&Path>, code: &str, lang: &str) -> PathBuf {
+    match out {
+        Some(p) if !p.is_dir() => p.with_extension(format!("{}.srt", lang)),
+        _ => target(out, code, lang),
+    }
+}
+
+/// The srt with every cue's text translated, numbering and timings kept
+async fn translate_srt(srt: &str, to: translate::Lang) -> Result<String> {
+    let srt = srt.replace("\r\n", "\n");
+    let mut cues: Vec<(Vec<&str>, String)> = Vec::new();
+    for block in srt.split("\n\n").map(str::trim).filter(|b| !b.is_empty()) {
+        let lines: Vec<&str> = block.lines().collect();
+        // Number and timing, then the text
+        let head = lines.iter().position(|l| l.contains("-->")).map_or(lines.len(), |i| i + 1);
+        cues.push((lines[..head].to_vec(), lines[head..].join("\n")));
+    }
+    let texts: Vec<String> = cues.iter().map(|(_, text)| text.clone()).collect();
+    let mut translated = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(TRANSLATE_BATCH) {
+        crate::cancel::check()?;
+        translated.extend(translate::texts(batch, to).await?);
+    }
+    let mut out = String::new();
+    for ((head, _), text) in cues.iter().zip(translated) {
+        out.push_str(&head.join("\n"));
+        out.push('\n');
+        out.push_str(text.trim());
+        out.push_str("\n\n");
+    }
+    Ok(out)
+}
+
+/// `--generate`: transcribe the local video, then translate it with `--translate`
+async fn generate(code: &str, opts: &SubOptions, json: bool) -> Result<()> {
+    let video = match &opts.file {
+        Some(f) if f.is_file() => f.clone(),
+        Some(f) => bail!("文件不存在: {}", f.display()),
+        None => whisper::find_video(code)?,
+    };
+    let model = whisper::model_path(opts.model.as_deref().unwrap_or(&crate::config::get().subtitles.model))?;
+    let path = target(opts.out.as_deref(), code, "ja");
+    let translated_path = opts.translate.map(|lang| translated_target(opts.out.as_deref(), code, lang.as_str()));
+    if util::dry_run_skip(format!("将用 whisper.cpp 识别 {} 并写入 {}", video.display(), path.display())) {
+        return Ok(());
+    }
+    whisper::transcribe(&video, &model, &path).await?;
+    let mut translated = None;
+    if let (Some(lang), Some(out)) = (opts.translate, translated_path) {
+        let srt = std::fs::read_to_string(&path).with_context(|| format!("读取失败: {}", path.display()))?;
+        // The Japanese file is kept either way
+        match translate_srt(&srt, lang).await {
+            Ok(text) => {
+                std::fs::write(&out, text).with_context(|| format!("写入失败: {}", out.display()))?;
+                translated = Some(out);
+            }
+            Err(e) => eprintln!("[WARN] 字幕翻译失败: {:#}", e),
+        }
+    }
+    if json {
+        util::print_output(&GeneratedOutput { code: code.to_string(), video, model, file: path, translated }, true);
+        return Ok(());
+    }
+    println!("{} {} ja → {}（whisper.cpp 生成）", "已保存".green().bold(), code, path.display());
+    if let (Some(lang), Some(t)) = (opts.translate, &translated) {
+        println!("{} {} {} → {}（机器翻译）", "已保存".green().bold(), code, lang.as_str(), t.display());
+    }
+    Ok(())
+}
+
+fn print_list(code: &str, list: &[Candidate]) {
+    println!("{} 共 {} 个字幕", code.bold(), list.len());
+    let rows = list
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| {
+            vec![
+                (idx + 1).to_string(),
+                c.subtitle.lang.clone(),
+                c.subtitle.downloads.to_string(),
+                c.provider.to_string(),
+                c.subtitle.title.clone(),
+            ]
+        })
+        .collect();
+    table::print_rows(&["#", "语言", "下载数", "来源", "标题"], rows);
+}
+
+/// `av sub <code>`: list (`list`) or save the best subtitle, or the `pick`-th one;
+/// generate one when none is found and `generate` is set
+pub async fn run(code: &str, opts: SubOptions, json: bool) -> Result<()> {
+    let code = code::normalize(code);
+    let found = candidates(&code).await?;
+    if found.is_empty() && opts.generate && !opts.list {
+        util::note(format!("在线没有找到 {} 的字幕，改用 whisper.cpp 生成", code));
+        return generate(&code, &opts, json).await;
+    }
+    if found.is_empty() {
+        bail!("没有找到 {} 的字幕（可加 --generate 用本地视频生成）", code);
+    }
+    let (out, list, pick) = (opts.out, opts.list, opts.pick);
+    if list {
+        if json {
+            util::print_output(&found, true);
+        } else {
+            print_list(&code, &found);
+        }
+        return Ok(());
+    }
+    let chosen = match pick {
+        Some(n) => found.get(n.wrapping_sub(1)).with_context(|| format!("没有第 {} 个字幕（共 {} 个，见 --list）", n, found.len()))?,
+        None => &found[0],
+    };
+    let path = target(out.as_deref(), &code, &chosen.subtitle.lang);
+    if util::dry_run_skip(format!("将下载 {} 字幕到 {}: {}", chosen.subtitle.lang, path.display(), chosen.subtitle.url)) {
+        return Ok(());
+    }
+    let bytes = subtitlecat::download(&chosen.subtitle.url).await?;
+    let (text, encoding) = decode(&bytes, &chosen.subtitle.lang);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+    }
+    std::fs::write(&path, text).with_context(|| format!("写入失败: {}", path.display()))?;
+    if json {
+        util::print_output(
+            &SubOutput {
+                code,
+                provider: chosen.provider,
+                lang: chosen.subtitle.lang.clone(),
+                url: chosen.subtitle.url.clone(),
+                file: path,
+                encoding: encoding.to_string(),
+            },
+            true,
+        );
+        return Ok(());
+    }
+    let converted = if encoding == "UTF-8" { String::new() } else { format!("（已从 {} 转为 UTF-8）", encoding) };
+    println!("{} {} {} → {}{}", "已保存".green().bold(), code, chosen.subtitle.lang, path.display(), converted);
+    Ok(())
+}
@@ -0,0 +1,289 @@
+//! The item tables (`list`, `search`, `top`) and the actor table, with columns picked by
+//! `--columns` or `[view] columns` / `[view] actor_columns`, and titles cut to
+//! `--title-width` / `[view] title_width` terminal columns.
+//!
+//! An item table has codes and titles, plus the release dates and scores JavDB's listing
+//! cards show; asking for any other column (actors, seeders, ...), or for a date or
+//! rating some card lacked, fetches every row's detail first, through the detail cache.
+//! The default table shows the card dates without fetching anything.
+
+use anyhow::{bail, Result};
+use colored::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::config;
+use crate::dates;
+use crate::library::Library;
+use crate::magnet;
+use crate::scraper;
+use crate::types::{ActorItem, AvDetail, AvItem, RankingPage};
+use crate::util;
+
+const ITEM_COLUMNS: &[(&str, &str)] = &[
+    ("index", "#"),
+    ("code", "番号"),
+    ("title", "标题"),
+    ("date", "发行日期"),
+    ("actors", "演员"),
+    ("studio", "片商"),
+    ("duration", "时长"),
+    ("rating", "评分"),
+    ("seeders", "做种"),
+    ("magnets", "磁力"),
+    ("genres", "类别"),
+    ("badges", "标记"),
+    ("sources", "来源"),
+    ("alt_titles", "其他标题"),
+];
+/// `date` is left out when no card had one
+const DEFAULT_ITEM_COLUMNS: &[&str] = &["index", "code", "date", "title"];
+
+const ACTOR_COLUMNS: &[(&str, &str)] = &[
+    ("index", "#"),
+    ("name", "演员"),
+    ("rank", "排名"),
+    ("works", "作品数"),
+    ("views", "浏览"),
+    ("debut", "出道"),
+    ("latest", "最新作品"),
+    ("url", "链接"),
+];
+const DEFAULT_ACTOR_COLUMNS: &[&str] = &["index", "name", "rank", "works", "views"];
+
+/// `--columns` and `--title-width` from the command line
+static OVERRIDES: Mutex<(Vec<String>, Option<usize>)> = Mutex::new((Vec::new(), None));
+
+pub fn set_overrides(columns: Vec<String>, title_width: Option<usize>) {
+    *OVERRIDES.lock().unwrap() = (columns, title_width);
+}
+
+/// The chosen columns, checked against `known`, and whether they were asked for rather
+/// than the defaults; the command line wins over the config
+fn columns(configured: &[String], defaults: &[&str], known: &[(&'static str, &str)]) -> Result<(Vec<&'static str>, bool)> {
+    let overrides = OVERRIDES.lock().unwrap().0.clone();
+    let (chosen, explicit): (Vec<String>, bool) = if !overrides.is_empty() {
+        (overrides, true)
+    } else if !configured.is_empty() {
+        (configured.to_vec(), true)
+    } else {
+        (defaults.iter().map(|s| s.to_string()).collect(), false)
+    };
+    let cols = chosen
+        .iter()
+        .map(|name| {
+            let name = name.trim().to_lowercase();
+            match known.iter().find(|(k, _)| *k == name) {
+                Some((key, _)) => Ok(*key),
+                None => {
+                    let valid: Vec<&str> = known.iter().map(|(k, _)| *k).collect();
+                    bail!("未知的列: {}（可用: {}）", name, valid.join(", "))
+                }
+            }
+        })
+        .collect::<Result<_>>()?;
+    Ok((cols, explicit))
+}
+
+fn title_width() -> Option<usize> {
+    OVERRIDES.lock().unwrap().1.or(config::get().view.title_width).filter(|w| *w > 0)
+}
+
+/// One cell; `badge` is a colored prefix, kept out of the width and wrapping arithmetic
+/// until printing
+struct Cell {
+    badge: Option<String>,
+    text: String,
+}
+
+impl Cell {
+    fn new(text: impl Into<String>) -> Self {
+        Cell { badge: None, text: text.into() }
+    }
+
+    fn plain(&self) -> String {
+        match &self.badge {
+            Some(b) => format!("{} {}", b, self.text),
+            None => self.text.clone(),
+        }
+    }
+
+    fn shown(&self) -> String {
+        match &self.badge {
+            Some(b) => format!("{} {}", b.green(), self.text),
+            None => self.text.clone(),
+        }
+    }
+}
+
+fn dash(v: Option<String>) -> Cell {
+    Cell::new(v.unwrap_or_else(|| "-".to_string()))
+}
+
+/// Columns padded by display width, so CJK text lines up. When stdout is a terminal too
+/// narrow for a row, the last column wraps onto indented continuation lines.
+fn render(headers: &[&str], rows: Vec<Vec<Cell>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| util::display_width(h)).collect();
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(util::display_width(&cell.plain()));
+        }
+    }
+    let Some(last) = widths.len().checked_sub(1) else { return };
+    // Everything before the last column, with the two-space gaps
+    let indent: usize = widths[..last].iter().map(|w| w + 2).sum();
+    let wrap_at = util::terminal_width().map(|cols| cols.saturating_sub(indent).max(10));
+
+    let lead = |cells: &[Cell]| -> String {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{}{}  ", c.shown(), " ".repeat(w - util::display_width(&c.plain()))))
+            .collect()
+    };
+    let heads: Vec<Cell> = headers.iter().map(|h| Cell::new(*h)).collect();
+    println!("{}{}", lead(&heads[..last]).bold(), headers[last].bold());
+    let seps: Vec<Cell> = widths[..last].iter().map(|w| Cell::new("-".repeat(*w))).collect();
+    println!("{}{}", lead(&seps), "-".repeat(wrap_at.map_or(widths[last], |w| w.min(widths[last]))));
+    for mut row in rows {
+        let tail = row.pop().unwrap_or_else(|| Cell::new(""));
+        let mut lines = match wrap_at {
+            Some(w) => util::wrap_width(&tail.plain(), w),
+            None => vec![tail.plain()],
+        };
+        // The badge is short enough to always land on the first line
+        if let Some(b) = &tail.badge {
+            lines[0] = lines[0].replacen(b.as_str(), &b.green().to_string(), 1);
+        }
+        println!("{}{}", lead(&row), lines[0]);
+        for line in &lines[1..] {
+            println!("{}{}", " ".repeat(indent), line);
+        }
+    }
+}
+
+fn header(known: &[(&str, &'static str)], key: &str) -> &'static str {
+    known.iter().find(|(k, _)| *k == key).map(|(_, h)| *h).unwrap_or("")
+}
+
+pub async fn print_items(items: &[AvItem]) -> Result<()> {
+    let (mut cols, explicit) = columns(&config::get().view.columns, DEFAULT_ITEM_COLUMNS, ITEM_COLUMNS)?;
+    if !explicit && items.iter().all(|i| i.release_date.is_none()) {
+        cols.retain(|c| *c != "date");
+    }
+    let dated = items.iter().all(|i| i.release_date.is_some());
+    let rated = items.iter().all(|i| i.rating.is_some());
+    let badged = items.iter().all(|i| i.has_magnets.is_some());
+    let needs_detail = explicit
+        && cols.iter().any(|c| match *c {
+            "index" | "code" | "title" | "sources" | "alt_titles" => false,
+            "date" => !dated,
+            "rating" => !rated,
+            "badges" => !badged,
+            _ => true,
+        });
+    let details: HashMap<String, AvDetail> = if needs_detail && !items.is_empty() {
+        let codes = items.iter().map(|i| i.code.clone()).collect();
+        scraper::fetch_details(codes).await.into_iter().map(|d| (d.code.clone(), d)).collect()
+    } else {
+        HashMap::new()
+    };
+
+    println!("{} {}", "共".bold(), items.len());
+    let width = title_width();
+    let lib = Library::load().unwrap_or_default();
+    let rows = items
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let d = details.get(&item.code);
+            cols.iter()
+                .map(|col| match *col {
+                    "index" => Cell::new((idx + 1).to_string()),
+                    "code" => Cell::new(item.code.clone()),
+                    "title" => {
+                        let badge = lib.badge(&item.code).map(|b| format!("[{}]", b));
+                        let prefix = badge.as_ref().map_or(0, |b| util::display_width(b) + 1);
+                        let text = match width {
+                            Some(w) => util::truncate_width(&item.title, w.saturating_sub(prefix).max(1)),
+                            None => item.title.clone(),
+                        };
+                        Cell { badge, text }
+                    }
+                    "date" => dash(item.release_date.as_deref().or(d.and_then(|d| d.release_date.as_deref())).map(dates::render)),
+                    "actors" => dash(d.map(|d| d.actor_names.join(", ")).filter(|s| !s.is_empty())),
+                    "studio" => dash(d.and_then(|d| d.studio.clone())),
+                    "duration" => dash(d.and_then(|d| d.duration_minutes).map(|m| format!("{} 分钟", m))),
+                    "rating" => dash(item.rating.or(d.and_then(|d| d.rating)).map(|r| format!("{:.2}", r))),
+                    "seeders" => dash(d.and_then(|d| d.magnet_infos.iter().filter_map(|m| m.seeders).max()).map(|s| s.to_string())),
+                    "magnets" => dash(d.map(|d| util::ranked_magnets(d).len().to_string())),
+                    "genres" => dash(d.map(|d| d.genres.join(", ")).filter(|s| !s.is_empty())),
+                    "badges" => {
+                        let magnets = item.has_magnets.or(d.map(|d| !util::ranked_magnets(d).is_empty()));
+                        let subs = item.has_subtitles.or(d.map(|d| d.magnet_infos.iter().any(magnet::has_subtitles)));
+                        let badges: Vec<&str> = [(magnets, "磁力"), (subs, "中字")]
+                            .into_iter()
+                            .filter(|(on, _)| *on == Some(true))
+                            .map(|(_, b)| b)
+                            .collect();
+                        dash(Some(badges.join(" ")).filter(|s| !s.is_empty()))
+                    }
+                    "sources" => dash(Some(item.sources.join(", ")).filter(|s| !s.is_empty())),
+                    "alt_titles" => dash(Some(item.alt_titles.join(" / ")).filter(|s| !s.is_empty())),
+                    _ => Cell::new(""),
+                })
+                .collect()
+        })
+        .collect();
+    let headers: Vec<&str> = cols.iter().map(|c| header(ITEM_COLUMNS, c)).collect();
+    render(&headers, rows);
+    Ok(())
+}
+
+/// Fixed columns, for listings that are neither items nor actors
+pub fn print_rows(headers: &[&str], rows: Vec<Vec<String>>) {
+    render(headers, rows.into_iter().map(|r| r.into_iter().map(Cell::new).collect()).collect());
+}
+
+/// `per_page` only numbers the rows; the site's page size decides the page count
+pub fn print_actors(found: &RankingPage, page: usize, per_page: usize) -> Result<()> {
+    let (cols, _) = columns(&config::get().view.actor_columns, DEFAULT_ACTOR_COLUMNS, ACTOR_COLUMNS)?;
+    println!("{} {} (page {} / {}):", "Total".bold(), found.total, page, found.pages.max(page));
+    actor_rows(&cols, &found.actors, (page - 1) * per_page);
+    Ok(())
+}
+
+/// One page of `actors --all` as it arrives: its ranking, page and the actors new on it,
+/// numbered on from the `before` listed so far
+pub fn print_actor_batch(label: &str, page: usize, actors: &[ActorItem], before: usize) -> Result<()> {
+    let (cols, _) = columns(&config::get().view.actor_columns, DEFAULT_ACTOR_COLUMNS, ACTOR_COLUMNS)?;
+    println!("{} 第 {} 页（新增 {}）:", label.bold(), page, actors.len());
+    if !actors.is_empty() {
+        actor_rows(&cols, actors, before);
+    }
+    Ok(())
+}
+
+fn actor_rows(cols: &[&str], actors: &[ActorItem], offset: usize) {
+    let rows = actors
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            cols.iter()
+                .map(|col| match *col {
+                    "index" => Cell::new((i + 1 + offset).to_string()),
+                    "name" => Cell::new(a.name.clone()),
+                    "rank" => dash(a.rank.map(|r| r.to_string())),
+                    "works" => dash(a.works_count.map(|w| w.to_string())),
+                    "views" => dash(a.views.map(|v| v.to_string())),
+                    "debut" => dash(a.debut_year.map(|y| y.to_string())),
+                    "latest" => dash(a.latest_release.clone()),
+                    "url" => dash(a.url.clone()),
+                    _ => Cell::new(""),
+                })
+                .collect()
+        })
+        .collect();
+    let headers: Vec<&str> = cols.iter().map(|c| header(ACTOR_COLUMNS, c)).collect();
+    render(&headers, rows);
+}
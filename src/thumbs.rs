@@ -0,0 +1,182 @@
+//! `av thumbs`: a grid of screenshots per local video, taken with ffmpeg at evenly spaced
+//! points and tiled like `av previews --sheet`, to see at a glance that a download is the
+//! title it claims to be and plays all the way through.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use which::which;
+
+use crate::artwork;
+use crate::scan;
+use crate::store;
+use crate::util;
+use crate::verify;
+
+#[derive(Debug, Serialize)]
+struct Sheet {
+    video: PathBuf,
+    sheet: PathBuf,
+    frames: usize,
+    /// Already there and kept (no `--force`)
+    skipped: bool,
+}
+
+pub struct ThumbsOptions {
+    pub columns: u32,
+    pub rows: u32,
+    /// Into `<data dir>/thumbs/` instead of next to each video
+    pub cache: bool,
+    pub force: bool,
+}
+
+/// Stable per path, so sheets of same-named videos in different folders don't collide
+fn path_hash(path: &Path) -> u32 {
+    let full = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    // FNV-1a
+    full.to_string_lossy().bytes().fold(0x811c9dc5u32, |h, b| (h ^ b as u32).wrapping_mul(0x01000193))
+}
+
+/// `ABP-123.mp4` → `ABP-123.thumbs.jpg` beside it, or `thumbs/ABP-123-<hash>.jpg` in the
+/// data directory
+fn sheet_path(video: &Path, cache: bool) -> PathBuf {
+    let stem = video.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    if cache {
+        store::data_dir().join("thumbs").join(format!("{}-{:08x}.jpg", stem, path_hash(video)))
+    } else {
+        video.with_file_name(format!("{}.thumbs.jpg", stem))
+    }
+}
+
+/// Every video under `target`, or `target` itself
+fn videos(target: &Path) -> Result<Vec<PathBuf>> {
+    if target.is_dir() {
+        let result = scan::scan(target)?;
+        let mut all: Vec<PathBuf> = result.groups.into_iter().flat_map(|g| g.files.into_iter().map(|f| f.path)).collect();
+        all.extend(result.unmatched);
+        return Ok(all);
+    }
+    if !target.exists() {
+        bail!("文件不存在: {}", target.display());
+    }
+    Ok(vec![target.to_path_buf()])
+}
+
+/// One frame at `secs` into `video`, saved as `out`
+async fn frame(video: &Path, secs: f64, out: &Path) -> Result<()> {
+    let status = tokio::process::Command::new("ffmpeg")
+        // -ss before -i seeks by keyframe, fast even deep into a long file
+        .args(["-v", "error", "-y", "-ss", &format!("{:.2}", secs), "-i"])
+        .arg(video)
+        .args(["-frames:v", "1", "-vf", "scale=400:-2"])
+        .arg(out)
+        .stdin(Stdio::null())
+        .status()
+        .await
+        .context("启动 ffmpeg 失败")?;
+    if !status.success() || !out.exists() {
+        bail!("ffmpeg 截图失败（{:.0} 秒处），退出码: {:?}", secs, status.code());
+    }
+    Ok(())
+}
+
+async fn make_sheet(video: PathBuf, opts: Arc<ThumbsOptions>) -> Result<Sheet> {
+    let sheet = sheet_path(&video, opts.cache);
+    if sheet.exists() && !opts.force {
+        return Ok(Sheet { video, sheet, frames: 0, skipped: true });
+    }
+    let minutes = verify::probe(&video).await?.duration_minutes.with_context(|| format!("无法读取时长: {}", video.display()))?;
+    let count = (opts.columns * opts.rows) as usize;
+    let tmp = std::env::temp_dir().join(format!("av-thumbs-{}-{:08x}", std::process::id(), path_hash(&video)));
+    std::fs::create_dir_all(&tmp).with_context(|| format!("创建目录失败: {}", tmp.display()))?;
+    let result = async {
+        let mut files = Vec::new();
+        for i in 0..count {
+            crate::cancel::check()?;
+            // The middle of each of `count` equal spans: never the very first or last second
+            let secs = minutes * 60.0 * (i as f64 + 0.5) / count as f64;
+            let out = tmp.join(format!("{:02}.jpg", i + 1));
+            match frame(&video, secs, &out).await {
+                Ok(()) => files.push(out),
+                Err(e) => util::debug(format!("thumbs: {}: {:#}", video.display(), e)),
+            }
+        }
+        if files.is_empty() {
+            bail!("没能从 {} 截取任何画面", video.display());
+        }
+        if let Some(dir) = sheet.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+        }
+        artwork::contact_sheet(&files, opts.columns, &sheet)?;
+        Ok(files.len())
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&tmp);
+    Ok(Sheet { video, sheet, frames: result?, skipped: false })
+}
+
+/// `av thumbs <file|dir>`
+pub async fn run(target: &Path, opts: ThumbsOptions, json: bool) -> Result<()> {
+    for tool in ["ffmpeg", "ffprobe"] {
+        if which(tool).is_err() {
+            bail!("未检测到 {}，请先安装: brew install ffmpeg", tool);
+        }
+    }
+    let videos = videos(target)?;
+    if videos.is_empty() {
+        bail!("{} 下没有找到视频文件", target.display());
+    }
+    if util::dry_run_skip(format!("将为 {} 个视频生成 {}x{} 截图拼图", videos.len(), opts.columns, opts.rows)) {
+        for v in &videos {
+            eprintln!("  {} → {}", v.display(), sheet_path(v, opts.cache).display());
+        }
+        return Ok(());
+    }
+    let opts = Arc::new(opts);
+    let sem = Arc::new(Semaphore::new(util::jobs()));
+    let mut set = JoinSet::new();
+    for (idx, video) in videos.into_iter().enumerate() {
+        let (sem, opts) = (sem.clone(), opts.clone());
+        set.spawn(async move {
+            let _permit = sem.acquire_owned().await;
+            let shown = video.clone();
+            (idx, shown, make_sheet(video, opts).await)
+        });
+    }
+    let mut results = set.join_all().await;
+    results.sort_by_key(|(idx, ..)| *idx);
+    let mut sheets = Vec::new();
+    let mut failed = 0;
+    for (_, video, result) in results {
+        match result {
+            Ok(s) => sheets.push(s),
+            Err(e) => {
+                if crate::cancel::is_cancelled() {
+                    return Err(e);
+                }
+                eprintln!("[WARN] {}: {:#}", video.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if json {
+        util::print_output(&sheets, true);
+    } else {
+        for s in &sheets {
+            if s.skipped {
+                println!("{} {}（已存在，--force 重新生成）", "跳过".yellow(), s.sheet.display());
+            } else {
+                println!("{} {}  {} 张截图", "已生成".green().bold(), s.sheet.display(), s.frames);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} 个视频未能生成截图拼图", failed);
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+//! Tracker scrapes for `av check-magnet`: asks the magnet's trackers (BEP 15 over UDP,
+//! `/scrape` over HTTP) how many peers the torrent has, so a dead magnet is caught before
+//! it sits in a download client forever. UDP can't go through the proxy, so with one
+//! configured only HTTP trackers are asked.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use regex::bytes::Regex;
+use reqwest::Url;
+use serde::Serialize;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use crate::http;
+use crate::magnet;
+use crate::scraper;
+use crate::types::MagnetInfo;
+use crate::sources::mock;
+use crate::util;
+
+/// Asked in addition to the magnet's own `tr` list, which is often empty
+const PUBLIC_TRACKERS: &[&str] = &[
+    "udp://tracker.opentrackr.org:1337/announce",
+    "udp://open.stealth.si:80/announce",
+    "udp://tracker.torrent.eu.org:451/announce",
+    "udp://exodus.desync.com:6969/announce",
+];
+/// Trackers asked per magnet at most
+const MAX_TRACKERS: usize = 8;
+/// BEP 15 magic number identifying the protocol in the connect request
+const UDP_PROTOCOL_ID: u64 = 0x41727101980;
+/// First wait before a UDP request is sent again, doubling after each miss. BEP 15 starts
+/// at 15 s; the scrape timeout bounds the retries either way.
+const UDP_RETRY: Duration = Duration::from_secs(2);
+
+static COMPLETE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"8:completei(\d+)e").unwrap());
+static INCOMPLETE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"10:incompletei(\d+)e").unwrap());
+static DOWNLOADED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"10:downloadedi(\d+)e").unwrap());
+static FAILURE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"14:failure reason(\d+):").unwrap());
+
+/// One tracker's answer for one torrent
+#[derive(Debug, Clone, Serialize)]
+pub struct Scrape {
+    pub tracker: String,
+    pub seeders: Option<u32>,
+    pub leechers: Option<u32>,
+    pub completed: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct MagnetCheck {
+    /// Position in `av install`'s list
+    index: usize,
+    url: String,
+    name: Option<String>,
+    infohash: String,
+    /// Some tracker reports a seeder; `None` when no tracker answered
+    alive: Option<bool>,
+    seeders: Option<u32>,
+    leechers: Option<u32>,
+    trackers: Vec<Scrape>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Counts {
+    seeders: u32,
+    completed: u32,
+    leechers: u32,
+}
+
+fn be_u32(b: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+/// Send `req` until an answer to the same action and transaction arrives, as UDP packets
+/// get lost
+async fn udp_exchange(sock: &UdpSocket, req: &[u8], action: u32, tid: u32, min_len: usize) -> Result<Vec<u8>> {
+    let mut wait = UDP_RETRY;
+    loop {
+        sock.send(req).await.context("发送 UDP 请求失败")?;
+        match tokio::time::timeout(wait, udp_reply(sock, action, tid, min_len)).await {
+            Ok(res) => return res,
+            Err(_) => wait *= 2,
+        }
+    }
+}
+
+async fn udp_reply(sock: &UdpSocket, action: u32, tid: u32, min_len: usize) -> Result<Vec<u8>> {
+    let mut buf = [0u8; 1024];
+    loop {
+        let n = sock.recv(&mut buf).await.context("接收 UDP 响应失败")?;
+        if n < 8 || be_u32(&buf, 4) != tid {
+            continue;
+        }
+        match be_u32(&buf, 0) {
+            a if a == action && n >= min_len => return Ok(buf[..n].to_vec()),
+            3 => bail!("tracker 返回错误: {}", String::from_utf8_lossy(&buf[8..n])),
+            _ => bail!("tracker 响应格式错误"),
+        }
+    }
+}
+
+async fn scrape_udp(url: &Url, hash: &[u8; 20]) -> Result<Counts> {
+    let host = url.host_str().context("tracker 地址缺少主机名")?;
+    let port = url.port().context("tracker 地址缺少端口")?;
+    let addr = tokio::net::lookup_host((host, port)).await.context("无法解析 tracker 主机")?.next().context("无法解析 tracker 主机")?;
+    let sock = UdpSocket::bind(if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }).await.context("创建 UDP 套接字失败")?;
+    sock.connect(addr).await.context("连接 tracker 失败")?;
+
+    let tid = fastrand::u32(..);
+    let mut connect = Vec::with_capacity(16);
+    connect.extend_from_slice(&UDP_PROTOCOL_ID.to_be_bytes());
+    connect.extend_from_slice(&0u32.to_be_bytes());
+    connect.extend_from_slice(&tid.to_be_bytes());
+    let resp = udp_exchange(&sock, &connect, 0, tid, 16).await?;
+    let connection_id = &resp[8..16];
+
+    let tid = tid.wrapping_add(1);
+    let mut scrape = Vec::with_capacity(36);
+    scrape.extend_from_slice(connection_id);
+    scrape.extend_from_slice(&2u32.to_be_bytes());
+    scrape.extend_from_slice(&tid.to_be_bytes());
+    scrape.extend_from_slice(hash);
+    let resp = udp_exchange(&sock, &scrape, 2, tid, 20).await?;
+    Ok(Counts { seeders: be_u32(&resp, 8), completed: be_u32(&resp, 12), leechers: be_u32(&resp, 16) })
+}
+
+/// The scrape URL trackers derive from their announce URL: `.../announce?x` → `.../scrape?x`
+fn scrape_url(announce: &Url) -> Option<Url> {
+    let mut url = announce.clone();
+    let last = announce.path_segments()?.next_back()?;
+    let rest = last.strip_prefix("announce")?;
+    let path = announce.path();
+    url.set_path(&format!("{}scrape{}", &path[..path.len() - last.len()], rest));
+    Some(url)
+}
+
+fn capture(re: &Regex, body: &[u8]) -> Option<u32> {
+    std::str::from_utf8(re.captures(body)?.get(1)?.as_bytes()).ok()?.parse().ok()
+}
+
+async fn scrape_http(url: &Url, hash: &[u8; 20], timeout: Duration) -> Result<Counts> {
+    let scrape = scrape_url(url).context("该 tracker 不支持 scrape")?;
+    let encoded: String = hash.iter().map(|b| format!("%{:02X}", b)).collect();
+    let sep = if scrape.query().is_some() { '&' } else { '?' };
+    let c = http::with_proxy(reqwest::Client::builder()).timeout(timeout).build().context("client build")?;
+    let resp = c.get(format!("{}{}info_hash={}", scrape, sep, encoded)).send().await.context("请求 tracker 失败")?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("tracker 返回 HTTP {}", status.as_u16());
+    }
+    let body = resp.bytes().await.context("读取 tracker 响应失败")?;
+    if let Some(m) = FAILURE.captures(&body) {
+        let reason = String::from_utf8_lossy(&body[m.get(0).map_or(0, |g| g.end())..]);
+        bail!("tracker 返回错误: {}", util::truncate_chars(&reason, 80));
+    }
+    Ok(Counts {
+        seeders: capture(&COMPLETE, &body).context("tracker 没有该种子的数据")?,
+        completed: capture(&DOWNLOADED, &body).unwrap_or(0),
+        leechers: capture(&INCOMPLETE, &body).unwrap_or(0),
+    })
+}
+
+/// Ask one tracker about `hash`, giving up after `timeout`
+pub async fn scrape(tracker: &str, hash: &[u8; 20], timeout: Duration) -> Scrape {
+    let res: Result<Counts> = async {
+        let url = Url::parse(tracker).context("tracker 地址无效")?;
+        match url.scheme() {
+            "udp" if http::proxy_url().is_some() => bail!("已配置代理，跳过 UDP tracker"),
+            "udp" => tokio::time::timeout(timeout, scrape_udp(&url, hash)).await.unwrap_or_else(|_| bail!("超时")),
+            "http" | "https" => scrape_http(&url, hash, timeout).await,
+            other => bail!("不支持的 tracker 协议: {}", other),
+        }
+    }
+    .await;
+    match res {
+        Ok(c) => Scrape {
+            tracker: tracker.to_string(),
+            seeders: Some(c.seeders),
+            leechers: Some(c.leechers),
+            completed: Some(c.completed),
+            error: None,
+        },
+        Err(e) => {
+            util::debug(format!("scrape {}: {:#}", tracker, e));
+            Scrape { tracker: tracker.to_string(), seeders: None, leechers: None, completed: None, error: Some(format!("{:#}", e)) }
+        }
+    }
+}
+
+async fn check_one(index: usize, url: String, name: Option<String>, known_seeders: Option<u32>, timeout: Duration) -> Result<MagnetCheck> {
+    let m = magnet::parse(&url)?;
+    let hash: [u8; 20] = hex::decode(&m.infohash).ok().and_then(|b| b.try_into().ok()).context("infohash 无效")?;
+    let trackers: Vec<Scrape> = if mock::is_enabled() {
+        // Offline: the fixture's seeders stand in for the swarm
+        vec![Scrape { tracker: "mock".to_string(), seeders: known_seeders, leechers: None, completed: None, error: None }]
+    } else {
+        let mut list: Vec<String> = m.trackers.clone();
+        for t in PUBLIC_TRACKERS {
+            if !list.iter().any(|x| x == t) {
+                list.push(t.to_string());
+            }
+        }
+        list.truncate(MAX_TRACKERS);
+        let mut tasks = tokio::task::JoinSet::new();
+        for (i, t) in list.into_iter().enumerate() {
+            tasks.spawn(async move { (i, scrape(&t, &hash, timeout).await) });
+        }
+        let mut done = tasks.join_all().await;
+        done.sort_by_key(|(i, _)| *i);
+        done.into_iter().map(|(_, s)| s).collect()
+    };
+    let seeders = trackers.iter().filter_map(|s| s.seeders).max();
+    let leechers = trackers.iter().filter_map(|s| s.leechers).max();
+    Ok(MagnetCheck { index, url, name, infohash: m.infohash, alive: seeders.map(|s| s > 0), seeders, leechers, trackers })
+}
+
+/// `av check-magnet <CODE|MAGNET> [N]`: scrape the trackers of a magnet link, the Nth
+/// magnet of a code (numbered as in `av install`), or all of the code's magnets
+pub async fn check(target: &str, index: Option<usize>, timeout_secs: u64, json: bool) -> Result<()> {
+    let timeout = Duration::from_secs(timeout_secs.max(1));
+    // (index, url, name, seeders shown by the source)
+    let mut candidates: Vec<(usize, String, Option<String>, Option<u32>)> = if target.trim().starts_with("magnet:") {
+        vec![(1, target.trim().to_string(), None, None)]
+    } else {
+        let d = scraper::fetch_detail(target).await?;
+        if !d.magnet_infos.is_empty() {
+            let mut infos = d.magnet_infos;
+            infos.sort_by(MagnetInfo::ranking);
+            infos.into_iter().enumerate().map(|(i, m)| (i + 1, m.url, m.name, m.seeders)).collect()
+        } else {
+            d.magnets.into_iter().enumerate().map(|(i, u)| (i + 1, u, None, None)).collect()
+        }
+    };
+    if candidates.is_empty() {
+        bail!("{} 没有可检测的磁力链接", target);
+    }
+    if let Some(n) = index {
+        let total = candidates.len();
+        candidates.retain(|c| c.0 == n);
+        if candidates.is_empty() {
+            bail!("序号 {} 超出范围（共 {} 个磁力）", n, total);
+        }
+    }
+
+    if !mock::is_enabled() && http::proxy_url().is_some() {
+        eprintln!("[WARN] 已配置代理，UDP tracker 无法经代理访问，只查询 HTTP tracker");
+    }
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, url, name, seeders) in candidates {
+        tasks.spawn(check_one(i, url, name, seeders, timeout));
+    }
+    let mut checks = Vec::new();
+    for res in tasks.join_all().await {
+        match res {
+            Ok(c) => checks.push(c),
+            Err(e) => eprintln!("[WARN] {:#}", e),
+        }
+    }
+    checks.sort_by_key(|c| c.index);
+
+    if json {
+        util::print_output(&checks, true);
+        return Ok(());
+    }
+    for c in &checks {
+        let answered = c.trackers.iter().filter(|s| s.error.is_none()).count();
+        let status = match c.alive {
+            Some(true) => "存活".green().bold(),
+            Some(false) => "无做种".red().bold(),
+            None => "未知".yellow().bold(),
+        };
+        let peers = match (c.seeders, c.leechers) {
+            (Some(s), Some(l)) => format!(" S:{} L:{}", s, l),
+            (Some(s), None) => format!(" S:{}", s),
+            _ => String::new(),
+        };
+        let name = c.name.as_deref().map(|n| format!(" {}", n)).unwrap_or_default();
+        println!(
+            "{}. {}{}{} {}",
+            c.index,
+            status,
+            peers,
+            name,
+            format!("（{}/{} 个 tracker 响应）", answered, c.trackers.len()).dimmed()
+        );
+        println!("   {}", c.url.cyan());
+    }
+    Ok(())
+}
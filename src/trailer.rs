@@ -0,0 +1,127 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, RANGE};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use which::which;
+
+use crate::scraper;
+use crate::sources::{dmm, javtrailers};
+use crate::util;
+
+#[derive(Debug, Serialize)]
+struct TrailerOutput {
+    code: String,
+    trailer_url: String,
+    /// Where `--download` saved it
+    file: Option<PathBuf>,
+}
+
+async fn download(url: &str, out: &Path) -> Result<()> {
+    if util::dry_run_skip(format!("将通过 ffmpeg 下载预告片到 {}: {}", out.display(), url)) {
+        return Ok(());
+    }
+    if which("ffmpeg").is_err() {
+        bail!("未检测到 ffmpeg，请先安装: brew install ffmpeg");
+    }
+    let status = tokio::process::Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i", url, "-c", "copy"])
+        .arg(out)
+        .stdin(Stdio::null())
+        .status()
+        .await
+        .context("启动 ffmpeg 失败")?;
+    if !status.success() {
+        bail!("ffmpeg 下载失败，退出码: {:?}", status.code());
+    }
+    Ok(())
+}
+
+/// DMM's sample mp4 first (the official file, at its best bitrate), then the detail's
+/// trailer; DMM is asked on its own when enabled and the chain left both empty
+async fn dmm_trailer(detail: &crate::types::AvDetail) -> Option<String> {
+    if let Some(url) = detail.sample_video_url.clone().or_else(|| detail.trailer_url.clone()) {
+        return Some(url);
+    }
+    if !dmm::dmm_enabled() {
+        return None;
+    }
+    match dmm::fetch_detail_from_dmm(&detail.code).await {
+        Ok(d) => d.and_then(|d| d.sample_video_url.or(d.trailer_url)),
+        Err(e) => {
+            util::debug(format!("DMM sample for {}: {:#}", detail.code, e));
+            None
+        }
+    }
+}
+
+/// DMM serves its sample files to Japanese addresses only; elsewhere they answer 403, or
+/// redirect to a "not available in your area" page
+async fn region_locked(url: &str) -> bool {
+    if !url.contains("dmm.co.jp") {
+        return false;
+    }
+    let mut headers = HeaderMap::new();
+    headers.insert(RANGE, HeaderValue::from_static("bytes=0-0"));
+    match scraper::send_from(&scraper::client(), url, "https://www.dmm.co.jp/", headers).await {
+        Ok(resp) => {
+            let html = resp.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).is_some_and(|t| t.starts_with("text/html"));
+            let locked = !resp.status().is_success() || html;
+            util::debug(format!("DMM sample {}: HTTP {}{}", url, resp.status().as_u16(), if html { " (HTML)" } else { "" }));
+            locked
+        }
+        Err(e) => {
+            util::debug(format!("DMM sample {}: {:#}", url, e));
+            true
+        }
+    }
+}
+
+/// DMM's sample, unless it is region-locked and JavTrailers has the trailer; JavTrailers
+/// also stands in when DMM has none
+async fn trailer_of(detail: &crate::types::AvDetail) -> Option<String> {
+    let dmm = dmm_trailer(detail).await;
+    if let Some(url) = &dmm {
+        if !region_locked(url).await {
+            return dmm;
+        }
+    }
+    match javtrailers::trailer(&detail.code).await {
+        Ok(Some(t)) => return Some(t.url),
+        Ok(None) => util::debug(format!("JavTrailers: no trailer for {}", detail.code)),
+        Err(e) => util::debug(format!("JavTrailers {}: {:#}", detail.code, e)),
+    }
+    if dmm.is_some() {
+        eprintln!("[WARN] DMM 预告片可能有地区限制（需日本 IP），JavTrailers 上也没有找到");
+    }
+    dmm
+}
+
+/// Print, open or download (`download = Some(None)` saves `<CODE>-trailer.mp4`) the sample video
+pub async fn run(code: &str, open: bool, download_to: Option<Option<PathBuf>>, json: bool) -> Result<()> {
+    let detail = scraper::fetch_detail(code).await?;
+    let Some(url) = trailer_of(&detail).await else {
+        bail!("未找到 {} 的预告片", detail.code);
+    };
+    let mut file = None;
+    if let Some(out) = download_to {
+        let out = out.unwrap_or_else(|| PathBuf::from(format!("{}-trailer.mp4", detail.code)));
+        download(&url, &out).await?;
+        if !util::is_dry_run() {
+            file = Some(out);
+        }
+    }
+    if open {
+        util::open_browser_url(&url).await?;
+    }
+    if json {
+        util::print_output(&TrailerOutput { code: detail.code, trailer_url: url, file }, true);
+        return Ok(());
+    }
+    println!("{} {}", detail.code.bold(), url.cyan());
+    if let Some(f) = file {
+        println!("{} {}", "已保存".green().bold(), f.display());
+    }
+    Ok(())
+}
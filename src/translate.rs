@@ -0,0 +1,147 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::{self, TranslateBackend, TranslateConfig};
+use crate::http;
+use crate::types::AvDetail;
+use crate::util;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+impl Lang {
+    fn deepl(self) -> &'static str {
+        match self {
+            Lang::Zh => "ZH",
+            Lang::En => "EN-US",
+        }
+    }
+
+    fn google(self) -> &'static str {
+        match self {
+            Lang::Zh => "zh-CN",
+            Lang::En => "en",
+        }
+    }
+
+    /// ISO 639-1 code, also what LibreTranslate expects
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Lang::Zh => "zh",
+            Lang::En => "en",
+        }
+    }
+}
+
+/// Translated title/plot shown next to the originals
+#[derive(Debug, Clone, Serialize)]
+pub struct Translation {
+    pub lang: Lang,
+    pub title: String,
+    pub plot: Option<String>,
+}
+
+fn client() -> Result<reqwest::Client> {
+    http::with_proxy(reqwest::Client::builder())
+        .timeout(Duration::from_secs(20))
+        .build()
+        .context("client build")
+}
+
+fn api_key(cfg: &TranslateConfig) -> Result<&str> {
+    cfg.api_key.as_deref().filter(|k| !k.trim().is_empty()).context("未配置翻译 API Key（[translate] api_key）")
+}
+
+async fn post(req: reqwest::RequestBuilder) -> Result<Value> {
+    let resp = req.send().await.context("翻译请求失败")?;
+    let status = resp.status();
+    let body = resp.text().await.context("读取翻译结果失败")?;
+    if !status.is_success() {
+        bail!("翻译服务返回 HTTP {}: {}", status.as_u16(), util::truncate_chars(body.trim(), 200));
+    }
+    serde_json::from_str(&body).context("翻译结果格式错误")
+}
+
+/// Translate `texts` from Japanese, keeping their order
+async fn translate_texts(cfg: &TranslateConfig, texts: &[String], to: Lang) -> Result<Vec<String>> {
+    let c = client()?;
+    let out: Vec<String> = match cfg.backend {
+        TranslateBackend::Deepl => {
+            let key = api_key(cfg)?;
+            // Free-plan keys end in ":fx" and live on a separate host
+            let default_url = if key.ends_with(":fx") { "https://api-free.deepl.com" } else { "https://api.deepl.com" };
+            let base = cfg.url.as_deref().unwrap_or(default_url).trim_end_matches('/');
+            let v = post(
+                c.post(format!("{}/v2/translate", base))
+                    .header("Authorization", format!("DeepL-Auth-Key {}", key))
+                    .json(&json!({ "text": texts, "source_lang": "JA", "target_lang": to.deepl() })),
+            )
+            .await?;
+            v["translations"]
+                .as_array()
+                .map(|a| a.iter().filter_map(|t| t["text"].as_str().map(|s| s.to_string())).collect())
+                .unwrap_or_default()
+        }
+        TranslateBackend::Google => {
+            let key = api_key(cfg)?;
+            let base = cfg.url.as_deref().unwrap_or("https://translation.googleapis.com").trim_end_matches('/');
+            let v = post(
+                c.post(format!("{}/language/translate/v2", base))
+                    .query(&[("key", key)])
+                    .json(&json!({ "q": texts, "source": "ja", "target": to.google(), "format": "text" })),
+            )
+            .await?;
+            v["data"]["translations"]
+                .as_array()
+                .map(|a| a.iter().filter_map(|t| t["translatedText"].as_str().map(|s| s.to_string())).collect())
+                .unwrap_or_default()
+        }
+        TranslateBackend::Libretranslate => {
+            let base = cfg.url.as_deref().context("LibreTranslate 需要配置服务地址（[translate] url）")?.trim_end_matches('/');
+            let mut body = json!({ "q": texts, "source": "ja", "target": to.as_str(), "format": "text" });
+            if let Some(key) = cfg.api_key.as_deref() {
+                body["api_key"] = json!(key);
+            }
+            let v = post(c.post(format!("{}/translate", base)).json(&body)).await?;
+            v["translatedText"]
+                .as_array()
+                .map(|a| a.iter().filter_map(|t| t.as_str().map(|s| s.to_string())).collect())
+                .unwrap_or_default()
+        }
+    };
+    if out.len() != texts.len() {
+        bail!("翻译结果数量不符（{} / {}）", out.len(), texts.len());
+    }
+    Ok(out)
+}
+
+/// Translate `texts` with the configured `[translate]` backend, keeping their order
+pub async fn texts(texts: &[String], to: Lang) -> Result<Vec<String>> {
+    let cfg = config::get()
+        .translate
+        .as_ref()
+        .context("未配置翻译服务，请在配置文件中添加 [translate]（backend = \"deepl\" / \"google\" / \"libretranslate\"）")?;
+    if texts.is_empty() {
+        return Ok(Vec::new());
+    }
+    util::debug(format!("translate: {} text(s) via {:?}", texts.len(), cfg.backend));
+    translate_texts(cfg, texts, to).await
+}
+
+/// Translate the detail's title and plot
+pub async fn detail(d: &AvDetail, to: Lang) -> Result<Translation> {
+    let mut texts = vec![d.title.clone()];
+    if let Some(plot) = d.plot.as_ref().filter(|p| !p.trim().is_empty()) {
+        texts.push(plot.clone());
+    }
+    let mut out = self::texts(&texts, to).await?.into_iter();
+    let title = out.next().unwrap_or_default();
+    Ok(Translation { lang: to, title, plot: out.next() })
+}
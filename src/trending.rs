@@ -0,0 +1,246 @@
+//! `av top --snapshot` / `--diff`: keep dated copies of the newest-releases listing and
+//! show which codes entered or left it since the last one. `av trends` reads the same
+//! snapshots (taken by hand or by a `top_snapshot` daemon job) to find the actors, studios
+//! and genres taking up more of the listing over time.
+
+use anyhow::{bail, Result};
+use chrono::{Duration, Local};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::cancel;
+use crate::code;
+use crate::scraper;
+use crate::store;
+use crate::types::{AvDetail, AvItem};
+use crate::util;
+
+const SNAPSHOTS: &str = "top_snapshots";
+/// Days of snapshots kept; older ones are dropped when a new one is taken
+const MAX_SNAPSHOTS: usize = 60;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A listed title with the metadata trends are counted by. Snapshots taken before the
+/// metadata was stored only have code and title.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Entry {
+    code: String,
+    title: String,
+    actors: Vec<String>,
+    studio: Option<String>,
+    genres: Vec<String>,
+}
+
+/// One listing per local date, `YYYY-MM-DD`; a second snapshot on the same day replaces it
+type Snapshots = BTreeMap<String, Vec<Entry>>;
+
+#[derive(Debug, Serialize)]
+struct Ranked {
+    rank: usize,
+    code: String,
+    title: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Diff {
+    /// Date of the snapshot compared against
+    since: String,
+    entered: Vec<Ranked>,
+    left: Vec<Ranked>,
+}
+
+/// `(code, title)` pairs not in `other`, with their rank in the listing
+fn ranked<'a>(listing: impl Iterator<Item = (&'a str, &'a str)>, other: &HashSet<&str>) -> Vec<Ranked> {
+    listing
+        .enumerate()
+        .filter(|(_, (code, _))| !other.contains(code))
+        .map(|(idx, (code, title))| Ranked { rank: idx + 1, code: code.to_string(), title: title.to_string() })
+        .collect()
+}
+
+/// `--diff`: `items` against the latest snapshot taken before today
+pub fn diff(items: &[AvItem], json: bool) -> Result<()> {
+    let snaps: Snapshots = store::load(SNAPSHOTS)?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let Some((since, old)) = snaps.range(..today).next_back().or_else(|| snaps.iter().next_back()) else {
+        bail!("还没有快照，先运行 av top --snapshot");
+    };
+    let old_codes: HashSet<&str> = old.iter().map(|i| i.code.as_str()).collect();
+    let new_codes: HashSet<&str> = items.iter().map(|i| i.code.as_str()).collect();
+    let d = Diff {
+        since: since.clone(),
+        entered: ranked(items.iter().map(|i| (i.code.as_str(), i.title.as_str())), &old_codes),
+        left: ranked(old.iter().map(|e| (e.code.as_str(), e.title.as_str())), &new_codes),
+    };
+    if json {
+        util::print_output(&d, true);
+        return Ok(());
+    }
+    println!("{}", format!("与 {} 的快照相比", d.since).dimmed());
+    if d.entered.is_empty() && d.left.is_empty() {
+        println!("{}", "榜单没有变化".yellow());
+        return Ok(());
+    }
+    if !d.entered.is_empty() {
+        println!("{}", format!("新上榜 {} 部:", d.entered.len()).green().bold());
+    }
+    for r in &d.entered {
+        println!("  {:>3}  {}  {}", r.rank, r.code.bold(), r.title);
+    }
+    if !d.left.is_empty() {
+        println!("{}", format!("已下榜 {} 部:", d.left.len()).red().bold());
+    }
+    for r in &d.left {
+        println!("  {:>3}  {}  {}", r.rank, r.code.bold(), r.title.dimmed());
+    }
+    Ok(())
+}
+
+/// `--snapshot`: store `items` as today's listing, with each title's actors, studio and
+/// genres for `av trends`. Titles whose detail can't be fetched are kept without them.
+pub async fn snapshot(items: &[AvItem]) -> Result<usize> {
+    if util::dry_run_skip(format!("将保存今天的榜单快照（{} 部）", items.len())) {
+        return Ok(0);
+    }
+    let details: HashMap<String, AvDetail> = scraper::fetch_details(items.iter().map(|i| i.code.clone()).collect())
+        .await
+        .into_iter()
+        .map(|d| (code::normalize(&d.code), d))
+        .collect();
+    // Cancelled half-way, most titles would lack their details; keep the previous snapshot
+    cancel::check()?;
+    let entries: Vec<Entry> = items
+        .iter()
+        .map(|i| match details.get(&code::normalize(&i.code)) {
+            Some(d) => Entry {
+                code: i.code.clone(),
+                title: i.title.clone(),
+                actors: d.actor_names.clone(),
+                studio: d.studio.clone(),
+                genres: d.genres.clone(),
+            },
+            None => Entry { code: i.code.clone(), title: i.title.clone(), ..Entry::default() },
+        })
+        .collect();
+    let mut snaps: Snapshots = store::load(SNAPSHOTS)?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    snaps.insert(today.clone(), entries);
+    while snaps.len() > MAX_SNAPSHOTS {
+        snaps.pop_first();
+    }
+    store::save(SNAPSHOTS, &snaps)?;
+    util::debug(format!("top snapshot {}: {} titles, {} with details", today, items.len(), details.len()));
+    Ok(items.len())
+}
+
+/// What `av trends` counts the listed titles by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Dimension {
+    Actor,
+    Studio,
+    Genre,
+}
+
+impl Dimension {
+    fn values(self, e: &Entry) -> Vec<String> {
+        match self {
+            Dimension::Actor => e.actors.clone(),
+            Dimension::Studio => e.studio.iter().cloned().collect(),
+            Dimension::Genre => e.genres.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Trend {
+    name: String,
+    /// Titles on the listing per snapshot, oldest first
+    counts: Vec<usize>,
+    /// Average of the later half of the window minus that of the earlier half
+    change: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Trends {
+    by: Dimension,
+    dates: Vec<String>,
+    rising: Vec<Trend>,
+}
+
+fn mean(v: &[usize]) -> f64 {
+    if v.is_empty() {
+        return 0.0;
+    }
+    v.iter().sum::<usize>() as f64 / v.len() as f64
+}
+
+fn sparkline(counts: &[usize]) -> String {
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    counts.iter().map(|&c| if c == 0 { ' ' } else { SPARKS[(c * (SPARKS.len() - 1)).div_ceil(max)] }).collect()
+}
+
+/// `av trends`: per `by` value, how many titles of each snapshot in the last `days` days
+/// it had, ranked by how much more of the listing it holds in the later half of the window
+pub fn run(by: Dimension, days: u32, limit: usize, json: bool) -> Result<()> {
+    let snaps: Snapshots = store::load(SNAPSHOTS)?;
+    let from = (Local::now() - Duration::days(i64::from(days))).format("%Y-%m-%d").to_string();
+    let window: Vec<(&String, &Vec<Entry>)> = snaps.range(from..).collect();
+    if window.len() < 2 {
+        bail!("最近 {} 天只有 {} 个榜单快照，至少需要 2 个（av top --snapshot 或 top_snapshot 定时任务）", days, window.len());
+    }
+    if window.iter().all(|(_, entries)| entries.iter().all(|e| by.values(e).is_empty())) {
+        bail!("快照中没有演员/片商/类别信息，请用新版本重新拍摄快照");
+    }
+    let mut counts: HashMap<String, Vec<usize>> = HashMap::new();
+    for (idx, (_, entries)) in window.iter().enumerate() {
+        for e in entries.iter() {
+            for v in by.values(e) {
+                counts.entry(v).or_insert_with(|| vec![0; window.len()])[idx] += 1;
+            }
+        }
+    }
+    let half = window.len() / 2;
+    let mut rising: Vec<Trend> = counts
+        .into_iter()
+        .map(|(name, counts)| {
+            let change = mean(&counts[window.len() - half..]) - mean(&counts[..half]);
+            Trend { name, counts, change }
+        })
+        .filter(|t| t.change > 0.0)
+        .collect();
+    rising.sort_by(|a, b| {
+        b.change.total_cmp(&a.change).then(b.counts.last().cmp(&a.counts.last())).then(a.name.cmp(&b.name))
+    });
+    rising.truncate(limit);
+    let t = Trends { by, dates: window.iter().map(|(d, _)| d.to_string()).collect(), rising };
+    if json {
+        util::print_output(&t, true);
+        return Ok(());
+    }
+    println!(
+        "{}",
+        format!("{} 至 {}，共 {} 个快照", t.dates[0], t.dates[t.dates.len() - 1], t.dates.len()).dimmed()
+    );
+    if t.rising.is_empty() {
+        println!("{}", "没有上升的条目".yellow());
+        return Ok(());
+    }
+    let width = t.rising.iter().map(|r| util::display_width(&r.name)).max().unwrap_or(0);
+    for (i, r) in t.rising.iter().enumerate() {
+        let first = r.counts[0];
+        let last = r.counts[r.counts.len() - 1];
+        println!(
+            "{:>3}. {}  {}  {} → {}  {}",
+            i + 1,
+            util::pad(&r.name, width).bold(),
+            sparkline(&r.counts).cyan(),
+            first,
+            last,
+            format!("+{:.1}", r.change).green()
+        );
+    }
+    Ok(())
+}
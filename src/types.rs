@@ -1,6 +1,8 @@
+use async_graphql::SimpleObject;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "Detail", rename_fields = "snake_case", complex)]
 pub struct AvDetail {
     pub code: String,
     pub title: String,
@@ -14,35 +16,271 @@ pub struct AvDetail {
     pub label: Option<String>,
     pub series: Option<String>,
     pub genres: Vec<String>,
+    /// Canonical ids for the known entries of `genres` (see `genre::lookup`)
+    #[serde(default)]
+    pub genre_ids: Vec<String>,
+    /// Weighted average of `ratings` on a 0–5 scale (see `rating::aggregate`)
     pub rating: Option<f32>,
+    /// Each source's own rating
+    #[serde(default)]
+    pub ratings: Vec<SourceRating>,
+    /// How many users of one site track the title; the first source that shows counters wins
+    #[serde(default)]
+    pub popularity: Option<Popularity>,
     pub preview_images: Vec<String>,
+    /// Official sample video (JavDB's preview video, DMM's sample movie)
+    #[serde(default)]
+    pub trailer_url: Option<String>,
+    /// DMM's sample movie as a direct mp4, read out of its sample player; only set when DMM
+    /// was asked
+    #[serde(default)]
+    pub sample_video_url: Option<String>,
+    /// From an amateur (素人) label or carrying the genre (see `amateur::is_amateur`)
+    #[serde(default)]
+    pub amateur: bool,
     pub magnet_infos: Vec<MagnetInfo>,
     pub magnets: Vec<String>,
 }
 
+impl AvDetail {
+    /// Put every set-like array in a canonical order, so the same title serializes to the
+    /// same JSON on every run whatever order the sources listed things in: genres and
+    /// ratings sorted, magnets best `quality_score` first (then most seeded, then by
+    /// link). Actors, previews and trackers stay in the site's order, which means something.
+    pub fn sort_stable(&mut self) {
+        self.genres.sort();
+        self.genres.dedup();
+        self.genre_ids.sort();
+        self.genre_ids.dedup();
+        self.ratings.sort_by(|a, b| a.source.cmp(&b.source));
+        self.magnet_infos.sort_by(MagnetInfo::ranking);
+        // Links with details in the same order, then the bare ones as found
+        let mut magnets: Vec<String> = self.magnet_infos.iter().map(|m| m.url.clone()).filter(|u| self.magnets.contains(u)).collect();
+        for m in &self.magnets {
+            if !magnets.contains(m) {
+                magnets.push(m.clone());
+            }
+        }
+        self.magnets = magnets;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct SourceRating {
+    pub source: String,
+    /// `raw` normalized to 0–5
+    pub value: f32,
+    /// As the site shows it, out of `scale`
+    pub raw: f32,
+    pub scale: f32,
+    pub votes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SimpleObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct Popularity {
+    pub source: String,
+    pub wanted: Option<u32>,
+    pub watched: Option<u32>,
+    pub owned: Option<u32>,
+    /// User reviews written for the title
+    pub reviews: Option<u32>,
+}
+
+/// A user review or comment left on a title's page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Review {
+    pub source: String,
+    pub author: String,
+    pub date: Option<String>,
+    /// The reviewer's own score, out of 5
+    pub score: Option<f32>,
+    pub text: String,
+    /// `--translate`: the text in the requested language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<String>,
+}
+
+/// A page `av view` can open to watch a title
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayCandidate {
+    /// Where the link came from: "javdb", a streaming site's host, or "search" for the
+    /// JavDB search page offered when nothing better was found
+    pub source: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "Item", rename_fields = "snake_case", complex)]
 pub struct AvItem {
     pub code: String,
     pub title: String,
+    /// `YYYY-MM-DD`, when the listing card shows it (JavDB cards do)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+    /// The site's user score on a 0–5 scale, when the card shows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f32>,
+    /// The card's cover thumbnail
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    /// The card's "has magnets" badge; `None` where listings don't have badges
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_magnets: Option<bool>,
+    /// The card's "has Chinese-subtitled magnets" badge
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_subtitles: Option<bool>,
+    /// The sources whose keyword search found it, in merge order (`javdb`, `sukebei`, ...)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// The other sources' titles for the same code (romanized, translated, a release name),
+    /// when they differ from `title`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alt_titles: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl From<&AvDetail> for AvItem {
+    /// The listing card a detail would have
+    fn from(d: &AvDetail) -> Self {
+        AvItem {
+            code: d.code.clone(),
+            title: d.title.clone(),
+            release_date: d.release_date.clone(),
+            rating: d.rating,
+            thumbnail_url: d.cover_url.clone(),
+            has_magnets: Some(!d.magnets.is_empty() || !d.magnet_infos.is_empty()),
+            has_subtitles: Some(d.magnet_infos.iter().any(crate::magnet::has_subtitles)),
+            sources: Vec::new(),
+            alt_titles: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "Magnet", rename_fields = "snake_case")]
 pub struct MagnetInfo {
     pub url: String,
     pub name: Option<String>,
     pub size: Option<String>,
+    /// `size` in bytes (`4.3 GiB` → 4617089843), filled in with the `quality_score`
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
     pub date: Option<String>,
     pub seeders: Option<u32>,
     pub leechers: Option<u32>,
     pub downloads: Option<u32>,
     pub resolution: Option<String>,
     pub codec: Option<String>,
+    /// From the torrent page's own running time when it gives one, else size over the
+    /// detail's `duration_minutes` (see `magnet::score_all`)
     pub avg_bitrate_mbps: Option<f32>,
+    /// 1-based part index when the torrent holds one part of a multi-part release (CD1/CD2)
+    #[serde(default)]
+    pub part: Option<u32>,
+    /// Parsed from `url` (see `magnet::parse`): 40-char upper-case hex
+    #[serde(default)]
+    pub infohash: Option<String>,
+    /// The link's `dn` parameter
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// The link's `tr` parameters
+    #[serde(default)]
+    pub trackers: Vec<String>,
+    /// Weighted score of seeders, bitrate, resolution and subtitle/leak keywords (see `magnet::score`)
+    #[serde(default)]
+    pub quality_score: Option<f32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl MagnetInfo {
+    /// Better first: higher `quality_score` (unscored last), then more seeders, then by URL
+    pub fn ranking(a: &MagnetInfo, b: &MagnetInfo) -> std::cmp::Ordering {
+        let score = |m: &MagnetInfo| m.quality_score.unwrap_or(f32::NEG_INFINITY);
+        score(b).total_cmp(&score(a)).then(b.seeders.cmp(&a.seeders)).then_with(|| a.url.cmp(&b.url))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "RankedActor", rename_fields = "snake_case", complex)]
 pub struct ActorItem {
     pub name: String,
-    pub hot: u32,
+    /// Position in JavDB's ranking (trending or weekly/monthly chart), when the page is ranked
+    #[serde(default)]
+    pub rank: Option<u32>,
+    /// Number of works listed for the actor, when the page shows it
+    #[serde(default)]
+    pub works_count: Option<u32>,
+    #[serde(default)]
+    pub views: Option<u64>,
+    /// The actor's JavDB page, i.e. her filmography
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    /// Year of her debut, from her profile; only looked up for `actors --debut-after`
+    #[serde(default)]
+    pub debut_year: Option<u16>,
+    /// `YYYY-MM-DD` of her newest title, from her JavDB page; only read for `actors --active-only`
+    #[serde(default)]
+    pub latest_release: Option<String>,
+}
+
+/// One page of an actors ranking
+#[derive(Debug, Clone, Default)]
+pub struct RankingPage {
+    pub actors: Vec<ActorItem>,
+    /// Actors the whole ranking lists
+    pub total: usize,
+    /// Pages the site splits the ranking into
+    pub pages: usize,
+    /// The site's page after this one, when there is one
+    pub next_url: Option<String>,
 }
 
+/// `actors --json` and `GET /actors`: one page of the ranking and how to get the next
+#[derive(Debug, Serialize, SimpleObject)]
+#[graphql(name = "ActorsPage", rename_fields = "snake_case")]
+pub struct ActorsEnvelope {
+    pub page: usize,
+    pub per_page: usize,
+    /// Actors the whole ranking lists
+    pub total: usize,
+    pub pages: usize,
+    pub has_next: bool,
+    /// Where page `page + 1` is: JavDB's page for the CLI, the API's own URL for `av serve`
+    pub next_page: Option<String>,
+    pub actors: Vec<ActorItem>,
+}
+
+impl RankingPage {
+    /// With `next_page` for the next page's URL, when there is a next page
+    pub fn envelope(self, page: usize, per_page: usize, next_page: impl FnOnce() -> Option<String>) -> ActorsEnvelope {
+        let has_next = self.next_url.is_some();
+        ActorsEnvelope {
+            page,
+            per_page,
+            total: self.total,
+            pages: self.pages,
+            has_next,
+            next_page: if has_next { next_page() } else { None },
+            actors: self.actors,
+        }
+    }
+}
+
+
+/// An actress's profile from an attribute source (minnano-av)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActressProfile {
+    pub name: String,
+    pub url: Option<String>,
+    /// `YYYY-MM-DD`
+    pub birth_date: Option<String>,
+    pub height_cm: Option<u16>,
+    pub bust: Option<u16>,
+    pub waist: Option<u16>,
+    pub hip: Option<u16>,
+    /// Cup letter, `A`..`O`
+    pub cup: Option<String>,
+    pub debut_year: Option<u16>,
+}
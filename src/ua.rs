@@ -0,0 +1,119 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+use crate::config;
+
+/// A consistent browser fingerprint: the UA string plus the client hints that browser sends
+#[derive(Debug, Clone)]
+pub struct BrowserProfile {
+    pub user_agent: String,
+    /// Chromium only; Firefox and Safari do not send `sec-ch-ua`
+    pub sec_ch_ua: Option<String>,
+    pub platform: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    /// New profile for every request
+    Request,
+    /// One profile per process (keeps Cloudflare clearance, which is bound to the UA, valid)
+    #[default]
+    Session,
+    /// Always the first profile in the pool
+    Off,
+}
+
+fn chrome(version: &str, os: &str, platform: &str) -> BrowserProfile {
+    BrowserProfile {
+        user_agent: format!(
+            "Mozilla/5.0 ({}) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{}.0.0.0 Safari/537.36",
+            os, version
+        ),
+        sec_ch_ua: Some(format!(
+            "\"Chromium\";v=\"{v}\", \"Google Chrome\";v=\"{v}\", \"Not.A/Brand\";v=\"99\"",
+            v = version
+        )),
+        platform: Some(platform.to_string()),
+    }
+}
+
+fn plain(ua: &str) -> BrowserProfile {
+    BrowserProfile { user_agent: ua.to_string(), sec_ch_ua: None, platform: None }
+}
+
+fn builtin_pool() -> Vec<BrowserProfile> {
+    vec![
+        chrome("131", "Macintosh; Intel Mac OS X 10_15_7", "macOS"),
+        chrome("131", "Windows NT 10.0; Win64; x64", "Windows"),
+        chrome("130", "Windows NT 10.0; Win64; x64", "Windows"),
+        chrome("131", "X11; Linux x86_64", "Linux"),
+        plain("Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:133.0) Gecko/20100101 Firefox/133.0"),
+        plain("Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0"),
+        plain("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.1 Safari/605.1.15"),
+    ]
+}
+
+/// `[network] user_agents` replaces the built-in pool; custom strings carry no client hints
+static POOL: LazyLock<Vec<BrowserProfile>> = LazyLock::new(|| {
+    let custom = &config::get().network.user_agents;
+    if custom.is_empty() {
+        builtin_pool()
+    } else {
+        custom.iter().map(|u| plain(u)).collect()
+    }
+});
+
+static SESSION: LazyLock<BrowserProfile> = LazyLock::new(|| match rotation() {
+    Rotation::Off => POOL[0].clone(),
+    _ => POOL[fastrand::usize(..POOL.len())].clone(),
+});
+
+fn rotation() -> Rotation {
+    config::get().network.user_agent_rotation
+}
+
+/// The profile used for client defaults (and for every request unless rotating per request)
+pub fn session_profile() -> &'static BrowserProfile {
+    &SESSION
+}
+
+fn pick() -> BrowserProfile {
+    match rotation() {
+        Rotation::Request => POOL[fastrand::usize(..POOL.len())].clone(),
+        _ => SESSION.clone(),
+    }
+}
+
+fn insert(headers: &mut HeaderMap, name: &'static str, value: &str) {
+    if let Ok(v) = HeaderValue::from_str(value) {
+        headers.insert(HeaderName::from_static(name), v);
+    }
+}
+
+/// Navigation headers a real browser sends for a top-level page load
+pub fn navigation_headers(profile: &BrowserProfile) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(v) = HeaderValue::from_str(&profile.user_agent) {
+        headers.insert(USER_AGENT, v);
+    }
+    if let Some(ch) = &profile.sec_ch_ua {
+        insert(&mut headers, "sec-ch-ua", ch);
+        insert(&mut headers, "sec-ch-ua-mobile", "?0");
+        if let Some(p) = &profile.platform {
+            insert(&mut headers, "sec-ch-ua-platform", &format!("\"{}\"", p));
+        }
+    }
+    insert(&mut headers, "sec-fetch-dest", "document");
+    insert(&mut headers, "sec-fetch-mode", "navigate");
+    insert(&mut headers, "sec-fetch-site", "same-origin");
+    insert(&mut headers, "sec-fetch-user", "?1");
+    insert(&mut headers, "upgrade-insecure-requests", "1");
+    headers
+}
+
+/// Per-request headers; only differs from the client defaults when rotating per request
+pub fn request_headers() -> HeaderMap {
+    navigation_headers(&pick())
+}
@@ -0,0 +1,444 @@
+//! `av update`: install a GitHub release in place of the running binary. The archive is
+//! checked against the release's `.sha256` (and its minisign signature when
+//! `[update] minisign_pubkey` is set) before anything is touched, and the previous binary
+//! is put back when the new one fails to start.
+//!
+//! With `[update] notify = true` other commands also check for a newer release, at most
+//! once a day, and mention it on stderr when they finish.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use which::which;
+
+use crate::config::{self, UpdateChannel};
+use crate::hooks;
+use crate::http;
+use crate::platform;
+use crate::sources::mock;
+use crate::store;
+use crate::util;
+
+const API: &str = "https://api.github.com";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Where the background check remembers its last answer
+const LAST_CHECK: &str = "update_check";
+/// Seconds between background checks
+const CHECK_INTERVAL: u64 = 86400;
+/// How long a finished command waits for a check still in flight
+const CHECK_WAIT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+/// The release workflow's target triple for this build
+fn target() -> Result<&'static str> {
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        Ok("x86_64-unknown-linux-gnu")
+    } else if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        Ok("aarch64-apple-darwin")
+    } else if cfg!(all(target_os = "windows", target_arch = "x86_64")) {
+        Ok("x86_64-pc-windows-msvc")
+    } else {
+        bail!("没有适用于当前平台（{} {}）的发行版", std::env::consts::OS, std::env::consts::ARCH)
+    }
+}
+
+fn asset_name(tag: &str) -> Result<String> {
+    let ext = if cfg!(windows) { "zip" } else { "tar.gz" };
+    Ok(format!("av-{}-{}.{}", tag, target()?, ext))
+}
+
+fn client() -> Result<reqwest::Client> {
+    http::with_proxy(reqwest::Client::builder())
+        .user_agent(format!("av/{}", VERSION))
+        .timeout(Duration::from_secs(300))
+        .build()
+        .context("client build")
+}
+
+fn with_token(req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match std::env::var("AV_GITHUB_TOKEN").or_else(|_| std::env::var("GITHUB_TOKEN")) {
+        Ok(token) if !token.trim().is_empty() => req.bearer_auth(token.trim()),
+        _ => req,
+    }
+}
+
+fn get(c: &reqwest::Client, path: &str) -> reqwest::RequestBuilder {
+    let url = format!("{}/repos/{}/{}", API, config::get().update.repo, path);
+    with_token(c.get(url).header("Accept", "application/vnd.github+json"))
+}
+
+async fn github<T: DeserializeOwned>(req: reqwest::RequestBuilder, what: &str) -> Result<T> {
+    let resp = req.send().await.context("查询 GitHub 发行版失败")?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        bail!("未找到发行版 {}", what);
+    }
+    if !resp.status().is_success() {
+        bail!("GitHub 返回 HTTP {}", resp.status().as_u16());
+    }
+    resp.json().await.context("GitHub 返回格式错误")
+}
+
+/// `v0.2.0` for `0.2.0`, the form release tags use
+fn tag_of(version: &str) -> String {
+    let v = version.trim();
+    if v.starts_with('v') { v.to_string() } else { format!("v{}", v) }
+}
+
+/// Release `tag`, or the newest one on `channel`
+pub async fn release(tag: Option<&str>, channel: UpdateChannel) -> Result<Release> {
+    let c = client()?;
+    match (tag, channel) {
+        (Some(t), _) => github(get(&c, &format!("releases/tags/{}", tag_of(t))), t).await,
+        (None, UpdateChannel::Stable) => github(get(&c, "releases/latest"), "latest").await,
+        (None, UpdateChannel::Nightly) => {
+            // Newest first, so the first non-draft is the latest build of either kind
+            let releases: Vec<Release> = github(get(&c, "releases").query(&[("per_page", "20")]), "nightly").await?;
+            releases.into_iter().find(|r| !r.draft).context("没有可用的发行版")
+        }
+    }
+}
+
+async fn download(c: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let resp = with_token(c.get(url)).send().await.with_context(|| format!("下载失败: {}", url))?;
+    if !resp.status().is_success() {
+        bail!("下载 {} 返回 HTTP {}", url, resp.status().as_u16());
+    }
+    Ok(resp.bytes().await.with_context(|| format!("下载失败: {}", url))?.to_vec())
+}
+
+/// Numeric core and dot-separated pre-release of `v1.2.3-nightly.20261014`; build
+/// metadata after `+` is ignored
+fn parse_version(v: &str) -> (Vec<u64>, Option<Vec<&str>>) {
+    let v = v.trim().trim_start_matches('v');
+    let v = v.split('+').next().unwrap_or_default();
+    let (core, pre) = match v.split_once('-') {
+        Some((core, pre)) => (core, Some(pre.split('.').collect())),
+        None => (v, None),
+    };
+    (core.split('.').map_while(|p| p.parse().ok()).collect(), pre)
+}
+
+/// Semver precedence: a pre-release sorts before its release, and numeric identifiers
+/// compare as numbers
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (core_a, pre_a) = parse_version(a);
+    let (core_b, pre_b) = parse_version(b);
+    core_a.cmp(&core_b).then_with(|| match (pre_a, pre_b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => {
+            for (x, y) in a.iter().zip(&b) {
+                let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => x.cmp(y),
+                };
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+    })
+}
+
+/// Whether release `tag` is a later version than `current`
+pub fn is_newer(tag: &str, current: &str) -> bool {
+    compare_versions(tag, current) == Ordering::Greater
+}
+
+/// The digest for `name` in a `sha256sum`-style file, or its only hash
+fn expected_sha(sums: &str, name: &str) -> Option<String> {
+    let lines: Vec<Vec<&str>> = sums.lines().map(|l| l.split_whitespace().collect()).filter(|f: &Vec<&str>| !f.is_empty()).collect();
+    let hash = match lines.as_slice() {
+        [only] if only.len() == 1 => only[0],
+        _ => lines.iter().find(|f| f.get(1).is_some_and(|n| n.trim_start_matches('*') == name))?[0],
+    };
+    Some(hash.to_lowercase())
+}
+
+async fn check_signature(archive: &Path, sig: &[u8], pubkey: &str) -> Result<()> {
+    if which("minisign").is_err() {
+        bail!("已配置 minisign_pubkey，但未检测到 minisign，请先安装: brew install minisign");
+    }
+    let sig_path = archive.with_extension("minisig");
+    std::fs::write(&sig_path, sig).context("写入签名文件失败")?;
+    let out = tokio::process::Command::new("minisign")
+        .arg("-Vm")
+        .arg(archive)
+        .arg("-x")
+        .arg(&sig_path)
+        .args(["-P", pubkey])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("启动 minisign 失败")?;
+    if !out.status.success() {
+        bail!("签名校验失败: {}", String::from_utf8_lossy(&out.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Unpack with the system `tar` (see `platform::tar`) and return the binary
+async fn extract(archive: &Path, dir: &Path) -> Result<PathBuf> {
+    let status = tokio::process::Command::new(platform::tar())
+        .arg("-xf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dir)
+        .stdin(Stdio::null())
+        .status()
+        .await
+        .context("启动 tar 失败")?;
+    if !status.success() {
+        bail!("解压失败，退出码: {:?}", status.code());
+    }
+    let bin = dir.join(format!("av{}", std::env::consts::EXE_SUFFIX));
+    if !bin.is_file() {
+        bail!("压缩包中没有 {}", bin.file_name().unwrap_or_default().to_string_lossy());
+    }
+    Ok(bin)
+}
+
+async fn starts(bin: &Path) -> Result<()> {
+    let out = tokio::process::Command::new(bin)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("无法运行 {}", bin.display()))?;
+    if !out.status.success() {
+        bail!("{} --version 退出码: {:?}", bin.display(), out.status.code());
+    }
+    Ok(())
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Swap `new` in for `current` with renames in `current`'s directory, so the binary is
+/// never half-written, and roll back when the swapped-in binary doesn't start
+async fn replace(current: &Path, new: &Path) -> Result<()> {
+    let staged = sibling(current, ".new");
+    let backup = sibling(current, ".old");
+    std::fs::copy(new, &staged).with_context(|| format!("写入失败: {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755)).context("设置执行权限失败")?;
+    }
+    if let Err(e) = starts(&staged).await {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e.context("新版本无法运行，未做更改"));
+    }
+
+    // A leftover from an earlier update (Windows can't delete a running binary)
+    let _ = std::fs::remove_file(&backup);
+    std::fs::rename(current, &backup).with_context(|| format!("无法替换 {}（权限不足？）", current.display()))?;
+    if let Err(e) = std::fs::rename(&staged, current) {
+        std::fs::rename(&backup, current).context("回滚失败，旧版本保存在 .old 文件中")?;
+        return Err(e).context("替换失败，已回滚");
+    }
+    if let Err(e) = starts(current).await {
+        std::fs::rename(&backup, current).context("回滚失败，旧版本保存在 .old 文件中")?;
+        return Err(e.context("新版本无法运行，已回滚"));
+    }
+    let _ = std::fs::remove_file(&backup);
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    current: &'static str,
+    latest: String,
+    channel: UpdateChannel,
+    /// Set when `--to` or `[update] pin` chose the version
+    pinned: bool,
+    prerelease: bool,
+    update_available: bool,
+}
+
+pub struct UpdateOptions {
+    /// A version to install instead of the channel's latest
+    pub to: Option<String>,
+    /// Overrides `[update] channel`
+    pub channel: Option<UpdateChannel>,
+    /// Only report whether an update is available
+    pub check: bool,
+    pub force: bool,
+}
+
+/// `av update [--to VERSION] [--channel stable|nightly] [--check] [--force]`
+pub async fn run(opts: UpdateOptions, json: bool) -> Result<()> {
+    let cfg = &config::get().update;
+    let channel = opts.channel.unwrap_or(cfg.channel);
+    let to = opts.to.or_else(|| cfg.pin.clone());
+    // Pinned hosts already on their version don't need to ask GitHub
+    if let Some(t) = to.as_deref().filter(|t| !opts.check && !opts.force && compare_versions(t, VERSION) == Ordering::Equal) {
+        util::note(format!("{} {}", "已是固定版本".green().bold(), tag_of(t)));
+        return Ok(());
+    }
+    if !json {
+        println!("正在检查更新...");
+    }
+    let release = release(to.as_deref(), channel).await?;
+    // A pinned version is installed whether it's newer or older; the channel only moves forward
+    let wanted = match &to {
+        Some(_) => compare_versions(&release.tag_name, VERSION) != Ordering::Equal,
+        None => is_newer(&release.tag_name, VERSION),
+    };
+
+    if opts.check {
+        let check = Check {
+            current: VERSION,
+            latest: release.tag_name.clone(),
+            channel,
+            pinned: to.is_some(),
+            prerelease: release.prerelease,
+            update_available: wanted,
+        };
+        if json {
+            util::print_output(&check, true);
+        } else if wanted {
+            println!("{} {} → {}（运行 av update 安装）", "有可用更新".yellow().bold(), VERSION, release.tag_name);
+        } else {
+            println!("{} {}", "已是最新版本".green().bold(), VERSION);
+        }
+        return Ok(());
+    }
+    if !wanted && !opts.force {
+        match &to {
+            Some(_) => util::note(format!("{} {}", "已是固定版本".green().bold(), VERSION)),
+            None => util::note(format!("{} {}", "已是最新版本".green().bold(), VERSION)),
+        }
+        return Ok(());
+    }
+    hooks::validate("self_update", &serde_json::json!({ "version": VERSION, "to": release.tag_name })).await?;
+
+    let name = asset_name(&release.tag_name)?;
+    let find = |n: &str| release.assets.iter().find(|a| a.name == n);
+    let Some(asset) = find(&name) else {
+        bail!("发行版 {} 中没有 {}", release.tag_name, name);
+    };
+    let Some(sums) = find(&format!("{}.sha256", name)).or_else(|| find("SHA256SUMS")) else {
+        bail!("发行版 {} 没有提供 SHA256 校验文件，拒绝安装", release.tag_name);
+    };
+    let pubkey = cfg.minisign_pubkey.clone();
+    let signature = match &pubkey {
+        Some(_) => match find(&format!("{}.minisig", name)) {
+            Some(a) => Some(a),
+            None => bail!("发行版 {} 没有 {} 的签名文件", release.tag_name, name),
+        },
+        None => None,
+    };
+    let current = std::env::current_exe().and_then(|p| p.canonicalize()).context("无法确定当前可执行文件路径")?;
+    if util::dry_run_skip(format!("将从 {} 下载 {} 并替换 {}", asset.browser_download_url, release.tag_name, current.display())) {
+        return Ok(());
+    }
+
+    let c = client()?;
+    util::note(format!("下载 {} ...", name));
+    let archive = download(&c, &asset.browser_download_url).await?;
+    let sums = String::from_utf8_lossy(&download(&c, &sums.browser_download_url).await?).into_owned();
+    let Some(expected) = expected_sha(&sums, &name) else {
+        bail!("校验文件中没有 {} 的 SHA256", name);
+    };
+    let actual = hex::encode(Sha256::digest(&archive));
+    if actual != expected {
+        bail!("SHA256 不匹配（期望 {}，实际 {}），已放弃更新", expected, actual);
+    }
+
+    let tmpdir = tempfile::tempdir().context("无法创建临时目录")?;
+    let archive_path = tmpdir.path().join(&name);
+    std::fs::write(&archive_path, &archive).context("写入临时文件失败")?;
+    if let (Some(key), Some(sig)) = (&pubkey, signature) {
+        check_signature(&archive_path, &download(&c, &sig.browser_download_url).await?, key).await?;
+    }
+    let unpacked = tmpdir.path().join("unpacked");
+    std::fs::create_dir_all(&unpacked).context("无法创建临时目录")?;
+    let bin = extract(&archive_path, &unpacked).await?;
+    replace(&current, &bin).await?;
+    util::note(format!("{} {} → {}", "更新成功！".green().bold(), VERSION, release.tag_name));
+    Ok(())
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LastCheck {
+    /// Unix seconds; failed checks count too, so an unreachable GitHub isn't retried every run
+    checked_at: u64,
+    latest: Option<String>,
+}
+
+/// Start the background check when notices are on and the last one is over a day old
+pub fn start_check() -> Option<JoinHandle<()>> {
+    let cfg = &config::get().update;
+    if !cfg.notify || cfg.pin.is_some() || mock::is_enabled() || util::is_dry_run() || !std::io::stderr().is_terminal() {
+        return None;
+    }
+    let last: LastCheck = store::load(LAST_CHECK).unwrap_or_default();
+    let now = util::now_secs();
+    if now.saturating_sub(last.checked_at) < CHECK_INTERVAL {
+        return None;
+    }
+    let channel = cfg.channel;
+    Some(tokio::spawn(async move {
+        let latest = match tokio::time::timeout(Duration::from_secs(10), release(None, channel)).await {
+            Ok(Ok(r)) => Some(r.tag_name),
+            Ok(Err(e)) => {
+                util::debug(format!("update check failed: {:#}", e));
+                last.latest
+            }
+            Err(_) => {
+                util::debug("update check timed out");
+                last.latest
+            }
+        };
+        if let Err(e) = store::save(LAST_CHECK, &LastCheck { checked_at: now, latest }) {
+            util::debug(format!("update check: {:#}", e));
+        }
+    }))
+}
+
+/// After a command: give the check a moment to finish, then mention a newer release
+pub async fn notice(check: Option<JoinHandle<()>>) {
+    if let Some(handle) = check {
+        let _ = tokio::time::timeout(CHECK_WAIT, handle).await;
+    }
+    let cfg = &config::get().update;
+    if !cfg.notify || cfg.pin.is_some() || !std::io::stderr().is_terminal() {
+        return;
+    }
+    let last: LastCheck = store::load(LAST_CHECK).unwrap_or_default();
+    if let Some(latest) = last.latest.filter(|l| is_newer(l, VERSION)) {
+        eprintln!("{} av {} 已发布（当前 {}），运行 av update 安装", "[更新]".yellow(), latest, VERSION);
+    }
+}
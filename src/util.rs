@@ -3,23 +3,34 @@ use colored::*;
 use serde::Serialize;
 use std::process::Stdio;
 use which::which;
-use std::env;
-use std::fs;
-use std::io::Write;
-use std::fs::File;
+use std::path::Path;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-// Unix-specific imports
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
+use crate::config::MetadataLang;
+use crate::types::{AvDetail, MagnetInfo};
+use crate::library::Library;
+use crate::notify;
+use crate::hooks;
+use crate::journal;
+use crate::platform;
+use crate::downloader::{self, DownloadClient};
+use crate::seeders;
 
-use crate::types::AvItem;
-use crate::types::AvDetail;
-use crate::types::ActorItem;
-
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex};
 
 static DEBUG: AtomicBool = AtomicBool::new(false);
 
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Default parallelism: quick enough for batches, gentle on JavDB's rate limiter
+pub const DEFAULT_JOBS: usize = 4;
+static JOBS: AtomicUsize = AtomicUsize::new(DEFAULT_JOBS);
+
+static LANG: Mutex<Option<MetadataLang>> = Mutex::new(None);
+
 pub fn set_debug(on: bool) {
     DEBUG.store(on, Ordering::Relaxed);
 }
@@ -28,188 +39,546 @@ pub fn is_debug() -> bool {
     DEBUG.load(Ordering::Relaxed)
 }
 
+pub fn set_json(on: bool) {
+    JSON.store(on, Ordering::Relaxed);
+}
+
+/// Output is JSON (`--json`, `--query`, `--fields`): stdout is for the data alone
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Progress and acknowledgements ("已保存 ..."): on stdout normally, on stderr in JSON
+/// mode so they can't corrupt piped JSON
+pub fn note<S: AsRef<str>>(msg: S) {
+    if is_json() {
+        eprintln!("{}", msg.as_ref());
+    } else {
+        println!("{}", msg.as_ref());
+    }
+}
+
+pub fn set_dry_run(on: bool) {
+    DRY_RUN.store(on, Ordering::Relaxed);
+}
+
+/// `--dry-run`: report downloads, notifications and writes instead of performing them
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Print what a side effect would have done; returns true when it must be skipped
+pub fn dry_run_skip<S: AsRef<str>>(action: S) -> bool {
+    if is_dry_run() {
+        eprintln!("{} {}", "[dry-run]".yellow(), action.as_ref());
+    }
+    is_dry_run()
+}
+
+pub fn set_jobs(n: usize) {
+    JOBS.store(n.max(1), Ordering::Relaxed);
+}
+
+/// How many codes/pages batch operations fetch at once
+pub fn jobs() -> usize {
+    JOBS.load(Ordering::Relaxed)
+}
+
+pub fn set_lang(lang: Option<MetadataLang>) {
+    *LANG.lock().unwrap() = lang;
+}
+
+/// Preferred metadata language, `None` when the user has no preference
+pub fn lang() -> Option<MetadataLang> {
+    *LANG.lock().unwrap()
+}
+
 pub fn debug<S: AsRef<str>>(msg: S) {
     if is_debug() {
         eprintln!("[DEBUG] {}", msg.as_ref());
     }
 }
 
+/// `--query`, applied to whatever `print_output` prints
+static QUERY: Mutex<Option<crate::query::Expr>> = Mutex::new(None);
+/// The first `--query` evaluation error, reported as the command's error once it finishes
+static QUERY_ERROR: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+
+pub fn set_query(expr: Option<crate::query::Expr>) {
+    *QUERY.lock().unwrap() = expr;
+}
+
+pub fn take_query_error() -> Option<anyhow::Error> {
+    QUERY_ERROR.lock().unwrap().take()
+}
+
+/// Through text rather than `serde_json::to_value`, which widens an `f32` like 65.4 into
+/// 65.4000015258789
+fn json_value<T: Serialize>(value: &T) -> serde_json::Result<serde_json::Value> {
+    serde_json::from_str(&serde_json::to_string(value)?)
+}
+
+/// Print JSON, through `--query` when one is set
+fn emit_json<T: Serialize>(value: &T) -> serde_json::Result<()> {
+    let query = QUERY.lock().unwrap().clone();
+    let Some(expr) = query else {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        return Ok(());
+    };
+    match expr.eval(&json_value(value)?).and_then(|results| crate::query::render(&results)) {
+        Ok(s) if s.is_empty() => {}
+        Ok(s) => println!("{}", s),
+        Err(e) => {
+            QUERY_ERROR.lock().unwrap().get_or_insert(e.context(crate::errors::Tagged::new("query", "--query 执行失败")));
+        }
+    }
+    Ok(())
+}
+
 pub fn print_output<T: Serialize + std::fmt::Debug>(value: &T, json: bool) {
     if json {
-        match serde_json::to_string_pretty(value) {
-            Ok(s) => println!("{}", s),
-            Err(_) => println!("{:?}", value),
+        if emit_json(value).is_err() {
+            println!("{:?}", value);
         }
     } else {
         println!("{:?}", value);
     }
 }
 
-pub async fn download_via_aria2(magnet: &str) -> Result<()> {
+/// One compact JSON value per line, for results printed as they are found rather than at
+/// the end; through `--query` like `print_output`
+pub fn print_json_line<T: Serialize + std::fmt::Debug>(value: &T) {
+    if QUERY.lock().unwrap().is_some() {
+        print_output(value, true);
+        return;
+    }
+    match serde_json::to_string(value) {
+        Ok(line) => println!("{}", line),
+        Err(_) => println!("{:?}", value),
+    }
+}
+
+/// Keep only `paths` (`code`, `magnet_infos.url`, ...) of `value`, in the order asked for;
+/// in a list, of every element
+fn pick_fields(value: serde_json::Value, paths: &[Vec<&str>]) -> Result<serde_json::Value> {
+    use serde_json::Value;
+    match value {
+        Value::Array(items) => Ok(Value::Array(items.into_iter().map(|v| pick_fields(v, paths)).collect::<Result<_>>()?)),
+        Value::Object(mut obj) => {
+            let mut heads: Vec<&str> = Vec::new();
+            for p in paths {
+                if !heads.contains(&p[0]) {
+                    heads.push(p[0]);
+                }
+            }
+            let mut out = serde_json::Map::new();
+            for head in heads {
+                let Some(field) = obj.shift_remove(head) else {
+                    let known: Vec<&str> = out.keys().chain(obj.keys()).map(String::as_str).collect();
+                    bail!("未知字段: {}（可选: {}）", head, known.join(", "));
+                };
+                let tails: Vec<Vec<&str>> = paths.iter().filter(|p| p[0] == head).map(|p| p[1..].to_vec()).collect();
+                let field = if tails.iter().any(|t| t.is_empty()) { field } else { pick_fields(field, &tails)? };
+                out.insert(head.to_string(), field);
+            }
+            Ok(Value::Object(out))
+        }
+        // `popularity.wanted` on a title without popularity
+        other => Ok(other),
+    }
+}
+
+/// `--fields`: JSON with only the listed fields
+pub fn print_fields<T: Serialize>(value: &T, fields: &[String]) -> Result<()> {
+    let paths: Vec<Vec<&str>> = fields.iter().map(|f| f.trim()).filter(|f| !f.is_empty()).map(|f| f.split('.').collect()).collect();
+    emit_json(&pick_fields(json_value(value)?, &paths)?)?;
+    Ok(())
+}
+
+/// Run aria2c on `magnet` until it finishes, saving into `dir`; with `stop_timeout` it
+/// gives up once the download has had no speed for that many seconds
+async fn run_aria2(magnet: &str, stop_timeout: Option<u64>, dir: Option<&Path>) -> Result<()> {
     if which("aria2c").is_err() {
         bail!("未检测到 aria2c，请先安装: brew install aria2");
     }
 
     let mut cmd = tokio::process::Command::new("aria2c");
-    cmd.arg("--seed-time=0").arg(magnet).stdin(Stdio::null());
+    cmd.args(downloader::aria2_args(&downloader::options()));
+    if let Some(secs) = stop_timeout {
+        cmd.arg(format!("--bt-stop-timeout={}", secs));
+    }
+    if let Some(dir) = dir {
+        cmd.arg(format!("--dir={}", dir.display()));
+    }
+    cmd.arg(magnet).stdin(Stdio::null());
 
     let status = cmd.status().await.context("启动 aria2c 失败")?;
     if !status.success() {
         bail!("aria2c 下载失败，退出码: {:?}", status.code());
     }
-    println!("{} {}", "下载完成".green().bold(), magnet);
     Ok(())
 }
 
-pub async fn open_system_uri(uri: &str) -> Result<()> {
-    #[cfg(target_os = "macos")]
-    let mut cmd = {
-        let mut c = tokio::process::Command::new("open");
-        c.arg(uri);
-        c
-    };
+async fn download_done(code: String, magnet: &str) {
+    note(format!("{} {}", "下载完成".green().bold(), magnet));
+    notify::emit(&notify::Event::DownloadComplete { code, magnet: magnet.to_string() }).await;
+}
 
-    #[cfg(target_os = "linux")]
-    let mut cmd = {
-        let mut c = tokio::process::Command::new("xdg-open");
-        c.arg(uri);
-        c
-    };
+pub async fn download_via_aria2(magnet: &str) -> Result<()> {
+    run_aria2(magnet, None, None).await?;
+    download_done(code_from_magnet(magnet), magnet).await;
+    Ok(())
+}
 
-    #[cfg(target_os = "windows")]
-    let mut cmd = {
-        let mut c = tokio::process::Command::new("cmd");
-        c.arg("/C").arg("start").arg("").arg(uri);
-        c
-    };
+/// Best-effort code for a magnet, taken from its display name (`dn=`)
+fn code_from_magnet(magnet: &str) -> String {
+    magnet
+        .split(['?', '&'])
+        .find_map(|kv| kv.strip_prefix("dn="))
+        .map(|dn| urlencoding::decode(dn).map(|s| s.into_owned()).unwrap_or_else(|_| dn.to_string()))
+        .unwrap_or_default()
+}
 
-    let status = cmd.status().await.context("调用系统打开 URI 失败")?;
+pub async fn open_system_uri(uri: &str) -> Result<()> {
+    if dry_run_skip(format!("将调用系统打开: {}", uri)) {
+        return Ok(());
+    }
+    let status = platform::open_command(uri).status().await.context("调用系统打开 URI 失败")?;
     if !status.success() {
         bail!("系统无法打开: {}", uri);
     }
-    println!("{} {}", "已交给系统默认的 BT 客户端处理".green().bold(), uri);
+    note(format!("{} {}", "已交给系统默认的 BT 客户端处理".green().bold(), uri));
     Ok(())
 }
 
+/// Hand `magnet` to a torrent client, placed by `code`'s metadata when there is a detail
+async fn send_to_client(client: &impl DownloadClient, magnet: &str, code: &str, d: Option<&AvDetail>) -> Result<()> {
+    let placement = downloader::placement(&client.templates(), code, d);
+    let described = downloader::describe(&placement);
+    if dry_run_skip(format!("将添加到 {}{}: {}", client.name(), described, magnet)) {
+        return Ok(());
+    }
+    let via = client.name().to_lowercase();
+    hooks::validate("download", &serde_json::json!({ "magnet": magnet, "via": via })).await?;
+    let result = async {
+        client.add(magnet, &placement).await?;
+        note(format!("{} {}{}", format!("已添加到 {}", client.name()).green().bold(), if code.is_empty() { magnet } else { code }, described));
+        if client.waits() {
+            let hash = crate::magnet::parse(magnet)?.infohash;
+            note(format!("等待 {} 完成下载…", client.name()));
+            client.wait(&hash, &placement).await?;
+            download_done(code.to_string(), magnet).await;
+        }
+        Ok(())
+    }
+    .await;
+    journal::record(code, magnet, &via, &result, client.waits());
+    result
+}
+
+/// `true` when `[download] client` (or a configured client section) took the magnet
+pub async fn dispatch(magnet: &str, code: &str, d: Option<&AvDetail>) -> Result<bool> {
+    match downloader::selected()? {
+        Some(downloader::Selected::Qbittorrent(c)) => send_to_client(c, magnet, code, d).await?,
+        Some(downloader::Selected::Deluge(c)) => send_to_client(c, magnet, code, d).await?,
+        Some(downloader::Selected::Rtorrent(c)) => send_to_client(c, magnet, code, d).await?,
+        Some(downloader::Selected::Remote(c)) => send_to_client(c, magnet, code, d).await?,
+        None => return Ok(false),
+    }
+    Ok(true)
+}
+
 pub async fn download_magnet(magnet: &str) -> Result<()> {
+    let code = crate::code::find(&code_from_magnet(magnet)).map(|c| c.to_string()).unwrap_or_default();
+    if dispatch(magnet, &code, None).await? {
+        return Ok(());
+    }
+    let via = if which("aria2c").is_ok() { "aria2c" } else { "系统默认的 BT 客户端" };
+    if dry_run_skip(format!("将通过 {} 下载: {}", via, magnet)) {
+        return Ok(());
+    }
+    hooks::validate("download", &serde_json::json!({ "magnet": magnet, "via": via })).await?;
     if which("aria2c").is_ok() {
-        download_via_aria2(magnet).await
+        let result = download_via_aria2(magnet).await;
+        journal::record(&code, magnet, "aria2c", &result, true);
+        result
     } else {
-        open_system_uri(magnet).await
+        let result = open_system_uri(magnet).await;
+        journal::record(&code, magnet, "system", &result, false);
+        result
     }
 }
 
+/// Download one of `d`'s magnets, starting with `first` (else the best one), into `dir`
+/// (aria2c's working directory when `None`). With `[magnets] fallback_timeout_secs` set,
+/// a magnet that finds no peers within the window is abandoned for the next-best one, up
+/// to `fallback_attempts` magnets. Without aria2c the best magnet goes to the system BT
+/// client instead, which can't be monitored or pointed at `dir`. With a torrent client set
+/// up (see `downloader`) and no `dir`, the best magnet is added there, placed by the
+/// detail's metadata.
+pub async fn download_detail(d: &AvDetail, first: Option<String>, dir: Option<&Path>) -> Result<()> {
+    let mut magnets = ranked_magnets(d);
+    if let Some(f) = first {
+        magnets.retain(|m| m != &f);
+        magnets.insert(0, f);
+    }
+    let Some(best) = magnets.first().cloned() else { bail!("{} 没有可用的磁力链接", d.code) };
+    if dir.is_none() && dispatch(&best, &d.code, Some(d)).await? {
+        return Ok(());
+    }
+    if which("aria2c").is_err() {
+        if dir.is_some() {
+            bail!("下载到指定目录需要 aria2c，请先安装: brew install aria2");
+        }
+        return download_magnet(&best).await;
+    }
+    let cfg = &crate::config::get().magnets;
+    let timeout = (cfg.fallback_timeout_secs > 0 && magnets.len() > 1).then_some(cfg.fallback_timeout_secs);
+    magnets.truncate(if timeout.is_some() { cfg.fallback_attempts.max(1) } else { 1 });
+    let into = dir.map(|p| format!(" 到 {}", p.display())).unwrap_or_default();
+    let plan = timeout.map(|t| format!("（{} 秒无速度则换用下一个磁力，共 {} 个候选）", t, magnets.len())).unwrap_or_default();
+    if dry_run_skip(format!("将通过 aria2c 下载 {}{}: {}{}", d.code, into, best, plan)) {
+        return Ok(());
+    }
+    let total = magnets.len();
+    let mut last_err = None;
+    for (i, m) in magnets.into_iter().enumerate() {
+        crate::cancel::check()?;
+        hooks::validate("download", &serde_json::json!({ "magnet": m, "via": "aria2c" })).await?;
+        let result = run_aria2(&m, timeout, dir).await;
+        journal::record(&d.code, &m, "aria2c", &result, true);
+        match result {
+            Ok(()) => {
+                download_done(d.code.clone(), &m).await;
+                return Ok(());
+            }
+            Err(e) if total > 1 => {
+                eprintln!("[WARN] {} 第 {}/{} 个磁力未能下载（{:#}）", d.code, i + 1, total, e);
+                last_err = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("没有可下载的磁力"))).with_context(|| format!("{} 的 {} 个磁力均未能下载", d.code, total))
+}
+
 pub async fn open_browser_url(url: &str) -> Result<()> {
     open_system_uri(url).await
 }
 
-pub async fn self_update() -> Result<()> {
-    println!("正在检查更新...");
-    
-    // 创建临时目录
-    let tmpdir = tempfile::tempdir().context("无法创建临时目录")?;
-    let installer_path = tmpdir.path().join("install.sh");
-    
-    // 下载安装脚本
-    let install_script_url = "https://raw.github.com/auv-sh/av/master/install.sh";
-    println!("下载安装脚本: {}", install_script_url);
-    
-    let response = reqwest::get(install_script_url).await.context("下载安装脚本失败")?;
-    let script_content = response.text().await.context("读取安装脚本内容失败")?;
-    
-    // 写入安装脚本到临时文件
-    let mut file = File::create(&installer_path).context("创建临时安装脚本文件失败")?;
-    file.write_all(script_content.as_bytes()).context("写入安装脚本内容失败")?;
-    
-    // 设置执行权限
-    #[cfg(unix)]
-    {
-        let mut perms = fs::metadata(&installer_path).context("获取文件权限失败")?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&installer_path, perms).context("设置执行权限失败")?;
-    }
-    
-    // Windows doesn't need explicit permission setting for execution
-    
-    // 获取当前可执行文件路径
-    let _current_exe = env::current_exe().context("无法确定当前可执行文件路径")?;
-    
-    // 执行安装脚本
-    println!("执行安装脚本...");
-    
-    #[cfg(unix)]
-    let status = tokio::process::Command::new("sh")
-        .arg(&installer_path)
-        .status()
-        .await
-        .context("执行安装脚本失败")?;
-        
-    #[cfg(windows)]
-    let status = {
-        // Windows 需要使用 PowerShell 或 cmd 来执行脚本
-        // 首先将 .sh 脚本内容转换为 .ps1 脚本
-        let ps_path = tmpdir.path().join("install.ps1");
-        let ps_content = script_content.replace("\r\n", "\n").replace("\n", "\r\n");
-        let mut ps_file = File::create(&ps_path).context("创建 PowerShell 脚本文件失败")?;
-        ps_file.write_all(ps_content.as_bytes()).context("写入 PowerShell 脚本内容失败")?;
-        
-        // 使用 PowerShell 执行脚本
-        tokio::process::Command::new("powershell")
-            .arg("-ExecutionPolicy")
-            .arg("Bypass")
-            .arg("-File")
-            .arg(&ps_path)
-            .status()
-            .await
-            .context("执行安装脚本失败")?
-    };
-    
-    if status.success() {
-        println!("{}", "更新成功！".green().bold());
-    } else {
-        bail!("安装脚本执行失败，退出码: {:?}", status.code());
+/// Terminal columns taken by `s`: two for CJK and other wide characters, none for
+/// combining marks
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// `s` followed by the spaces that bring it to `width` terminal columns
+pub fn pad(s: &str, width: usize) -> String {
+    format!("{}{}", s, " ".repeat(width.saturating_sub(display_width(s))))
+}
+
+/// Shorten to at most `max` terminal columns, ending with an ellipsis when cut
+pub fn truncate_width(s: &str, max: usize) -> String {
+    if display_width(s) <= max {
+        return s.to_string();
     }
-    
-    Ok(())
+    let mut out = String::new();
+    let mut width = display_width("…");
+    for ch in s.chars() {
+        let w = ch.width().unwrap_or(0);
+        if width + w > max {
+            break;
+        }
+        width += w;
+        out.push(ch);
+    }
+    out.push('…');
+    out
+}
+
+/// Split `s` into lines of at most `width` terminal columns, breaking after a space when
+/// the line has one and anywhere otherwise (CJK text has no spaces to break at)
+pub fn wrap_width(s: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for ch in s.chars() {
+        let w = ch.width().unwrap_or(0);
+        if line_width + w > width && !line.is_empty() {
+            let rest = match line.rfind(' ') {
+                Some(i) if i > 0 => line.split_off(i + 1),
+                _ => String::new(),
+            };
+            lines.push(line.trim_end().to_string());
+            line_width = display_width(&rest);
+            line = rest;
+        }
+        line.push(ch);
+        line_width += w;
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
 }
 
-pub fn print_items_table(items: &[AvItem]) {
-    println!("{} {}", "共".bold(), items.len());
+/// Columns of the terminal stdout is attached to, `None` when it's piped
+pub fn terminal_width() -> Option<usize> {
+    let term = console::Term::stdout();
+    if !term.is_term() {
+        return None;
+    }
+    term.size_checked().map(|(_, cols)| cols as usize)
+}
 
-    let index_header = "#";
-    let code_header = "番号";
-    let title_header = "标题";
+/// Best-scored magnet for a detail (see `MagnetInfo::ranking`), falling back to the first bare link
+pub fn best_magnet(d: &AvDetail) -> Option<String> {
+    d.magnet_infos
+        .iter()
+        .min_by(|a, b| MagnetInfo::ranking(a, b))
+        .map(|m| m.url.clone())
+        .or_else(|| d.magnets.first().cloned())
+}
 
-    let index_width = std::cmp::max(index_header.len(), format!("{}", items.len()).len());
-    let code_width = std::cmp::max(
-        code_header.len(),
-        items.iter().map(|i| i.code.len()).max().unwrap_or(0),
-    );
+/// Every magnet of a detail, best scored first (so `best_magnet` leads), then the bare links
+pub fn ranked_magnets(d: &AvDetail) -> Vec<String> {
+    let mut infos: Vec<_> = d.magnet_infos.iter().collect();
+    infos.sort_by(|a, b| MagnetInfo::ranking(a, b));
+    let mut out: Vec<String> = infos.into_iter().map(|m| m.url.clone()).collect();
+    for m in &d.magnets {
+        if !out.contains(m) {
+            out.push(m.clone());
+        }
+    }
+    out
+}
 
-    println!(
-        "{:<iw$}  {:<cw$}  {}",
-        index_header.bold(),
-        code_header.bold(),
-        title_header.bold(),
-        iw = index_width,
-        cw = code_width
-    );
+/// Upper-cased hex BitTorrent infohash from a `magnet:?xt=urn:btih:` link
+pub fn magnet_infohash(magnet: &str) -> Option<String> {
+    crate::magnet::parse(magnet).ok().map(|m| m.infohash)
+}
 
-    let sep_i = "-".repeat(index_width);
-    let sep_c = "-".repeat(code_width);
-    println!("{:<iw$}  {:<cw$}  {}", sep_i, sep_c, "-".repeat(10), iw = index_width, cw = code_width);
+/// Shorten to at most `max` characters, ending with an ellipsis when cut
+pub fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(max.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
 
-    for (idx, item) in items.iter().enumerate() {
-        let row_index = idx + 1;
-        println!(
-            "{:<iw$}  {:<cw$}  {}",
-            row_index,
-            item.code,
-            item.title,
-            iw = index_width,
-            cw = code_width
-        );
+pub fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0
+            c if (c as u32) < 0x20 && c != '\n' && c != '\t' && c != '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The text of an XML element or attribute: entities decoded, a CDATA wrapper removed
+pub fn xml_unescape(s: &str) -> String {
+    let s = s.trim();
+    let s = s.strip_prefix("<![CDATA[").and_then(|s| s.strip_suffix("]]>")).unwrap_or(s);
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let Some(end) = rest.find(';').filter(|e| *e <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let decoded = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            n if n.starts_with("#x") => u32::from_str_radix(&n[2..], 16).ok().and_then(char::from_u32),
+            n if n.starts_with('#') => n[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
     }
+    out.push_str(rest);
+    out
+}
+
+static SCRAPED_DATE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(\d{4})-(\d{2})-(\d{2})(?:[ T](\d{2}):(\d{2}))?").unwrap());
+
+/// Convert a `YYYY-MM-DD[ HH:MM]` date (as scraped) into an RFC 2822 timestamp for feeds.
+/// Release dates are Japanese, so the date and time are taken as JST (`+0900`); impossible
+/// dates and times (`2024-02-31`, `25:00`) give `None`.
+pub fn rfc2822_from_date(s: &str) -> Option<String> {
+    let caps = SCRAPED_DATE.captures(s)?;
+    let num = |i: usize| caps.get(i).and_then(|x| x.as_str().parse::<u32>().ok());
+    let date = chrono::NaiveDate::from_ymd_opt(num(1)? as i32, num(2)?, num(3)?)?;
+    let time = date.and_hms_opt(num(4).unwrap_or(0), num(5).unwrap_or(0), 0)?;
+    let jst = chrono::FixedOffset::east_opt(9 * 3600)?;
+    let at = time.and_local_timezone(jst).single()?;
+    Some(at.format("%a, %d %b %Y %H:%M:%S %z").to_string())
+}
+
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Parse "90s", "30m", "6h", "1d" or combinations like "1h30m"; bare numbers are seconds
+pub fn parse_duration(s: &str) -> Result<std::time::Duration> {
+    let s = s.trim();
+    if s.is_empty() {
+        bail!("时间间隔为空");
+    }
+    let mut total: u64 = 0;
+    let mut num = String::new();
+    for ch in s.chars() {
+        if ch.is_ascii_digit() {
+            num.push(ch);
+            continue;
+        }
+        let n: u64 = num.parse().with_context(|| format!("无法解析时间间隔: {}", s))?;
+        num.clear();
+        let unit = match ch {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => bail!("未知时间单位 '{}': {}", ch, s),
+        };
+        total += n * unit;
+    }
+    if !num.is_empty() {
+        total += num.parse::<u64>().with_context(|| format!("无法解析时间间隔: {}", s))?;
+    }
+    if total == 0 {
+        bail!("时间间隔必须大于 0: {}", s);
+    }
+    Ok(std::time::Duration::from_secs(total))
 }
 
 pub fn looks_uncensored(text: &str) -> bool {
@@ -232,11 +601,14 @@ pub fn looks_uncensored(text: &str) -> bool {
 pub fn print_detail_human(detail: &AvDetail) {
     println!("番号： {}", detail.code.bold());
     println!("标题： {}", detail.title);
+    if let Some(badge) = Library::load().unwrap_or_default().badge(&detail.code) {
+        println!("状态： {}", badge.green());
+    }
     if !detail.actor_names.is_empty() {
         println!("演员： {}", detail.actor_names.join(", "));
     }
     if let Some(date) = &detail.release_date {
-        println!("发行： {}", date);
+        println!("发行： {}", crate::dates::render(date));
     }
     if let Some(cover) = &detail.cover_url {
         println!("封面： {}", cover);
@@ -260,7 +632,26 @@ pub fn print_detail_human(detail: &AvDetail) {
         println!("类别： {}", detail.genres.join(", "));
     }
     if let Some(r) = detail.rating {
-        println!("评分： {}", r);
+        let sources: Vec<String> = detail
+            .ratings
+            .iter()
+            .map(|s| match s.votes {
+                Some(v) => format!("{} {}/{} · {} 人", s.source, s.raw, s.scale, v),
+                None => format!("{} {}/{}", s.source, s.raw, s.scale),
+            })
+            .collect();
+        if sources.is_empty() {
+            println!("评分： {}", r);
+        } else {
+            println!("评分： {}（{}）", r, sources.join("，"));
+        }
+    }
+    if let Some(p) = &detail.popularity {
+        let parts: Vec<String> = [(p.wanted, "人想看"), (p.watched, "人看过"), (p.owned, "人拥有"), (p.reviews, "条评论")]
+            .iter()
+            .filter_map(|(n, what)| n.map(|n| format!("{} {}", n, what)))
+            .collect();
+        println!("人气： {}（{}）", parts.join(" · "), p.source);
     }
     if let Some(plot) = &detail.plot {
         println!("剧情：\n{}", plot);
@@ -273,45 +664,35 @@ pub fn print_detail_human(detail: &AvDetail) {
     }
     if !detail.magnets.is_empty() {
         println!("磁力： 共{}条", detail.magnets.len());
+        let history = seeders::load();
         for (i, m) in detail.magnet_infos.iter().take(5).enumerate() {
             let mut line = format!("  {}. {}", i + 1, m.url);
             if let Some(name) = &m.name { line.push_str(&format!("\n     {}", name)); }
+            if let Some(p) = m.part { line.push_str(&format!(" | CD{}", p)); }
             if let Some(size) = &m.size { line.push_str(&format!(" | {}", size)); }
             if let Some(res) = &m.resolution { line.push_str(&format!(" | {}", res)); }
             if let Some(codec) = &m.codec { line.push_str(&format!(" | {}", codec)); }
             if let Some(b) = m.avg_bitrate_mbps { line.push_str(&format!(" | ~{:.2} Mbps", b)); }
             if let Some(s) = m.seeders { line.push_str(&format!(" | S:{}", s)); }
             if let Some(lc) = m.leechers { line.push_str(&format!(" L:{}", lc)); }
+            if let Some(q) = m.quality_score { line.push_str(&format!(" | Q:{:.1}", q)); }
+            if let Some(t) = m.infohash.as_deref().and_then(|h| seeders::trend(&history, h)) { line.push_str(&format!(" | 做种 {}", t)); }
             println!("{}", line);
         }
     }
 }
 
-pub fn print_actors_table(actors: &[ActorItem], page: usize, per_page: usize, total: usize) {
-    println!("{} {} (page {} / {}):", "Total".bold(), total, page, ((total + per_page - 1) / per_page));
-    let index_header = "#";
-    let name_header = "演员";
-    let hot_header = "热度";
-    let index_width = std::cmp::max(index_header.len(), format!("{}", actors.len()).len());
-    let name_width = std::cmp::max(name_header.len(), actors.iter().map(|a| a.name.len()).max().unwrap_or(0));
-    println!(
-        "{:<iw$}  {:<nw$}  {}",
-        index_header.bold(),
-        name_header.bold(),
-        hot_header.bold(),
-        iw = index_width,
-        nw = name_width
-    );
-    println!(
-        "{:<iw$}  {:<nw$}  {}",
-        "-".repeat(index_width),
-        "-".repeat(name_width),
-        "-".repeat(6),
-        iw = index_width,
-        nw = name_width
-    );
-    for (i, a) in actors.iter().enumerate() {
-        println!("{:<iw$}  {:<nw$}  {}", i + 1 + (page - 1) * per_page, a.name, a.hot, iw = index_width, nw = name_width);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc2822_dates() {
+        assert_eq!(rfc2822_from_date("2024-06-01").as_deref(), Some("Sat, 01 Jun 2024 00:00:00 +0900"));
+        assert_eq!(rfc2822_from_date("2024-02-29 21:05").as_deref(), Some("Thu, 29 Feb 2024 21:05:00 +0900"));
+        for bad in ["2024-02-31", "2023-02-29", "2024-13-01", "2024-06-01 24:00", "2024-06-01 12:60", "soon"] {
+            assert_eq!(rfc2822_from_date(bad), None, "{}", bad);
+        }
     }
 }
-
@@ -0,0 +1,200 @@
+//! `av verify`: probe downloaded videos with ffprobe and compare them with the code's
+//! metadata, to catch truncated downloads and files whose name claims a better resolution
+//! than they have.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use which::which;
+
+use crate::code;
+use crate::magnet;
+use crate::scan;
+use crate::scraper;
+use crate::util;
+
+/// A title is truncated when its videos run shorter than the listed runtime by more than
+/// this share of it...
+const SHORT_RATIO: f64 = 0.05;
+/// ...and by more than this many minutes, so rounding on short titles isn't flagged
+const SHORT_MINUTES: f64 = 3.0;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Probe {
+    pub duration_minutes: Option<f64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub probe: Probe,
+    /// `4K` / `1080p` / `720p` / `480p`, from the frame size
+    pub resolution: Option<&'static str>,
+    /// What the file name says it is
+    pub claimed_resolution: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub code: String,
+    pub files: Vec<FileReport>,
+    /// The listed runtime
+    pub expected_minutes: Option<u32>,
+    /// All parts together
+    pub actual_minutes: Option<f64>,
+    pub issues: Vec<String>,
+}
+
+pub(crate) async fn probe(path: &Path) -> Result<Probe> {
+    let out = tokio::process::Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-show_entries", "format=duration:stream=width,height", "-of", "json"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("启动 ffprobe 失败")?;
+    if !out.status.success() {
+        bail!("ffprobe 无法读取: {}", String::from_utf8_lossy(&out.stderr).trim());
+    }
+    let v: Value = serde_json::from_slice(&out.stdout).context("ffprobe 输出格式错误")?;
+    let stream = &v["streams"][0];
+    Ok(Probe {
+        duration_minutes: v["format"]["duration"].as_str().and_then(|d| d.parse::<f64>().ok()).map(|s| s / 60.0),
+        width: stream["width"].as_u64().map(|w| w as u32),
+        height: stream["height"].as_u64().map(|h| h as u32),
+    })
+}
+
+/// Resolution class of a frame. Letterboxed (1920x800) and portrait encodes are judged by
+/// their long side as if it were 16:9.
+fn resolution_of(width: u32, height: u32) -> &'static str {
+    let lines = (width.max(height) * 9 / 16).max(width.min(height));
+    match lines {
+        1800.. => "4K",
+        1000.. => "1080p",
+        650.. => "720p",
+        _ => "480p",
+    }
+}
+
+fn rank(res: &str) -> u8 {
+    match res {
+        "4K" => 4,
+        "1080p" => 3,
+        "720p" => 2,
+        _ => 1,
+    }
+}
+
+async fn verify(code: String, paths: Vec<PathBuf>) -> Report {
+    let mut issues = Vec::new();
+    let mut files = Vec::new();
+    for path in paths {
+        let probe = match probe(&path).await {
+            Ok(p) => p,
+            Err(e) => {
+                issues.push(format!("{}: {:#}", path.display(), e));
+                Probe::default()
+            }
+        };
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let resolution = probe.width.zip(probe.height).map(|(w, h)| resolution_of(w, h));
+        let claimed_resolution = magnet::resolution_in(name);
+        if let (Some(actual), Some(claimed)) = (resolution, claimed_resolution) {
+            if rank(actual) < rank(claimed) {
+                issues.push(format!("{}: 文件名标注 {}，实际为 {}", name, claimed, actual));
+            }
+        }
+        files.push(FileReport { path, probe, resolution, claimed_resolution });
+    }
+
+    let actual_minutes = files.iter().map(|f| f.probe.duration_minutes).sum::<Option<f64>>();
+    let expected_minutes = match scraper::fetch_detail(&code).await {
+        Ok(d) => d.duration_minutes,
+        Err(e) => {
+            issues.push(format!("获取元数据失败: {:#}", e));
+            None
+        }
+    };
+    if let (Some(expected), Some(actual)) = (expected_minutes, actual_minutes) {
+        let missing = expected as f64 - actual;
+        if missing > SHORT_MINUTES && missing > expected as f64 * SHORT_RATIO {
+            issues.push(format!("时长 {:.0} 分钟，短于资料的 {} 分钟，可能不完整", actual, expected));
+        }
+    }
+    Report { code, files, expected_minutes, actual_minutes, issues }
+}
+
+/// The videos under `target` (or `target` itself) grouped by code
+pub(crate) fn targets(target: &Path) -> Result<Vec<(String, Vec<PathBuf>)>> {
+    if target.is_dir() {
+        let result = scan::scan(target)?;
+        for p in &result.unmatched {
+            eprintln!("[WARN] 无法识别番号，跳过: {}", p.display());
+        }
+        return Ok(result.groups.into_iter().map(|g| (g.code, g.files.into_iter().map(|f| f.path).collect())).collect());
+    }
+    if !target.exists() {
+        bail!("文件不存在: {}", target.display());
+    }
+    let stem = target.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let Some(c) = code::find(stem) else {
+        bail!("无法从文件名识别番号: {}", target.display());
+    };
+    Ok(vec![(c.to_string(), vec![target.to_path_buf()])])
+}
+
+pub async fn run(target: &Path, json: bool) -> Result<()> {
+    if which("ffprobe").is_err() {
+        bail!("未检测到 ffprobe（随 ffmpeg 安装），请先安装: brew install ffmpeg");
+    }
+    let groups = targets(target)?;
+    if groups.is_empty() {
+        bail!("{} 下没有找到视频文件", target.display());
+    }
+
+    let sem = Arc::new(Semaphore::new(util::jobs()));
+    let mut set = JoinSet::new();
+    for (idx, (code, paths)) in groups.into_iter().enumerate() {
+        let sem = sem.clone();
+        set.spawn(async move {
+            let _permit = sem.acquire_owned().await;
+            (idx, verify(code, paths).await)
+        });
+    }
+    let mut reports: Vec<(usize, Report)> = set.join_all().await;
+    reports.sort_by_key(|(idx, _)| *idx);
+    let reports: Vec<Report> = reports.into_iter().map(|(_, r)| r).collect();
+    let flagged = reports.iter().filter(|r| !r.issues.is_empty()).count();
+
+    if json {
+        util::print_output(&reports, true);
+    } else {
+        for r in &reports {
+            let mark = if r.issues.is_empty() { "✓".green() } else { "✗".red() };
+            let runtime = match (r.actual_minutes, r.expected_minutes) {
+                (Some(a), Some(e)) => format!("{:.0}/{} 分钟", a, e),
+                (Some(a), None) => format!("{:.0} 分钟", a),
+                _ => "时长未知".to_string(),
+            };
+            let res: Vec<&str> = r.files.iter().filter_map(|f| f.resolution).collect();
+            println!("{} {} {} {}", mark, r.code.bold(), runtime, res.join("+").dimmed());
+            for issue in &r.issues {
+                println!("    {}", issue.yellow());
+            }
+        }
+    }
+    if flagged > 0 {
+        bail!("{} 个番号的文件有问题", flagged);
+    }
+    Ok(())
+}
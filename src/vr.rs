@@ -0,0 +1,74 @@
+//! VR releases: `--vr` keeps only them, `--no-vr` drops them. VR files run to tens of
+//! gigabytes and need a headset, so people tend to want them exclusively or not at all.
+//!
+//! A listing only has codes and titles, so `search`/`list`/`top` go by the studio prefix
+//! (`SIVR-`, `DSVR-`, `VRKM-`, ...) and the `【VR】` tag studios put in titles. Details
+//! also have the VR genre, and a magnet's own name can say VR (`install`, `detail`).
+
+use regex::Regex;
+use std::sync::{LazyLock, Mutex};
+
+use crate::code::{self, Code};
+use crate::genre;
+use crate::types::{AvDetail, AvItem, MagnetInfo};
+use crate::util;
+
+/// Labels with "VR" in the prefix that aren't VR (V&R Produce's `VRTM`)
+const NOT_VR: &[&str] = &["VRTM"];
+
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)【VR】|\[VR\]|\bVR\b|VR専用|VR专用|VR專用|8KVR").unwrap());
+
+/// `--vr` → `Some(true)`, `--no-vr` → `Some(false)`
+static FILTER: Mutex<Option<bool>> = Mutex::new(None);
+
+pub fn set_filter(filter: Option<bool>) {
+    *FILTER.lock().unwrap() = filter;
+}
+
+fn filter() -> Option<bool> {
+    *FILTER.lock().unwrap()
+}
+
+/// Prefixes containing "VR": `SIVR`, `DSVR`, `VRKM`, `3DSVR`
+fn vr_prefix(c: &str) -> bool {
+    match code::parse(c) {
+        Some(Code::Standard { prefix, .. }) => prefix.contains("VR") && !NOT_VR.contains(&prefix.as_str()),
+        _ => false,
+    }
+}
+
+/// By code and title alone, for listings
+pub fn looks_vr(item: &AvItem) -> bool {
+    vr_prefix(&item.code) || TAG.is_match(&item.title)
+}
+
+pub fn is_vr(d: &AvDetail) -> bool {
+    vr_prefix(&d.code) || TAG.is_match(&d.title) || genre::has_genre(d, "vr")
+}
+
+fn magnet_is_vr(m: &MagnetInfo) -> bool {
+    TAG.is_match(m.name.as_deref().unwrap_or("")) || TAG.is_match(m.display_name.as_deref().unwrap_or(""))
+}
+
+/// `--vr` / `--no-vr` on a listing
+pub fn filter_items(items: &mut Vec<AvItem>) {
+    if let Some(want) = filter() {
+        items.retain(|i| looks_vr(i) == want);
+    }
+}
+
+/// `--vr` / `--no-vr` on a detail's magnets: all of a VR release's magnets count as VR,
+/// otherwise those whose name says so
+pub fn filter_magnets(d: &mut AvDetail) {
+    let Some(want) = filter() else { return };
+    let whole = is_vr(d);
+    let (kept, dropped): (Vec<MagnetInfo>, Vec<MagnetInfo>) =
+        std::mem::take(&mut d.magnet_infos).into_iter().partition(|m| (whole || magnet_is_vr(m)) == want);
+    let kept_urls: Vec<&str> = kept.iter().map(|m| m.url.as_str()).collect();
+    // Bare links have nothing to go by but the release itself
+    d.magnets.retain(|u| kept_urls.contains(&u.as_str()) || (whole == want && !dropped.iter().any(|m| &m.url == u)));
+    d.magnet_infos = kept;
+    if !dropped.is_empty() {
+        util::debug(format!("{}: {} magnet(s) dropped by the VR filter", d.code, dropped.len()));
+    }
+}
@@ -0,0 +1,226 @@
+// The `av serve --web` frontend: hash-routed views over the JSON API.
+//   #/                 latest releases (/top)
+//   #/search/<query>   /search, or the detail page when the query is a code
+//   #/actor/<name>     /list/<actor>
+//   #/detail/<code>    /detail/<code>, with buttons that POST /download
+"use strict";
+
+const view = document.getElementById("view");
+const apikey = new URLSearchParams(location.search).get("apikey");
+const CODE = /^[A-Za-z]{2,10}-?\d{2,6}$/;
+
+function esc(value) {
+  return String(value ?? "").replace(/[&<>"']/g, (c) => `&#${c.charCodeAt(0)};`);
+}
+
+function url(path, params = {}) {
+  const u = new URL(path, location.origin);
+  for (const [k, v] of Object.entries(params)) {
+    if (v !== undefined && v !== null) u.searchParams.set(k, v);
+  }
+  if (apikey) u.searchParams.set("apikey", apikey);
+  return u;
+}
+
+async function api(path, params, init) {
+  const resp = await fetch(url(path, params), init);
+  const body = await resp.json().catch(() => ({}));
+  if (!resp.ok) throw new Error(body.error || `HTTP ${resp.status}`);
+  return body;
+}
+
+let toastTimer;
+function toast(text, isError) {
+  const el = document.getElementById("toast");
+  el.textContent = text;
+  el.className = isError ? "error" : "";
+  el.hidden = false;
+  clearTimeout(toastTimer);
+  toastTimer = setTimeout(() => (el.hidden = true), 5000);
+}
+
+function loading(text) {
+  view.innerHTML = `<p class="muted">${esc(text)}…</p>`;
+}
+
+function failed(e) {
+  view.innerHTML = `<p class="error">加载失败: ${esc(e.message)}</p>`;
+}
+
+function card(item) {
+  const badges = [
+    item.release_date && `<span class="badge">${esc(item.release_date)}</span>`,
+    item.rating != null && `<span class="badge">★ ${esc(item.rating.toFixed(1))}</span>`,
+    item.has_magnets && `<span class="badge">磁力</span>`,
+    item.has_subtitles && `<span class="badge">字幕</span>`,
+  ].filter(Boolean);
+  const thumb = item.thumbnail_url
+    ? `<img class="thumb" loading="lazy" referrerpolicy="no-referrer" src="${esc(item.thumbnail_url)}" alt="">`
+    : `<div class="thumb"></div>`;
+  return `<a class="card" href="#/detail/${encodeURIComponent(item.code)}">
+    ${thumb}
+    <div class="body">
+      <span class="code">${esc(item.code)}</span>
+      <span class="title">${esc(item.title)}</span>
+      <span>${badges.join("")}</span>
+    </div>
+  </a>`;
+}
+
+function grid(heading, items) {
+  view.innerHTML = items.length
+    ? `<h1>${esc(heading)}</h1><div class="grid">${items.map(card).join("")}</div>`
+    : `<h1>${esc(heading)}</h1><p class="muted">没有结果</p>`;
+}
+
+async function showTop() {
+  loading("加载最新发布");
+  try {
+    grid("最新发布", await api("/top", { limit: 40 }));
+  } catch (e) {
+    failed(e);
+  }
+}
+
+async function showSearch(query) {
+  if (CODE.test(query)) {
+    location.replace(`#/detail/${encodeURIComponent(query.toUpperCase())}`);
+    return;
+  }
+  loading(`搜索 ${query}`);
+  try {
+    grid(`搜索: ${query}`, await api("/search", { q: query }));
+  } catch (e) {
+    failed(e);
+  }
+}
+
+async function showActor(name) {
+  loading(`加载 ${name} 的作品`);
+  try {
+    grid(`演员: ${name}`, await api(`/list/${encodeURIComponent(name)}`));
+  } catch (e) {
+    failed(e);
+  }
+}
+
+function row(label, value) {
+  return value ? `<dt>${esc(label)}</dt><dd>${value}</dd>` : "";
+}
+
+function magnetRows(d) {
+  const infos = d.magnet_infos?.length ? d.magnet_infos : (d.magnets || []).map((url) => ({ url }));
+  return infos
+    .map((m) => `<tr>
+      <td class="name">${esc(m.display_name || m.name || m.url)}</td>
+      <td>${esc(m.size || "")}</td>
+      <td>${esc(m.resolution || "")}</td>
+      <td>${m.seeders != null ? esc(m.seeders) : ""}</td>
+      <td>${esc(m.date || "")}</td>
+      <td class="buttons">
+        <button data-send="${esc(m.url)}">发送到下载器</button>
+        <button data-copy="${esc(m.url)}">复制</button>
+      </td>
+    </tr>`)
+    .join("");
+}
+
+async function showDetail(code) {
+  loading(`加载 ${code}`);
+  let d;
+  try {
+    d = await api(`/detail/${encodeURIComponent(code)}`);
+  } catch (e) {
+    failed(e);
+    return;
+  }
+  const actors = (d.actor_names || [])
+    .map((a) => `<a href="#/actor/${encodeURIComponent(a)}">${esc(a)}</a>`)
+    .join("、");
+  const genres = (d.genres || []).map((g) => `<span class="badge">${esc(g)}</span>`).join("");
+  const previews = (d.preview_images || [])
+    .map((p) => `<a href="${esc(p)}" target="_blank" rel="noreferrer"><img loading="lazy" referrerpolicy="no-referrer" src="${esc(p)}" alt=""></a>`)
+    .join("");
+  const magnets = magnetRows(d);
+  view.innerHTML = `<h1>${esc(d.title)}</h1>
+    <div class="detail">
+      <div>
+        ${d.cover_url ? `<img class="cover" referrerpolicy="no-referrer" src="${esc(d.cover_url)}" alt="">` : ""}
+        ${previews ? `<div class="previews">${previews}</div>` : ""}
+      </div>
+      <div>
+        <dl>
+          ${row("番号", esc(d.code))}
+          ${row("发行日期", esc(d.release_date))}
+          ${row("时长", d.duration_minutes && `${esc(d.duration_minutes)} 分钟`)}
+          ${row("导演", esc(d.director))}
+          ${row("片商", esc(d.studio))}
+          ${row("厂牌", esc(d.label))}
+          ${row("系列", esc(d.series))}
+          ${row("评分", d.rating != null && `★ ${esc(d.rating.toFixed(2))}`)}
+          ${row("演员", actors)}
+          ${row("类别", genres)}
+        </dl>
+        <div class="actions">
+          <button class="primary" data-send="" ${magnets ? "" : "disabled"}>下载最佳磁力</button>
+          ${d.trailer_url || d.sample_video_url ? `<a href="${esc(d.trailer_url || d.sample_video_url)}" target="_blank" rel="noreferrer"><button>预告片</button></a>` : ""}
+        </div>
+        ${d.plot ? `<p class="plot">${esc(d.plot)}</p>` : ""}
+      </div>
+    </div>
+    <h1>磁力链接</h1>
+    ${magnets
+      ? `<table><thead><tr><th>名称</th><th>大小</th><th>分辨率</th><th>做种</th><th>日期</th><th></th></tr></thead><tbody>${magnets}</tbody></table>`
+      : `<p class="muted">没有可用的磁力链接</p>`}`;
+  view.dataset.code = d.code;
+}
+
+async function send(button, magnet) {
+  button.disabled = true;
+  try {
+    const body = { code: view.dataset.code };
+    if (magnet) body.magnet = magnet;
+    const sent = await api("/download", {}, {
+      method: "POST",
+      headers: { "content-type": "application/json" },
+      body: JSON.stringify(body),
+    });
+    toast(sent.status === "waiting" ? `${sent.client} 正在下载 ${sent.code}` : `已添加到 ${sent.client}: ${sent.code}`);
+  } catch (e) {
+    toast(`发送失败: ${e.message}`, true);
+  } finally {
+    button.disabled = false;
+  }
+}
+
+view.addEventListener("click", (e) => {
+  const button = e.target.closest("button");
+  if (!button) return;
+  if (button.dataset.send !== undefined) {
+    send(button, button.dataset.send);
+  } else if (button.dataset.copy) {
+    navigator.clipboard.writeText(button.dataset.copy).then(
+      () => toast("已复制磁力链接"),
+      () => toast("复制失败", true),
+    );
+  }
+});
+
+document.getElementById("search").addEventListener("submit", (e) => {
+  e.preventDefault();
+  const q = document.getElementById("q").value.trim();
+  if (q) location.hash = `#/search/${encodeURIComponent(q)}`;
+});
+
+function route() {
+  const [, page, ...rest] = location.hash.split("/");
+  const arg = decodeURIComponent(rest.join("/"));
+  window.scrollTo(0, 0);
+  if (page === "search" && arg) return showSearch(arg);
+  if (page === "actor" && arg) return showActor(arg);
+  if (page === "detail" && arg) return showDetail(arg);
+  return showTop();
+}
+
+window.addEventListener("hashchange", route);
+route();
@@ -0,0 +1,138 @@
+//! Local speech recognition with whisper.cpp for `av sub --generate`: the video's audio is
+//! extracted with ffmpeg as the 16 kHz mono WAV whisper.cpp reads, and transcribed as
+//! Japanese into an `.srt`.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use which::which;
+
+use crate::code;
+use crate::config;
+use crate::scan;
+use crate::store;
+use crate::util;
+
+/// whisper.cpp's executable names, newest first
+const BINARIES: [&str; 2] = ["whisper-cli", "whisper-cpp"];
+
+const MODELS_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+fn binary() -> Result<PathBuf> {
+    if let Some(p) = &config::get().subtitles.whisper {
+        return which(p).with_context(|| format!("找不到 [subtitles] whisper 指定的程序: {}", p.display()));
+    }
+    BINARIES
+        .iter()
+        .find_map(|b| which(b).ok())
+        .context("未检测到 whisper.cpp（whisper-cli），请先安装: brew install whisper-cpp，或在 [subtitles] whisper 中指定路径")
+}
+
+fn models_dir() -> PathBuf {
+    config::get().subtitles.models_dir.clone().unwrap_or_else(|| store::data_dir().join("whisper"))
+}
+
+/// `small` → `<models dir>/ggml-small.bin`; a path to a model file is taken as it is
+pub fn model_path(model: &str) -> Result<PathBuf> {
+    let given = Path::new(model);
+    if given.is_file() {
+        return Ok(given.to_path_buf());
+    }
+    let path = models_dir().join(format!("ggml-{}.bin", model));
+    if !path.is_file() {
+        bail!("没有找到 whisper 模型 {}，请下载 {}/ggml-{}.bin 放到 {}", path.display(), MODELS_URL, model, models_dir().display());
+    }
+    Ok(path)
+}
+
+/// `code`'s video in `<library_dir>/<CODE>/`, the library, the download directory or the
+/// current directory, the first part of a split release
+pub fn find_video(code: &str) -> Result<PathBuf> {
+    let pipeline = &config::get().pipeline;
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(l) = &pipeline.library_dir {
+        dirs.push(l.join(code));
+        dirs.push(l.clone());
+    }
+    if let Some(d) = &pipeline.download_dir {
+        dirs.push(d.join(code));
+        dirs.push(d.clone());
+    }
+    dirs.push(PathBuf::from("."));
+    for dir in dirs.iter().filter(|d| d.is_dir()) {
+        let found = match scan::scan(dir) {
+            Ok(found) => found,
+            Err(e) => {
+                util::debug(format!("whisper: scan {}: {:#}", dir.display(), e));
+                continue;
+            }
+        };
+        if let Some(group) = found.groups.into_iter().find(|g| code::same_release(&g.code, code)) {
+            if group.files.len() > 1 {
+                eprintln!("[WARN] {} 有 {} 个分段，只处理第一段；其余可用 --file 指定", code, group.files.len());
+            }
+            if let Some(first) = group.files.into_iter().next() {
+                return Ok(first.path);
+            }
+        }
+    }
+    bail!("本地没有找到 {} 的视频（已查找媒体库、下载目录与当前目录），请用 --file 指定", code)
+}
+
+async fn run(cmd: &mut tokio::process::Command, what: &str) -> Result<()> {
+    let status = cmd.stdin(Stdio::null()).status().await.with_context(|| format!("启动 {} 失败", what))?;
+    if !status.success() {
+        bail!("{} 失败，退出码: {:?}", what, status.code());
+    }
+    Ok(())
+}
+
+/// Transcribe `video`'s Japanese speech with `model` into the `.srt` file `out`
+pub async fn transcribe(video: &Path, model: &Path, out: &Path) -> Result<()> {
+    if which("ffmpeg").is_err() {
+        bail!("未检测到 ffmpeg，请先安装: brew install ffmpeg");
+    }
+    let whisper = binary()?;
+    let tmp = std::env::temp_dir().join(format!("av-whisper-{}", std::process::id()));
+    std::fs::create_dir_all(&tmp).with_context(|| format!("创建目录失败: {}", tmp.display()))?;
+    let result = async {
+        let wav = tmp.join("audio.wav");
+        util::note(format!("提取音轨: {}", video.display()));
+        run(
+            tokio::process::Command::new("ffmpeg")
+                .args(["-v", "error", "-y", "-i"])
+                .arg(video)
+                .args(["-vn", "-ac", "1", "-ar", "16000", "-c:a", "pcm_s16le"])
+                .arg(&wav),
+            "ffmpeg",
+        )
+        .await?;
+        util::note(format!("whisper.cpp 识别中（{}），长片可能需要较长时间", model.display()));
+        // -of takes the output name without the extension -osrt adds
+        let stem = tmp.join("out");
+        run(
+            tokio::process::Command::new(&whisper)
+                .arg("-m")
+                .arg(model)
+                .arg("-f")
+                .arg(&wav)
+                .args(["-l", "ja", "-osrt", "-of"])
+                .arg(&stem),
+            "whisper.cpp",
+        )
+        .await?;
+        let srt = stem.with_extension("srt");
+        if !srt.is_file() {
+            bail!("whisper.cpp 没有生成字幕文件");
+        }
+        if let Some(dir) = out.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+        }
+        // Copied: the temp directory may be on another filesystem
+        std::fs::copy(&srt, out).with_context(|| format!("写入失败: {}", out.display()))?;
+        Ok(())
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&tmp);
+    result
+}
@@ -0,0 +1,24 @@
+//! `av who`: who is in a title, for amateur-label codes whose listings name nobody. AV-Wiki
+//! is asked directly rather than through the detail chain, so its answer shows even when
+//! JavDB does list someone.
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+
+use crate::code;
+use crate::sources::avwiki;
+use crate::util;
+
+/// `av who <code>`
+pub async fn run(code: &str, json: bool) -> Result<()> {
+    let Some(found) = avwiki::lookup(code).await? else {
+        bail!("AV-Wiki 没有 {} 的出演者信息: {}", code::normalize(code), avwiki::search_url(&code::normalize(code)));
+    };
+    if json {
+        util::print_output(&found, true);
+        return Ok(());
+    }
+    println!("{} {}", found.code.bold(), found.actresses.join("、").green().bold());
+    println!("{}", found.url.dimmed());
+    Ok(())
+}
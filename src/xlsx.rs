@@ -0,0 +1,363 @@
+//! `--xlsx`: spreadsheets for collections kept in Excel (or LibreOffice, Numbers). A
+//! small writer of the Office Open XML parts a workbook needs — a bold frozen header row
+//! with filters, columns sized to their contents, numbers as numbers and external
+//! hyperlinks — zipped here rather than pulling in a spreadsheet crate.
+//!
+//! The export itself: one row per title, each with its detail (fetched `--jobs` at a time,
+//! through the cache) for the cast, studio, genres, cover and best magnet; `list --stats`
+//! adds its tables as sheets of their own.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use crate::scraper;
+use crate::types::{AvDetail, AvItem};
+use crate::util;
+
+/// Column widths are their longest cell, within these (in characters)
+const MIN_WIDTH: usize = 6;
+const MAX_WIDTH: usize = 60;
+/// Excel won't open a link longer than this; such a cell keeps only its text
+const MAX_URL_LEN: usize = 2079;
+const MAX_SHEET_NAME: usize = 31;
+
+/// Cell style indexes into `STYLES`' `cellXfs`
+const STYLE_HEADER: u32 = 1;
+const STYLE_LINK: u32 = 2;
+const STYLE_DECIMAL: u32 = 3;
+
+pub enum Cell {
+    Empty,
+    Text(String),
+    Int(i64),
+    /// Shown with two decimals
+    Decimal(f64),
+    Link { text: String, url: String },
+}
+
+impl Cell {
+    pub fn text(s: impl Into<String>) -> Cell {
+        let s = s.into();
+        if s.is_empty() { Cell::Empty } else { Cell::Text(s) }
+    }
+
+    pub fn link(text: impl Into<String>, url: Option<&str>) -> Cell {
+        match url.filter(|u| !u.is_empty()) {
+            Some(url) => Cell::Link { text: text.into(), url: url.to_string() },
+            None => Cell::Empty,
+        }
+    }
+
+    /// Ratings are `f32`s; widened as written (4.3, not 4.300000190734863)
+    pub fn decimal(n: f32) -> Cell {
+        Cell::Decimal(n.to_string().parse().unwrap_or(n.into()))
+    }
+
+    /// Characters of its longest line, CJK counting two as in the terminal tables
+    fn width(&self) -> usize {
+        let shown = match self {
+            Cell::Empty => return 0,
+            Cell::Text(s) | Cell::Link { text: s, .. } => s.lines().map(util::display_width).max().unwrap_or(0),
+            Cell::Int(n) => n.to_string().len(),
+            Cell::Decimal(n) => format!("{:.2}", n).len(),
+        };
+        shown + 2
+    }
+}
+
+pub struct Sheet {
+    name: String,
+    headers: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Sheet {
+    pub fn new(name: &str, headers: &[&str]) -> Sheet {
+        // Excel refuses these in sheet names
+        let name: String = name.chars().map(|c| if "[]:*?/\\".contains(c) { '_' } else { c }).take(MAX_SHEET_NAME).collect();
+        Sheet { name, headers: headers.iter().map(|h| h.to_string()).collect(), rows: Vec::new() }
+    }
+
+    pub fn push(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+}
+
+#[derive(Default)]
+pub struct Workbook {
+    sheets: Vec<Sheet>,
+}
+
+impl Workbook {
+    pub fn add(&mut self, sheet: Sheet) {
+        self.sheets.push(sheet);
+    }
+
+    /// The `.xlsx` file's bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut zip = Zip::default();
+        let overrides: String = (1..=self.sheets.len())
+            .map(|i| format!(r#"<Override PartName="/xl/worksheets/sheet{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#, i))
+            .collect();
+        zip.add(
+            "[Content_Types].xml",
+            &format!(
+                r#"{}<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>{}</Types>"#,
+                XML_HEAD, overrides
+            ),
+        )?;
+        zip.add(
+            "_rels/.rels",
+            &format!(r#"{}<Relationships xmlns="{}"><Relationship Id="rId1" Type="{}/officeDocument" Target="xl/workbook.xml"/></Relationships>"#, XML_HEAD, RELS_NS, REL_TYPE),
+        )?;
+        let sheets: String = self
+            .sheets
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!(r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#, util::xml_escape(&s.name), i + 1, i + 1))
+            .collect();
+        zip.add(
+            "xl/workbook.xml",
+            &format!(r#"{}<workbook xmlns="{}" xmlns:r="{}"><sheets>{}</sheets></workbook>"#, XML_HEAD, MAIN_NS, R_NS, sheets),
+        )?;
+        let mut rels: String = (1..=self.sheets.len())
+            .map(|i| format!(r#"<Relationship Id="rId{}" Type="{}/worksheet" Target="worksheets/sheet{}.xml"/>"#, i, REL_TYPE, i))
+            .collect();
+        rels.push_str(&format!(r#"<Relationship Id="rId{}" Type="{}/styles" Target="styles.xml"/>"#, self.sheets.len() + 1, REL_TYPE));
+        zip.add("xl/_rels/workbook.xml.rels", &format!(r#"{}<Relationships xmlns="{}">{}</Relationships>"#, XML_HEAD, RELS_NS, rels))?;
+        zip.add("xl/styles.xml", STYLES)?;
+        for (i, sheet) in self.sheets.iter().enumerate() {
+            let (xml, links) = sheet_xml(sheet);
+            zip.add(&format!("xl/worksheets/sheet{}.xml", i + 1), &xml)?;
+            if !links.is_empty() {
+                let rels: String = links
+                    .iter()
+                    .enumerate()
+                    .map(|(n, url)| format!(r#"<Relationship Id="rId{}" Type="{}/hyperlink" Target="{}" TargetMode="External"/>"#, n + 1, REL_TYPE, util::xml_escape(url)))
+                    .collect();
+                zip.add(&format!("xl/worksheets/_rels/sheet{}.xml.rels", i + 1), &format!(r#"{}<Relationships xmlns="{}">{}</Relationships>"#, XML_HEAD, RELS_NS, rels))?;
+            }
+        }
+        zip.finish()
+    }
+
+    /// Whether it was written (`--dry-run` only says it would be)
+    pub fn save(&self, path: &Path) -> Result<bool> {
+        let bytes = self.to_bytes()?;
+        if util::dry_run_skip(format!("将写入 {}（{} 字节）", path.display(), bytes.len())) {
+            return Ok(false);
+        }
+        std::fs::write(path, bytes).with_context(|| format!("写入表格失败: {}", path.display()))?;
+        Ok(true)
+    }
+}
+
+const XML_HEAD: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#;
+const MAIN_NS: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const R_NS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const REL_TYPE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const RELS_NS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+
+/// Fonts: default, bold, hyperlink blue. Cell formats in the `STYLE_*` order.
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><fonts count="3"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font><font><u/><sz val="11"/><color rgb="FF0563C1"/><name val="Calibri"/></font></fonts><fills count="3"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill><fill><patternFill patternType="solid"><fgColor rgb="FFE7E6E6"/><bgColor indexed="64"/></patternFill></fill></fills><borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders><cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs><cellXfs count="4"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/><xf numFmtId="0" fontId="1" fillId="2" borderId="0" xfId="0" applyFont="1" applyFill="1"/><xf numFmtId="0" fontId="2" fillId="0" borderId="0" xfId="0" applyFont="1"/><xf numFmtId="2" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/></cellXfs><cellStyles count="1"><cellStyle name="Normal" xfId="0" builtinId="0"/></cellStyles></styleSheet>"#;
+
+/// `0` → `A`, `27` → `AB`
+fn column_name(mut idx: usize) -> String {
+    let mut out = Vec::new();
+    loop {
+        out.push((b'A' + (idx % 26) as u8) as char);
+        if idx < 26 {
+            break;
+        }
+        idx = idx / 26 - 1;
+    }
+    out.iter().rev().collect()
+}
+
+fn text_cell(r: &str, text: &str, style: u32) -> String {
+    let s = if style == 0 { String::new() } else { format!(r#" s="{}""#, style) };
+    format!(r#"<c r="{}" t="inlineStr"{}><is><t xml:space="preserve">{}</t></is></c>"#, r, s, util::xml_escape(text))
+}
+
+/// The worksheet part, and the URLs its hyperlinks' `rId`s (1-based) point at
+fn sheet_xml(sheet: &Sheet) -> (String, Vec<String>) {
+    let columns = sheet.headers.len().max(sheet.rows.iter().map(Vec::len).max().unwrap_or(0));
+    let widths: Vec<usize> = (0..columns)
+        .map(|c| {
+            let header = sheet.headers.get(c).map(|h| util::display_width(h) + 4).unwrap_or(0);
+            let widest = sheet.rows.iter().filter_map(|r| r.get(c)).map(Cell::width).max().unwrap_or(0);
+            header.max(widest).clamp(MIN_WIDTH, MAX_WIDTH)
+        })
+        .collect();
+
+    let mut data = String::new();
+    let mut links = Vec::new();
+    let mut link_refs = String::new();
+    data.push_str(r#"<row r="1">"#);
+    for (c, h) in sheet.headers.iter().enumerate() {
+        data.push_str(&text_cell(&format!("{}1", column_name(c)), h, STYLE_HEADER));
+    }
+    data.push_str("</row>");
+    for (i, row) in sheet.rows.iter().enumerate() {
+        let r = i + 2;
+        data.push_str(&format!(r#"<row r="{}">"#, r));
+        for (c, cell) in row.iter().enumerate() {
+            let at = format!("{}{}", column_name(c), r);
+            match cell {
+                Cell::Empty => {}
+                Cell::Text(s) => data.push_str(&text_cell(&at, s, 0)),
+                Cell::Int(n) => data.push_str(&format!(r#"<c r="{}"><v>{}</v></c>"#, at, n)),
+                Cell::Decimal(n) if n.is_finite() => data.push_str(&format!(r#"<c r="{}" s="{}"><v>{}</v></c>"#, at, STYLE_DECIMAL, n)),
+                Cell::Decimal(_) => {}
+                Cell::Link { text, url } if url.len() <= MAX_URL_LEN => {
+                    links.push(url.clone());
+                    link_refs.push_str(&format!(r#"<hyperlink ref="{}" r:id="rId{}"/>"#, at, links.len()));
+                    data.push_str(&text_cell(&at, text, STYLE_LINK));
+                }
+                Cell::Link { text, .. } => data.push_str(&text_cell(&at, text, 0)),
+            }
+        }
+        data.push_str("</row>");
+    }
+
+    let last = column_name(columns.saturating_sub(1));
+    let cols: String = widths.iter().enumerate().map(|(c, w)| format!(r#"<col min="{}" max="{}" width="{}" customWidth="1"/>"#, c + 1, c + 1, w)).collect();
+    let mut xml = format!(
+        r#"{}<worksheet xmlns="{}" xmlns:r="{}"><dimension ref="A1:{}{}"/><sheetViews><sheetView workbookViewId="0"><pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/></sheetView></sheetViews><sheetFormatPr defaultRowHeight="15"/>"#,
+        XML_HEAD,
+        MAIN_NS,
+        R_NS,
+        last,
+        sheet.rows.len() + 1
+    );
+    if !cols.is_empty() {
+        xml.push_str(&format!("<cols>{}</cols>", cols));
+    }
+    xml.push_str(&format!("<sheetData>{}</sheetData>", data));
+    if columns > 0 {
+        xml.push_str(&format!(r#"<autoFilter ref="A1:{}{}"/>"#, last, sheet.rows.len() + 1));
+    }
+    if !link_refs.is_empty() {
+        xml.push_str(&format!("<hyperlinks>{}</hyperlinks>", link_refs));
+    }
+    xml.push_str("</worksheet>");
+    (xml, links)
+}
+
+/// A zip archive of deflated entries, as OOXML packages are
+#[derive(Default)]
+struct Zip {
+    out: Vec<u8>,
+    directory: Vec<u8>,
+    entries: u16,
+}
+
+impl Zip {
+    fn add(&mut self, name: &str, content: &str) -> Result<()> {
+        let raw = content.as_bytes();
+        let mut enc = DeflateEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(raw)?;
+        let packed = enc.finish()?;
+        let crc = crc32fast::hash(raw);
+        let offset = self.out.len() as u32;
+        // Version 2.0, UTF-8 names, deflate, 1980-01-01 00:00
+        let fields = |buf: &mut Vec<u8>| {
+            for v in [20u16, 1 << 11, 8, 0, 0x21] {
+                buf.extend(v.to_le_bytes());
+            }
+            for v in [crc, packed.len() as u32, raw.len() as u32] {
+                buf.extend(v.to_le_bytes());
+            }
+            buf.extend((name.len() as u16).to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+        };
+        self.out.extend(0x04034b50u32.to_le_bytes());
+        fields(&mut self.out);
+        self.out.extend(name.as_bytes());
+        self.out.extend(&packed);
+
+        self.directory.extend(0x02014b50u32.to_le_bytes());
+        self.directory.extend(20u16.to_le_bytes());
+        fields(&mut self.directory);
+        // Comment length, disk, internal and external attributes, then where it starts
+        for v in [0u16, 0, 0] {
+            self.directory.extend(v.to_le_bytes());
+        }
+        self.directory.extend(0u32.to_le_bytes());
+        self.directory.extend(offset.to_le_bytes());
+        self.directory.extend(name.as_bytes());
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        let start = self.out.len() as u32;
+        let size = self.directory.len() as u32;
+        self.out.append(&mut self.directory);
+        self.out.extend(0x06054b50u32.to_le_bytes());
+        for v in [0u16, 0, self.entries, self.entries] {
+            self.out.extend(v.to_le_bytes());
+        }
+        self.out.extend(size.to_le_bytes());
+        self.out.extend(start.to_le_bytes());
+        self.out.extend(0u16.to_le_bytes());
+        Ok(self.out)
+    }
+}
+
+/// The title columns shared by every export: the item, filled in from its detail when
+/// there is one
+pub const TITLE_HEADERS: [&str; 13] = ["番号", "标题", "演员", "发行日期", "时长（分钟）", "片商", "系列", "类别", "评分", "字幕", "封面", "磁力", "磁力数"];
+
+pub fn title_row(item: &AvItem, detail: Option<&AvDetail>) -> Vec<Cell> {
+    let d = detail;
+    let magnets = d.map(util::ranked_magnets).unwrap_or_default();
+    let best = magnets.first().map(String::as_str);
+    // The magnet's own name and size read better than the link
+    let best_label = best
+        .and_then(|url| d?.magnet_infos.iter().find(|m| m.url == url))
+        .map(|m| [m.display_name.as_deref().or(m.name.as_deref()), m.size.as_deref()].into_iter().flatten().collect::<Vec<_>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| "磁力链接".to_string());
+    vec![
+        Cell::text(&item.code),
+        Cell::text(d.map(|d| d.title.as_str()).unwrap_or(&item.title)),
+        Cell::text(d.map(|d| d.actor_names.join("、")).unwrap_or_default()),
+        Cell::text(d.and_then(|d| d.release_date.clone()).or_else(|| item.release_date.clone()).unwrap_or_default()),
+        d.and_then(|d| d.duration_minutes).map(|m| Cell::Int(m.into())).unwrap_or(Cell::Empty),
+        Cell::text(d.and_then(|d| d.studio.clone()).unwrap_or_default()),
+        Cell::text(d.and_then(|d| d.series.clone()).unwrap_or_default()),
+        Cell::text(d.map(|d| d.genres.join("、")).unwrap_or_default()),
+        d.and_then(|d| d.rating).or(item.rating).map(Cell::decimal).unwrap_or(Cell::Empty),
+        Cell::text(if item.has_subtitles == Some(true) { "有" } else { "" }),
+        Cell::link("封面", d.and_then(|d| d.cover_url.as_deref()).or(item.thumbnail_url.as_deref())),
+        Cell::link(best_label, best),
+        if d.is_some() { Cell::Int(magnets.len() as i64) } else { Cell::Empty },
+    ]
+}
+
+/// The titles' sheet, one row per item, filled in from `details` (by code) where there's one
+pub fn titles_sheet(name: &str, items: &[AvItem], details: &HashMap<String, &AvDetail>) -> Sheet {
+    let mut sheet = Sheet::new(name, &TITLE_HEADERS);
+    for item in items {
+        sheet.push(title_row(item, details.get(&item.code).copied()));
+    }
+    sheet
+}
+
+/// `list`/`search --xlsx`: the titles, details fetched for every item
+pub async fn export_items(items: &[AvItem], sheet_name: &str, path: &Path) -> Result<()> {
+    let details = scraper::fetch_details(items.iter().map(|i| i.code.clone()).collect()).await;
+    let by_code: HashMap<String, &AvDetail> = details.iter().map(|d| (d.code.clone(), d)).collect();
+    let mut book = Workbook::default();
+    book.add(titles_sheet(sheet_name, items, &by_code));
+    if book.save(path)? {
+        eprintln!("{} {}（{} 部，{} 部有详情）", "已导出表格:".green().bold(), path.display(), items.len(), details.len());
+    }
+    Ok(())
+}